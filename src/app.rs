@@ -1,10 +1,21 @@
-use std::collections::HashMap;
+use std::collections::{HashMap, HashSet};
+use std::time::{Duration, Instant};
 
+use crate::config::keybindings as keymap_config;
+use crate::config::schemes as scheme_config;
 use crate::config::sessions as config;
-use crate::ssh::forward::PortForwarder;
-use crate::ssh::session::{AuthType, ProxyConfig, SessionConfig, SshConnection};
-use crate::ssh::sftp::SftpBrowser;
-use crate::terminal::widget::TerminalWidget;
+use crate::ssh::forward::{ForwardSummary, PortForwarder};
+use crate::ssh::session::{
+    AuthType, JumpHost, ProxyConfig, SessionConfig, SshConnection, UploadPermissions,
+};
+use crate::ssh::sftp::{SftpBrowser, SftpPeer};
+use crate::terminal::highlight::{self, HighlightRule};
+use crate::terminal::keybindings::{self, KeyChord, KeyMap, KeySend};
+use crate::terminal::palette::{self, ColorScheme};
+use crate::terminal::panes::{SplitDir, SplitPanes};
+use crate::terminal::replay::ReplayViewer;
+use crate::terminal::tmux::{TmuxAction, TmuxSession};
+use crate::terminal::widget::{Activity, TerminalWidget};
 
 pub struct AppState {
     sessions: Vec<SessionConfig>,
@@ -16,16 +27,79 @@ pub struct AppState {
     show_connect_dialog: bool,
     connect_dialog: ConnectDialog,
     last_error: Option<String>,
+    replay: Option<ReplayViewer>,
+    toasts: crate::notify::Toasts,
+    // Broadcast mode: ввод активного терминала уходит и в эти сессии
+    broadcast: bool,
+    broadcast_targets: HashSet<String>,
+    // Окно настройки сочетаний клавиш (черновик до [save])
+    keymap_edit: Option<KeyMap>,
+    // Окно со всеми перенаправлениями всех сессий
+    show_forwards: bool,
+    // Импортированные цветовые схемы (встроенные — ColorScheme::builtin)
+    color_schemes: Vec<ColorScheme>,
 }
 
 struct Connection {
     config: SessionConfig, // конфиг с паролем — живёт только пока есть соединение
     terminal: TerminalWidget,
     ssh: SshConnection,
+    // Дополнительные панели Shell-вкладки (свои каналы на том же соединении)
+    panes: SplitPanes,
+    // Удалённый `tmux -CC`: окна и панели tmux вместо обычного терминала
+    tmux: Option<TmuxSession>,
     sftp: Option<SftpBrowser>,
     forward: Option<PortForwarder>,
     active_tab: Tab,
+    // Бейдж в списке сессий: вывод / звонок, пока сессия не активна
+    activity: Activity,
+    monitors: Monitors,
     error: Option<String>,
+    status: Option<String>,
+}
+
+/// Мониторы сессии (как в SecureCRT/iTerm): тишина дольше N секунд —
+/// задача, видимо, закончилась; вывод после N секунд тишины — что-то произошло.
+struct Monitors {
+    silence: bool,
+    activity: bool,
+    secs: u32,
+    last_output: Instant,
+    silence_fired: bool,
+}
+
+impl Default for Monitors {
+    fn default() -> Self {
+        Monitors {
+            silence: false,
+            activity: false,
+            secs: 30,
+            last_output: Instant::now(),
+            silence_fired: false,
+        }
+    }
+}
+
+impl Monitors {
+    /// Текст уведомления, если монитор сработал на этом кадре.
+    fn check(&mut self, output: bool, now: Instant) -> Option<String> {
+        let threshold = Duration::from_secs(self.secs.max(1) as u64);
+        let quiet = now.duration_since(self.last_output);
+        if output {
+            self.last_output = now;
+            self.silence_fired = false;
+            if self.activity && quiet >= threshold {
+                return Some(format!(
+                    "new output after {} of silence",
+                    crate::notify::format_duration(quiet)
+                ));
+            }
+        } else if self.silence && !self.silence_fired && quiet >= threshold {
+            self.silence_fired = true;
+            return Some(format!("silent for {}", crate::notify::format_duration(quiet)));
+        }
+        None
+    }
 }
 
 #[derive(PartialEq, Clone, Copy)]
@@ -54,6 +128,30 @@ struct SessionDialog {
     proxy_enabled: bool,
     proxy_host: String,
     proxy_port: String,
+    // Jump-хосты, "user@host:port, ..."
+    jump_hosts: String,
+    // Правила подсветки/триггеров
+    highlight_rules: Vec<HighlightRule>,
+    // Уведомление о долгих командах, секунды (пусто/0 — выкл.)
+    notify_after: String,
+    // Свои сочетания клавиш → байты
+    key_sends: Vec<KeySend>,
+    color_scheme: Option<ColorScheme>,
+    // Разделители слов для двойного клика
+    word_separators: String,
+    // Порог подтверждения вставки, символов (0 — только многострочная)
+    paste_confirm_chars: u32,
+    // Одновременных SFTP-передач
+    sftp_parallel: u32,
+    // Лимит скорости SFTP, KiB/s (0 — нет)
+    sftp_rate_limit_kib: u32,
+    // Повторы SFTP при обрывах и пауза перед первым
+    sftp_retries: u32,
+    sftp_retry_delay_secs: u32,
+    // Папка загрузок SFTP (пусто — системная)
+    sftp_download_dir: String,
+    // Права залитых файлов и папок
+    sftp_upload_perms: UploadPermissions,
 }
 
 impl Default for SessionDialog {
@@ -70,6 +168,19 @@ impl Default for SessionDialog {
             proxy_enabled: false,
             proxy_host: "127.0.0.1".to_string(),
             proxy_port: String::new(),
+            jump_hosts: String::new(),
+            highlight_rules: Vec::new(),
+            notify_after: crate::ssh::session::default_notify_after_secs().to_string(),
+            key_sends: Vec::new(),
+            color_scheme: None,
+            word_separators: crate::ssh::session::default_word_separators(),
+            paste_confirm_chars: crate::ssh::session::default_paste_confirm_chars(),
+            sftp_parallel: crate::ssh::session::default_sftp_parallel(),
+            sftp_rate_limit_kib: 0,
+            sftp_retries: crate::ssh::session::default_sftp_retries(),
+            sftp_retry_delay_secs: crate::ssh::session::default_sftp_retry_delay_secs(),
+            sftp_download_dir: String::new(),
+            sftp_upload_perms: UploadPermissions::default(),
         }
     }
 }
@@ -102,6 +213,7 @@ impl AppState {
     pub fn new(cc: &eframe::CreationContext) -> Self {
         crate::theme::apply(&cc.egui_ctx);
         let sessions = config::load_sessions();
+        keybindings::install(&keymap_config::load_keymap());
 
         AppState {
             sessions,
@@ -113,22 +225,49 @@ impl AppState {
             show_connect_dialog: false,
             connect_dialog: ConnectDialog::default(),
             last_error: None,
+            replay: None,
+            toasts: crate::notify::Toasts::default(),
+            broadcast: false,
+            broadcast_targets: HashSet::new(),
+            keymap_edit: None,
+            show_forwards: false,
+            color_schemes: scheme_config::load_schemes(),
         }
     }
 
     /// Подключиться к сессии (конфиг уже содержит пароль / ключ).
     fn connect_session(&mut self, config: &SessionConfig) {
         let ssh = SshConnection::new(config);
-        let terminal = TerminalWidget::new(80, 24);
+        let mut terminal = TerminalWidget::new(80, 24);
+        terminal.set_highlight_rules(&config.highlight_rules);
+        terminal.set_key_sends(&config.key_sends);
+        terminal.set_color_scheme(&config.color_scheme.clone().unwrap_or_default());
+        terminal.set_word_separators(&config.word_separators);
+        terminal.set_paste_confirm_chars(config.paste_confirm_chars);
+        // Сессия уже открыта — её правила работают на своих соединениях: переносим их
+        // в новую вкладку, а не запускаем [auto] второй раз рядом с живыми
+        let forward = match self.connections.remove(&config.id).and_then(|c| c.forward) {
+            Some(forward) => forward,
+            None => {
+                let mut forward = PortForwarder::new(config);
+                forward.start_auto();
+                forward
+            }
+        };
 
         let connection = Connection {
             config: config.clone(),
             terminal,
             ssh,
+            panes: SplitPanes::default(),
+            tmux: None,
             sftp: None,
-            forward: None,
+            forward: Some(forward),
             active_tab: Tab::Shell,
+            activity: Activity::default(),
+            monitors: Monitors::default(),
             error: None,
+            status: None,
         };
 
         self.connections.insert(config.id.clone(), connection);
@@ -138,6 +277,7 @@ impl AppState {
 
     fn disconnect_session(&mut self, session_id: &str) {
         self.connections.remove(session_id);
+        self.broadcast_targets.remove(session_id);
         if self.active_session_id.as_deref() == Some(session_id) {
             self.active_session_id = None;
         }
@@ -197,6 +337,27 @@ impl AppState {
         } else {
             None
         };
+        let jump_hosts = JumpHost::parse_list(&self.dialog.jump_hosts).unwrap_or_default();
+
+        let highlight_rules = self.dialog.highlight_rules.clone();
+        let notify_after_secs = self.dialog.notify_after.trim().parse().unwrap_or(0);
+        let key_sends: Vec<KeySend> = self
+            .dialog
+            .key_sends
+            .iter()
+            .filter(|s| !s.keys.trim().is_empty())
+            .cloned()
+            .collect();
+        let color_scheme = self.dialog.color_scheme.clone();
+        let scheme = color_scheme.clone().unwrap_or_default();
+        let word_separators = self.dialog.word_separators.clone();
+        let paste_confirm_chars = self.dialog.paste_confirm_chars;
+        let sftp_parallel = self.dialog.sftp_parallel;
+        let sftp_rate_limit_kib = self.dialog.sftp_rate_limit_kib;
+        let sftp_retries = self.dialog.sftp_retries;
+        let sftp_retry_delay_secs = self.dialog.sftp_retry_delay_secs;
+        let sftp_download_dir = self.dialog.sftp_download_dir.trim().to_string();
+        let sftp_upload_perms = self.dialog.sftp_upload_perms;
 
         if let Some(id) = &self.dialog.editing_id.clone() {
             if let Some(session) = self.sessions.iter_mut().find(|s| &s.id == id) {
@@ -206,6 +367,60 @@ impl AppState {
                 session.username = self.dialog.username.clone();
                 session.auth_type = auth_type;
                 session.proxy = proxy;
+                session.jump_hosts = jump_hosts;
+                session.highlight_rules = highlight_rules.clone();
+                session.notify_after_secs = notify_after_secs;
+                session.key_sends = key_sends.clone();
+                session.color_scheme = color_scheme.clone();
+                session.word_separators = word_separators.clone();
+                session.paste_confirm_chars = paste_confirm_chars;
+                session.sftp_parallel = sftp_parallel;
+                session.sftp_rate_limit_kib = sftp_rate_limit_kib;
+                session.sftp_retries = sftp_retries;
+                session.sftp_retry_delay_secs = sftp_retry_delay_secs;
+                session.sftp_download_dir = sftp_download_dir.clone();
+                session.sftp_upload_perms = sftp_upload_perms;
+            }
+            // Правила подсветки и сочетания применяются к живому соединению сразу
+            if let Some(conn) = self.connections.get_mut(id) {
+                conn.terminal.set_highlight_rules(&highlight_rules);
+                conn.terminal.set_key_sends(&key_sends);
+                conn.terminal.set_color_scheme(&scheme);
+                conn.terminal.set_word_separators(&word_separators);
+                conn.terminal.set_paste_confirm_chars(paste_confirm_chars);
+                for (terminal, _) in conn.panes.extra_mut() {
+                    terminal.set_highlight_rules(&highlight_rules);
+                    terminal.set_key_sends(&key_sends);
+                    terminal.set_color_scheme(&scheme);
+                    terminal.set_word_separators(&word_separators);
+                    terminal.set_paste_confirm_chars(paste_confirm_chars);
+                }
+                if let Some(tmux) = &mut conn.tmux {
+                    tmux.set_highlight_rules(&highlight_rules);
+                    tmux.set_key_sends(&key_sends);
+                    tmux.set_color_scheme(&scheme);
+                    tmux.set_word_separators(&word_separators);
+                    tmux.set_paste_confirm_chars(paste_confirm_chars);
+                }
+                conn.config.highlight_rules = highlight_rules;
+                conn.config.notify_after_secs = notify_after_secs;
+                conn.config.key_sends = key_sends;
+                conn.config.color_scheme = color_scheme;
+                if let Some(sftp) = &mut conn.sftp {
+                    sftp.set_parallel(sftp_parallel);
+                    sftp.set_rate_limit(sftp_rate_limit_kib);
+                    sftp.set_retry(sftp_retries, sftp_retry_delay_secs);
+                    sftp.set_download_dir(&sftp_download_dir);
+                    sftp.set_upload_perms(sftp_upload_perms);
+                }
+                conn.config.word_separators = word_separators;
+                conn.config.paste_confirm_chars = paste_confirm_chars;
+                conn.config.sftp_parallel = sftp_parallel;
+                conn.config.sftp_rate_limit_kib = sftp_rate_limit_kib;
+                conn.config.sftp_retries = sftp_retries;
+                conn.config.sftp_retry_delay_secs = sftp_retry_delay_secs;
+                conn.config.sftp_download_dir = sftp_download_dir;
+                conn.config.sftp_upload_perms = sftp_upload_perms;
             }
         } else {
             let session = SessionConfig {
@@ -216,6 +431,20 @@ impl AppState {
                 username: self.dialog.username.clone(),
                 auth_type,
                 proxy,
+                jump_hosts,
+                highlight_rules,
+                notify_after_secs,
+                key_sends,
+                color_scheme,
+                word_separators,
+                paste_confirm_chars,
+                sftp_parallel,
+                sftp_rate_limit_kib,
+                sftp_retries,
+                sftp_retry_delay_secs,
+                sftp_download_dir,
+                sftp_upload_perms,
+                forward_rules: Vec::new(),
                 key_passphrase: None,
             };
             self.sessions.push(session);
@@ -251,6 +480,15 @@ impl AppState {
                 egui::ScrollArea::vertical().show(ui, |ui| {
                     for session in &self.sessions {
                         let is_connected = self.connections.contains_key(&session.id);
+                        let activity = self
+                            .connections
+                            .get(&session.id)
+                            .map(|c| c.activity)
+                            .unwrap_or_default();
+                        let monitored = self
+                            .connections
+                            .get(&session.id)
+                            .is_some_and(|c| c.monitors.silence || c.monitors.activity);
                         let is_active = self.active_session_id.as_ref() == Some(&session.id);
 
                         let row_width = ui.available_width();
@@ -282,23 +520,39 @@ impl AppState {
                         let text_left = rect.min.x + 8.0;
                         let text_color = if is_active {
                             crate::theme::GREEN_BRIGHT
+                        } else if activity.bell {
+                            crate::theme::AMBER
+                        } else if activity.output {
+                            crate::theme::CYAN
                         } else {
                             crate::theme::GREEN_DIM
                         };
                         let font = egui::FontId::monospace(13.0);
 
                         // Status prefix
-                        let prefix = if is_connected { "> " } else { "  " };
+                        let prefix = if activity.output || activity.bell {
+                            "* "
+                        } else if is_connected {
+                            "> "
+                        } else {
+                            "  "
+                        };
                         ui.painter().text(
                             egui::pos2(text_left, rect.center().y),
                             egui::Align2::LEFT_CENTER,
-                            &format!("{}{}", prefix, session.name),
+                            format!("{}{}", prefix, session.name),
                             font,
                             text_color,
                         );
 
                         // Status indicator text
-                        let (status_text, status_color) = if is_connected {
+                        let (status_text, status_color) = if activity.bell {
+                            ("BEL", crate::theme::AMBER)
+                        } else if activity.output {
+                            ("NEW", crate::theme::CYAN)
+                        } else if monitored {
+                            ("MON", crate::theme::GREEN)
+                        } else if is_connected {
                             ("ON", crate::theme::GREEN)
                         } else {
                             ("--", crate::theme::GREY)
@@ -330,6 +584,35 @@ impl AppState {
                                     disconnect_id = Some(session.id.clone());
                                     ui.close_menu();
                                 }
+                                if let Some(conn) = self.connections.get_mut(&session.id) {
+                                    let monitors = &mut conn.monitors;
+                                    ui.separator();
+                                    if ui.checkbox(&mut monitors.silence, "monitor silence").changed() {
+                                        monitors.silence_fired = false;
+                                    }
+                                    ui.checkbox(&mut monitors.activity, "monitor activity");
+                                    ui.horizontal(|ui| {
+                                        ui.label("after");
+                                        ui.add(
+                                            egui::DragValue::new(&mut monitors.secs)
+                                                .range(1..=86400)
+                                                .suffix(" s"),
+                                        );
+                                    });
+                                    if let Some(fwd) =
+                                        conn.forward.as_mut().filter(|f| f.has_rules())
+                                    {
+                                        ui.separator();
+                                        if ui.button("[start all forwards]").clicked() {
+                                            fwd.start_all();
+                                            ui.close_menu();
+                                        }
+                                        if ui.button("[stop all forwards]").clicked() {
+                                            fwd.stop_all();
+                                            ui.close_menu();
+                                        }
+                                    }
+                                }
                             }
                             if ui.button("[edit]").clicked() {
                                 edit_session = Some(session.clone());
@@ -386,6 +669,19 @@ impl AppState {
                             .as_ref()
                             .map(|p| p.port.to_string())
                             .unwrap_or_default(),
+                        jump_hosts: JumpHost::format_list(&session.jump_hosts),
+                        highlight_rules: session.highlight_rules.clone(),
+                        notify_after: session.notify_after_secs.to_string(),
+                        key_sends: session.key_sends.clone(),
+                        color_scheme: session.color_scheme.clone(),
+                        word_separators: session.word_separators.clone(),
+                        paste_confirm_chars: session.paste_confirm_chars,
+                        sftp_parallel: session.sftp_parallel,
+                        sftp_rate_limit_kib: session.sftp_rate_limit_kib,
+                        sftp_retries: session.sftp_retries,
+                        sftp_retry_delay_secs: session.sftp_retry_delay_secs,
+                        sftp_download_dir: session.sftp_download_dir.clone(),
+                        sftp_upload_perms: session.sftp_upload_perms,
                     };
                     self.show_session_dialog = true;
                     self.dialog_focus_needed = true;
@@ -397,13 +693,405 @@ impl AppState {
                     self.show_session_dialog = true;
                     self.dialog_focus_needed = true;
                 }
+                if ui
+                    .button("[keys...]")
+                    .on_hover_text("rebind copy / paste / zoom / scroll / search")
+                    .clicked()
+                {
+                    self.keymap_edit = Some(keybindings::current());
+                }
+                if ui
+                    .button("[replay...]")
+                    .on_hover_text("play back a recorded session (.cast / script log)")
+                    .clicked()
+                {
+                    self.open_replay();
+                }
+                if ui
+                    .button("[forwards...]")
+                    .on_hover_text("port forwards of all connected sessions")
+                    .clicked()
+                {
+                    self.show_forwards = true;
+                }
+            });
+    }
+
+    fn open_replay(&mut self) {
+        let dialog = rfd::FileDialog::new()
+            .set_title("Open session recording")
+            .add_filter("asciicast", &["cast", "json"])
+            .add_filter("all files", &["*"]);
+        if let Some(path) = dialog.pick_file() {
+            match ReplayViewer::load(&path) {
+                Ok(viewer) => self.replay = Some(viewer),
+                Err(e) => self.last_error = Some(e),
+            }
+        }
+    }
+
+    // ── Вывод фоновых сессий и триггеры ──
+
+    /// Вывод читается для всех соединений, не только активного —
+    /// иначе триггеры в фоновых вкладках сработают только при переключении.
+    fn process_connections_output(&mut self, ctx: &egui::Context) {
+        let mut fired = false;
+        let window_focused = ctx.input(|i| i.viewport().focused.unwrap_or(true));
+        for (id, conn) in self.connections.iter_mut() {
+            if let Some(fwd) = &mut conn.forward {
+                fwd.poll();
+            }
+            if let Some(tmux) = &mut conn.tmux {
+                tmux.process(&conn.ssh);
+                if let Some(rest) = tmux.take_exit() {
+                    conn.tmux = None;
+                    conn.terminal.emulator.process(&rest);
+                }
+            } else {
+                conn.terminal.process_ssh_output(&conn.ssh);
+                if let Some(initial) = conn.terminal.take_tmux_handoff() {
+                    let mut tmux = TmuxSession::new(initial, &conn.config.highlight_rules);
+                    tmux.set_key_sends(&conn.config.key_sends);
+                    tmux.set_color_scheme(&conn.config.color_scheme.clone().unwrap_or_default());
+                    tmux.set_word_separators(&conn.config.word_separators);
+                    tmux.set_paste_confirm_chars(conn.config.paste_confirm_chars);
+                    conn.tmux = Some(tmux);
+                }
+            }
+            let mut hits = conn.terminal.take_rule_hits();
+            for (terminal, ssh) in conn.panes.extra_mut() {
+                terminal.process_ssh_output(ssh);
+                hits.extend(terminal.take_rule_hits());
+            }
+            if let Some(tmux) = &mut conn.tmux {
+                for terminal in tmux.terminals_mut() {
+                    hits.extend(terminal.take_rule_hits());
+                }
+            }
+
+            // Вывод и BEL в неактивных сессиях — бейдж в списке сессий
+            let mut activity = conn.terminal.take_activity();
+            for (terminal, _) in conn.panes.extra_mut() {
+                activity.merge(terminal.take_activity());
+            }
+            if let Some(tmux) = &mut conn.tmux {
+                for terminal in tmux.terminals_mut() {
+                    activity.merge(terminal.take_activity());
+                }
+            }
+            if self.active_session_id.as_ref() == Some(id) {
+                conn.activity = Activity::default();
+            } else {
+                conn.activity.merge(activity);
+            }
+            if activity.bell && !window_focused {
+                fired = true;
+            }
+
+            let watching = window_focused
+                && self.active_session_id.as_ref() == Some(id)
+                && conn.active_tab == Tab::Shell;
+
+            // Мониторы тишины / активности (включаются в меню сессии)
+            if let Some(event) = conn.monitors.check(activity.output, Instant::now()) {
+                if !watching {
+                    let title = format!("{}: {}", conn.config.name, event);
+                    self.toasts.push(ctx, &title, "monitor");
+                    crate::notify::desktop(&title, "monitor");
+                    fired = true;
+                }
+            }
+
+            // Долгие команды (OSC 133): уведомляем, если пользователь смотрит не сюда
+            let mut finished = conn.terminal.take_finished_commands();
+            for (terminal, _) in conn.panes.extra_mut() {
+                finished.extend(terminal.take_finished_commands());
+            }
+            if let Some(tmux) = &mut conn.tmux {
+                for terminal in tmux.terminals_mut() {
+                    finished.extend(terminal.take_finished_commands());
+                }
+            }
+            let threshold = conn.config.notify_after_secs as u64;
+            for command in finished {
+                if watching || threshold == 0 || command.duration.as_secs() < threshold {
+                    continue;
+                }
+                let status = match command.exit_code {
+                    Some(0) => "done".to_string(),
+                    Some(code) => format!("failed (exit {})", code),
+                    None => "finished".to_string(),
+                };
+                let title = format!(
+                    "{}: {} in {}",
+                    conn.config.name,
+                    status,
+                    crate::notify::format_duration(command.duration)
+                );
+                let body = if command.command.is_empty() {
+                    "command finished".to_string()
+                } else {
+                    command.command
+                };
+                self.toasts.push(ctx, &title, &body);
+                crate::notify::desktop(&title, &body);
+                fired = true;
+            }
+
+            for hit in hits {
+                if !hit.action.trim().is_empty() {
+                    highlight::run_action(&hit.action, &conn.config.name, &hit);
+                }
+                if hit.notify {
+                    let title = format!("{}: /{}/", conn.config.name, hit.pattern);
+                    self.toasts.push(ctx, &title, &hit.line);
+                    crate::notify::desktop(&title, &hit.line);
+                    fired = true;
+                }
+            }
+        }
+        if fired {
+            ctx.send_viewport_cmd(egui::ViewportCommand::RequestUserAttention(
+                egui::UserAttentionType::Informational,
+            ));
+        }
+    }
+
+    // ── Broadcast mode ──
+
+    /// Баннер с выбором сессий-получателей. Заметный — чтобы случайно
+    /// не набрать `rm` на всём парке серверов.
+    fn render_broadcast_banner(&mut self, ctx: &egui::Context) {
+        if !self.broadcast {
+            return;
+        }
+        let active = self.active_session_id.clone();
+        let mut stop = false;
+
+        egui::TopBottomPanel::top("broadcast_banner")
+            .frame(
+                egui::Frame::none()
+                    .fill(egui::Color32::from_rgb(0x3a, 0x0a, 0x0a))
+                    .stroke(egui::Stroke::new(1.0, crate::theme::RED))
+                    .inner_margin(egui::Margin::symmetric(8.0, 4.0)),
+            )
+            .show(ctx, |ui| {
+                ui.horizontal_wrapped(|ui| {
+                    let count = self.broadcast_targets.len();
+                    ui.label(
+                        egui::RichText::new(format!(
+                            "!! BROADCAST -- keystrokes also go to {} session(s):",
+                            count
+                        ))
+                        .color(crate::theme::RED)
+                        .strong(),
+                    );
+                    for session in &self.sessions {
+                        if !self.connections.contains_key(&session.id)
+                            || active.as_ref() == Some(&session.id)
+                        {
+                            continue;
+                        }
+                        let mut selected = self.broadcast_targets.contains(&session.id);
+                        if ui.checkbox(&mut selected, &session.name).changed() {
+                            if selected {
+                                self.broadcast_targets.insert(session.id.clone());
+                            } else {
+                                self.broadcast_targets.remove(&session.id);
+                            }
+                        }
+                    }
+                    if ui.button("[all]").clicked() {
+                        self.broadcast_targets = self
+                            .connections
+                            .keys()
+                            .filter(|id| active.as_ref() != Some(*id))
+                            .cloned()
+                            .collect();
+                    }
+                    if ui
+                        .button(egui::RichText::new("[stop broadcast]").color(crate::theme::RED))
+                        .clicked()
+                    {
+                        stop = true;
+                    }
+                });
+            });
+
+        if stop {
+            self.broadcast = false;
+        }
+    }
+
+    /// Собирать ввод только у активного соединения, пока включён broadcast.
+    fn sync_broadcast_capture(&mut self) {
+        for (id, conn) in self.connections.iter_mut() {
+            let capture = self.broadcast && self.active_session_id.as_ref() == Some(id);
+            conn.terminal.set_input_capture(capture);
+            for (terminal, _) in conn.panes.extra_mut() {
+                terminal.set_input_capture(capture);
+            }
+        }
+    }
+
+    /// Разослать набранное в активном терминале по выбранным сессиям.
+    fn dispatch_broadcast_input(&mut self) {
+        let active_id = match &self.active_session_id {
+            Some(id) if self.broadcast => id.clone(),
+            _ => return,
+        };
+        let mut input = Vec::new();
+        if let Some(conn) = self.connections.get_mut(&active_id) {
+            input = conn.terminal.take_captured_input();
+            for (terminal, _) in conn.panes.extra_mut() {
+                input.extend(terminal.take_captured_input());
+            }
+        }
+        if input.is_empty() {
+            return;
+        }
+        for id in &self.broadcast_targets {
+            if *id == active_id {
+                continue;
+            }
+            if let Some(conn) = self.connections.get_mut(id) {
+                conn.terminal.emulator.reset_scroll();
+                conn.ssh.send(&input);
+            }
+        }
+    }
+
+    // ── Окно воспроизведения записи ──
+
+    fn render_replay_window(&mut self, ctx: &egui::Context) {
+        let viewer = match &mut self.replay {
+            Some(v) => v,
+            None => return,
+        };
+
+        let mut open = true;
+        egui::Window::new(format!("replay: {}", viewer.title))
+            .id(egui::Id::new("replay_window"))
+            .open(&mut open)
+            .resizable(true)
+            .collapsible(true)
+            .default_size([820.0, 520.0])
+            .show(ctx, |ui| {
+                viewer.show(ui);
+            });
+
+        if !open {
+            self.replay = None;
+        }
+    }
+
+    // ── Обзор перенаправлений ──
+
+    /// Перенаправления всех подключённых сессий в одной таблице: запуск и остановка
+    /// без перехода во вкладку FWD каждой сессии.
+    fn render_forwards_window(&mut self, ctx: &egui::Context) {
+        if !self.show_forwards {
+            return;
+        }
+        let mut rows: Vec<(String, String, ForwardSummary)> = self
+            .connections
+            .iter()
+            .flat_map(|(id, conn)| {
+                let summaries = conn.forward.as_ref().map(|f| f.summaries()).unwrap_or_default();
+                summaries
+                    .into_iter()
+                    .map(move |s| (id.clone(), conn.config.name.clone(), s))
+            })
+            .collect();
+        rows.sort_by(|a, b| a.1.cmp(&b.1).then(a.2.index.cmp(&b.2.index)));
+
+        let mut open = true;
+        let mut start: Option<(String, usize)> = None;
+        let mut stop: Option<(String, usize)> = None;
+        let mut goto: Option<String> = None;
+        egui::Window::new("port forwards")
+            .id(egui::Id::new("forwards_window"))
+            .open(&mut open)
+            .resizable(true)
+            .collapsible(true)
+            .default_width(560.0)
+            .show(ctx, |ui| {
+                if rows.is_empty() {
+                    ui.colored_label(
+                        crate::theme::GREEN_DIM,
+                        "// no forwards in connected sessions",
+                    );
+                    return;
+                }
+                egui::Grid::new("forwards_grid")
+                    .num_columns(7)
+                    .striped(true)
+                    .spacing([12.0, 4.0])
+                    .show(ui, |ui| {
+                        ui.strong("SESSION");
+                        ui.strong("");
+                        ui.strong("NAME");
+                        ui.strong("TYPE");
+                        ui.strong("ENDPOINTS");
+                        ui.strong("#");
+                        ui.strong("");
+                        ui.end_row();
+                        for (id, name, summary) in &rows {
+                            if ui.link(name).on_hover_text("open the FWD tab").clicked() {
+                                goto = Some(id.clone());
+                            }
+                            if let Some(error) = &summary.failed {
+                                ui.colored_label(crate::theme::RED, "FAIL").on_hover_text(error);
+                            } else if summary.paused {
+                                ui.colored_label(crate::theme::AMBER, "PAUSE");
+                            } else if summary.running {
+                                ui.colored_label(crate::theme::GREEN, "ON");
+                            } else {
+                                ui.colored_label(crate::theme::GREY, "--");
+                            }
+                            ui.label(&summary.rule.label);
+                            let (flag, color) = summary.rule.forward_type.flag();
+                            ui.colored_label(color, flag);
+                            ui.monospace(summary.rule.endpoints());
+                            ui.label(summary.connections.to_string());
+                            if summary.running || summary.failed.is_some() {
+                                if ui.button("[stop]").clicked() {
+                                    stop = Some((id.clone(), summary.index));
+                                }
+                            } else if ui.button("[start]").clicked() {
+                                start = Some((id.clone(), summary.index));
+                            }
+                            ui.end_row();
+                        }
+                    });
             });
+
+        if let Some((id, index)) = start {
+            if let Some(fwd) = self.connections.get_mut(&id).and_then(|c| c.forward.as_mut()) {
+                fwd.start_forward(index);
+            }
+        }
+        if let Some((id, index)) = stop {
+            if let Some(fwd) = self.connections.get_mut(&id).and_then(|c| c.forward.as_mut()) {
+                fwd.stop_forward(index);
+            }
+        }
+        if let Some(id) = goto {
+            if let Some(conn) = self.connections.get_mut(&id) {
+                conn.active_tab = Tab::Forward;
+                self.active_session_id = Some(id);
+            }
+        }
+        if !open {
+            self.show_forwards = false;
+        }
     }
 
     // ── Центральная панель ──
 
     fn render_central_panel(&mut self, ctx: &egui::Context) {
-        let any_dialog = self.show_session_dialog || self.show_connect_dialog;
+        let any_dialog =
+            self.show_session_dialog || self.show_connect_dialog || self.keymap_edit.is_some();
         egui::CentralPanel::default().show(ctx, |ui| {
             let active_id = match self.active_session_id.clone() {
                 Some(id) => id,
@@ -418,6 +1106,14 @@ impl AppState {
                 }
             };
 
+            // Другие сессии с открытой SFTP-вкладкой — цели для [send to]
+            let peers: Vec<SftpPeer> = self
+                .connections
+                .iter()
+                .filter(|(id, _)| **id != active_id)
+                .filter_map(|(_, c)| c.sftp.as_ref()?.peer(&c.config.name))
+                .collect();
+
             let conn = match self.connections.get_mut(&active_id) {
                 Some(c) => c,
                 None => {
@@ -444,12 +1140,25 @@ impl AppState {
                 conn.error = Some(err);
             }
 
+            if let Some(err) = conn.terminal.take_recording_error() {
+                conn.error = Some(err);
+            }
+
             if let Some(err) = &conn.error {
                 ui.colored_label(
                     crate::theme::RED,
                     format!("ERR: {}", err),
                 );
             }
+            if let Some(msg) = &conn.status {
+                ui.colored_label(crate::theme::GREEN, msg);
+            }
+
+            let mut toggle_recording = false;
+            let mut save_buffer: Option<bool> = None; // Some(ansi)
+            let mut split: Option<SplitDir> = None;
+            let mut close_pane = false;
+            let mut tmux_action: Option<TmuxAction> = None;
 
             ui.horizontal(|ui| {
                 ui.selectable_value(&mut conn.active_tab, Tab::Shell, "[SHELL]");
@@ -462,13 +1171,194 @@ impl AppState {
                     } else {
                         ui.colored_label(crate::theme::RED, "[OFFLINE]");
                     }
+
+                    if conn.terminal.is_recording() {
+                        if ui
+                            .button(egui::RichText::new("[stop rec]").color(crate::theme::RED))
+                            .clicked()
+                        {
+                            toggle_recording = true;
+                        }
+                        ui.colored_label(crate::theme::RED, "* REC");
+                    } else if ui
+                        .button("[rec]")
+                        .on_hover_text("record session to asciicast v2 (.cast)")
+                        .clicked()
+                    {
+                        toggle_recording = true;
+                    }
+
+                    ui.menu_button("[save buf]", |ui| {
+                        if ui.button("as text...").clicked() {
+                            save_buffer = Some(false);
+                            ui.close_menu();
+                        }
+                        if ui
+                            .button("as ansi...")
+                            .on_hover_text("keeps colors; view with `cat` / `less -R`")
+                            .clicked()
+                        {
+                            save_buffer = Some(true);
+                            ui.close_menu();
+                        }
+                    })
+                    .response
+                    .on_hover_text("save scrollback + screen to file");
+
+                    if conn.active_tab == Tab::Shell && conn.ssh.is_alive() {
+                        let broadcast_label = if self.broadcast {
+                            egui::RichText::new("[broadcast: on]").color(crate::theme::RED)
+                        } else {
+                            egui::RichText::new("[broadcast]")
+                        };
+                        if ui
+                            .button(broadcast_label)
+                            .on_hover_text("send keystrokes to several sessions at once")
+                            .clicked()
+                        {
+                            self.broadcast = !self.broadcast;
+                        }
+                        if conn.tmux.is_some() {
+                            if ui
+                                .button("[detach tmux]")
+                                .on_hover_text("tmux -CC: detach, sessions keep running")
+                                .clicked()
+                            {
+                                tmux_action = Some(TmuxAction::Detach);
+                            }
+                            if ui.button("[+ window]").clicked() {
+                                tmux_action = Some(TmuxAction::NewWindow);
+                            }
+                        }
+                        let can_close = if conn.tmux.is_some() {
+                            true
+                        } else {
+                            conn.panes.is_split() && !conn.panes.is_primary_focused()
+                        };
+                        if can_close
+                            && ui
+                                .button("[close pane]")
+                                .on_hover_text("Ctrl+Shift+W")
+                                .clicked()
+                        {
+                            close_pane = true;
+                        }
+                        if ui
+                            .button("[split -]")
+                            .on_hover_text("split stacked (Ctrl+Shift+O)")
+                            .clicked()
+                        {
+                            split = Some(SplitDir::Vertical);
+                        }
+                        if ui
+                            .button("[split |]")
+                            .on_hover_text("split side by side (Ctrl+Shift+E)\nAlt+Arrows: move between panes")
+                            .clicked()
+                        {
+                            split = Some(SplitDir::Horizontal);
+                        }
+                    }
                 });
             });
             ui.separator();
 
+            if toggle_recording {
+                conn.status = None;
+                if conn.terminal.is_recording() {
+                    match conn.terminal.stop_recording() {
+                        Ok(Some(path)) => {
+                            conn.status = Some(format!("recording saved: {}", path.display()));
+                        }
+                        Ok(None) => {}
+                        Err(e) => conn.error = Some(e),
+                    }
+                } else {
+                    let file_name =
+                        crate::terminal::recorder::default_file_name(&conn.config.name, "cast");
+                    let mut dialog = rfd::FileDialog::new()
+                        .set_title("Save session recording")
+                        .set_file_name(&file_name)
+                        .add_filter("asciicast", &["cast"]);
+                    if let Some(dir) = dirs::document_dir().or_else(dirs::home_dir) {
+                        dialog = dialog.set_directory(dir);
+                    }
+                    if let Some(path) = dialog.save_file() {
+                        let title = format!(
+                            "{} ({}@{})",
+                            conn.config.name, conn.config.username, conn.config.host
+                        );
+                        if let Err(e) = conn.terminal.start_recording(&path, &title) {
+                            conn.error = Some(e);
+                        }
+                    }
+                }
+            }
+
+            if let Some(ansi) = save_buffer {
+                conn.status = None;
+                let extension = if ansi { "ans" } else { "txt" };
+                let file_name =
+                    crate::terminal::recorder::default_file_name(&conn.config.name, extension);
+                let mut dialog = rfd::FileDialog::new()
+                    .set_title("Save terminal buffer")
+                    .set_file_name(&file_name)
+                    .add_filter(if ansi { "ansi text" } else { "text" }, &[extension, "log"]);
+                if let Some(dir) = dirs::document_dir().or_else(dirs::home_dir) {
+                    dialog = dialog.set_directory(dir);
+                }
+                if let Some(path) = dialog.save_file() {
+                    match conn.terminal.save_buffer(&path, ansi) {
+                        Ok(()) => conn.status = Some(format!("buffer saved: {}", path.display())),
+                        Err(e) => conn.error = Some(e),
+                    }
+                }
+            }
+
             match conn.active_tab {
+                Tab::Shell if conn.tmux.is_some() => {
+                    if let Some(tmux) = &mut conn.tmux {
+                        let action = match split {
+                            Some(SplitDir::Horizontal) => Some(TmuxAction::SplitHorizontal),
+                            Some(SplitDir::Vertical) => Some(TmuxAction::SplitVertical),
+                            None if close_pane => Some(TmuxAction::ClosePane),
+                            None => tmux_action,
+                        };
+                        if let Some(action) = action {
+                            tmux.action(&conn.ssh, action);
+                        }
+                        if !any_dialog && conn.ssh.is_alive() {
+                            tmux.handle_shortcuts(ui, &conn.ssh);
+                        }
+                        tmux.show(ui, &conn.ssh, !any_dialog);
+                    }
+                }
                 Tab::Shell => {
-                    conn.terminal.show(ui, &conn.ssh, !any_dialog);
+                    let rules = conn.config.highlight_rules.clone();
+                    let key_sends = conn.config.key_sends.clone();
+                    let scheme = conn.config.color_scheme.clone().unwrap_or_default();
+                    let word_separators = conn.config.word_separators.clone();
+                    let paste_confirm_chars = conn.config.paste_confirm_chars;
+                    let new_terminal = || {
+                        let mut terminal = TerminalWidget::new(80, 24);
+                        terminal.set_highlight_rules(&rules);
+                        terminal.set_key_sends(&key_sends);
+                        terminal.set_color_scheme(&scheme);
+                        terminal.set_word_separators(&word_separators);
+                        terminal.set_paste_confirm_chars(paste_confirm_chars);
+                        terminal
+                    };
+                    if let Some(dir) = split {
+                        conn.panes.split(dir, &conn.ssh, new_terminal());
+                    }
+                    if close_pane {
+                        conn.panes.close_focused();
+                    }
+                    if !any_dialog && conn.ssh.is_alive() {
+                        let primary = (&conn.terminal, &conn.ssh);
+                        conn.panes.handle_shortcuts(ui, primary, new_terminal);
+                    }
+                    conn.panes
+                        .show(ui, (&mut conn.terminal, &conn.ssh), !any_dialog);
                 }
                 Tab::Sftp => {
                     if conn.sftp.is_none() {
@@ -484,7 +1374,16 @@ impl AppState {
                     }
 
                     if let Some(sftp) = &mut conn.sftp {
+                        sftp.set_peers(peers);
                         sftp.show(ui);
+                        // [open shell here] — в активную панель Shell-вкладки
+                        if let Some(command) = sftp.take_shell_command() {
+                            match &conn.tmux {
+                                Some(tmux) => tmux.send_to_focused(command.as_bytes()),
+                                None => conn.panes.focused_ssh(&conn.ssh).send(command.as_bytes()),
+                            }
+                            conn.active_tab = Tab::Shell;
+                        }
                     }
                 }
                 Tab::Forward => {
@@ -494,6 +1393,16 @@ impl AppState {
 
                     if let Some(fwd) = &mut conn.forward {
                         fwd.show(ui);
+                        // Правила живут в сессии — переживают отключение
+                        if let Some(rules) = fwd.take_rules_changed() {
+                            if let Some(session) =
+                                self.sessions.iter_mut().find(|s| s.id == active_id)
+                            {
+                                session.forward_rules = rules.clone();
+                                config::save_sessions(&self.sessions);
+                            }
+                            conn.config.forward_rules = rules;
+                        }
                     }
                 }
             }
@@ -657,7 +1566,7 @@ impl AppState {
         }
 
         // Собираем активные SOCKS5-прокси до мутабельного заимствования в closure
-        let active_proxies: Vec<(String, String, u16)> = self
+        let active_proxies: Vec<(String, String, u16, String)> = self
             .connections
             .iter()
             .flat_map(|(id, conn)| {
@@ -672,7 +1581,9 @@ impl AppState {
                     .map(|fwd| {
                         fwd.active_socks5_proxies()
                             .into_iter()
-                            .map(move |(host, port)| (session_name.clone(), host, port))
+                            .map(move |(host, port, label)| {
+                                (session_name.clone(), host, port, label)
+                            })
                     })
                     .into_iter()
                     .flatten()
@@ -686,6 +1597,7 @@ impl AppState {
         };
 
         let mut open = true;
+        let mut import_scheme = false;
         egui::Window::new(title)
             .open(&mut open)
             .resizable(false)
@@ -830,11 +1742,15 @@ impl AppState {
                                         crate::theme::GREEN_DIM,
                                         "// active socks5 proxies:",
                                     );
-                                    for (name, host, port) in &active_proxies {
+                                    for (name, host, port, label) in &active_proxies {
                                         let is_selected =
                                             self.dialog.proxy_host == *host
                                                 && self.dialog.proxy_port == port.to_string();
-                                        let label_text = format!("{}:{} -- {}", host, port, name);
+                                        let label_text = if label.is_empty() {
+                                            format!("{}:{} -- {}", host, port, name)
+                                        } else {
+                                            format!("{}:{} -- {} / {}", host, port, name, label)
+                                        };
                                         let resp = ui.add(
                                             egui::SelectableLabel::new(
                                                 is_selected,
@@ -850,15 +1766,236 @@ impl AppState {
                                 ui.end_row();
                             }
                         }
+
+                        ui.label("jump hosts:");
+                        ui.vertical(|ui| {
+                            ui.add(
+                                egui::TextEdit::singleline(&mut self.dialog.jump_hosts)
+                                    .hint_text("user@bastion:22, inner")
+                                    .desired_width(260.0),
+                            )
+                            .on_hover_text(
+                                "like ssh -J: hops in order; shell, SFTP and port forwards \
+                                 all go through them. Each hop uses this session's key or \
+                                 agent; the password is never sent to a hop",
+                            );
+                            if let Err(e) = JumpHost::parse_list(&self.dialog.jump_hosts) {
+                                ui.colored_label(crate::theme::RED, e);
+                            } else if self.dialog.auth_choice == 0
+                                && !self.dialog.jump_hosts.trim().is_empty()
+                            {
+                                ui.colored_label(
+                                    crate::theme::RED,
+                                    "jump hosts need key or agent auth",
+                                );
+                            }
+                        });
+                        ui.end_row();
+
+                        ui.label("notify:");
+                        ui.horizontal(|ui| {
+                            ui.label("commands longer than");
+                            ui.add(
+                                egui::TextEdit::singleline(&mut self.dialog.notify_after)
+                                    .hint_text("0")
+                                    .desired_width(40.0),
+                            );
+                            ui.label("s");
+                        })
+                        .response
+                        .on_hover_text(
+                            "when unfocused; needs shell integration (OSC 133), 0 = off",
+                        );
+                        ui.end_row();
+
+                        ui.label("colors:");
+                        ui.horizontal(|ui| {
+                            let current = self
+                                .dialog
+                                .color_scheme
+                                .as_ref()
+                                .map(|s| s.name.clone())
+                                .unwrap_or_else(|| "phosphor (default)".to_string());
+                            egui::ComboBox::from_id_salt("color_scheme")
+                                .selected_text(current)
+                                .show_ui(ui, |ui| {
+                                    if ui
+                                        .selectable_label(
+                                            self.dialog.color_scheme.is_none(),
+                                            "phosphor (default)",
+                                        )
+                                        .clicked()
+                                    {
+                                        self.dialog.color_scheme = None;
+                                    }
+                                    let schemes = ColorScheme::builtin()
+                                        .into_iter()
+                                        .skip(1)
+                                        .chain(self.color_schemes.iter().cloned());
+                                    for scheme in schemes {
+                                        let selected =
+                                            self.dialog.color_scheme.as_ref() == Some(&scheme);
+                                        if ui.selectable_label(selected, &scheme.name).clicked() {
+                                            self.dialog.color_scheme = Some(scheme);
+                                        }
+                                    }
+                                });
+                            if ui
+                                .button("[import...]")
+                                .on_hover_text(".itermcolors / Xresources / base16 yaml")
+                                .clicked()
+                            {
+                                import_scheme = true;
+                            }
+                            let preview = self.dialog.color_scheme.clone().unwrap_or_default();
+                            Self::paint_scheme_preview(ui, &preview);
+                        });
+                        ui.end_row();
+
+                        ui.label("word breaks:");
+                        ui.horizontal(|ui| {
+                            ui.add(
+                                egui::TextEdit::singleline(&mut self.dialog.word_separators)
+                                    .font(egui::TextStyle::Monospace)
+                                    .desired_width(160.0),
+                            );
+                            if ui.button("[defaults]").clicked() {
+                                self.dialog.word_separators =
+                                    crate::ssh::session::default_word_separators();
+                            }
+                        })
+                        .response
+                        .on_hover_text(
+                            "characters that end a word on double-click; whitespace always does",
+                        );
+                        ui.end_row();
+
+                        ui.label("paste confirm:");
+                        ui.add(
+                            egui::DragValue::new(&mut self.dialog.paste_confirm_chars)
+                                .range(0..=1_000_000)
+                                .speed(16)
+                                .suffix(" chars"),
+                        )
+                        .on_hover_text(
+                            "ask before pasting more than this; multi-line text always asks; \
+                             0 — only multi-line",
+                        );
+                        ui.end_row();
+
+                        ui.label("sftp parallel:");
+                        ui.add(egui::DragValue::new(&mut self.dialog.sftp_parallel).range(1..=8))
+                            .on_hover_text("simultaneous transfers, each on its own SFTP channel");
+                        ui.end_row();
+
+                        ui.label("sftp limit:");
+                        ui.add(
+                            egui::DragValue::new(&mut self.dialog.sftp_rate_limit_kib)
+                                .range(0..=1_048_576)
+                                .speed(16)
+                                .suffix(" KiB/s"),
+                        )
+                        .on_hover_text("shared by all transfers of the session; 0 — unlimited");
+                        ui.end_row();
+
+                        ui.label("sftp retries:");
+                        ui.horizontal(|ui| {
+                            ui.add(
+                                egui::DragValue::new(&mut self.dialog.sftp_retries).range(0..=10),
+                            );
+                            ui.label("after");
+                            ui.add(
+                                egui::DragValue::new(&mut self.dialog.sftp_retry_delay_secs)
+                                    .range(1..=60)
+                                    .suffix(" s"),
+                            );
+                        })
+                        .response
+                        .on_hover_text(
+                            "dropped channels and timeouts are retried, reconnecting if needed; \
+                             the delay doubles each attempt",
+                        );
+                        ui.end_row();
+
+                        ui.label("downloads:");
+                        ui.horizontal(|ui| {
+                            ui.add(
+                                egui::TextEdit::singleline(&mut self.dialog.sftp_download_dir)
+                                    .hint_text("system downloads folder")
+                                    .desired_width(220.0),
+                            );
+                            if ui.button("[browse...]").clicked() {
+                                if let Some(dir) = rfd::FileDialog::new()
+                                    .set_title("SFTP download folder")
+                                    .pick_folder()
+                                {
+                                    self.dialog.sftp_download_dir =
+                                        dir.to_string_lossy().to_string();
+                                }
+                            }
+                        })
+                        .response
+                        .on_hover_text("where [get] saves files; [get to...] asks every time");
+                        ui.end_row();
+
+                        ui.label("upload perms:");
+                        ui.horizontal(|ui| {
+                            let perms = &mut self.dialog.sftp_upload_perms;
+                            let fixed = UploadPermissions::Fixed {
+                                file: 0o644,
+                                dir: 0o755,
+                            };
+                            let label = match perms {
+                                UploadPermissions::Server => "server umask",
+                                UploadPermissions::Preserve => "preserve source",
+                                UploadPermissions::Fixed { .. } => "fixed",
+                            };
+                            egui::ComboBox::from_id_salt("sftp_upload_perms")
+                                .selected_text(label)
+                                .show_ui(ui, |ui| {
+                                    ui.selectable_value(
+                                        perms,
+                                        UploadPermissions::Server,
+                                        "server umask",
+                                    );
+                                    ui.selectable_value(
+                                        perms,
+                                        UploadPermissions::Preserve,
+                                        "preserve source",
+                                    );
+                                    let is_fixed = matches!(perms, UploadPermissions::Fixed { .. });
+                                    let clicked = ui.selectable_label(is_fixed, "fixed").clicked();
+                                    if clicked && !is_fixed {
+                                        *perms = fixed;
+                                    }
+                                });
+                            if let UploadPermissions::Fixed { file, dir } = perms {
+                                ui.label("files");
+                                ui.add(egui::DragValue::new(file).octal(4, false).range(0..=0o7777));
+                                ui.label("dirs");
+                                ui.add(egui::DragValue::new(dir).octal(4, false).range(0..=0o7777));
+                            }
+                        })
+                        .response
+                        .on_hover_text(
+                            "applied to new files and folders after upload and to [mkdir]; \
+                             resumed uploads keep their permissions",
+                        );
+                        ui.end_row();
                     });
 
+                ui.add_space(4.0);
+                Self::render_highlight_rules_editor(ui, &mut self.dialog.highlight_rules);
+                Self::render_key_sends_editor(ui, &mut self.dialog.key_sends);
+
                 ui.add_space(8.0);
                 ui.separator();
                 ui.add_space(4.0);
 
                 let can_save = !self.dialog.name.is_empty()
                     && !self.dialog.host.is_empty()
-                    && !self.dialog.username.is_empty();
+                    && !self.dialog.username.is_empty()
+                    && JumpHost::parse_list(&self.dialog.jump_hosts).is_ok();
 
                 let mut action = DialogAction::None;
 
@@ -916,10 +2053,257 @@ impl AppState {
                 }
             });
 
+        if import_scheme {
+            self.import_color_scheme();
+        }
         if !open {
             self.show_session_dialog = false;
         }
     }
+
+    /// Импорт схемы из файла: попадает в библиотеку и выбирается в диалоге.
+    fn import_color_scheme(&mut self) {
+        let dialog = rfd::FileDialog::new()
+            .set_title("Import color scheme")
+            .add_filter("iTerm2", &["itermcolors"])
+            .add_filter("base16", &["yaml", "yml"])
+            .add_filter("all files", &["*"]);
+        let path = match dialog.pick_file() {
+            Some(p) => p,
+            None => return,
+        };
+        match palette::import(&path) {
+            Ok(scheme) => {
+                self.color_schemes.retain(|s| s.name != scheme.name);
+                self.color_schemes.push(scheme.clone());
+                scheme_config::save_schemes(&self.color_schemes);
+                self.dialog.color_scheme = Some(scheme);
+            }
+            Err(e) => self.last_error = Some(e),
+        }
+    }
+
+    /// Полоска из 16 ANSI-цветов схемы на её фоне.
+    fn paint_scheme_preview(ui: &mut egui::Ui, scheme: &ColorScheme) {
+        let swatch = egui::vec2(8.0, 14.0);
+        let (rect, _) = ui.allocate_exact_size(
+            egui::vec2(swatch.x * 16.0 + 4.0, swatch.y + 4.0),
+            egui::Sense::hover(),
+        );
+        let painter = ui.painter();
+        painter.rect_filled(rect, 0.0, scheme.background_color());
+        for idx in 0..16u8 {
+            let min = rect.min + egui::vec2(2.0 + idx as f32 * swatch.x, 2.0);
+            painter.rect_filled(
+                egui::Rect::from_min_size(min, swatch),
+                0.0,
+                scheme.indexed(idx),
+            );
+        }
+    }
+}
+
+impl AppState {
+    // ── Редактор правил подсветки (в диалоге сессии) ──
+
+    fn render_highlight_rules_editor(ui: &mut egui::Ui, rules: &mut Vec<HighlightRule>) {
+        let header = format!("highlight rules ({})", rules.len());
+        egui::CollapsingHeader::new(header)
+            .id_salt("highlight_rules")
+            .show(ui, |ui| {
+                ui.colored_label(
+                    crate::theme::GREEN_DIM,
+                    "// regex over output; notify / action fire on new lines",
+                );
+                ui.add_space(4.0);
+
+                let mut remove: Option<usize> = None;
+                for (idx, rule) in rules.iter_mut().enumerate() {
+                    ui.push_id(idx, |ui| {
+                        ui.horizontal(|ui| {
+                            ui.checkbox(&mut rule.enabled, "");
+                            let valid =
+                                rule.pattern.is_empty() || regex::Regex::new(&rule.pattern).is_ok();
+                            let mut edit = egui::TextEdit::singleline(&mut rule.pattern)
+                                .hint_text("ERROR|FAILED")
+                                .desired_width(150.0)
+                                .font(egui::TextStyle::Monospace);
+                            if !valid {
+                                edit = edit.text_color(crate::theme::RED);
+                            }
+                            ui.add(edit).on_hover_text(if valid {
+                                "regex pattern"
+                            } else {
+                                "invalid regex -- rule ignored"
+                            });
+                            ui.color_edit_button_srgb(&mut rule.color);
+                            ui.checkbox(&mut rule.notify, "notify");
+                            if ui.small_button("[x]").clicked() {
+                                remove = Some(idx);
+                            }
+                        });
+                        ui.horizontal(|ui| {
+                            ui.add_space(24.0);
+                            ui.label("action:");
+                            ui.add(
+                                egui::TextEdit::singleline(&mut rule.action)
+                                    .hint_text("local command (optional)")
+                                    .desired_width(220.0),
+                            )
+                            .on_hover_text(
+                                "runs locally on match; env: SSHERALD_SESSION, SSHERALD_PATTERN, SSHERALD_LINE",
+                            );
+                        });
+                    });
+                }
+                if let Some(idx) = remove {
+                    rules.remove(idx);
+                }
+
+                if ui.button("[+ rule]").clicked() {
+                    rules.push(HighlightRule::default());
+                }
+            });
+    }
+}
+
+impl AppState {
+    // ── Свои последовательности клавиш (в диалоге сессии) ──
+
+    fn render_key_sends_editor(ui: &mut egui::Ui, sends: &mut Vec<KeySend>) {
+        let header = format!("key sequences ({})", sends.len());
+        egui::CollapsingHeader::new(header)
+            .id_salt("key_sends")
+            .show(ui, |ui| {
+                ui.colored_label(
+                    crate::theme::GREEN_DIM,
+                    "// key -> bytes to the shell; escapes: \\e \\n \\r \\t \\xHH",
+                );
+                ui.add_space(4.0);
+
+                let mut remove: Option<usize> = None;
+                for (idx, send) in sends.iter_mut().enumerate() {
+                    ui.push_id(idx, |ui| {
+                        ui.horizontal(|ui| {
+                            let valid = send.keys.trim().is_empty()
+                                || KeyChord::parse_list(&send.keys).is_some();
+                            let mut edit = egui::TextEdit::singleline(&mut send.keys)
+                                .hint_text("Alt+F1")
+                                .desired_width(120.0)
+                                .font(egui::TextStyle::Monospace);
+                            if !valid {
+                                edit = edit.text_color(crate::theme::RED);
+                            }
+                            ui.add(edit).on_hover_text(if valid {
+                                "key chord, e.g. Ctrl+Shift+K"
+                            } else {
+                                "unknown key -- ignored"
+                            });
+                            ui.label("->");
+                            ui.add(
+                                egui::TextEdit::singleline(&mut send.send)
+                                    .hint_text("\\e[15~")
+                                    .desired_width(160.0)
+                                    .font(egui::TextStyle::Monospace),
+                            );
+                            if ui.small_button("[x]").clicked() {
+                                remove = Some(idx);
+                            }
+                        });
+                    });
+                }
+                if let Some(idx) = remove {
+                    sends.remove(idx);
+                }
+
+                if ui.button("[+ key]").clicked() {
+                    sends.push(KeySend::default());
+                }
+            });
+    }
+
+    // ── Окно сочетаний клавиш (глобальные действия терминала) ──
+
+    fn render_keymap_window(&mut self, ctx: &egui::Context) {
+        let map = match &mut self.keymap_edit {
+            Some(m) => m,
+            None => return,
+        };
+
+        let mut open = true;
+        let mut save = false;
+        let mut close = false;
+        egui::Window::new("keybindings")
+            .id(egui::Id::new("keymap_window"))
+            .open(&mut open)
+            .resizable(false)
+            .collapsible(false)
+            .show(ctx, |ui| {
+                ui.colored_label(
+                    crate::theme::GREEN_DIM,
+                    "// e.g. Ctrl+Shift+C; several: comma-separated; empty = unbound",
+                );
+                ui.add_space(4.0);
+
+                egui::Grid::new("keymap_grid")
+                    .num_columns(2)
+                    .spacing([12.0, 4.0])
+                    .show(ui, |ui| {
+                        for binding in &mut map.bindings {
+                            ui.label(binding.action.label());
+                            let valid = KeyChord::parse_list(&binding.keys).is_some();
+                            let mut edit = egui::TextEdit::singleline(&mut binding.keys)
+                                .desired_width(200.0)
+                                .font(egui::TextStyle::Monospace);
+                            if !valid {
+                                edit = edit.text_color(crate::theme::RED);
+                            }
+                            ui.add(edit).on_hover_text(if valid {
+                                binding.action.default_keys()
+                            } else {
+                                "unknown key -- binding ignored"
+                            });
+                            ui.end_row();
+                        }
+                    });
+
+                ui.add_space(4.0);
+                ui.checkbox(&mut map.alt_meta, "alt sends ESC (meta)")
+                    .on_hover_text("Alt+b -> ESC b, for readline / emacs M-b, M-f");
+                ui.horizontal(|ui| {
+                    ui.label("wheel on alt screen:");
+                    ui.add(egui::DragValue::new(&mut map.alt_scroll_lines).range(0..=20));
+                    ui.label("arrow keys per notch");
+                })
+                .response
+                .on_hover_text("less, vim, htop... 0 = scroll local scrollback");
+
+                ui.add_space(8.0);
+                ui.separator();
+                ui.add_space(4.0);
+
+                ui.horizontal(|ui| {
+                    if ui.button("[save]").clicked() {
+                        save = true;
+                    }
+                    if ui.button("[defaults]").clicked() {
+                        *map = KeyMap::default();
+                    }
+                    if ui.button("[cancel]").clicked() {
+                        close = true;
+                    }
+                });
+            });
+
+        if save {
+            keybindings::install(map);
+            keymap_config::save_keymap(map);
+            close = true;
+        }
+        if close || !open {
+            self.keymap_edit = None;
+        }
+    }
 }
 
 impl eframe::App for AppState {
@@ -1081,10 +2465,21 @@ impl eframe::App for AppState {
             self.connections.remove(id);
         }
 
+        self.broadcast_targets.retain(|id| self.connections.contains_key(id));
+
+        self.process_connections_output(ctx);
+
+        self.render_broadcast_banner(ctx);
         self.render_sessions_panel(ctx);
+        self.sync_broadcast_capture();
         self.render_central_panel(ctx);
+        self.dispatch_broadcast_input();
         self.render_session_dialog(ctx);
         self.render_connect_dialog(ctx);
+        self.render_replay_window(ctx);
+        self.render_keymap_window(ctx);
+        self.render_forwards_window(ctx);
+        self.toasts.show(ctx);
 
         if !self.connections.is_empty() {
             ctx.request_repaint_after(std::time::Duration::from_millis(16));
@@ -0,0 +1,65 @@
+use crate::ssh::forward::{ForwardPreset, ForwardSettings};
+use std::path::PathBuf;
+
+fn config_dir() -> PathBuf {
+    let dir = dirs::config_dir()
+        .unwrap_or_else(|| PathBuf::from("."))
+        .join("ssherald");
+    std::fs::create_dir_all(&dir).ok();
+    dir
+}
+
+fn config_path() -> PathBuf {
+    config_dir().join("forward_presets.json")
+}
+
+fn settings_path() -> PathBuf {
+    config_dir().join("forwards.json")
+}
+
+/// Профиль браузера для [open browser]: ssherald/browser/<браузер>-<порт прокси>.
+/// Один прокси — один профиль, вход на сайты сохраняется между запусками.
+pub fn browser_profile_path(browser: &str, port: u16) -> PathBuf {
+    dirs::cache_dir()
+        .unwrap_or_else(std::env::temp_dir)
+        .join("ssherald")
+        .join("browser")
+        .join(format!("{}-{}", browser, port))
+}
+
+/// Шаблоны правил, общие для всех сессий.
+pub fn load_presets() -> Vec<ForwardPreset> {
+    std::fs::read_to_string(config_path())
+        .ok()
+        .and_then(|data| serde_json::from_str(&data).ok())
+        .unwrap_or_default()
+}
+
+pub fn save_presets(presets: &[ForwardPreset]) {
+    if let Ok(json) = serde_json::to_string_pretty(presets) {
+        let _ = std::fs::write(config_path(), json);
+    }
+}
+
+/// [options] панели перенаправлений, общие для всех вкладок: файл читается один раз.
+static SETTINGS: parking_lot::Mutex<Option<ForwardSettings>> = parking_lot::Mutex::new(None);
+
+/// [options] панели перенаправлений. Нет файла — по умолчанию.
+pub fn load_settings() -> ForwardSettings {
+    SETTINGS
+        .lock()
+        .get_or_insert_with(|| {
+            std::fs::read_to_string(settings_path())
+                .ok()
+                .and_then(|data| serde_json::from_str(&data).ok())
+                .unwrap_or_default()
+        })
+        .clone()
+}
+
+pub fn save_settings(settings: &ForwardSettings) {
+    *SETTINGS.lock() = Some(settings.clone());
+    if let Ok(json) = serde_json::to_string_pretty(settings) {
+        let _ = std::fs::write(settings_path(), json);
+    }
+}
@@ -0,0 +1,25 @@
+use crate::terminal::keybindings::KeyMap;
+use std::path::PathBuf;
+
+fn config_path() -> PathBuf {
+    let dir = dirs::config_dir()
+        .unwrap_or_else(|| PathBuf::from("."))
+        .join("ssherald");
+    std::fs::create_dir_all(&dir).ok();
+    dir.join("keybindings.json")
+}
+
+/// Нет файла или он битый — сочетания по умолчанию.
+pub fn load_keymap() -> KeyMap {
+    std::fs::read_to_string(config_path())
+        .ok()
+        .and_then(|data| serde_json::from_str::<KeyMap>(&data).ok())
+        .map(KeyMap::normalized)
+        .unwrap_or_default()
+}
+
+pub fn save_keymap(map: &KeyMap) {
+    if let Ok(json) = serde_json::to_string_pretty(map) {
+        let _ = std::fs::write(config_path(), json);
+    }
+}
@@ -1 +1,5 @@
+pub mod forwards;
+pub mod keybindings;
+pub mod schemes;
 pub mod sessions;
+pub mod sftp;
@@ -0,0 +1,24 @@
+use crate::terminal::palette::ColorScheme;
+use std::path::PathBuf;
+
+fn config_path() -> PathBuf {
+    let dir = dirs::config_dir()
+        .unwrap_or_else(|| PathBuf::from("."))
+        .join("ssherald");
+    std::fs::create_dir_all(&dir).ok();
+    dir.join("color_schemes.json")
+}
+
+/// Импортированные пользователем схемы (встроенные тут не хранятся).
+pub fn load_schemes() -> Vec<ColorScheme> {
+    std::fs::read_to_string(config_path())
+        .ok()
+        .and_then(|data| serde_json::from_str(&data).ok())
+        .unwrap_or_default()
+}
+
+pub fn save_schemes(schemes: &[ColorScheme]) {
+    if let Ok(json) = serde_json::to_string_pretty(schemes) {
+        let _ = std::fs::write(config_path(), json);
+    }
+}
@@ -1,4 +1,12 @@
-use crate::ssh::session::{AuthType, ProxyConfig, SessionConfig};
+use crate::ssh::forward::ForwardRule;
+use crate::ssh::session::{
+    default_notify_after_secs, default_paste_confirm_chars, default_sftp_parallel,
+    default_sftp_retries, default_sftp_retry_delay_secs, default_word_separators, AuthType,
+    JumpHost, ProxyConfig, SessionConfig, UploadPermissions,
+};
+use crate::terminal::highlight::HighlightRule;
+use crate::terminal::keybindings::KeySend;
+use crate::terminal::palette::ColorScheme;
 use std::path::PathBuf;
 
 #[derive(serde::Serialize, serde::Deserialize, Default)]
@@ -18,6 +26,34 @@ struct StoredSession {
     proxy_host: Option<String>,
     #[serde(default)]
     proxy_port: Option<u16>,
+    #[serde(default)]
+    jump_hosts: Vec<JumpHost>,
+    #[serde(default)]
+    highlight_rules: Vec<HighlightRule>,
+    #[serde(default = "default_notify_after_secs")]
+    notify_after_secs: u32,
+    #[serde(default)]
+    key_sends: Vec<KeySend>,
+    #[serde(default)]
+    color_scheme: Option<ColorScheme>,
+    #[serde(default = "default_word_separators")]
+    word_separators: String,
+    #[serde(default = "default_paste_confirm_chars")]
+    paste_confirm_chars: u32,
+    #[serde(default = "default_sftp_parallel")]
+    sftp_parallel: u32,
+    #[serde(default)]
+    sftp_rate_limit_kib: u32,
+    #[serde(default = "default_sftp_retries")]
+    sftp_retries: u32,
+    #[serde(default = "default_sftp_retry_delay_secs")]
+    sftp_retry_delay_secs: u32,
+    #[serde(default)]
+    sftp_download_dir: String,
+    #[serde(default)]
+    sftp_upload_perms: UploadPermissions,
+    #[serde(default)]
+    forward_rules: Vec<ForwardRule>,
 }
 
 #[derive(serde::Serialize, serde::Deserialize)]
@@ -68,6 +104,20 @@ pub fn load_sessions() -> Vec<SessionConfig> {
                 username: s.username,
                 auth_type,
                 proxy,
+                jump_hosts: s.jump_hosts,
+                highlight_rules: s.highlight_rules,
+                notify_after_secs: s.notify_after_secs,
+                key_sends: s.key_sends,
+                color_scheme: s.color_scheme,
+                word_separators: s.word_separators,
+                paste_confirm_chars: s.paste_confirm_chars,
+                sftp_parallel: s.sftp_parallel,
+                sftp_rate_limit_kib: s.sftp_rate_limit_kib,
+                sftp_retries: s.sftp_retries,
+                sftp_retry_delay_secs: s.sftp_retry_delay_secs,
+                sftp_download_dir: s.sftp_download_dir,
+                sftp_upload_perms: s.sftp_upload_perms,
+                forward_rules: s.forward_rules,
                 key_passphrase: None,
             }
         })
@@ -97,6 +147,20 @@ pub fn save_sessions(sessions: &[SessionConfig]) {
                     auth_type,
                     proxy_host,
                     proxy_port,
+                    jump_hosts: s.jump_hosts.clone(),
+                    highlight_rules: s.highlight_rules.clone(),
+                    notify_after_secs: s.notify_after_secs,
+                    key_sends: s.key_sends.clone(),
+                    color_scheme: s.color_scheme.clone(),
+                    word_separators: s.word_separators.clone(),
+                    paste_confirm_chars: s.paste_confirm_chars,
+                    sftp_parallel: s.sftp_parallel,
+                    sftp_rate_limit_kib: s.sftp_rate_limit_kib,
+                    sftp_retries: s.sftp_retries,
+                    sftp_retry_delay_secs: s.sftp_retry_delay_secs,
+                    sftp_download_dir: s.sftp_download_dir.clone(),
+                    sftp_upload_perms: s.sftp_upload_perms,
+                    forward_rules: s.forward_rules.clone(),
                 }
             })
             .collect(),
@@ -0,0 +1,148 @@
+use crate::ssh::sftp::{SftpSettings, TransferRecord};
+use std::collections::HashMap;
+use std::path::PathBuf;
+use std::sync::OnceLock;
+
+fn config_dir() -> PathBuf {
+    let dir = dirs::config_dir()
+        .unwrap_or_else(|| PathBuf::from("."))
+        .join("ssherald");
+    std::fs::create_dir_all(&dir).ok();
+    dir
+}
+
+fn config_path() -> PathBuf {
+    config_dir().join("sftp.json")
+}
+
+/// Последняя открытая папка по id сессии
+fn last_paths_path() -> PathBuf {
+    config_dir().join("sftp_paths.json")
+}
+
+/// Журнал передач всех сессий (последние HISTORY_MAX)
+fn history_path() -> PathBuf {
+    config_dir().join("sftp_history.json")
+}
+
+const HISTORY_MAX: usize = 1000;
+
+/// Копия файла для [open with...]: ssherald/open/<sha256 сессии и пути>/<имя>.
+/// Один и тот же удалённый файл всегда попадает в одно место.
+pub fn open_cache_path(session_id: &str, remote: &str) -> PathBuf {
+    use sha2::Digest;
+    let digest = sha2::Sha256::digest(format!("{}\0{}", session_id, remote).as_bytes());
+    let key: String = digest.iter().take(8).map(|b| format!("{:02x}", b)).collect();
+    let name = remote.rsplit('/').next().filter(|n| !n.is_empty()).unwrap_or("file");
+    dirs::cache_dir()
+        .unwrap_or_else(std::env::temp_dir)
+        .join("ssherald")
+        .join("open")
+        .join(key)
+        .join(name)
+}
+
+/// Буфер обмена для [paste to file...]: ssherald/paste/<время>/<имя>.
+/// Свой каталог на каждую вставку — пока загрузка стоит в очереди, файл не перезапишут.
+pub fn paste_cache_path(name: &str) -> PathBuf {
+    let stamp = std::time::SystemTime::now()
+        .duration_since(std::time::UNIX_EPOCH)
+        .map(|d| d.as_nanos())
+        .unwrap_or_default();
+    dirs::cache_dir()
+        .unwrap_or_else(std::env::temp_dir)
+        .join("ssherald")
+        .join("paste")
+        .join(format!("{:x}", stamp))
+        .join(name)
+}
+
+/// Сортировка и колонки SFTP-браузера. Нет файла — по умолчанию.
+pub fn load_settings() -> SftpSettings {
+    std::fs::read_to_string(config_path())
+        .ok()
+        .and_then(|data| serde_json::from_str(&data).ok())
+        .unwrap_or_default()
+}
+
+/// Файл общий для всех вкладок: перечитываем и меняем только своё поле,
+/// иначе вкладка затрёт то, что сохранила соседняя.
+pub fn update_settings(change: impl FnOnce(&mut SftpSettings)) {
+    let mut settings = load_settings();
+    change(&mut settings);
+    if let Ok(json) = serde_json::to_string_pretty(&settings) {
+        let _ = std::fs::write(config_path(), json);
+    }
+}
+
+fn load_last_paths() -> HashMap<String, String> {
+    std::fs::read_to_string(last_paths_path())
+        .ok()
+        .and_then(|data| serde_json::from_str(&data).ok())
+        .unwrap_or_default()
+}
+
+pub fn load_last_path(session_id: &str) -> Option<String> {
+    load_last_paths().remove(session_id)
+}
+
+pub fn save_last_path(session_id: &str, path: &str) {
+    let mut paths = load_last_paths();
+    paths.insert(session_id.to_string(), path.to_string());
+    if let Ok(json) = serde_json::to_string_pretty(&paths) {
+        let _ = std::fs::write(last_paths_path(), json);
+    }
+}
+
+/// Журнал передач, старые — в начале.
+pub fn load_history() -> Vec<TransferRecord> {
+    std::fs::read_to_string(history_path())
+        .ok()
+        .and_then(|data| serde_json::from_str(&data).ok())
+        .unwrap_or_default()
+}
+
+enum HistoryOp {
+    Append(Vec<TransferRecord>),
+    Clear,
+}
+
+/// Журнал пишет один фоновый поток: UI не ждёт диска, порядок записей сохраняется.
+fn history_writer() -> &'static std::sync::mpsc::Sender<HistoryOp> {
+    static WRITER: OnceLock<std::sync::mpsc::Sender<HistoryOp>> = OnceLock::new();
+    WRITER.get_or_init(|| {
+        let (tx, rx) = std::sync::mpsc::channel();
+        std::thread::spawn(move || {
+            while let Ok(op) = rx.recv() {
+                match op {
+                    HistoryOp::Append(records) => write_history(records),
+                    HistoryOp::Clear => {
+                        let _ = std::fs::remove_file(history_path());
+                    }
+                }
+            }
+        });
+        tx
+    })
+}
+
+fn write_history(records: Vec<TransferRecord>) {
+    let mut history = load_history();
+    history.extend(records);
+    let excess = history.len().saturating_sub(HISTORY_MAX);
+    history.drain(..excess);
+    if let Ok(json) = serde_json::to_string_pretty(&history) {
+        let _ = std::fs::write(history_path(), json);
+    }
+}
+
+/// Дописать пачку записей за один проход по файлу.
+pub fn append_history(records: Vec<TransferRecord>) {
+    if !records.is_empty() {
+        let _ = history_writer().send(HistoryOp::Append(records));
+    }
+}
+
+pub fn clear_history() {
+    let _ = history_writer().send(HistoryOp::Clear);
+}
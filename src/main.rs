@@ -2,6 +2,7 @@
 
 mod app;
 mod config;
+mod notify;
 mod ssh;
 mod terminal;
 mod theme;
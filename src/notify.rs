@@ -0,0 +1,146 @@
+//! Уведомления: всплывающие toast'ы внутри окна + best-effort
+//! системное уведомление (notify-send / osascript / PowerShell).
+
+const TOAST_LIFETIME: f64 = 6.0;
+const MAX_TOASTS: usize = 5;
+
+struct Toast {
+    title: String,
+    body: String,
+    created: f64,
+}
+
+#[derive(Default)]
+pub struct Toasts {
+    items: Vec<Toast>,
+}
+
+impl Toasts {
+    pub fn push(&mut self, ctx: &egui::Context, title: &str, body: &str) {
+        let now = ctx.input(|i| i.time);
+        self.items.push(Toast {
+            title: title.to_string(),
+            body: body.to_string(),
+            created: now,
+        });
+        if self.items.len() > MAX_TOASTS {
+            self.items.remove(0);
+        }
+    }
+
+    pub fn show(&mut self, ctx: &egui::Context) {
+        let now = ctx.input(|i| i.time);
+        self.items.retain(|t| now - t.created < TOAST_LIFETIME);
+        if self.items.is_empty() {
+            return;
+        }
+
+        let mut dismiss: Option<usize> = None;
+        egui::Area::new(egui::Id::new("toasts"))
+            .anchor(egui::Align2::RIGHT_BOTTOM, egui::vec2(-12.0, -12.0))
+            .order(egui::Order::Foreground)
+            .show(ctx, |ui| {
+                for (idx, toast) in self.items.iter().enumerate() {
+                    egui::Frame::none()
+                        .fill(crate::theme::BG_PANEL)
+                        .stroke(egui::Stroke::new(1.0, crate::theme::AMBER))
+                        .inner_margin(egui::Margin::same(8.0))
+                        .show(ui, |ui| {
+                            ui.set_max_width(360.0);
+                            ui.horizontal(|ui| {
+                                ui.colored_label(crate::theme::AMBER, &toast.title);
+                                ui.with_layout(
+                                    egui::Layout::right_to_left(egui::Align::Center),
+                                    |ui| {
+                                        if ui.small_button("x").clicked() {
+                                            dismiss = Some(idx);
+                                        }
+                                    },
+                                );
+                            });
+                            if !toast.body.is_empty() {
+                                ui.colored_label(crate::theme::GREEN_DIM, &toast.body);
+                            }
+                        });
+                    ui.add_space(4.0);
+                }
+            });
+
+        if let Some(idx) = dismiss {
+            self.items.remove(idx);
+        }
+        ctx.request_repaint_after(std::time::Duration::from_millis(500));
+    }
+}
+
+/// Системное уведомление. Ошибки игнорируются — toast внутри окна
+/// показывается в любом случае.
+pub fn desktop(title: &str, body: &str) {
+    #[cfg(target_os = "linux")]
+    let cmd = {
+        let mut c = std::process::Command::new("notify-send");
+        c.args(["--app-name=SSHerald", title, body]);
+        c
+    };
+    #[cfg(target_os = "macos")]
+    let cmd = {
+        let script = format!(
+            "display notification \"{}\" with title \"{}\"",
+            body.replace('"', "'"),
+            title.replace('"', "'")
+        );
+        let mut c = std::process::Command::new("osascript");
+        c.args(["-e", &script]);
+        c
+    };
+    #[cfg(windows)]
+    let cmd = {
+        use std::os::windows::process::CommandExt;
+        const CREATE_NO_WINDOW: u32 = 0x0800_0000;
+        let script = format!(
+            "[reflection.assembly]::loadwithpartialname('System.Windows.Forms') | Out-Null; \
+             $n = New-Object System.Windows.Forms.NotifyIcon; \
+             $n.Icon = [System.Drawing.SystemIcons]::Information; $n.Visible = $true; \
+             $n.ShowBalloonTip(5000, '{}', '{}', 'Info'); Start-Sleep -Seconds 6; $n.Dispose()",
+            title.replace('\'', "''"),
+            body.replace('\'', "''")
+        );
+        let mut c = std::process::Command::new("powershell");
+        c.args(["-NoProfile", "-WindowStyle", "Hidden", "-Command", &script]);
+        c.creation_flags(CREATE_NO_WINDOW);
+        c
+    };
+    #[cfg(any(target_os = "linux", target_os = "macos", windows))]
+    {
+        let mut cmd = cmd;
+        let spawned = cmd
+            .stdin(std::process::Stdio::null())
+            .stdout(std::process::Stdio::null())
+            .stderr(std::process::Stdio::null())
+            .spawn();
+        // Ждём в стороне, чтобы не копить зомби
+        if let Ok(mut child) = spawned {
+            std::thread::spawn(move || {
+                let _ = child.wait();
+            });
+        }
+    }
+    #[cfg(not(any(target_os = "linux", target_os = "macos", windows)))]
+    {
+        let _ = (title, body);
+    }
+}
+
+/// Длительность для людей: `850ms`, `12.3s`, `4m 05s`, `1h 02m`.
+pub fn format_duration(duration: std::time::Duration) -> String {
+    let secs = duration.as_secs();
+    if secs >= 3600 {
+        format!("{}h {:02}m", secs / 3600, secs % 3600 / 60)
+    } else if secs >= 60 {
+        format!("{}m {:02}s", secs / 60, secs % 60)
+    } else if secs >= 1 {
+        format!("{:.1}s", duration.as_secs_f32())
+    } else {
+        format!("{}ms", duration.as_millis())
+    }
+}
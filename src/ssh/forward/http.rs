@@ -0,0 +1,205 @@
+use super::*;
+
+// ── HTTP-прокси ──
+
+/// Предел заголовков запроса к прокси
+const HTTP_HEAD_LIMIT: usize = 16 * 1024;
+
+pub(super) async fn run_http_proxy_async(
+    config: &SessionConfig,
+    rule: &ForwardRule,
+    alive: &AtomicBool,
+    paused: &AtomicBool,
+    stats: &Arc<ForwardStats>,
+) -> Result<(), Box<dyn std::error::Error + Send + Sync>> {
+    let listener = bind_local(rule, stats).await?;
+    let session = Arc::new(create_russh_session(config, SshHandler::new()).await?);
+
+    while alive.load(Ordering::Relaxed) {
+        let accept = tokio::time::timeout(std::time::Duration::from_millis(500), listener.accept())
+            .await;
+
+        match accept {
+            Ok(Ok(_)) if paused.load(Ordering::Relaxed) => continue, // закрываем сразу
+            Ok(Ok((stream, peer))) => {
+                let session = session.clone();
+                let conn = stats.open(peer.to_string(), String::new());
+
+                tokio::spawn(async move {
+                    let result = tokio::select! {
+                        r = handle_http_client(session, stream, &conn) => r,
+                        _ = conn.live.kill.notified() => Ok(()),
+                    };
+                    if let Err(e) = result {
+                        conn.log_error(e);
+                    }
+                });
+            }
+            Ok(Err(_)) => break,
+            Err(_) => continue,
+        }
+    }
+
+    Ok(())
+}
+
+/// Заголовки запроса до пустой строки и то, что клиент успел прислать после них.
+/// Заголовки — байтами: значения не обязаны быть UTF-8.
+async fn read_http_head(
+    stream: &mut tokio::net::TcpStream,
+) -> Result<(Vec<u8>, Vec<u8>), Box<dyn std::error::Error + Send + Sync>> {
+    let mut buf = Vec::with_capacity(1024);
+    let mut chunk = [0u8; 4096];
+    loop {
+        if let Some(pos) = buf.windows(4).position(|w| w == b"\r\n\r\n") {
+            let rest = buf.split_off(pos + 4);
+            buf.truncate(pos);
+            return Ok((buf, rest));
+        }
+        if buf.len() > HTTP_HEAD_LIMIT {
+            return Err("HTTP: слишком длинные заголовки".into());
+        }
+        let n = stream.read(&mut chunk).await?;
+        if n == 0 {
+            return Err("HTTP: соединение закрыто до конца заголовков".into());
+        }
+        buf.extend_from_slice(&chunk[..n]);
+    }
+}
+
+/// "host:port", "[::1]:port" или "host" с портом по умолчанию.
+fn split_authority(authority: &str, default_port: u16) -> Option<(String, u16)> {
+    let (host, port) = if let Some(rest) = authority.strip_prefix('[') {
+        let (host, tail) = rest.split_once(']')?;
+        match tail.strip_prefix(':') {
+            Some(port) => (host, port.parse().ok()?),
+            None if tail.is_empty() => (host, default_port),
+            None => return None,
+        }
+    } else {
+        match authority.rsplit_once(':') {
+            Some((host, port)) => (host, port.parse().ok()?),
+            None => (authority, default_port),
+        }
+    };
+    (!host.is_empty()).then(|| (host.to_string(), port))
+}
+
+async fn http_error(stream: &mut tokio::net::TcpStream, status: &str) -> std::io::Result<()> {
+    let reply = format!(
+        "HTTP/1.1 {}\r\nContent-Length: 0\r\nConnection: close\r\n\r\n",
+        status
+    );
+    stream.write_all(reply.as_bytes()).await
+}
+
+/// Запрос к HTTP-прокси: CONNECT host:port — туннель,
+/// METHOD http://host/path — запрос пересылается серверу в обычной форме.
+async fn handle_http_client(
+    session: Arc<russh::client::Handle<SshHandler>>,
+    mut stream: tokio::net::TcpStream,
+    conn: &ConnectionGuard,
+) -> Result<(), Box<dyn std::error::Error + Send + Sync>> {
+    let (head, rest) = tokio::time::timeout(HANDSHAKE_TIMEOUT, read_http_head(&mut stream))
+        .await
+        .map_err(|_| "HTTP: клиент не прислал запрос целиком")??;
+    let mut lines = head.split(|&b| b == b'\n').map(|l| l.strip_suffix(b"\r").unwrap_or(l));
+    // Строка запроса — ASCII; заголовки дальше пересылаются как есть
+    let request_line = std::str::from_utf8(lines.next().unwrap_or_default()).unwrap_or_default();
+    let mut parts = request_line.split_whitespace();
+    let (Some(method), Some(target), Some(version)) = (parts.next(), parts.next(), parts.next())
+    else {
+        http_error(&mut stream, "400 Bad Request").await?;
+        return Err("HTTP: неверная строка запроса".into());
+    };
+
+    // OPTIONS * — вопрос к самому прокси ([test]), дальше не пересылается
+    if method.eq_ignore_ascii_case("OPTIONS") && target == "*" {
+        conn.set_destination("OPTIONS *".to_string());
+        stream
+            .write_all(b"HTTP/1.1 200 OK\r\nAllow: CONNECT, GET, HEAD, POST, PUT, DELETE\r\n\
+                Content-Length: 0\r\nConnection: close\r\n\r\n")
+            .await?;
+        return Ok(());
+    }
+
+    let connect = method.eq_ignore_ascii_case("CONNECT");
+    let (authority, path) = if connect {
+        (target, "")
+    } else {
+        let Some(url) = target
+            .get(..7)
+            .filter(|scheme| scheme.eq_ignore_ascii_case("http://"))
+            .map(|_| &target[7..])
+        else {
+            http_error(&mut stream, "400 Bad Request").await?;
+            return Err(format!("HTTP: поддерживаются только http:// URI, не {}", target).into());
+        };
+        match url.find('/') {
+            Some(i) => (&url[..i], &url[i..]),
+            None => (url, "/"),
+        }
+    };
+    let Some((host, port)) = split_authority(authority, if connect { 443 } else { 80 }) else {
+        http_error(&mut stream, "400 Bad Request").await?;
+        return Err(format!("HTTP: неверный адрес {}", authority).into());
+    };
+    conn.set_destination(format!("{}:{}", host, port));
+
+    let channel = match session
+        .channel_open_direct_tcpip(&host, port as u32, "127.0.0.1", 0)
+        .await
+    {
+        Ok(ch) => ch,
+        Err(e) => {
+            http_error(&mut stream, "502 Bad Gateway").await?;
+            return Err(format!("SSH direct-tcpip к {}:{} не удался: {}", host, port, e).into());
+        }
+    };
+
+    if connect {
+        stream
+            .write_all(b"HTTP/1.1 200 Connection established\r\n\r\n")
+            .await?;
+        relay_prefixed(channel, stream, &rest, conn).await;
+        return Ok(());
+    }
+
+    // Заголовки прокси и hop-by-hop серверу не нужны; соединение — на один запрос,
+    // иначе следующий запрос клиента мог бы быть к другому хосту
+    let headers: Vec<(&[u8], &[u8])> = lines
+        .filter(|l| !l.is_empty())
+        .map(|line| {
+            let colon = line.iter().position(|&b| b == b':').unwrap_or(line.len());
+            (line[..colon].trim_ascii(), line)
+        })
+        .collect();
+    // Заголовки, которые клиент сам назвал в Connection, тоже только до прокси
+    let listed: Vec<Vec<u8>> = headers
+        .iter()
+        .filter(|(name, _)| name.eq_ignore_ascii_case(b"connection"))
+        .flat_map(|(name, line)| line[name.len()..].split(|&b| b == b',' || b == b':'))
+        .map(|token| token.trim_ascii().to_ascii_lowercase())
+        .filter(|token| !token.is_empty())
+        .collect();
+    let mut prefix = format!("{} {} {}\r\n", method, path, version).into_bytes();
+    for (name, line) in headers {
+        let proxy_header = name
+            .get(..6)
+            .is_some_and(|p| p.eq_ignore_ascii_case(b"proxy-"));
+        let hop_by_hop = [&b"connection"[..], b"keep-alive"]
+            .iter()
+            .any(|h| name.eq_ignore_ascii_case(h))
+            || listed.iter().any(|h| name.eq_ignore_ascii_case(h));
+        if proxy_header || hop_by_hop {
+            continue;
+        }
+        prefix.extend_from_slice(line);
+        prefix.extend_from_slice(b"\r\n");
+    }
+    prefix.extend_from_slice(b"Connection: close\r\n\r\n");
+    prefix.extend_from_slice(&rest);
+
+    relay_prefixed(channel, stream, &prefix, conn).await;
+    Ok(())
+}
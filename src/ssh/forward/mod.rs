@@ -0,0 +1,3052 @@
+use crate::ssh::session::{create_russh_session, ForwardedChannel, SessionConfig, SshHandler};
+use crate::ssh::sftp::{format_local, format_size, RateLimiter};
+use serde::{Deserialize, Serialize};
+use std::collections::{HashMap, VecDeque};
+use std::net::{IpAddr, SocketAddr};
+use std::path::{Path, PathBuf};
+use std::sync::atomic::{AtomicBool, AtomicU64, AtomicUsize, Ordering};
+use std::sync::Arc;
+use std::time::{Duration, Instant};
+
+use tokio::io::{AsyncReadExt, AsyncWriteExt};
+
+mod http;
+mod socks;
+
+use http::run_http_proxy_async;
+use socks::run_dynamic_forward_async;
+
+/// Сколько клиент SOCKS/HTTP-прокси может тянуть с приветствием и запросом
+const HANDSHAKE_TIMEOUT: Duration = Duration::from_secs(30);
+
+// ── Типы ──
+
+#[derive(Clone, PartialEq, Serialize, Deserialize)]
+pub enum ForwardType {
+    Local,
+    Remote,
+    Dynamic,
+    /// Локальный HTTP-прокси (CONNECT и GET с абсолютным URI)
+    Http,
+}
+
+impl ForwardType {
+    /// Что проверяет [test]
+    fn probe_hint(&self) -> &'static str {
+        match self {
+            ForwardType::Local => "connect through the tunnel to the destination",
+            ForwardType::Remote => "connect to the local target the server forwards to",
+            ForwardType::Dynamic => "SOCKS5 greeting and login, no destination is contacted",
+            ForwardType::Http => "OPTIONS * to the proxy itself, no destination is contacted",
+        }
+    }
+
+    /// Ключ ssh и цвет в таблицах
+    pub fn flag(&self) -> (&'static str, egui::Color32) {
+        match self {
+            ForwardType::Local => ("-L", crate::theme::GREEN),
+            ForwardType::Remote => ("-R", crate::theme::AMBER),
+            ForwardType::Dynamic => ("-D", crate::theme::CYAN),
+            ForwardType::Http => ("HTTP", crate::theme::MAGENTA),
+        }
+    }
+}
+
+/// Правило перенаправления; хранится в настройках сессии.
+#[derive(Clone, Serialize, Deserialize)]
+pub struct ForwardRule {
+    /// Подпись пользователя: «prod DB», «grafana»; пустая — правило видно по адресам
+    #[serde(default)]
+    pub label: String,
+    pub forward_type: ForwardType,
+    pub local_host: String,
+    pub local_port: u16,
+    pub remote_host: String,
+    pub remote_port: u16,
+    /// Запускать сразу при подключении сессии
+    #[serde(default)]
+    pub auto_start: bool,
+    /// Логин и пароль SOCKS5 (RFC 1929) для -D; None — без аутентификации
+    #[serde(default)]
+    pub socks_auth: Option<SocksAuth>,
+    /// Закрывать соединения без трафика через столько минут; 0 — не закрывать
+    #[serde(default)]
+    pub idle_minutes: u32,
+    /// По тому же тайм-ауту останавливать и само правило, если соединений нет
+    #[serde(default)]
+    pub idle_stop: bool,
+    /// Предел скорости в каждую сторону на все соединения правила, KiB/s; 0 — нет
+    #[serde(default)]
+    pub rate_limit_kib: u32,
+    /// Адрес из exposed_address(), доступ к которому пользователь подтвердил;
+    /// другой адрес (правило изменили или импортировали) спрашивается заново
+    #[serde(default)]
+    pub exposure_confirmed: Option<String>,
+}
+
+/// Именованный шаблон правила — общий для всех сессий.
+#[derive(Clone, Serialize, Deserialize)]
+pub struct ForwardPreset {
+    pub name: String,
+    pub rule: ForwardRule,
+}
+
+/// Настройки перенаправлений, общие для всех сессий.
+#[derive(Clone, Default, Serialize, Deserialize)]
+pub struct ForwardSettings {
+    /// Не запускать правила, доступные с других машин
+    #[serde(default)]
+    pub loopback_only: bool,
+}
+
+#[derive(Clone, PartialEq, Serialize, Deserialize)]
+pub struct SocksAuth {
+    pub user: String,
+    /// Не сохраняется (как и пароль сессии); пустой — спрашивается при запуске
+    #[serde(skip)]
+    pub password: String,
+}
+
+impl ForwardRule {
+    pub fn idle_timeout(&self) -> Option<Duration> {
+        (self.idle_minutes > 0).then(|| Duration::from_secs(self.idle_minutes as u64 * 60))
+    }
+
+    /// -R слушает на всех интерфейсах сервера, а не только на loopback.
+    pub fn remote_public(&self) -> bool {
+        matches!(self.remote_host.as_str(), "" | "*" | "0.0.0.0" | "::")
+    }
+
+    /// Где туннель доступен с других машин; None — только loopback.
+    /// Любой адрес, кроме loopback, — наружу: и 0.0.0.0, и адрес в локальной сети.
+    pub fn exposed_address(&self) -> Option<String> {
+        let loopback = |host: &str| {
+            let host = host.trim_start_matches('[').trim_end_matches(']');
+            host.eq_ignore_ascii_case("localhost")
+                || host.parse::<IpAddr>().is_ok_and(|ip| ip.is_loopback())
+        };
+        if self.forward_type == ForwardType::Remote {
+            if self.remote_public() {
+                return Some(format!("all server interfaces, port {}", self.remote_port));
+            }
+            return (!loopback(&self.remote_host))
+                .then(|| format!("{}:{} on the server", self.remote_host, self.remote_port));
+        }
+        (!loopback(&self.local_host)).then(|| format!("{}:{}", self.local_host, self.local_port))
+    }
+
+    /// Подпись и адреса — для заголовков и сообщений.
+    pub fn title(&self) -> String {
+        if self.label.is_empty() {
+            self.endpoints()
+        } else {
+            format!("{} ({})", self.label, self.endpoints())
+        }
+    }
+
+    /// "127.0.0.1:8080 -> db:5432" — одной строкой для общего списка.
+    pub fn endpoints(&self) -> String {
+        let local = format!("{}:{}", self.local_host, self.local_port);
+        let remote = format!("{}:{}", self.remote_host, self.remote_port);
+        match self.forward_type {
+            ForwardType::Local => format!("{} -> {}", local, remote),
+            ForwardType::Remote => format!("{} <- {}", local, remote),
+            ForwardType::Dynamic if self.socks_auth.is_some() => {
+                format!("socks5 {} (login)", local)
+            }
+            ForwardType::Dynamic => format!("socks5 {}", local),
+            ForwardType::Http => format!("http proxy {}", local),
+        }
+    }
+}
+
+// ── Разбор строк OpenSSH ──
+
+/// То же правило командой ssh: `ssh -N -L ... user@host`. Для HTTP-прокси аналога нет.
+/// Логин SOCKS в команду не попадает: у `ssh -D` его нет.
+pub fn ssh_command(config: &SessionConfig, rule: &ForwardRule) -> Option<String> {
+    let addr = |host: &str, port: u16| {
+        if host.contains(':') {
+            format!("[{}]:{}", host, port)
+        } else {
+            format!("{}:{}", host, port)
+        }
+    };
+    let local = addr(&rule.local_host, rule.local_port);
+    let remote = addr(&rule.remote_host, rule.remote_port);
+    let (flag, spec) = match rule.forward_type {
+        ForwardType::Local => ("-L", format!("{}:{}", local, remote)),
+        ForwardType::Remote => ("-R", format!("{}:{}", remote, local)),
+        ForwardType::Dynamic => ("-D", local),
+        ForwardType::Http => return None,
+    };
+    let mut args = vec!["ssh".to_string(), "-N".to_string(), flag.to_string(), spec];
+    if !config.jump_hosts.is_empty() {
+        let hops = crate::ssh::session::JumpHost::format_list(&config.jump_hosts);
+        args.push("-J".to_string());
+        args.push(hops.replace(' ', ""));
+    }
+    if let Some(proxy) = &config.proxy {
+        args.push("-o".to_string());
+        args.push(format!(
+            "ProxyCommand=nc -X 5 -x {} %h %p",
+            addr(&proxy.host, proxy.port)
+        ));
+    }
+    if config.port != 22 {
+        args.push("-p".to_string());
+        args.push(config.port.to_string());
+    }
+    args.push(format!("{}@{}", config.username, config.host));
+    Some(args.iter().map(|a| shell_arg(a)).collect::<Vec<_>>().join(" "))
+}
+
+/// Аргумент для командной строки: в кавычках, только если в нём есть что-то кроме
+/// безопасных символов.
+fn shell_arg(arg: &str) -> String {
+    let safe = |c: char| c.is_ascii_alphanumeric() || "@%+=:,./_-".contains(c);
+    if !arg.is_empty() && arg.chars().all(safe) {
+        arg.to_string()
+    } else {
+        crate::ssh::sftp::shell_quote(arg)
+    }
+}
+
+// ── Браузер через SOCKS ──
+
+#[derive(Clone, Copy)]
+enum Browser {
+    Chromium,
+    Firefox,
+}
+
+impl Browser {
+    fn name(self) -> &'static str {
+        match self {
+            Browser::Chromium => "chrome",
+            Browser::Firefox => "firefox",
+        }
+    }
+
+    /// Где искать исполняемый файл; пробуем по порядку.
+    fn candidates(self) -> Vec<PathBuf> {
+        let names: &[&str] = match self {
+            #[cfg(target_os = "macos")]
+            Browser::Chromium => &[
+                "/Applications/Google Chrome.app/Contents/MacOS/Google Chrome",
+                "/Applications/Chromium.app/Contents/MacOS/Chromium",
+                "/Applications/Brave Browser.app/Contents/MacOS/Brave Browser",
+                "/Applications/Microsoft Edge.app/Contents/MacOS/Microsoft Edge",
+            ],
+            #[cfg(target_os = "macos")]
+            Browser::Firefox => &["/Applications/Firefox.app/Contents/MacOS/firefox"],
+            #[cfg(windows)]
+            Browser::Chromium => &[
+                r"Google\Chrome\Application\chrome.exe",
+                r"Microsoft\Edge\Application\msedge.exe",
+                r"BraveSoftware\Brave-Browser\Application\brave.exe",
+            ],
+            #[cfg(windows)]
+            Browser::Firefox => &[r"Mozilla Firefox\firefox.exe"],
+            #[cfg(not(any(target_os = "macos", windows)))]
+            Browser::Chromium => &[
+                "google-chrome",
+                "chromium",
+                "chromium-browser",
+                "brave-browser",
+                "microsoft-edge",
+            ],
+            #[cfg(not(any(target_os = "macos", windows)))]
+            Browser::Firefox => &["firefox"],
+        };
+        // На Windows браузеры не в PATH — ищем в Program Files
+        #[cfg(windows)]
+        {
+            let roots: Vec<PathBuf> = ["ProgramFiles", "ProgramFiles(x86)", "LOCALAPPDATA"]
+                .iter()
+                .filter_map(|var| std::env::var_os(var).map(PathBuf::from))
+                .collect();
+            names
+                .iter()
+                .flat_map(|name| roots.iter().map(move |root| root.join(name)))
+                .collect()
+        }
+        #[cfg(not(windows))]
+        names.iter().map(PathBuf::from).collect()
+    }
+
+    /// Аргументы запуска с отдельным профилем; для Firefox профиль готовится заранее.
+    fn args(self, profile: &Path, rule: &ForwardRule) -> std::io::Result<Vec<String>> {
+        let profile = profile.to_string_lossy().to_string();
+        let host = proxy_connect_host(rule);
+        match self {
+            Browser::Chromium => Ok(vec![
+                format!("--user-data-dir={}", profile),
+                format!("--proxy-server=socks5://{}", proxy_authority(rule)),
+                // Имена — только через прокси, без локального DNS
+                format!("--host-resolver-rules=MAP * ~NOTFOUND , EXCLUDE {}", host),
+                "--no-first-run".to_string(),
+            ]),
+            Browser::Firefox => {
+                let prefs = format!(
+                    "user_pref(\"network.proxy.type\", 1);\n\
+                     user_pref(\"network.proxy.socks\", \"{}\");\n\
+                     user_pref(\"network.proxy.socks_port\", {});\n\
+                     user_pref(\"network.proxy.socks_version\", 5);\n\
+                     user_pref(\"network.proxy.socks_remote_dns\", true);\n\
+                     user_pref(\"network.proxy.no_proxies_on\", \"\");\n\
+                     user_pref(\"network.trr.mode\", 5);\n\
+                     user_pref(\"browser.shell.checkDefaultBrowser\", false);\n",
+                    host, rule.local_port
+                );
+                std::fs::create_dir_all(&profile)?;
+                std::fs::write(Path::new(&profile).join("user.js"), prefs)?;
+                Ok(vec!["-no-remote".to_string(), "-profile".to_string(), profile])
+            }
+        }
+    }
+}
+
+/// Адрес, по которому подключаться к локальному слушателю: 0.0.0.0 — это loopback.
+fn proxy_connect_host(rule: &ForwardRule) -> String {
+    match rule.local_host.as_str() {
+        "" | "*" | "0.0.0.0" => "127.0.0.1".to_string(),
+        "::" => "::1".to_string(),
+        host => host.trim_start_matches('[').trim_end_matches(']').to_string(),
+    }
+}
+
+/// host:port для URL прокси; IPv6 — в скобках.
+fn proxy_authority(rule: &ForwardRule) -> String {
+    let host = proxy_connect_host(rule);
+    if host.contains(':') {
+        format!("[{}]:{}", host, rule.local_port)
+    } else {
+        format!("{}:{}", host, rule.local_port)
+    }
+}
+
+/// Отдельный профиль браузера на порт прокси: свои настройки, не трогает основной.
+fn launch_browser(browser: Browser, rule: &ForwardRule) -> Result<(), String> {
+    let profile = crate::config::forwards::browser_profile_path(browser.name(), rule.local_port);
+    let args = browser
+        .args(&profile, rule)
+        .map_err(|e| format!("{} profile: {}", browser.name(), e))?;
+    for exe in browser.candidates() {
+        let spawned = std::process::Command::new(&exe)
+            .args(&args)
+            .stdin(std::process::Stdio::null())
+            .stdout(std::process::Stdio::null())
+            .stderr(std::process::Stdio::null())
+            .spawn();
+        if let Ok(mut child) = spawned {
+            // Браузер живёт дольше кнопки: ждём его в стороне, чтобы не копить зомби
+            std::thread::spawn(move || {
+                let _ = child.wait();
+            });
+            return Ok(());
+        }
+    }
+    Err(format!("{} not found", browser.name()))
+}
+
+/// Строка для ручного запуска Chrome с прокси.
+fn chrome_command(rule: &ForwardRule) -> String {
+    format!(
+        "google-chrome --user-data-dir=\"{}\" --proxy-server=\"socks5://{}\" \
+         --host-resolver-rules=\"MAP * ~NOTFOUND , EXCLUDE {}\"",
+        crate::config::forwards::browser_profile_path("chrome", rule.local_port).display(),
+        proxy_authority(rule),
+        proxy_connect_host(rule)
+    )
+}
+
+/// Правила из текста в духе OpenSSH: ключи `-L`, `-R`, `-D` (слитно, через пробел или
+/// в связке вроде `-fNL`) и строки `LocalForward`, `RemoteForward`, `DynamicForward`
+/// из ssh_config. Прочие слова (`ssh`, `user@host`) и строки-комментарии `#`
+/// пропускаются, поэтому можно вставить команду или кусок конфига целиком.
+pub fn parse_forward_specs(text: &str) -> Result<Vec<ForwardRule>, String> {
+    let mut rules = Vec::new();
+    let mut tokens = text
+        .lines()
+        .filter(|line| !line.trim_start().starts_with('#'))
+        .flat_map(str::split_whitespace);
+    while let Some(token) = tokens.next() {
+        let (forward_type, spec) = match token {
+            _ if token.len() > 1 && token.starts_with('-') && !token.starts_with("--") => {
+                // Связка коротких ключей, как у getopt: ключ с аргументом забирает
+                // остаток слова или следующее слово
+                let mut found = None;
+                for (i, flag) in token.char_indices().skip(1) {
+                    if !SSH_FLAGS_WITH_ARG.contains(flag) {
+                        continue;
+                    }
+                    let rest = &token[i + flag.len_utf8()..];
+                    let arg = match rest {
+                        "" => tokens.next(),
+                        rest => Some(rest),
+                    };
+                    if matches!(flag, 'L' | 'R' | 'D') {
+                        let spec = arg.ok_or_else(|| format!("-{}: no forward spec", flag))?;
+                        found = Some((spec_type(flag), spec.to_string()));
+                    }
+                    break;
+                }
+                match found {
+                    Some(found) => found,
+                    None => continue,
+                }
+            }
+            _ if token.eq_ignore_ascii_case("DynamicForward") => {
+                let spec = tokens.next().ok_or("DynamicForward: no port")?;
+                (ForwardType::Dynamic, spec.to_string())
+            }
+            _ if token.eq_ignore_ascii_case("LocalForward")
+                || token.eq_ignore_ascii_case("RemoteForward") =>
+            {
+                let (Some(listen), Some(dest)) = (tokens.next(), tokens.next()) else {
+                    return Err(format!("{}: expected listen and destination", token));
+                };
+                let forward_type = if token.eq_ignore_ascii_case("LocalForward") {
+                    ForwardType::Local
+                } else {
+                    ForwardType::Remote
+                };
+                (forward_type, format!("{}:{}", listen, dest))
+            }
+            _ => continue,
+        };
+        rules.push(parse_forward_spec(forward_type, &spec)?);
+    }
+    if rules.is_empty() {
+        return Err("no -L/-R/-D forwards found".to_string());
+    }
+    Ok(rules)
+}
+
+/// Ключи ssh с аргументом (из getopt-строки OpenSSH): в связке `-fNL 8080:h:80`
+/// на них связка кончается.
+const SSH_FLAGS_WITH_ARG: &str = "bBcDeEFiIJlLmoOpPQRSwW";
+
+fn spec_type(flag: char) -> ForwardType {
+    match flag {
+        'L' => ForwardType::Local,
+        'R' => ForwardType::Remote,
+        _ => ForwardType::Dynamic,
+    }
+}
+
+/// `[bind:]port:host:hostport` для -L/-R, `[bind:]port` для -D.
+fn parse_forward_spec(forward_type: ForwardType, spec: &str) -> Result<ForwardRule, String> {
+    let fields = split_spec(spec).ok_or_else(|| format!("{}: unbalanced [ ]", spec))?;
+    let port = |s: &str| {
+        s.parse::<u16>()
+            .map_err(|_| format!("{}: bad port '{}'", spec, s))
+    };
+    // Пустой адрес и * у OpenSSH — все интерфейсы
+    let bind = |s: &str| match s {
+        "" | "*" => "0.0.0.0".to_string(),
+        _ => s.to_string(),
+    };
+    let fields: Vec<&str> = fields.iter().map(String::as_str).collect();
+
+    let mut rule = ForwardRule {
+        label: String::new(),
+        forward_type: forward_type.clone(),
+        local_host: "127.0.0.1".to_string(),
+        local_port: 0,
+        remote_host: "localhost".to_string(),
+        remote_port: 0,
+        auto_start: false,
+        socks_auth: None,
+        idle_minutes: 0,
+        idle_stop: false,
+        rate_limit_kib: 0,
+        exposure_confirmed: None,
+    };
+    match (forward_type, fields.as_slice()) {
+        (ForwardType::Dynamic, [listen_port]) => rule.local_port = port(listen_port)?,
+        (ForwardType::Dynamic, [listen_host, listen_port]) => {
+            rule.local_host = bind(listen_host);
+            rule.local_port = port(listen_port)?;
+        }
+        (ForwardType::Local, [listen @ .., host, host_port]) if listen.len() <= 2 => {
+            if let [listen_host, _] = listen {
+                rule.local_host = bind(listen_host);
+            }
+            rule.local_port = port(listen.last().ok_or_else(|| format!("{}: no port", spec))?)?;
+            rule.remote_host = host.to_string();
+            rule.remote_port = port(host_port)?;
+        }
+        // -R слушает на сервере: там remote_*, а host:hostport — на нашей стороне
+        (ForwardType::Remote, [listen @ .., host, host_port]) if listen.len() <= 2 => {
+            if let [listen_host, _] = listen {
+                rule.remote_host = bind(listen_host);
+            }
+            rule.remote_port = port(listen.last().ok_or_else(|| format!("{}: no port", spec))?)?;
+            rule.local_host = host.to_string();
+            rule.local_port = port(host_port)?;
+        }
+        _ => return Err(format!("{}: unrecognized forward spec", spec)),
+    }
+    Ok(rule)
+}
+
+/// Делит по ':', адреса в [ ] (IPv6) — целиком и без скобок.
+fn split_spec(spec: &str) -> Option<Vec<String>> {
+    let mut fields = vec![String::new()];
+    let mut bracket = false;
+    for c in spec.chars() {
+        match c {
+            '[' if !bracket => bracket = true,
+            ']' if bracket => bracket = false,
+            ':' if !bracket => fields.push(String::new()),
+            _ => fields.last_mut()?.push(c),
+        }
+    }
+    (!bracket).then_some(fields)
+}
+
+/// Строка общего обзора перенаправлений всех сессий.
+pub struct ForwardSummary {
+    pub index: usize,
+    pub rule: ForwardRule,
+    pub running: bool,
+    pub paused: bool,
+    /// Последняя ошибка, пока правило ждёт перезапуска
+    pub failed: Option<String>,
+    pub connections: usize,
+}
+
+// ── Журнал событий правила ──
+
+/// Сколько последних событий хранит журнал правила
+const LOG_LEN: usize = 200;
+
+#[derive(Clone, Copy, PartialEq)]
+enum EventLevel {
+    Info,
+    Conn,
+    Error,
+}
+
+#[derive(Clone)]
+struct ForwardEvent {
+    /// Unix-время, сек
+    time: u64,
+    level: EventLevel,
+    text: String,
+}
+
+/// События правила: запуск, соединения, ошибки. Общий для всех запусков правила.
+#[derive(Default)]
+struct ForwardLog {
+    events: parking_lot::Mutex<VecDeque<ForwardEvent>>,
+}
+
+impl ForwardLog {
+    fn push(&self, level: EventLevel, text: impl Into<String>) {
+        let time = std::time::SystemTime::now()
+            .duration_since(std::time::UNIX_EPOCH)
+            .map(|d| d.as_secs())
+            .unwrap_or_default();
+        let mut events = self.events.lock();
+        if events.len() == LOG_LEN {
+            events.pop_front();
+        }
+        events.push_back(ForwardEvent {
+            time,
+            level,
+            text: text.into(),
+        });
+    }
+
+    fn show(&self, ui: &mut egui::Ui) {
+        // Копия: потоки соединений пишут в журнал, пока он рисуется
+        let events: Vec<ForwardEvent> = self.events.lock().iter().cloned().collect();
+        if events.is_empty() {
+            ui.colored_label(crate::theme::GREEN_DIM, "// no events");
+            return;
+        }
+        egui::ScrollArea::vertical()
+            .max_height(160.0)
+            .stick_to_bottom(true)
+            .auto_shrink([false, true])
+            .show(ui, |ui| {
+                for event in events.iter() {
+                    let color = match event.level {
+                        EventLevel::Info => crate::theme::GREEN,
+                        EventLevel::Conn => crate::theme::GREEN_DIM,
+                        EventLevel::Error => crate::theme::RED,
+                    };
+                    ui.horizontal(|ui| {
+                        ui.monospace(format_local(event.time, "%H:%M:%S"))
+                            .on_hover_text(format_local(event.time, "%Y-%m-%d %H:%M:%S %:z"));
+                        ui.colored_label(color, &event.text);
+                    });
+                }
+            });
+    }
+}
+
+// ── Активное перенаправление ──
+
+/// Счётчики потока перенаправления; копирующие циклы пишут в них напрямую.
+struct ForwardStats {
+    /// Принято соединений с запуска
+    connections: AtomicUsize,
+    /// Из локального сокета в SSH
+    sent: AtomicU64,
+    /// Из SSH в локальный сокет
+    received: AtomicU64,
+    /// Открытые сейчас соединения
+    live: parking_lot::Mutex<Vec<Arc<LiveConnection>>>,
+    next_id: AtomicU64,
+    log: Arc<ForwardLog>,
+    resolve: SocksResolve,
+    /// Тайм-аут простоя соединения из правила
+    idle: Option<Duration>,
+    limit_sent: Arc<RateLimiter>,
+    limit_received: Arc<RateLimiter>,
+    /// Локальный порт оказался занят: внутри ближайший свободный
+    port_conflict: parking_lot::Mutex<Option<Option<u16>>>,
+}
+
+impl ForwardStats {
+    fn new(log: Arc<ForwardLog>, rule: &ForwardRule) -> Self {
+        ForwardStats {
+            connections: AtomicUsize::new(0),
+            sent: AtomicU64::new(0),
+            received: AtomicU64::new(0),
+            live: parking_lot::Mutex::new(Vec::new()),
+            next_id: AtomicU64::new(0),
+            log,
+            resolve: SocksResolve::default(),
+            idle: rule.idle_timeout(),
+            limit_sent: RateLimiter::new(rule.rate_limit_kib),
+            limit_received: RateLimiter::new(rule.rate_limit_kib),
+            port_conflict: parking_lot::Mutex::new(None),
+        }
+    }
+
+    /// Новый предел действует и на уже открытые соединения.
+    fn set_rate_limit(&self, limit_kib: u32) {
+        self.limit_sent.set_limit_kib(limit_kib);
+        self.limit_received.set_limit_kib(limit_kib);
+    }
+
+    fn total(&self) -> u64 {
+        self.sent.load(Ordering::Relaxed) + self.received.load(Ordering::Relaxed)
+    }
+
+    /// Регистрирует принятое соединение; из списка оно уходит вместе с guard.
+    fn open(self: &Arc<Self>, peer: String, destination: String) -> ConnectionGuard {
+        self.connections.fetch_add(1, Ordering::Relaxed);
+        let live = Arc::new(LiveConnection {
+            id: self.next_id.fetch_add(1, Ordering::Relaxed),
+            peer,
+            destination: parking_lot::Mutex::new(destination),
+            opened: Instant::now(),
+            sent: AtomicU64::new(0),
+            received: AtomicU64::new(0),
+            relaying: AtomicBool::new(false),
+            kill: tokio::sync::Notify::new(),
+        });
+        self.live.lock().push(live.clone());
+        ConnectionGuard {
+            stats: self.clone(),
+            live,
+        }
+    }
+}
+
+/// Сколько последних адресов SOCKS держим для подсказки
+const SOCKS_RECENT: usize = 20;
+
+/// Как SOCKS-клиенты задают адрес: именем (резолвит SSH-сервер) или готовым IP
+/// (клиент уже сходил в локальный DNS).
+#[derive(Default)]
+struct SocksResolve {
+    by_name: AtomicU64,
+    by_ip: AtomicU64,
+    /// Адрес и признак «задан именем»
+    recent: parking_lot::Mutex<VecDeque<(String, bool)>>,
+}
+
+impl SocksResolve {
+    fn record(&self, destination: String, by_name: bool) {
+        let counter = if by_name { &self.by_name } else { &self.by_ip };
+        counter.fetch_add(1, Ordering::Relaxed);
+        let mut recent = self.recent.lock();
+        if recent.len() == SOCKS_RECENT {
+            recent.pop_front();
+        }
+        recent.push_back((destination, by_name));
+    }
+
+    /// DNS remote / DNS local в строке правила, с подсказкой по последним адресам.
+    fn show(&self, ui: &mut egui::Ui) {
+        let by_name = self.by_name.load(Ordering::Relaxed);
+        let by_ip = self.by_ip.load(Ordering::Relaxed);
+        if by_name + by_ip == 0 {
+            return;
+        }
+        let (label, color) = if by_ip == 0 {
+            ("DNS remote", crate::theme::GREEN)
+        } else {
+            ("DNS local", crate::theme::AMBER)
+        };
+        ui.colored_label(color, label).on_hover_ui(|ui| {
+            ui.label(format!(
+                "{} by hostname (resolved by the server), {} by IP (resolved locally)",
+                by_name, by_ip
+            ));
+            ui.separator();
+            for (destination, by_name) in self.recent.lock().iter().rev() {
+                if *by_name {
+                    ui.colored_label(crate::theme::GREEN, format!("name {}", destination));
+                } else {
+                    ui.colored_label(crate::theme::AMBER, format!("ip   {}", destination));
+                }
+            }
+        });
+    }
+}
+
+/// Одно проксируемое соединение правила.
+struct LiveConnection {
+    id: u64,
+    /// Кто подключился: локальный клиент или, для -R, источник на стороне сервера
+    peer: String,
+    /// Куда; у SOCKS и HTTP известно только после рукопожатия
+    destination: parking_lot::Mutex<String>,
+    opened: Instant,
+    sent: AtomicU64,
+    received: AtomicU64,
+    /// Канал до цели открыт, идёт перекачка
+    relaying: AtomicBool,
+    /// [kill] из таблицы соединений
+    kill: tokio::sync::Notify,
+}
+
+/// Живёт столько же, сколько задача соединения.
+struct ConnectionGuard {
+    stats: Arc<ForwardStats>,
+    live: Arc<LiveConnection>,
+}
+
+impl ConnectionGuard {
+    fn set_destination(&self, destination: String) {
+        *self.live.destination.lock() = destination;
+    }
+
+    /// Канал открыт, данные пошли.
+    fn log_open(&self) {
+        self.live.relaying.store(true, Ordering::Relaxed);
+        let destination = self.live.destination.lock().clone();
+        self.stats.log.push(
+            EventLevel::Conn,
+            format!("{} -> {}", self.live.peer, destination),
+        );
+    }
+
+    fn log_error(&self, error: impl std::fmt::Display) {
+        self.stats
+            .log
+            .push(EventLevel::Error, format!("{}: {}", self.live.peer, error));
+    }
+}
+
+impl Drop for ConnectionGuard {
+    fn drop(&mut self) {
+        self.stats.live.lock().retain(|c| c.id != self.live.id);
+        let sent = self.live.sent.load(Ordering::Relaxed);
+        let received = self.live.received.load(Ordering::Relaxed);
+        if sent + received > 0 {
+            self.stats.log.push(
+                EventLevel::Conn,
+                format!(
+                    "{} closed: {} / {} in {}",
+                    self.live.peer,
+                    format_size(sent),
+                    format_size(received),
+                    crate::notify::format_duration(self.live.opened.elapsed())
+                ),
+            );
+        }
+    }
+}
+
+struct ActiveForward {
+    alive: Arc<AtomicBool>,
+    /// Новые соединения сразу закрываются; открытые и статистика остаются
+    paused: Arc<AtomicBool>,
+    error: Arc<parking_lot::Mutex<Option<String>>>,
+    stats: Arc<ForwardStats>,
+    started: Instant,
+    /// Остановлено пользователем — не сбой, перезапуск не нужен
+    stopping: bool,
+    /// Трафик правила при последней активности и её время — для idle_stop
+    last_activity: (u64, Instant),
+}
+
+/// Первая пауза перед перезапуском упавшего перенаправления; дальше удваивается
+const RESTART_DELAY_MIN: Duration = Duration::from_secs(2);
+const RESTART_DELAY_MAX: Duration = Duration::from_secs(60);
+/// Столько должно проработать перезапущенное правило, чтобы сбой считался прошедшим
+const RESTART_STABLE: Duration = Duration::from_secs(10);
+
+/// Упавшее правило: ошибка и когда пробовать снова.
+struct Failure {
+    error: String,
+    attempts: u32,
+    retry_at: Instant,
+}
+
+impl Failure {
+    fn next(previous: Option<&Failure>, error: String) -> Self {
+        let attempts = previous.map_or(0, |f| f.attempts) + 1;
+        let delay = (RESTART_DELAY_MIN * 2u32.pow((attempts - 1).min(5))).min(RESTART_DELAY_MAX);
+        Failure {
+            error,
+            attempts,
+            retry_at: Instant::now() + delay,
+        }
+    }
+
+    /// "retry in 8s (attempt 3)"
+    fn retry_label(&self) -> String {
+        let left = self.retry_at.saturating_duration_since(Instant::now());
+        format!("retry in {}s (attempt {})", left.as_secs() + 1, self.attempts + 1)
+    }
+}
+
+/// Сколько секунд скорости помнит спарклайн
+const SPARKLINE_LEN: usize = 60;
+const SPARKLINE_STEP: Duration = Duration::from_secs(1);
+
+/// Скорость (байт/с, оба направления) по секундам — для спарклайна.
+struct Throughput {
+    samples: VecDeque<f32>,
+    last_total: u64,
+    last_at: Instant,
+}
+
+impl Throughput {
+    fn new() -> Self {
+        Throughput {
+            samples: VecDeque::with_capacity(SPARKLINE_LEN),
+            last_total: 0,
+            last_at: Instant::now(),
+        }
+    }
+
+    /// Не чаще раза в секунду; пропущенное время (вкладка была скрыта) — средним.
+    fn sample(&mut self, total: u64) {
+        let elapsed = self.last_at.elapsed();
+        if elapsed < SPARKLINE_STEP {
+            return;
+        }
+        let rate = total.saturating_sub(self.last_total) as f32 / elapsed.as_secs_f32();
+        let steps = (elapsed.as_secs() as usize).clamp(1, SPARKLINE_LEN);
+        for _ in 0..steps {
+            if self.samples.len() == SPARKLINE_LEN {
+                self.samples.pop_front();
+            }
+            self.samples.push_back(rate);
+        }
+        self.last_total = total;
+        self.last_at = Instant::now();
+    }
+
+    fn current(&self) -> f32 {
+        self.samples.back().copied().unwrap_or(0.0)
+    }
+
+    fn show(&self, ui: &mut egui::Ui) {
+        let (rect, _) = ui.allocate_exact_size(egui::vec2(90.0, 16.0), egui::Sense::hover());
+        let painter = ui.painter_at(rect);
+        painter.line_segment(
+            [rect.left_bottom(), rect.right_bottom()],
+            egui::Stroke::new(1.0, crate::theme::GREEN_DARK),
+        );
+        let peak = self.samples.iter().copied().fold(0.0, f32::max);
+        if peak <= 0.0 || self.samples.len() < 2 {
+            return;
+        }
+        let step = rect.width() / (SPARKLINE_LEN - 1) as f32;
+        let offset = SPARKLINE_LEN - self.samples.len();
+        let points = self
+            .samples
+            .iter()
+            .enumerate()
+            .map(|(i, rate)| {
+                egui::pos2(
+                    rect.left() + (offset + i) as f32 * step,
+                    rect.bottom() - rate / peak * (rect.height() - 1.0),
+                )
+            })
+            .collect();
+        painter.add(egui::Shape::line(
+            points,
+            egui::Stroke::new(1.0, crate::theme::GREEN),
+        ));
+    }
+}
+
+/// Сохранённое правило и его поток, если оно запущено.
+struct ForwardEntry {
+    rule: ForwardRule,
+    log: Arc<ForwardLog>,
+    /// Журнал развёрнут под таблицей
+    show_log: bool,
+    active: Option<ActiveForward>,
+    failure: Option<Failure>,
+    /// Локальный порт занят — правило не запущено; внутри ближайший свободный
+    port_conflict: Option<Option<u16>>,
+    /// Не запущено: правило открыто наружу, а в [options] разрешён только loopback
+    blocked: bool,
+    /// Последний [test]
+    probe: Option<Probe>,
+    throughput: Throughput,
+    /// Правило изменено в контекстном меню, но ещё не сохранено
+    unsaved_edit: bool,
+    /// [pause] держится и после перезапуска по сбою; снимается [resume] или остановкой
+    paused: bool,
+}
+
+impl ForwardEntry {
+    fn new(rule: ForwardRule) -> Self {
+        ForwardEntry {
+            rule,
+            log: Arc::new(ForwardLog::default()),
+            show_log: false,
+            active: None,
+            failure: None,
+            port_conflict: None,
+            blocked: false,
+            probe: None,
+            throughput: Throughput::new(),
+            unsaved_edit: false,
+            paused: false,
+        }
+    }
+
+    fn is_running(&self) -> bool {
+        self.active
+            .as_ref()
+            .is_some_and(|a| a.alive.load(Ordering::Relaxed))
+    }
+
+    fn is_paused(&self) -> bool {
+        self.active
+            .as_ref()
+            .is_some_and(|a| a.paused.load(Ordering::Relaxed))
+    }
+
+    /// Ошибка для общего списка: сбой или занятый порт.
+    fn problem(&self) -> Option<String> {
+        if self.blocked {
+            return Some("not loopback, forbidden by [options]".to_string());
+        }
+        match (&self.port_conflict, &self.failure) {
+            (Some(_), _) => Some(format!("port {} already in use", self.rule.local_port)),
+            (None, Some(failure)) => Some(failure.error.clone()),
+            (None, None) => None,
+        }
+    }
+}
+
+/// Сколько портов выше занятого перебирать в поисках свободного
+const FREE_PORT_SEARCH: u16 = 100;
+
+/// Слушает ли уже кто-то host:port. Сокет настраивается как у tokio::net::TcpListener
+/// (SO_REUSEADDR на unix), чтобы TIME_WAIT после остановки правила не считался занятостью.
+fn port_in_use(host: &str, port: u16) -> bool {
+    use std::net::ToSocketAddrs;
+    let Some(addr) = (host, port).to_socket_addrs().ok().and_then(|mut a| a.next()) else {
+        return false;
+    };
+    let socket = match addr {
+        SocketAddr::V4(_) => tokio::net::TcpSocket::new_v4(),
+        SocketAddr::V6(_) => tokio::net::TcpSocket::new_v6(),
+    };
+    let Ok(socket) = socket else {
+        return false;
+    };
+    #[cfg(unix)]
+    let _ = socket.set_reuseaddr(true);
+    matches!(socket.bind(addr), Err(e) if e.kind() == std::io::ErrorKind::AddrInUse)
+}
+
+/// Ближайший свободный порт выше занятого.
+fn next_free_port(host: &str, port: u16) -> Option<u16> {
+    (port.saturating_add(1)..=port.saturating_add(FREE_PORT_SEARCH))
+        .find(|&p| p > port && !port_in_use(host, p))
+}
+
+/// Проверка порта в диалоге начинается, когда ввод затих на столько
+const PORT_CHECK_DEBOUNCE: Duration = Duration::from_millis(300);
+/// Сколько верить прошлой проверке той же пары host:port
+const PORT_CHECK_TTL: Duration = Duration::from_secs(5);
+
+/// None — порт свободен; Some — занят, внутри ближайший свободный
+type PortBusy = Option<Option<u16>>;
+type PortKey = (String, u16);
+
+/// Занятость порта для диалога добавления. Резолв имени и перебор портов идут
+/// в отдельном потоке, ответы помнятся на пару host:port.
+#[derive(Default)]
+struct PortChecks {
+    results: HashMap<PortKey, (Instant, PortBusy)>,
+    /// Что сейчас в полях и с какого момента
+    typed: Option<(PortKey, Instant)>,
+    running: Option<(PortKey, Arc<std::sync::OnceLock<PortBusy>>)>,
+}
+
+impl PortChecks {
+    /// Some — занят (внутри ближайший свободный); None — свободен или ещё не ясно.
+    fn busy(&mut self, ctx: &egui::Context, host: &str, port: u16) -> PortBusy {
+        let key = (host.to_string(), port);
+        let now = Instant::now();
+        if let Some((done, slot)) = self.running.take_if(|(_, slot)| slot.get().is_some()) {
+            if let Some(&busy) = slot.get() {
+                self.results.insert(done, (now, busy));
+            }
+        }
+        match self.results.get(&key) {
+            Some(&(at, busy)) if now - at < PORT_CHECK_TTL => return busy,
+            _ => {}
+        }
+        match &self.typed {
+            Some((typed, since)) if *typed == key => {
+                if self.running.is_none() && now - *since >= PORT_CHECK_DEBOUNCE {
+                    let slot = Arc::new(std::sync::OnceLock::new());
+                    let (out, host) = (slot.clone(), key.0.clone());
+                    std::thread::spawn(move || {
+                        let busy = port_in_use(&host, port).then(|| next_free_port(&host, port));
+                        let _ = out.set(busy);
+                    });
+                    self.running = Some((key.clone(), slot));
+                }
+            }
+            _ => self.typed = Some((key.clone(), now)),
+        }
+        ctx.request_repaint_after(PORT_CHECK_DEBOUNCE);
+        // Пока идёт проверка — прежний ответ, если он был
+        self.results.get(&key).and_then(|&(_, busy)| busy)
+    }
+}
+
+/// Сколько ждать, пока порт отпустит прежний запуск того же правила: после [stop]
+/// его цикл accept замечает остановку не позже чем через 500 мс
+const PORT_RELEASE_WAIT: Duration = Duration::from_millis(800);
+
+/// Слушающий сокет правила. Порт занят и дольше PORT_RELEASE_WAIT — ближайший
+/// свободный уходит в stats.port_conflict, и правило не перезапускается.
+async fn bind_local(
+    rule: &ForwardRule,
+    stats: &ForwardStats,
+) -> Result<tokio::net::TcpListener, Box<dyn std::error::Error + Send + Sync>> {
+    let addr = format!("{}:{}", rule.local_host, rule.local_port);
+    let deadline = Instant::now() + PORT_RELEASE_WAIT;
+    loop {
+        match tokio::net::TcpListener::bind(&addr).await {
+            Ok(listener) => {
+                stats.log.push(EventLevel::Info, format!("listening on {}", addr));
+                return Ok(listener);
+            }
+            Err(e) if e.kind() == std::io::ErrorKind::AddrInUse && Instant::now() < deadline => {
+                tokio::time::sleep(Duration::from_millis(100)).await;
+            }
+            Err(e) if e.kind() == std::io::ErrorKind::AddrInUse => {
+                let free = next_free_port(&rule.local_host, rule.local_port);
+                *stats.port_conflict.lock() = Some(free);
+                return Err(format!("port {} already in use", rule.local_port).into());
+            }
+            Err(e) => return Err(e.into()),
+        }
+    }
+}
+
+// ── Менеджер перенаправлений ──
+
+pub struct PortForwarder {
+    config: SessionConfig,
+    forwards: Vec<ForwardEntry>,
+    /// Список правил изменился — приложение сохранит его в сессию
+    rules_changed: bool,
+    // UI: диалог добавления
+    show_add_dialog: bool,
+    new_label: String,
+    new_forward_type: usize, // 0=Local, 1=Remote, 2=Dynamic, 3=Http
+    new_local_host: String,
+    new_local_port: String,
+    new_remote_host: String,
+    new_remote_port: String,
+    new_auto_start: bool,
+    new_idle_minutes: String,
+    new_idle_stop: bool,
+    new_rate_limit_kib: u32,
+    new_socks_login: bool,
+    new_socks_user: String,
+    new_socks_password: String,
+    /// -R: слушать на всех интерфейсах сервера (GatewayPorts)
+    new_gateway: bool,
+    /// Проверки порта в диалоге
+    new_port_checks: PortChecks,
+    presets: Vec<ForwardPreset>,
+    new_preset_name: String,
+    /// Правило из диалога доступно снаружи, пользователь это подтвердил
+    new_expose_confirmed: bool,
+    settings: ForwardSettings,
+    /// Окно открытых соединений правила
+    show_connections: Option<usize>,
+    /// Правила, ждущие пароль SOCKS5 перед запуском; спрашиваем по очереди
+    password_prompt: Vec<usize>,
+    /// Правила, ждущие подтверждения доступа с других машин — по очереди
+    expose_prompt: Vec<usize>,
+    password_input: String,
+    // UI: импорт строк OpenSSH
+    show_import_dialog: bool,
+    import_text: String,
+    import_error: Option<String>,
+    // Сообщения
+    status_message: Option<String>,
+}
+
+impl PortForwarder {
+    pub fn new(config: &SessionConfig) -> Self {
+        let forwards = config
+            .forward_rules
+            .iter()
+            .map(|rule| ForwardEntry::new(rule.clone()))
+            .collect();
+        PortForwarder {
+            config: config.clone(),
+            forwards,
+            rules_changed: false,
+            show_add_dialog: false,
+            new_label: String::new(),
+            new_forward_type: 0,
+            new_local_host: "127.0.0.1".to_string(),
+            new_local_port: String::new(),
+            new_remote_host: "localhost".to_string(),
+            new_remote_port: String::new(),
+            new_auto_start: false,
+            new_idle_minutes: String::new(),
+            new_idle_stop: false,
+            new_rate_limit_kib: 0,
+            new_socks_login: false,
+            new_socks_user: String::new(),
+            new_socks_password: String::new(),
+            new_gateway: false,
+            new_port_checks: PortChecks::default(),
+            presets: Vec::new(),
+            new_preset_name: String::new(),
+            new_expose_confirmed: false,
+            settings: crate::config::forwards::load_settings(),
+            show_connections: None,
+            password_prompt: Vec::new(),
+            expose_prompt: Vec::new(),
+            password_input: String::new(),
+            show_import_dialog: false,
+            import_text: String::new(),
+            import_error: None,
+            status_message: None,
+        }
+    }
+
+    /// Правила с [auto] — при подключении сессии.
+    pub fn start_auto(&mut self) {
+        for index in 0..self.forwards.len() {
+            if self.forwards[index].rule.auto_start {
+                self.start_forward(index);
+            }
+        }
+    }
+
+    /// [start all]: запускает все остановленные правила; возвращает, сколько запущено.
+    pub fn start_all(&mut self) -> usize {
+        let stopped: Vec<usize> = (0..self.forwards.len())
+            .filter(|&i| !self.forwards[i].is_running())
+            .collect();
+        for &index in &stopped {
+            self.start_forward(index);
+        }
+        stopped.iter().filter(|&&i| self.forwards[i].is_running()).count()
+    }
+
+    /// [stop all]: останавливает все правила и отменяет ожидающие перезапуски.
+    pub fn stop_all(&mut self) -> usize {
+        let mut stopped = 0;
+        for index in 0..self.forwards.len() {
+            let fwd = &self.forwards[index];
+            if fwd.is_running() || fwd.failure.is_some() {
+                self.stop_forward(index);
+                stopped += 1;
+            }
+        }
+        stopped
+    }
+
+    pub fn has_rules(&self) -> bool {
+        !self.forwards.is_empty()
+    }
+
+    /// Правила для сохранения в сессию, если список менялся с прошлого вызова.
+    pub fn take_rules_changed(&mut self) -> Option<Vec<ForwardRule>> {
+        if !std::mem::take(&mut self.rules_changed) {
+            return None;
+        }
+        Some(self.forwards.iter().map(|f| f.rule.clone()).collect())
+    }
+
+    /// Правила сессии с их состоянием — для общего обзора.
+    pub fn summaries(&self) -> Vec<ForwardSummary> {
+        self.forwards
+            .iter()
+            .enumerate()
+            .map(|(index, f)| ForwardSummary {
+                index,
+                rule: f.rule.clone(),
+                running: f.is_running(),
+                paused: f.is_paused(),
+                failed: f.problem(),
+                connections: f
+                    .active
+                    .as_ref()
+                    .map_or(0, |a| a.stats.connections.load(Ordering::Relaxed)),
+            })
+            .collect()
+    }
+
+    pub fn start_forward(&mut self, index: usize) {
+        let Some(entry) = self.forwards.get_mut(index) else {
+            return;
+        };
+        if entry.is_running() {
+            return;
+        }
+        if entry.rule.socks_auth.as_ref().is_some_and(|a| a.password.is_empty()) {
+            if !self.password_prompt.contains(&index) {
+                entry.log.push(EventLevel::Info, "waiting for SOCKS password");
+                self.password_prompt.push(index);
+            }
+            return;
+        }
+        // Настройка общая для всех сессий — могла поменяться в другой вкладке
+        self.settings = crate::config::forwards::load_settings();
+        entry.blocked = false;
+        if let Some(address) = entry.rule.exposed_address() {
+            if self.settings.loopback_only {
+                entry.failure = None;
+                entry.blocked = true;
+                entry.log.push(
+                    EventLevel::Error,
+                    format!("not started: {} is reachable from other machines", address),
+                );
+                return;
+            }
+            // Откуда бы правило ни пришло (импорт, шаблон, старый файл) — без
+            // подтверждения именно этого адреса наружу не открываем
+            if entry.rule.exposure_confirmed.as_ref() != Some(&address) {
+                if !self.expose_prompt.contains(&index) {
+                    entry.log.push(EventLevel::Info, "waiting for confirmation to expose");
+                    self.expose_prompt.push(index);
+                }
+                return;
+            }
+        }
+        // Занятый порт выяснится при bind в потоке правила (bind_local)
+        entry.port_conflict = None;
+        let rule = entry.rule.clone();
+        let alive = Arc::new(AtomicBool::new(true));
+        let error: Arc<parking_lot::Mutex<Option<String>>> =
+            Arc::new(parking_lot::Mutex::new(None));
+        let stats = Arc::new(ForwardStats::new(entry.log.clone(), &entry.rule));
+        match &entry.failure {
+            Some(failure) => entry.log.push(
+                EventLevel::Info,
+                format!("restarting (attempt {})", failure.attempts + 1),
+            ),
+            None => entry.log.push(EventLevel::Info, "starting"),
+        }
+
+        let config = self.config.clone();
+        let rule_clone = rule;
+        let alive_clone = alive.clone();
+        let paused = Arc::new(AtomicBool::new(entry.paused));
+        let paused_clone = paused.clone();
+        let error_clone = error.clone();
+        let stats_clone = stats.clone();
+
+        std::thread::spawn(move || {
+            let rt = match tokio::runtime::Runtime::new() {
+                Ok(rt) => rt,
+                Err(e) => {
+                    *error_clone.lock() = Some(format!("Tokio runtime: {}", e));
+                    alive_clone.store(false, Ordering::Relaxed);
+                    return;
+                }
+            };
+            let result = match rule_clone.forward_type {
+                ForwardType::Local => rt.block_on(run_local_forward_async(
+                    &config,
+                    &rule_clone,
+                    &alive_clone,
+                    &paused_clone,
+                    &stats_clone,
+                )),
+                ForwardType::Remote => rt.block_on(run_remote_forward_async(
+                    &config,
+                    &rule_clone,
+                    &alive_clone,
+                    &paused_clone,
+                    &stats_clone,
+                )),
+                ForwardType::Dynamic => rt.block_on(run_dynamic_forward_async(
+                    &config,
+                    &rule_clone,
+                    &alive_clone,
+                    &paused_clone,
+                    &stats_clone,
+                )),
+                ForwardType::Http => rt.block_on(run_http_proxy_async(
+                    &config,
+                    &rule_clone,
+                    &alive_clone,
+                    &paused_clone,
+                    &stats_clone,
+                )),
+            };
+            if let Err(e) = result {
+                *error_clone.lock() = Some(e.to_string());
+            }
+            alive_clone.store(false, Ordering::Relaxed);
+        });
+
+        entry.active = Some(ActiveForward {
+            alive,
+            paused,
+            error,
+            stats,
+            started: Instant::now(),
+            stopping: false,
+            last_activity: (0, Instant::now()),
+        });
+        entry.throughput = Throughput::new();
+    }
+
+    /// Каждый кадр, и при скрытой вкладке: завершившиеся сами правила становятся
+    /// упавшими и перезапускаются с растущей паузой.
+    pub fn poll(&mut self) {
+        let now = Instant::now();
+        let mut restart = Vec::new();
+        let mut idle = Vec::new();
+        for (index, fwd) in self.forwards.iter_mut().enumerate() {
+            if let (Some(active), Some(timeout), true) =
+                (&mut fwd.active, fwd.rule.idle_timeout(), fwd.rule.idle_stop)
+            {
+                let total = active.stats.total();
+                if total != active.last_activity.0 || !active.stats.live.lock().is_empty() {
+                    active.last_activity = (total, now);
+                } else if !active.stopping && now - active.last_activity.1 >= timeout {
+                    idle.push(index);
+                }
+            }
+            let stopped = fwd
+                .active
+                .take_if(|a| !a.alive.load(Ordering::Relaxed));
+            if let Some(active) = stopped {
+                let conflict = active.stats.port_conflict.lock().take();
+                if active.stopping {
+                    fwd.failure = None;
+                    fwd.log.push(EventLevel::Info, "stopped");
+                } else if let Some(free) = conflict {
+                    // Занятый порт сам не освободится — не перезапускаем, предлагаем [use N]
+                    fwd.failure = None;
+                    fwd.port_conflict = Some(free);
+                    fwd.log.push(
+                        EventLevel::Error,
+                        format!("port {} already in use", fwd.rule.local_port),
+                    );
+                } else {
+                    let error = active
+                        .error
+                        .lock()
+                        .take()
+                        .unwrap_or_else(|| "forward stopped unexpectedly".to_string());
+                    fwd.log.push(EventLevel::Error, format!("failed: {}", error));
+                    fwd.failure = Some(Failure::next(fwd.failure.as_ref(), error));
+                }
+            } else if fwd
+                .active
+                .as_ref()
+                .is_some_and(|a| a.started.elapsed() >= RESTART_STABLE)
+            {
+                fwd.failure = None;
+            }
+            if fwd.active.is_none() && fwd.failure.as_ref().is_some_and(|f| f.retry_at <= now) {
+                restart.push(index);
+            }
+        }
+        for index in restart {
+            self.start_forward(index);
+        }
+        for index in idle {
+            let fwd = &self.forwards[index];
+            fwd.log.push(
+                EventLevel::Info,
+                format!("no connections for {} min", fwd.rule.idle_minutes),
+            );
+            self.stop_forward(index);
+        }
+    }
+
+    /// Возвращает список активных SOCKS5-прокси (host, port, подпись).
+    /// С логином не попадают: подключение сессии через прокси логин не передаёт.
+    pub fn active_socks5_proxies(&self) -> Vec<(String, u16, String)> {
+        self.forwards
+            .iter()
+            .filter(|f| {
+                f.rule.forward_type == ForwardType::Dynamic
+                    && f.rule.socks_auth.is_none()
+                    && f.is_running()
+            })
+            .map(|f| (f.rule.local_host.clone(), f.rule.local_port, f.rule.label.clone()))
+            .collect()
+    }
+
+    /// Останавливает правило и отменяет перезапуск, если оно упало.
+    pub fn stop_forward(&mut self, index: usize) {
+        let Some(entry) = self.forwards.get_mut(index) else {
+            return;
+        };
+        entry.failure = None;
+        entry.port_conflict = None;
+        entry.paused = false;
+        if let Some(active) = &mut entry.active {
+            active.stopping = true;
+            active.alive.store(false, Ordering::Relaxed);
+        }
+    }
+
+    /// Переносит правило на свободный порт и запускает.
+    fn move_to_port(&mut self, index: usize, port: u16) {
+        if let Some(entry) = self.forwards.get_mut(index) {
+            entry.rule.local_port = port;
+            self.rules_changed = true;
+            self.start_forward(index);
+        }
+    }
+
+    /// Пауза: правило работает, но новые соединения закрываются сразу.
+    pub fn set_paused(&mut self, index: usize, paused: bool) {
+        if let Some(fwd) = self.forwards.get_mut(index) {
+            if let Some(active) = &fwd.active {
+                fwd.paused = paused;
+                active.paused.store(paused, Ordering::Relaxed);
+                fwd.log
+                    .push(EventLevel::Info, if paused { "paused" } else { "resumed" });
+            }
+        }
+    }
+
+    /// Закрывает открытые соединения правила.
+    fn drain_forward(&mut self, index: usize) {
+        if let Some(active) = self.forwards.get(index).and_then(|f| f.active.as_ref()) {
+            for conn in active.stats.live.lock().iter() {
+                conn.kill.notify_one();
+            }
+            active.stats.log.push(EventLevel::Info, "open connections closed");
+        }
+    }
+
+    /// [test]: пробное соединение через правило в отдельном потоке.
+    fn test_forward(&mut self, index: usize) {
+        let Some(fwd) = self.forwards.get_mut(index) else {
+            return;
+        };
+        let Some(active) = &fwd.active else {
+            return;
+        };
+        let result = Arc::new(parking_lot::Mutex::new(None));
+        fwd.probe = Some(Probe {
+            result: result.clone(),
+        });
+        let rule = fwd.rule.clone();
+        let stats = active.stats.clone();
+        std::thread::spawn(move || {
+            let outcome = match tokio::runtime::Builder::new_current_thread()
+                .enable_all()
+                .build()
+            {
+                Ok(rt) => rt.block_on(async {
+                    tokio::time::timeout(PROBE_TIMEOUT, probe_rule(&rule, &stats))
+                        .await
+                        .unwrap_or_else(|_| Err("no answer".to_string()))
+                }),
+                Err(e) => Err(format!("Tokio runtime: {}", e)),
+            };
+            match &outcome {
+                Ok(latency) => stats.log.push(
+                    EventLevel::Info,
+                    format!("test ok: {} ms", latency.as_millis()),
+                ),
+                Err(e) => stats.log.push(EventLevel::Error, format!("test failed: {}", e)),
+            }
+            *result.lock() = Some(outcome);
+        });
+    }
+
+    fn remove_forward(&mut self, index: usize) {
+        self.show_connections = None;
+        self.password_prompt.clear();
+        self.expose_prompt.clear();
+        self.stop_forward(index);
+        self.forwards.remove(index);
+        self.rules_changed = true;
+    }
+
+    // ── UI ──
+
+    pub fn show(&mut self, ui: &mut egui::Ui) {
+        for fwd in &mut self.forwards {
+            if let Some(active) = &fwd.active {
+                fwd.throughput.sample(active.stats.total());
+            }
+        }
+
+        // Панель инструментов
+        ui.horizontal(|ui| {
+            if ui.button("[+ add rule]").clicked() {
+                self.show_add_dialog = true;
+                self.new_label.clear();
+                self.new_forward_type = 0;
+                self.new_local_host = "127.0.0.1".to_string();
+                self.new_local_port.clear();
+                self.new_remote_host = "localhost".to_string();
+                self.new_remote_port.clear();
+                self.new_auto_start = false;
+                self.new_idle_minutes.clear();
+                self.new_idle_stop = false;
+                self.new_rate_limit_kib = 0;
+                self.new_socks_login = false;
+                self.new_socks_user.clear();
+                self.new_socks_password.clear();
+                self.new_gateway = false;
+                self.presets = crate::config::forwards::load_presets();
+                self.new_preset_name.clear();
+                self.new_expose_confirmed = false;
+                self.settings = crate::config::forwards::load_settings();
+            }
+            if ui
+                .button("[import...]")
+                .on_hover_text("paste -L/-R/-D options or LocalForward lines")
+                .clicked()
+            {
+                self.show_import_dialog = true;
+                self.import_text.clear();
+                self.import_error = None;
+            }
+            let any_stopped = self.forwards.iter().any(|f| !f.is_running());
+            if ui
+                .add_enabled(any_stopped, egui::Button::new("[start all]"))
+                .clicked()
+            {
+                let started = self.start_all();
+                self.status_message = Some(format!("{} forwards started", started));
+            }
+            let any_running = self
+                .forwards
+                .iter()
+                .any(|f| f.is_running() || f.failure.is_some());
+            if ui
+                .add_enabled(any_running, egui::Button::new("[stop all]"))
+                .clicked()
+            {
+                let stopped = self.stop_all();
+                self.status_message = Some(format!("{} forwards stopped", stopped));
+            }
+            ui.menu_button("[options]", |ui| {
+                if ui
+                    .checkbox(&mut self.settings.loopback_only, "loopback only")
+                    .on_hover_text(
+                        "refuse rules reachable from other machines: 0.0.0.0, LAN addresses, \
+                         -R on all server interfaces; applies to every session",
+                    )
+                    .changed()
+                {
+                    crate::config::forwards::save_settings(&self.settings);
+                }
+            });
+        });
+
+        // Статус / ошибки
+        if let Some(msg) = self.status_message.take() {
+            ui.colored_label(crate::theme::GREEN, &msg);
+        }
+        let mut move_port: Option<(usize, u16)> = None;
+        for (idx, fwd) in self.forwards.iter().enumerate() {
+            if fwd.blocked {
+                ui.colored_label(
+                    crate::theme::RED,
+                    format!(
+                        "ERR {}: reachable from other machines, [options] allow loopback only",
+                        fwd.rule.title()
+                    ),
+                );
+            } else if let Some(free) = fwd.port_conflict {
+                ui.horizontal(|ui| {
+                    ui.colored_label(
+                        crate::theme::RED,
+                        format!(
+                            "ERR {}: port {} already in use",
+                            fwd.rule.title(),
+                            fwd.rule.local_port
+                        ),
+                    );
+                    match free {
+                        Some(port) => {
+                            if ui
+                                .small_button(format!("[use {}]", port))
+                                .on_hover_text("use the next free port")
+                                .clicked()
+                            {
+                                move_port = Some((idx, port));
+                            }
+                        }
+                        None => {
+                            ui.colored_label(crate::theme::GREY, "no free port nearby");
+                        }
+                    }
+                });
+            } else if let Some(failure) = &fwd.failure {
+                let when = if fwd.active.is_some() {
+                    "restarting...".to_string()
+                } else {
+                    failure.retry_label()
+                };
+                ui.colored_label(
+                    crate::theme::RED,
+                    format!("ERR {}: {} -- {}", fwd.rule.title(), failure.error, when),
+                );
+            }
+        }
+        if let Some((idx, port)) = move_port {
+            self.move_to_port(idx, port);
+            self.status_message = Some(format!("forward moved to port {}", port));
+        }
+
+        ui.separator();
+
+        if self.forwards.is_empty() {
+            ui.vertical_centered(|ui| {
+                ui.add_space(ui.available_height() / 3.0);
+                ui.colored_label(crate::theme::GREEN_DIM, "// no port forwards");
+                ui.add_space(8.0);
+                ui.colored_label(crate::theme::GREY, "// click [+ add rule] to create one");
+            });
+        } else {
+            // Таблица правил: запущенные и остановленные
+            let mut start_idx: Option<usize> = None;
+            let mut stop_idx: Option<usize> = None;
+            let mut remove_idx: Option<usize> = None;
+            let mut auto_toggled = false;
+            let mut rule_edited = false;
+            let mut browser_launch: Option<(Browser, usize)> = None;
+            let mut connections_idx: Option<usize> = None;
+            let mut pause_idx: Option<(usize, bool)> = None;
+            let mut drain_idx: Option<usize> = None;
+            let mut test_idx: Option<usize> = None;
+
+            let config = &self.config;
+            egui_extras::TableBuilder::new(ui)
+                .striped(true)
+                .resizable(true)
+                .sense(egui::Sense::click())
+                .cell_layout(egui::Layout::left_to_right(egui::Align::Center))
+                .column(egui_extras::Column::auto().at_least(30.0))
+                .column(egui_extras::Column::auto().at_least(80.0))
+                .column(egui_extras::Column::auto().at_least(70.0))
+                .column(egui_extras::Column::remainder().at_least(140.0))
+                .column(egui_extras::Column::auto().at_least(20.0))
+                .column(egui_extras::Column::remainder().at_least(140.0))
+                .column(egui_extras::Column::auto().at_least(50.0))
+                .column(egui_extras::Column::auto().at_least(120.0))
+                .column(egui_extras::Column::auto().at_least(160.0))
+                .column(egui_extras::Column::auto().at_least(40.0))
+                .column(egui_extras::Column::auto().at_least(90.0))
+                .header(24.0, |mut header| {
+                    header.col(|ui| { ui.strong(""); });
+                    header.col(|ui| {
+                        ui.strong("NAME").on_hover_text("right-click a row to rename");
+                    });
+                    header.col(|ui| { ui.strong("TYPE"); });
+                    header.col(|ui| { ui.strong("LOCAL"); });
+                    header.col(|ui| { ui.strong(""); });
+                    header.col(|ui| { ui.strong("REMOTE"); });
+                    header.col(|ui| { ui.strong("#"); });
+                    header.col(|ui| {
+                        ui.strong("TRAFFIC").on_hover_text("bytes sent / received since start");
+                    });
+                    header.col(|ui| { ui.strong("RATE"); });
+                    header.col(|ui| {
+                        ui.strong("AUTO").on_hover_text("start when the session connects");
+                    });
+                    header.col(|ui| { ui.strong(""); });
+                })
+                .body(|body| {
+                    let count = self.forwards.len();
+                    body.rows(24.0, count, |mut row| {
+                        let idx = row.index();
+                        let fwd = &mut self.forwards[idx];
+                        let running = fwd.is_running();
+
+                        row.col(|ui| {
+                            if fwd.blocked {
+                                ui.colored_label(crate::theme::RED, "DENY")
+                                    .on_hover_text("not loopback, forbidden by [options]");
+                            } else if fwd.port_conflict.is_some() {
+                                ui.colored_label(crate::theme::AMBER, "BUSY")
+                                    .on_hover_text("local port already in use");
+                            } else if running && fwd.failure.is_none() && fwd.is_paused() {
+                                ui.colored_label(crate::theme::AMBER, "PAUSE")
+                                    .on_hover_text("new connections are refused");
+                            } else if running && fwd.failure.is_none() {
+                                ui.colored_label(crate::theme::GREEN, "ON");
+                            } else if let Some(failure) = &fwd.failure {
+                                ui.colored_label(crate::theme::RED, "FAIL")
+                                    .on_hover_text(&failure.error);
+                            } else {
+                                ui.colored_label(crate::theme::GREY, "--");
+                            }
+                        });
+                        row.col(|ui| {
+                            if fwd.rule.label.is_empty() {
+                                ui.colored_label(crate::theme::GREY, "-");
+                            } else {
+                                ui.strong(&fwd.rule.label);
+                            }
+                        });
+                        row.col(|ui| {
+                            let (label, color) = fwd.rule.forward_type.flag();
+                            ui.colored_label(color, label);
+                        });
+                        row.col(|ui| {
+                            ui.monospace(format!(
+                                "{}:{}",
+                                fwd.rule.local_host, fwd.rule.local_port
+                            ));
+                        });
+                        row.col(|ui| {
+                            let arrow = match fwd.rule.forward_type {
+                                ForwardType::Local => "->",
+                                ForwardType::Remote => "<-",
+                                ForwardType::Dynamic | ForwardType::Http => "<>",
+                            };
+                            ui.label(arrow);
+                        });
+                        row.col(|ui| {
+                            if matches!(
+                                fwd.rule.forward_type,
+                                ForwardType::Dynamic | ForwardType::Http
+                            ) {
+                                let label = if fwd.rule.socks_auth.is_some() {
+                                    "* (login)"
+                                } else {
+                                    "*"
+                                };
+                                ui.colored_label(crate::theme::GREY, label);
+                                if let Some(active) = &fwd.active {
+                                    active.stats.resolve.show(ui);
+                                }
+                            } else {
+                                ui.monospace(format!(
+                                    "{}:{}",
+                                    fwd.rule.remote_host, fwd.rule.remote_port
+                                ));
+                            }
+                        });
+                        row.col(|ui| {
+                            match &fwd.active {
+                                Some(active) => {
+                                    let open = active.stats.live.lock().len();
+                                    let total = active.stats.connections.load(Ordering::Relaxed);
+                                    if ui
+                                        .link(format!("{}", open))
+                                        .on_hover_text(format!(
+                                            "{} open, {} since start; click for the list",
+                                            open, total
+                                        ))
+                                        .clicked()
+                                    {
+                                        connections_idx = Some(idx);
+                                    }
+                                }
+                                None => {
+                                    ui.colored_label(crate::theme::GREY, "-");
+                                }
+                            }
+                        });
+                        row.col(|ui| {
+                            if let Some(active) = &fwd.active {
+                                let sent = active.stats.sent.load(Ordering::Relaxed);
+                                let received = active.stats.received.load(Ordering::Relaxed);
+                                ui.monospace(format!(
+                                    "{} / {}",
+                                    format_size(sent),
+                                    format_size(received)
+                                ))
+                                .on_hover_text("local -> ssh / ssh -> local");
+                            }
+                        });
+                        row.col(|ui| {
+                            if fwd.active.is_some() {
+                                fwd.throughput.show(ui);
+                                ui.monospace(format!(
+                                    "{}/s",
+                                    format_size(fwd.throughput.current() as u64)
+                                ));
+                            }
+                            if fwd.rule.rate_limit_kib > 0 {
+                                ui.colored_label(
+                                    crate::theme::GREY,
+                                    format!(
+                                        "max {}/s",
+                                        format_size(fwd.rule.rate_limit_kib as u64 * 1024)
+                                    ),
+                                )
+                                .on_hover_text("rate limit, each direction");
+                            }
+                        });
+                        row.col(|ui| {
+                            auto_toggled |= ui.checkbox(&mut fwd.rule.auto_start, "").changed();
+                        });
+                        row.col(|ui| {
+                            if fwd.failure.is_some() && !running {
+                                if ui.button("[retry]").on_hover_text("retry now").clicked() {
+                                    start_idx = Some(idx);
+                                }
+                                if ui
+                                    .button("[stop]")
+                                    .on_hover_text("stop retrying")
+                                    .clicked()
+                                {
+                                    stop_idx = Some(idx);
+                                }
+                            } else if running {
+                                if fwd.is_paused() {
+                                    if ui.button("[resume]").clicked() {
+                                        pause_idx = Some((idx, false));
+                                    }
+                                    let open = fwd
+                                        .active
+                                        .as_ref()
+                                        .map_or(0, |a| a.stats.live.lock().len());
+                                    if open > 0
+                                        && ui
+                                            .button("[drain]")
+                                            .on_hover_text("close open connections too")
+                                            .clicked()
+                                    {
+                                        drain_idx = Some(idx);
+                                    }
+                                } else if ui
+                                    .button("[pause]")
+                                    .on_hover_text("refuse new connections, keep open ones")
+                                    .clicked()
+                                {
+                                    pause_idx = Some((idx, true));
+                                }
+                                if ui.button("[stop]").clicked() {
+                                    stop_idx = Some(idx);
+                                }
+                                if !fwd.is_paused()
+                                    && ui
+                                        .button("[test]")
+                                        .on_hover_text(fwd.rule.forward_type.probe_hint())
+                                        .clicked()
+                                {
+                                    test_idx = Some(idx);
+                                }
+                                if let Some(probe) = &fwd.probe {
+                                    probe.show(ui);
+                                }
+                            } else if ui.button("[start]").clicked() {
+                                start_idx = Some(idx);
+                            }
+                            if ui
+                                .selectable_label(fwd.show_log, "[log]")
+                                .on_hover_text("show events of this rule")
+                                .clicked()
+                            {
+                                fwd.show_log = !fwd.show_log;
+                            }
+                            if ui
+                                .button("[x]")
+                                .on_hover_text("stop and delete the rule")
+                                .clicked()
+                            {
+                                remove_idx = Some(idx);
+                            }
+                        });
+
+                        let menu = row.response().context_menu(|ui| {
+                            ui.horizontal(|ui| {
+                                ui.label("label:");
+                                // Сохраняем по Enter или уходу из поля, не на каждую букву
+                                let label = ui.add(
+                                    egui::TextEdit::singleline(&mut fwd.rule.label)
+                                        .desired_width(120.0),
+                                );
+                                fwd.unsaved_edit |= label.changed();
+                                if label.lost_focus() && std::mem::take(&mut fwd.unsaved_edit) {
+                                    rule_edited = true;
+                                }
+                            });
+                            ui.horizontal(|ui| {
+                                ui.label("limit:");
+                                let limit = ui
+                                    .add(
+                                        egui::DragValue::new(&mut fwd.rule.rate_limit_kib)
+                                            .range(0..=1_048_576)
+                                            .speed(16)
+                                            .suffix(" KiB/s"),
+                                    )
+                                    .on_hover_text("each direction; 0 — unlimited");
+                                // Предел действует сразу, а на диск — когда отпустили
+                                if limit.changed() {
+                                    if let Some(active) = &fwd.active {
+                                        active.stats.set_rate_limit(fwd.rule.rate_limit_kib);
+                                    }
+                                    fwd.unsaved_edit = true;
+                                }
+                                if (limit.drag_stopped() || limit.lost_focus())
+                                    && std::mem::take(&mut fwd.unsaved_edit)
+                                {
+                                    rule_edited = true;
+                                }
+                            });
+                            let command = ssh_command(config, &fwd.rule);
+                            let copy = ui
+                                .add_enabled(
+                                    command.is_some(),
+                                    egui::Button::new("[copy ssh command]"),
+                                )
+                                .on_disabled_hover_text("ssh has no HTTP proxy mode");
+                            let copy = if fwd.rule.socks_auth.is_some() {
+                                copy.on_hover_text("without the SOCKS login: ssh -D has none")
+                            } else {
+                                copy
+                            };
+                            if let (true, Some(command)) = (copy.clicked(), command) {
+                                ui.ctx().copy_text(command);
+                                ui.close_menu();
+                            }
+                            if fwd.rule.forward_type == ForwardType::Dynamic {
+                                ui.add_enabled_ui(running && fwd.rule.socks_auth.is_none(), |ui| {
+                                    ui.menu_button("[open browser]", |ui| {
+                                        for browser in [Browser::Chromium, Browser::Firefox] {
+                                            if ui.button(browser.name()).clicked() {
+                                                browser_launch = Some((browser, idx));
+                                                ui.close_menu();
+                                            }
+                                        }
+                                        ui.separator();
+                                        if ui.button("[copy chrome command]").clicked() {
+                                            ui.ctx().copy_text(chrome_command(&fwd.rule));
+                                            ui.close_menu();
+                                        }
+                                        if ui
+                                            .button("[copy proxy url]")
+                                            .on_hover_text("socks5h: names resolved by the server")
+                                            .clicked()
+                                        {
+                                            ui.ctx().copy_text(format!(
+                                                "socks5h://{}",
+                                                proxy_authority(&fwd.rule)
+                                            ));
+                                            ui.close_menu();
+                                        }
+                                    });
+                                })
+                                .response
+                                .on_disabled_hover_text(
+                                    "start the proxy first; browsers cannot log in to SOCKS5",
+                                );
+                            }
+                        });
+                        // Меню закрыли, не дождавшись lost_focus, — правку всё равно сохраняем
+                        if menu.is_none() && std::mem::take(&mut fwd.unsaved_edit) {
+                            rule_edited = true;
+                        }
+                    });
+                });
+
+            // Развёрнутые журналы правил
+            for fwd in self.forwards.iter().filter(|f| f.show_log) {
+                ui.separator();
+                ui.horizontal(|ui| {
+                    ui.strong(format!("LOG {}", fwd.rule.title()));
+                    if ui.small_button("[clear]").clicked() {
+                        fwd.log.events.lock().clear();
+                    }
+                });
+                ui.push_id(Arc::as_ptr(&fwd.log) as usize, |ui| fwd.log.show(ui));
+            }
+
+            self.rules_changed |= auto_toggled || rule_edited;
+            if connections_idx.is_some() {
+                self.show_connections = connections_idx;
+            }
+            if let Some(idx) = start_idx {
+                self.start_forward(idx);
+                self.status_message = Some("forward started".to_string());
+            }
+            if let Some((idx, paused)) = pause_idx {
+                self.set_paused(idx, paused);
+                self.status_message =
+                    Some(if paused { "forward paused" } else { "forward resumed" }.to_string());
+            }
+            if let Some(idx) = test_idx {
+                self.test_forward(idx);
+            }
+            if let Some(idx) = drain_idx {
+                self.drain_forward(idx);
+                self.status_message = Some("connections closed".to_string());
+            }
+            if let Some(idx) = stop_idx {
+                self.stop_forward(idx);
+                self.status_message = Some("forward stopped".to_string());
+            }
+            if let Some(idx) = remove_idx {
+                self.remove_forward(idx);
+                self.status_message = Some("forward deleted".to_string());
+            }
+            if let Some((browser, idx)) = browser_launch {
+                let fwd = &self.forwards[idx];
+                self.status_message = Some(match launch_browser(browser, &fwd.rule) {
+                    Ok(()) => {
+                        fwd.log.push(EventLevel::Info, format!("{} started", browser.name()));
+                        format!("{} started with a separate profile", browser.name())
+                    }
+                    Err(e) => e,
+                });
+            }
+        }
+
+        // Диалог добавления
+        if self.show_add_dialog {
+            self.render_add_dialog(ui);
+        }
+        if self.show_import_dialog {
+            self.render_import_dialog(ui);
+        }
+        if self.show_connections.is_some() {
+            self.render_connections_window(ui);
+        }
+        if !self.password_prompt.is_empty() {
+            self.render_password_prompt(ui);
+        } else if !self.expose_prompt.is_empty() {
+            self.render_expose_prompt(ui);
+        }
+    }
+
+    /// Пароль SOCKS5 живёт только в памяти: после перезапуска программы — спрашиваем.
+    fn render_password_prompt(&mut self, ui: &mut egui::Ui) {
+        let index = self.password_prompt[0];
+        let Some(user) = self
+            .forwards
+            .get(index)
+            .and_then(|f| f.rule.socks_auth.as_ref())
+            .map(|a| a.user.clone())
+        else {
+            self.password_prompt.remove(0);
+            return;
+        };
+        let title = self.forwards[index].rule.title();
+        let mut start = false;
+        let mut cancel = false;
+        egui::Window::new("SOCKS password")
+            .collapsible(false)
+            .resizable(false)
+            .show(ui.ctx(), |ui| {
+                ui.colored_label(crate::theme::GREY, format!("// {}", title));
+                ui.horizontal(|ui| {
+                    ui.label(format!("password for {}:", user));
+                    let response = ui.add(
+                        egui::TextEdit::singleline(&mut self.password_input)
+                            .password(true)
+                            .desired_width(160.0),
+                    );
+                    response.request_focus();
+                    if response.lost_focus() && ui.input(|i| i.key_pressed(egui::Key::Enter)) {
+                        start = true;
+                    }
+                });
+                ui.colored_label(crate::theme::GREY, "// not saved to disk");
+                ui.horizontal(|ui| {
+                    let ok = (1..=255).contains(&self.password_input.len());
+                    if ui.add_enabled(ok, egui::Button::new("[start]")).clicked() {
+                        start = true;
+                    }
+                    if ui.button("[cancel]").clicked() {
+                        cancel = true;
+                    }
+                });
+            });
+        if start && (1..=255).contains(&self.password_input.len()) {
+            self.password_prompt.remove(0);
+            let password = std::mem::take(&mut self.password_input);
+            if let Some(auth) = self.forwards[index].rule.socks_auth.as_mut() {
+                auth.password = password;
+            }
+            self.start_forward(index);
+        } else if cancel {
+            self.password_prompt.remove(0);
+            self.password_input.clear();
+        }
+    }
+
+    /// Правило слушает не только loopback: запускаем после явного согласия.
+    fn render_expose_prompt(&mut self, ui: &mut egui::Ui) {
+        let index = self.expose_prompt[0];
+        let Some(address) = self.forwards.get(index).and_then(|f| f.rule.exposed_address())
+        else {
+            self.expose_prompt.remove(0);
+            return;
+        };
+        let title = self.forwards[index].rule.title();
+        let mut start = false;
+        let mut cancel = false;
+        egui::Window::new("Expose forward")
+            .collapsible(false)
+            .resizable(false)
+            .show(ui.ctx(), |ui| {
+                ui.colored_label(crate::theme::GREY, format!("// {}", title));
+                ui.colored_label(
+                    crate::theme::RED,
+                    format!(
+                        "!! listens on {}: anyone who can reach it uses your ssh tunnel",
+                        address
+                    ),
+                );
+                ui.horizontal(|ui| {
+                    if ui.button("[expose and start]").clicked() {
+                        start = true;
+                    }
+                    if ui.button("[cancel]").clicked() {
+                        cancel = true;
+                    }
+                });
+            });
+        if start {
+            self.expose_prompt.remove(0);
+            self.forwards[index].rule.exposure_confirmed = Some(address);
+            self.rules_changed = true;
+            self.start_forward(index);
+        } else if cancel {
+            self.expose_prompt.remove(0);
+            self.forwards[index].log.push(EventLevel::Info, "not exposed, not started");
+        }
+    }
+
+    /// Открытые соединения одного правила: откуда, куда, трафик, возраст и [kill].
+    fn render_connections_window(&mut self, ui: &mut egui::Ui) {
+        let Some(fwd) = self.show_connections.and_then(|i| self.forwards.get(i)) else {
+            self.show_connections = None;
+            return;
+        };
+        let live: Vec<Arc<LiveConnection>> = fwd
+            .active
+            .as_ref()
+            .map(|a| a.stats.live.lock().clone())
+            .unwrap_or_default();
+
+        let mut open = true;
+        egui::Window::new(format!("connections: {}", fwd.rule.title()))
+            .id(egui::Id::new("forward_connections"))
+            .open(&mut open)
+            .resizable(true)
+            .default_width(520.0)
+            .show(ui.ctx(), |ui| {
+                if live.is_empty() {
+                    ui.colored_label(crate::theme::GREEN_DIM, "// no open connections");
+                    return;
+                }
+                egui::Grid::new("forward_connections_grid")
+                    .num_columns(5)
+                    .striped(true)
+                    .spacing([12.0, 4.0])
+                    .show(ui, |ui| {
+                        ui.strong("SOURCE");
+                        ui.strong("DESTINATION");
+                        ui.strong("SENT / RECV");
+                        ui.strong("AGE");
+                        ui.strong("");
+                        ui.end_row();
+                        for conn in &live {
+                            ui.monospace(&conn.peer);
+                            let destination = conn.destination.lock().clone();
+                            if destination.is_empty() {
+                                ui.colored_label(crate::theme::GREY, "...");
+                            } else {
+                                ui.monospace(destination);
+                            }
+                            ui.monospace(format!(
+                                "{} / {}",
+                                format_size(conn.sent.load(Ordering::Relaxed)),
+                                format_size(conn.received.load(Ordering::Relaxed))
+                            ));
+                            ui.label(crate::notify::format_duration(conn.opened.elapsed()));
+                            if ui.button("[kill]").on_hover_text("close this connection").clicked()
+                            {
+                                conn.kill.notify_one();
+                                fwd.log.push(EventLevel::Info, format!("killed {}", conn.peer));
+                            }
+                            ui.end_row();
+                        }
+                    });
+            });
+        if !open {
+            self.show_connections = None;
+        }
+    }
+
+    fn render_import_dialog(&mut self, ui: &mut egui::Ui) {
+        let mut do_import = false;
+
+        egui::Window::new("import forwards")
+            .collapsible(false)
+            .resizable(false)
+            .default_width(420.0)
+            .show(ui.ctx(), |ui| {
+                ui.colored_label(
+                    crate::theme::GREY,
+                    "// -L 8080:localhost:80   -R 0.0.0.0:9000:127.0.0.1:9000   -D 1080",
+                );
+                ui.colored_label(crate::theme::GREY, "// LocalForward 5432 db:5432");
+                ui.add(
+                    egui::TextEdit::multiline(&mut self.import_text)
+                        .desired_rows(5)
+                        .desired_width(f32::INFINITY)
+                        .code_editor(),
+                );
+                if let Some(err) = &self.import_error {
+                    ui.colored_label(crate::theme::RED, format!("ERR: {}", err));
+                }
+                ui.horizontal(|ui| {
+                    if ui
+                        .add_enabled(
+                            !self.import_text.trim().is_empty(),
+                            egui::Button::new("[import]"),
+                        )
+                        .clicked()
+                    {
+                        do_import = true;
+                    }
+                    if ui.button("[cancel]").clicked() {
+                        self.show_import_dialog = false;
+                    }
+                });
+            });
+
+        if do_import {
+            match parse_forward_specs(&self.import_text) {
+                Ok(rules) => {
+                    let count = rules.len();
+                    self.forwards.extend(rules.into_iter().map(ForwardEntry::new));
+                    self.rules_changed = true;
+                    self.show_import_dialog = false;
+                    self.status_message = Some(format!("{} rule(s) imported", count));
+                }
+                Err(e) => self.import_error = Some(e),
+            }
+        }
+    }
+
+    fn render_add_dialog(&mut self, ui: &mut egui::Ui) {
+        let mut do_add = false;
+        let mut apply_preset: Option<usize> = None;
+        let mut delete_preset: Option<usize> = None;
+        let mut save_preset = false;
+
+        egui::Window::new("add port forward")
+            .collapsible(false)
+            .resizable(false)
+            .default_width(400.0)
+            .show(ui.ctx(), |ui| {
+                egui::Grid::new("forward_dialog_grid")
+                    .num_columns(2)
+                    .spacing([10.0, 8.0])
+                    .show(ui, |ui| {
+                        if !self.presets.is_empty() {
+                            ui.label("preset:");
+                            ui.horizontal(|ui| {
+                                egui::ComboBox::from_id_salt("forward_preset")
+                                    .selected_text("choose...")
+                                    .show_ui(ui, |ui| {
+                                        for (i, preset) in self.presets.iter().enumerate() {
+                                            if ui
+                                                .selectable_label(false, &preset.name)
+                                                .on_hover_text(preset.rule.endpoints())
+                                                .clicked()
+                                            {
+                                                apply_preset = Some(i);
+                                            }
+                                        }
+                                    });
+                                ui.menu_button("[delete...]", |ui| {
+                                    for (i, preset) in self.presets.iter().enumerate() {
+                                        if ui.button(&preset.name).clicked() {
+                                            delete_preset = Some(i);
+                                            ui.close_menu();
+                                        }
+                                    }
+                                });
+                            });
+                            ui.end_row();
+                        }
+
+                        ui.label("label:");
+                        ui.add(
+                            egui::TextEdit::singleline(&mut self.new_label)
+                                .hint_text("prod DB (optional)"),
+                        );
+                        ui.end_row();
+
+                        ui.label("type:");
+                        ui.horizontal(|ui| {
+                            ui.radio_value(&mut self.new_forward_type, 0, "-L local");
+                            ui.radio_value(&mut self.new_forward_type, 1, "-R remote");
+                            ui.radio_value(&mut self.new_forward_type, 2, "-D socks5");
+                            ui.radio_value(&mut self.new_forward_type, 3, "http proxy");
+                        });
+                        ui.end_row();
+
+                        // У -R локальная сторона — куда подключаться, а слушает сервер
+                        let remote = self.new_forward_type == 1;
+                        ui.label(if remote { "local host:" } else { "bind host:" });
+                        ui.add(
+                            egui::TextEdit::singleline(&mut self.new_local_host)
+                                .hint_text("127.0.0.1"),
+                        );
+                        ui.end_row();
+
+                        ui.label(if remote { "local port:" } else { "bind port:" });
+                        ui.horizontal(|ui| {
+                            ui.add(
+                                egui::TextEdit::singleline(&mut self.new_local_port)
+                                    .desired_width(80.0)
+                                    .hint_text(match self.new_forward_type {
+                                        2 => "1080",
+                                        3 => "3128",
+                                        _ => "8080",
+                                    }),
+                            );
+                            if let Some(free) = self.new_port_busy(ui.ctx()) {
+                                ui.colored_label(crate::theme::RED, "in use");
+                                if let Some(free) = free {
+                                    if ui
+                                        .small_button(format!("[use {}]", free))
+                                        .on_hover_text("use the next free port")
+                                        .clicked()
+                                    {
+                                        self.new_local_port = free.to_string();
+                                    }
+                                }
+                            }
+                        });
+                        ui.end_row();
+
+                        if remote {
+                            ui.label("server port:");
+                            ui.add(
+                                egui::TextEdit::singleline(&mut self.new_remote_port)
+                                    .hint_text("9000"),
+                            );
+                            ui.end_row();
+
+                            ui.label("");
+                            ui.checkbox(&mut self.new_gateway, "listen on all server interfaces")
+                                .on_hover_text(
+                                    "binds 0.0.0.0 instead of loopback; sshd must allow it with \
+                                     GatewayPorts yes or clientspecified, otherwise it listens \
+                                     on loopback only or refuses",
+                                );
+                            ui.end_row();
+                        } else if self.new_forward_type == 0 {
+                            ui.label("dest host:");
+                            ui.add(
+                                egui::TextEdit::singleline(&mut self.new_remote_host)
+                                    .hint_text("localhost"),
+                            );
+                            ui.end_row();
+
+                            ui.label("dest port:");
+                            ui.add(
+                                egui::TextEdit::singleline(&mut self.new_remote_port)
+                                    .hint_text("5432"),
+                            );
+                            ui.end_row();
+                        } else if self.new_forward_type == 2 {
+                            ui.label("");
+                            ui.checkbox(&mut self.new_socks_login, "require login")
+                                .on_hover_text(
+                                    "SOCKS5 username/password; clients without it are refused",
+                                );
+                            ui.end_row();
+
+                            if self.new_socks_login {
+                                ui.label("user:");
+                                ui.text_edit_singleline(&mut self.new_socks_user);
+                                ui.end_row();
+
+                                ui.label("password:");
+                                ui.add(
+                                    egui::TextEdit::singleline(&mut self.new_socks_password)
+                                        .password(true),
+                                )
+                                .on_hover_text(
+                                    "kept in memory only; asked again after restart",
+                                );
+                                ui.end_row();
+                            }
+                        }
+
+                        ui.label("idle timeout:");
+                        ui.horizontal(|ui| {
+                            ui.add(
+                                egui::TextEdit::singleline(&mut self.new_idle_minutes)
+                                    .desired_width(50.0)
+                                    .hint_text("off"),
+                            )
+                            .on_hover_text("close connections without traffic for N minutes");
+                            ui.label("min");
+                            ui.add_enabled(
+                                !self.new_idle_minutes.is_empty(),
+                                egui::Checkbox::new(&mut self.new_idle_stop, "then stop the rule"),
+                            )
+                            .on_hover_text("stop listening once no connections are left");
+                        });
+                        ui.end_row();
+
+                        ui.label("rate limit:");
+                        ui.add(
+                            egui::DragValue::new(&mut self.new_rate_limit_kib)
+                                .range(0..=1_048_576)
+                                .speed(16)
+                                .suffix(" KiB/s"),
+                        )
+                        .on_hover_text(
+                            "each direction, shared by all connections of the rule; 0 — unlimited",
+                        );
+                        ui.end_row();
+
+                        ui.label("");
+                        ui.checkbox(&mut self.new_auto_start, "auto-start on connect");
+                        ui.end_row();
+                    });
+
+                ui.add_space(4.0);
+                ui.separator();
+                ui.add_space(4.0);
+
+                let local_str = format!(
+                    "{}:{}",
+                    if self.new_local_host.is_empty() { "..." } else { &self.new_local_host },
+                    if self.new_local_port.is_empty() { "..." } else { &self.new_local_port },
+                );
+                let remote_str = format!(
+                    "{}:{}",
+                    if self.new_remote_host.is_empty() { "..." } else { &self.new_remote_host },
+                    if self.new_remote_port.is_empty() { "..." } else { &self.new_remote_port },
+                );
+
+                let description = match self.new_forward_type {
+                    0 => format!("{} -> ssh -> {}", local_str, remote_str),
+                    1 => format!(
+                        "{} <- ssh <- {}:{}",
+                        local_str,
+                        if self.new_gateway { "0.0.0.0" } else { "localhost" },
+                        if self.new_remote_port.is_empty() { "..." } else { &self.new_remote_port },
+                    ),
+                    2 => format!("socks5 proxy on {}", local_str),
+                    _ => format!("http proxy on {} (CONNECT, http://)", local_str),
+                };
+                ui.colored_label(crate::theme::GREEN_DIM, &description);
+
+                // Туннель наружу: запрет из [options] или явное подтверждение
+                let exposed = self.dialog_rule().exposed_address();
+                if let Some(address) = &exposed {
+                    ui.add_space(4.0);
+                    ui.colored_label(
+                        crate::theme::RED,
+                        format!(
+                            "!! listens on {}: anyone who can reach it uses your ssh tunnel",
+                            address
+                        ),
+                    );
+                    if self.settings.loopback_only {
+                        ui.colored_label(
+                            crate::theme::RED,
+                            "forbidden: [options] allow loopback only",
+                        );
+                    } else {
+                        ui.checkbox(
+                            &mut self.new_expose_confirmed,
+                            "I understand, expose it to the network",
+                        );
+                    }
+                }
+                if exposed.is_none() {
+                    self.new_expose_confirmed = false;
+                }
+                let expose_ok = exposed.is_none()
+                    || (self.new_expose_confirmed && !self.settings.loopback_only);
+
+                ui.add_space(4.0);
+
+                ui.horizontal(|ui| {
+                    let local_port_ok = self.new_local_port.parse::<u16>().is_ok();
+                    let idle = self.new_idle_minutes.trim();
+                    let idle_ok = idle.is_empty() || idle.parse::<u32>().is_ok();
+                    let rule_ok = idle_ok && match self.new_forward_type {
+                        2 => {
+                            // RFC 1929: логин и пароль — от 1 до 255 байт
+                            let login_ok = !self.new_socks_login
+                                || [&self.new_socks_user, &self.new_socks_password]
+                                    .iter()
+                                    .all(|s| (1..=255).contains(&s.len()));
+                            local_port_ok && login_ok && !self.new_local_host.is_empty()
+                        }
+                        3 => local_port_ok && !self.new_local_host.is_empty(),
+                        1 => {
+                            local_port_ok
+                                && self.new_remote_port.parse::<u16>().is_ok()
+                                && !self.new_local_host.is_empty()
+                        }
+                        _ => {
+                            let remote_port_ok = self.new_remote_port.parse::<u16>().is_ok();
+                            local_port_ok
+                                && remote_port_ok
+                                && !self.new_local_host.is_empty()
+                                && !self.new_remote_host.is_empty()
+                        }
+                    };
+                    let can_add = rule_ok && expose_ok && self.new_port_busy(ui.ctx()).is_none();
+
+                    if ui
+                        .add_enabled(can_add, egui::Button::new("[start]"))
+                        .clicked()
+                    {
+                        do_add = true;
+                    }
+                    if ui.button("[cancel]").clicked() {
+                        self.show_add_dialog = false;
+                    }
+
+                    ui.separator();
+                    ui.add(
+                        egui::TextEdit::singleline(&mut self.new_preset_name)
+                            .desired_width(110.0)
+                            .hint_text("preset name"),
+                    );
+                    if ui
+                        .add_enabled(
+                            rule_ok && !self.new_preset_name.trim().is_empty(),
+                            egui::Button::new("[save preset]"),
+                        )
+                        .on_hover_text("same name replaces the preset")
+                        .clicked()
+                    {
+                        save_preset = true;
+                    }
+                });
+            });
+
+        if let Some(i) = apply_preset {
+            let rule = self.presets[i].rule.clone();
+            self.load_dialog_rule(&rule);
+        }
+        if let Some(i) = delete_preset {
+            let name = self.presets.remove(i).name;
+            crate::config::forwards::save_presets(&self.presets);
+            self.status_message = Some(format!("preset '{}' deleted", name));
+        }
+        if save_preset {
+            let name = self.new_preset_name.trim().to_string();
+            let preset = ForwardPreset {
+                name: name.clone(),
+                rule: self.dialog_rule(),
+            };
+            match self.presets.iter_mut().find(|p| p.name == name) {
+                Some(existing) => *existing = preset,
+                None => self.presets.push(preset),
+            }
+            crate::config::forwards::save_presets(&self.presets);
+            self.new_preset_name.clear();
+            self.status_message = Some(format!("preset '{}' saved", name));
+        }
+
+        if do_add {
+            let mut rule = self.dialog_rule();
+            // Галочка диалога — то же подтверждение, что и окно при запуске
+            rule.exposure_confirmed = rule.exposed_address();
+            self.forwards.push(ForwardEntry::new(rule));
+            self.rules_changed = true;
+            self.start_forward(self.forwards.len() - 1);
+            self.show_add_dialog = false;
+            self.status_message = Some("forward started".to_string());
+        }
+    }
+
+    /// Правило из полей диалога добавления.
+    fn dialog_rule(&self) -> ForwardRule {
+        let forward_type = match self.new_forward_type {
+            0 => ForwardType::Local,
+            1 => ForwardType::Remote,
+            2 => ForwardType::Dynamic,
+            _ => ForwardType::Http,
+        };
+        ForwardRule {
+            label: self.new_label.trim().to_string(),
+            forward_type: forward_type.clone(),
+            local_host: self.new_local_host.clone(),
+            local_port: self.new_local_port.parse().unwrap_or(0),
+            remote_host: match forward_type {
+                ForwardType::Remote if self.new_gateway => "0.0.0.0".to_string(),
+                ForwardType::Remote => "localhost".to_string(),
+                _ => self.new_remote_host.clone(),
+            },
+            remote_port: self.new_remote_port.parse().unwrap_or(0),
+            auto_start: self.new_auto_start,
+            idle_minutes: self.new_idle_minutes.trim().parse().unwrap_or(0),
+            idle_stop: self.new_idle_stop && !self.new_idle_minutes.is_empty(),
+            rate_limit_kib: self.new_rate_limit_kib,
+            socks_auth: (forward_type == ForwardType::Dynamic && self.new_socks_login).then(|| {
+                SocksAuth {
+                    user: self.new_socks_user.clone(),
+                    password: self.new_socks_password.clone(),
+                }
+            }),
+            exposure_confirmed: None,
+        }
+    }
+
+    /// Заполняет поля диалога из шаблона.
+    fn load_dialog_rule(&mut self, rule: &ForwardRule) {
+        self.new_label = rule.label.clone();
+        self.new_forward_type = match rule.forward_type {
+            ForwardType::Local => 0,
+            ForwardType::Remote => 1,
+            ForwardType::Dynamic => 2,
+            ForwardType::Http => 3,
+        };
+        self.new_local_host = rule.local_host.clone();
+        self.new_local_port = rule.local_port.to_string();
+        self.new_remote_host = rule.remote_host.clone();
+        self.new_remote_port = rule.remote_port.to_string();
+        self.new_auto_start = rule.auto_start;
+        self.new_idle_minutes = match rule.idle_minutes {
+            0 => String::new(),
+            minutes => minutes.to_string(),
+        };
+        self.new_idle_stop = rule.idle_stop;
+        self.new_rate_limit_kib = rule.rate_limit_kib;
+        self.new_gateway = rule.forward_type == ForwardType::Remote && rule.remote_public();
+        if self.new_forward_type == 1 {
+            self.new_remote_host = "localhost".to_string();
+        }
+        self.new_socks_login = rule.socks_auth.is_some();
+        let auth = rule.socks_auth.clone().unwrap_or(SocksAuth {
+            user: String::new(),
+            password: String::new(),
+        });
+        self.new_socks_user = auth.user;
+        self.new_socks_password = auth.password;
+    }
+
+    /// Some, если локальный порт из диалога занят (внутри — ближайший свободный).
+    fn new_port_busy(&mut self, ctx: &egui::Context) -> PortBusy {
+        if self.new_forward_type == 1 {
+            return None;
+        }
+        let port = self.new_local_port.parse::<u16>().ok()?;
+        self.new_port_checks.busy(ctx, &self.new_local_host, port)
+    }
+}
+
+/// Сессия закрыта — её перенаправления тоже.
+impl Drop for PortForwarder {
+    fn drop(&mut self) {
+        for index in 0..self.forwards.len() {
+            self.stop_forward(index);
+        }
+    }
+}
+
+// ── Local Port Forwarding (-L) ──
+
+async fn run_local_forward_async(
+    config: &SessionConfig,
+    rule: &ForwardRule,
+    alive: &AtomicBool,
+    paused: &AtomicBool,
+    stats: &Arc<ForwardStats>,
+) -> Result<(), Box<dyn std::error::Error + Send + Sync>> {
+    let listener = bind_local(rule, stats).await?;
+    let session = Arc::new(create_russh_session(config, SshHandler::new()).await?);
+
+    while alive.load(Ordering::Relaxed) {
+        let accept = tokio::time::timeout(std::time::Duration::from_millis(500), listener.accept())
+            .await;
+
+        match accept {
+            Ok(Ok(_)) if paused.load(Ordering::Relaxed) => continue, // закрываем сразу
+            Ok(Ok((stream, peer))) => {
+                let session = session.clone();
+                let host = rule.remote_host.clone();
+                let port = rule.remote_port;
+                let conn = stats.open(peer.to_string(), format!("{}:{}", host, port));
+
+                tokio::spawn(async move {
+                    if let Err(e) = relay_direct_tcpip(session, stream, &host, port, &conn).await {
+                        conn.log_error(e);
+                    }
+                });
+            }
+            Ok(Err(_)) => break,
+            Err(_) => continue, // timeout — проверяем alive
+        }
+    }
+
+    Ok(())
+}
+
+async fn relay_direct_tcpip(
+    session: Arc<russh::client::Handle<SshHandler>>,
+    local_stream: tokio::net::TcpStream,
+    remote_host: &str,
+    remote_port: u16,
+    conn: &ConnectionGuard,
+) -> Result<(), Box<dyn std::error::Error + Send + Sync>> {
+    let channel = session
+        .channel_open_direct_tcpip(remote_host, remote_port as u32, "127.0.0.1", 0)
+        .await?;
+
+    relay(channel, local_stream, conn).await;
+    Ok(())
+}
+
+/// Перекачка между SSH-каналом и TCP-сокетом в обе стороны, пока одна не закроется
+/// или соединение не убьют из таблицы.
+async fn relay(
+    channel: russh::Channel<russh::client::Msg>,
+    tcp: tokio::net::TcpStream,
+    conn: &ConnectionGuard,
+) {
+    relay_prefixed(channel, tcp, &[], conn).await;
+}
+
+/// Как relay, но сначала отправляет в канал уже прочитанные из сокета байты.
+async fn relay_prefixed(
+    channel: russh::Channel<russh::client::Msg>,
+    tcp: tokio::net::TcpStream,
+    prefix: &[u8],
+    conn: &ConnectionGuard,
+) {
+    conn.log_open();
+    let sent = [&conn.stats.sent, &conn.live.sent];
+    let received = [&conn.stats.received, &conn.live.received];
+    let mut channel_stream = channel.into_stream();
+    if !prefix.is_empty() {
+        if channel_stream.write_all(prefix).await.is_err() {
+            return;
+        }
+        for counter in sent {
+            counter.fetch_add(prefix.len() as u64, Ordering::Relaxed);
+        }
+    }
+    let (mut ch_read, mut ch_write) = tokio::io::split(channel_stream);
+    let (mut tcp_read, mut tcp_write) = tcp.into_split();
+
+    tokio::select! {
+        r = copy_counted(&mut ch_read, &mut tcp_write, &received, &conn.stats.limit_received) => {
+            let _ = r;
+        }
+        r = copy_counted(&mut tcp_read, &mut ch_write, &sent, &conn.stats.limit_sent) => {
+            let _ = r;
+        }
+        _ = conn.live.kill.notified() => {}
+        _ = idle_watch(&conn.live, conn.stats.idle) => {
+            conn.stats
+                .log
+                .push(EventLevel::Info, format!("{}: idle, closed", conn.live.peer));
+        }
+    }
+}
+
+/// Завершается, когда по соединению `idle` не было трафика; без тайм-аута — никогда.
+async fn idle_watch(live: &LiveConnection, idle: Option<Duration>) {
+    let Some(idle) = idle else {
+        return std::future::pending().await;
+    };
+    let total = || live.sent.load(Ordering::Relaxed) + live.received.load(Ordering::Relaxed);
+    let mut last = (total(), Instant::now());
+    loop {
+        tokio::time::sleep(idle.min(Duration::from_secs(5))).await;
+        let now = total();
+        if now != last.0 {
+            last = (now, Instant::now());
+        } else if last.1.elapsed() >= idle {
+            return;
+        }
+    }
+}
+
+/// tokio::io::copy, но каждый кусок сразу попадает в счётчики правила и соединения
+/// и проходит через предел скорости правила.
+async fn copy_counted<R, W>(
+    reader: &mut R,
+    writer: &mut W,
+    counters: &[&AtomicU64],
+    limiter: &RateLimiter,
+) -> std::io::Result<u64>
+where
+    R: tokio::io::AsyncRead + Unpin,
+    W: tokio::io::AsyncWrite + Unpin,
+{
+    let mut buf = vec![0u8; 32 * 1024];
+    let mut total = 0;
+    loop {
+        let len = limiter.chunk_len().min(buf.len());
+        let n = reader.read(&mut buf[..len]).await?;
+        if n == 0 {
+            writer.flush().await?;
+            return Ok(total);
+        }
+        limiter.acquire(n).await;
+        writer.write_all(&buf[..n]).await?;
+        for counter in counters {
+            counter.fetch_add(n as u64, Ordering::Relaxed);
+        }
+        total += n as u64;
+    }
+}
+
+// ── Remote Port Forwarding (-R) ──
+
+async fn run_remote_forward_async(
+    config: &SessionConfig,
+    rule: &ForwardRule,
+    alive: &AtomicBool,
+    paused: &AtomicBool,
+    stats: &Arc<ForwardStats>,
+) -> Result<(), Box<dyn std::error::Error + Send + Sync>> {
+    let (tx, mut rx) = tokio::sync::mpsc::unbounded_channel();
+    let mut session = create_russh_session(config, SshHandler::with_forwarded_tx(tx)).await?;
+
+    // Запрашиваем remote forwarding у SSH-сервера
+    if let Err(e) = session
+        .tcpip_forward(&rule.remote_host, rule.remote_port as u32)
+        .await
+    {
+        if rule.remote_public() {
+            return Err(format!(
+                "сервер отказал в {}:{} ({}); для всех интерфейсов в sshd_config \
+                 нужен GatewayPorts yes или clientspecified",
+                rule.remote_host, rule.remote_port, e
+            )
+            .into());
+        }
+        return Err(e.into());
+    }
+    stats.log.push(
+        EventLevel::Info,
+        format!("server listening on {}:{}", rule.remote_host, rule.remote_port),
+    );
+
+    let local_host = rule.local_host.clone();
+    let local_port = rule.local_port;
+
+    while alive.load(Ordering::Relaxed) {
+        let channel_opt = tokio::time::timeout(std::time::Duration::from_millis(500), rx.recv())
+            .await;
+
+        match channel_opt {
+            Ok(Some(forwarded)) if paused.load(Ordering::Relaxed) => {
+                let _ = forwarded.channel.close().await;
+            }
+            Ok(Some(forwarded)) => {
+                let host = local_host.clone();
+                let conn = stats.open(
+                    format!("{}:{}", forwarded.originator_address, forwarded.originator_port),
+                    format!("{}:{}", host, local_port),
+                );
+
+                tokio::spawn(async move {
+                    let result =
+                        relay_forwarded_channel(forwarded.channel, &host, local_port, &conn).await;
+                    if let Err(e) = result {
+                        conn.log_error(e);
+                    }
+                });
+            }
+            Ok(None) => break,
+            Err(_) => continue,
+        }
+    }
+
+    // Отменяем remote forwarding
+    let _ = session
+        .cancel_tcpip_forward(&rule.remote_host, rule.remote_port as u32)
+        .await;
+
+    Ok(())
+}
+
+async fn relay_forwarded_channel(
+    channel: russh::Channel<russh::client::Msg>,
+    local_host: &str,
+    local_port: u16,
+    conn: &ConnectionGuard,
+) -> Result<(), Box<dyn std::error::Error + Send + Sync>> {
+    let local_stream =
+        tokio::net::TcpStream::connect(format!("{}:{}", local_host, local_port)).await?;
+
+    relay(channel, local_stream, conn).await;
+    Ok(())
+}
+
+// ── Проверка правила ([test]) ──
+
+/// Сколько ждать ответа пробного соединения
+const PROBE_TIMEOUT: Duration = Duration::from_secs(10);
+
+/// Итог [test]: задержка ответа (канала до цели или прокси) или ошибка.
+struct Probe {
+    result: Arc<parking_lot::Mutex<Option<Result<Duration, String>>>>,
+}
+
+impl Probe {
+    fn show(&self, ui: &mut egui::Ui) {
+        match &*self.result.lock() {
+            None => {
+                ui.spinner();
+            }
+            Some(Ok(latency)) => {
+                ui.colored_label(crate::theme::GREEN, format!("OK {} ms", latency.as_millis()));
+            }
+            Some(Err(e)) => {
+                ui.colored_label(crate::theme::RED, "FAIL").on_hover_text(e);
+            }
+        }
+    }
+}
+
+/// Пробное соединение через слушателя правила. -D и HTTP проверяют только ответ
+/// самого прокси (рукопожатие SOCKS5 / OPTIONS *), -R — только локальную цель —
+/// слушатель на сервере подтверждён запуском правила.
+async fn probe_rule(rule: &ForwardRule, stats: &ForwardStats) -> Result<Duration, String> {
+    if rule.forward_type == ForwardType::Remote {
+        let started = Instant::now();
+        tokio::net::TcpStream::connect(format!("{}:{}", rule.local_host, rule.local_port))
+            .await
+            .map_err(|e| format!("local target: {}", e))?;
+        return Ok(started.elapsed());
+    }
+
+    let connected = Instant::now();
+    let mut stream = tokio::net::TcpStream::connect(proxy_authority(rule))
+        .await
+        .map_err(|e| format!("listener: {}", e))?;
+    let me = stream.local_addr().map_err(|e| e.to_string())?.to_string();
+    let io = |e: std::io::Error| e.to_string();
+
+    // Прокси: цель подключения выбирает клиент, своей у правила нет — проверяем,
+    // что прокси отвечает и пускает, никуда дальше не подключаясь
+    match rule.forward_type {
+        ForwardType::Dynamic => {
+            let started = Instant::now();
+            let method = if rule.socks_auth.is_some() { 0x02 } else { 0x00 };
+            stream.write_all(&[0x05, 0x01, method]).await.map_err(io)?;
+            let mut reply = [0u8; 2];
+            stream.read_exact(&mut reply).await.map_err(io)?;
+            if reply[1] != method {
+                return Err("SOCKS5 method refused".to_string());
+            }
+            if let Some(auth) = &rule.socks_auth {
+                let mut login = vec![0x01, auth.user.len() as u8];
+                login.extend_from_slice(auth.user.as_bytes());
+                login.push(auth.password.len() as u8);
+                login.extend_from_slice(auth.password.as_bytes());
+                stream.write_all(&login).await.map_err(io)?;
+                stream.read_exact(&mut reply).await.map_err(io)?;
+                if reply[1] != 0x00 {
+                    return Err("SOCKS5 login refused".to_string());
+                }
+            }
+            Ok(started.elapsed())
+        }
+        ForwardType::Http => {
+            let started = Instant::now();
+            stream
+                .write_all(b"OPTIONS * HTTP/1.1\r\nHost: proxy\r\n\r\n")
+                .await
+                .map_err(io)?;
+            let mut buf = [0u8; 256];
+            let n = stream.read(&mut buf).await.map_err(io)?;
+            let head = String::from_utf8_lossy(&buf[..n]);
+            let status = head.lines().next().unwrap_or_default().to_string();
+            if status.split_whitespace().nth(1) != Some("200") {
+                return Err(probe_error(stats, &me)
+                    .await
+                    .unwrap_or_else(|| format!("proxy answered '{}'", status)));
+            }
+            Ok(started.elapsed())
+        }
+        // -L: канал открывается после accept; ждём, пока наше соединение
+        // начнёт перекачку или запишет ошибку
+        _ => loop {
+            let relaying = stats
+                .live
+                .lock()
+                .iter()
+                .find(|c| c.peer == me)
+                .map(|c| c.relaying.load(Ordering::Relaxed));
+            if relaying == Some(true) {
+                return Ok(connected.elapsed());
+            }
+            if let Some(error) = logged_error(stats, &me) {
+                return Err(error);
+            }
+            tokio::time::sleep(Duration::from_millis(10)).await;
+        },
+    }
+}
+
+/// Ошибка пробного соединения из журнала; запись может отстать от ответа клиенту.
+async fn probe_error(stats: &ForwardStats, peer: &str) -> Option<String> {
+    for _ in 0..20 {
+        if let Some(error) = logged_error(stats, peer) {
+            return Some(error);
+        }
+        tokio::time::sleep(Duration::from_millis(10)).await;
+    }
+    None
+}
+
+fn logged_error(stats: &ForwardStats, peer: &str) -> Option<String> {
+    let prefix = format!("{}: ", peer);
+    stats
+        .log
+        .events
+        .lock()
+        .iter()
+        .rev()
+        .filter(|e| e.level == EventLevel::Error)
+        .find_map(|e| e.text.strip_prefix(&prefix).map(str::to_string))
+}
@@ -0,0 +1,381 @@
+use super::*;
+
+// ── Dynamic Port Forwarding / SOCKS5 (-D) ──
+
+/// tcpip-forward нужен &mut; обычные CONNECT открывают каналы под read-блокировкой.
+type SharedSession = Arc<tokio::sync::RwLock<russh::client::Handle<SshHandler>>>;
+
+/// Сколько BIND ждёт входящего соединения
+const SOCKS_BIND_TIMEOUT: Duration = Duration::from_secs(120);
+
+/// Ожидающие BIND: порт на сервере -> получатель входящего соединения.
+struct BindContext {
+    routes: parking_lot::Mutex<HashMap<u32, BindRoute>>,
+    /// Адрес сервера, каким его видит sshd; спрашивается при первом BIND
+    server_ip: tokio::sync::OnceCell<Option<IpAddr>>,
+}
+
+/// Ожидание входящего соединения на порт BIND.
+struct BindRoute {
+    /// От кого ждём (DST.ADDR запроса); пусто — от любого
+    expected: Vec<IpAddr>,
+    tx: tokio::sync::oneshot::Sender<ForwardedChannel>,
+}
+
+/// Третье поле $SSH_CONNECTION — адрес, на котором sshd принял нас. Локальный
+/// резолв имени сервера за прокси, jump-хостом или пробросом дал бы не тот адрес.
+async fn server_address(session: &SharedSession) -> Option<IpAddr> {
+    let query = async {
+        let mut channel = session.read().await.channel_open_session().await.ok()?;
+        channel.exec(true, "echo $SSH_CONNECTION").await.ok()?;
+        let mut output = Vec::new();
+        while let Some(msg) = channel.wait().await {
+            match msg {
+                russh::ChannelMsg::Data { ref data } => output.extend_from_slice(data),
+                russh::ChannelMsg::Close => break,
+                _ => {}
+            }
+        }
+        String::from_utf8_lossy(&output)
+            .split_whitespace()
+            .nth(2)?
+            .parse()
+            .ok()
+    };
+    tokio::time::timeout(Duration::from_secs(5), query).await.ok().flatten()
+}
+
+pub(super) async fn run_dynamic_forward_async(
+    config: &SessionConfig,
+    rule: &ForwardRule,
+    alive: &AtomicBool,
+    paused: &AtomicBool,
+    stats: &Arc<ForwardStats>,
+) -> Result<(), Box<dyn std::error::Error + Send + Sync>> {
+    let listener = bind_local(rule, stats).await?;
+    let (tx, mut rx) = tokio::sync::mpsc::unbounded_channel();
+    let session = create_russh_session(config, SshHandler::with_forwarded_tx(tx)).await?;
+    let session: SharedSession = Arc::new(tokio::sync::RwLock::new(session));
+    let bind = Arc::new(BindContext {
+        routes: parking_lot::Mutex::new(HashMap::new()),
+        server_ip: tokio::sync::OnceCell::new(),
+    });
+
+    while alive.load(Ordering::Relaxed) {
+        tokio::select! {
+            accept = listener.accept() => {
+                let Ok((stream, peer)) = accept else { break };
+                if paused.load(Ordering::Relaxed) {
+                    continue; // закрываем сразу
+                }
+                let session = session.clone();
+                let bind = bind.clone();
+                let auth = rule.socks_auth.clone();
+                let conn = stats.open(peer.to_string(), String::new());
+
+                tokio::spawn(async move {
+                    let result =
+                        handle_socks5_client(session, stream, auth.as_ref(), &bind, &conn).await;
+                    if let Err(e) = result {
+                        conn.log_error(e);
+                    }
+                });
+            }
+            Some(forwarded) = rx.recv() => {
+                // Первое соединение от ожидаемого пира; чужие и лишние закрываем
+                let route = {
+                    let mut routes = bind.routes.lock();
+                    let expected = routes.get(&forwarded.connected_port).is_some_and(|r| {
+                        r.expected.is_empty()
+                            || forwarded
+                                .originator_address
+                                .parse::<IpAddr>()
+                                .is_ok_and(|ip| r.expected.contains(&ip))
+                    });
+                    if expected {
+                        routes.remove(&forwarded.connected_port)
+                    } else {
+                        None
+                    }
+                };
+                match route {
+                    Some(route) => {
+                        let _ = route.tx.send(forwarded);
+                    }
+                    None => {
+                        let _ = forwarded.channel.close().await;
+                    }
+                }
+            }
+            _ = tokio::time::sleep(Duration::from_millis(500)) => {} // проверяем alive
+        }
+    }
+
+    Ok(())
+}
+
+/// Подпереговоры RFC 1929: версия 1, логин и пароль с длинами в байт.
+async fn socks5_login(
+    stream: &mut tokio::net::TcpStream,
+    auth: &SocksAuth,
+) -> Result<(), Box<dyn std::error::Error + Send + Sync>> {
+    let mut version = [0u8; 1];
+    stream.read_exact(&mut version).await?;
+    if version[0] != 0x01 {
+        return Err("SOCKS5: неверная версия аутентификации".into());
+    }
+    let user = read_socks_field(stream).await?;
+    let password = read_socks_field(stream).await?;
+    let ok = user == auth.user.as_bytes() && password == auth.password.as_bytes();
+    stream.write_all(&[0x01, if ok { 0x00 } else { 0x01 }]).await?;
+    if !ok {
+        return Err("SOCKS5: неверный логин или пароль".into());
+    }
+    Ok(())
+}
+
+/// Байт длины и сама строка.
+async fn read_socks_field(stream: &mut tokio::net::TcpStream) -> std::io::Result<Vec<u8>> {
+    let mut len = [0u8; 1];
+    stream.read_exact(&mut len).await?;
+    let mut field = vec![0u8; len[0] as usize];
+    stream.read_exact(&mut field).await?;
+    Ok(field)
+}
+
+/// Ответ SOCKS5 с адресом; None — 0.0.0.0:0.
+fn socks5_reply(code: u8, addr: Option<SocketAddr>) -> Vec<u8> {
+    let mut reply = vec![0x05, code, 0x00];
+    match addr {
+        Some(SocketAddr::V6(addr)) => {
+            reply.push(0x04);
+            reply.extend_from_slice(&addr.ip().octets());
+        }
+        Some(SocketAddr::V4(addr)) => {
+            reply.push(0x01);
+            reply.extend_from_slice(&addr.ip().octets());
+        }
+        None => reply.extend_from_slice(&[0x01, 0, 0, 0, 0]),
+    }
+    reply.extend_from_slice(&addr.map_or(0, |a| a.port()).to_be_bytes());
+    reply
+}
+
+/// SOCKS5 рукопожатие + relay через SSH direct-tcpip.
+async fn handle_socks5_client(
+    session: SharedSession,
+    mut stream: tokio::net::TcpStream,
+    auth: Option<&SocksAuth>,
+    bind: &BindContext,
+    conn: &ConnectionGuard,
+) -> Result<(), Box<dyn std::error::Error + Send + Sync>> {
+    // 1–2. Приветствие и запрос; [kill] действует и до открытия канала
+    let handshake = tokio::time::timeout(HANDSHAKE_TIMEOUT, socks5_handshake(&mut stream, auth));
+    let (req_header, dest_host, dest_port) = tokio::select! {
+        r = handshake => r.map_err(|_| "SOCKS5: клиент не закончил рукопожатие")??,
+        _ = conn.live.kill.notified() => return Ok(()),
+    };
+
+    if req_header[1] == 0x02 {
+        return socks5_bind(session, stream, bind, (&dest_host, dest_port), conn).await;
+    }
+    let destination = format!("{}:{}", dest_host, dest_port);
+    conn.stats.resolve.record(destination.clone(), req_header[3] == 0x03);
+    conn.set_destination(destination);
+
+    // 3. Открываем SSH-канал до целевого хоста
+    let open = async {
+        session
+            .read()
+            .await
+            .channel_open_direct_tcpip(&dest_host, dest_port as u32, "127.0.0.1", 0)
+            .await
+    };
+    let opened = tokio::select! {
+        r = open => r,
+        _ = conn.live.kill.notified() => return Ok(()),
+    };
+    let channel = match opened {
+        Ok(ch) => ch,
+        Err(e) => {
+            stream
+                .write_all(&[0x05, 0x05, 0x00, 0x01, 0, 0, 0, 0, 0, 0])
+                .await?;
+            return Err(format!(
+                "SSH direct-tcpip к {}:{} не удался: {}",
+                dest_host, dest_port, e
+            )
+            .into());
+        }
+    };
+
+    // 4. Ответ: успех
+    stream
+        .write_all(&[0x05, 0x00, 0x00, 0x01, 0, 0, 0, 0, 0, 0])
+        .await?;
+
+    // 5. Relay данных
+    relay(channel, stream, conn).await;
+    Ok(())
+}
+
+/// Приветствие, логин и запрос клиента: заголовок запроса и адрес назначения.
+async fn socks5_handshake(
+    stream: &mut tokio::net::TcpStream,
+    auth: Option<&SocksAuth>,
+) -> Result<([u8; 4], String, u16), Box<dyn std::error::Error + Send + Sync>> {
+    // 1. Greeting
+    let mut header = [0u8; 2];
+    stream.read_exact(&mut header).await?;
+    if header[0] != 0x05 {
+        return Err("Не SOCKS5".into());
+    }
+
+    let nmethods = header[1] as usize;
+    let mut methods = vec![0u8; nmethods];
+    stream.read_exact(&mut methods).await?;
+
+    // С логином — только метод 0x02, иначе без аутентификации (0x00)
+    let method = if auth.is_some() { 0x02 } else { 0x00 };
+    if !methods.contains(&method) {
+        stream.write_all(&[0x05, 0xFF]).await?;
+        return Err("Нет подходящего метода аутентификации".into());
+    }
+    stream.write_all(&[0x05, method]).await?;
+    if let Some(auth) = auth {
+        socks5_login(stream, auth).await?;
+    }
+
+    // 2. Request
+    let mut req_header = [0u8; 4];
+    stream.read_exact(&mut req_header).await?;
+    if req_header[0] != 0x05 || !matches!(req_header[1], 0x01 | 0x02) {
+        let reply = [0x05, 0x07, 0x00, 0x01, 0, 0, 0, 0, 0, 0];
+        stream.write_all(&reply).await?;
+        return Err("Команда не поддерживается (только CONNECT и BIND)".into());
+    }
+
+    let (dest_host, dest_port) = match req_header[3] {
+        0x01 => {
+            let mut addr = [0u8; 4];
+            stream.read_exact(&mut addr).await?;
+            let mut port_buf = [0u8; 2];
+            stream.read_exact(&mut port_buf).await?;
+            (
+                format!("{}.{}.{}.{}", addr[0], addr[1], addr[2], addr[3]),
+                u16::from_be_bytes(port_buf),
+            )
+        }
+        0x03 => {
+            let mut len_buf = [0u8; 1];
+            stream.read_exact(&mut len_buf).await?;
+            let mut domain = vec![0u8; len_buf[0] as usize];
+            stream.read_exact(&mut domain).await?;
+            let mut port_buf = [0u8; 2];
+            stream.read_exact(&mut port_buf).await?;
+            (
+                String::from_utf8_lossy(&domain).to_string(),
+                u16::from_be_bytes(port_buf),
+            )
+        }
+        0x04 => {
+            let mut addr = [0u8; 16];
+            stream.read_exact(&mut addr).await?;
+            let mut port_buf = [0u8; 2];
+            stream.read_exact(&mut port_buf).await?;
+            let segments: Vec<String> = (0..8)
+                .map(|i| format!("{:x}", u16::from_be_bytes([addr[i * 2], addr[i * 2 + 1]])))
+                .collect();
+            (segments.join(":"), u16::from_be_bytes(port_buf))
+        }
+        _ => {
+            stream
+                .write_all(&[0x05, 0x08, 0x00, 0x01, 0, 0, 0, 0, 0, 0])
+                .await?;
+            return Err("Неподдерживаемый тип адреса".into());
+        }
+    };
+
+    Ok((req_header, dest_host, dest_port))
+}
+
+/// BIND: слушает порт на SSH-сервере (tcpip-forward), сообщает его клиенту,
+/// ждёт одно входящее соединение и соединяет его с клиентом.
+/// Принимается только соединение с адреса из DST.ADDR (имя резолвится локально);
+/// остальные закрывает `run_dynamic_forward_async`. 0.0.0.0 или нерезолвящееся
+/// имя — принимается первый подключившийся.
+async fn socks5_bind(
+    session: SharedSession,
+    mut stream: tokio::net::TcpStream,
+    bind: &BindContext,
+    peer: (&str, u16),
+    conn: &ConnectionGuard,
+) -> Result<(), Box<dyn std::error::Error + Send + Sync>> {
+    let server_ip = *bind
+        .server_ip
+        .get_or_init(|| server_address(&session))
+        .await;
+    // Слушаем только на адресе, который сообщаем клиенту; не узнали его — везде
+    let address = server_ip.map_or("0.0.0.0".to_string(), |ip| ip.to_string());
+    let port = match session.write().await.tcpip_forward(&address, 0).await {
+        Ok(port) => port,
+        Err(e) => {
+            stream.write_all(&socks5_reply(0x01, None)).await?;
+            return Err(format!("SOCKS5 BIND: tcpip-forward отклонён: {}", e).into());
+        }
+    };
+    // DST.ADDR — от кого клиент ждёт соединения (RFC 1928); 0.0.0.0 — от любого
+    let expected: Vec<IpAddr> = match peer.0.parse::<IpAddr>() {
+        Ok(ip) if ip.is_unspecified() => Vec::new(),
+        Ok(ip) => vec![ip],
+        Err(_) => tokio::net::lookup_host(peer)
+            .await
+            .map(|addrs| addrs.map(|a| a.ip()).collect())
+            .unwrap_or_default(),
+    };
+    let (tx, rx) = tokio::sync::oneshot::channel();
+    bind.routes.lock().insert(port, BindRoute { expected, tx });
+    conn.set_destination(format!("BIND :{}", port));
+
+    // Первый ответ: где слушаем
+    let listening = server_ip.map(|ip| SocketAddr::new(ip, port as u16));
+    let written = stream.write_all(&socks5_reply(0x00, listening)).await;
+    let mut killed = false;
+    let forwarded = match written {
+        Ok(()) => tokio::select! {
+            r = tokio::time::timeout(SOCKS_BIND_TIMEOUT, rx) => r.ok().and_then(Result::ok),
+            _ = conn.live.kill.notified() => {
+                killed = true;
+                None
+            }
+        },
+        Err(_) => None,
+    };
+    // Слушатель на сервере снимаем при любом исходе, в том числе по [kill]
+    bind.routes.lock().remove(&port);
+    let _ = session.read().await.cancel_tcpip_forward(&address, port).await;
+    written?;
+    if killed {
+        return Ok(());
+    }
+
+    let Some(forwarded) = forwarded else {
+        let _ = stream.write_all(&socks5_reply(0x06, None)).await;
+        return Err("SOCKS5 BIND: входящее соединение не пришло".into());
+    };
+
+    // Второй ответ: кто подключился
+    let peer = forwarded
+        .originator_address
+        .parse::<IpAddr>()
+        .ok()
+        .map(|ip| SocketAddr::new(ip, forwarded.originator_port as u16));
+    stream.write_all(&socks5_reply(0x00, peer)).await?;
+    conn.set_destination(format!(
+        "BIND :{} <- {}:{}",
+        port, forwarded.originator_address, forwarded.originator_port
+    ));
+
+    relay(forwarded.channel, stream, conn).await;
+    Ok(())
+}
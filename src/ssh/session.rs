@@ -7,12 +7,83 @@ use russh::client;
 use russh::keys::{self, PrivateKeyWithHashAlg};
 use tokio::io::{AsyncReadExt, AsyncWriteExt};
 
+use crate::ssh::forward::ForwardRule;
+use crate::terminal::highlight::HighlightRule;
+use crate::terminal::keybindings::KeySend;
+use crate::terminal::palette::ColorScheme;
+
 #[derive(Clone, Serialize, Deserialize, Default)]
 pub struct ProxyConfig {
     pub host: String,
     pub port: u16,
 }
 
+/// Промежуточный SSH-сервер цепочки (как ProxyJump); пустой логин — логин сессии.
+#[derive(Clone, Serialize, Deserialize)]
+pub struct JumpHost {
+    pub username: String,
+    pub host: String,
+    pub port: u16,
+}
+
+impl JumpHost {
+    /// "user@host:port" через запятую, как у ssh -J.
+    pub fn parse_list(text: &str) -> Result<Vec<JumpHost>, String> {
+        text.split(',')
+            .map(str::trim)
+            .filter(|hop| !hop.is_empty())
+            .map(|hop| {
+                let (username, address) = match hop.rsplit_once('@') {
+                    Some((user, address)) => (user.to_string(), address),
+                    None => (String::new(), hop),
+                };
+                let (host, port) = match address.strip_prefix('[') {
+                    Some(rest) => match rest.split_once(']') {
+                        Some((host, "")) => (host, "22"),
+                        Some((host, port)) => (host, port.strip_prefix(':').unwrap_or(port)),
+                        None => return Err(format!("{}: unbalanced [ ]", hop)),
+                    },
+                    None => address.rsplit_once(':').unwrap_or((address, "22")),
+                };
+                let port = port
+                    .parse()
+                    .map_err(|_| format!("{}: bad port '{}'", hop, port))?;
+                if host.is_empty() {
+                    return Err(format!("{}: no host", hop));
+                }
+                Ok(JumpHost {
+                    username,
+                    host: host.to_string(),
+                    port,
+                })
+            })
+            .collect()
+    }
+
+    pub fn format_list(hops: &[JumpHost]) -> String {
+        hops.iter()
+            .map(|hop| {
+                let user = if hop.username.is_empty() {
+                    String::new()
+                } else {
+                    format!("{}@", hop.username)
+                };
+                let host = if hop.host.contains(':') {
+                    format!("[{}]", hop.host)
+                } else {
+                    hop.host.clone()
+                };
+                if hop.port == 22 {
+                    format!("{}{}", user, host)
+                } else {
+                    format!("{}{}:{}", user, host, hop.port)
+                }
+            })
+            .collect::<Vec<_>>()
+            .join(", ")
+    }
+}
+
 #[derive(Clone, Serialize, Deserialize)]
 pub struct SessionConfig {
     pub id: String,
@@ -23,10 +94,109 @@ pub struct SessionConfig {
     pub auth_type: AuthType,
     #[serde(default)]
     pub proxy: Option<ProxyConfig>,
+    /// Цепочка промежуточных серверов; через неё идут shell, SFTP и все перенаправления
+    #[serde(default)]
+    pub jump_hosts: Vec<JumpHost>,
+    #[serde(default)]
+    pub highlight_rules: Vec<HighlightRule>,
+    /// Уведомлять о командах дольше N секунд (0 — выключено, нужен OSC 133)
+    #[serde(default = "default_notify_after_secs")]
+    pub notify_after_secs: u32,
+    /// Свои сочетания клавиш → последовательности байт
+    #[serde(default)]
+    pub key_sends: Vec<KeySend>,
+    /// Цветовая схема терминала (None — глобальная phosphor)
+    #[serde(default)]
+    pub color_scheme: Option<ColorScheme>,
+    /// Символы-разделители слов для выделения двойным кликом (пробел — всегда)
+    #[serde(default = "default_word_separators")]
+    pub word_separators: String,
+    /// Вставка длиннее стольких символов спрашивает подтверждения (0 — только многострочная)
+    #[serde(default = "default_paste_confirm_chars")]
+    pub paste_confirm_chars: u32,
+    /// Сколько SFTP-передач идёт одновременно (каждая — в своём канале)
+    #[serde(default = "default_sftp_parallel")]
+    pub sftp_parallel: u32,
+    /// Ограничение скорости SFTP на сессию, KiB/s (0 — без ограничения)
+    #[serde(default)]
+    pub sftp_rate_limit_kib: u32,
+    /// Повторов SFTP-операции при обрыве канала/соединения (0 — не повторять)
+    #[serde(default = "default_sftp_retries")]
+    pub sftp_retries: u32,
+    /// Пауза перед первым повтором, сек; каждый следующий — вдвое дольше
+    #[serde(default = "default_sftp_retry_delay_secs")]
+    pub sftp_retry_delay_secs: u32,
+    /// Куда скачивает SFTP-вкладка; пусто — системная папка загрузок
+    #[serde(default)]
+    pub sftp_download_dir: String,
+    /// Права залитых файлов и созданных папок
+    #[serde(default)]
+    pub sftp_upload_perms: UploadPermissions,
+    /// Правила перенаправления портов (вкладка FWD)
+    #[serde(default)]
+    pub forward_rules: Vec<ForwardRule>,
     #[serde(skip)]
     pub key_passphrase: Option<String>,
 }
 
+pub fn default_notify_after_secs() -> u32 {
+    10
+}
+
+/// Разделители слов по умолчанию: `/ . - : @ ~ =` остаются частью слова,
+/// чтобы путь, URL или user@host выделялись одним двойным кликом.
+pub const DEFAULT_WORD_SEPARATORS: &str = "()[]{}<>'\"`,;|";
+
+pub fn default_word_separators() -> String {
+    DEFAULT_WORD_SEPARATORS.to_string()
+}
+
+pub fn default_paste_confirm_chars() -> u32 {
+    1024
+}
+
+pub fn default_sftp_parallel() -> u32 {
+    2
+}
+
+pub fn default_sftp_retries() -> u32 {
+    3
+}
+
+pub fn default_sftp_retry_delay_secs() -> u32 {
+    2
+}
+
+/// Права новых файлов и папок при заливке по SFTP (ставятся setstat после создания).
+#[derive(Clone, Copy, PartialEq, Eq, Serialize, Deserialize, Default)]
+pub enum UploadPermissions {
+    /// Как решит сервер (umask sftp-server)
+    #[default]
+    Server,
+    /// Как у локального источника
+    Preserve,
+    /// Одни и те же: файлам и папкам
+    Fixed { file: u32, dir: u32 },
+}
+
+impl UploadPermissions {
+    pub fn file_mode(self, local_mode: Option<u32>) -> Option<u32> {
+        match self {
+            UploadPermissions::Server => None,
+            UploadPermissions::Preserve => local_mode.map(|mode| mode & 0o7777),
+            UploadPermissions::Fixed { file, .. } => Some(file & 0o7777),
+        }
+    }
+
+    pub fn dir_mode(self, local_mode: Option<u32>) -> Option<u32> {
+        match self {
+            UploadPermissions::Server => None,
+            UploadPermissions::Preserve => local_mode.map(|mode| mode & 0o7777),
+            UploadPermissions::Fixed { dir, .. } => Some(dir & 0o7777),
+        }
+    }
+}
+
 #[derive(Clone, Serialize, Deserialize)]
 pub enum AuthType {
     Password(String),
@@ -37,6 +207,16 @@ pub enum AuthType {
 pub enum SshCommand {
     Data(Vec<u8>),
     Resize { cols: u32, rows: u32 },
+    /// Ещё один shell-канал на том же соединении (split pane)
+    OpenChannel(ChannelEndpoints),
+}
+
+/// Концы каналов для нового shell-канала — та же схема, что у SshConnection.
+pub struct ChannelEndpoints {
+    input_rx: mpsc::Receiver<SshCommand>,
+    output_tx: mpsc::Sender<Vec<u8>>,
+    alive: Arc<AtomicBool>,
+    error: Arc<parking_lot::Mutex<Option<String>>>,
 }
 
 pub struct SshConnection {
@@ -48,25 +228,35 @@ pub struct SshConnection {
 
 // ── russh client handler ──
 
+/// Входящее forwarded-tcpip соединение и откуда оно пришло.
+pub struct ForwardedChannel {
+    pub channel: russh::Channel<russh::client::Msg>,
+    /// Порт на сервере, на который подключились
+    pub connected_port: u32,
+    pub originator_address: String,
+    pub originator_port: u32,
+}
+
 pub struct SshHandler {
-    /// Канал для forwarded-tcpip (Remote Port Forward).
-    /// None для обычных shell/sftp/local/dynamic соединений.
-    pub forwarded_tx:
-        Option<tokio::sync::mpsc::UnboundedSender<russh::Channel<russh::client::Msg>>>,
+    /// Канал для forwarded-tcpip (Remote Port Forward, SOCKS5 BIND).
+    /// None для обычных shell/sftp/local соединений.
+    pub forwarded_tx: Option<tokio::sync::mpsc::UnboundedSender<ForwardedChannel>>,
+    /// Сессия предыдущего jump-хоста: живёт, пока живёт эта
+    upstream: Option<Box<client::Handle<SshHandler>>>,
 }
 
 impl SshHandler {
     pub fn new() -> Self {
         SshHandler {
             forwarded_tx: None,
+            upstream: None,
         }
     }
 
-    pub fn with_forwarded_tx(
-        tx: tokio::sync::mpsc::UnboundedSender<russh::Channel<russh::client::Msg>>,
-    ) -> Self {
+    pub fn with_forwarded_tx(tx: tokio::sync::mpsc::UnboundedSender<ForwardedChannel>) -> Self {
         SshHandler {
             forwarded_tx: Some(tx),
+            upstream: None,
         }
     }
 }
@@ -85,13 +275,18 @@ impl client::Handler for SshHandler {
         &mut self,
         channel: russh::Channel<russh::client::Msg>,
         _connected_address: &str,
-        _connected_port: u32,
-        _originator_address: &str,
-        _originator_port: u32,
+        connected_port: u32,
+        originator_address: &str,
+        originator_port: u32,
         _session: &mut client::Session,
     ) -> impl std::future::Future<Output = Result<(), Self::Error>> + Send {
         if let Some(tx) = &self.forwarded_tx {
-            let _ = tx.send(channel);
+            let _ = tx.send(ForwardedChannel {
+                channel,
+                connected_port,
+                originator_address: originator_address.to_string(),
+                originator_port,
+            });
         }
         async { Ok(()) }
     }
@@ -151,6 +346,53 @@ impl SshConnection {
     pub fn take_error(&self) -> Option<String> {
         self.error.lock().take()
     }
+
+    /// Локальная труба с интерфейсом SSH-канала — для терминалов, чей ввод/вывод
+    /// идёт не прямо в канал (панели tmux control mode).
+    /// Возвращает (соединение, куда приходит ввод, куда писать вывод).
+    pub fn local() -> (SshConnection, mpsc::Receiver<SshCommand>, mpsc::Sender<Vec<u8>>) {
+        let (input_tx, input_rx) = mpsc::channel::<SshCommand>();
+        let (output_tx, output_rx) = mpsc::channel::<Vec<u8>>();
+        let connection = SshConnection {
+            input_tx,
+            output_rx,
+            alive: Arc::new(AtomicBool::new(true)),
+            error: Arc::new(parking_lot::Mutex::new(None)),
+        };
+        (connection, input_rx, output_tx)
+    }
+
+    /// Открыть ещё один shell на том же SSH-соединении (без повторной аутентификации).
+    /// Канал живёт, пока жив возвращённый SshConnection и само соединение.
+    pub fn open_channel(&self) -> SshConnection {
+        let (input_tx, input_rx) = mpsc::channel::<SshCommand>();
+        let (output_tx, output_rx) = mpsc::channel::<Vec<u8>>();
+        let alive = Arc::new(AtomicBool::new(true));
+        let error: Arc<parking_lot::Mutex<Option<String>>> =
+            Arc::new(parking_lot::Mutex::new(None));
+
+        let endpoints = ChannelEndpoints {
+            input_rx,
+            output_tx,
+            alive: alive.clone(),
+            error: error.clone(),
+        };
+        if self
+            .input_tx
+            .send(SshCommand::OpenChannel(endpoints))
+            .is_err()
+        {
+            *error.lock() = Some("connection is closed".to_string());
+            alive.store(false, Ordering::Relaxed);
+        }
+
+        SshConnection {
+            input_tx,
+            output_rx,
+            alive,
+            error,
+        }
+    }
 }
 
 impl Drop for SshConnection {
@@ -161,20 +403,45 @@ impl Drop for SshConnection {
 
 // ── Основной async-цикл SSH-сессии ──
 
+type SessionHandle = Arc<client::Handle<SshHandler>>;
+
 async fn run_session_async(
     config: &SessionConfig,
     input_rx: mpsc::Receiver<SshCommand>,
     output_tx: mpsc::Sender<Vec<u8>>,
     alive: &AtomicBool,
 ) -> Result<(), Box<dyn std::error::Error + Send + Sync>> {
-    let session = create_russh_session(config, SshHandler::new()).await?;
+    let session: SessionHandle = Arc::new(create_russh_session(config, SshHandler::new()).await?);
+
+    let channel = open_shell_channel(&session).await?;
+    run_shell_channel(&session, channel, input_rx, output_tx, alive).await?;
 
-    let mut channel = session.channel_open_session().await?;
+    let _ = session
+        .disconnect(russh::Disconnect::ByApplication, "", "")
+        .await;
+
+    Ok(())
+}
+
+async fn open_shell_channel(
+    session: &SessionHandle,
+) -> Result<russh::Channel<client::Msg>, Box<dyn std::error::Error + Send + Sync>> {
+    let channel = session.channel_open_session().await?;
     channel
         .request_pty(true, "xterm-256color", 80, 24, 0, 0, &[])
         .await?;
     channel.request_shell(true).await?;
+    Ok(channel)
+}
 
+/// Цикл одного shell-канала: вывод → output_tx, input_rx → канал.
+async fn run_shell_channel(
+    session: &SessionHandle,
+    mut channel: russh::Channel<client::Msg>,
+    input_rx: mpsc::Receiver<SshCommand>,
+    output_tx: mpsc::Sender<Vec<u8>>,
+    alive: &AtomicBool,
+) -> Result<(), Box<dyn std::error::Error + Send + Sync>> {
     loop {
         if !alive.load(Ordering::Relaxed) {
             break;
@@ -208,6 +475,9 @@ async fn run_session_async(
                         SshCommand::Resize { cols, rows } => {
                             channel.window_change(cols, rows, 0, 0).await?;
                         }
+                        SshCommand::OpenChannel(endpoints) => {
+                            spawn_pane_channel(session.clone(), endpoints);
+                        }
                     }
                 }
             }
@@ -215,13 +485,32 @@ async fn run_session_async(
     }
 
     let _ = channel.close().await;
-    let _ = session
-        .disconnect(russh::Disconnect::ByApplication, "", "")
-        .await;
-
     Ok(())
 }
 
+fn spawn_pane_channel(session: SessionHandle, endpoints: ChannelEndpoints) {
+    let task: std::pin::Pin<Box<dyn std::future::Future<Output = ()> + Send>> =
+        Box::pin(async move {
+            let ChannelEndpoints {
+                input_rx,
+                output_tx,
+                alive,
+                error,
+            } = endpoints;
+            let result = match open_shell_channel(&session).await {
+                Ok(channel) => {
+                    run_shell_channel(&session, channel, input_rx, output_tx, &alive).await
+                }
+                Err(e) => Err(e),
+            };
+            if let Err(e) = result {
+                *error.lock() = Some(e.to_string());
+            }
+            alive.store(false, Ordering::Relaxed);
+        });
+    tokio::spawn(task);
+}
+
 // ── Создание аутентифицированной russh-сессии ──
 // Используется всеми модулями: shell, sftp, forward.
 
@@ -231,23 +520,75 @@ pub async fn create_russh_session(
 ) -> Result<client::Handle<SshHandler>, Box<dyn std::error::Error + Send + Sync>> {
     let ssh_config = Arc::new(client::Config::default());
 
-    let mut session = match &config.proxy {
-        Some(proxy) => {
-            let tcp = connect_tcp_async(&proxy.host, proxy.port).await?;
-            let tcp = socks5_connect_async(tcp, &config.host, config.port).await?;
-            client::connect_stream(ssh_config, tcp, handler).await?
-        }
-        None => {
-            let addr = format!("{}:{}", config.host, config.port);
-            client::connect(ssh_config, &*addr, handler).await?
-        }
+    // Первый узел цепочки — через прокси или напрямую, остальные — direct-tcpip
+    // из предыдущего. Jump-хосты входят тем же ключом или агентом, что и сама
+    // сессия; пароль цели промежуточным серверам не отдаём.
+    if !config.jump_hosts.is_empty() && matches!(config.auth_type, AuthType::Password(_)) {
+        return Err("jump hosts need key or agent auth: \
+                    the session password is never sent to intermediate hosts"
+            .into());
+    }
+    let final_hop = JumpHost {
+        username: config.username.clone(),
+        host: config.host.clone(),
+        port: config.port,
     };
+    let hops: Vec<&JumpHost> = config.jump_hosts.iter().chain([&final_hop]).collect();
+    let mut handler = Some(handler);
+    let mut upstream: Option<client::Handle<SshHandler>> = None;
+    for (i, hop) in hops.iter().enumerate() {
+        let mut hop_handler = if i + 1 == hops.len() {
+            handler.take().unwrap_or_else(SshHandler::new)
+        } else {
+            SshHandler::new()
+        };
+        let mut session = match upstream.take() {
+            Some(previous) => {
+                let channel = previous
+                    .channel_open_direct_tcpip(&hop.host, hop.port as u32, "127.0.0.1", 0)
+                    .await
+                    .map_err(|e| format!("jump to {}:{}: {}", hop.host, hop.port, e))?;
+                hop_handler.upstream = Some(Box::new(previous));
+                client::connect_stream(ssh_config.clone(), channel.into_stream(), hop_handler)
+                    .await?
+            }
+            None => match &config.proxy {
+                Some(proxy) => {
+                    let tcp = connect_tcp_async(&proxy.host, proxy.port).await?;
+                    let tcp = socks5_connect_async(tcp, &hop.host, hop.port).await?;
+                    client::connect_stream(ssh_config.clone(), tcp, hop_handler).await?
+                }
+                None => {
+                    let addr = format!("{}:{}", hop.host, hop.port);
+                    client::connect(ssh_config.clone(), &*addr, hop_handler).await?
+                }
+            },
+        };
+        let username = if hop.username.is_empty() {
+            &config.username
+        } else {
+            &hop.username
+        };
+        let result = if i + 1 == hops.len() {
+            authenticate(&mut session, config, username).await
+        } else {
+            authenticate_hop(&mut session, config, username).await
+        };
+        result.map_err(|e| format!("{}@{}: {}", username, hop.host, e))?;
+        upstream = Some(session);
+    }
+    upstream.ok_or_else(|| "no SSH hops".into())
+}
 
-    // Аутентификация
+async fn authenticate(
+    session: &mut client::Handle<SshHandler>,
+    config: &SessionConfig,
+    username: &str,
+) -> Result<(), Box<dyn std::error::Error + Send + Sync>> {
     match &config.auth_type {
         AuthType::Password(pwd) => {
             let auth = session
-                .authenticate_password(&config.username, pwd)
+                .authenticate_password(username, pwd)
                 .await?;
             if !matches!(auth, client::AuthResult::Success) {
                 return Err("Authentication failed".into());
@@ -258,21 +599,33 @@ pub async fn create_russh_session(
             let passphrase = config.key_passphrase.as_deref();
             let key = keys::load_secret_key(&expanded, passphrase)
                 .map_err(|e| format!("Key load error {}: {}", expanded, e))?;
-            let hash_alg = best_rsa_hash(&session).await;
+            let hash_alg = best_rsa_hash(session).await;
             let key_with_alg = PrivateKeyWithHashAlg::new(Arc::new(key), hash_alg);
             let auth = session
-                .authenticate_publickey(&config.username, key_with_alg)
+                .authenticate_publickey(username, key_with_alg)
                 .await?;
             if !matches!(auth, client::AuthResult::Success) {
                 return Err("Public key authentication failed".into());
             }
         }
         AuthType::Agent => {
-            auth_with_agent(&mut session, &config.username).await?;
+            auth_with_agent(session, username).await?;
         }
     }
 
-    Ok(session)
+    Ok(())
+}
+
+/// Вход на jump-хост: только ключ или агент, пароль цели ему не уходит.
+async fn authenticate_hop(
+    session: &mut client::Handle<SshHandler>,
+    config: &SessionConfig,
+    username: &str,
+) -> Result<(), Box<dyn std::error::Error + Send + Sync>> {
+    match &config.auth_type {
+        AuthType::Password(_) => Err("password auth is not used for jump hosts".into()),
+        AuthType::KeyFile(_) | AuthType::Agent => authenticate(session, config, username).await,
+    }
 }
 
 // ── Helper: negotiate best RSA hash with the server ──
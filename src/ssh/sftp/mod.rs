@@ -0,0 +1,6401 @@
+use std::collections::{BTreeMap, HashMap, HashSet, VecDeque};
+use std::path::{Path, PathBuf};
+use std::sync::atomic::{AtomicBool, AtomicU64, Ordering};
+use std::sync::mpsc;
+use std::sync::Arc;
+use std::time::{Duration, Instant};
+
+use serde::{Deserialize, Serialize};
+
+use crate::ssh::session::{create_russh_session, SessionConfig, SshHandler, UploadPermissions};
+
+mod remote_ops;
+mod scp;
+mod sync;
+
+use remote_ops::{
+    df_usage, download_archive, is_direct, remote_copy, scp_login, run_remove_all, run_search, run_sudo,
+    run_tree_stats, send_to,
+};
+use scp::{civil_from_days, scp_thread_async};
+use sync::{
+    run_sync, run_sync_compare, SyncDialog, SyncDirection, SyncItem, SyncOp, SyncPlan, SyncSpec,
+};
+
+const CHUNK_SIZE: usize = 256 * 1024; // 256 KB per I/O op — sweet spot for SFTP throughput
+/// Скачивание держит до стольких чтений в полёте: на канале с RTT 200 мс одно
+/// чтение за раз упирается в размер куска за RTT, окно — в READ_AHEAD раз больше
+const READ_AHEAD: usize = 32;
+/// С такого окна чтение начинается, пока скорость канала не измерена
+const READ_AHEAD_MIN: usize = 4;
+/// Окно растёт, только пока измеренная скорость успевает отдать его целиком за это
+/// время. У russh-sftp на каждый запрос 10 с — на медленном канале хвост большого
+/// окна иначе ловит тайм-аут при живом соединении
+const READ_AHEAD_BUDGET: Duration = Duration::from_secs(3);
+/// Кусок чтения в окне. sftp-server режет чтения больше ~255 KB, и 256 KB пришлось
+/// бы каждый раз дочитывать
+const READ_AHEAD_CHUNK: usize = 64 * 1024;
+/// Больше этого картинку для превью не тянем
+const PREVIEW_MAX_BYTES: u64 = 8 * 1024 * 1024;
+/// Превью уменьшается до этого размера (в пикселях по большей стороне)
+const PREVIEW_MAX_SIDE: u32 = 1024;
+/// Поиск останавливается на стольких находках
+const SEARCH_MAX_RESULTS: usize = 2000;
+/// Обход через SFTP (без find) читает не больше стольких папок
+const SEARCH_MAX_DIRS: usize = 20_000;
+/// Листинг уходит в UI страницами: не больше записей или не реже интервала
+const LIST_PAGE_SIZE: usize = 2000;
+const LIST_PAGE_INTERVAL: Duration = Duration::from_millis(200);
+/// [watch]: как часто проверять mtime папки
+const WATCH_INTERVAL: Duration = Duration::from_secs(2);
+/// mtime папки не меняется, когда файл в ней дописывается, — иногда перечитываем целиком
+const WATCH_FULL_RELOAD: Duration = Duration::from_secs(15);
+/// Потолок паузы между повторами (она удваивается с каждой попыткой)
+const RETRY_MAX_DELAY: Duration = Duration::from_secs(60);
+
+#[derive(Clone)]
+pub struct SftpEntry {
+    pub name: String,
+    pub path: String,
+    pub is_dir: bool,
+    pub size: u64,
+    pub modified: Option<u64>,
+    /// st_mode целиком: тип файла + права
+    pub mode: Option<u32>,
+    pub uid: Option<u32>,
+    pub gid: Option<u32>,
+    /// Симлинк: куда указывает. is_dir и size тогда — от цели
+    pub link_target: Option<String>,
+    /// false — цель симлинка не существует
+    pub target_ok: bool,
+}
+
+enum SftpRequest {
+    ListDir(String),
+    /// Первый листинг: последняя папка сессии, если она ещё есть, иначе домашняя
+    ListStart(Option<String>),
+    /// resume: None — спросить, если файл уже частично скачан/залит
+    Download {
+        remote: String,
+        local: String,
+        progress: Arc<TransferState>,
+        resume: Option<bool>,
+    },
+    Upload {
+        local: String,
+        remote: String,
+        progress: Arc<TransferState>,
+        resume: Option<bool>,
+        perms: UploadPermissions,
+    },
+    /// Папка целиком: подпапки создаются, файлы заливаются по очереди.
+    /// resume: None — спросить, если на сервере уже есть такие файлы;
+    /// Some(true) — залитые пропустить, недолитые докачать
+    UploadDir {
+        local: String,
+        remote: String,
+        progress: Arc<TransferState>,
+        resume: Option<bool>,
+        perms: UploadPermissions,
+    },
+    /// Сравнить папки и вернуть план синхронизации (ничего не меняя)
+    SyncCompare {
+        id: u64,
+        spec: SyncSpec,
+    },
+    /// Папка одним архивом: `tar czf -` на сервере, stdout — в локальный файл
+    DownloadArchive {
+        remote: String,
+        local: String,
+        progress: Arc<TransferState>,
+    },
+    /// Копия на сервере: `cp -a`, если можно, иначе чтение/запись через SFTP
+    RemoteCopy {
+        sources: Vec<String>,
+        dest: String,
+        progress: Arc<TransferState>,
+    },
+    /// На другой сервер: scp прямо с сервера, если пустит, иначе через клиент
+    SendTo {
+        sources: Vec<String>,
+        peer: SftpPeer,
+        progress: Arc<TransferState>,
+    },
+    /// Выполнить план: шаги по порядку, как одна передача в очереди.
+    /// resume: None — спросить, если план заменяет файлы приёмника
+    Sync {
+        spec: SyncSpec,
+        items: Vec<SyncItem>,
+        progress: Arc<TransferState>,
+        resume: Option<bool>,
+        perms: UploadPermissions,
+    },
+    /// mode: права новой папки (setstat после создания); None — как решит сервер
+    Mkdir {
+        path: String,
+        mode: Option<u32>,
+    },
+    /// Пустой файл; существующий не трогаем
+    NewFile(String),
+    /// atime и mtime — текущее время
+    Touch(String),
+    Remove(String),
+    /// Папка со всем содержимым
+    RemoveAll(String),
+    /// Свободное место на файловой системе пути
+    DiskUsage(String),
+    /// mtime папки — для [watch]
+    StatDir(String),
+    /// Сколько файлов и байт в дереве (для подтверждения удаления и [size])
+    TreeStats {
+        id: u64,
+        path: String,
+    },
+    Rename {
+        from: String,
+        to: String,
+    },
+    /// setstat: только биты прав (07777)
+    Chmod {
+        path: String,
+        mode: u32,
+    },
+    /// setstat: uid и gid (в протоколе они всегда идут парой)
+    Chown {
+        path: String,
+        uid: u32,
+        gid: u32,
+    },
+    /// Прочитать /etc/passwd и /etc/group для имён владельцев
+    LoadAccounts,
+    Symlink {
+        link: String,
+        target: String,
+    },
+    /// Изменение через `sudo` на exec-канале (режим sudo). Без пароля — спросим, если нужен
+    Sudo {
+        req: Box<SftpRequest>,
+        password: Option<String>,
+    },
+    /// Картинка для панели превью (не больше PREVIEW_MAX_BYTES)
+    Preview(String),
+    /// Рекурсивный поиск; id отличает результаты нового поиска от старого
+    Search {
+        id: u64,
+        query: SearchQuery,
+        cancel: Arc<AtomicBool>,
+    },
+}
+
+impl SftpRequest {
+    /// Задание после ответа на конфликт: новый путь на сервере (после [rename]) и докачка.
+    fn answered(self, new_remote: &str, new_resume: Option<bool>) -> SftpRequest {
+        match self {
+            SftpRequest::UploadDir {
+                local,
+                progress,
+                perms,
+                ..
+            } => SftpRequest::UploadDir {
+                local,
+                remote: new_remote.to_string(),
+                progress,
+                resume: new_resume,
+                perms,
+            },
+            SftpRequest::Sync {
+                spec,
+                items,
+                progress,
+                perms,
+                ..
+            } => SftpRequest::Sync {
+                spec,
+                items,
+                progress,
+                resume: new_resume,
+                perms,
+            },
+            req => req,
+        }
+    }
+
+    fn progress(&self) -> Option<&TransferState> {
+        match self {
+            SftpRequest::Download { progress, .. }
+            | SftpRequest::Upload { progress, .. }
+            | SftpRequest::UploadDir { progress, .. }
+            | SftpRequest::Sync { progress, .. }
+            | SftpRequest::RemoteCopy { progress, .. }
+            | SftpRequest::SendTo { progress, .. }
+            | SftpRequest::DownloadArchive { progress, .. } => Some(progress),
+            SftpRequest::Sudo { req, .. } => req.progress(),
+            _ => None,
+        }
+    }
+
+    /// Меняет что-то на сервере — в режиме sudo уходит через `sudo`.
+    fn is_write(&self) -> bool {
+        matches!(
+            self,
+            SftpRequest::Upload { .. }
+                | SftpRequest::UploadDir { .. }
+                | SftpRequest::Mkdir { .. }
+                | SftpRequest::NewFile(_)
+                | SftpRequest::Touch(_)
+                | SftpRequest::Remove(_)
+                | SftpRequest::RemoveAll(_)
+                | SftpRequest::Rename { .. }
+                | SftpRequest::Chmod { .. }
+                | SftpRequest::Chown { .. }
+                | SftpRequest::Symlink { .. }
+        )
+    }
+
+    fn elevated(self, password: Option<String>) -> SftpRequest {
+        if self.is_write() {
+            SftpRequest::Sudo {
+                req: Box::new(self),
+                password,
+            }
+        } else {
+            self
+        }
+    }
+}
+
+enum SftpResponse {
+    DirListing(String, Vec<SftpEntry>),
+    /// Часть большого листинга: first — заменить таблицу, last — всё пришло
+    DirPage {
+        path: String,
+        entries: Vec<SftpEntry>,
+        first: bool,
+        last: bool,
+    },
+    Error(String),
+    Success(String),
+    /// Файл назначения уже частично есть — продолжить или начать заново?
+    Conflict(TransferConflict),
+    Accounts(Accounts),
+    Preview(String, Result<PreviewImage, String>),
+    Search(u64, SearchUpdate),
+    SyncPlan(u64, Result<SyncPlan, String>),
+    TreeStats(u64, Result<TreeStats, String>),
+    DiskUsage(String, DiskUsage),
+    /// mtime папки; None — сервер не сообщил
+    DirStamp(String, Option<u64>),
+    /// На сервере нет SFTP — дальше через scp и `ls` (причина — для статуса)
+    ScpFallback(String),
+    /// Обрыв: воркер переподключается и повторит запрос
+    Retrying(String),
+    /// sudo спросил пароль (или не принял его) — запрос вернулся для повтора
+    SudoPassword { req: Box<SftpRequest>, wrong: bool },
+    /// Заливка не поместится на сервере — ждёт [upload anyway] или [skip]
+    LowSpace(LowSpace),
+}
+
+#[derive(Clone, Copy)]
+struct DiskUsage {
+    total: u64,
+    /// Доступно непривилегированному пользователю
+    avail: u64,
+}
+
+#[derive(Clone, Copy, Default)]
+struct TreeStats {
+    files: u64,
+    dirs: u64,
+    bytes: u64,
+}
+
+/// Другая открытая сессия с SFTP-вкладкой — цель для [send to].
+#[derive(Clone)]
+pub struct SftpPeer {
+    name: String,
+    /// user@host и порт — для scp прямо с сервера на сервер. None — сессия идёт через
+    /// прокси, jump-хосты или локальный проброс (с другого сервера её адрес недоступен)
+    /// либо логин не годится для командной строки
+    login: Option<String>,
+    port: u16,
+    /// Текущая папка её SFTP-вкладки — туда и кладём
+    dir: String,
+    session: Arc<russh::client::Handle<SshHandler>>,
+}
+
+struct RemoteClipboard {
+    paths: Vec<String>,
+    /// Вырезано: при вставке — перемещение
+    cut: bool,
+}
+
+/// Переименование на месте (F2, [rename]): имя в строке таблицы становится полем ввода.
+struct InlineRename {
+    path: String,
+    name: String,
+    /// Фокус и выделение имени без расширения ставятся в первом кадре
+    focused: bool,
+    /// Почему имя не подходит — показывается рядом с полем
+    error: Option<String>,
+}
+
+impl InlineRename {
+    fn new(entry: &SftpEntry) -> Self {
+        InlineRename {
+            path: entry.path.clone(),
+            name: entry.name.clone(),
+            focused: false,
+            error: None,
+        }
+    }
+
+    /// Поле вместо имени. Some(true) — Enter, Some(false) — отмена (Esc, щелчок мимо).
+    fn show(&mut self, ui: &mut egui::Ui) -> Option<bool> {
+        let mut output = egui::TextEdit::singleline(&mut self.name)
+            .font(egui::TextStyle::Monospace)
+            .desired_width(ui.available_width().min(320.0))
+            .show(ui);
+        if !self.focused {
+            self.focused = true;
+            output.response.request_focus();
+            // Как в файловых менеджерах: выделено имя без расширения
+            let stem = match self.name.rfind('.') {
+                Some(dot) if dot > 0 => dot,
+                _ => self.name.len(),
+            };
+            let range = egui::text::CCursorRange::two(
+                egui::text::CCursor::new(0),
+                egui::text::CCursor::new(self.name[..stem].chars().count()),
+            );
+            output.state.cursor.set_char_range(Some(range));
+            output.state.store(ui.ctx(), output.response.id);
+        }
+        if let Some(error) = &self.error {
+            ui.colored_label(crate::theme::RED, error);
+        }
+        if output.response.changed() {
+            self.error = None;
+        }
+        if !output.response.lost_focus() {
+            return None;
+        }
+        Some(ui.input(|i| i.key_pressed(egui::Key::Enter)))
+    }
+}
+
+/// [paste to file...]: содержимое буфера обмена ждёт имени файла.
+struct PasteFile {
+    dir: String,
+    name: String,
+    data: Vec<u8>,
+    /// Что лежало в буфере — для подписи в окне
+    kind: String,
+}
+
+impl PasteFile {
+    /// Текст — как есть, картинка — в PNG. Пустой буфер — ошибка.
+    fn from_clipboard(dir: String) -> Result<Self, String> {
+        let mut clipboard = arboard::Clipboard::new().map_err(|e| e.to_string())?;
+        if let Ok(text) = clipboard.get_text() {
+            if !text.is_empty() {
+                return Ok(PasteFile {
+                    dir,
+                    name: "clipboard.txt".to_string(),
+                    kind: format!("text, {} lines", text.lines().count()),
+                    data: text.into_bytes(),
+                });
+            }
+        }
+        let image = clipboard
+            .get_image()
+            .map_err(|_| "clipboard has no text or image".to_string())?;
+        let (width, height) = (image.width as u32, image.height as u32);
+        let rgba = image::RgbaImage::from_raw(width, height, image.bytes.into_owned())
+            .ok_or("clipboard image has a wrong size")?;
+        let mut png = std::io::Cursor::new(Vec::new());
+        rgba.write_to(&mut png, image::ImageFormat::Png).map_err(|e| e.to_string())?;
+        Ok(PasteFile {
+            dir,
+            name: "screenshot.png".to_string(),
+            kind: format!("image {}x{}", width, height),
+            data: png.into_inner(),
+        })
+    }
+}
+
+/// Подтверждение удаления; для папки сначала считаем, что пропадёт.
+struct DeleteConfirm {
+    path: String,
+    /// Удалять рекурсивно (папка, а не симлинк на неё)
+    recursive: bool,
+    id: u64,
+    stats: Option<Result<TreeStats, String>>,
+}
+
+enum SearchUpdate {
+    Found(Vec<SftpEntry>),
+    /// Итог: сколько найдено и каким способом
+    Done(String),
+    Failed(String),
+}
+
+/// Что ищем. Имя — как в фильтре (подстрока или glob), остальное — необязательно.
+#[derive(Clone)]
+struct SearchQuery {
+    root: String,
+    pattern: String,
+    min_size: Option<u64>,
+    max_size: Option<u64>,
+    /// Изменён не раньше (unix-время)
+    newer_than: Option<u64>,
+    max_depth: u32,
+}
+
+impl SearchQuery {
+    fn matches(&self, entry: &SftpEntry) -> bool {
+        if !name_matches(&self.pattern, &entry.name) {
+            return false;
+        }
+        // Размер и дата — только для файлов
+        if entry.is_dir {
+            return self.min_size.is_none() && self.max_size.is_none() && self.newer_than.is_none();
+        }
+        self.min_size.is_none_or(|min| entry.size >= min)
+            && self.max_size.is_none_or(|max| entry.size <= max)
+            && self
+                .newer_than
+                .is_none_or(|t| entry.modified.is_some_and(|m| m >= t))
+    }
+}
+
+/// Окно поиска: поля запроса (строками, как ввёл пользователь) и найденное.
+struct SearchDialog {
+    root: String,
+    pattern: String,
+    min_size: String,
+    max_size: String,
+    within_days: String,
+    max_depth: u32,
+    results: Vec<SftpEntry>,
+    /// id текущего поиска и флаг его остановки
+    running: Option<(u64, Arc<AtomicBool>)>,
+    status: String,
+}
+
+impl SearchDialog {
+    fn new(root: &str) -> Self {
+        SearchDialog {
+            root: root.to_string(),
+            pattern: String::new(),
+            min_size: String::new(),
+            max_size: String::new(),
+            within_days: String::new(),
+            max_depth: 8,
+            results: Vec::new(),
+            running: None,
+            status: String::new(),
+        }
+    }
+
+    fn query(&self) -> Result<SearchQuery, String> {
+        let size = |text: &str, what: &str| -> Result<Option<u64>, String> {
+            if text.trim().is_empty() {
+                return Ok(None);
+            }
+            parse_size(text)
+                .map(Some)
+                .ok_or_else(|| format!("bad {} size: {}", what, text.trim()))
+        };
+        let newer_than = match self.within_days.trim() {
+            "" => None,
+            days => {
+                let days: u64 = days.parse().map_err(|_| format!("bad days: {}", days))?;
+                let now = std::time::SystemTime::now()
+                    .duration_since(std::time::UNIX_EPOCH)
+                    .map(|d| d.as_secs())
+                    .unwrap_or(0);
+                Some(now.saturating_sub(days * 86400))
+            }
+        };
+        Ok(SearchQuery {
+            root: self.root.trim().to_string(),
+            pattern: self.pattern.trim().to_string(),
+            min_size: size(&self.min_size, "min")?,
+            max_size: size(&self.max_size, "max")?,
+            newer_than,
+            max_depth: self.max_depth,
+        })
+    }
+
+    fn stop(&mut self) {
+        if let Some((_, cancel)) = self.running.take() {
+            cancel.store(true, Ordering::Relaxed);
+        }
+    }
+}
+
+/// Что сделать с найденным — выполняется после отрисовки окна.
+enum SearchAction {
+    Open(SftpEntry),
+    Download(SftpEntry),
+    Remove(SftpEntry),
+}
+
+/// Уменьшенная картинка, декодированная в потоке SFTP.
+struct PreviewImage {
+    width: usize,
+    height: usize,
+    rgba: Vec<u8>,
+    /// Размер оригинала
+    original: (u32, u32),
+}
+
+/// Панель превью выбранной картинки.
+struct Preview {
+    path: String,
+    name: String,
+    size: u64,
+    state: PreviewState,
+}
+
+enum PreviewState {
+    Loading,
+    /// Пришла из потока SFTP, текстура создаётся при отрисовке
+    Decoded(PreviewImage),
+    Ready(egui::TextureHandle, (u32, u32)),
+    Failed(String),
+}
+
+/// Пользователи и группы сервера. Только локальные (/etc/passwd, /etc/group):
+/// LDAP/SSSD-пользователей тут не будет, для них вводится числовой id.
+#[derive(Default)]
+struct Accounts {
+    users: HashMap<u32, String>,
+    groups: HashMap<u32, String>,
+}
+
+impl Accounts {
+    /// Формат passwd и group совпадает в первых трёх полях: name:x:id
+    fn parse_ids(text: &str) -> HashMap<u32, String> {
+        text.lines()
+            .filter(|line| !line.starts_with('#'))
+            .filter_map(|line| {
+                let mut fields = line.split(':');
+                let name = fields.next()?;
+                let id = fields.nth(1)?.parse().ok()?;
+                Some((id, name.to_string()))
+            })
+            .collect()
+    }
+
+    fn user_name(&self, uid: u32) -> Option<&str> {
+        self.users.get(&uid).map(String::as_str)
+    }
+
+    fn group_name(&self, gid: u32) -> Option<&str> {
+        self.groups.get(&gid).map(String::as_str)
+    }
+
+    /// Имя или число → id
+    fn find(ids: &HashMap<u32, String>, text: &str) -> Option<u32> {
+        let text = text.trim();
+        text.parse().ok().or_else(|| {
+            ids.iter()
+                .find(|(_, name)| name.as_str() == text)
+                .map(|(id, _)| *id)
+        })
+    }
+}
+
+/// Файл назначения уже есть — передача ждёт решения пользователя.
+struct TransferConflict {
+    is_upload: bool,
+    local: String,
+    remote: String,
+    progress: Arc<TransferState>,
+    /// Сколько байт можно докачать (0 — докачка невозможна)
+    offset: u64,
+    source: FileStamp,
+    existing: FileStamp,
+    /// Имя для [rename] — свободное на момент проверки, можно поправить
+    rename: String,
+    /// Папка или синхронизация — один вопрос на всё задание; None — один файл
+    batch: Option<BatchConflict>,
+}
+
+/// Конфликт целого задания: source/existing и offset — суммы по файлам.
+struct BatchConflict {
+    /// Сколько файлов приёмника будет заменено
+    files: u64,
+    /// Уйдёт снова с ответом
+    req: SftpRequest,
+}
+
+impl TransferConflict {
+    /// Синхронизацию переименовать некуда: пути заданы планом.
+    fn can_rename(&self) -> bool {
+        !matches!(&self.batch, Some(batch) if matches!(batch.req, SftpRequest::Sync { .. }))
+    }
+}
+
+/// Заливка больше свободного места (statvfs) — запрос вернулся из воркера
+/// и уйдёт снова, если пользователь всё же решит заливать.
+struct LowSpace {
+    req: SftpRequest,
+    progress: Arc<TransferState>,
+    shortage: SpaceShortage,
+}
+
+/// Ошибка проверки места до начала записи (отличаем от прочих через downcast).
+#[derive(Debug, Clone)]
+struct SpaceShortage {
+    dir: String,
+    need: u64,
+    avail: u64,
+}
+
+impl std::fmt::Display for SpaceShortage {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        write!(
+            f,
+            "not enough space in {}: need {}, {} free",
+            self.dir,
+            format_size(self.need),
+            format_size(self.avail)
+        )
+    }
+}
+
+impl std::error::Error for SpaceShortage {}
+
+/// Размер и mtime файла — для сравнения в диалоге конфликта.
+#[derive(Clone, Copy)]
+struct FileStamp {
+    size: u64,
+    modified: Option<u64>,
+}
+
+#[derive(Clone, Copy, PartialEq)]
+enum ConflictAnswer {
+    Overwrite,
+    Skip,
+    Rename,
+    Resume,
+}
+
+/// Повторы при обрывах: сколько раз и пауза перед первым (дальше — вдвое дольше).
+#[derive(Clone, Copy)]
+struct RetryPolicy {
+    attempts: u32,
+    delay: Duration,
+}
+
+impl RetryPolicy {
+    fn new(attempts: u32, delay_secs: u32) -> Self {
+        RetryPolicy {
+            attempts,
+            delay: Duration::from_secs(delay_secs.max(1) as u64),
+        }
+    }
+
+    /// Пауза перед повтором номер `attempt` (с единицы)
+    fn backoff(&self, attempt: u32) -> Duration {
+        let factor = 2u32.saturating_pow(attempt.saturating_sub(1));
+        self.delay.saturating_mul(factor).min(RETRY_MAX_DELAY)
+    }
+}
+
+/// Обрыв канала или соединения, таймаут — такое лечится повтором.
+/// «Нет такого файла», «нет прав», «диск полон» — нет. Смотрим на тип ошибки
+/// и код статуса по всей цепочке source, а не на текст сообщения.
+fn is_transient(error: &(dyn std::error::Error + 'static)) -> bool {
+    use russh_sftp::client::error::Error as SftpError;
+    use russh_sftp::protocol::StatusCode;
+    std::iter::successors(Some(error), |e| e.source()).any(|e| {
+        if let Some(e) = e.downcast_ref::<SftpError>() {
+            return match e {
+                SftpError::Status(status) => matches!(
+                    status.status_code,
+                    StatusCode::NoConnection | StatusCode::ConnectionLost
+                ),
+                // IO — транспорт канала; Send/RecvError — канал закрыт на полпути
+                SftpError::IO(_) | SftpError::Timeout | SftpError::UnexpectedBehavior(_) => true,
+                SftpError::Limited(_) | SftpError::UnexpectedPacket => false,
+            };
+        }
+        if let Some(e) = e.downcast_ref::<russh::Error>() {
+            return matches!(
+                e,
+                russh::Error::Disconnect
+                    | russh::Error::HUP
+                    | russh::Error::ConnectionTimeout
+                    | russh::Error::KeepaliveTimeout
+                    | russh::Error::InactivityTimeout
+                    | russh::Error::SendError
+                    | russh::Error::RecvError
+                    | russh::Error::Elapsed(_)
+                    | russh::Error::ChannelOpenFailure(_)
+            ) || matches!(e, russh::Error::IO(io) if io_transient(io));
+        }
+        if let Some(e) = e.downcast_ref::<std::io::Error>() {
+            return io_transient(e);
+        }
+        e.is::<tokio::time::error::Elapsed>()
+    })
+}
+
+fn io_transient(error: &std::io::Error) -> bool {
+    use std::io::ErrorKind;
+    matches!(
+        error.kind(),
+        ErrorKind::ConnectionReset
+            | ErrorKind::ConnectionAborted
+            | ErrorKind::BrokenPipe
+            | ErrorKind::TimedOut
+            | ErrorKind::UnexpectedEof
+            | ErrorKind::NotConnected
+    )
+}
+
+/// Ограничение скорости — один token bucket на все передачи сессии
+/// (и на каждое направление правила перенаправления).
+/// Токены уходят в минус: следующий чанк ждёт, пока долг не погасится.
+pub(crate) struct RateLimiter {
+    /// Байт/с, 0 — без ограничения
+    rate: AtomicU64,
+    bucket: parking_lot::Mutex<(f64, Instant)>,
+}
+
+impl RateLimiter {
+    pub(crate) fn new(limit_kib: u32) -> Arc<Self> {
+        let limiter = RateLimiter {
+            rate: AtomicU64::new(0),
+            bucket: parking_lot::Mutex::new((0.0, Instant::now())),
+        };
+        limiter.set_limit_kib(limit_kib);
+        Arc::new(limiter)
+    }
+
+    pub(crate) fn set_limit_kib(&self, limit_kib: u32) {
+        self.rate.store(limit_kib as u64 * 1024, Ordering::Relaxed);
+    }
+
+    /// Размер чанка: при низком лимите — не больше четверти секунды трафика,
+    /// чтобы прогресс шёл ровно, а не рывками по 256 KB.
+    pub(crate) fn chunk_len(&self) -> usize {
+        match self.rate.load(Ordering::Relaxed) {
+            0 => CHUNK_SIZE,
+            rate => ((rate / 4) as usize).clamp(4096, CHUNK_SIZE),
+        }
+    }
+
+    /// Списывает `n` байт; если ведро пусто — ждёт.
+    pub(crate) async fn acquire(&self, n: usize) {
+        let rate = self.rate.load(Ordering::Relaxed) as f64;
+        if rate == 0.0 {
+            return;
+        }
+        let wait = {
+            let mut bucket = self.bucket.lock();
+            let (tokens, last) = &mut *bucket;
+            let now = Instant::now();
+            // Запас — не больше секунды трафика
+            *tokens = (*tokens + now.duration_since(*last).as_secs_f64() * rate).min(rate);
+            *last = now;
+            *tokens -= n as f64;
+            if *tokens < 0.0 {
+                Duration::from_secs_f64(-*tokens / rate)
+            } else {
+                Duration::ZERO
+            }
+        };
+        if !wait.is_zero() {
+            tokio::time::sleep(wait).await;
+        }
+    }
+}
+
+pub struct TransferState {
+    pub name: String,
+    pub total: AtomicU64,
+    pub transferred: AtomicU64,
+    pub done: AtomicBool,
+    pub failed: AtomicBool,
+    /// [skip] в диалоге конфликта или нехватки места: завершена, но ничего не передано
+    pub skipped: AtomicBool,
+    pub is_upload: bool,
+    // Передача папки: текущий файл, его прогресс и счётчики файлов
+    pub is_dir: bool,
+    pub current: parking_lot::Mutex<String>,
+    pub file_total: AtomicU64,
+    pub file_transferred: AtomicU64,
+    pub files_total: AtomicU64,
+    pub files_done: AtomicU64,
+    // Запросы из очереди — воркер проверяет их между чанками
+    pub pause: AtomicBool,
+    pub cancel: AtomicBool,
+    pub error: parking_lot::Mutex<Option<String>>,
+    /// Ошибка — обрыв или таймаут (is_transient): очередь повторит передачу
+    pub transient: AtomicBool,
+    /// [upload anyway]: свободное место больше не проверяем
+    pub space_confirmed: AtomicBool,
+    /// Сколько раз не удалось поставить права: передача от этого не падает
+    pub chmod_failed: AtomicU64,
+}
+
+impl TransferState {
+    fn new(name: &str, total: u64, is_upload: bool) -> Arc<Self> {
+        Arc::new(Self::with_kind(name, total, is_upload, false))
+    }
+
+    fn new_dir(name: &str, is_upload: bool) -> Arc<Self> {
+        Arc::new(Self::with_kind(name, 0, is_upload, true))
+    }
+
+    fn with_kind(name: &str, total: u64, is_upload: bool, is_dir: bool) -> Self {
+        TransferState {
+            name: name.to_string(),
+            total: AtomicU64::new(total),
+            transferred: AtomicU64::new(0),
+            done: AtomicBool::new(false),
+            failed: AtomicBool::new(false),
+            skipped: AtomicBool::new(false),
+            is_upload,
+            is_dir,
+            current: parking_lot::Mutex::new(String::new()),
+            file_total: AtomicU64::new(0),
+            file_transferred: AtomicU64::new(0),
+            files_total: AtomicU64::new(0),
+            files_done: AtomicU64::new(0),
+            pause: AtomicBool::new(false),
+            cancel: AtomicBool::new(false),
+            error: parking_lot::Mutex::new(None),
+            transient: AtomicBool::new(false),
+            space_confirmed: AtomicBool::new(false),
+            chmod_failed: AtomicU64::new(0),
+        }
+    }
+
+    /// Перед повторным запуском (retry / resume после паузы).
+    fn reset(&self) {
+        self.transferred.store(0, Ordering::Relaxed);
+        self.file_transferred.store(0, Ordering::Relaxed);
+        self.files_done.store(0, Ordering::Relaxed);
+        self.done.store(false, Ordering::Relaxed);
+        self.failed.store(false, Ordering::Relaxed);
+        self.skipped.store(false, Ordering::Relaxed);
+        self.pause.store(false, Ordering::Relaxed);
+        self.cancel.store(false, Ordering::Relaxed);
+        self.chmod_failed.store(0, Ordering::Relaxed);
+        self.transient.store(false, Ordering::Relaxed);
+        *self.error.lock() = None;
+    }
+
+    /// Остановлена ли передача пользователем (пауза/отмена — не ошибка).
+    fn interruption(&self) -> Option<&'static str> {
+        if self.cancel.load(Ordering::Relaxed) {
+            Some("cancelled")
+        } else if self.pause.load(Ordering::Relaxed) {
+            Some("paused")
+        } else {
+            None
+        }
+    }
+
+    fn skip(&self) {
+        self.skipped.store(true, Ordering::Relaxed);
+        self.done.store(true, Ordering::Relaxed);
+    }
+
+    fn check_interrupted(&self) -> Result<(), String> {
+        match self.interruption() {
+            Some(reason) => Err(format!("{} {}", self.name, reason)),
+            None => Ok(()),
+        }
+    }
+
+    fn file_fraction(&self) -> f32 {
+        let total = self.file_total.load(Ordering::Relaxed);
+        if total == 0 {
+            return 0.0;
+        }
+        let done = self.file_transferred.load(Ordering::Relaxed);
+        (done as f64 / total as f64) as f32
+    }
+
+    fn fraction(&self) -> f32 {
+        let total = self.total.load(Ordering::Relaxed);
+        if total == 0 {
+            return 0.0;
+        }
+        let done = self.transferred.load(Ordering::Relaxed);
+        (done as f64 / total as f64) as f32
+    }
+}
+
+/// Что передаём — хранится в очереди, чтобы можно было перезапустить.
+#[derive(Clone)]
+enum TransferJob {
+    Download { remote: String, local: String },
+    Upload { local: String, remote: String },
+    UploadDir { local: String, remote: String },
+    Sync { spec: SyncSpec, items: Vec<SyncItem> },
+    RemoteCopy { sources: Vec<String>, dest: String },
+    SendTo { sources: Vec<String>, peer: SftpPeer },
+    DownloadArchive { remote: String, local: String },
+}
+
+#[derive(Clone, Copy, PartialEq, Eq)]
+enum TransferStatus {
+    Queued,
+    Running,
+    Paused,
+    Done,
+    Skipped,
+    Failed,
+}
+
+impl TransferStatus {
+    fn is_finished(self) -> bool {
+        matches!(self, TransferStatus::Done | TransferStatus::Skipped | TransferStatus::Failed)
+    }
+}
+
+/// Элемент очереди передач. Воркер получает задачу, только когда до неё дошла очередь.
+struct QueuedTransfer {
+    job: TransferJob,
+    progress: Arc<TransferState>,
+    status: TransferStatus,
+    /// Для следующего запуска: None — спросить про докачку
+    resume: Option<bool>,
+    /// Начало последнего запуска — для длительности в журнале
+    started: Option<Instant>,
+    /// Автоматический повтор после обрыва: номер попытки и когда запускать
+    attempt: u32,
+    retry_at: Option<Instant>,
+}
+
+impl QueuedTransfer {
+    fn direction(&self) -> &'static str {
+        match self.job {
+            TransferJob::RemoteCopy { .. } => "CP",
+            TransferJob::SendTo { .. } => "SEND",
+            _ if self.progress.is_upload => "PUT",
+            _ => "GET",
+        }
+    }
+
+    /// Откуда и куда — для журнала
+    fn endpoints(&self) -> (String, String) {
+        match &self.job {
+            TransferJob::Download { remote, local }
+            | TransferJob::DownloadArchive { remote, local } => (remote.clone(), local.clone()),
+            TransferJob::Upload { local, remote } | TransferJob::UploadDir { local, remote } => {
+                (local.clone(), remote.clone())
+            }
+            TransferJob::Sync { spec, .. } => match spec.direction {
+                SyncDirection::Upload => (spec.local.clone(), spec.remote.clone()),
+                SyncDirection::Download => (spec.remote.clone(), spec.local.clone()),
+            },
+            TransferJob::RemoteCopy { sources, dest } => (sources.join(", "), dest.clone()),
+            TransferJob::SendTo { sources, peer } => {
+                (sources.join(", "), format!("{}:{}", peer.name, peer.dir))
+            }
+        }
+    }
+
+    fn request(&self, perms: UploadPermissions) -> SftpRequest {
+        let progress = Arc::clone(&self.progress);
+        match self.job.clone() {
+            TransferJob::Download { remote, local } => SftpRequest::Download {
+                remote,
+                local,
+                progress,
+                resume: self.resume,
+            },
+            TransferJob::Upload { local, remote } => SftpRequest::Upload {
+                local,
+                remote,
+                progress,
+                resume: self.resume,
+                perms,
+            },
+            TransferJob::UploadDir { local, remote } => SftpRequest::UploadDir {
+                local,
+                remote,
+                progress,
+                resume: self.resume,
+                perms,
+            },
+            TransferJob::Sync { spec, items } => SftpRequest::Sync {
+                spec,
+                items,
+                progress,
+                resume: self.resume,
+                perms,
+            },
+            TransferJob::SendTo { sources, peer } => SftpRequest::SendTo {
+                sources,
+                peer,
+                progress,
+            },
+            TransferJob::RemoteCopy { sources, dest } => SftpRequest::RemoteCopy {
+                sources,
+                dest,
+                progress,
+            },
+            TransferJob::DownloadArchive { remote, local } => SftpRequest::DownloadArchive {
+                remote,
+                local,
+                progress,
+            },
+        }
+    }
+}
+
+/// Завершённая передача в журнале (sftp_history.json).
+#[derive(Clone, Serialize, Deserialize)]
+pub struct TransferRecord {
+    /// Unix-время окончания
+    pub time: u64,
+    /// user@host
+    pub session: String,
+    /// GET, PUT или CP
+    pub direction: String,
+    pub source: String,
+    pub dest: String,
+    pub bytes: u64,
+    pub duration_ms: u64,
+    /// "ok", "skipped", "cancelled" или текст ошибки
+    pub result: String,
+}
+
+/// Действие над элементом очереди из UI.
+enum QueueAction {
+    MoveUp(usize),
+    MoveDown(usize),
+    Pause(usize),
+    Resume(usize),
+    Cancel(usize),
+    Retry(usize),
+    ClearFinished,
+}
+
+/// Файл, открытый внешней программой через [open with...]: копия лежит в кэше,
+/// после сохранения в программе предлагаем залить её обратно.
+struct OpenedFile {
+    remote: String,
+    local: PathBuf,
+    app: PathBuf,
+    download: Arc<TransferState>,
+    /// mtime копии после скачивания или последней заливки; None — ещё качается
+    baseline: Option<std::time::SystemTime>,
+    changed: bool,
+}
+
+/// Окно свойств файла: права (сетка rwx и восьмеричное поле, синхронные
+/// друг с другом), владелец и группа — именем или числом.
+struct PropertiesDialog {
+    path: String,
+    name: String,
+    is_dir: bool,
+    mode: u32,
+    octal: String,
+    /// None — сервер не прислал права: chmod только если их задали явно
+    original_mode: Option<u32>,
+    mode_touched: bool,
+    owner: String,
+    group: String,
+    original_ids: Option<(u32, u32)>,
+}
+
+impl PropertiesDialog {
+    fn new(entry: &SftpEntry, accounts: &Accounts) -> Self {
+        let original_mode = entry.mode.map(|mode| mode & 0o7777);
+        let mode = original_mode.unwrap_or(0);
+        let owner = entry
+            .uid
+            .map(|uid| accounts.user_name(uid).map_or(uid.to_string(), str::to_string))
+            .unwrap_or_default();
+        let group = entry
+            .gid
+            .map(|gid| accounts.group_name(gid).map_or(gid.to_string(), str::to_string))
+            .unwrap_or_default();
+        PropertiesDialog {
+            path: entry.path.clone(),
+            name: entry.name.clone(),
+            is_dir: entry.is_dir,
+            mode,
+            octal: original_mode.map(|mode| format!("{:04o}", mode)).unwrap_or_default(),
+            original_mode,
+            mode_touched: false,
+            owner,
+            group,
+            original_ids: entry.uid.zip(entry.gid),
+        }
+    }
+}
+
+/// Необязательные колонки таблицы — как в `ls -l`. Переключаются
+/// из контекстного меню заголовка.
+#[derive(Clone, Copy, PartialEq, Serialize, Deserialize)]
+#[serde(default)]
+pub struct Columns {
+    perms: bool,
+    owner: bool,
+    group: bool,
+    /// MODIFIED как «3 days ago», точная дата — в подсказке
+    relative_time: bool,
+}
+
+impl Default for Columns {
+    fn default() -> Self {
+        Columns {
+            perms: true,
+            owner: true,
+            group: true,
+            relative_time: false,
+        }
+    }
+}
+
+impl Columns {
+    /// Заголовок колонки; ПКМ по любому — меню с колонками. true — клик (сортировка).
+    fn header(&mut self, ui: &mut egui::Ui, title: &str) -> bool {
+        let label = egui::Label::new(egui::RichText::new(title).strong());
+        let response = ui.add(label.sense(egui::Sense::click()));
+        response.context_menu(|ui| {
+            ui.checkbox(&mut self.perms, "permissions");
+            ui.checkbox(&mut self.owner, "owner");
+            ui.checkbox(&mut self.group, "group");
+            ui.separator();
+            ui.checkbox(&mut self.relative_time, "relative time");
+        });
+        response.clicked()
+    }
+}
+
+#[derive(Clone, Copy, PartialEq, Eq, Default, Serialize, Deserialize)]
+pub enum SortColumn {
+    #[default]
+    Name,
+    Size,
+    Modified,
+}
+
+/// Сортировка листинга. Папки всегда идут первыми, порядок — внутри групп.
+#[derive(Clone, Copy, PartialEq, Eq, Default, Serialize, Deserialize)]
+pub struct SortOrder {
+    pub column: SortColumn,
+    pub descending: bool,
+}
+
+impl SortOrder {
+    /// Клик по заголовку: та же колонка — сменить направление, другая — по возрастанию.
+    fn toggle(&mut self, column: SortColumn) {
+        if self.column == column {
+            self.descending = !self.descending;
+        } else {
+            *self = SortOrder {
+                column,
+                descending: false,
+            };
+        }
+    }
+
+    /// Заголовок со стрелкой у активной колонки.
+    fn title(&self, title: &str, column: SortColumn) -> String {
+        match (self.column == column, self.descending) {
+            (true, false) => format!("{} ^", title),
+            (true, true) => format!("{} v", title),
+            (false, _) => title.to_string(),
+        }
+    }
+
+    fn sort(&self, entries: &mut [SftpEntry]) {
+        entries.sort_by(|a, b| {
+            let order = match self.column {
+                SortColumn::Name => a.name.cmp(&b.name),
+                SortColumn::Size => a.size.cmp(&b.size).then(a.name.cmp(&b.name)),
+                SortColumn::Modified => a.modified.cmp(&b.modified).then(a.name.cmp(&b.name)),
+            };
+            let order = if self.descending { order.reverse() } else { order };
+            b.is_dir.cmp(&a.is_dir).then(order)
+        });
+    }
+}
+
+/// Настройки браузера, общие для всех сессий (sftp.json).
+#[derive(Clone, Copy, PartialEq, Default, Serialize, Deserialize)]
+pub struct SftpSettings {
+    #[serde(default)]
+    pub sort: SortOrder,
+    #[serde(default)]
+    pub columns: Columns,
+    /// Следить за текущей папкой и перечитывать её при изменениях
+    #[serde(default)]
+    pub watch: bool,
+}
+
+pub struct SftpBrowser {
+    pub current_path: String,
+    pub entries: Vec<SftpEntry>,
+    pub error: Option<String>,
+    pub loading: bool,
+    pub status_message: Option<String>,
+    request_tx: tokio::sync::mpsc::UnboundedSender<SftpRequest>,
+    response_rx: mpsc::Receiver<SftpResponse>,
+    navigate_to: Option<String>,
+    selected: HashSet<String>,
+    show_mkdir_dialog: bool,
+    /// Диалог mkdir создаёт файл, а не папку
+    mkdir_is_file: bool,
+    mkdir_name: String,
+    show_symlink_dialog: bool,
+    symlink_target: String,
+    symlink_name: String,
+    preview: Option<Preview>,
+    /// Фильтр имён текущей папки: подстрока или glob (*, ?)
+    filter: String,
+    search: Option<SearchDialog>,
+    sync: Option<SyncDialog>,
+    delete_confirm: Option<DeleteConfirm>,
+    /// Вырезанные/скопированные пути для [paste]
+    clipboard: Option<RemoteClipboard>,
+    /// [open shell here]: команда для Shell-вкладки, её забирает приложение
+    shell_command: Option<String>,
+    disk_usage: Option<DiskUsage>,
+    /// Сервер без SFTP: работаем через scp и ls
+    scp_only: bool,
+    /// Индексы self.entries, прошедших фильтр, в порядке таблицы
+    view: Vec<usize>,
+    /// Фильтр, по которому построен view
+    view_filter: String,
+    /// Листинг или сортировка изменились — view пересчитать
+    view_dirty: bool,
+    /// Строка подвала; None — пересчитать (сменились view, выделение или размеры папок)
+    summary: Option<String>,
+    /// Листинг ещё приходит страницами
+    listing_more: bool,
+    /// [watch]: mtime текущей папки при последней проверке
+    dir_mtime: Option<u64>,
+    watch_checked: Instant,
+    /// Когда текущая папка была прочитана целиком
+    listed_at: Instant,
+    /// Строка под клавиатурным курсором (путь)
+    cursor: Option<String>,
+    /// Набранные буквы для перехода по имени; сбрасываются после паузы
+    type_ahead: String,
+    type_ahead_at: Instant,
+    /// Переименование в таблице (F2, [rename])
+    rename: Option<InlineRename>,
+    paste_file: Option<PasteFile>,
+    /// Файлы, которые тащат из таблицы для скачивания (путь, имя, размер)
+    drag_download: Vec<(String, String, u64)>,
+    /// Размеры папок по [size]; None — ещё считается
+    dir_sizes: HashMap<String, Option<TreeStats>>,
+    /// id запроса TreeStats → папка для [size]
+    size_requests: HashMap<u64, String>,
+    /// Номер последнего поиска/сравнения — ответы на старые отбрасываются
+    request_seq: u64,
+    /// Строка пути в режиме редактирования (иначе — хлебные крошки)
+    path_edit: Option<String>,
+    back: Vec<String>,
+    forward: Vec<String>,
+    /// Текущий переход — по истории, в неё не записывается
+    history_nav: bool,
+    /// Имена подпапок прочитанных папок — для дополнения пути по Tab
+    dir_cache: HashMap<String, Vec<String>>,
+    properties_dialog: Option<PropertiesDialog>,
+    accounts: Accounts,
+    settings: SftpSettings,
+    transfers: Vec<QueuedTransfer>,
+    /// Сколько передач идёт одновременно
+    parallel: usize,
+    limiter: Arc<RateLimiter>,
+    retry: RetryPolicy,
+    conflicts: Vec<TransferConflict>,
+    /// Заливки, которым не хватает места на сервере — по одной за раз
+    low_space: Vec<LowSpace>,
+    /// Галочка «apply to all» в диалоге конфликта
+    conflict_apply_all: bool,
+    /// Ответ, применяемый ко всем следующим конфликтам текущей очереди
+    conflict_policy: Option<ConflictAnswer>,
+    /// Ключ для запоминания последней папки
+    session_id: String,
+    /// user@host — для журнала передач
+    session_host: String,
+    port: u16,
+    /// user@host для scp с другого сервера: только если сервер доступен напрямую
+    /// (без прокси, jump-хостов и loopback) и логин годится для командной строки
+    scp_login: Option<String>,
+    /// Папка для [get] из настроек сессии; пусто — системная «Загрузки»
+    download_folder: String,
+    /// Права новых файлов и папок при заливке
+    upload_perms: UploadPermissions,
+    /// Соединение воркера (после переподключения — новое) — для [send to] из других сессий
+    connection: Arc<parking_lot::Mutex<Option<Arc<russh::client::Handle<SshHandler>>>>>,
+    /// Другие сессии, куда можно отправить файлы (обновляет приложение каждый кадр)
+    peers: Vec<SftpPeer>,
+    /// Режим sudo: запись, удаление и права — через `sudo` на exec-канале
+    elevated: bool,
+    /// Пароль sudo, пока режим включён (только в памяти)
+    sudo_password: Option<String>,
+    /// Запросы, которым sudo не хватило пароля — уйдут снова после ввода
+    sudo_pending: Vec<SftpRequest>,
+    /// Окно пароля sudo: ввод и «прошлый не подошёл»
+    sudo_prompt: Option<(String, bool)>,
+    /// Окно журнала передач (загружается при открытии)
+    history: Option<Vec<TransferRecord>>,
+    /// Файлы, открытые внешними программами; их копии проверяются раз в секунду
+    opened: Vec<OpenedFile>,
+    opened_checked: Instant,
+}
+
+impl SftpBrowser {
+    pub fn new(config: &SessionConfig) -> Result<Self, String> {
+        let (req_tx, req_rx) = tokio::sync::mpsc::unbounded_channel();
+        let (resp_tx, resp_rx) = mpsc::channel();
+
+        let parallel = config.sftp_parallel.max(1) as usize;
+        let limiter = RateLimiter::new(config.sftp_rate_limit_kib);
+        let retry = RetryPolicy::new(config.sftp_retries, config.sftp_retry_delay_secs);
+        let worker_limiter = Arc::clone(&limiter);
+        let session_id = config.id.clone();
+        let session_host = format!("{}@{}", config.username, config.host);
+        let connection = Arc::new(parking_lot::Mutex::new(None));
+        let worker_connection = Arc::clone(&connection);
+        let port = config.port;
+        let scp_login = is_direct(config)
+            .then(|| scp_login(&config.username, &config.host))
+            .flatten();
+        let download_folder = config.sftp_download_dir.clone();
+        let upload_perms = config.sftp_upload_perms;
+        let config = config.clone();
+
+        std::thread::spawn(move || {
+            let rt = match tokio::runtime::Runtime::new() {
+                Ok(rt) => rt,
+                Err(e) => {
+                    let _ = resp_tx.send(SftpResponse::Error(format!(
+                        "Tokio runtime: {}",
+                        e
+                    )));
+                    return;
+                }
+            };
+            let worker = sftp_thread_async(
+                &config,
+                req_rx,
+                &resp_tx,
+                worker_limiter,
+                &worker_connection,
+            );
+            if let Err(e) = rt.block_on(worker) {
+                let _ = resp_tx.send(SftpResponse::Error(e.to_string()));
+            }
+        });
+
+        let browser = SftpBrowser {
+            current_path: "/".to_string(),
+            entries: Vec::new(),
+            error: None,
+            loading: true,
+            status_message: None,
+            request_tx: req_tx,
+            response_rx: resp_rx,
+            navigate_to: None,
+            selected: HashSet::new(),
+            show_mkdir_dialog: false,
+            mkdir_is_file: false,
+            mkdir_name: String::new(),
+            show_symlink_dialog: false,
+            symlink_target: String::new(),
+            symlink_name: String::new(),
+            preview: None,
+            filter: String::new(),
+            search: None,
+            sync: None,
+            delete_confirm: None,
+            clipboard: None,
+            shell_command: None,
+            disk_usage: None,
+            scp_only: false,
+            view: Vec::new(),
+            view_filter: String::new(),
+            view_dirty: false,
+            summary: None,
+            listing_more: false,
+            dir_mtime: None,
+            watch_checked: Instant::now(),
+            listed_at: Instant::now(),
+            cursor: None,
+            type_ahead: String::new(),
+            type_ahead_at: Instant::now(),
+            rename: None,
+            paste_file: None,
+            drag_download: Vec::new(),
+            dir_sizes: HashMap::new(),
+            size_requests: HashMap::new(),
+            request_seq: 0,
+            path_edit: None,
+            back: Vec::new(),
+            forward: Vec::new(),
+            history_nav: false,
+            dir_cache: HashMap::new(),
+            properties_dialog: None,
+            accounts: Accounts::default(),
+            settings: crate::config::sftp::load_settings(),
+            transfers: Vec::new(),
+            parallel,
+            limiter,
+            retry,
+            conflicts: Vec::new(),
+            low_space: Vec::new(),
+            conflict_apply_all: false,
+            conflict_policy: None,
+            session_id,
+            session_host,
+            port,
+            scp_login,
+            download_folder,
+            upload_perms,
+            connection,
+            peers: Vec::new(),
+            elevated: false,
+            sudo_password: None,
+            sudo_pending: Vec::new(),
+            sudo_prompt: None,
+            history: None,
+            opened: Vec::new(),
+            opened_checked: Instant::now(),
+        };
+
+        browser
+            .request_tx
+            .send(SftpRequest::ListStart(crate::config::sftp::load_last_path(
+                &browser.session_id,
+            )))
+            .map_err(|e| e.to_string())?;
+        let _ = browser.request_tx.send(SftpRequest::LoadAccounts);
+
+        Ok(browser)
+    }
+
+    pub fn navigate(&mut self, path: &str) {
+        self.loading = true;
+        self.error = None;
+        self.selected.clear();
+        self.summary = None;
+        // Фильтр — для текущей папки; reload его не сбрасывает
+        if path != self.current_path {
+            self.filter.clear();
+        }
+        let _ = self
+            .request_tx
+            .send(SftpRequest::ListDir(path.to_string()));
+    }
+
+    pub fn download(&mut self, remote: &str, local: &str, file_size: u64) {
+        let name = std::path::Path::new(remote)
+            .file_name()
+            .unwrap_or_default()
+            .to_string_lossy()
+            .to_string();
+        self.enqueue(
+            TransferJob::Download {
+                remote: remote.to_string(),
+                local: local.to_string(),
+            },
+            TransferState::new(&name, file_size, false),
+        );
+    }
+
+    pub fn upload(&mut self, local: &str, remote: &str) {
+        let file_size = std::fs::metadata(local).map(|m| m.len()).unwrap_or(0);
+        let name = std::path::Path::new(local)
+            .file_name()
+            .unwrap_or_default()
+            .to_string_lossy()
+            .to_string();
+        self.enqueue(
+            TransferJob::Upload {
+                local: local.to_string(),
+                remote: remote.to_string(),
+            },
+            TransferState::new(&name, file_size, true),
+        );
+    }
+
+    /// Ответ на конфликт. Докачка невозможна (файл не меньше источника) — пропуск.
+    fn answer_conflict(&mut self, mut conflict: TransferConflict, answer: ConflictAnswer) {
+        let resume = match answer {
+            ConflictAnswer::Overwrite => Some(false),
+            ConflictAnswer::Resume if conflict.offset > 0 => Some(true),
+            // Новое имя тоже может быть занято — тогда спросим снова
+            ConflictAnswer::Rename if !conflict.rename.trim().is_empty() => {
+                let name = conflict.rename.trim();
+                if conflict.is_upload {
+                    conflict.remote = remote_join(&remote_parent(&conflict.remote), name);
+                } else {
+                    let local = Path::new(&conflict.local).with_file_name(name);
+                    conflict.local = local.to_string_lossy().to_string();
+                }
+                self.retarget_transfer(&conflict);
+                None
+            }
+            _ => {
+                conflict.progress.skip();
+                self.status_message = Some(format!("skipped {}", conflict.progress.name));
+                return;
+            }
+        };
+        let request = if let Some(batch) = conflict.batch {
+            batch.req.answered(&conflict.remote, resume)
+        } else if conflict.is_upload {
+            SftpRequest::Upload {
+                local: conflict.local,
+                remote: conflict.remote,
+                progress: conflict.progress,
+                resume,
+                perms: self.upload_perms,
+            }
+        } else {
+            SftpRequest::Download {
+                remote: conflict.remote,
+                local: conflict.local,
+                progress: conflict.progress,
+                resume,
+            }
+        };
+        self.send(request);
+    }
+
+    /// Запрос воркеру; в режиме sudo изменения уходят через `sudo`.
+    fn send(&self, request: SftpRequest) {
+        let request = if self.elevated {
+            request.elevated(self.sudo_password.clone())
+        } else {
+            request
+        };
+        let _ = self.request_tx.send(request);
+    }
+
+    /// После [rename] повтор из очереди должен идти по новому пути.
+    fn retarget_transfer(&mut self, conflict: &TransferConflict) {
+        let Some(t) = self
+            .transfers
+            .iter_mut()
+            .find(|t| Arc::ptr_eq(&t.progress, &conflict.progress))
+        else {
+            return;
+        };
+        match &mut t.job {
+            TransferJob::Download { local, .. } => *local = conflict.local.clone(),
+            TransferJob::Upload { remote, .. } | TransferJob::UploadDir { remote, .. } => {
+                *remote = conflict.remote.clone()
+            }
+            _ => {}
+        }
+    }
+
+    fn push_conflict(&mut self, conflict: TransferConflict) {
+        match self.conflict_policy {
+            Some(ConflictAnswer::Rename) if !conflict.can_rename() => self.conflicts.push(conflict),
+            Some(answer) => self.answer_conflict(conflict, answer),
+            None => self.conflicts.push(conflict),
+        }
+    }
+
+    /// Рекурсивная заливка локальной папки в `remote` (создаётся, если нет).
+    pub fn upload_dir(&mut self, local: &str, remote: &str) {
+        let name = format!("{}/", local_file_name(Path::new(local)));
+        self.enqueue(
+            TransferJob::UploadDir {
+                local: local.to_string(),
+                remote: remote.to_string(),
+            },
+            TransferState::new_dir(&name, true),
+        );
+    }
+
+    fn enqueue(&mut self, job: TransferJob, progress: Arc<TransferState>) {
+        self.transfers.push(QueuedTransfer {
+            job,
+            progress,
+            status: TransferStatus::Queued,
+            resume: None,
+            started: None,
+            attempt: 0,
+            retry_at: None,
+        });
+    }
+
+    /// KiB/s на все передачи сессии, 0 — без ограничения. Действует сразу.
+    pub fn set_rate_limit(&self, limit_kib: u32) {
+        self.limiter.set_limit_kib(limit_kib);
+    }
+
+    pub fn set_parallel(&mut self, parallel: u32) {
+        self.parallel = parallel.max(1) as usize;
+        self.pump_queue();
+    }
+
+    /// Права для следующих заливок и [mkdir]; уже идущие передачи не трогает.
+    pub fn set_upload_perms(&mut self, perms: UploadPermissions) {
+        self.upload_perms = perms;
+    }
+
+    /// Повторы передач в очереди; листинг воркер повторяет по настройкам на момент подключения.
+    pub fn set_retry(&mut self, attempts: u32, delay_secs: u32) {
+        self.retry = RetryPolicy::new(attempts, delay_secs);
+    }
+
+    /// Обновляет статусы по флагам воркера и запускает следующие задачи,
+    /// пока занято меньше `parallel` каналов.
+    fn pump_queue(&mut self) {
+        let mut finished = Vec::new();
+        for t in &mut self.transfers {
+            if t.status != TransferStatus::Running {
+                continue;
+            }
+            if t.progress.skipped.load(Ordering::Relaxed) {
+                t.status = TransferStatus::Skipped;
+            } else if t.progress.done.load(Ordering::Relaxed) {
+                t.status = TransferStatus::Done;
+            } else if t.progress.failed.load(Ordering::Relaxed) {
+                t.status = match t.progress.interruption() {
+                    Some(_) if t.progress.cancel.load(Ordering::Relaxed) => TransferStatus::Done,
+                    Some(_) => TransferStatus::Paused,
+                    None => TransferStatus::Failed,
+                };
+            }
+            // Обрыв — не повод сдаваться: обратно в очередь с паузой, дальше — докачка
+            if t.status == TransferStatus::Failed && t.attempt < self.retry.attempts {
+                let error = t.progress.error.lock().clone().unwrap_or_default();
+                if t.progress.transient.load(Ordering::Relaxed) {
+                    t.attempt += 1;
+                    t.retry_at = Some(Instant::now() + self.retry.backoff(t.attempt));
+                    t.resume = Some(true);
+                    t.status = TransferStatus::Queued;
+                    if self.error.as_ref() == Some(&error) {
+                        self.error = None;
+                    }
+                    self.status_message = Some(format!(
+                        "{}: {} — retry {}/{}",
+                        t.progress.name, error, t.attempt, self.retry.attempts
+                    ));
+                    continue;
+                }
+            }
+            if t.status.is_finished() {
+                finished.push(transfer_record(t, &self.session_host));
+            }
+        }
+        crate::config::sftp::append_history(finished);
+        // Отменённые из списка убираем сразу
+        self.transfers.retain(|t| {
+            !(t.status == TransferStatus::Done && t.progress.cancel.load(Ordering::Relaxed))
+        });
+        // «Для всех» действует, пока очередь не разойдётся
+        let active = self
+            .transfers
+            .iter()
+            .any(|t| matches!(t.status, TransferStatus::Queued | TransferStatus::Running));
+        if !active {
+            self.conflict_policy = None;
+        }
+
+        let running = self
+            .transfers
+            .iter()
+            .filter(|t| t.status == TransferStatus::Running)
+            .count();
+        let free = self.parallel.saturating_sub(running);
+        let now = Instant::now();
+        let sudo = self.elevated.then(|| self.sudo_password.clone());
+        let perms = self.upload_perms;
+        for t in self
+            .transfers
+            .iter_mut()
+            .filter(|t| t.status == TransferStatus::Queued && t.retry_at.is_none_or(|at| at <= now))
+            .take(free)
+        {
+            if t.retry_at.take().is_some() {
+                t.progress.reset();
+            }
+            t.status = TransferStatus::Running;
+            t.started = Some(Instant::now());
+            let request = match &sudo {
+                Some(password) => t.request(perms).elevated(password.clone()),
+                None => t.request(perms),
+            };
+            let _ = self.request_tx.send(request);
+        }
+    }
+
+    /// Пауза/отмена передачи, которая ждёт ответа на конфликт или о нехватке места, —
+    /// воркер её уже не ведёт.
+    fn drop_conflict(&mut self, progress: &Arc<TransferState>) {
+        let before = self.conflicts.len() + self.low_space.len();
+        self.conflicts
+            .retain(|o| !Arc::ptr_eq(&o.progress, progress));
+        self.low_space
+            .retain(|o| !Arc::ptr_eq(&o.progress, progress));
+        if self.conflicts.len() + self.low_space.len() != before {
+            progress.failed.store(true, Ordering::Relaxed);
+        }
+    }
+
+    fn apply_queue_action(&mut self, action: QueueAction) {
+        match action {
+            QueueAction::MoveUp(i) if i > 0 => self.transfers.swap(i, i - 1),
+            QueueAction::MoveDown(i) if i + 1 < self.transfers.len() => {
+                self.transfers.swap(i, i + 1)
+            }
+            QueueAction::Pause(i) => {
+                let t = &mut self.transfers[i];
+                match t.status {
+                    TransferStatus::Queued => {
+                        t.status = TransferStatus::Paused;
+                        t.retry_at = None;
+                    }
+                    TransferStatus::Running => {
+                        t.progress.pause.store(true, Ordering::Relaxed);
+                        let progress = Arc::clone(&t.progress);
+                        self.drop_conflict(&progress);
+                    }
+                    _ => {}
+                }
+            }
+            // После паузы — докачка с того же места без вопросов
+            QueueAction::Resume(i) => {
+                let t = &mut self.transfers[i];
+                t.progress.reset();
+                t.resume = Some(true);
+                t.status = TransferStatus::Queued;
+                t.attempt = 0;
+                t.retry_at = None;
+            }
+            QueueAction::Retry(i) => {
+                let t = &mut self.transfers[i];
+                t.progress.reset();
+                t.resume = None;
+                t.status = TransferStatus::Queued;
+                t.attempt = 0;
+                t.retry_at = None;
+            }
+            QueueAction::Cancel(i) => {
+                if self.transfers[i].status == TransferStatus::Running {
+                    // Частичный файл остаётся — его можно будет докачать
+                    let progress = Arc::clone(&self.transfers[i].progress);
+                    progress.cancel.store(true, Ordering::Relaxed);
+                    self.drop_conflict(&progress);
+                } else {
+                    self.transfers.remove(i);
+                }
+            }
+            QueueAction::ClearFinished => self
+                .transfers
+                .retain(|t| !matches!(t.status, TransferStatus::Done | TransferStatus::Skipped)),
+            _ => {}
+        }
+        self.pump_queue();
+    }
+
+    /// Файл или папка с локального диска — в текущую удалённую папку.
+    fn upload_path(&mut self, path: &Path) {
+        let remote = format!(
+            "{}/{}",
+            self.current_path.trim_end_matches('/'),
+            local_file_name(path)
+        );
+        if path.is_dir() {
+            self.upload_dir(&path.to_string_lossy(), &remote);
+        } else {
+            self.upload(&path.to_string_lossy(), &remote);
+        }
+    }
+
+    pub fn mkdir(&self, path: &str) {
+        self.send(SftpRequest::Mkdir {
+            path: path.to_string(),
+            mode: self.upload_perms.dir_mode(None),
+        });
+    }
+
+    pub fn remove(&self, path: &str) {
+        self.send(SftpRequest::Remove(path.to_string()));
+    }
+
+    pub fn chmod(&self, path: &str, mode: u32) {
+        self.send(SftpRequest::Chmod {
+            path: path.to_string(),
+            mode,
+        });
+    }
+
+    // ── Path bar & history ──
+
+    /// Новая папка — в историю «назад», «вперёд» сбрасывается.
+    fn record_history(&mut self, path: &str) {
+        let listed = self.dir_cache.contains_key(&self.current_path);
+        if !std::mem::take(&mut self.history_nav) && listed && path != self.current_path {
+            self.back.push(self.current_path.clone());
+            self.forward.clear();
+        }
+    }
+
+    /// Имена подпапок прочитанной папки — для дополнения пути по Tab.
+    fn cache_subdirs(&mut self) {
+        if self.dir_cache.len() >= 256 {
+            self.dir_cache.clear();
+        }
+        let dirs = self
+            .entries
+            .iter()
+            .filter(|e| e.is_dir)
+            .map(|e| e.name.clone())
+            .collect();
+        self.dir_cache.insert(self.current_path.clone(), dirs);
+    }
+
+    fn go_back(&mut self) {
+        if let Some(path) = self.back.pop() {
+            self.forward.push(self.current_path.clone());
+            self.history_nav = true;
+            self.navigate(&path);
+        }
+    }
+
+    fn go_forward(&mut self) {
+        if let Some(path) = self.forward.pop() {
+            self.back.push(self.current_path.clone());
+            self.history_nav = true;
+            self.navigate(&path);
+        }
+    }
+
+    /// Хлебные крошки; [edit] превращает их в поле ввода
+    /// (Enter — перейти, Esc — отмена, Tab — дополнить).
+    fn show_path_bar(&mut self, ui: &mut egui::Ui) {
+        let edit_id = ui.make_persistent_id("sftp_path_edit");
+        let Some(text) = &mut self.path_edit else {
+            let mut target = None;
+            ui.horizontal(|ui| {
+                ui.spacing_mut().item_spacing.x = 2.0;
+                let mut path = String::new();
+                let segments = self.current_path.split('/').filter(|s| !s.is_empty());
+                for (i, segment) in std::iter::once("/").chain(segments).enumerate() {
+                    if i > 0 {
+                        path.push('/');
+                        path.push_str(segment);
+                    }
+                    if i > 1 {
+                        ui.monospace("/");
+                    }
+                    let clicked = ui
+                        .add(
+                            egui::Label::new(egui::RichText::new(segment).monospace())
+                                .sense(egui::Sense::click()),
+                        )
+                        .on_hover_cursor(egui::CursorIcon::PointingHand)
+                        .clicked();
+                    if clicked {
+                        target = Some(if i == 0 { "/".to_string() } else { path.clone() });
+                    }
+                }
+            });
+            if ui.small_button("[edit]").clicked() {
+                self.path_edit = Some(self.current_path.clone());
+                ui.memory_mut(|m| m.request_focus(edit_id));
+            }
+            if target.is_some() {
+                self.navigate_to = target;
+            }
+            return;
+        };
+
+        let mut output = egui::TextEdit::singleline(text)
+            .id(edit_id)
+            .font(egui::TextStyle::Monospace)
+            .desired_width(320.0)
+            .lock_focus(true)
+            .show(ui);
+        let response = &output.response;
+        if response.has_focus() && ui.input(|i| i.key_pressed(egui::Key::Tab)) {
+            if let Some(completed) = complete_path(&self.dir_cache, &self.current_path, text) {
+                *text = completed;
+                let end = egui::text::CCursor::new(text.chars().count());
+                output
+                    .state
+                    .cursor
+                    .set_char_range(Some(egui::text::CCursorRange::one(end)));
+                output.state.store(ui.ctx(), edit_id);
+            }
+        }
+        if response.lost_focus() {
+            if ui.input(|i| i.key_pressed(egui::Key::Enter)) {
+                self.navigate_to = Some(join_remote(&self.current_path, text));
+            }
+            self.path_edit = None;
+        }
+    }
+
+    fn start_search(&mut self) {
+        let Some(search) = &mut self.search else {
+            return;
+        };
+        search.stop();
+        match search.query() {
+            Ok(query) => {
+                self.request_seq += 1;
+                let cancel = Arc::new(AtomicBool::new(false));
+                search.results.clear();
+                search.status = "searching...".to_string();
+                search.running = Some((self.request_seq, Arc::clone(&cancel)));
+                let _ = self.request_tx.send(SftpRequest::Search {
+                    id: self.request_seq,
+                    query,
+                    cancel,
+                });
+            }
+            Err(e) => search.status = e,
+        }
+    }
+
+    fn apply_search_update(&mut self, id: u64, update: SearchUpdate) {
+        // Результаты остановленного или прошлого поиска не нужны
+        let Some(search) = self
+            .search
+            .as_mut()
+            .filter(|s| s.running.as_ref().is_some_and(|(run, _)| *run == id))
+        else {
+            return;
+        };
+        match update {
+            SearchUpdate::Found(entries) => search.results.extend(entries),
+            SearchUpdate::Done(summary) => {
+                search.status = summary;
+                search.running = None;
+            }
+            SearchUpdate::Failed(e) => {
+                search.status = format!("ERR: {}", e);
+                search.running = None;
+            }
+        }
+    }
+
+    fn show_search_dialog(&mut self, ctx: &egui::Context) {
+        let Some(search) = &mut self.search else {
+            return;
+        };
+        let mut open = true;
+        let mut start = false;
+        let mut action: Option<SearchAction> = None;
+        egui::Window::new("find")
+            .collapsible(false)
+            .resizable(true)
+            .default_width(520.0)
+            .open(&mut open)
+            .show(ctx, |ui| {
+                egui::Grid::new("search_grid")
+                    .num_columns(2)
+                    .spacing([12.0, 4.0])
+                    .show(ui, |ui| {
+                        ui.label("in:");
+                        ui.add(
+                            egui::TextEdit::singleline(&mut search.root)
+                                .font(egui::TextStyle::Monospace)
+                                .desired_width(320.0),
+                        );
+                        ui.end_row();
+                        ui.label("name:");
+                        let response = ui.add(
+                            egui::TextEdit::singleline(&mut search.pattern)
+                                .hint_text("substring or *.glob")
+                                .font(egui::TextStyle::Monospace)
+                                .desired_width(320.0),
+                        );
+                        if response.lost_focus() && ui.input(|i| i.key_pressed(egui::Key::Enter)) {
+                            start = true;
+                        }
+                        ui.end_row();
+                        ui.label("size:");
+                        ui.horizontal(|ui| {
+                            ui.add(
+                                egui::TextEdit::singleline(&mut search.min_size)
+                                    .hint_text("min")
+                                    .desired_width(60.0),
+                            );
+                            ui.label("..");
+                            ui.add(
+                                egui::TextEdit::singleline(&mut search.max_size)
+                                    .hint_text("max")
+                                    .desired_width(60.0),
+                            );
+                        })
+                        .response
+                        .on_hover_text("bytes or with K/M/G suffix");
+                        ui.end_row();
+                        ui.label("modified:");
+                        ui.horizontal(|ui| {
+                            ui.label("within");
+                            ui.add(
+                                egui::TextEdit::singleline(&mut search.within_days)
+                                    .desired_width(40.0),
+                            );
+                            ui.label("days");
+                        });
+                        ui.end_row();
+                        ui.label("depth:");
+                        ui.add(egui::DragValue::new(&mut search.max_depth).range(1..=64));
+                        ui.end_row();
+                    });
+
+                ui.horizontal(|ui| {
+                    if search.running.is_some() {
+                        if ui.button("[stop]").clicked() {
+                            search.stop();
+                            search.status = format!("stopped, {} found", search.results.len());
+                        }
+                        ui.spinner();
+                    } else if ui.button("[search]").clicked() {
+                        start = true;
+                    }
+                    ui.colored_label(crate::theme::GREY, &search.status);
+                });
+                ui.separator();
+
+                egui::ScrollArea::vertical()
+                    .max_height(320.0)
+                    .auto_shrink([false, true])
+                    .show_rows(ui, 20.0, search.results.len(), |ui, range| {
+                        for entry in &search.results[range] {
+                            ui.horizontal(|ui| {
+                                if ui.small_button("[open]").clicked() {
+                                    action = Some(SearchAction::Open(entry.clone()));
+                                }
+                                if !entry.is_dir && ui.small_button("[get]").clicked() {
+                                    action = Some(SearchAction::Download(entry.clone()));
+                                }
+                                if ui.small_button("[rm]").clicked() {
+                                    action = Some(SearchAction::Remove(entry.clone()));
+                                }
+                                let suffix = if entry.is_dir { "/" } else { "" };
+                                ui.monospace(format!("{}{}", entry.path, suffix));
+                                if !entry.is_dir {
+                                    ui.colored_label(crate::theme::GREY, format_size(entry.size));
+                                }
+                            });
+                        }
+                    });
+            });
+        if start {
+            self.start_search();
+        }
+        if let Some(action) = action {
+            match action {
+                SearchAction::Open(entry) => {
+                    if entry.is_dir {
+                        self.navigate(&entry.path);
+                    } else {
+                        // Папка файла, отфильтрованная до него самого
+                        let parent = Path::new(&entry.path)
+                            .parent()
+                            .map(|p| p.to_string_lossy().to_string())
+                            .unwrap_or_else(|| "/".to_string());
+                        self.navigate(&parent);
+                        self.filter = entry.name.clone();
+                    }
+                }
+                SearchAction::Download(entry) => {
+                    if let Some(dir) = self.download_dir() {
+                        let local = dir.join(&entry.name);
+                        self.download(&entry.path, &local.to_string_lossy(), entry.size);
+                    }
+                }
+                SearchAction::Remove(entry) => self.confirm_delete(&entry),
+            }
+        }
+        if !open {
+            if let Some(search) = &mut self.search {
+                search.stop();
+            }
+            self.search = None;
+        }
+    }
+
+    fn start_sync_compare(&mut self) {
+        let Some(sync) = &mut self.sync else {
+            return;
+        };
+        if sync.spec.local.trim().is_empty() || sync.spec.remote.trim().is_empty() {
+            sync.status = "choose both folders".to_string();
+            return;
+        }
+        self.request_seq += 1;
+        sync.comparing = Some(self.request_seq);
+        sync.plan = None;
+        sync.status = "comparing...".to_string();
+        let _ = self.request_tx.send(SftpRequest::SyncCompare {
+            id: self.request_seq,
+            spec: sync.spec.clone(),
+        });
+    }
+
+    fn apply_sync_plan(&mut self, id: u64, result: Result<SyncPlan, String>) {
+        let Some(sync) = self.sync.as_mut().filter(|s| s.comparing == Some(id)) else {
+            return;
+        };
+        sync.comparing = None;
+        let result = result.map(|plan| {
+            sync.unreadable = plan.unreadable;
+            plan.items
+        });
+        match result {
+            Ok(items) if items.is_empty() => sync.status = "up to date".to_string(),
+            Ok(items) => {
+                let count = |op| items.iter().filter(|i| i.op == op).count();
+                let bytes = items
+                    .iter()
+                    .filter(|i| i.op == SyncOp::Copy)
+                    .map(|i| i.size)
+                    .sum();
+                sync.status = format!(
+                    "{} to copy ({}), {} mkdir, {} to delete",
+                    count(SyncOp::Copy),
+                    format_size(bytes),
+                    count(SyncOp::Mkdir),
+                    count(SyncOp::Delete),
+                );
+                sync.plan = Some((sync.spec.clone(), items));
+            }
+            Err(e) => sync.status = format!("ERR: {}", e),
+        }
+    }
+
+    /// Отмеченные шаги плана — одной задачей в очередь передач.
+    fn run_sync_plan(&mut self) {
+        let Some((spec, items)) = self.sync.take().and_then(|s| s.plan) else {
+            return;
+        };
+        let items: Vec<SyncItem> = items.into_iter().filter(|i| i.enabled).collect();
+        let name = format!("sync {}/", local_file_name(Path::new(&spec.local)));
+        let is_upload = spec.direction == SyncDirection::Upload;
+        self.enqueue(
+            TransferJob::Sync { spec, items },
+            TransferState::new_dir(&name, is_upload),
+        );
+    }
+
+    fn show_sync_dialog(&mut self, ctx: &egui::Context) {
+        let Some(sync) = &mut self.sync else {
+            return;
+        };
+        let mut open = true;
+        let mut compare = false;
+        let mut run = false;
+        egui::Window::new("sync")
+            .collapsible(false)
+            .resizable(true)
+            .default_width(560.0)
+            .open(&mut open)
+            .show(ctx, |ui| {
+                egui::Grid::new("sync_grid")
+                    .num_columns(2)
+                    .spacing([12.0, 4.0])
+                    .show(ui, |ui| {
+                        ui.label("local:");
+                        ui.horizontal(|ui| {
+                            ui.add(
+                                egui::TextEdit::singleline(&mut sync.spec.local)
+                                    .hint_text("local folder")
+                                    .font(egui::TextStyle::Monospace)
+                                    .desired_width(320.0),
+                            );
+                            if ui.button("[browse...]").clicked() {
+                                if let Some(dir) = rfd::FileDialog::new()
+                                    .set_title("Local folder to sync")
+                                    .pick_folder()
+                                {
+                                    sync.spec.local = dir.to_string_lossy().to_string();
+                                }
+                            }
+                        });
+                        ui.end_row();
+                        ui.label("remote:");
+                        ui.add(
+                            egui::TextEdit::singleline(&mut sync.spec.remote)
+                                .font(egui::TextStyle::Monospace)
+                                .desired_width(320.0),
+                        );
+                        ui.end_row();
+                        ui.label("direction:");
+                        ui.horizontal(|ui| {
+                            let direction = &mut sync.spec.direction;
+                            ui.radio_value(direction, SyncDirection::Upload, "local -> remote");
+                            ui.radio_value(direction, SyncDirection::Download, "remote -> local");
+                        });
+                        ui.end_row();
+                        ui.label("options:");
+                        ui.vertical(|ui| {
+                            ui.checkbox(&mut sync.spec.delete, "delete files missing in source");
+                            let checksum = "compare same-size files by sha256";
+                            ui.checkbox(&mut sync.spec.checksum, checksum)
+                                .on_hover_text("needs sha256sum on the server; reads every file");
+                        });
+                        ui.end_row();
+                    });
+
+                ui.horizontal(|ui| {
+                    if sync.comparing.is_some() {
+                        ui.spinner();
+                    } else if ui.button("[compare]").clicked() {
+                        compare = true;
+                    }
+                    ui.colored_label(crate::theme::GREY, &sync.status);
+                });
+                for error in &sync.unreadable {
+                    ui.colored_label(crate::theme::RED, format!("skipped {}", error));
+                }
+
+                if let Some((spec, items)) = &mut sync.plan {
+                    ui.separator();
+                    egui::ScrollArea::vertical()
+                        .max_height(320.0)
+                        .auto_shrink([false, true])
+                        .show_rows(ui, 20.0, items.len(), |ui, range| {
+                            for item in &mut items[range] {
+                                ui.horizontal(|ui| {
+                                    ui.checkbox(&mut item.enabled, "");
+                                    let (label, color) = item.label(spec.direction);
+                                    ui.colored_label(color, format!("{:5}", label));
+                                    let suffix = if item.is_dir { "/" } else { "" };
+                                    let rel = if item.rel.is_empty() { "." } else { &item.rel };
+                                    ui.monospace(format!("{}{}", rel, suffix));
+                                    if item.op == SyncOp::Copy {
+                                        ui.colored_label(
+                                            crate::theme::GREY,
+                                            format_size(item.size),
+                                        );
+                                    }
+                                    ui.colored_label(crate::theme::GREY, item.reason);
+                                });
+                            }
+                        });
+                    let enabled = items.iter().filter(|i| i.enabled).count();
+                    if ui
+                        .add_enabled(enabled > 0, egui::Button::new(format!("[run {}]", enabled)))
+                        .clicked()
+                    {
+                        run = true;
+                    }
+                }
+            });
+        if compare {
+            self.start_sync_compare();
+        }
+        if run {
+            self.run_sync_plan();
+        }
+        if !open {
+            self.sync = None;
+        }
+    }
+
+    /// Открывает превью картинки (или закрывает, если это не картинка).
+    fn show_preview_of(&mut self, entry: &SftpEntry) {
+        if entry.is_dir || !is_image_name(&entry.name) {
+            return;
+        }
+        let state = if entry.size > PREVIEW_MAX_BYTES {
+            PreviewState::Failed(format!(
+                "too large for preview (> {})",
+                format_size(PREVIEW_MAX_BYTES)
+            ))
+        } else {
+            let _ = self
+                .request_tx
+                .send(SftpRequest::Preview(entry.path.clone()));
+            PreviewState::Loading
+        };
+        self.preview = Some(Preview {
+            path: entry.path.clone(),
+            name: entry.name.clone(),
+            size: entry.size,
+            state,
+        });
+    }
+
+    fn set_preview_image(&mut self, path: &str, result: Result<PreviewImage, String>) {
+        // Пока грузилось, могли выбрать другой файл
+        let Some(preview) = self.preview.as_mut().filter(|p| p.path == path) else {
+            return;
+        };
+        preview.state = match result {
+            Ok(image) => PreviewState::Decoded(image),
+            Err(e) => PreviewState::Failed(e),
+        };
+    }
+
+    fn show_preview_panel(&mut self, ui: &mut egui::Ui) {
+        let Some(preview) = &mut self.preview else {
+            return;
+        };
+        if let PreviewState::Decoded(image) = &preview.state {
+            let color_image =
+                egui::ColorImage::from_rgba_unmultiplied([image.width, image.height], &image.rgba);
+            let texture =
+                ui.ctx()
+                    .load_texture("sftp-preview", color_image, egui::TextureOptions::LINEAR);
+            preview.state = PreviewState::Ready(texture, image.original);
+        }
+        let mut close = false;
+        egui::SidePanel::right("sftp_preview")
+            .resizable(true)
+            .default_width(280.0)
+            .show_inside(ui, |ui| {
+                ui.horizontal(|ui| {
+                    ui.monospace(&preview.name);
+                    ui.with_layout(egui::Layout::right_to_left(egui::Align::Center), |ui| {
+                        if ui.small_button("[x]").clicked() {
+                            close = true;
+                        }
+                    });
+                });
+                match &preview.state {
+                    PreviewState::Loading | PreviewState::Decoded(_) => {
+                        ui.spinner();
+                    }
+                    PreviewState::Failed(e) => {
+                        ui.colored_label(crate::theme::RED, e);
+                    }
+                    PreviewState::Ready(texture, (w, h)) => {
+                        ui.colored_label(
+                            crate::theme::GREY,
+                            format!("{}x{}  {}", w, h, format_size(preview.size)),
+                        );
+                        ui.add(
+                            egui::Image::new(texture)
+                                .max_width(ui.available_width())
+                                .maintain_aspect_ratio(true),
+                        );
+                    }
+                }
+            });
+        if close {
+            self.preview = None;
+        }
+    }
+
+    pub fn symlink(&self, link: &str, target: &str) {
+        self.send(SftpRequest::Symlink {
+            link: link.to_string(),
+            target: target.to_string(),
+        });
+    }
+
+    pub fn chown(&self, path: &str, uid: u32, gid: u32) {
+        self.send(SftpRequest::Chown {
+            path: path.to_string(),
+            uid,
+            gid,
+        });
+    }
+
+    pub fn rename(&self, from: &str, to: &str) {
+        self.send(SftpRequest::Rename {
+            from: from.to_string(),
+            to: to.to_string(),
+        });
+    }
+
+    fn poll(&mut self) {
+        while let Ok(response) = self.response_rx.try_recv() {
+            match response {
+                SftpResponse::DirListing(path, entries) => {
+                    self.apply_dir_page(path, entries, true, true);
+                }
+                SftpResponse::DirPage {
+                    path,
+                    entries,
+                    first,
+                    last,
+                } => self.apply_dir_page(path, entries, first, last),
+                SftpResponse::Error(e) => {
+                    self.error = Some(e);
+                    self.loading = false;
+                    self.listing_more = false;
+                    self.history_nav = false;
+                }
+                SftpResponse::Success(msg) => {
+                    self.status_message = Some(msg);
+                    let _ = self
+                        .request_tx
+                        .send(SftpRequest::ListDir(self.current_path.clone()));
+                }
+                SftpResponse::Conflict(conflict) => self.push_conflict(conflict),
+                SftpResponse::LowSpace(low) => self.low_space.push(low),
+                SftpResponse::Accounts(accounts) => self.accounts = accounts,
+                SftpResponse::Preview(path, result) => self.set_preview_image(&path, result),
+                SftpResponse::Search(id, update) => self.apply_search_update(id, update),
+                SftpResponse::SyncPlan(id, result) => self.apply_sync_plan(id, result),
+                SftpResponse::TreeStats(id, result) => {
+                    if let Some(path) = self.size_requests.remove(&id) {
+                        match result {
+                            Ok(stats) => {
+                                self.dir_sizes.insert(path, Some(stats));
+                                self.summary = None;
+                            }
+                            Err(e) => {
+                                self.dir_sizes.remove(&path);
+                                self.summary = None;
+                                self.error = Some(format!("size '{}': {}", path, e));
+                            }
+                        }
+                    } else if let Some(confirm) = &mut self.delete_confirm {
+                        if confirm.id == id {
+                            confirm.stats = Some(result);
+                        }
+                    }
+                }
+                SftpResponse::DiskUsage(path, usage) => {
+                    if path == self.current_path {
+                        self.disk_usage = Some(usage);
+                    }
+                }
+                SftpResponse::DirStamp(path, mtime) => {
+                    if path != self.current_path {
+                        continue;
+                    }
+                    let changed = self.dir_mtime.is_some() && mtime != self.dir_mtime;
+                    self.dir_mtime = mtime;
+                    if changed && !self.loading && !self.listing_more {
+                        self.reload_quietly();
+                    }
+                }
+                SftpResponse::ScpFallback(reason) => {
+                    self.scp_only = true;
+                    self.status_message = Some(format!("{} — SCP mode", reason));
+                }
+                SftpResponse::Retrying(msg) => self.status_message = Some(msg),
+                SftpResponse::SudoPassword { req, wrong } => {
+                    if wrong {
+                        self.sudo_password = None;
+                    }
+                    self.sudo_pending.push(*req);
+                    let prompt = self.sudo_prompt.get_or_insert_with(|| (String::new(), false));
+                    prompt.1 |= wrong;
+                }
+            }
+        }
+
+        self.pump_queue();
+    }
+
+    pub fn show(&mut self, ui: &mut egui::Ui) {
+        self.poll();
+        self.watch_directory(ui.ctx());
+        // Отложенный повтор должен стартовать и без движения мыши
+        if self.transfers.iter().any(|t| t.retry_at.is_some()) {
+            ui.ctx().request_repaint_after(Duration::from_secs(1));
+        }
+
+        // Drag & Drop
+        let dropped = ui.ctx().input(|i| i.raw.dropped_files.clone());
+        for file in &dropped {
+            if let Some(path) = &file.path {
+                self.upload_path(path);
+            }
+        }
+
+        // Toolbar
+        ui.horizontal(|ui| {
+            if ui
+                .add_enabled(!self.back.is_empty(), egui::Button::new("[<]"))
+                .on_hover_text("back")
+                .clicked()
+            {
+                self.go_back();
+            }
+            if ui
+                .add_enabled(!self.forward.is_empty(), egui::Button::new("[>]"))
+                .on_hover_text("forward")
+                .clicked()
+            {
+                self.go_forward();
+            }
+            if ui.button("[..]").clicked() {
+                let parent = std::path::Path::new(&self.current_path)
+                    .parent()
+                    .map(|p| p.to_string_lossy().to_string())
+                    .unwrap_or_else(|| "/".to_string());
+                self.navigate_to = Some(parent);
+            }
+            ui.separator();
+            self.show_path_bar(ui);
+            ui.separator();
+            if ui.button("[reload]").clicked() {
+                self.navigate_to = Some(self.current_path.clone());
+            }
+            let mut watch = self.settings.watch;
+            if ui
+                .checkbox(&mut watch, "watch")
+                .on_hover_text("reload when the folder changes on the server")
+                .changed()
+            {
+                self.settings.watch = watch;
+                self.dir_mtime = None;
+                crate::config::sftp::update_settings(|settings| settings.watch = watch);
+            }
+            ui.separator();
+            if ui.button("[mkdir]").clicked() {
+                self.show_mkdir_dialog = true;
+                self.mkdir_is_file = false;
+                self.mkdir_name.clear();
+            }
+            if ui.button("[new file]").clicked() {
+                self.show_mkdir_dialog = true;
+                self.mkdir_is_file = true;
+                self.mkdir_name.clear();
+            }
+            if ui.button("[find]").clicked() && self.search.is_none() {
+                self.search = Some(SearchDialog::new(&self.current_path));
+            }
+            if ui.button("[sync]").clicked() && self.sync.is_none() {
+                self.sync = Some(SyncDialog::new(&self.current_path));
+            }
+            if ui.button("[history]").clicked() && self.history.is_none() {
+                self.history = Some(crate::config::sftp::load_history());
+            }
+            let mut elevated = self.elevated;
+            if ui
+                .checkbox(&mut elevated, "sudo")
+                .on_hover_text(
+                    "upload, create, delete, rename and permission changes run via sudo \
+                     (password asked once and kept until sudo is turned off)",
+                )
+                .changed()
+            {
+                self.elevated = elevated;
+                if !elevated {
+                    self.sudo_password = None;
+                }
+            }
+            if self.scp_only {
+                ui.colored_label(crate::theme::AMBER, "SCP").on_hover_text(
+                    "server has no SFTP: listing via ls, single files via scp, \
+                     no folders, search, sync or permissions",
+                );
+            }
+        });
+
+        ui.horizontal(|ui| {
+            let n = self.selected.len();
+            if ui
+                .add_enabled(n > 0, egui::Button::new(format!("[get {}]", n)))
+                .clicked()
+            {
+                self.download_selected();
+            }
+            if ui.add_enabled(n > 0, egui::Button::new("[get to...]")).clicked() {
+                if let Some(dir) = self.pick_download_dir() {
+                    self.download_selected_to(&dir);
+                }
+            }
+            ui.separator();
+            if ui.button("[put...]").clicked() {
+                self.upload_via_dialog();
+            }
+            if ui.button("[put dir...]").clicked() {
+                if let Some(dir) = rfd::FileDialog::new()
+                    .set_title("Select folder to upload")
+                    .pick_folder()
+                {
+                    self.upload_path(&dir);
+                }
+            }
+            if let Some(clipboard) = &self.clipboard {
+                let verb = if clipboard.cut { "move" } else { "paste" };
+                let hover = clipboard.paths.join("\n");
+                if ui
+                    .button(format!("[{} {}]", verb, clipboard.paths.len()))
+                    .on_hover_text(hover)
+                    .clicked()
+                {
+                    self.paste();
+                }
+            }
+            ui.separator();
+            if n > 0 {
+                if ui.button("[clear]").clicked() {
+                    self.selected.clear();
+                    self.summary = None;
+                }
+            } else if !self.entries.is_empty() {
+                if ui.button("[sel all]").clicked() {
+                    // Только видимые через фильтр
+                    let visible: Vec<String> = self
+                        .view
+                        .iter()
+                        .filter_map(|&i| self.entries.get(i))
+                        .filter(|e| !e.is_dir)
+                        .map(|e| e.path.clone())
+                        .collect();
+                    self.selected.extend(visible);
+                    self.summary = None;
+                }
+            }
+        });
+
+        self.show_opened_files(ui);
+
+        // Transfer queue
+        if !self.transfers.is_empty() {
+            ui.add_space(2.0);
+            if let Some(action) = self.show_transfer_queue(ui) {
+                self.apply_queue_action(action);
+            }
+            ui.add_space(2.0);
+            if self
+                .transfers
+                .iter()
+                .any(|t| t.status == TransferStatus::Running)
+            {
+                ui.ctx().request_repaint();
+            }
+        }
+
+        // Errors / status
+        if let Some(err) = &self.error {
+            ui.colored_label(crate::theme::RED, format!("ERR: {}", err));
+        }
+        if let Some(msg) = self.status_message.take() {
+            ui.colored_label(crate::theme::GREEN, &msg);
+        }
+
+        if self.loading {
+            ui.spinner();
+            return;
+        }
+
+        ui.separator();
+
+        // Подвал считает по view — он должен соответствовать текущему листингу
+        self.refresh_view();
+        self.show_footer(ui);
+        self.show_preview_panel(ui);
+
+        // Filter
+        ui.horizontal(|ui| {
+            ui.label("filter:");
+            let response = ui.add(
+                egui::TextEdit::singleline(&mut self.filter)
+                    .hint_text("substring or *.glob")
+                    .font(egui::TextStyle::Monospace)
+                    .desired_width(200.0),
+            );
+            if response.has_focus() && ui.input(|i| i.key_pressed(egui::Key::Escape)) {
+                self.filter.clear();
+            }
+            self.refresh_view();
+            if !self.filter.is_empty() {
+                ui.colored_label(
+                    crate::theme::GREY,
+                    format!("{}/{}", self.view.len(), self.entries.len()),
+                );
+                if ui.small_button("[x]").clicked() {
+                    self.filter.clear();
+                }
+            }
+            if self.listing_more {
+                ui.spinner();
+                ui.colored_label(
+                    crate::theme::GREY,
+                    format!("listing... {} entries", self.entries.len()),
+                );
+            }
+        });
+        self.refresh_view();
+
+        // File table
+        let mut navigate_path: Option<String> = None;
+        let mut delete_entry: Option<SftpEntry> = None;
+        let mut touch_path: Option<String> = None;
+        let mut clip_entry: Option<(String, bool)> = None;
+        let mut shell_dir: Option<String> = None;
+        let mut size_of: Option<String> = None;
+        let mut drag_started: Option<String> = None;
+        let mut archive_of: Option<SftpEntry> = None;
+        let mut open_with: Option<SftpEntry> = None;
+        let mut paste_into: Option<String> = None;
+        let mut get_to: Option<SftpEntry> = None;
+        let mut send_to: Option<(String, SftpPeer)> = None;
+        let mut properties_for: Option<SftpEntry> = None;
+        let mut symlink_to: Option<SftpEntry> = None;
+        let mut rename_of: Option<SftpEntry> = None;
+        // Поле переименования: true — Enter, false — Esc или щелчок мимо
+        let mut rename_done: Option<bool> = None;
+        let mut toggle_selection: Vec<(String, bool)> = Vec::new();
+        let mut download_single: Vec<(String, String, u64)> = Vec::new();
+
+        let scroll_row = self.handle_table_keys(ui.ctx());
+        let cursor_row = self.cursor_row();
+        let mut cursor_click: Option<String> = None;
+        // Таблица рисует только видимые строки — записи берём по индексам, без копий
+        let entries = &self.entries;
+        let view = &self.view;
+        let selected = &self.selected;
+        let dir_sizes = &self.dir_sizes;
+        let peers = &self.peers;
+        let download_dir = self.download_dir();
+        let current_path = self.current_path.clone();
+        let rename = &mut self.rename;
+
+        let available_height = ui.available_height();
+        let accounts = &self.accounts;
+        let mut columns = self.settings.columns;
+        let shown = self.settings.columns;
+        let sort = self.settings.sort;
+        let mut sort_by: Option<SortColumn> = None;
+        let now = std::time::SystemTime::now()
+            .duration_since(std::time::UNIX_EPOCH)
+            .map(|d| d.as_secs())
+            .unwrap_or(0);
+
+        egui::ScrollArea::vertical()
+            .max_height(available_height)
+            .show(ui, |ui| {
+                let mut table = egui_extras::TableBuilder::new(ui)
+                    .striped(true)
+                    .resizable(true)
+                    .cell_layout(egui::Layout::left_to_right(egui::Align::Center))
+                    .column(egui_extras::Column::exact(28.0))
+                    .column(egui_extras::Column::remainder().at_least(200.0));
+                if shown.perms {
+                    table = table.column(egui_extras::Column::auto().at_least(90.0));
+                }
+                if shown.owner {
+                    table = table.column(egui_extras::Column::auto().at_least(60.0));
+                }
+                if shown.group {
+                    table = table.column(egui_extras::Column::auto().at_least(60.0));
+                }
+                if let Some(row) = scroll_row {
+                    table = table.scroll_to_row(row, None);
+                }
+                table
+                    .column(egui_extras::Column::auto().at_least(80.0))
+                    .column(egui_extras::Column::auto().at_least(140.0))
+                    .header(24.0, |mut header| {
+                        header.col(|ui| { ui.label(""); });
+                        header.col(|ui| {
+                            if columns.header(ui, &sort.title("NAME", SortColumn::Name)) {
+                                sort_by = Some(SortColumn::Name);
+                            }
+                        });
+                        if shown.perms {
+                            header.col(|ui| {
+                                columns.header(ui, "PERMS");
+                            });
+                        }
+                        if shown.owner {
+                            header.col(|ui| {
+                                columns.header(ui, "OWNER");
+                            });
+                        }
+                        if shown.group {
+                            header.col(|ui| {
+                                columns.header(ui, "GROUP");
+                            });
+                        }
+                        header.col(|ui| {
+                            if columns.header(ui, &sort.title("SIZE", SortColumn::Size)) {
+                                sort_by = Some(SortColumn::Size);
+                            }
+                        });
+                        header.col(|ui| {
+                            if columns.header(ui, &sort.title("MODIFIED", SortColumn::Modified)) {
+                                sort_by = Some(SortColumn::Modified);
+                            }
+                        });
+                    })
+                    .body(|body| {
+                        body.rows(22.0, view.len(), |mut row| {
+                            let idx = row.index();
+                            let entry = &entries[view[idx]];
+                            row.set_selected(cursor_row == Some(idx));
+
+                            row.col(|ui| {
+                                let mut checked = selected.contains(&entry.path);
+                                if ui.checkbox(&mut checked, "").changed() {
+                                    toggle_selection.push((entry.path.clone(), checked));
+                                }
+                            });
+
+                            row.col(|ui| {
+                                let editing = rename.as_mut().filter(|r| r.path == entry.path);
+                                if let Some(edit) = editing {
+                                    let (icon, color) = entry_glyph(entry);
+                                    ui.colored_label(color, icon);
+                                    rename_done = edit.show(ui);
+                                    return;
+                                }
+                                let is_sel = selected.contains(&entry.path);
+                                let (icon, color) = entry_glyph(entry);
+                                let text = match &entry.link_target {
+                                    Some(target) => {
+                                        format!("{} {} -> {}", icon, entry.name, target)
+                                    }
+                                    None => format!("{} {}", icon, entry.name),
+                                };
+                                let label = egui::RichText::new(text).color(color);
+
+                                let response = ui
+                                    .selectable_label(is_sel, label)
+                                    .interact(egui::Sense::drag());
+                                if !entry.is_dir && response.drag_started() {
+                                    drag_started = Some(entry.path.clone());
+                                }
+
+                                if response.clicked() {
+                                    cursor_click = Some(entry.path.clone());
+                                    if entry.is_dir {
+                                        navigate_path = Some(entry.path.clone());
+                                    } else {
+                                        toggle_selection.push((entry.path.clone(), !is_sel));
+                                    }
+                                }
+
+                                response.context_menu(|ui| {
+                                    if !entry.is_dir {
+                                        if ui.button("[get]").clicked() {
+                                            if let Some(dir) = &download_dir {
+                                                let local = dir.join(&entry.name);
+                                                download_single.push((
+                                                    entry.path.clone(),
+                                                    local.to_string_lossy().to_string(),
+                                                    entry.size,
+                                                ));
+                                            }
+                                            ui.close_menu();
+                                        }
+                                    }
+                                    if !entry.is_dir && ui.button("[get to...]").clicked() {
+                                        get_to = Some(entry.clone());
+                                        ui.close_menu();
+                                    }
+                                    if !entry.is_dir && ui.button("[open with...]").clicked() {
+                                        open_with = Some(entry.clone());
+                                        ui.close_menu();
+                                    }
+                                    if entry.is_dir {
+                                        if ui.button("[open]").clicked() {
+                                            navigate_path = Some(entry.path.clone());
+                                            ui.close_menu();
+                                        }
+                                    }
+                                    if entry.is_dir && ui.button("[get .tar.gz]").clicked() {
+                                        archive_of = Some(entry.clone());
+                                        ui.close_menu();
+                                    }
+                                    if entry.is_dir && ui.button("[calculate size]").clicked() {
+                                        size_of = Some(entry.path.clone());
+                                        ui.close_menu();
+                                    }
+                                    if ui.button("[copy path]").clicked() {
+                                        ui.ctx().copy_text(entry.path.clone());
+                                        ui.close_menu();
+                                    }
+                                    if ui.button("[open shell here]").clicked() {
+                                        let dir = if entry.is_dir {
+                                            entry.path.clone()
+                                        } else {
+                                            remote_parent(&entry.path)
+                                        };
+                                        shell_dir = Some(dir);
+                                        ui.close_menu();
+                                    }
+                                    if ui.button("[properties]").clicked() {
+                                        properties_for = Some(entry.clone());
+                                        ui.close_menu();
+                                    }
+                                    if ui.button("[symlink...]").clicked() {
+                                        symlink_to = Some(entry.clone());
+                                        ui.close_menu();
+                                    }
+                                    if ui.button("[rename]").clicked() {
+                                        rename_of = Some(entry.clone());
+                                        ui.close_menu();
+                                    }
+                                    if ui.button("[touch]").clicked() {
+                                        touch_path = Some(entry.path.clone());
+                                        ui.close_menu();
+                                    }
+                                    if ui.button("[cut]").clicked() {
+                                        clip_entry = Some((entry.path.clone(), true));
+                                        ui.close_menu();
+                                    }
+                                    if ui.button("[copy]").clicked() {
+                                        clip_entry = Some((entry.path.clone(), false));
+                                        ui.close_menu();
+                                    }
+                                    if !peers.is_empty() {
+                                        ui.menu_button("[send to]", |ui| {
+                                            for peer in peers {
+                                                let label = format!("{}:{}", peer.name, peer.dir);
+                                                if ui.button(label).clicked() {
+                                                    send_to =
+                                                        Some((entry.path.clone(), peer.clone()));
+                                                    ui.close_menu();
+                                                }
+                                            }
+                                        });
+                                    }
+                                    if ui.button("[paste to file...]").clicked() {
+                                        let dir = if entry.is_dir {
+                                            entry.path.clone()
+                                        } else {
+                                            remote_parent(&entry.path)
+                                        };
+                                        paste_into = Some(dir);
+                                        ui.close_menu();
+                                    }
+                                    ui.separator();
+                                    if ui.button("[rm]").clicked() {
+                                        delete_entry = Some(entry.clone());
+                                        ui.close_menu();
+                                    }
+                                });
+                            });
+
+                            if shown.perms {
+                                row.col(|ui| {
+                                    if let Some(mode) = entry.mode {
+                                        ui.monospace(format!(
+                                            "{}{}",
+                                            file_type_char(mode),
+                                            format_mode(mode)
+                                        ));
+                                    }
+                                });
+                            }
+                            if shown.owner {
+                                row.col(|ui| {
+                                    if let Some(uid) = entry.uid {
+                                        match accounts.user_name(uid) {
+                                            Some(name) => ui.label(name),
+                                            None => ui.label(uid.to_string()),
+                                        };
+                                    }
+                                });
+                            }
+                            if shown.group {
+                                row.col(|ui| {
+                                    if let Some(gid) = entry.gid {
+                                        match accounts.group_name(gid) {
+                                            Some(name) => ui.label(name),
+                                            None => ui.label(gid.to_string()),
+                                        };
+                                    }
+                                });
+                            }
+
+                            row.col(|ui| {
+                                if !entry.is_dir {
+                                    ui.label(format_size(entry.size));
+                                } else if let Some(stats) = dir_sizes.get(&entry.path) {
+                                    match stats {
+                                        Some(stats) => ui
+                                            .label(format_size(stats.bytes))
+                                            .on_hover_text(format!(
+                                                "{} files, {} dirs",
+                                                stats.files, stats.dirs
+                                            )),
+                                        None => ui.colored_label(crate::theme::GREY, "..."),
+                                    };
+                                }
+                            });
+
+                            row.col(|ui| {
+                                if let Some(ts) = entry.modified {
+                                    let text = if shown.relative_time {
+                                        format_relative(ts, now)
+                                    } else {
+                                        format_modified(ts, now)
+                                    };
+                                    ui.label(text).on_hover_text(format_exact(ts));
+                                }
+                            });
+                        });
+                    });
+            });
+        let before = self.settings;
+        self.settings.columns = columns;
+        if let Some(column) = sort_by {
+            self.settings.sort.toggle(column);
+            self.settings.sort.sort(&mut self.entries);
+            self.view_dirty = true;
+            self.refresh_view();
+        }
+        if self.settings != before {
+            let after = self.settings;
+            crate::config::sftp::update_settings(|settings| {
+                if after.columns != before.columns {
+                    settings.columns = after.columns;
+                }
+                if after.sort != before.sort {
+                    settings.sort = after.sort;
+                }
+            });
+        }
+
+        // Drag & drop overlay
+        let hovering = ui.ctx().input(|i| !i.raw.hovered_files.is_empty());
+        if hovering {
+            let rect = ui.max_rect();
+            ui.painter().rect_filled(
+                rect,
+                0.0,
+                egui::Color32::from_rgba_premultiplied(0, 30, 0, 80),
+            );
+            ui.painter().rect_stroke(
+                rect.shrink(4.0),
+                0.0,
+                egui::Stroke::new(1.0, crate::theme::GREEN),
+            );
+            ui.painter().text(
+                rect.center(),
+                egui::Align2::CENTER_CENTER,
+                "[ DROP FILES OR FOLDERS TO UPLOAD ]",
+                egui::FontId::monospace(16.0),
+                crate::theme::GREEN_BRIGHT,
+            );
+        }
+
+        // Deferred actions
+        if cursor_click.is_some() {
+            self.cursor = cursor_click;
+        }
+        if let Some(path) = navigate_path.or(self.navigate_to.take()) {
+            self.navigate(&path);
+        }
+        if let Some(path) = drag_started {
+            // Тащим выделение, если файл в нём, иначе только его
+            let paths = if self.selected.contains(&path) {
+                self.selected.clone()
+            } else {
+                HashSet::from([path])
+            };
+            self.drag_download = self
+                .entries
+                .iter()
+                .filter(|e| !e.is_dir && paths.contains(&e.path))
+                .map(|e| (e.path.clone(), e.name.clone(), e.size))
+                .collect();
+        }
+        self.handle_drag_download(ui.ctx());
+        if let Some(entry) = archive_of {
+            self.download_archive(&entry);
+        }
+        if let Some(entry) = open_with {
+            self.open_with(&entry);
+        }
+        if let Some(entry) = get_to {
+            if let Some(dir) = self.pick_download_dir() {
+                // Из выделения — всё выделенное, иначе только этот файл
+                if self.selected.contains(&entry.path) {
+                    self.download_selected_to(&dir);
+                } else {
+                    let local = dir.join(&entry.name);
+                    self.download(&entry.path, &local.to_string_lossy(), entry.size);
+                }
+            }
+        }
+        if let Some((path, peer)) = send_to {
+            self.send_to(path, peer);
+        }
+        if let Some(dir) = paste_into {
+            match PasteFile::from_clipboard(dir) {
+                Ok(paste) => self.paste_file = Some(paste),
+                Err(e) => self.error = Some(format!("paste to file: {}", e)),
+            }
+        }
+        if let Some(path) = size_of {
+            self.calculate_size(path);
+        }
+        if let Some(dir) = shell_dir {
+            self.shell_command = Some(format!("cd {}\r", shell_quote(&dir)));
+        }
+        if let Some((path, cut)) = clip_entry {
+            // Из выделения — всё выделенное, иначе только этот файл
+            let paths = if self.selected.contains(&path) {
+                self.selected.iter().cloned().collect()
+            } else {
+                vec![path]
+            };
+            self.clipboard = Some(RemoteClipboard { paths, cut });
+        }
+        if let Some(path) = touch_path {
+            self.send(SftpRequest::Touch(path));
+        }
+        if let Some(entry) = delete_entry {
+            self.confirm_delete(&entry);
+        }
+        if let Some(entry) = properties_for {
+            self.properties_dialog = Some(PropertiesDialog::new(&entry, &self.accounts));
+        }
+        match rename_done {
+            Some(true) => self.finish_rename(),
+            Some(false) => self.rename = None,
+            None => {}
+        }
+        if let Some(entry) = rename_of {
+            self.cursor = Some(entry.path.clone());
+            self.rename = Some(InlineRename::new(&entry));
+        }
+        if let Some(entry) = symlink_to {
+            self.show_symlink_dialog = true;
+            self.symlink_target = entry.path;
+            self.symlink_name = format!("{}.link", entry.name);
+        }
+        for (path, selected) in toggle_selection {
+            self.summary = None;
+            if selected {
+                if let Some(entry) = self.entries.iter().find(|e| e.path == path).cloned() {
+                    self.show_preview_of(&entry);
+                }
+                self.selected.insert(path);
+            } else {
+                if self.preview.as_ref().is_some_and(|p| p.path == path) {
+                    self.preview = None;
+                }
+                self.selected.remove(&path);
+            }
+        }
+        for (remote, local, size) in download_single {
+            self.download(&remote, &local, size);
+        }
+
+        // Symlink dialog: ссылка создаётся в текущей папке
+        if self.show_symlink_dialog {
+            egui::Window::new("symlink")
+                .collapsible(false)
+                .resizable(false)
+                .show(ui.ctx(), |ui| {
+                    egui::Grid::new("symlink_grid").num_columns(2).show(ui, |ui| {
+                        ui.label("target:");
+                        ui.text_edit_singleline(&mut self.symlink_target);
+                        ui.end_row();
+                        ui.label("link name:");
+                        ui.text_edit_singleline(&mut self.symlink_name);
+                        ui.end_row();
+                    });
+                    ui.horizontal(|ui| {
+                        let ready =
+                            !self.symlink_name.is_empty() && !self.symlink_target.is_empty();
+                        if ui.add_enabled(ready, egui::Button::new("[create]")).clicked() {
+                            let link = format!(
+                                "{}/{}",
+                                current_path.trim_end_matches('/'),
+                                self.symlink_name
+                            );
+                            self.symlink(&link, &self.symlink_target);
+                            self.show_symlink_dialog = false;
+                        }
+                        if ui.button("[cancel]").clicked() {
+                            self.show_symlink_dialog = false;
+                        }
+                    });
+                });
+        }
+
+        // Mkdir / new file dialog
+        if self.show_mkdir_dialog {
+            let title = if self.mkdir_is_file { "new file" } else { "mkdir" };
+            egui::Window::new(title)
+                .collapsible(false)
+                .resizable(false)
+                .show(ui.ctx(), |ui| {
+                    ui.horizontal(|ui| {
+                        ui.label("name:");
+                        ui.text_edit_singleline(&mut self.mkdir_name);
+                    });
+                    ui.horizontal(|ui| {
+                        if ui.button("[create]").clicked() && !self.mkdir_name.is_empty() {
+                            let full_path = format!(
+                                "{}/{}",
+                                current_path.trim_end_matches('/'),
+                                self.mkdir_name
+                            );
+                            if self.mkdir_is_file {
+                                self.send(SftpRequest::NewFile(full_path));
+                            } else {
+                                self.mkdir(&full_path);
+                            }
+                            self.show_mkdir_dialog = false;
+                        }
+                        if ui.button("[cancel]").clicked() {
+                            self.show_mkdir_dialog = false;
+                        }
+                    });
+                });
+        }
+
+        self.show_paste_file_dialog(ui.ctx());
+        self.show_sudo_prompt(ui.ctx());
+        self.show_history_window(ui.ctx());
+        self.show_properties_dialog(ui.ctx());
+        self.show_search_dialog(ui.ctx());
+        self.show_sync_dialog(ui.ctx());
+
+        self.show_conflict_dialog(ui.ctx());
+        self.show_low_space_dialog(ui.ctx());
+        self.show_delete_dialog(ui.ctx());
+    }
+
+    /// Размер папки обходом дерева в фоне; результат — в колонке SIZE и в свойствах.
+    fn calculate_size(&mut self, path: String) {
+        self.request_seq += 1;
+        self.size_requests.insert(self.request_seq, path.clone());
+        self.dir_sizes.insert(path.clone(), None);
+        self.summary = None;
+        let _ = self.request_tx.send(SftpRequest::TreeStats {
+            id: self.request_seq,
+            path,
+        });
+    }
+
+    /// Журнал передач всех сессий, новые сверху.
+    fn show_history_window(&mut self, ctx: &egui::Context) {
+        let Some(history) = &self.history else {
+            return;
+        };
+        let mut open = true;
+        let mut clear = false;
+        egui::Window::new("transfer history")
+            .open(&mut open)
+            .default_size([720.0, 360.0])
+            .show(ctx, |ui| {
+                ui.horizontal(|ui| {
+                    ui.colored_label(crate::theme::GREY, format!("{} transfers", history.len()));
+                    if !history.is_empty() && ui.button("[clear]").clicked() {
+                        clear = true;
+                    }
+                });
+                ui.separator();
+                egui::ScrollArea::vertical().show(ui, |ui| {
+                    egui::Grid::new("sftp_history_grid")
+                        .striped(true)
+                        .show(ui, |ui| {
+                            for record in history.iter().rev() {
+                                // Время передачи — событие на этой машине, в её поясе
+                                ui.label(format_local(record.time, "%Y-%m-%d %H:%M"));
+                                ui.label(&record.direction);
+                                ui.label(&record.session);
+                                ui.label(format!("{} -> {}", record.source, record.dest));
+                                ui.label(format_size(record.bytes));
+                                ui.label(format!("{:.1}s", record.duration_ms as f64 / 1000.0));
+                                let color = match record.result.as_str() {
+                                    "ok" => crate::theme::GREEN,
+                                    "skipped" => crate::theme::GREY,
+                                    _ => crate::theme::RED,
+                                };
+                                ui.colored_label(color, &record.result);
+                                ui.end_row();
+                            }
+                        });
+                });
+            });
+        if clear {
+            crate::config::sftp::clear_history();
+            self.history = Some(Vec::new());
+        }
+        if !open {
+            self.history = None;
+        }
+    }
+
+    /// Клавиатура таблицы, пока не занята полем ввода или диалогом:
+    /// ↑/↓, PgUp/PgDn, Home/End — курсор; Enter — открыть папку или скачать файл;
+    /// Backspace — наверх; Delete — удалить; F2 — переименовать; Space — выделить;
+    /// буквы — переход к первому имени, начинающемуся с набранного.
+    /// Возвращает строку, до которой надо прокрутить таблицу.
+    fn handle_table_keys(&mut self, ctx: &egui::Context) -> Option<usize> {
+        let busy = ctx.memory(|m| m.focused().is_some())
+            || self.show_mkdir_dialog
+            || self.show_symlink_dialog
+            || self.rename.is_some()
+            || self.paste_file.is_some()
+            || self.sudo_prompt.is_some()
+            || self.delete_confirm.is_some()
+            || self.properties_dialog.is_some()
+            || self.search.is_some()
+            || self.sync.is_some()
+            || !self.conflicts.is_empty()
+            || !self.low_space.is_empty();
+        if busy || self.view.is_empty() {
+            return None;
+        }
+        let last = self.view.len() - 1;
+        let start = self.cursor_row();
+        let mut index = start;
+        let step = |index: Option<usize>, delta: isize| {
+            Some(index.map_or(0, |i| i.saturating_add_signed(delta).min(last)))
+        };
+        let events = ctx.input(|i| i.events.clone());
+        for event in events {
+            match event {
+                egui::Event::Key {
+                    key,
+                    pressed: true,
+                    modifiers,
+                    ..
+                } if !modifiers.ctrl && !modifiers.alt => {
+                    let current = index.map(|i| self.entries[self.view[i]].clone());
+                    match key {
+                        egui::Key::ArrowDown => index = step(index, 1),
+                        egui::Key::ArrowUp => index = step(index, -1),
+                        egui::Key::PageDown => index = step(index, 20),
+                        egui::Key::PageUp => index = step(index, -20),
+                        egui::Key::Home => index = Some(0),
+                        egui::Key::End => index = Some(last),
+                        egui::Key::Enter => match &current {
+                            Some(entry) if entry.is_dir => {
+                                self.navigate_to = Some(entry.path.clone());
+                            }
+                            Some(entry) => {
+                                if let Some(dir) = self.download_dir() {
+                                    let local = dir.join(&entry.name);
+                                    self.download(
+                                        &entry.path,
+                                        &local.to_string_lossy(),
+                                        entry.size,
+                                    );
+                                }
+                            }
+                            None => {}
+                        },
+                        egui::Key::Backspace => {
+                            // Курсор — на папке, из которой вышли
+                            self.cursor = Some(self.current_path.clone());
+                            self.navigate_to = Some(remote_parent(&self.current_path));
+                            return None;
+                        }
+                        egui::Key::Delete => {
+                            if let Some(entry) = &current {
+                                self.confirm_delete(entry);
+                            }
+                        }
+                        egui::Key::F2 => {
+                            if let Some(entry) = current {
+                                self.rename = Some(InlineRename::new(&entry));
+                            }
+                        }
+                        egui::Key::Space if self.type_ahead.is_empty() => {
+                            if let Some(entry) = current {
+                                if !self.selected.remove(&entry.path) {
+                                    self.selected.insert(entry.path.clone());
+                                }
+                                self.summary = None;
+                            }
+                        }
+                        egui::Key::Escape => self.type_ahead.clear(),
+                        _ => {}
+                    }
+                }
+                egui::Event::Text(text) => {
+                    if self.type_ahead_at.elapsed() > Duration::from_secs(1) {
+                        self.type_ahead.clear();
+                    }
+                    // Пробел в начале — это Space (выделение), а не часть имени
+                    if self.type_ahead.is_empty() && text.trim().is_empty() {
+                        continue;
+                    }
+                    self.type_ahead.push_str(&text.to_lowercase());
+                    self.type_ahead_at = Instant::now();
+                    let prefix = &self.type_ahead;
+                    if let Some(i) = self
+                        .view
+                        .iter()
+                        .position(|&i| self.entries[i].name.to_lowercase().starts_with(prefix))
+                    {
+                        index = Some(i);
+                    }
+                }
+                _ => {}
+            }
+        }
+        if index == start {
+            return None;
+        }
+        let i = index?;
+        self.cursor = Some(self.entries[self.view[i]].path.clone());
+        Some(i)
+    }
+
+    /// [watch]: раз в WATCH_INTERVAL спросить mtime папки, раз в
+    /// WATCH_FULL_RELOAD — перечитать её в любом случае.
+    fn watch_directory(&mut self, ctx: &egui::Context) {
+        if !self.settings.watch || self.loading || self.listing_more {
+            return;
+        }
+        ctx.request_repaint_after(WATCH_INTERVAL);
+        if self.watch_checked.elapsed() < WATCH_INTERVAL {
+            return;
+        }
+        self.watch_checked = Instant::now();
+        if self.listed_at.elapsed() >= WATCH_FULL_RELOAD {
+            self.reload_quietly();
+        } else {
+            let _ = self
+                .request_tx
+                .send(SftpRequest::StatDir(self.current_path.clone()));
+        }
+    }
+
+    /// Перечитать текущую папку без спиннера; выделение и фильтр остаются.
+    fn reload_quietly(&mut self) {
+        self.listed_at = Instant::now();
+        let _ = self
+            .request_tx
+            .send(SftpRequest::ListDir(self.current_path.clone()));
+    }
+
+    /// Строка курсора в текущем представлении
+    fn cursor_row(&self) -> Option<usize> {
+        let cursor = self.cursor.as_ref()?;
+        self.view.iter().position(|&i| &self.entries[i].path == cursor)
+    }
+
+    /// Индексы записей, прошедших фильтр, — пересчитываются только когда
+    /// меняется листинг, сортировка или фильтр, а не каждый кадр.
+    fn refresh_view(&mut self) {
+        if !self.view_dirty && self.view_filter == self.filter {
+            return;
+        }
+        self.view = (0..self.entries.len())
+            .filter(|&i| name_matches(&self.filter, &self.entries[i].name))
+            .collect();
+        self.view_filter.clone_from(&self.filter);
+        self.view_dirty = false;
+        self.summary = None;
+    }
+
+    /// Страница листинга. Первая заменяет таблицу, следующие дописываются;
+    /// сортировка целиком и кэш подпапок — когда пришла последняя.
+    fn apply_dir_page(
+        &mut self,
+        path: String,
+        mut entries: Vec<SftpEntry>,
+        first: bool,
+        last: bool,
+    ) {
+        if first {
+            self.settings.sort.sort(&mut entries);
+            if path != self.current_path {
+                crate::config::sftp::save_last_path(&self.session_id, &path);
+                self.dir_mtime = None;
+            }
+            self.record_history(&path);
+            let _ = self.request_tx.send(SftpRequest::DiskUsage(path.clone()));
+            self.current_path = path;
+            self.entries = entries;
+            self.loading = false;
+        } else if path == self.current_path {
+            self.entries.append(&mut entries);
+        } else {
+            return;
+        }
+        if last {
+            if !first {
+                self.settings.sort.sort(&mut self.entries);
+            }
+            self.cache_subdirs();
+            self.listed_at = Instant::now();
+        }
+        self.listing_more = !last;
+        // Сразу, а не на следующем кадре: view индексирует entries
+        self.view_dirty = true;
+        self.refresh_view();
+    }
+
+    /// Enter в поле переименования: неподходящее имя оставляет поле открытым с ошибкой.
+    fn finish_rename(&mut self) {
+        let Some(edit) = &mut self.rename else {
+            return;
+        };
+        let name = edit.name.trim();
+        let to = remote_join(&remote_parent(&edit.path), name);
+        edit.error = if name.is_empty() {
+            Some("empty name".to_string())
+        } else if name.contains('/') {
+            Some("name can't contain '/'".to_string())
+        } else if name == "." || name == ".." {
+            Some(format!("'{}' is reserved", name))
+        } else if to != edit.path && self.entries.iter().any(|e| e.path == to) {
+            Some(format!("'{}' already exists", name))
+        } else {
+            None
+        };
+        if edit.error.is_some() {
+            edit.focused = false;
+            return;
+        }
+        let Some(edit) = self.rename.take() else {
+            return;
+        };
+        if to != edit.path {
+            self.rename(&edit.path, &to);
+            self.cursor = Some(to);
+        }
+    }
+
+    /// Имя для вставки из буфера. Содержимое пишется во временный файл и
+    /// уходит обычной загрузкой — с очередью, конфликтами и режимом SCP.
+    fn show_paste_file_dialog(&mut self, ctx: &egui::Context) {
+        let Some(paste) = &mut self.paste_file else {
+            return;
+        };
+        let mut submit = false;
+        let mut close = false;
+        egui::Window::new("paste to file")
+            .collapsible(false)
+            .resizable(false)
+            .show(ctx, |ui| {
+                let size = format_size(paste.data.len() as u64);
+                ui.colored_label(
+                    crate::theme::GREY,
+                    format!("{}, {} -> {}", paste.kind, size, paste.dir),
+                );
+                ui.horizontal(|ui| {
+                    ui.label("name:");
+                    let response = ui.text_edit_singleline(&mut paste.name);
+                    if response.lost_focus() && ui.input(|i| i.key_pressed(egui::Key::Enter)) {
+                        submit = true;
+                    }
+                });
+                ui.horizontal(|ui| {
+                    if ui.button("[save]").clicked() {
+                        submit = true;
+                    }
+                    if ui.button("[cancel]").clicked() {
+                        close = true;
+                    }
+                });
+            });
+        let name = paste.name.trim();
+        let valid = !name.is_empty() && !name.contains('/') && name != "." && name != "..";
+        if close || (submit && valid) {
+            let Some(paste) = self.paste_file.take() else {
+                return;
+            };
+            if !submit {
+                return;
+            }
+            let name = paste.name.trim();
+            let local = crate::config::sftp::paste_cache_path(name);
+            let written = local
+                .parent()
+                .map_or(Ok(()), std::fs::create_dir_all)
+                .and_then(|_| std::fs::write(&local, &paste.data));
+            match written {
+                Ok(()) => self.upload(&local.to_string_lossy(), &remote_join(&paste.dir, name)),
+                Err(e) => self.error = Some(format!("paste to file: {}", e)),
+            }
+        }
+    }
+
+    /// Пароль sudo. [ok] — ждущие запросы уходят снова; [cancel] — они отменяются,
+    /// передачи в очереди помечаются ошибкой.
+    fn show_sudo_prompt(&mut self, ctx: &egui::Context) {
+        let Some((password, wrong)) = &mut self.sudo_prompt else {
+            return;
+        };
+        let mut submit = false;
+        let mut close = false;
+        egui::Window::new("sudo")
+            .collapsible(false)
+            .resizable(false)
+            .show(ctx, |ui| {
+                if *wrong {
+                    ui.colored_label(crate::theme::RED, "wrong password, try again");
+                }
+                ui.colored_label(
+                    crate::theme::GREY,
+                    format!("{} operation(s) waiting", self.sudo_pending.len()),
+                );
+                ui.horizontal(|ui| {
+                    ui.label("password:");
+                    let response = ui.add(egui::TextEdit::singleline(password).password(true));
+                    response.request_focus();
+                    if response.lost_focus() && ui.input(|i| i.key_pressed(egui::Key::Enter)) {
+                        submit = true;
+                    }
+                });
+                ui.horizontal(|ui| {
+                    if ui.button("[ok]").clicked() {
+                        submit = true;
+                    }
+                    if ui.button("[cancel]").clicked() {
+                        close = true;
+                    }
+                });
+            });
+        if !submit && !close {
+            return;
+        }
+        let Some((password, _)) = self.sudo_prompt.take() else {
+            return;
+        };
+        let pending = std::mem::take(&mut self.sudo_pending);
+        if submit {
+            self.sudo_password = Some(password.clone());
+            for req in pending {
+                let _ = self.request_tx.send(SftpRequest::Sudo {
+                    req: Box::new(req),
+                    password: Some(password.clone()),
+                });
+            }
+            return;
+        }
+        for req in pending {
+            if let Some(progress) = req.progress() {
+                *progress.error.lock() = Some("sudo: cancelled".to_string());
+                progress.failed.store(true, Ordering::Relaxed);
+            }
+        }
+    }
+
+    /// [open with...]: скачать в кэш (без вопроса о конфликте — кэш наш)
+    /// и открыть выбранной программой, когда скачается.
+    fn open_with(&mut self, entry: &SftpEntry) {
+        let Some(app) = rfd::FileDialog::new()
+            .set_title("Open with application")
+            .pick_file()
+        else {
+            return;
+        };
+        let local = crate::config::sftp::open_cache_path(&self.session_id, &entry.path);
+        let download = TransferState::new(&entry.name, entry.size, false);
+        self.enqueue(
+            TransferJob::Download {
+                remote: entry.path.clone(),
+                local: local.to_string_lossy().to_string(),
+            },
+            Arc::clone(&download),
+        );
+        if let Some(t) = self.transfers.last_mut() {
+            t.resume = Some(false);
+        }
+        self.opened.retain(|f| f.remote != entry.path);
+        self.opened.push(OpenedFile {
+            remote: entry.path.clone(),
+            local,
+            app,
+            download,
+            baseline: None,
+            changed: false,
+        });
+    }
+
+    /// Раз в секунду: запустить программу для скачанных копий и заметить
+    /// сохранённые (mtime копии ушёл от запомненного).
+    fn watch_opened_files(&mut self, ctx: &egui::Context) {
+        if self.opened.is_empty() {
+            return;
+        }
+        ctx.request_repaint_after(Duration::from_secs(1));
+        if self.opened_checked.elapsed() < Duration::from_secs(1) {
+            return;
+        }
+        self.opened_checked = Instant::now();
+        let modified = |path: &Path| std::fs::metadata(path).and_then(|m| m.modified()).ok();
+        // Скачивание не удалось или отменено (не пауза) — следить не за чем
+        self.opened.retain(|f| {
+            !f.download.failed.load(Ordering::Relaxed) || f.download.pause.load(Ordering::Relaxed)
+        });
+        for file in &mut self.opened {
+            match file.baseline {
+                None if file.download.done.load(Ordering::Relaxed) => {
+                    file.baseline = modified(&file.local);
+                    if let Err(e) = launch_with(&file.app, &file.local) {
+                        self.error = Some(format!("open {}: {}", file.app.display(), e));
+                    }
+                }
+                Some(baseline) if modified(&file.local).is_some_and(|m| m != baseline) => {
+                    file.changed = true;
+                }
+                _ => {}
+            }
+        }
+    }
+
+    /// Строки открытых файлов над очередью: изменённые — с [upload].
+    fn show_opened_files(&mut self, ui: &mut egui::Ui) {
+        self.watch_opened_files(ui.ctx());
+        let mut upload: Option<usize> = None;
+        let mut ignore: Option<usize> = None;
+        let mut forget: Option<usize> = None;
+        for (i, file) in self.opened.iter().enumerate() {
+            ui.horizontal(|ui| {
+                let name = local_file_name(&file.local);
+                if file.changed {
+                    ui.colored_label(crate::theme::AMBER, format!("{} changed locally", name))
+                        .on_hover_text(&file.remote);
+                    if ui.button("[upload]").clicked() {
+                        upload = Some(i);
+                    }
+                    if ui.button("[ignore]").clicked() {
+                        ignore = Some(i);
+                    }
+                } else {
+                    let state = if file.baseline.is_some() { "open in" } else { "fetching for" };
+                    ui.colored_label(
+                        crate::theme::GREY,
+                        format!("{} {} {}", name, state, local_file_name(&file.app)),
+                    )
+                    .on_hover_text(&file.remote);
+                }
+                if ui.small_button("[x]").on_hover_text("stop watching").clicked() {
+                    forget = Some(i);
+                }
+            });
+        }
+        if let Some(i) = upload {
+            let file = &self.opened[i];
+            let job = TransferJob::Upload {
+                local: file.local.to_string_lossy().to_string(),
+                remote: file.remote.clone(),
+            };
+            let size = std::fs::metadata(&file.local).map(|m| m.len()).unwrap_or(0);
+            let progress = TransferState::new(&local_file_name(&file.local), size, true);
+            self.enqueue(job, progress);
+            if let Some(t) = self.transfers.last_mut() {
+                t.resume = Some(false);
+            }
+        }
+        if let Some(i) = upload.or(ignore) {
+            let file = &mut self.opened[i];
+            file.baseline = std::fs::metadata(&file.local).and_then(|m| m.modified()).ok();
+            file.changed = false;
+        }
+        if let Some(i) = forget {
+            let file = self.opened.remove(i);
+            if let Some(dir) = file.local.parent() {
+                std::fs::remove_dir_all(dir).ok();
+            }
+        }
+    }
+
+    /// Папка одним tar.gz — быстрее поштучного SFTP, когда мелких файлов тысячи.
+    fn download_archive(&mut self, entry: &SftpEntry) {
+        let file_name = format!("{}.tar.gz", entry.name);
+        let mut dialog = rfd::FileDialog::new()
+            .set_title("Save archive")
+            .set_file_name(&file_name);
+        if let Some(dir) = self.download_dir() {
+            dialog = dialog.set_directory(dir);
+        }
+        let Some(local) = dialog.save_file() else {
+            return;
+        };
+        self.enqueue(
+            TransferJob::DownloadArchive {
+                remote: entry.path.clone(),
+                local: local.to_string_lossy().to_string(),
+            },
+            TransferState::new(&file_name, 0, false),
+        );
+    }
+
+    /// Скачивание перетаскиванием. Это не системный drag-and-drop: winit не умеет
+    /// его начинать, и куда отпустили файлы, неизвестно. Отпустили за пределами
+    /// окна — открываем выбор папки (по умолчанию рабочий стол) и скачиваем туда.
+    fn handle_drag_download(&mut self, ctx: &egui::Context) {
+        if self.drag_download.is_empty() {
+            return;
+        }
+        let (released, pos) = ctx.input(|i| (i.pointer.any_released(), i.pointer.latest_pos()));
+        if !released {
+            let text = match self.drag_download.as_slice() {
+                [(_, name, _)] => format!("get {} - release outside to pick a folder", name),
+                files => format!("get {} files - release outside to pick a folder", files.len()),
+            };
+            let layer = egui::LayerId::background();
+            egui::show_tooltip(ctx, layer, egui::Id::new("sftp_drag_download"), |ui| {
+                ui.colored_label(crate::theme::GREEN, text);
+            });
+            return;
+        }
+        let files = std::mem::take(&mut self.drag_download);
+        // Указатель ушёл за окно (pos None — курсор покинул окно)
+        let outside = pos.is_none_or(|p| !ctx.screen_rect().contains(p));
+        if !outside {
+            return;
+        }
+        let mut dialog = rfd::FileDialog::new().set_title("Download to");
+        if let Some(dir) = dirs::desktop_dir() {
+            dialog = dialog.set_directory(dir);
+        }
+        if let Some(dir) = dialog.pick_folder() {
+            for (path, name, size) in files {
+                let local = dir.join(&name);
+                self.download(&path, &local.to_string_lossy(), size);
+            }
+        }
+    }
+
+    /// Нижняя строка: что выделено (без выделения — что в папке)
+    /// и свободное место на файловой системе текущей папки.
+    fn show_footer(&mut self, ui: &mut egui::Ui) {
+        if self.summary.is_none() {
+            self.summary = Some(self.selection_summary());
+        }
+        egui::TopBottomPanel::bottom("sftp_footer")
+            .frame(egui::Frame::none())
+            .show_inside(ui, |ui| {
+                ui.horizontal(|ui| {
+                    let summary = self.summary.clone().unwrap_or_default();
+                    let color = if self.selected.is_empty() {
+                        crate::theme::GREY
+                    } else {
+                        crate::theme::GREEN
+                    };
+                    ui.colored_label(color, summary);
+
+                    let Some(usage) = self.disk_usage else {
+                        return;
+                    };
+                    let used = usage.total.saturating_sub(usage.avail);
+                    let percent = (used * 100).checked_div(usage.total).unwrap_or(0);
+                    let color = if percent >= 95 {
+                        crate::theme::RED
+                    } else if percent >= 90 {
+                        crate::theme::AMBER
+                    } else {
+                        crate::theme::GREY
+                    };
+                    ui.with_layout(egui::Layout::right_to_left(egui::Align::Center), |ui| {
+                        ui.colored_label(
+                            color,
+                            format!(
+                                "free {} of {} ({}% used)",
+                                format_size(usage.avail),
+                                format_size(usage.total),
+                                percent
+                            ),
+                        );
+                    });
+                });
+            });
+    }
+
+    /// «N items selected, X total». Папки считаются, если их размер уже подсчитан
+    /// ([calculate size]), — тогда без «+». Без выделения — видимое содержимое папки.
+    fn selection_summary(&self) -> String {
+        if self.selected.is_empty() {
+            let (mut dirs, mut files, mut bytes) = (0, 0, 0);
+            for entry in self.view.iter().filter_map(|&i| self.entries.get(i)) {
+                if entry.is_dir {
+                    dirs += 1;
+                } else {
+                    files += 1;
+                    bytes += entry.size;
+                }
+            }
+            return format!(
+                "{} items ({} dirs, {} files), {} in files",
+                dirs + files,
+                dirs,
+                files,
+                format_size(bytes)
+            );
+        }
+        let mut bytes = 0;
+        let mut uncounted = false;
+        for entry in self.entries.iter().filter(|e| self.selected.contains(&e.path)) {
+            if !entry.is_dir {
+                bytes += entry.size;
+            } else if let Some(Some(stats)) = self.dir_sizes.get(&entry.path) {
+                bytes += stats.bytes;
+            } else {
+                uncounted = true;
+            }
+        }
+        format!(
+            "{} items selected, {}{} total",
+            self.selected.len(),
+            format_size(bytes),
+            if uncounted { "+" } else { "" }
+        )
+    }
+
+    /// Эта сессия как цель [send to] для других; None — ещё не подключена или без SFTP.
+    pub fn peer(&self, name: &str) -> Option<SftpPeer> {
+        if self.scp_only {
+            return None;
+        }
+        let session = self.connection.lock().clone()?;
+        Some(SftpPeer {
+            name: name.to_string(),
+            login: self.scp_login.clone(),
+            port: self.port,
+            dir: self.current_path.clone(),
+            session,
+        })
+    }
+
+    /// Без SFTP на этой стороне отправлять нечем — меню [send to] не показываем.
+    pub fn set_peers(&mut self, peers: Vec<SftpPeer>) {
+        self.peers = if self.scp_only { Vec::new() } else { peers };
+    }
+
+    /// [send to]: выделенное (или один файл) — в текущую папку другой сессии.
+    fn send_to(&mut self, path: String, peer: SftpPeer) {
+        let sources: Vec<String> = if self.selected.contains(&path) {
+            self.selected.iter().cloned().collect()
+        } else {
+            vec![path]
+        };
+        let name = match sources.as_slice() {
+            [path] => format!("{} -> {}", path.rsplit('/').next().unwrap_or(path), peer.name),
+            paths => format!("{} items -> {}", paths.len(), peer.name),
+        };
+        self.enqueue(
+            TransferJob::SendTo { sources, peer },
+            TransferState::new_dir(&name, false),
+        );
+    }
+
+    /// Команда `cd` для Shell-вкладки после [open shell here] (забирается один раз).
+    pub fn take_shell_command(&mut self) -> Option<String> {
+        self.shell_command.take()
+    }
+
+    /// Вставка в текущую папку: вырезанное переносится rename'ом,
+    /// скопированное — задачей в очереди передач.
+    fn paste(&mut self) {
+        let Some(clipboard) = self.clipboard.take() else {
+            return;
+        };
+        let dest = self.current_path.clone();
+        if clipboard.cut {
+            for path in &clipboard.paths {
+                if remote_parent(path) == dest {
+                    continue;
+                }
+                let name = path.rsplit('/').next().unwrap_or(path);
+                self.rename(path, &remote_join(&dest, name));
+            }
+            return;
+        }
+        let name = match clipboard.paths.as_slice() {
+            [path] => format!("cp {}", path.rsplit('/').next().unwrap_or(path)),
+            paths => format!("cp {} items", paths.len()),
+        };
+        self.enqueue(
+            TransferJob::RemoteCopy {
+                sources: clipboard.paths.clone(),
+                dest,
+            },
+            TransferState::new_dir(&name, false),
+        );
+        // Скопированное можно вставить ещё раз
+        self.clipboard = Some(clipboard);
+    }
+
+    /// Открывает подтверждение; для папки запрашивает подсчёт содержимого.
+    fn confirm_delete(&mut self, entry: &SftpEntry) {
+        let recursive = entry.is_dir && entry.link_target.is_none();
+        self.request_seq += 1;
+        let stats = if recursive {
+            let _ = self.request_tx.send(SftpRequest::TreeStats {
+                id: self.request_seq,
+                path: entry.path.clone(),
+            });
+            None
+        } else {
+            Some(Ok(TreeStats {
+                files: 1,
+                dirs: 0,
+                bytes: entry.size,
+            }))
+        };
+        self.delete_confirm = Some(DeleteConfirm {
+            path: entry.path.clone(),
+            recursive,
+            id: self.request_seq,
+            stats,
+        });
+    }
+
+    fn show_delete_dialog(&mut self, ctx: &egui::Context) {
+        let Some(confirm) = &self.delete_confirm else {
+            return;
+        };
+        let mut delete = false;
+        let mut cancel = false;
+        egui::Window::new("delete")
+            .collapsible(false)
+            .resizable(false)
+            .show(ctx, |ui| {
+                let command = if confirm.recursive { "rm -r" } else { "rm" };
+                ui.monospace(format!("{} {}", command, confirm.path));
+                match &confirm.stats {
+                    None => {
+                        ui.horizontal(|ui| {
+                            ui.spinner();
+                            ui.colored_label(crate::theme::GREY, "counting...");
+                        });
+                    }
+                    Some(Ok(stats)) => {
+                        ui.colored_label(
+                            crate::theme::RED,
+                            format!(
+                                "{} files, {} dirs, {} will be destroyed",
+                                stats.files,
+                                stats.dirs,
+                                format_size(stats.bytes)
+                            ),
+                        );
+                    }
+                    Some(Err(e)) => {
+                        ui.colored_label(crate::theme::AMBER, format!("can't count: {}", e));
+                    }
+                }
+                ui.horizontal(|ui| {
+                    if ui
+                        .add_enabled(confirm.stats.is_some(), egui::Button::new("[delete]"))
+                        .clicked()
+                    {
+                        delete = true;
+                    }
+                    if ui.button("[cancel]").clicked() {
+                        cancel = true;
+                    }
+                });
+            });
+        if delete {
+            if let Some(confirm) = self.delete_confirm.take() {
+                if confirm.recursive {
+                    self.send(SftpRequest::RemoveAll(confirm.path.clone()));
+                } else {
+                    self.remove(&confirm.path);
+                }
+                if let Some(search) = &mut self.search {
+                    search.results.retain(|e| e.path != confirm.path);
+                }
+            }
+        }
+        if cancel {
+            self.delete_confirm = None;
+        }
+    }
+
+    /// Конфликты — по одному за раз.
+    fn show_conflict_dialog(&mut self, ctx: &egui::Context) {
+        let Some(conflict) = self.conflicts.first_mut() else {
+            return;
+        };
+        let apply_all = &mut self.conflict_apply_all;
+        let mut answer = None;
+        egui::Window::new("file exists")
+            .collapsible(false)
+            .resizable(false)
+            .show(ctx, |ui| {
+                let (direction, target) = if conflict.is_upload {
+                    ("PUT", &conflict.remote)
+                } else {
+                    ("GET", &conflict.local)
+                };
+                ui.monospace(format!("{} {}", direction, conflict.progress.name));
+                let warning = match &conflict.batch {
+                    Some(batch) => format!("{} files in {} already exist", batch.files, target),
+                    None => format!("{} already exists", target),
+                };
+                ui.colored_label(crate::theme::AMBER, warning);
+                let stamp_row = |ui: &mut egui::Ui, title: &str, stamp: &FileStamp| {
+                    ui.label(title);
+                    ui.monospace(format_size(stamp.size));
+                    ui.monospace(stamp.modified.map(format_timestamp).unwrap_or_default());
+                    ui.end_row();
+                };
+                egui::Grid::new("conflict_grid")
+                    .num_columns(3)
+                    .spacing([12.0, 4.0])
+                    .show(ui, |ui| {
+                        stamp_row(ui, "source:", &conflict.source);
+                        // План синхронизации размеров приёмника не хранит
+                        if conflict.can_rename() {
+                            stamp_row(ui, "existing:", &conflict.existing);
+                        }
+                    });
+                let can_rename = conflict.can_rename();
+                if can_rename {
+                    ui.horizontal(|ui| {
+                        ui.label("rename to:");
+                        ui.add(
+                            egui::TextEdit::singleline(&mut conflict.rename)
+                                .font(egui::TextStyle::Monospace)
+                                .desired_width(220.0),
+                        );
+                    });
+                }
+                ui.horizontal(|ui| {
+                    if ui.button("[overwrite]").clicked() {
+                        answer = Some(ConflictAnswer::Overwrite);
+                    }
+                    if ui.button("[skip]").clicked() {
+                        answer = Some(ConflictAnswer::Skip);
+                    }
+                    if ui.add_enabled(can_rename, egui::Button::new("[rename]")).clicked() {
+                        answer = Some(ConflictAnswer::Rename);
+                    }
+                    let resume = egui::Button::new(format!(
+                        "[resume from {}]",
+                        format_size(conflict.offset)
+                    ));
+                    if ui.add_enabled(conflict.offset > 0, resume).clicked() {
+                        answer = Some(ConflictAnswer::Resume);
+                    }
+                });
+                ui.checkbox(apply_all, "apply to all")
+                    .on_hover_text("same answer for the rest of the queue");
+            });
+        if let Some(answer) = answer {
+            let conflict = self.conflicts.remove(0);
+            if std::mem::take(&mut self.conflict_apply_all) {
+                self.conflict_policy = Some(answer);
+            }
+            self.answer_conflict(conflict, answer);
+            // Уже пришедшие конфликты — тоже
+            if self.conflict_policy.is_some() {
+                for conflict in std::mem::take(&mut self.conflicts) {
+                    self.push_conflict(conflict);
+                }
+            }
+        }
+    }
+
+    /// Нехватка места — по одной заливке за раз.
+    fn show_low_space_dialog(&mut self, ctx: &egui::Context) {
+        let Some(low) = self.low_space.first() else {
+            return;
+        };
+        let mut answer = None;
+        egui::Window::new("not enough space")
+            .collapsible(false)
+            .resizable(false)
+            .show(ctx, |ui| {
+                ui.monospace(format!("PUT {}", low.progress.name));
+                ui.colored_label(
+                    crate::theme::AMBER,
+                    format!("{} may run out of space", low.shortage.dir),
+                );
+                egui::Grid::new("low_space_grid")
+                    .num_columns(2)
+                    .spacing([12.0, 4.0])
+                    .show(ui, |ui| {
+                        ui.label("upload:");
+                        ui.monospace(format_size(low.shortage.need));
+                        ui.end_row();
+                        ui.label("free:");
+                        ui.monospace(format_size(low.shortage.avail));
+                        ui.end_row();
+                    });
+                ui.horizontal(|ui| {
+                    if ui.button("[upload anyway]").clicked() {
+                        answer = Some(true);
+                    }
+                    if ui.button("[skip]").clicked() {
+                        answer = Some(false);
+                    }
+                });
+            });
+        let Some(upload) = answer else {
+            return;
+        };
+        let low = self.low_space.remove(0);
+        if upload {
+            low.progress.space_confirmed.store(true, Ordering::Relaxed);
+            self.send(low.req);
+        } else {
+            low.progress.skip();
+            self.status_message = Some(format!("skipped {}", low.progress.name));
+        }
+    }
+
+    fn show_properties_dialog(&mut self, ctx: &egui::Context) {
+        let Some(dialog) = &mut self.properties_dialog else {
+            return;
+        };
+        let accounts = &self.accounts;
+        let dir_sizes = &self.dir_sizes;
+        let mut open = true;
+        let mut close = false;
+        let mut apply = false;
+        let mut calculate = false;
+        egui::Window::new("properties")
+            .collapsible(false)
+            .resizable(false)
+            .open(&mut open)
+            .show(ctx, |ui| {
+                let kind = if dialog.is_dir { 'd' } else { '-' };
+                ui.monospace(format!("{}{} {}", kind, format_mode(dialog.mode), dialog.name));
+                if dialog.original_mode.is_none() && !dialog.mode_touched {
+                    ui.colored_label(crate::theme::GREY, "mode unknown: left as is");
+                }
+                if dialog.is_dir {
+                    ui.horizontal(|ui| {
+                        ui.label("size:");
+                        match dir_sizes.get(&dialog.path) {
+                            Some(Some(stats)) => {
+                                ui.label(format!(
+                                    "{} ({} files, {} dirs)",
+                                    format_size(stats.bytes),
+                                    stats.files,
+                                    stats.dirs
+                                ));
+                            }
+                            Some(None) => {
+                                ui.spinner();
+                            }
+                            None => {}
+                        }
+                        if !matches!(dir_sizes.get(&dialog.path), Some(None))
+                            && ui.small_button("[calculate]").clicked()
+                        {
+                            calculate = true;
+                        }
+                    });
+                }
+                ui.add_space(4.0);
+
+                let before = dialog.mode;
+                egui::Grid::new("chmod_grid")
+                    .num_columns(4)
+                    .spacing([12.0, 4.0])
+                    .show(ui, |ui| {
+                        ui.label("");
+                        for label in ["read", "write", "exec"] {
+                            ui.colored_label(crate::theme::GREY, label);
+                        }
+                        ui.end_row();
+                        for (who, shift) in [("owner", 6), ("group", 3), ("other", 0)] {
+                            ui.label(who);
+                            for bit in [4, 2, 1] {
+                                let mask = bit << shift;
+                                let mut set = dialog.mode & mask != 0;
+                                if ui.checkbox(&mut set, "").changed() {
+                                    dialog.mode ^= mask;
+                                }
+                            }
+                            ui.end_row();
+                        }
+                        ui.label("special");
+                        let specials = [("setuid", 0o4000), ("setgid", 0o2000), ("sticky", 0o1000)];
+                        for (label, mask) in specials {
+                            let mut set = dialog.mode & mask != 0;
+                            if ui.checkbox(&mut set, label).changed() {
+                                dialog.mode ^= mask;
+                            }
+                        }
+                        ui.end_row();
+                    });
+                if dialog.mode != before {
+                    dialog.octal = format!("{:04o}", dialog.mode);
+                    dialog.mode_touched = true;
+                }
+
+                ui.horizontal(|ui| {
+                    ui.label("octal:");
+                    let response = ui.add(
+                        egui::TextEdit::singleline(&mut dialog.octal)
+                            .font(egui::TextStyle::Monospace)
+                            .desired_width(48.0),
+                    );
+                    if response.changed() {
+                        if let Ok(mode) = u32::from_str_radix(dialog.octal.trim(), 8) {
+                            if mode <= 0o7777 {
+                                dialog.mode = mode;
+                                dialog.mode_touched = true;
+                            }
+                        }
+                    }
+                });
+
+                ui.add_space(4.0);
+                let mut ids_valid = true;
+                // Сервер мог не прислать uid/gid — тогда владельца можно не трогать
+                let ids_optional = dialog.original_ids.is_none();
+                egui::Grid::new("chown_grid")
+                    .num_columns(3)
+                    .spacing([12.0, 4.0])
+                    .show(ui, |ui| {
+                        let fields = [
+                            ("owner", &mut dialog.owner, &accounts.users),
+                            ("group", &mut dialog.group, &accounts.groups),
+                        ];
+                        for (label, text, ids) in fields {
+                            ui.label(label);
+                            ui.add(
+                                egui::TextEdit::singleline(text)
+                                    .font(egui::TextStyle::Monospace)
+                                    .desired_width(120.0),
+                            );
+                            match Accounts::find(ids, text) {
+                                None if ids_optional && text.trim().is_empty() => {
+                                    ui.label("");
+                                }
+                                Some(id) => {
+                                    ui.colored_label(crate::theme::GREY, format!("id {}", id));
+                                }
+                                None => {
+                                    ids_valid = false;
+                                    ui.colored_label(crate::theme::RED, "unknown");
+                                }
+                            }
+                            ui.end_row();
+                        }
+                    });
+
+                ui.add_space(4.0);
+                ui.horizontal(|ui| {
+                    if ui
+                        .add_enabled(ids_valid, egui::Button::new("[apply]"))
+                        .clicked()
+                    {
+                        apply = true;
+                    }
+                    if ui.button("[cancel]").clicked() {
+                        close = true;
+                    }
+                });
+            });
+        let calculate = calculate.then(|| dialog.path.clone());
+        if apply {
+            let path = dialog.path.clone();
+            let changed = match dialog.original_mode {
+                Some(original) => dialog.mode != original,
+                None => dialog.mode_touched,
+            };
+            let mode = changed.then_some(dialog.mode);
+            let ids = Accounts::find(&accounts.users, &dialog.owner)
+                .zip(Accounts::find(&accounts.groups, &dialog.group))
+                .filter(|ids| Some(*ids) != dialog.original_ids);
+            if let Some(mode) = mode {
+                self.chmod(&path, mode);
+            }
+            if let Some((uid, gid)) = ids {
+                self.chown(&path, uid, gid);
+            }
+            close = true;
+        }
+        if close || !open {
+            self.properties_dialog = None;
+        }
+        if let Some(path) = calculate {
+            self.calculate_size(path);
+        }
+    }
+
+    /// Очередь передач: строка на задачу, кнопки по статусу, полоски у активных.
+    fn show_transfer_queue(&self, ui: &mut egui::Ui) -> Option<QueueAction> {
+        let mut action = None;
+        let finished = self
+            .transfers
+            .iter()
+            .filter(|t| matches!(t.status, TransferStatus::Done | TransferStatus::Skipped))
+            .count();
+        ui.horizontal(|ui| {
+            ui.colored_label(
+                crate::theme::GREY,
+                format!("QUEUE {}/{}", finished, self.transfers.len()),
+            );
+            if finished > 0 && ui.small_button("[clear done]").clicked() {
+                action = Some(QueueAction::ClearFinished);
+            }
+        });
+
+        let last = self.transfers.len() - 1;
+        for (i, t) in self.transfers.iter().enumerate() {
+            let transfer = &t.progress;
+            let total = transfer.total.load(Ordering::Relaxed);
+            let transferred = transfer.transferred.load(Ordering::Relaxed);
+            let direction = t.direction();
+            let retry;
+            let (status, color) = match t.status {
+                TransferStatus::Queued => match t.retry_at {
+                    Some(at) => {
+                        retry = format!(
+                            "retry {}/{} in {}s",
+                            t.attempt,
+                            self.retry.attempts,
+                            at.saturating_duration_since(Instant::now()).as_secs() + 1
+                        );
+                        (retry.as_str(), crate::theme::AMBER)
+                    }
+                    None => ("queued", crate::theme::GREY),
+                },
+                TransferStatus::Running => ("", crate::theme::GREEN_DIM),
+                TransferStatus::Paused => ("paused", crate::theme::AMBER),
+                TransferStatus::Done => ("ok", crate::theme::GREEN),
+                TransferStatus::Skipped => ("skipped", crate::theme::GREY),
+                TransferStatus::Failed => ("failed", crate::theme::RED),
+            };
+
+            ui.horizontal(|ui| {
+                ui.colored_label(
+                    color,
+                    format!(
+                        "{} {} {}/{} {}",
+                        direction,
+                        transfer.name,
+                        format_size(transferred),
+                        format_size(total),
+                        status,
+                    ),
+                );
+                if transfer.is_dir && t.status == TransferStatus::Running {
+                    ui.colored_label(
+                        crate::theme::GREY,
+                        format!(
+                            "[{}/{} files] {} {}/{}",
+                            transfer.files_done.load(Ordering::Relaxed),
+                            transfer.files_total.load(Ordering::Relaxed),
+                            transfer.current.lock(),
+                            format_size(transfer.file_transferred.load(Ordering::Relaxed)),
+                            format_size(transfer.file_total.load(Ordering::Relaxed)),
+                        ),
+                    );
+                }
+
+                ui.with_layout(egui::Layout::right_to_left(egui::Align::Center), |ui| {
+                    let label = if t.status.is_finished() { "[x]" } else { "[cancel]" };
+                    if ui.small_button(label).clicked() {
+                        action = Some(QueueAction::Cancel(i));
+                    }
+                    match t.status {
+                        TransferStatus::Queued | TransferStatus::Running => {
+                            if ui.small_button("[pause]").clicked() {
+                                action = Some(QueueAction::Pause(i));
+                            }
+                        }
+                        TransferStatus::Paused => {
+                            if ui.small_button("[resume]").clicked() {
+                                action = Some(QueueAction::Resume(i));
+                            }
+                        }
+                        TransferStatus::Failed => {
+                            if ui.small_button("[retry]").clicked() {
+                                action = Some(QueueAction::Retry(i));
+                            }
+                        }
+                        TransferStatus::Done | TransferStatus::Skipped => {}
+                    }
+                    if t.status == TransferStatus::Queued {
+                        if ui.add_enabled(i < last, egui::Button::new("[v]").small()).clicked() {
+                            action = Some(QueueAction::MoveDown(i));
+                        }
+                        if ui.add_enabled(i > 0, egui::Button::new("[^]").small()).clicked() {
+                            action = Some(QueueAction::MoveUp(i));
+                        }
+                    }
+                });
+            });
+
+            if t.status == TransferStatus::Failed || t.retry_at.is_some() {
+                if let Some(err) = transfer.error.lock().as_ref() {
+                    ui.colored_label(crate::theme::RED, format!("  {}", err));
+                }
+            }
+
+            if !matches!(t.status, TransferStatus::Running | TransferStatus::Paused) {
+                continue;
+            }
+            let bar_color = if t.status == TransferStatus::Paused {
+                crate::theme::AMBER
+            } else {
+                crate::theme::GREEN
+            };
+
+            // У папки — ещё тонкая полоска текущего файла
+            if transfer.is_dir {
+                let file_rect = ui.allocate_space(egui::vec2(ui.available_width(), 2.0)).1;
+                ui.painter().rect_filled(file_rect, 0.0, crate::theme::BG_WIDGET);
+                let filled = egui::Rect::from_min_size(
+                    file_rect.min,
+                    egui::vec2(
+                        file_rect.width() * transfer.file_fraction(),
+                        file_rect.height(),
+                    ),
+                );
+                ui.painter().rect_filled(filled, 0.0, crate::theme::GREEN_DIM);
+            }
+
+            let bar_rect = ui.allocate_space(egui::vec2(ui.available_width(), 4.0)).1;
+            ui.painter().rect_filled(
+                bar_rect,
+                0.0,
+                crate::theme::BG_WIDGET,
+            );
+            let filled = egui::Rect::from_min_size(
+                bar_rect.min,
+                egui::vec2(bar_rect.width() * transfer.fraction(), bar_rect.height()),
+            );
+            ui.painter().rect_filled(filled, 0.0, bar_color);
+        }
+        action
+    }
+
+    fn download_selected(&mut self) {
+        match self.download_dir() {
+            Some(dir) => self.download_selected_to(&dir),
+            None => self.error = Some("cannot determine downloads dir".to_string()),
+        }
+    }
+
+    fn download_selected_to(&mut self, dir: &Path) {
+        let selected: Vec<_> = self
+            .entries
+            .iter()
+            .filter(|e| self.selected.contains(&e.path))
+            .map(|e| (e.path.clone(), e.name.clone(), e.size))
+            .collect();
+        for (path, name, size) in &selected {
+            let local = dir.join(name);
+            self.download(path, &local.to_string_lossy(), *size);
+        }
+        self.selected.clear();
+        self.summary = None;
+    }
+
+    /// Папка для [get]: из настроек сессии (`~/` — домашняя), иначе системная «Загрузки».
+    fn download_dir(&self) -> Option<PathBuf> {
+        match self.download_folder.as_str() {
+            "" => dirs::download_dir(),
+            dir => match dir.strip_prefix("~/") {
+                Some(rest) => Some(dirs::home_dir()?.join(rest)),
+                None => Some(PathBuf::from(dir)),
+            },
+        }
+    }
+
+    /// [get to...]: папка на один раз, настройка сессии не меняется.
+    fn pick_download_dir(&self) -> Option<PathBuf> {
+        let mut dialog = rfd::FileDialog::new().set_title("Download to");
+        if let Some(dir) = self.download_dir() {
+            dialog = dialog.set_directory(dir);
+        }
+        dialog.pick_folder()
+    }
+
+    pub fn set_download_dir(&mut self, dir: &str) {
+        self.download_folder = dir.to_string();
+    }
+
+    fn upload_via_dialog(&mut self) {
+        let dialog = rfd::FileDialog::new().set_title("Select files to upload");
+
+        if let Some(files) = dialog.pick_files() {
+            for file in &files {
+                self.upload_path(file);
+            }
+        }
+    }
+}
+
+/// Запись журнала для передачи, которая только что закончилась.
+fn transfer_record(t: &QueuedTransfer, session: &str) -> TransferRecord {
+    let (source, dest) = t.endpoints();
+    let result = if t.progress.cancel.load(Ordering::Relaxed) {
+        "cancelled".to_string()
+    } else if t.status == TransferStatus::Skipped {
+        "skipped".to_string()
+    } else if t.status == TransferStatus::Failed {
+        t.progress.error.lock().clone().unwrap_or_else(|| "failed".to_string())
+    } else {
+        "ok".to_string()
+    };
+    TransferRecord {
+        time: std::time::SystemTime::now()
+            .duration_since(std::time::UNIX_EPOCH)
+            .map(|d| d.as_secs())
+            .unwrap_or(0),
+        session: session.to_string(),
+        direction: t.direction().to_string(),
+        source,
+        dest,
+        bytes: t.progress.transferred.load(Ordering::Relaxed),
+        duration_ms: t.started.map_or(0, |s| s.elapsed().as_millis() as u64),
+        result,
+    }
+}
+
+/// Запуск программы с файлом. Бандл macOS (.app) — через `open -a`.
+/// Процесс ждём в отдельном потоке, иначе после выхода он остаётся зомби.
+fn launch_with(app: &Path, file: &Path) -> std::io::Result<()> {
+    let mut cmd = if app.extension().is_some_and(|ext| ext == "app") {
+        let mut c = std::process::Command::new("open");
+        c.arg("-a").arg(app);
+        c
+    } else {
+        std::process::Command::new(app)
+    };
+    cmd.arg(file)
+        .stdin(std::process::Stdio::null())
+        .stdout(std::process::Stdio::null())
+        .stderr(std::process::Stdio::null())
+        .spawn()
+        .map(|mut child| {
+            std::thread::spawn(move || {
+                let _ = child.wait();
+            });
+        })
+}
+
+// ── Background async SFTP thread ──
+
+async fn sftp_thread_async(
+    config: &SessionConfig,
+    mut req_rx: tokio::sync::mpsc::UnboundedReceiver<SftpRequest>,
+    resp_tx: &mpsc::Sender<SftpResponse>,
+    limiter: Arc<RateLimiter>,
+    connection: &parking_lot::Mutex<Option<Arc<russh::client::Handle<SshHandler>>>>,
+) -> Result<(), Box<dyn std::error::Error + Send + Sync>> {
+    let retry = RetryPolicy::new(config.sftp_retries, config.sftp_retry_delay_secs);
+    let mut session = Arc::new(create_russh_session(config, SshHandler::new()).await?);
+    let mut sftp = match open_sftp(&session).await {
+        Ok(sftp) => sftp,
+        // Нет sftp-server (сетевое железо, старые системы) — работаем через scp
+        Err(e) => {
+            let _ = resp_tx.send(SftpResponse::ScpFallback(e.to_string()));
+            return scp_thread_async(session, req_rx, resp_tx, limiter).await;
+        }
+    };
+    let mut lister = open_raw_sftp(&session).await?;
+    *connection.lock() = Some(Arc::clone(&session));
+    // Запросы, оборвавшиеся на полпути: номер повтора и когда повторять.
+    // Пока пауза не вышла, воркер обслуживает остальные запросы
+    let mut pending: VecDeque<(SftpRequest, u32, tokio::time::Instant)> = VecDeque::new();
+
+    loop {
+        let due = pending.iter().map(|p| p.2).min();
+        let (req, attempt) = tokio::select! {
+            _ = tokio::time::sleep_until(due.unwrap_or_else(tokio::time::Instant::now)),
+                if due.is_some() =>
+            {
+                let i = pending.iter().position(|p| Some(p.2) == due).unwrap_or(0);
+                match pending.remove(i) {
+                    Some((req, attempt, _)) => (req, attempt),
+                    None => continue,
+                }
+            }
+            next = req_rx.recv() => match next {
+                Some(req) => {
+                    // Новый листинг отменяет повтор старого: показать надо свежую папку
+                    if matches!(req, SftpRequest::ListStart(_) | SftpRequest::ListDir(_)) {
+                        pending.retain(|p| !matches!(p.0, SftpRequest::ListDir(_)));
+                    }
+                    (req, 0)
+                }
+                None => break,
+            },
+        };
+        // Соединение упало (или повторяем) — сначала переподключаемся
+        if attempt > 0 || session.is_closed() {
+            if let Err(e) = reopen_sftp(config, &mut session, &mut sftp, &mut lister).await {
+                let transient = is_transient(e.as_ref());
+                let e = e.to_string();
+                if attempt < retry.attempts && transient {
+                    let _ = resp_tx.send(SftpResponse::Retrying(format!(
+                        "reconnect: {} — retry {}/{}",
+                        e,
+                        attempt + 1,
+                        retry.attempts
+                    )));
+                    let at = tokio::time::Instant::now() + retry.backoff(attempt + 1);
+                    pending.push_back((req, attempt + 1, at));
+                } else if let Some(progress) = req.progress() {
+                    // Иначе задача так и останется «running» в очереди
+                    finish_transfer(resp_tx, progress, Err(e.into()), String::new());
+                } else {
+                    let _ = resp_tx.send(SftpResponse::Error(e));
+                }
+                continue;
+            }
+            *connection.lock() = Some(Arc::clone(&session));
+        }
+        match req {
+            SftpRequest::ListStart(last) => {
+                // realpath "." — домашняя папка (куда sftp-server попадает при входе)
+                let path = match last {
+                    Some(path) if sftp.metadata(&path).await.is_ok_and(|m| m.is_dir()) => path,
+                    _ => sftp.canonicalize(".").await.unwrap_or_else(|_| "/".to_string()),
+                };
+                if let Err(e) = stream_dir(&lister, &sftp, &path, resp_tx).await {
+                    pending.extend(retry_listing(retry, attempt, e.as_ref(), path, resp_tx));
+                }
+            }
+            SftpRequest::ListDir(path) => {
+                if let Err(e) = stream_dir(&lister, &sftp, &path, resp_tx).await {
+                    pending.extend(retry_listing(retry, attempt, e.as_ref(), path, resp_tx));
+                }
+            }
+            // Поиск тоже не держит основной канал
+            SftpRequest::Search { id, query, cancel } => {
+                tokio::spawn(run_search(
+                    Arc::clone(&session),
+                    id,
+                    query,
+                    cancel,
+                    resp_tx.clone(),
+                ));
+            }
+            SftpRequest::StatDir(path) => {
+                let mtime = sftp.metadata(&path).await.ok().and_then(|m| m.mtime);
+                let _ = resp_tx.send(SftpResponse::DirStamp(path, mtime.map(u64::from)));
+            }
+            SftpRequest::DiskUsage(path) => {
+                let usage = match sftp.fs_info(path.clone()).await {
+                    Ok(Some(st)) => Some(DiskUsage {
+                        total: st.blocks * st.fragment_size,
+                        avail: st.blocks_avail * st.fragment_size,
+                    }),
+                    // Без statvfs@openssh.com — df на сервере
+                    _ => df_usage(&session, &path).await,
+                };
+                if let Some(usage) = usage {
+                    let _ = resp_tx.send(SftpResponse::DiskUsage(path, usage));
+                }
+            }
+            // Обход большого дерева не держит основной канал
+            SftpRequest::RemoveAll(path) => {
+                tokio::spawn(run_remove_all(Arc::clone(&session), path, resp_tx.clone()));
+            }
+            SftpRequest::TreeStats { id, path } => {
+                tokio::spawn(run_tree_stats(
+                    Arc::clone(&session),
+                    id,
+                    path,
+                    resp_tx.clone(),
+                ));
+            }
+            SftpRequest::SyncCompare { id, spec } => {
+                tokio::spawn(run_sync_compare(
+                    Arc::clone(&session),
+                    id,
+                    spec,
+                    resp_tx.clone(),
+                ));
+            }
+            SftpRequest::Sudo { req, password } => {
+                tokio::spawn(run_sudo(
+                    Arc::clone(&session),
+                    *req,
+                    password,
+                    resp_tx.clone(),
+                    Arc::clone(&limiter),
+                ));
+            }
+            // Передачи — каждая в своей задаче и своём канале, листинг не ждёт
+            req @ (SftpRequest::Download { .. }
+            | SftpRequest::Upload { .. }
+            | SftpRequest::UploadDir { .. }
+            | SftpRequest::Sync { .. }
+            | SftpRequest::RemoteCopy { .. }
+            | SftpRequest::SendTo { .. }
+            | SftpRequest::DownloadArchive { .. }) => {
+                tokio::spawn(run_transfer(
+                    Arc::clone(&session),
+                    req,
+                    resp_tx.clone(),
+                    Arc::clone(&limiter),
+                ));
+            }
+            SftpRequest::Mkdir { path, mode } => {
+                let result = match sftp.create_dir(&path).await {
+                    Ok(()) => set_remote_mode(&sftp, &path, mode).await,
+                    Err(e) => Err(e.to_string().into()),
+                };
+                let _ = resp_tx.send(match result {
+                    Ok(()) => SftpResponse::Success(format!("OK: mkdir {}", path)),
+                    Err(e) => SftpResponse::Error(e.to_string()),
+                });
+            }
+            SftpRequest::NewFile(path) => {
+                let flags = russh_sftp::protocol::OpenFlags::CREATE
+                    | russh_sftp::protocol::OpenFlags::EXCLUDE
+                    | russh_sftp::protocol::OpenFlags::WRITE;
+                let response = match sftp.open_with_flags(&path, flags).await {
+                    Ok(mut file) => {
+                        use tokio::io::AsyncWriteExt;
+                        file.shutdown().await.ok();
+                        SftpResponse::Success(format!("OK: new file {}", path))
+                    }
+                    Err(e) => SftpResponse::Error(format!("new file '{}': {}", path, e)),
+                };
+                let _ = resp_tx.send(response);
+            }
+            SftpRequest::Touch(path) => {
+                // Время в SFTP v3 — u32: часы до 1970 или после 2106 не ставим
+                let Some(now) = unix_secs(std::time::SystemTime::now()) else {
+                    let error = format!("touch '{}': system time out of SFTP range", path);
+                    let _ = resp_tx.send(SftpResponse::Error(error));
+                    continue;
+                };
+                let attrs = russh_sftp::protocol::FileAttributes {
+                    atime: Some(now),
+                    mtime: Some(now),
+                    ..russh_sftp::protocol::FileAttributes::empty()
+                };
+                let response = match sftp.set_metadata(&path, attrs).await {
+                    Ok(()) => SftpResponse::Success(format!("OK: touch {}", path)),
+                    Err(e) => SftpResponse::Error(format!("touch '{}': {}", path, e)),
+                };
+                let _ = resp_tx.send(response);
+            }
+            SftpRequest::Remove(path) => {
+                let result = match sftp.remove_file(&path).await {
+                    Ok(()) => Ok(()),
+                    Err(_) => sftp.remove_dir(&path).await,
+                };
+                match result {
+                    Ok(()) => {
+                        let _ = resp_tx.send(SftpResponse::Success(format!("OK: rm {}", path)));
+                    }
+                    Err(e) => {
+                        let _ = resp_tx.send(SftpResponse::Error(e.to_string()));
+                    }
+                }
+            }
+            SftpRequest::Rename { from, to } => match sftp.rename(&from, &to).await {
+                Ok(()) => {
+                    let _ = resp_tx.send(SftpResponse::Success(format!(
+                        "OK: mv {} -> {}",
+                        from, to
+                    )));
+                }
+                Err(e) => {
+                    let _ = resp_tx.send(SftpResponse::Error(e.to_string()));
+                }
+            },
+            // OpenSSH sftp-server читает аргументы SSH_FXP_SYMLINK в обратном
+            // порядке (известная несовместимость со спецификацией) — подстраиваемся под него
+            SftpRequest::Preview(path) => {
+                let result = load_preview(&sftp, &path).await;
+                let _ = resp_tx.send(SftpResponse::Preview(path, result));
+            }
+            SftpRequest::Symlink { link, target } => match sftp.symlink(&target, &link).await {
+                Ok(()) => {
+                    let _ = resp_tx.send(SftpResponse::Success(format!(
+                        "OK: ln -s {} {}",
+                        target, link
+                    )));
+                }
+                Err(e) => {
+                    let _ = resp_tx.send(SftpResponse::Error(format!(
+                        "symlink '{}': {}",
+                        link, e
+                    )));
+                }
+            },
+            SftpRequest::Chown { path, uid, gid } => {
+                let attrs = russh_sftp::protocol::FileAttributes {
+                    uid: Some(uid),
+                    gid: Some(gid),
+                    ..russh_sftp::protocol::FileAttributes::empty()
+                };
+                match sftp.set_metadata(&path, attrs).await {
+                    Ok(()) => {
+                        let _ = resp_tx.send(SftpResponse::Success(format!(
+                            "OK: chown {}:{} {}",
+                            uid, gid, path
+                        )));
+                    }
+                    Err(e) => {
+                        let _ = resp_tx.send(SftpResponse::Error(format!(
+                            "chown '{}': {}",
+                            path, e
+                        )));
+                    }
+                }
+            }
+            SftpRequest::LoadAccounts => {
+                let read = |path: &'static str| {
+                    let sftp = &sftp;
+                    async move {
+                        sftp.read(path)
+                            .await
+                            .map(|data| String::from_utf8_lossy(&data).into_owned())
+                            .unwrap_or_default()
+                    }
+                };
+                let accounts = Accounts {
+                    users: Accounts::parse_ids(&read("/etc/passwd").await),
+                    groups: Accounts::parse_ids(&read("/etc/group").await),
+                };
+                let _ = resp_tx.send(SftpResponse::Accounts(accounts));
+            }
+            SftpRequest::Chmod { path, mode } => {
+                let attrs = russh_sftp::protocol::FileAttributes {
+                    permissions: Some(mode & 0o7777),
+                    ..russh_sftp::protocol::FileAttributes::empty()
+                };
+                match sftp.set_metadata(&path, attrs).await {
+                    Ok(()) => {
+                        let _ = resp_tx.send(SftpResponse::Success(format!(
+                            "OK: chmod {:04o} {}",
+                            mode, path
+                        )));
+                    }
+                    Err(e) => {
+                        let _ = resp_tx.send(SftpResponse::Error(format!(
+                            "chmod '{}': {}",
+                            path, e
+                        )));
+                    }
+                }
+            }
+        }
+    }
+
+    Ok(())
+}
+
+/// Ещё один SFTP-канал на том же SSH-соединении.
+async fn open_sftp(
+    session: &russh::client::Handle<SshHandler>,
+) -> Result<russh_sftp::client::SftpSession, Box<dyn std::error::Error + Send + Sync>> {
+    let channel = open_sftp_channel(session).await?;
+    Ok(russh_sftp::client::SftpSession::new(channel.into_stream()).await?)
+}
+
+/// Низкоуровневая сессия для постраничного readdir: SftpSession::read_dir
+/// отдаёт папку только целиком.
+async fn open_raw_sftp(
+    session: &russh::client::Handle<SshHandler>,
+) -> Result<russh_sftp::client::RawSftpSession, Box<dyn std::error::Error + Send + Sync>> {
+    let channel = open_sftp_channel(session).await?;
+    let raw = russh_sftp::client::RawSftpSession::new(channel.into_stream());
+    raw.init().await?;
+    Ok(raw)
+}
+
+async fn open_sftp_channel(
+    session: &russh::client::Handle<SshHandler>,
+) -> Result<russh::Channel<russh::client::Msg>, Box<dyn std::error::Error + Send + Sync>> {
+    let mut channel = session.channel_open_session().await?;
+    channel.request_subsystem(true, "sftp").await?;
+    // Без sftp-server сервер отвечает отказом (или сразу закрывает канал)
+    loop {
+        match channel.wait().await {
+            Some(russh::ChannelMsg::Success) => break,
+            Some(russh::ChannelMsg::Failure)
+            | Some(russh::ChannelMsg::Eof)
+            | Some(russh::ChannelMsg::Close)
+            | None => return Err("server has no sftp subsystem".into()),
+            Some(_) => {}
+        }
+    }
+    Ok(channel)
+}
+
+/// Одна передача в отдельном SFTP-канале; канал закрывается по окончании.
+async fn run_transfer(
+    session: Arc<russh::client::Handle<SshHandler>>,
+    req: SftpRequest,
+    resp_tx: mpsc::Sender<SftpResponse>,
+    limiter: Arc<RateLimiter>,
+) {
+    let sftp = match open_sftp(&session).await {
+        Ok(sftp) => sftp,
+        Err(e) => {
+            if let Some(progress) = req.progress() {
+                finish_transfer(&resp_tx, progress, Err(e), String::new());
+            }
+            return;
+        }
+    };
+    match req {
+        SftpRequest::RemoteCopy {
+            sources,
+            dest,
+            progress,
+        } => {
+            let result = remote_copy(&session, &sftp, &sources, &dest, &progress, &limiter).await;
+            let message = format!("OK: cp {} items -> {}", sources.len(), dest);
+            finish_transfer(&resp_tx, &progress, result, message);
+        }
+        SftpRequest::SendTo {
+            sources,
+            peer,
+            progress,
+        } => {
+            let result = send_to(&session, &sftp, &sources, &peer, &progress, &limiter).await;
+            let message = format!("OK: sent {} items -> {}:{}", sources.len(), peer.name, peer.dir);
+            finish_transfer(&resp_tx, &progress, result, message);
+        }
+        SftpRequest::DownloadArchive {
+            remote,
+            local,
+            progress,
+        } => {
+            let result = download_archive(&session, &remote, &local, &progress, &limiter).await;
+            if result.is_err() {
+                // Недописанный архив бесполезен — докачать его нельзя
+                tokio::fs::remove_file(&local).await.ok();
+            }
+            finish_transfer(&resp_tx, &progress, result, format!("OK: get {}", local));
+        }
+        req => {
+            // Скачивание читает с упреждением — по своему каналу, без обёртки File
+            match open_raw_sftp(&session).await {
+                Ok(reader) => {
+                    let reader = Arc::new(reader);
+                    transfer_on(&sftp, &reader, req, &resp_tx, &limiter).await;
+                    reader.close_session().ok();
+                }
+                Err(e) => {
+                    if let Some(progress) = req.progress() {
+                        finish_transfer(&resp_tx, progress, Err(e), String::new());
+                    }
+                }
+            }
+        }
+    }
+    sftp.close().await.ok();
+}
+
+async fn transfer_on(
+    sftp: &russh_sftp::client::SftpSession,
+    reader: &Arc<russh_sftp::client::RawSftpSession>,
+    req: SftpRequest,
+    resp_tx: &mpsc::Sender<SftpResponse>,
+    limiter: &RateLimiter,
+) {
+    match req {
+        SftpRequest::Download {
+            remote,
+            local,
+            progress,
+            resume,
+        } => {
+            let offset = match resume {
+                Some(false) => 0,
+                _ => partial_download_offset(sftp, &remote, &local, &progress).await,
+            };
+            if resume.is_none() {
+                if let Some(existing) = local_stamp(Path::new(&local)).await {
+                    let source = remote_stamp(sftp, &remote).await.unwrap_or(FileStamp {
+                        size: progress.total.load(Ordering::Relaxed),
+                        modified: None,
+                    });
+                    let rename = free_local_name(Path::new(&local)).await;
+                    let _ = resp_tx.send(SftpResponse::Conflict(TransferConflict {
+                        is_upload: false,
+                        local,
+                        remote,
+                        progress,
+                        offset,
+                        source,
+                        existing,
+                        rename,
+                        batch: None,
+                    }));
+                    return;
+                }
+            }
+            let result =
+                download_chunked(reader, &remote, &local, &progress, offset, limiter).await;
+            finish_transfer(resp_tx, &progress, result, format!("OK: get {}", remote));
+        }
+        SftpRequest::Upload {
+            local,
+            remote,
+            progress,
+            resume,
+            perms,
+        } => {
+            let offset = match resume {
+                Some(false) => 0,
+                _ => partial_upload_offset(sftp, &local, &remote).await,
+            };
+            if resume.is_none() {
+                if let Some(existing) = remote_stamp(sftp, &remote).await {
+                    let source = local_stamp(Path::new(&local)).await.unwrap_or(FileStamp {
+                        size: progress.total.load(Ordering::Relaxed),
+                        modified: None,
+                    });
+                    let rename = free_remote_name(sftp, &remote).await;
+                    let _ = resp_tx.send(SftpResponse::Conflict(TransferConflict {
+                        is_upload: true,
+                        local,
+                        remote,
+                        progress,
+                        offset,
+                        source,
+                        existing,
+                        rename,
+                        batch: None,
+                    }));
+                    return;
+                }
+            }
+            let need = progress.total.load(Ordering::Relaxed).saturating_sub(offset);
+            let mode = perms.file_mode(local_mode(Path::new(&local)));
+            let dir = remote_parent(&remote);
+            let result = match check_free_space(sftp, &dir, need, &progress).await {
+                Ok(()) => {
+                    upload_chunked(sftp, &local, &remote, &progress, offset, mode, limiter).await
+                }
+                Err(e) => Err(e),
+            };
+            if let Some(shortage) = space_shortage(&result) {
+                let _ = resp_tx.send(SftpResponse::LowSpace(LowSpace {
+                    progress: Arc::clone(&progress),
+                    req: SftpRequest::Upload {
+                        local,
+                        remote,
+                        progress,
+                        resume: Some(offset > 0),
+                        perms,
+                    },
+                    shortage,
+                }));
+                return;
+            }
+            finish_transfer(resp_tx, &progress, result, format!("OK: put {}", remote));
+        }
+        SftpRequest::UploadDir {
+            local,
+            remote,
+            progress,
+            resume,
+            perms,
+        } => {
+            if resume.is_none() {
+                if let Some((source, existing, offset, files)) =
+                    upload_dir_conflict(sftp, &local, &remote).await
+                {
+                    let rename = free_remote_name(sftp, &remote).await;
+                    let req = SftpRequest::UploadDir {
+                        local: local.clone(),
+                        remote: remote.clone(),
+                        progress: Arc::clone(&progress),
+                        resume,
+                        perms,
+                    };
+                    let _ = resp_tx.send(SftpResponse::Conflict(TransferConflict {
+                        is_upload: true,
+                        local,
+                        remote,
+                        progress,
+                        offset,
+                        source,
+                        existing,
+                        rename,
+                        batch: Some(BatchConflict { files, req }),
+                    }));
+                    return;
+                }
+            }
+            let resume = resume == Some(true);
+            let result =
+                upload_dir_recursive(sftp, &local, &remote, &progress, resume, perms, limiter)
+                    .await;
+            if let Some(shortage) = space_shortage(&result) {
+                let _ = resp_tx.send(SftpResponse::LowSpace(LowSpace {
+                    progress: Arc::clone(&progress),
+                    req: SftpRequest::UploadDir {
+                        local,
+                        remote,
+                        progress,
+                        resume: Some(resume),
+                        perms,
+                    },
+                    shortage,
+                }));
+                return;
+            }
+            let message = format!(
+                "OK: put {} ({} files)",
+                remote,
+                progress.files_done.load(Ordering::Relaxed)
+            );
+            finish_transfer(resp_tx, &progress, result, message);
+        }
+        SftpRequest::Sync {
+            spec,
+            items,
+            progress,
+            resume,
+            perms,
+        } => {
+            let replaced: Vec<&SyncItem> = items.iter().filter(|i| i.replaces).collect();
+            if resume.is_none() && !replaced.is_empty() {
+                let size = replaced.iter().map(|i| i.size).sum();
+                let files = replaced.len() as u64;
+                let (local, remote) = (spec.local.clone(), spec.remote.clone());
+                let is_upload = spec.direction == SyncDirection::Upload;
+                let req = SftpRequest::Sync {
+                    spec,
+                    items,
+                    progress: Arc::clone(&progress),
+                    resume,
+                    perms,
+                };
+                let _ = resp_tx.send(SftpResponse::Conflict(TransferConflict {
+                    is_upload,
+                    local,
+                    remote,
+                    progress,
+                    offset: 0,
+                    source: FileStamp {
+                        size,
+                        modified: None,
+                    },
+                    existing: FileStamp {
+                        size: 0,
+                        modified: None,
+                    },
+                    rename: String::new(),
+                    batch: Some(BatchConflict { files, req }),
+                }));
+                return;
+            }
+            let result = run_sync(sftp, reader, &spec, &items, &progress, perms, limiter).await;
+            let message = format!(
+                "OK: sync {} <-> {} ({} steps)",
+                spec.local,
+                spec.remote,
+                progress.files_done.load(Ordering::Relaxed)
+            );
+            finish_transfer(resp_tx, &progress, result, message);
+        }
+        _ => {}
+    }
+}
+
+/// Stdout команды на сервере целиком и код возврата (stderr не нужен).
+/// Код приходит после EOF, поэтому ждём закрытия канала.
+async fn exec_output(
+    session: &russh::client::Handle<SshHandler>,
+    command: String,
+) -> Result<(String, Option<u32>), Box<dyn std::error::Error + Send + Sync>> {
+    let mut channel = session.channel_open_session().await?;
+    channel.exec(true, command).await?;
+    let mut output = Vec::new();
+    let mut exit_status = None;
+    while let Some(msg) = channel.wait().await {
+        match msg {
+            russh::ChannelMsg::Data { ref data } => output.extend_from_slice(data),
+            russh::ChannelMsg::ExitStatus { exit_status: code } => exit_status = Some(code),
+            russh::ChannelMsg::Close => break,
+            _ => {}
+        }
+    }
+    channel.close().await.ok();
+    Ok((String::from_utf8_lossy(&output).into_owned(), exit_status))
+}
+
+/// Команда, которую можно прервать: при паузе/отмене передачи процесс получает
+/// TERM, а канал закрывается. Возвращает код выхода.
+async fn exec_interruptible(
+    session: &russh::client::Handle<SshHandler>,
+    command: String,
+    progress: &TransferState,
+) -> Result<Option<u32>, Box<dyn std::error::Error + Send + Sync>> {
+    let mut channel = session.channel_open_session().await?;
+    channel.exec(true, command).await?;
+    let mut exit_status = None;
+    let mut tick = tokio::time::interval(Duration::from_millis(200));
+    loop {
+        tokio::select! {
+            msg = channel.wait() => match msg {
+                Some(russh::ChannelMsg::ExitStatus { exit_status: code }) => {
+                    exit_status = Some(code)
+                }
+                Some(russh::ChannelMsg::Close) | None => break,
+                _ => {}
+            },
+            _ = tick.tick() => {
+                if let Err(e) = progress.check_interrupted() {
+                    channel.signal(russh::Sig::TERM).await.ok();
+                    channel.close().await.ok();
+                    return Err(e.into());
+                }
+            }
+        }
+    }
+    channel.close().await.ok();
+    Ok(exit_status)
+}
+
+/// Время файла для SFTP: секунды с эпохи, если влезают в u32.
+fn unix_secs(time: std::time::SystemTime) -> Option<u32> {
+    let secs = time.duration_since(std::time::UNIX_EPOCH).ok()?.as_secs();
+    u32::try_from(secs).ok()
+}
+
+/// root + относительный путь; "" — сам root
+fn remote_join(root: &str, rel: &str) -> String {
+    if rel.is_empty() {
+        root.to_string()
+    } else {
+        format!("{}/{}", root.trim_end_matches('/'), rel)
+    }
+}
+
+/// Аргумент для sh: в одинарных кавычках, ' → '\''
+pub(crate) fn shell_quote(s: &str) -> String {
+    format!("'{}'", s.replace('\'', "'\\''"))
+}
+
+/// Путь из строки ввода: относительный — от base, с разбором . и ..
+fn join_remote(base: &str, input: &str) -> String {
+    let input = input.trim();
+    let mut parts: Vec<&str> = Vec::new();
+    let start = if input.starts_with('/') { "" } else { base };
+    for part in start.split('/').chain(input.split('/')) {
+        match part {
+            "" | "." => {}
+            ".." => {
+                parts.pop();
+            }
+            part => parts.push(part),
+        }
+    }
+    format!("/{}", parts.join("/"))
+}
+
+/// Дополняет последний сегмент по подпапкам из кеша листингов:
+/// единственный вариант — целиком со слешем, несколько — до общего префикса.
+fn complete_path(
+    cache: &HashMap<String, Vec<String>>,
+    current: &str,
+    text: &str,
+) -> Option<String> {
+    let (dir, prefix) = text.split_at(text.rfind('/').map_or(0, |i| i + 1));
+    let names: Vec<&String> = cache
+        .get(&join_remote(current, dir))?
+        .iter()
+        .filter(|name| name.starts_with(prefix))
+        .collect();
+    match names.as_slice() {
+        [] => None,
+        [name] => Some(format!("{}{}/", dir, name)),
+        [first, rest @ ..] => {
+            let mut common = first.as_str();
+            for name in rest {
+                let len = common
+                    .char_indices()
+                    .zip(name.chars())
+                    .find(|((_, a), b)| a != b)
+                    .map_or(common.len().min(name.len()), |((i, _), _)| i);
+                common = &common[..len];
+            }
+            (common.len() > prefix.len()).then(|| format!("{}{}", dir, common))
+        }
+    }
+}
+
+/// "512", "10K", "1.5M", "2G" → байты
+fn parse_size(text: &str) -> Option<u64> {
+    let text = text.trim().to_ascii_uppercase();
+    let (number, multiplier) = match text.chars().last()? {
+        'K' => (&text[..text.len() - 1], 1024.0),
+        'M' => (&text[..text.len() - 1], 1024.0 * 1024.0),
+        'G' => (&text[..text.len() - 1], 1024.0 * 1024.0 * 1024.0),
+        _ => (text.as_str(), 1.0),
+    };
+    let value: f64 = number.trim().parse().ok()?;
+    (value >= 0.0).then_some((value * multiplier) as u64)
+}
+
+/// Итог передачи: флаги для очереди и сообщение в строку статуса.
+/// Пауза и отмена ошибкой не считаются.
+fn finish_transfer(
+    resp_tx: &mpsc::Sender<SftpResponse>,
+    progress: &TransferState,
+    result: Result<(), Box<dyn std::error::Error + Send + Sync>>,
+    message: String,
+) {
+    match result {
+        Ok(()) => {
+            progress.done.store(true, Ordering::Relaxed);
+            let message = match progress.chmod_failed.load(Ordering::Relaxed) {
+                0 => message,
+                n => format!("{} (permissions not set on {})", message, n),
+            };
+            let _ = resp_tx.send(SftpResponse::Success(message));
+        }
+        Err(e) => {
+            if progress.interruption().is_none() {
+                let _ = resp_tx.send(SftpResponse::Error(e.to_string()));
+            }
+            *progress.error.lock() = Some(e.to_string());
+            progress.transient.store(is_transient(e.as_ref()), Ordering::Relaxed);
+            progress.failed.store(true, Ordering::Relaxed);
+        }
+    }
+}
+
+/// Заново открывает каналы SFTP после обрыва; упало само соединение — и его.
+async fn reopen_sftp(
+    config: &SessionConfig,
+    session: &mut Arc<russh::client::Handle<SshHandler>>,
+    sftp: &mut russh_sftp::client::SftpSession,
+    lister: &mut russh_sftp::client::RawSftpSession,
+) -> Result<(), Box<dyn std::error::Error + Send + Sync>> {
+    if session.is_closed() {
+        *session = Arc::new(create_russh_session(config, SshHandler::new()).await?);
+    }
+    *sftp = open_sftp(session).await?;
+    *lister = open_raw_sftp(session).await?;
+    Ok(())
+}
+
+/// Листинг упал: обрыв — повторить (воркер переподключится), иначе показать ошибку.
+fn retry_listing(
+    retry: RetryPolicy,
+    attempt: u32,
+    error: &(dyn std::error::Error + 'static),
+    path: String,
+    resp_tx: &mpsc::Sender<SftpResponse>,
+) -> Option<(SftpRequest, u32, tokio::time::Instant)> {
+    if attempt < retry.attempts && is_transient(error) {
+        let _ = resp_tx.send(SftpResponse::Retrying(format!(
+            "{}: {} — retry {}/{}",
+            path,
+            error,
+            attempt + 1,
+            retry.attempts
+        )));
+        let at = tokio::time::Instant::now() + retry.backoff(attempt + 1);
+        Some((SftpRequest::ListDir(path), attempt + 1, at))
+    } else {
+        let _ = resp_tx.send(SftpResponse::Error(error.to_string()));
+        None
+    }
+}
+
+/// Листинг для таблицы страницами (DirPage): огромная папка начинает
+/// показываться сразу, а не после чтения всех записей.
+async fn stream_dir(
+    raw: &russh_sftp::client::RawSftpSession,
+    sftp: &russh_sftp::client::SftpSession,
+    path: &str,
+    resp_tx: &mpsc::Sender<SftpResponse>,
+) -> Result<(), Box<dyn std::error::Error + Send + Sync>> {
+    let handle = raw.opendir(path).await?.handle;
+    let mut page = Vec::new();
+    let mut first = true;
+    let mut flushed = Instant::now();
+    let result = loop {
+        match raw.readdir(handle.as_str()).await {
+            Ok(name) => {
+                for file in name.files {
+                    if file.filename != "." && file.filename != ".." {
+                        page.push(dir_entry(sftp, path, file.filename, file.attrs).await);
+                    }
+                }
+            }
+            Err(russh_sftp::client::error::Error::Status(status))
+                if status.status_code == russh_sftp::protocol::StatusCode::Eof =>
+            {
+                break Ok(());
+            }
+            Err(e) => break Err(e),
+        }
+        if page.len() >= LIST_PAGE_SIZE || flushed.elapsed() >= LIST_PAGE_INTERVAL {
+            let _ = resp_tx.send(SftpResponse::DirPage {
+                path: path.to_string(),
+                entries: std::mem::take(&mut page),
+                first,
+                last: false,
+            });
+            first = false;
+            flushed = Instant::now();
+        }
+    };
+    raw.close(handle).await.ok();
+    result?;
+    let _ = resp_tx.send(SftpResponse::DirPage {
+        path: path.to_string(),
+        entries: page,
+        first,
+        last: true,
+    });
+    Ok(())
+}
+
+async fn list_dir_async(
+    sftp: &russh_sftp::client::SftpSession,
+    path: &str,
+) -> Result<Vec<SftpEntry>, Box<dyn std::error::Error + Send + Sync>> {
+    let entries = sftp.read_dir(path).await?;
+    let mut result: Vec<SftpEntry> = Vec::new();
+    for entry in entries {
+        let name = entry.file_name();
+        if name == "." || name == ".." {
+            continue;
+        }
+        result.push(dir_entry(sftp, path, name, entry.metadata()).await);
+    }
+    // Сортирует UI — по выбранной колонке
+    Ok(result)
+}
+
+/// Запись папки из readdir; для симлинка — тип и размер цели.
+async fn dir_entry(
+    sftp: &russh_sftp::client::SftpSession,
+    path: &str,
+    name: String,
+    metadata: russh_sftp::client::fs::Metadata,
+) -> SftpEntry {
+    let file_path = if path == "/" {
+        format!("/{}", name)
+    } else {
+        format!("{}/{}", path.trim_end_matches('/'), name)
+    };
+    // readdir отдаёт атрибуты самой ссылки (как lstat)
+    let mode = metadata.permissions.unwrap_or(0);
+    let mut is_dir = mode & 0o170000 == 0o040000;
+    let mut size = metadata.len();
+    let mut link_target = None;
+    let mut target_ok = true;
+    if mode & 0o170000 == 0o120000 {
+        link_target = Some(sftp.read_link(&file_path).await.unwrap_or_default());
+        // stat идёт по ссылке: тип и размер — цели
+        match sftp.metadata(&file_path).await {
+            Ok(target) => {
+                let target_mode = target.permissions.unwrap_or(0);
+                is_dir = target_mode & 0o170000 == 0o040000;
+                size = target.len();
+            }
+            Err(_) => target_ok = false,
+        }
+    }
+    let modified = metadata.modified().ok().and_then(|t| {
+        t.duration_since(std::time::UNIX_EPOCH)
+            .ok()
+            .map(|d| d.as_secs())
+    });
+    SftpEntry {
+        name,
+        path: file_path,
+        is_dir,
+        size,
+        modified,
+        mode: metadata.permissions,
+        uid: metadata.uid,
+        gid: metadata.gid,
+        link_target,
+        target_ok,
+    }
+}
+
+/// Сколько байт удалённого файла уже лежит локально (0 — нечего продолжать).
+async fn partial_download_offset(
+    sftp: &russh_sftp::client::SftpSession,
+    remote: &str,
+    local: &str,
+    progress: &TransferState,
+) -> u64 {
+    let existing = match tokio::fs::metadata(local).await {
+        Ok(meta) if meta.is_file() => meta.len(),
+        _ => return 0,
+    };
+    let mut total = progress.total.load(Ordering::Relaxed);
+    if total == 0 {
+        total = sftp.metadata(remote).await.map(|m| m.len()).unwrap_or(0);
+        progress.total.store(total, Ordering::Relaxed);
+    }
+    if existing < total {
+        existing
+    } else {
+        0
+    }
+}
+
+async fn local_stamp(path: &Path) -> Option<FileStamp> {
+    let meta = tokio::fs::metadata(path).await.ok()?;
+    let modified = meta
+        .modified()
+        .ok()
+        .and_then(|t| t.duration_since(std::time::UNIX_EPOCH).ok())
+        .map(|d| d.as_secs());
+    meta.is_file().then_some(FileStamp {
+        size: meta.len(),
+        modified,
+    })
+}
+
+async fn remote_stamp(sftp: &russh_sftp::client::SftpSession, path: &str) -> Option<FileStamp> {
+    let meta = sftp.metadata(path).await.ok()?;
+    (!meta.is_dir()).then_some(FileStamp {
+        size: meta.len(),
+        modified: meta.mtime.map(u64::from),
+    })
+}
+
+/// "name.ext" → "name (1).ext", "name (2).ext"...
+fn numbered_name(name: &str, n: u32) -> String {
+    match name.rsplit_once('.') {
+        Some((stem, ext)) if !stem.is_empty() => format!("{} ({}).{}", stem, n, ext),
+        _ => format!("{} ({})", name, n),
+    }
+}
+
+/// Первое свободное имя рядом с локальным файлом.
+async fn free_local_name(path: &Path) -> String {
+    let name = local_file_name(path);
+    for n in 1..100 {
+        let candidate = numbered_name(&name, n);
+        if !tokio::fs::try_exists(path.with_file_name(&candidate))
+            .await
+            .unwrap_or(false)
+        {
+            return candidate;
+        }
+    }
+    numbered_name(&name, 100)
+}
+
+async fn free_remote_name(sftp: &russh_sftp::client::SftpSession, path: &str) -> String {
+    let parent = remote_parent(path);
+    let name = path.rsplit('/').next().unwrap_or(path);
+    for n in 1..100 {
+        let candidate = numbered_name(name, n);
+        if !sftp
+            .try_exists(remote_join(&parent, &candidate))
+            .await
+            .unwrap_or(false)
+        {
+            return candidate;
+        }
+    }
+    numbered_name(name, 100)
+}
+
+/// "/a/b/c" → "/a/b", "/c" → "/"
+fn remote_parent(path: &str) -> String {
+    match path.trim_end_matches('/').rsplit_once('/') {
+        Some(("", _)) | None => "/".to_string(),
+        Some((parent, _)) => parent.to_string(),
+    }
+}
+
+/// Сколько байт локального файла уже залито на сервер (0 — нечего продолжать).
+async fn partial_upload_offset(
+    sftp: &russh_sftp::client::SftpSession,
+    local: &str,
+    remote: &str,
+) -> u64 {
+    let size = match tokio::fs::metadata(local).await {
+        Ok(meta) => meta.len(),
+        Err(_) => return 0,
+    };
+    let existing = match sftp.metadata(remote).await {
+        Ok(meta) if !meta.is_dir() => meta.len(),
+        _ => return 0,
+    };
+    if existing < size {
+        existing
+    } else {
+        0
+    }
+}
+
+/// offset > 0 — докачка: локальный файл дописывается, удалённый читается с offset.
+async fn download_chunked(
+    reader: &Arc<russh_sftp::client::RawSftpSession>,
+    remote: &str,
+    local: &str,
+    progress: &TransferState,
+    offset: u64,
+    limiter: &RateLimiter,
+) -> Result<(), Box<dyn std::error::Error + Send + Sync>> {
+    use tokio::io::{AsyncSeekExt, AsyncWriteExt};
+
+    let handle = reader
+        .open(
+            remote,
+            russh_sftp::protocol::OpenFlags::READ,
+            russh_sftp::protocol::FileAttributes::default(),
+        )
+        .await
+        .map_err(|e| format!("open remote '{}': {}", remote, e))?
+        .handle;
+
+    // Для одиночного файла total — его размер; у папки total уже больше
+    let file_size = match reader.fstat(handle.as_str()).await {
+        Ok(attrs) => attrs.attrs.size.unwrap_or(0),
+        Err(_) => 0,
+    };
+    progress.total.fetch_max(file_size, Ordering::Relaxed);
+    progress.file_total.store(file_size, Ordering::Relaxed);
+
+    let result = async {
+        // Ensure the local parent directory exists
+        if let Some(parent) = std::path::Path::new(local).parent() {
+            tokio::fs::create_dir_all(parent)
+                .await
+                .map_err(|e| format!("create local dir '{}': {}", parent.display(), e))?;
+        }
+
+        let mut local_file = if offset > 0 {
+            let mut file = tokio::fs::OpenOptions::new()
+                .write(true)
+                .open(local)
+                .await
+                .map_err(|e| format!("open local file '{}': {}", local, e))?;
+            // Хвост за offset (если файл успел вырасти) отбрасываем
+            file.set_len(offset).await?;
+            file.seek(std::io::SeekFrom::Start(offset)).await?;
+            file
+        } else {
+            tokio::fs::File::create(local)
+                .await
+                .map_err(|e| format!("create local file '{}': {}", local, e))?
+        };
+
+        progress.transferred.fetch_add(offset, Ordering::Relaxed);
+        progress.file_transferred.store(offset, Ordering::Relaxed);
+        let span = offset..file_size;
+        read_ahead(reader, &handle, remote, &mut local_file, progress, span, limiter).await?;
+        local_file.flush().await?;
+        Ok(())
+    }
+    .await;
+
+    reader.close(handle).await.ok();
+    result
+}
+
+/// Чтение с упреждением с `span.start`: от READ_AHEAD_MIN до READ_AHEAD запросов
+/// в полёте по измеренной скорости, ответы пишутся по порядку смещений.
+/// За `span.end` (известный размер) — по одному запросу, пока сервер не скажет EOF
+/// (файл мог вырасти).
+async fn read_ahead(
+    reader: &Arc<russh_sftp::client::RawSftpSession>,
+    handle: &str,
+    remote: &str,
+    local_file: &mut tokio::fs::File,
+    progress: &TransferState,
+    span: std::ops::Range<u64>,
+    limiter: &RateLimiter,
+) -> Result<(), Box<dyn std::error::Error + Send + Sync>> {
+    use tokio::io::AsyncWriteExt;
+    type ReadTask = tokio::task::JoinHandle<
+        Result<russh_sftp::protocol::Data, russh_sftp::client::error::Error>,
+    >;
+
+    let spawn_read = |at: u64, len: u32| -> ReadTask {
+        let reader = Arc::clone(reader);
+        let handle = handle.to_string();
+        tokio::spawn(async move { reader.read(handle, at, len).await })
+    };
+
+    let mut in_flight: std::collections::VecDeque<(u64, u32, ReadTask)> = Default::default();
+    let mut next = span.start;
+    let mut total_read = span.start;
+    let started = Instant::now();
+    let mut window = READ_AHEAD_MIN;
+    let result = loop {
+        if let Err(e) = progress.check_interrupted() {
+            break Err(e.into());
+        }
+        while in_flight.len() < window && (next < span.end || in_flight.is_empty()) {
+            let len = limiter.chunk_len().min(READ_AHEAD_CHUNK) as u32;
+            in_flight.push_back((next, len, spawn_read(next, len)));
+            next += len as u64;
+        }
+        let Some((at, len, task)) = in_flight.pop_front() else {
+            break Ok(());
+        };
+        let data = match task.await {
+            Ok(Ok(data)) => data.data,
+            Ok(Err(russh_sftp::client::error::Error::Status(status)))
+                if status.status_code == russh_sftp::protocol::StatusCode::Eof =>
+            {
+                break Ok(());
+            }
+            Ok(Err(e)) => {
+                break Err(format!("read remote '{}' at offset {}: {}", remote, at, e).into());
+            }
+            Err(e) => break Err(e.into()),
+        };
+        if data.is_empty() {
+            break Ok(());
+        }
+        let n = data.len();
+        limiter.acquire(n).await;
+        if let Err(e) = local_file.write_all(&data).await {
+            break Err(e.into());
+        }
+        total_read += n as u64;
+        progress.transferred.fetch_add(n as u64, Ordering::Relaxed);
+        progress.file_transferred.store(total_read, Ordering::Relaxed);
+        // Окно — сколько кусков канал отдаёт за READ_AHEAD_BUDGET
+        let rate = (total_read - span.start) as f64 / started.elapsed().as_secs_f64().max(0.001);
+        window = ((rate * READ_AHEAD_BUDGET.as_secs_f64() / READ_AHEAD_CHUNK as f64) as usize)
+            .clamp(READ_AHEAD_MIN, READ_AHEAD);
+        // Сервер отдал меньше (свой лимит на чтение) — остаток куска дочитываем первым
+        if (n as u32) < len {
+            let (rest_at, rest_len) = (at + n as u64, len - n as u32);
+            in_flight.push_front((rest_at, rest_len, spawn_read(rest_at, rest_len)));
+        }
+    };
+    // Запросы за концом файла или после ошибки больше не нужны
+    for (_, _, task) in in_flight {
+        task.abort();
+    }
+    result
+}
+
+/// offset > 0 — дозаливка: удалённый файл не обрезается, пишем с offset.
+/// mode — права нового файла (setstat после заливки); при докачке не меняются.
+async fn upload_chunked(
+    sftp: &russh_sftp::client::SftpSession,
+    local: &str,
+    remote: &str,
+    progress: &TransferState,
+    offset: u64,
+    mode: Option<u32>,
+    limiter: &RateLimiter,
+) -> Result<(), Box<dyn std::error::Error + Send + Sync>> {
+    use tokio::io::{AsyncReadExt, AsyncSeekExt, AsyncWriteExt};
+
+    let mut local_file = tokio::fs::File::open(local)
+        .await
+        .map_err(|e| format!("open local '{}': {}", local, e))?;
+    let meta = local_file.metadata().await?;
+    let file_size = meta.len();
+    // Для одиночного файла total — его размер; у папки total уже больше
+    progress.total.fetch_max(file_size, Ordering::Relaxed);
+    progress.file_total.store(file_size, Ordering::Relaxed);
+    progress.file_transferred.store(0, Ordering::Relaxed);
+
+    // Права ставим только новому файлу; у перезаписанного остаются его собственные
+    let created = offset == 0 && !sftp.try_exists(remote).await.unwrap_or(false);
+    let mut remote_file = if offset > 0 {
+        let mut file = sftp
+            .open_with_flags(remote, russh_sftp::protocol::OpenFlags::WRITE)
+            .await
+            .map_err(|e| format!("open remote '{}': {}", remote, e))?;
+        file.seek(std::io::SeekFrom::Start(offset))
+            .await
+            .map_err(|e| format!("seek remote '{}' to {}: {}", remote, offset, e))?;
+        local_file.seek(std::io::SeekFrom::Start(offset)).await?;
+        file
+    } else {
+        sftp.create(remote)
+            .await
+            .map_err(|e| format!("create remote '{}': {}", remote, e))?
+    };
+
+    let mut buf = vec![0u8; CHUNK_SIZE];
+    let mut total_written: u64 = offset;
+    progress.transferred.fetch_add(offset, Ordering::Relaxed);
+    progress.file_transferred.store(offset, Ordering::Relaxed);
+
+    loop {
+        progress.check_interrupted()?;
+        let len = limiter.chunk_len();
+        let n = local_file.read(&mut buf[..len]).await?;
+        if n == 0 {
+            break;
+        }
+        limiter.acquire(n).await;
+        remote_file
+            .write_all(&buf[..n])
+            .await
+            .map_err(|e| format!("write remote '{}' at offset {}: {}", remote, total_written, e))?;
+        total_written += n as u64;
+        progress.transferred.fetch_add(n as u64, Ordering::Relaxed);
+        progress.file_transferred.store(total_written, Ordering::Relaxed);
+    }
+
+    remote_file.shutdown().await?;
+    if created {
+        apply_remote_mode(sftp, remote, mode, progress).await;
+    }
+    Ok(())
+}
+
+/// Содержимое локальной папки; пути относительные, через '/'.
+struct LocalTree {
+    /// Подпапки — родители раньше детей
+    dirs: Vec<String>,
+    files: Vec<LocalFile>,
+}
+
+struct LocalFile {
+    path: PathBuf,
+    rel: String,
+    size: u64,
+    /// mtime, unix-время (для синхронизации)
+    modified: u64,
+}
+
+/// Обход локальной папки. Симлинки на папки не обходим (защита от циклов),
+/// симлинки на файлы — считаем файлами.
+fn walk_local_dir(root: &Path) -> std::io::Result<LocalTree> {
+    let mut dirs = Vec::new();
+    let mut files = Vec::new();
+    let mut stack = vec![(root.to_path_buf(), String::new())];
+
+    while let Some((dir, rel)) = stack.pop() {
+        let mut entries: Vec<_> = std::fs::read_dir(&dir)?.collect::<Result<_, _>>()?;
+        entries.sort_by_key(|e| e.file_name());
+        for entry in entries {
+            let name = entry.file_name().to_string_lossy().to_string();
+            let child_rel = if rel.is_empty() {
+                name
+            } else {
+                format!("{}/{}", rel, name)
+            };
+            let file_type = entry.file_type()?;
+            if file_type.is_dir() {
+                dirs.push(child_rel.clone());
+                stack.push((entry.path(), child_rel));
+            } else {
+                let meta = std::fs::metadata(entry.path())?;
+                if meta.is_file() {
+                    let modified = meta
+                        .modified()
+                        .ok()
+                        .and_then(|t| t.duration_since(std::time::UNIX_EPOCH).ok())
+                        .map_or(0, |d| d.as_secs());
+                    files.push(LocalFile {
+                        path: entry.path(),
+                        rel: child_rel,
+                        size: meta.len(),
+                        modified,
+                    });
+                }
+            }
+        }
+    }
+
+    Ok(LocalTree { dirs, files })
+}
+
+/// Обход на блокирующем пуле: большая папка не держит поток воркера.
+async fn walk_local_dir_async(root: &Path) -> std::io::Result<LocalTree> {
+    let root = root.to_path_buf();
+    tokio::task::spawn_blocking(move || walk_local_dir(&root))
+        .await
+        .map_err(std::io::Error::other)?
+}
+
+/// statvfs@openssh.com на папке назначения: `need` байт не поместится — SpaceShortage
+/// до начала записи. Без расширения и после [upload anyway] не проверяем.
+async fn check_free_space(
+    sftp: &russh_sftp::client::SftpSession,
+    dir: &str,
+    need: u64,
+    progress: &TransferState,
+) -> Result<(), Box<dyn std::error::Error + Send + Sync>> {
+    if need == 0 || progress.space_confirmed.load(Ordering::Relaxed) {
+        return Ok(());
+    }
+    if let Ok(Some(st)) = sftp.fs_info(dir.to_string()).await {
+        let avail = st.blocks_avail * st.fragment_size;
+        if need > avail {
+            return Err(Box::new(SpaceShortage {
+                dir: dir.to_string(),
+                need,
+                avail,
+            }));
+        }
+    }
+    Ok(())
+}
+
+fn space_shortage(
+    result: &Result<(), Box<dyn std::error::Error + Send + Sync>>,
+) -> Option<SpaceShortage> {
+    result.as_ref().err()?.downcast_ref::<SpaceShortage>().cloned()
+}
+
+/// mode ставится только созданной папке; существующая остаётся как есть.
+async fn ensure_remote_dir(
+    sftp: &russh_sftp::client::SftpSession,
+    path: &str,
+    mode: Option<u32>,
+    progress: &TransferState,
+) -> Result<(), Box<dyn std::error::Error + Send + Sync>> {
+    if sftp.try_exists(path).await.unwrap_or(false) {
+        return Ok(());
+    }
+    sftp.create_dir(path)
+        .await
+        .map_err(|e| format!("mkdir remote '{}': {}", path, e))?;
+    apply_remote_mode(sftp, path, mode, progress).await;
+    Ok(())
+}
+
+/// Права внутри передачи: не вышло (чужой владелец, сервер без setstat) — только
+/// пометка в итоге, файл уже на месте.
+async fn apply_remote_mode(
+    sftp: &russh_sftp::client::SftpSession,
+    path: &str,
+    mode: Option<u32>,
+    progress: &TransferState,
+) {
+    if set_remote_mode(sftp, path, mode).await.is_err() {
+        progress.chmod_failed.fetch_add(1, Ordering::Relaxed);
+    }
+}
+
+/// setstat только с битами прав; None — ничего не делаем.
+async fn set_remote_mode(
+    sftp: &russh_sftp::client::SftpSession,
+    path: &str,
+    mode: Option<u32>,
+) -> Result<(), Box<dyn std::error::Error + Send + Sync>> {
+    let Some(mode) = mode else {
+        return Ok(());
+    };
+    let attrs = russh_sftp::protocol::FileAttributes {
+        permissions: Some(mode & 0o7777),
+        ..russh_sftp::protocol::FileAttributes::empty()
+    };
+    sftp.set_metadata(path, attrs)
+        .await
+        .map_err(|e| format!("chmod {:04o} '{}': {}", mode, path, e))?;
+    Ok(())
+}
+
+/// Права локального файла или папки (на Windows их нет).
+fn local_mode(path: &Path) -> Option<u32> {
+    #[cfg(unix)]
+    {
+        use std::os::unix::fs::PermissionsExt;
+        std::fs::metadata(path).ok().map(|m| m.permissions().mode())
+    }
+    #[cfg(not(unix))]
+    {
+        let _ = path;
+        None
+    }
+}
+
+/// Заливка папки поверх существующей: суммы по источнику и по уже лежащим на
+/// сервере файлам, сколько байт можно не заливать заново и сколько файлов
+/// заменится. Нет ни одного совпадения — None.
+async fn upload_dir_conflict(
+    sftp: &russh_sftp::client::SftpSession,
+    local: &str,
+    remote: &str,
+) -> Option<(FileStamp, FileStamp, u64, u64)> {
+    if !sftp.try_exists(remote).await.unwrap_or(false) {
+        return None;
+    }
+    let tree = walk_local_dir_async(Path::new(local)).await.ok()?;
+    let remote = remote.trim_end_matches('/');
+    let mut source = FileStamp {
+        size: 0,
+        modified: None,
+    };
+    let mut existing = source;
+    let (mut offset, mut files) = (0, 0);
+    for file in &tree.files {
+        source.size += file.size;
+        source.modified = source.modified.max(Some(file.modified));
+        let Some(stamp) = remote_stamp(sftp, &format!("{}/{}", remote, file.rel)).await else {
+            continue;
+        };
+        files += 1;
+        existing.size += stamp.size;
+        existing.modified = existing.modified.max(stamp.modified);
+        if stamp.size <= file.size {
+            offset += stamp.size;
+        }
+    }
+    (files > 0).then_some((source, existing, offset, files))
+}
+
+/// resume: файлы того же размера пропускаются, меньшие — докачиваются.
+async fn upload_dir_recursive(
+    sftp: &russh_sftp::client::SftpSession,
+    local: &str,
+    remote: &str,
+    progress: &TransferState,
+    resume: bool,
+    perms: UploadPermissions,
+    limiter: &RateLimiter,
+) -> Result<(), Box<dyn std::error::Error + Send + Sync>> {
+    let tree = walk_local_dir_async(Path::new(local))
+        .await
+        .map_err(|e| format!("read local dir '{}': {}", local, e))?;
+    progress
+        .total
+        .store(tree.files.iter().map(|f| f.size).sum(), Ordering::Relaxed);
+    progress.files_total.store(tree.files.len() as u64, Ordering::Relaxed);
+
+    let remote = remote.trim_end_matches('/');
+    let need = progress.total.load(Ordering::Relaxed);
+    check_free_space(sftp, &remote_parent(remote), need, progress).await?;
+    let root = Path::new(local);
+    ensure_remote_dir(sftp, remote, perms.dir_mode(local_mode(root)), progress).await?;
+    for dir in &tree.dirs {
+        let mode = perms.dir_mode(local_mode(&root.join(dir)));
+        ensure_remote_dir(sftp, &format!("{}/{}", remote, dir), mode, progress).await?;
+    }
+
+    for file in &tree.files {
+        *progress.current.lock() = file.rel.clone();
+        let remote_file = format!("{}/{}", remote, file.rel);
+        let local_file = file.path.to_string_lossy();
+        let mode = perms.file_mode(local_mode(&file.path));
+        let existing = match resume {
+            true => remote_stamp(sftp, &remote_file).await.map(|stamp| stamp.size),
+            false => None,
+        };
+        match existing {
+            Some(size) if size == file.size => {
+                progress.transferred.fetch_add(size, Ordering::Relaxed);
+            }
+            existing => {
+                let offset = existing.filter(|&size| size < file.size).unwrap_or(0);
+                upload_chunked(sftp, &local_file, &remote_file, progress, offset, mode, limiter)
+                    .await?;
+            }
+        }
+        progress.files_done.fetch_add(1, Ordering::Relaxed);
+    }
+    Ok(())
+}
+
+fn local_file_name(path: &Path) -> String {
+    path.file_name()
+        .unwrap_or_default()
+        .to_string_lossy()
+        .to_string()
+}
+
+pub(crate) fn format_size(bytes: u64) -> String {
+    if bytes < 1024 {
+        format!("{}B", bytes)
+    } else if bytes < 1024 * 1024 {
+        format!("{:.1}K", bytes as f64 / 1024.0)
+    } else if bytes < 1024 * 1024 * 1024 {
+        format!("{:.1}M", bytes as f64 / (1024.0 * 1024.0))
+    } else {
+        format!("{:.2}G", bytes as f64 / (1024.0 * 1024.0 * 1024.0))
+    }
+}
+
+/// Фильтр имён: с '*' или '?' — glob по всему имени, иначе подстрока.
+/// Регистр не учитывается; пустой фильтр пропускает всё.
+fn name_matches(filter: &str, name: &str) -> bool {
+    let filter = filter.trim().to_lowercase();
+    let name = name.to_lowercase();
+    if filter.contains(['*', '?']) {
+        let pattern: Vec<char> = filter.chars().collect();
+        let name: Vec<char> = name.chars().collect();
+        glob_match(&pattern, &name)
+    } else {
+        name.contains(&filter)
+    }
+}
+
+/// '*' — любая последовательность, '?' — один символ. Жадный перебор с откатом
+/// к последней звёздочке — без рекурсии и экспоненты.
+fn glob_match(pattern: &[char], name: &[char]) -> bool {
+    let (mut p, mut n) = (0, 0);
+    let mut star: Option<(usize, usize)> = None;
+    while n < name.len() {
+        match pattern.get(p) {
+            Some('*') => {
+                star = Some((p, n));
+                p += 1;
+            }
+            Some(&c) if c == '?' || c == name[n] => {
+                p += 1;
+                n += 1;
+            }
+            _ => match star {
+                Some((sp, sn)) => {
+                    p = sp + 1;
+                    n = sn + 1;
+                    star = Some((sp, sn + 1));
+                }
+                None => return false,
+            },
+        }
+    }
+    pattern[p..].iter().all(|&c| c == '*')
+}
+
+fn is_archive_name(name: &str) -> bool {
+    let name = name.to_ascii_lowercase();
+    [
+        ".tar", ".tgz", ".tbz2", ".txz", ".gz", ".bz2", ".xz", ".zst", ".zip", ".7z", ".rar",
+        ".deb", ".rpm", ".jar",
+    ]
+    .iter()
+    .any(|ext| name.ends_with(ext))
+}
+
+/// Значок и цвет строки таблицы, как в цветном `ls`: папки, исполняемые,
+/// симлинки (битые — красным), архивы, картинки, особые файлы.
+fn entry_glyph(entry: &SftpEntry) -> (&'static str, egui::Color32) {
+    let mode = entry.mode.unwrap_or(0);
+    if entry.link_target.is_some() {
+        let icon = if entry.is_dir { "l/" } else { " l" };
+        let color = if entry.target_ok {
+            crate::theme::CYAN
+        } else {
+            crate::theme::RED
+        };
+        return (icon, color);
+    }
+    if entry.is_dir {
+        return ("d/", crate::theme::GREEN_BRIGHT);
+    }
+    match file_type_char(mode) {
+        'p' => return (" |", crate::theme::AMBER),
+        's' => return (" =", crate::theme::AMBER),
+        'c' | 'b' => return (" #", crate::theme::AMBER),
+        _ => {}
+    }
+    if mode & 0o111 != 0 {
+        (" *", crate::theme::AMBER)
+    } else if is_archive_name(&entry.name) {
+        (" z", crate::theme::RED)
+    } else if is_image_name(&entry.name) {
+        (" i", crate::theme::MAGENTA)
+    } else {
+        (" -", crate::theme::GREEN)
+    }
+}
+
+fn is_image_name(name: &str) -> bool {
+    let ext = name.rsplit_once('.').map(|(_, ext)| ext.to_ascii_lowercase());
+    matches!(ext.as_deref(), Some("png" | "jpg" | "jpeg" | "gif"))
+}
+
+/// Читает картинку (с ограничением по размеру) и уменьшает до PREVIEW_MAX_SIDE.
+async fn load_preview(
+    sftp: &russh_sftp::client::SftpSession,
+    path: &str,
+) -> Result<PreviewImage, String> {
+    use tokio::io::AsyncReadExt;
+
+    let file = sftp
+        .open(path)
+        .await
+        .map_err(|e| format!("open '{}': {}", path, e))?;
+    let mut data = Vec::new();
+    file.take(PREVIEW_MAX_BYTES + 1)
+        .read_to_end(&mut data)
+        .await
+        .map_err(|e| format!("read '{}': {}", path, e))?;
+    if data.len() as u64 > PREVIEW_MAX_BYTES {
+        return Err(format!("too large for preview (> {})", format_size(PREVIEW_MAX_BYTES)));
+    }
+    let image = image::load_from_memory(&data).map_err(|e| e.to_string())?;
+    let original = (image.width(), image.height());
+    let image = if original.0 > PREVIEW_MAX_SIDE || original.1 > PREVIEW_MAX_SIDE {
+        image.thumbnail(PREVIEW_MAX_SIDE, PREVIEW_MAX_SIDE)
+    } else {
+        image
+    };
+    let rgba = image.to_rgba8();
+    Ok(PreviewImage {
+        width: rgba.width() as usize,
+        height: rgba.height() as usize,
+        rgba: rgba.into_raw(),
+        original,
+    })
+}
+
+/// Символ типа файла из st_mode, как первая буква в `ls -l`.
+fn file_type_char(mode: u32) -> char {
+    match mode & 0o170000 {
+        0o040000 => 'd',
+        0o120000 => 'l',
+        0o020000 => 'c',
+        0o060000 => 'b',
+        0o010000 => 'p',
+        0o140000 => 's',
+        _ => '-',
+    }
+}
+
+/// Права как в `ls -l`, без символа типа: "rwxr-sr-t".
+fn format_mode(mode: u32) -> String {
+    let mut out = String::with_capacity(9);
+    for (shift, special, special_char) in [(6, 0o4000, 's'), (3, 0o2000, 's'), (0, 0o1000, 't')] {
+        let bits = (mode >> shift) & 0o7;
+        out.push(if bits & 4 != 0 { 'r' } else { '-' });
+        out.push(if bits & 2 != 0 { 'w' } else { '-' });
+        out.push(match (bits & 1 != 0, mode & special != 0) {
+            (true, true) => special_char,
+            (false, true) => special_char.to_ascii_uppercase(),
+            (true, false) => 'x',
+            (false, false) => '-',
+        });
+    }
+    out
+}
+
+/// События на этой машине (журналы, история передач) — в её часовом поясе.
+pub fn format_local(ts: u64, format: &str) -> String {
+    i64::try_from(ts)
+        .ok()
+        .and_then(|secs| chrono::DateTime::from_timestamp(secs, 0))
+        .map(|t| t.with_timezone(&chrono::Local).format(format).to_string())
+        .unwrap_or_default()
+}
+
+/// Даты файлов — в UTC. Без SFTP они берутся из `ls`, чей часовой пояс неизвестен
+/// и читается как UTC (ls_timestamp); Unix-время SFTP показываем так же, чтобы
+/// листинги через SFTP и scp совпадали.
+fn format_timestamp(ts: u64) -> String {
+    let (year, month, day) = civil_from_days((ts / 86400) as i64);
+    format!("{:04}-{:02}-{:02}", year, month, day)
+}
+
+fn format_datetime(ts: u64) -> String {
+    format!("{} {:02}:{:02}", format_timestamp(ts), ts % 86400 / 3600, ts % 3600 / 60)
+}
+
+fn format_exact(ts: u64) -> String {
+    format!("{}:{:02} UTC", format_datetime(ts), ts % 60)
+}
+
+/// Как `ls -l`: за последние полгода — со временем, старше — только дата.
+fn format_modified(ts: u64, now: u64) -> String {
+    const HALF_YEAR: u64 = 183 * 86400;
+    if ts <= now + 86400 && now.saturating_sub(ts) < HALF_YEAR {
+        format_datetime(ts)
+    } else {
+        format_timestamp(ts)
+    }
+}
+
+/// «5 min ago», «3 days ago»; из будущего (часы сервера спешат) — обычная дата.
+fn format_relative(ts: u64, now: u64) -> String {
+    if ts > now + 60 {
+        return format_datetime(ts);
+    }
+    let ago = now.saturating_sub(ts);
+    let (count, unit) = match ago {
+        0..60 => return "just now".to_string(),
+        60..3600 => (ago / 60, "min"),
+        3600..86400 => (ago / 3600, "hour"),
+        86400..2_592_000 => (ago / 86400, "day"),
+        2_592_000..31_536_000 => (ago / 2_592_000, "month"),
+        _ => (ago / 31_536_000, "year"),
+    };
+    let plural = if count == 1 || unit == "min" { "" } else { "s" };
+    format!("{} {}{} ago", count, unit, plural)
+}
@@ -0,0 +1,874 @@
+use super::scp::shell_command;
+use super::*;
+
+// ── sudo ──
+
+/// Метки в выводе sudo: запрос пароля (в stderr) и старт команды (в stdout)
+const SUDO_PROMPT: &str = "[ssherald-sudo-password]";
+const SUDO_READY: &str = "[ssherald-sudo-ready]";
+
+/// sudo спросил пароль, а его нет (или спросил повторно — не подошёл).
+#[derive(Debug)]
+struct SudoPasswordNeeded {
+    wrong: bool,
+}
+
+impl std::fmt::Display for SudoPasswordNeeded {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        f.write_str(if self.wrong { "sudo: wrong password" } else { "sudo: password required" })
+    }
+}
+
+impl std::error::Error for SudoPasswordNeeded {}
+
+/// Операция режима sudo. Нужен пароль — запрос возвращается в UI и придёт снова с паролем;
+/// передача в очереди при этом остаётся «running».
+pub(super) async fn run_sudo(
+    session: Arc<russh::client::Handle<SshHandler>>,
+    req: SftpRequest,
+    password: Option<String>,
+    resp_tx: mpsc::Sender<SftpResponse>,
+    limiter: Arc<RateLimiter>,
+) {
+    let password = password.as_deref();
+    let (result, message) = match &req {
+        SftpRequest::Upload {
+            local,
+            remote,
+            progress,
+            ..
+        } => {
+            let result = sudo_upload(&session, password, local, remote, progress, &limiter).await;
+            (result, format!("OK: sudo put {}", remote))
+        }
+        req => match shell_command(req) {
+            Some((command, message)) => {
+                let result = sudo_exec(&session, password, &command)
+                    .await
+                    .map_err(|e| match e.downcast_ref::<SudoPasswordNeeded>() {
+                        Some(_) => e,
+                        None => format!("{}: {}", message, e).into(),
+                    });
+                (result, format!("OK: sudo {}", message))
+            }
+            None => (Err("not available in sudo mode".into()), String::new()),
+        },
+    };
+    if let Err(e) = &result {
+        if let Some(needed) = e.downcast_ref::<SudoPasswordNeeded>() {
+            let wrong = needed.wrong;
+            let _ = resp_tx.send(SftpResponse::SudoPassword {
+                req: Box::new(req),
+                wrong,
+            });
+            return;
+        }
+    }
+    match req.progress() {
+        Some(progress) => finish_transfer(&resp_tx, progress, result, message),
+        None => {
+            let _ = resp_tx.send(match result {
+                Ok(()) => SftpResponse::Success(message),
+                Err(e) => SftpResponse::Error(e.to_string()),
+            });
+        }
+    }
+}
+
+/// `sudo sh -c` на exec-канале. Пароль отдаём, только если sudo его спросил (NOPASSWD
+/// и свежий тикет — не спросит); канал возвращается, когда команда уже запущена.
+async fn sudo_open(
+    session: &russh::client::Handle<SshHandler>,
+    password: Option<&str>,
+    command: &str,
+) -> Result<russh::Channel<russh::client::Msg>, Box<dyn std::error::Error + Send + Sync>> {
+    let script = format!("echo '{}'; {}", SUDO_READY, command);
+    let command = format!(
+        "sudo -S -p {} sh -c {}",
+        shell_quote(SUDO_PROMPT),
+        shell_quote(&script)
+    );
+    let mut channel = session.channel_open_session().await?;
+    channel.exec(true, command).await?;
+    let mut stdout = String::new();
+    let mut stderr = String::new();
+    let mut sent = false;
+    let error: Box<dyn std::error::Error + Send + Sync> = loop {
+        match channel.wait().await {
+            Some(russh::ChannelMsg::Data { ref data }) => {
+                stdout.push_str(&String::from_utf8_lossy(data));
+                if stdout.contains(SUDO_READY) {
+                    return Ok(channel);
+                }
+            }
+            Some(russh::ChannelMsg::ExtendedData { ref data, .. }) => {
+                stderr.push_str(&String::from_utf8_lossy(data));
+                if !stderr.contains(SUDO_PROMPT) {
+                    continue;
+                }
+                stderr = stderr.replace(SUDO_PROMPT, "");
+                match password {
+                    // Второй запрос — первый пароль не подошёл
+                    Some(_) if sent => break Box::new(SudoPasswordNeeded { wrong: true }),
+                    Some(password) => {
+                        channel.data(format!("{}\n", password).as_bytes()).await?;
+                        sent = true;
+                    }
+                    None => break Box::new(SudoPasswordNeeded { wrong: false }),
+                }
+            }
+            Some(russh::ChannelMsg::ExitStatus { .. })
+            | Some(russh::ChannelMsg::Eof)
+            | Some(russh::ChannelMsg::Close)
+            | None => {
+                let reason = stderr.lines().map(str::trim).find(|l| !l.is_empty());
+                break format!("sudo: {}", reason.unwrap_or("failed")).into();
+            }
+            _ => {}
+        }
+    };
+    channel.close().await.ok();
+    Err(error)
+}
+
+/// Команда целиком через sudo; ошибка — первая строка её вывода.
+async fn sudo_exec(
+    session: &russh::client::Handle<SshHandler>,
+    password: Option<&str>,
+    command: &str,
+) -> Result<(), Box<dyn std::error::Error + Send + Sync>> {
+    let mut channel = sudo_open(session, password, &format!("{} 2>&1", command)).await?;
+    channel.eof().await.ok();
+    let mut output = Vec::new();
+    let mut exit_status = None;
+    while let Some(msg) = channel.wait().await {
+        match msg {
+            russh::ChannelMsg::Data { ref data } => output.extend_from_slice(data),
+            russh::ChannelMsg::ExitStatus { exit_status: code } => exit_status = Some(code),
+            russh::ChannelMsg::Close => break,
+            _ => {}
+        }
+    }
+    channel.close().await.ok();
+    match exit_status {
+        Some(0) => Ok(()),
+        code => {
+            let output = String::from_utf8_lossy(&output);
+            let line = output.lines().map(str::trim).find(|l| !l.is_empty());
+            Err(line.map(str::to_string).unwrap_or_else(|| format!("exit {:?}", code)).into())
+        }
+    }
+}
+
+/// Заливка через sudo: данные идут во временный файл рядом с целью, он получает
+/// владельца и права существующего файла и только целиком заменяет его через mv —
+/// оборванная заливка не портит конфиг root. Без докачки и вопросов о конфликте.
+async fn sudo_upload(
+    session: &russh::client::Handle<SshHandler>,
+    password: Option<&str>,
+    local: &str,
+    remote: &str,
+    progress: &TransferState,
+    limiter: &RateLimiter,
+) -> Result<(), Box<dyn std::error::Error + Send + Sync>> {
+    use tokio::io::AsyncReadExt;
+
+    let mut file = tokio::fs::File::open(local)
+        .await
+        .map_err(|e| format!("open local '{}': {}", local, e))?;
+    let size = file.metadata().await?.len();
+    progress.total.store(size, Ordering::Relaxed);
+    progress.file_total.store(size, Ordering::Relaxed);
+
+    let name = remote.rsplit('/').next().unwrap_or(remote);
+    let tmp = remote_join(
+        &remote_parent(remote),
+        &format!(".{}.ssherald-{}", name, &uuid::Uuid::new_v4().simple().to_string()[..8]),
+    );
+    let (tmp, target) = (shell_quote(&tmp), shell_quote(remote));
+    // Размер проверяется: закрытый канал для cat — обычный EOF, и недокачанный
+    // файл иначе ушёл бы в mv
+    let command = format!(
+        "cat > {tmp} && [ \"$(wc -c < {tmp} | tr -d ' ')\" -eq {size} ] \
+         && {{ [ ! -e {target} ] || {{ chmod --reference={target} {tmp} \
+         && chown --reference={target} {tmp}; }}; }} \
+         && mv -f {tmp} {target} || {{ rm -f {tmp}; exit 1; }}",
+    );
+    let mut channel = sudo_open(session, password, &command).await?;
+    let mut buf = vec![0u8; CHUNK_SIZE];
+    let mut sent: u64 = 0;
+    let streamed: Result<(), Box<dyn std::error::Error + Send + Sync>> = async {
+        loop {
+            progress.check_interrupted()?;
+            let n = file.read(&mut buf[..limiter.chunk_len()]).await?;
+            if n == 0 {
+                break Ok(());
+            }
+            limiter.acquire(n).await;
+            channel.data(&buf[..n]).await?;
+            sent += n as u64;
+            progress.transferred.store(sent, Ordering::Relaxed);
+            progress.file_transferred.store(sent, Ordering::Relaxed);
+        }
+    }
+    .await;
+    if let Err(e) = streamed {
+        channel.close().await.ok();
+        return Err(e);
+    }
+    channel.eof().await.ok();
+    let mut stderr = String::new();
+    let mut exit_status = None;
+    while let Some(msg) = channel.wait().await {
+        match msg {
+            russh::ChannelMsg::ExtendedData { ref data, .. } => {
+                stderr.push_str(&String::from_utf8_lossy(data))
+            }
+            russh::ChannelMsg::ExitStatus { exit_status: code } => exit_status = Some(code),
+            russh::ChannelMsg::Close => break,
+            _ => {}
+        }
+    }
+    channel.close().await.ok();
+    match exit_status {
+        Some(0) => Ok(()),
+        code => Err(format!(
+            "sudo put '{}': {}",
+            remote,
+            stderr.lines().next().map(str::to_string).unwrap_or_else(|| format!("exit {:?}", code))
+        )
+        .into()),
+    }
+}
+
+// ── Archive download ──
+
+/// `tar czf -` в exec-канале, stdout пишется в `local`. Размер архива заранее
+/// неизвестен — total растёт вместе с transferred.
+pub(super) async fn download_archive(
+    session: &russh::client::Handle<SshHandler>,
+    remote: &str,
+    local: &str,
+    progress: &TransferState,
+    limiter: &RateLimiter,
+) -> Result<(), Box<dyn std::error::Error + Send + Sync>> {
+    use tokio::io::AsyncWriteExt;
+
+    let name = remote.trim_end_matches('/').rsplit('/').next().unwrap_or(remote);
+    let command = format!(
+        "tar czf - -C {} -- {}",
+        shell_quote(&remote_parent(remote)),
+        shell_quote(name)
+    );
+    if let Some(parent) = Path::new(local).parent() {
+        tokio::fs::create_dir_all(parent)
+            .await
+            .map_err(|e| format!("create local dir '{}': {}", parent.display(), e))?;
+    }
+    let mut file = tokio::fs::File::create(local)
+        .await
+        .map_err(|e| format!("create local file '{}': {}", local, e))?;
+
+    let mut channel = session.channel_open_session().await?;
+    channel.exec(true, command).await?;
+    let mut stderr = Vec::new();
+    let mut exit_status = None;
+    while let Some(msg) = channel.wait().await {
+        if let Err(e) = progress.check_interrupted() {
+            channel.close().await.ok();
+            return Err(e.into());
+        }
+        match msg {
+            russh::ChannelMsg::Data { ref data } => {
+                limiter.acquire(data.len()).await;
+                file.write_all(data).await?;
+                let done = progress.transferred.fetch_add(data.len() as u64, Ordering::Relaxed);
+                progress.total.fetch_max(done + data.len() as u64, Ordering::Relaxed);
+            }
+            russh::ChannelMsg::ExtendedData { ref data, .. } => stderr.extend_from_slice(data),
+            russh::ChannelMsg::ExitStatus { exit_status: code } => exit_status = Some(code),
+            russh::ChannelMsg::Close => break,
+            _ => {}
+        }
+    }
+    channel.close().await.ok();
+    file.flush().await?;
+    match exit_status {
+        Some(0) => Ok(()),
+        code => {
+            let stderr = String::from_utf8_lossy(&stderr);
+            let reason = stderr.lines().next().unwrap_or("no exit status");
+            Err(format!("tar {} (exit {:?}): {}", remote, code, reason).into())
+        }
+    }
+}
+
+// ── Remote copy ──
+
+/// Каждый источник — в `dest` под своим именем (занято — "name (1)").
+/// Сначала `cp -a` на сервере; нет shell или cp — поток через SFTP.
+pub(super) async fn remote_copy(
+    session: &russh::client::Handle<SshHandler>,
+    sftp: &russh_sftp::client::SftpSession,
+    sources: &[String],
+    dest: &str,
+    progress: &TransferState,
+    limiter: &RateLimiter,
+) -> Result<(), Box<dyn std::error::Error + Send + Sync>> {
+    progress.files_total.store(sources.len() as u64, Ordering::Relaxed);
+    for source in sources {
+        progress.check_interrupted()?;
+        if dest == source || dest.starts_with(&format!("{}/", source.trim_end_matches('/'))) {
+            return Err(format!("can't copy '{}' into itself", source).into());
+        }
+        let name = source.rsplit('/').next().unwrap_or(source);
+        *progress.current.lock() = name.to_string();
+        let mut target = remote_join(dest, name);
+        if sftp.try_exists(&target).await.unwrap_or(false) {
+            target = remote_join(dest, &free_remote_name(sftp, &target).await);
+        }
+
+        let command = format!("cp -a -- {} {}", shell_quote(source), shell_quote(&target));
+        let copied = match exec_interruptible(session, command, progress).await {
+            Ok(code) => code == Some(0),
+            Err(e) => {
+                remove_partial(sftp, &target).await;
+                return Err(e);
+            }
+        };
+        if !copied {
+            // Имя цели было свободным: всё, что там есть, — обрывки неудачного cp
+            remove_partial(sftp, &target).await;
+            let stats = tree_stats_or_file(sftp, source).await?;
+            progress.total.fetch_add(stats.bytes, Ordering::Relaxed);
+            copy_remote_tree(sftp, sftp, source, &target, progress, limiter).await?;
+        }
+        progress.files_done.fetch_add(1, Ordering::Relaxed);
+    }
+    Ok(())
+}
+
+/// [send to]: на другой сервер. Сначала `scp` прямо с этого сервера (если у него
+/// есть ключ к цели — BatchMode не даст зависнуть на пароле), иначе поток через клиент.
+pub(super) async fn send_to(
+    session: &russh::client::Handle<SshHandler>,
+    sftp: &russh_sftp::client::SftpSession,
+    sources: &[String],
+    peer: &SftpPeer,
+    progress: &TransferState,
+    limiter: &RateLimiter,
+) -> Result<(), Box<dyn std::error::Error + Send + Sync>> {
+    let dst = open_sftp(&peer.session)
+        .await
+        .map_err(|e| format!("{}: {}", peer.name, e))?;
+    progress.files_total.store(sources.len() as u64, Ordering::Relaxed);
+    let result = async {
+        for source in sources {
+            progress.check_interrupted()?;
+            let name = source.rsplit('/').next().unwrap_or(source);
+            *progress.current.lock() = name.to_string();
+            let mut target = remote_join(&peer.dir, name);
+            if dst.try_exists(&target).await.unwrap_or(false) {
+                target = remote_join(&peer.dir, &free_remote_name(&dst, &target).await);
+            }
+            let stats = tree_stats_or_file(sftp, source).await?;
+            progress.total.fetch_add(stats.bytes, Ordering::Relaxed);
+
+            // Путь цели scp разбирает по-разному в разных версиях — только простые имена
+            let copied = match &peer.login {
+                Some(login) if scp_plain_path(&target) => {
+                    let command = format!(
+                        "scp -rp -o BatchMode=yes -o ConnectTimeout=10 -P {} -- {} {}",
+                        peer.port,
+                        shell_quote(source),
+                        shell_quote(&format!("{}:{}", login, target))
+                    );
+                    match exec_interruptible(session, command, progress).await {
+                        Ok(code) => code == Some(0),
+                        Err(e) => {
+                            remove_partial(&dst, &target).await;
+                            return Err(e);
+                        }
+                    }
+                }
+                _ => false,
+            };
+            if copied {
+                progress.transferred.fetch_add(stats.bytes, Ordering::Relaxed);
+            } else {
+                // Имя цели было свободным: всё, что там есть, — обрывки неудачного scp
+                remove_partial(&dst, &target).await;
+                copy_remote_tree(sftp, &dst, source, &target, progress, limiter).await?;
+            }
+            progress.files_done.fetch_add(1, Ordering::Relaxed);
+        }
+        Ok(())
+    }
+    .await;
+    dst.close().await.ok();
+    result
+}
+
+/// Убирает недокопированный файл или папку; ошибки не важны — дальше всё равно копия.
+async fn remove_partial(sftp: &russh_sftp::client::SftpSession, path: &str) {
+    match sftp.metadata(path).await {
+        Ok(meta) if meta.is_dir() => {
+            remove_recursive(sftp, path).await.ok();
+        }
+        Ok(_) => {
+            sftp.remove_file(path).await.ok();
+        }
+        Err(_) => {}
+    }
+}
+
+/// Адрес сессии годится для scp с другого сервера: подключение без прокси и jump-хостов,
+/// и не к loopback (localhost — почти всегда локальный проброс порта).
+pub(super) fn is_direct(config: &SessionConfig) -> bool {
+    let host = config.host.trim_start_matches('[').trim_end_matches(']');
+    let loopback = host.eq_ignore_ascii_case("localhost")
+        || host
+            .parse::<std::net::IpAddr>()
+            .is_ok_and(|ip| ip.is_loopback());
+    config.proxy.is_none() && config.jump_hosts.is_empty() && !loopback
+}
+
+/// Путь без пробелов, кавычек и метасимволов — его можно отдать scp как есть.
+/// "user@host" для scp на этот сервер с другого; IPv6 — в скобках. Логин или адрес
+/// с чем-то кроме простых символов (или с '-' в начале) — None, копия пойдёт через клиент.
+pub(super) fn scp_login(username: &str, host: &str) -> Option<String> {
+    let host = host.trim_start_matches('[').trim_end_matches(']');
+    let plain = |s: &str, extra: char| {
+        !s.is_empty()
+            && !s.starts_with('-')
+            && s.chars()
+                .all(|c| c.is_ascii_alphanumeric() || matches!(c, '.' | '_' | '-') || c == extra)
+    };
+    if !plain(username, '-') || !plain(host, ':') {
+        return None;
+    }
+    Some(if host.contains(':') {
+        format!("{}@[{}]", username, host)
+    } else {
+        format!("{}@{}", username, host)
+    })
+}
+
+fn scp_plain_path(path: &str) -> bool {
+    path.chars()
+        .all(|c| c.is_ascii_alphanumeric() || matches!(c, '/' | '.' | '_' | '-' | '+' | ','))
+}
+
+async fn tree_stats_or_file(
+    sftp: &russh_sftp::client::SftpSession,
+    path: &str,
+) -> Result<TreeStats, Box<dyn std::error::Error + Send + Sync>> {
+    let meta = sftp.metadata(path).await?;
+    if meta.is_dir() {
+        tree_stats(sftp, path).await
+    } else {
+        Ok(TreeStats {
+            files: 1,
+            dirs: 0,
+            bytes: meta.len(),
+        })
+    }
+}
+
+/// Копия через SFTP: папки создаются, файлы читаются и пишутся чанками.
+/// Симлинки копируются как файлы, на которые указывают; папки-симлинки пропускаются.
+/// `sftp` — откуда, `dst_sftp` — куда (тот же сервер или другой).
+async fn copy_remote_tree(
+    sftp: &russh_sftp::client::SftpSession,
+    dst_sftp: &russh_sftp::client::SftpSession,
+    from: &str,
+    to: &str,
+    progress: &TransferState,
+    limiter: &RateLimiter,
+) -> Result<(), Box<dyn std::error::Error + Send + Sync>> {
+    if !sftp.metadata(from).await?.is_dir() {
+        return copy_remote_file(sftp, dst_sftp, from, to, progress, limiter).await;
+    }
+    let mut stack = vec![(from.to_string(), to.to_string())];
+    while let Some((src_dir, dst_dir)) = stack.pop() {
+        ensure_remote_dir(dst_sftp, &dst_dir, None, progress).await?;
+        for entry in list_dir_async(sftp, &src_dir).await? {
+            let target = remote_join(&dst_dir, &entry.name);
+            if !entry.is_dir {
+                copy_remote_file(sftp, dst_sftp, &entry.path, &target, progress, limiter).await?;
+            } else if entry.link_target.is_none() {
+                stack.push((entry.path, target));
+            }
+        }
+    }
+    Ok(())
+}
+
+async fn copy_remote_file(
+    sftp: &russh_sftp::client::SftpSession,
+    dst_sftp: &russh_sftp::client::SftpSession,
+    from: &str,
+    to: &str,
+    progress: &TransferState,
+    limiter: &RateLimiter,
+) -> Result<(), Box<dyn std::error::Error + Send + Sync>> {
+    use tokio::io::{AsyncReadExt, AsyncWriteExt};
+
+    let mut src = sftp
+        .open(from)
+        .await
+        .map_err(|e| format!("open remote '{}': {}", from, e))?;
+    let meta = src.metadata().await?;
+    let mut dst = dst_sftp
+        .create(to)
+        .await
+        .map_err(|e| format!("create remote '{}': {}", to, e))?;
+    *progress.current.lock() = from.rsplit('/').next().unwrap_or(from).to_string();
+    progress.file_total.store(meta.len(), Ordering::Relaxed);
+    progress.file_transferred.store(0, Ordering::Relaxed);
+
+    let mut buf = vec![0u8; CHUNK_SIZE];
+    let mut copied: u64 = 0;
+    loop {
+        progress.check_interrupted()?;
+        let len = limiter.chunk_len();
+        let n = src
+            .read(&mut buf[..len])
+            .await
+            .map_err(|e| format!("read remote '{}' at offset {}: {}", from, copied, e))?;
+        if n == 0 {
+            break;
+        }
+        limiter.acquire(n).await;
+        dst.write_all(&buf[..n])
+            .await
+            .map_err(|e| format!("write remote '{}' at offset {}: {}", to, copied, e))?;
+        copied += n as u64;
+        progress.transferred.fetch_add(n as u64, Ordering::Relaxed);
+        progress.file_transferred.store(copied, Ordering::Relaxed);
+    }
+    dst.shutdown().await?;
+    src.shutdown().await.ok();
+
+    // Права — как у оригинала (cp -a сохранил бы и их)
+    if let Some(permissions) = meta.permissions {
+        let attrs = russh_sftp::protocol::FileAttributes {
+            permissions: Some(permissions & 0o7777),
+            ..russh_sftp::protocol::FileAttributes::empty()
+        };
+        dst_sftp.set_metadata(to, attrs).await.ok();
+    }
+    Ok(())
+}
+
+/// `df -P -k`: вторая строка — "fs 1024-blocks used available capacity mount".
+pub(super) async fn df_usage(
+    session: &russh::client::Handle<SshHandler>,
+    path: &str,
+) -> Option<DiskUsage> {
+    let command = format!("df -P -k {}", shell_quote(path));
+    let (output, _) = exec_output(session, command).await.ok()?;
+    let line = output.lines().nth(1)?;
+    let fields: Vec<&str> = line.split_whitespace().collect();
+    // Имя файловой системы может содержать пробелы — считаем с конца
+    let n = fields.len();
+    if n < 6 {
+        return None;
+    }
+    let total: u64 = fields[n - 5].parse().ok()?;
+    let avail: u64 = fields[n - 3].parse().ok()?;
+    Some(DiskUsage {
+        total: total * 1024,
+        avail: avail * 1024,
+    })
+}
+
+// ── Recursive delete ──
+
+pub(super) async fn run_tree_stats(
+    session: Arc<russh::client::Handle<SshHandler>>,
+    id: u64,
+    path: String,
+    resp_tx: mpsc::Sender<SftpResponse>,
+) {
+    let result = async {
+        let sftp = open_sftp(&session).await?;
+        let stats = tree_stats(&sftp, &path).await;
+        sftp.close().await.ok();
+        stats
+    };
+    let result = result.await.map_err(|e| e.to_string());
+    let _ = resp_tx.send(SftpResponse::TreeStats(id, result));
+}
+
+pub(super) async fn run_remove_all(
+    session: Arc<russh::client::Handle<SshHandler>>,
+    path: String,
+    resp_tx: mpsc::Sender<SftpResponse>,
+) {
+    let result = async {
+        let sftp = open_sftp(&session).await?;
+        let removed = remove_recursive(&sftp, &path).await;
+        sftp.close().await.ok();
+        removed
+    };
+    let response = match result.await {
+        Ok(count) => SftpResponse::Success(format!("OK: rm -r {} ({} entries)", path, count)),
+        Err(e) => SftpResponse::Error(format!("rm -r '{}': {}", path, e)),
+    };
+    let _ = resp_tx.send(response);
+}
+
+/// Симлинки на папки считаются файлами — удаляется ссылка, не цель.
+async fn tree_stats(
+    sftp: &russh_sftp::client::SftpSession,
+    path: &str,
+) -> Result<TreeStats, Box<dyn std::error::Error + Send + Sync>> {
+    let mut stats = TreeStats::default();
+    let mut stack = vec![path.to_string()];
+    while let Some(dir) = stack.pop() {
+        stats.dirs += 1;
+        for entry in list_dir_async(sftp, &dir).await? {
+            if entry.is_dir && entry.link_target.is_none() {
+                stack.push(entry.path);
+            } else {
+                stats.files += 1;
+                stats.bytes += entry.size;
+            }
+        }
+    }
+    Ok(stats)
+}
+
+/// Файлы — по ходу обхода, папки — в обратном порядке (дети раньше родителей).
+async fn remove_recursive(
+    sftp: &russh_sftp::client::SftpSession,
+    path: &str,
+) -> Result<u64, Box<dyn std::error::Error + Send + Sync>> {
+    let mut removed = 0;
+    let mut stack = vec![path.to_string()];
+    let mut dirs = Vec::new();
+    while let Some(dir) = stack.pop() {
+        for entry in list_dir_async(sftp, &dir).await? {
+            if entry.is_dir && entry.link_target.is_none() {
+                stack.push(entry.path);
+            } else {
+                sftp.remove_file(&entry.path)
+                    .await
+                    .map_err(|e| format!("rm '{}': {}", entry.path, e))?;
+                removed += 1;
+            }
+        }
+        dirs.push(dir);
+    }
+    for dir in dirs.iter().rev() {
+        sftp.remove_dir(dir)
+            .await
+            .map_err(|e| format!("rmdir '{}': {}", dir, e))?;
+        removed += 1;
+    }
+    Ok(removed)
+}
+
+// ── Recursive search ──
+
+/// Сначала `find` на сервере (быстро, один запрос), если его нет или он без
+/// -printf (busybox) — обход дерева через SFTP.
+pub(super) async fn run_search(
+    session: Arc<russh::client::Handle<SshHandler>>,
+    id: u64,
+    query: SearchQuery,
+    cancel: Arc<AtomicBool>,
+    resp_tx: mpsc::Sender<SftpResponse>,
+) {
+    let update = match search_with_find(&session, id, &query, &cancel, &resp_tx).await {
+        Ok(Some((found, false))) => SearchUpdate::Done(format!("{} found (find)", found)),
+        Ok(Some((found, true))) => SearchUpdate::Done(format!(
+            "{} found (find, some folders unreadable)",
+            found
+        )),
+        _ => {
+            let walk = async {
+                let sftp = open_sftp(&session).await?;
+                let result = search_walk(&sftp, id, &query, &cancel, &resp_tx).await;
+                sftp.close().await.ok();
+                result
+            };
+            match walk.await {
+                Ok(summary) => SearchUpdate::Done(summary),
+                Err(e) => SearchUpdate::Failed(e.to_string()),
+            }
+        }
+    };
+    let _ = resp_tx.send(SftpResponse::Search(id, update));
+}
+
+/// Ok(None) — find недоступен, нужен обход через SFTP. Иначе — сколько найдено
+/// и были ли ошибки в подпапках (код 1: обычно «Permission denied»).
+async fn search_with_find(
+    session: &russh::client::Handle<SshHandler>,
+    id: u64,
+    query: &SearchQuery,
+    cancel: &AtomicBool,
+    resp_tx: &mpsc::Sender<SftpResponse>,
+) -> Result<Option<(usize, bool)>, Box<dyn std::error::Error + Send + Sync>> {
+    // find не знает `--`: корень с минусом в начале прочитал бы как опцию
+    let root = if query.root.starts_with('-') {
+        format!("./{}", query.root)
+    } else {
+        query.root.clone()
+    };
+    let mut command = format!(
+        "find {} -mindepth 1 -maxdepth {}",
+        shell_quote(&root),
+        query.max_depth
+    );
+    if !query.pattern.is_empty() {
+        let glob = if query.pattern.contains(['*', '?']) {
+            query.pattern.clone()
+        } else {
+            format!("*{}*", query.pattern)
+        };
+        command.push_str(&format!(" -iname {}", shell_quote(&glob)));
+    }
+    command.push_str(" -printf '%y\\t%s\\t%T@\\t%p\\n'");
+
+    let mut channel = session.channel_open_session().await?;
+    channel.exec(true, command).await?;
+
+    let mut pending = Vec::new();
+    let mut batch = Vec::new();
+    let mut found = 0;
+    let mut exit_status = None;
+    // Начало stderr: по нему отличаем «нет -printf» от нечитаемых подпапок
+    let mut stderr = Vec::new();
+    let mut tick = tokio::time::interval(Duration::from_millis(200));
+    loop {
+        let msg = tokio::select! {
+            msg = channel.wait() => match msg {
+                Some(msg) => msg,
+                None => break,
+            },
+            _ = tick.tick() => {
+                if cancel.load(Ordering::Relaxed) {
+                    channel.signal(russh::Sig::TERM).await.ok();
+                    break;
+                }
+                continue;
+            }
+        };
+        match msg {
+            russh::ChannelMsg::Data { ref data } => {
+                pending.extend_from_slice(data);
+                while let Some(pos) = pending.iter().position(|&b| b == b'\n') {
+                    let line: Vec<u8> = pending.drain(..=pos).collect();
+                    let line = String::from_utf8_lossy(&line[..line.len() - 1]).into_owned();
+                    if let Some(entry) = parse_find_line(&line).filter(|e| query.matches(e)) {
+                        batch.push(entry);
+                        found += 1;
+                    }
+                }
+                if batch.len() >= 200 {
+                    let entries = std::mem::take(&mut batch);
+                    let _ = resp_tx.send(SftpResponse::Search(id, SearchUpdate::Found(entries)));
+                }
+                if found >= SEARCH_MAX_RESULTS || cancel.load(Ordering::Relaxed) {
+                    break;
+                }
+            }
+            russh::ChannelMsg::ExtendedData { ref data, .. } if stderr.len() < 4096 => {
+                stderr.extend_from_slice(data);
+            }
+            russh::ChannelMsg::ExitStatus { exit_status: code } => exit_status = Some(code),
+            // Код возврата приходит после EOF
+            russh::ChannelMsg::Close => break,
+            _ => {}
+        }
+    }
+    channel.close().await.ok();
+
+    // Код 1 и в stderr только нечитаемые подпапки — find отработал, пусть и не везде.
+    // Не понял -printf (busybox) или find нет вовсе — ищем обходом через SFTP.
+    let stderr = String::from_utf8_lossy(&stderr).to_lowercase();
+    let mut lines: Vec<&str> = stderr.split('\n').collect();
+    lines.pop(); // последняя строка пустая или обрезана
+    let partial = exit_status == Some(1)
+        && lines.iter().all(|line| {
+            line.ends_with("permission denied") || line.ends_with("no such file or directory")
+        });
+    if found == 0
+        && exit_status != Some(0)
+        && !partial
+        && !cancel.load(Ordering::Relaxed)
+    {
+        return Ok(None);
+    }
+    if !batch.is_empty() {
+        let _ = resp_tx.send(SftpResponse::Search(id, SearchUpdate::Found(batch)));
+    }
+    Ok(Some((found, partial)))
+}
+
+/// "f\t1234\t1700000000.123\t/path/name" (find -printf '%y\t%s\t%T@\t%p')
+fn parse_find_line(line: &str) -> Option<SftpEntry> {
+    let mut fields = line.splitn(4, '\t');
+    let kind = fields.next()?;
+    let size = fields.next()?.parse().ok()?;
+    let modified = fields.next()?.split('.').next()?.parse().ok();
+    let path = fields.next()?.to_string();
+    let name = path.rsplit('/').next().unwrap_or(&path).to_string();
+    Some(SftpEntry {
+        name,
+        path,
+        is_dir: kind == "d",
+        size,
+        modified,
+        mode: None,
+        uid: None,
+        gid: None,
+        link_target: None,
+        target_ok: true,
+    })
+}
+
+/// Обход в ширину через SFTP. Симлинки на папки не обходим (циклы),
+/// нечитаемые подпапки пропускаем.
+async fn search_walk(
+    sftp: &russh_sftp::client::SftpSession,
+    id: u64,
+    query: &SearchQuery,
+    cancel: &AtomicBool,
+    resp_tx: &mpsc::Sender<SftpResponse>,
+) -> Result<String, Box<dyn std::error::Error + Send + Sync>> {
+    let mut queue = std::collections::VecDeque::from([(query.root.clone(), 0u32)]);
+    let mut dirs = 0;
+    let mut found = 0;
+    while let Some((dir, depth)) = queue.pop_front() {
+        if cancel.load(Ordering::Relaxed) || found >= SEARCH_MAX_RESULTS {
+            break;
+        }
+        if dirs >= SEARCH_MAX_DIRS {
+            return Ok(format!("{} found, stopped after {} dirs", found, dirs));
+        }
+        let entries = match list_dir_async(sftp, &dir).await {
+            Ok(entries) => entries,
+            Err(e) if dirs == 0 => return Err(e),
+            Err(_) => continue,
+        };
+        dirs += 1;
+        let mut batch = Vec::new();
+        for entry in entries {
+            if entry.is_dir && entry.link_target.is_none() && depth + 1 < query.max_depth {
+                queue.push_back((entry.path.clone(), depth + 1));
+            }
+            if query.matches(&entry) {
+                batch.push(entry);
+            }
+        }
+        if !batch.is_empty() {
+            found += batch.len();
+            let _ = resp_tx.send(SftpResponse::Search(id, SearchUpdate::Found(batch)));
+        }
+    }
+    Ok(format!("{} found in {} dirs", found, dirs))
+}
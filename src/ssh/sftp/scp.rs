@@ -0,0 +1,583 @@
+use super::*;
+
+// ── SCP fallback ──
+
+const SCP_UNSUPPORTED: &str = "not available without SFTP";
+
+/// Сервер без sftp-server: листинг через `ls -lan`, файлы по одному через
+/// `scp -f`/`scp -t`, mkdir/rm/mv — командами оболочки. Остальное требует SFTP.
+pub(super) async fn scp_thread_async(
+    session: Arc<russh::client::Handle<SshHandler>>,
+    mut req_rx: tokio::sync::mpsc::UnboundedReceiver<SftpRequest>,
+    resp_tx: &mpsc::Sender<SftpResponse>,
+    limiter: Arc<RateLimiter>,
+) -> Result<(), Box<dyn std::error::Error + Send + Sync>> {
+    while let Some(req) = req_rx.recv().await {
+        match req {
+            SftpRequest::ListStart(last) => {
+                if let Some(path) = last {
+                    if let Ok(entries) = scp_list(&session, &path).await {
+                        let _ = resp_tx.send(SftpResponse::DirListing(path, entries));
+                        continue;
+                    }
+                }
+                let home = match exec_output(&session, "pwd".to_string()).await {
+                    Ok((output, Some(0))) if output.trim().starts_with('/') => {
+                        output.trim().to_string()
+                    }
+                    _ => "/".to_string(),
+                };
+                let response = match scp_list(&session, &home).await {
+                    Ok(entries) => SftpResponse::DirListing(home, entries),
+                    Err(e) => SftpResponse::Error(e.to_string()),
+                };
+                let _ = resp_tx.send(response);
+            }
+            SftpRequest::ListDir(path) => {
+                let response = match scp_list(&session, &path).await {
+                    Ok(entries) => SftpResponse::DirListing(path, entries),
+                    Err(e) => SftpResponse::Error(e.to_string()),
+                };
+                let _ = resp_tx.send(response);
+            }
+            // Без stat — [watch] только перечитывает папку по таймеру
+            SftpRequest::StatDir(path) => {
+                let _ = resp_tx.send(SftpResponse::DirStamp(path, None));
+            }
+            SftpRequest::DiskUsage(path) => {
+                if let Some(usage) = df_usage(&session, &path).await {
+                    let _ = resp_tx.send(SftpResponse::DiskUsage(path, usage));
+                }
+            }
+            req @ (SftpRequest::Download { .. }
+            | SftpRequest::Upload { .. }
+            | SftpRequest::DownloadArchive { .. }) => {
+                tokio::spawn(run_scp_transfer(
+                    Arc::clone(&session),
+                    req,
+                    resp_tx.clone(),
+                    Arc::clone(&limiter),
+                ));
+            }
+            SftpRequest::Sudo { req, password } => {
+                tokio::spawn(run_sudo(
+                    Arc::clone(&session),
+                    *req,
+                    password,
+                    resp_tx.clone(),
+                    Arc::clone(&limiter),
+                ));
+            }
+            req @ (SftpRequest::Mkdir { .. }
+            | SftpRequest::NewFile(_)
+            | SftpRequest::Touch(_)
+            | SftpRequest::Remove(_)
+            | SftpRequest::RemoveAll(_)
+            | SftpRequest::Rename { .. }) => {
+                if let Some((command, message)) = shell_command(&req) {
+                    scp_shell(&session, resp_tx, command, message).await;
+                }
+            }
+            SftpRequest::Search { id, .. } => {
+                let update = SearchUpdate::Failed(SCP_UNSUPPORTED.to_string());
+                let _ = resp_tx.send(SftpResponse::Search(id, update));
+            }
+            SftpRequest::TreeStats { id, .. } => {
+                let _ = resp_tx.send(SftpResponse::TreeStats(id, Err(SCP_UNSUPPORTED.into())));
+            }
+            SftpRequest::SyncCompare { id, .. } => {
+                let _ = resp_tx.send(SftpResponse::SyncPlan(id, Err(SCP_UNSUPPORTED.into())));
+            }
+            SftpRequest::Preview(path) => {
+                let _ = resp_tx.send(SftpResponse::Preview(path, Err(SCP_UNSUPPORTED.into())));
+            }
+            SftpRequest::LoadAccounts => {}
+            // Папки, синхронизация, копии на сервере, права, симлинки
+            req => match req.progress() {
+                Some(progress) => {
+                    finish_transfer(resp_tx, progress, Err(SCP_UNSUPPORTED.into()), String::new())
+                }
+                None => {
+                    let _ = resp_tx.send(SftpResponse::Error(SCP_UNSUPPORTED.to_string()));
+                }
+            },
+        }
+    }
+    Ok(())
+}
+
+/// Операция SFTP как команда оболочки (режим SCP и sudo): команда и подпись для статуса.
+pub(super) fn shell_command(req: &SftpRequest) -> Option<(String, String)> {
+    Some(match req {
+        SftpRequest::Mkdir { path, mode: None } => {
+            (format!("mkdir {}", shell_quote(path)), format!("mkdir {}", path))
+        }
+        SftpRequest::Mkdir {
+            path,
+            mode: Some(mode),
+        } => (
+            format!("mkdir -m {:o} {}", mode & 0o7777, shell_quote(path)),
+            format!("mkdir {}", path),
+        ),
+        // noclobber: существующий файл не трогаем, как EXCLUDE в SFTP
+        SftpRequest::NewFile(path) => (
+            format!("set -C; : > {}", shell_quote(path)),
+            format!("new file {}", path),
+        ),
+        SftpRequest::Touch(path) => {
+            (format!("touch {}", shell_quote(path)), format!("touch {}", path))
+        }
+        SftpRequest::Remove(path) => {
+            let path_q = shell_quote(path);
+            (
+                format!("rm -f {} 2>/dev/null || rmdir {}", path_q, path_q),
+                format!("rm {}", path),
+            )
+        }
+        SftpRequest::RemoveAll(path) => {
+            (format!("rm -rf {}", shell_quote(path)), format!("rm -r {}", path))
+        }
+        SftpRequest::Rename { from, to } => (
+            format!("mv {} {}", shell_quote(from), shell_quote(to)),
+            format!("mv {} -> {}", from, to),
+        ),
+        SftpRequest::Chmod { path, mode } => (
+            format!("chmod {:o} {}", mode & 0o7777, shell_quote(path)),
+            format!("chmod {:o} {}", mode & 0o7777, path),
+        ),
+        SftpRequest::Chown { path, uid, gid } => (
+            format!("chown {}:{} {}", uid, gid, shell_quote(path)),
+            format!("chown {}:{} {}", uid, gid, path),
+        ),
+        SftpRequest::Symlink { link, target } => (
+            format!("ln -s {} {}", shell_quote(target), shell_quote(link)),
+            format!("ln -s {} {}", target, link),
+        ),
+        _ => return None,
+    })
+}
+
+/// Команда оболочки вместо операции SFTP; в ошибке — первая строка вывода.
+async fn scp_shell(
+    session: &russh::client::Handle<SshHandler>,
+    resp_tx: &mpsc::Sender<SftpResponse>,
+    command: String,
+    message: String,
+) {
+    let response = match exec_output(session, format!("{} 2>&1", command)).await {
+        Ok((_, Some(0))) => SftpResponse::Success(format!("OK: {}", message)),
+        Ok((output, code)) => SftpResponse::Error(format!(
+            "{}: {}",
+            message,
+            output.lines().next().map(str::to_string).unwrap_or_else(|| format!("exit {:?}", code))
+        )),
+        Err(e) => SftpResponse::Error(format!("{}: {}", message, e)),
+    };
+    let _ = resp_tx.send(response);
+}
+
+/// Передача в своём exec-канале. Без докачки и вопросов о конфликте —
+/// scp всегда пишет файл целиком.
+async fn run_scp_transfer(
+    session: Arc<russh::client::Handle<SshHandler>>,
+    req: SftpRequest,
+    resp_tx: mpsc::Sender<SftpResponse>,
+    limiter: Arc<RateLimiter>,
+) {
+    match req {
+        SftpRequest::Download {
+            remote,
+            local,
+            progress,
+            ..
+        } => {
+            let result = scp_download(&session, &remote, &local, &progress, &limiter).await;
+            if result.is_err() {
+                tokio::fs::remove_file(&local).await.ok();
+            }
+            finish_transfer(&resp_tx, &progress, result, format!("OK: get {}", remote));
+        }
+        SftpRequest::Upload {
+            local,
+            remote,
+            progress,
+            perms,
+            ..
+        } => {
+            let mode = perms.file_mode(local_mode(Path::new(&local))).unwrap_or(0o644);
+            let result = scp_upload(&session, &local, &remote, &progress, mode, &limiter).await;
+            finish_transfer(&resp_tx, &progress, result, format!("OK: put {}", remote));
+        }
+        SftpRequest::DownloadArchive {
+            remote,
+            local,
+            progress,
+        } => {
+            let result = download_archive(&session, &remote, &local, &progress, &limiter).await;
+            if result.is_err() {
+                tokio::fs::remove_file(&local).await.ok();
+            }
+            finish_transfer(&resp_tx, &progress, result, format!("OK: get {}", local));
+        }
+        _ => {}
+    }
+}
+
+/// Листинг через `ls -lan`: числовые uid/gid, время — с точностью ls.
+async fn scp_list(
+    session: &russh::client::Handle<SshHandler>,
+    path: &str,
+) -> Result<Vec<SftpEntry>, Box<dyn std::error::Error + Send + Sync>> {
+    // Слэш на конце: для файла ls выдаст ошибку, а не сам файл
+    let dir = format!("{}/", path.trim_end_matches('/'));
+    let command = format!("LC_ALL=C ls -lan {} 2>&1", shell_quote(&dir));
+    let (output, code) = exec_output(session, command).await?;
+    if code != Some(0) {
+        let reason = output.lines().next().unwrap_or("ls failed");
+        return Err(format!("ls '{}': {}", path, reason).into());
+    }
+    let now = std::time::SystemTime::now()
+        .duration_since(std::time::UNIX_EPOCH)
+        .map(|d| d.as_secs())
+        .unwrap_or(0);
+    let mut entries: Vec<SftpEntry> = output
+        .lines()
+        .filter_map(|line| parse_ls_line(path, line, now))
+        .filter(|e| e.name != "." && e.name != "..")
+        .collect();
+    entries.sort_by(|a, b| b.is_dir.cmp(&a.is_dir).then(a.name.cmp(&b.name)));
+    Ok(entries)
+}
+
+/// `drwxr-xr-x 2 0 0 4096 Jan  1 12:00 name` (у устройств размер — «1, 3»).
+fn parse_ls_line(dir: &str, line: &str, now: u64) -> Option<SftpEntry> {
+    let mut rest = line;
+    let mut next_field = || {
+        let trimmed = rest.trim_start();
+        let end = trimmed.find(char::is_whitespace)?;
+        rest = &trimmed[end..];
+        Some(&trimmed[..end])
+    };
+    let mode = parse_mode_string(next_field()?)?;
+    let _links = next_field()?;
+    let uid = next_field()?.parse().ok();
+    let gid = next_field()?.parse().ok();
+    let mut size_field = next_field()?;
+    if size_field.ends_with(',') {
+        // major, minor
+        next_field()?;
+        size_field = "0";
+    }
+    let size = size_field.parse().ok()?;
+    let modified = ls_timestamp(next_field()?, next_field()?, next_field()?, now);
+    // Имя — всё после одного пробела (может содержать пробелы)
+    let name = rest.strip_prefix(' ')?;
+    let (name, link_target) = match file_type_char(mode) {
+        'l' => match name.split_once(" -> ") {
+            Some((name, target)) => (name, Some(target.to_string())),
+            None => (name, None),
+        },
+        _ => (name, None),
+    };
+    Some(SftpEntry {
+        name: name.to_string(),
+        path: remote_join(dir, name),
+        is_dir: file_type_char(mode) == 'd',
+        size,
+        modified,
+        mode: Some(mode),
+        uid,
+        gid,
+        link_target,
+        target_ok: true,
+    })
+}
+
+/// "drwsr-xr-t" → st_mode. Хвост после десяти символов (ACL «+», SELinux «.») игнорируется.
+fn parse_mode_string(text: &str) -> Option<u32> {
+    let chars: Vec<char> = text.chars().take(10).collect();
+    if chars.len() < 10 {
+        return None;
+    }
+    let mut mode = match chars[0] {
+        'd' => 0o040000,
+        'l' => 0o120000,
+        'c' => 0o020000,
+        'b' => 0o060000,
+        'p' => 0o010000,
+        's' => 0o140000,
+        '-' => 0o100000,
+        _ => return None,
+    };
+    for (i, &c) in chars[1..].iter().enumerate() {
+        let bit = 1 << (8 - i);
+        match c {
+            'r' | 'w' | 'x' => mode |= bit,
+            '-' => {}
+            // s/t — исполняемый бит плюс setuid/setgid/sticky, S/T — без исполняемого
+            's' | 'S' | 't' | 'T' => {
+                if c.is_ascii_lowercase() {
+                    mode |= bit;
+                }
+                mode |= 0o4000 >> (i / 3);
+            }
+            _ => return None,
+        }
+    }
+    Some(mode)
+}
+
+/// "Jan 1 12:00" (последние полгода) или "Jan 1 2020". Время сервера
+/// считаем UTC — точнее ls без --full-time не скажет.
+fn ls_timestamp(month: &str, day: &str, time_or_year: &str, now: u64) -> Option<u64> {
+    const MONTHS: [&str; 12] = [
+        "Jan", "Feb", "Mar", "Apr", "May", "Jun", "Jul", "Aug", "Sep", "Oct", "Nov", "Dec",
+    ];
+    let month = MONTHS.iter().position(|&m| m == month)? as u32 + 1;
+    let day: u32 = day.parse().ok()?;
+    let (year, secs) = match time_or_year.split_once(':') {
+        Some((h, m)) => {
+            let secs = h.parse::<u64>().ok()? * 3600 + m.parse::<u64>().ok()? * 60;
+            let (this_year, _, _) = civil_from_days((now / 86400) as i64);
+            let ts = days_from_civil(this_year, month, day) as u64 * 86400 + secs;
+            // Дата «в будущем» — значит, прошлый год
+            if ts > now + 86400 {
+                (this_year - 1, secs)
+            } else {
+                (this_year, secs)
+            }
+        }
+        None => (time_or_year.parse().ok()?, 0),
+    };
+    let days = days_from_civil(year, month, day);
+    (days >= 0).then(|| days as u64 * 86400 + secs)
+}
+
+/// Год, месяц и день по числу дней от 1970-01-01 (обратное к days_from_civil).
+pub(super) fn civil_from_days(days: i64) -> (i64, u32, u32) {
+    let days = days + 719_468;
+    let era = days.div_euclid(146_097);
+    let day_of_era = days - era * 146_097;
+    let year_of_era =
+        (day_of_era - day_of_era / 1460 + day_of_era / 36_524 - day_of_era / 146_096) / 365;
+    let day_of_year = day_of_era - (365 * year_of_era + year_of_era / 4 - year_of_era / 100);
+    let shifted_month = (5 * day_of_year + 2) / 153;
+    let day = (day_of_year - (153 * shifted_month + 2) / 5 + 1) as u32;
+    let month = if shifted_month < 10 { shifted_month + 3 } else { shifted_month - 9 } as u32;
+    let year = year_of_era + era * 400 + i64::from(month <= 2);
+    (year, month, day)
+}
+
+/// Дни от 1970-01-01 по григорианскому календарю.
+fn days_from_civil(year: i64, month: u32, day: u32) -> i64 {
+    let year = if month <= 2 { year - 1 } else { year };
+    let era = year.div_euclid(400);
+    let year_of_era = year - era * 400;
+    let month = month as i64;
+    let day_of_year = (153 * (month + if month > 2 { -3 } else { 9 }) + 2) / 5 + day as i64 - 1;
+    let day_of_era = year_of_era * 365 + year_of_era / 4 - year_of_era / 100 + day_of_year;
+    era * 146_097 + day_of_era - 719_468
+}
+
+/// exec-канал с протоколом scp: строки-заголовки, байты-подтверждения и данные.
+struct ScpChannel {
+    channel: russh::Channel<russh::client::Msg>,
+    buf: Vec<u8>,
+    stderr: Vec<u8>,
+}
+
+impl ScpChannel {
+    async fn open(
+        session: &russh::client::Handle<SshHandler>,
+        command: String,
+    ) -> Result<Self, Box<dyn std::error::Error + Send + Sync>> {
+        let channel = session.channel_open_session().await?;
+        channel.exec(true, command).await?;
+        Ok(ScpChannel {
+            channel,
+            buf: Vec::new(),
+            stderr: Vec::new(),
+        })
+    }
+
+    /// Дождаться следующих данных; канал закрылся раньше — ошибка с stderr.
+    async fn fill(&mut self) -> Result<(), Box<dyn std::error::Error + Send + Sync>> {
+        loop {
+            match self.channel.wait().await {
+                Some(russh::ChannelMsg::Data { ref data }) => {
+                    self.buf.extend_from_slice(data);
+                    return Ok(());
+                }
+                Some(russh::ChannelMsg::ExtendedData { ref data, .. }) => {
+                    self.stderr.extend_from_slice(data)
+                }
+                Some(russh::ChannelMsg::ExitStatus { .. })
+                | Some(russh::ChannelMsg::Eof)
+                | Some(russh::ChannelMsg::Close)
+                | None => {
+                    let stderr = String::from_utf8_lossy(&self.stderr);
+                    let reason = stderr.lines().next().unwrap_or("connection closed");
+                    return Err(format!("scp: {}", reason).into());
+                }
+                Some(_) => {}
+            }
+        }
+    }
+
+    /// Строка без '\n'. \x01/\x02 в начале — ошибка удалённой стороны.
+    async fn read_line(&mut self) -> Result<String, Box<dyn std::error::Error + Send + Sync>> {
+        loop {
+            if let Some(pos) = self.buf.iter().position(|&b| b == b'\n') {
+                let line: Vec<u8> = self.buf.drain(..=pos).collect();
+                let line = String::from_utf8_lossy(&line[..pos]).into_owned();
+                return match line.as_bytes().first() {
+                    Some(1) | Some(2) => Err(format!("scp: {}", &line[1..]).into()),
+                    _ => Ok(line),
+                };
+            }
+            self.fill().await?;
+        }
+    }
+
+    /// Подтверждение: 0 — ок, 1/2 и текст — ошибка.
+    async fn ack(&mut self) -> Result<(), Box<dyn std::error::Error + Send + Sync>> {
+        if self.buf.is_empty() {
+            self.fill().await?;
+        }
+        match self.buf[0] {
+            0 => {
+                self.buf.remove(0);
+                Ok(())
+            }
+            1 | 2 => self.read_line().await.map(|_| ()),
+            other => Err(format!("scp: unexpected reply byte {}", other).into()),
+        }
+    }
+
+    /// Следующая порция данных файла, не больше max байт.
+    async fn take(
+        &mut self,
+        max: u64,
+    ) -> Result<Vec<u8>, Box<dyn std::error::Error + Send + Sync>> {
+        if self.buf.is_empty() {
+            self.fill().await?;
+        }
+        let n = self.buf.len().min(max as usize);
+        Ok(self.buf.drain(..n).collect())
+    }
+
+    async fn send(&self, data: &[u8]) -> Result<(), Box<dyn std::error::Error + Send + Sync>> {
+        Ok(self.channel.data(data).await?)
+    }
+
+    async fn finish(self) {
+        self.channel.eof().await.ok();
+        self.channel.close().await.ok();
+    }
+}
+
+/// `scp -f`: сервер присылает "C0644 size name", затем данные и 0.
+async fn scp_download(
+    session: &russh::client::Handle<SshHandler>,
+    remote: &str,
+    local: &str,
+    progress: &TransferState,
+    limiter: &RateLimiter,
+) -> Result<(), Box<dyn std::error::Error + Send + Sync>> {
+    use tokio::io::AsyncWriteExt;
+
+    let mut scp = ScpChannel::open(session, format!("scp -f {}", shell_quote(remote))).await?;
+    scp.send(&[0]).await?;
+    let header = scp.read_line().await?;
+    let size: u64 = match header.strip_prefix('C') {
+        Some(rest) => rest
+            .split(' ')
+            .nth(1)
+            .and_then(|s| s.parse().ok())
+            .ok_or_else(|| format!("scp: bad header '{}'", header))?,
+        None => return Err(format!("scp: '{}' is not a regular file", remote).into()),
+    };
+    progress.total.fetch_max(size, Ordering::Relaxed);
+    progress.file_total.store(size, Ordering::Relaxed);
+
+    if let Some(parent) = Path::new(local).parent() {
+        tokio::fs::create_dir_all(parent)
+            .await
+            .map_err(|e| format!("create local dir '{}': {}", parent.display(), e))?;
+    }
+    let mut file = tokio::fs::File::create(local)
+        .await
+        .map_err(|e| format!("create local file '{}': {}", local, e))?;
+    scp.send(&[0]).await?;
+
+    let mut left = size;
+    while left > 0 {
+        progress.check_interrupted()?;
+        let data = scp.take(left.min(limiter.chunk_len() as u64)).await?;
+        limiter.acquire(data.len()).await;
+        file.write_all(&data).await?;
+        left -= data.len() as u64;
+        progress.transferred.fetch_add(data.len() as u64, Ordering::Relaxed);
+        progress.file_transferred.store(size - left, Ordering::Relaxed);
+    }
+    scp.ack().await?;
+    scp.send(&[0]).await?;
+    file.flush().await?;
+    scp.finish().await;
+    Ok(())
+}
+
+/// `scp -t`: ждём 0, шлём "C<mode> size name", данные и 0, ждём подтверждения.
+async fn scp_upload(
+    session: &russh::client::Handle<SshHandler>,
+    local: &str,
+    remote: &str,
+    progress: &TransferState,
+    mode: u32,
+    limiter: &RateLimiter,
+) -> Result<(), Box<dyn std::error::Error + Send + Sync>> {
+    use tokio::io::AsyncReadExt;
+
+    let file = tokio::fs::File::open(local)
+        .await
+        .map_err(|e| format!("open local file '{}': {}", local, e))?;
+    let size = file.metadata().await?.len();
+    // Заголовок уже объявил размер: дописанное после stat не отправляем.
+    let mut file = file.take(size);
+    progress.total.fetch_max(size, Ordering::Relaxed);
+    progress.file_total.store(size, Ordering::Relaxed);
+
+    let name = remote.rsplit('/').next().unwrap_or(remote);
+    // Заголовок построчный: перевод строки в имени сломает протокол.
+    if name.contains(['\n', '\r']) {
+        let name = name.escape_debug();
+        return Err(format!("scp: file name '{}' contains a line break", name).into());
+    }
+    let mut scp = ScpChannel::open(session, format!("scp -t {}", shell_quote(remote))).await?;
+    scp.ack().await?;
+    scp.send(format!("C{:04o} {} {}\n", mode & 0o7777, size, name).as_bytes()).await?;
+    scp.ack().await?;
+
+    let mut buf = vec![0u8; CHUNK_SIZE];
+    let mut sent: u64 = 0;
+    loop {
+        progress.check_interrupted()?;
+        let len = limiter.chunk_len();
+        let n = file.read(&mut buf[..len]).await?;
+        if n == 0 {
+            break;
+        }
+        limiter.acquire(n).await;
+        scp.send(&buf[..n]).await?;
+        sent += n as u64;
+        progress.transferred.fetch_add(n as u64, Ordering::Relaxed);
+        progress.file_transferred.store(sent, Ordering::Relaxed);
+    }
+    if sent != size {
+        return Err(format!("'{}' shrank during upload ({} of {} bytes)", local, sent, size).into());
+    }
+    scp.send(&[0]).await?;
+    scp.ack().await?;
+    scp.finish().await;
+    Ok(())
+}
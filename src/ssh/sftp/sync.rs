@@ -0,0 +1,416 @@
+use super::*;
+
+// ── Sync ──
+
+#[derive(Clone, Copy, PartialEq)]
+pub(super) enum SyncDirection {
+    /// local → remote
+    Upload,
+    /// remote → local
+    Download,
+}
+
+/// Какие папки синхронизируем и как сравниваем.
+#[derive(Clone)]
+pub(super) struct SyncSpec {
+    pub(super) local: String,
+    pub(super) remote: String,
+    pub(super) direction: SyncDirection,
+    /// Удалять в приёмнике то, чего нет в источнике
+    pub(super) delete: bool,
+    /// Файлы одного размера сравнивать по sha256 вместо mtime
+    pub(super) checksum: bool,
+}
+
+/// Шаг плана; действует на приёмник (какой — по направлению).
+#[derive(Clone, Copy, PartialEq)]
+pub(super) enum SyncOp {
+    Mkdir,
+    Copy,
+    Delete,
+}
+
+#[derive(Clone)]
+pub(super) struct SyncItem {
+    pub(super) op: SyncOp,
+    /// Путь относительно корней; "" — сам корень
+    pub(super) rel: String,
+    pub(super) is_dir: bool,
+    pub(super) size: u64,
+    pub(super) reason: &'static str,
+    /// Файл в приёмнике уже есть и будет заменён
+    pub(super) replaces: bool,
+    pub(super) enabled: bool,
+}
+
+impl SyncItem {
+    pub(super) fn label(&self, direction: SyncDirection) -> (&'static str, egui::Color32) {
+        match (self.op, direction) {
+            (SyncOp::Mkdir, _) => ("mkdir", crate::theme::GREEN_DIM),
+            (SyncOp::Copy, SyncDirection::Upload) => ("put", crate::theme::GREEN),
+            (SyncOp::Copy, SyncDirection::Download) => ("get", crate::theme::GREEN),
+            (SyncOp::Delete, _) => ("rm", crate::theme::RED),
+        }
+    }
+}
+
+/// Шаги плана и папки сервера, которые не удалось прочитать (их содержимое
+/// в план не попало и не удаляется).
+pub(super) struct SyncPlan {
+    pub(super) items: Vec<SyncItem>,
+    pub(super) unreadable: Vec<String>,
+}
+
+/// Окно синхронизации: параметры, затем план (dry run), который можно
+/// поправить галочками и выполнить.
+pub(super) struct SyncDialog {
+    pub(super) spec: SyncSpec,
+    /// План и параметры, с которыми он посчитан
+    pub(super) plan: Option<(SyncSpec, Vec<SyncItem>)>,
+    /// id сравнения, которое ещё идёт
+    pub(super) comparing: Option<u64>,
+    pub(super) status: String,
+    /// Непрочитанные папки последнего сравнения: "путь: ошибка"
+    pub(super) unreadable: Vec<String>,
+}
+
+impl SyncDialog {
+    pub(super) fn new(remote: &str) -> Self {
+        SyncDialog {
+            spec: SyncSpec {
+                local: String::new(),
+                remote: remote.to_string(),
+                direction: SyncDirection::Upload,
+                delete: false,
+                checksum: false,
+            },
+            plan: None,
+            comparing: None,
+            status: String::new(),
+            unreadable: Vec::new(),
+        }
+    }
+}
+
+struct SyncFile {
+    size: u64,
+    modified: u64,
+    is_dir: bool,
+}
+
+/// Относительный путь → файл; BTreeMap — родители раньше детей
+type SyncTree = BTreeMap<String, SyncFile>;
+
+pub(super) async fn run_sync_compare(
+    session: Arc<russh::client::Handle<SshHandler>>,
+    id: u64,
+    spec: SyncSpec,
+    resp_tx: mpsc::Sender<SftpResponse>,
+) {
+    let result = async {
+        let sftp = open_sftp(&session).await?;
+        let plan = sync_plan(&session, &sftp, &spec).await;
+        sftp.close().await.ok();
+        plan
+    };
+    let result = result.await.map_err(|e| e.to_string());
+    let _ = resp_tx.send(SftpResponse::SyncPlan(id, result));
+}
+
+/// Dry run: что скопировать, создать и удалить в приёмнике.
+/// Файл копируется, если его нет, размер другой или источник новее
+/// (с checksum — если sha256 другой). Папка против файла с тем же именем
+/// пропускается. Непрочитанные папки сервера и всё под ними не трогаем.
+async fn sync_plan(
+    session: &russh::client::Handle<SshHandler>,
+    sftp: &russh_sftp::client::SftpSession,
+    spec: &SyncSpec,
+) -> Result<SyncPlan, Box<dyn std::error::Error + Send + Sync>> {
+    let local_root = Path::new(&spec.local);
+    let local_exists = local_root.is_dir();
+    let local = if local_exists {
+        local_sync_tree(local_root)
+            .await
+            .map_err(|e| format!("read local dir '{}': {}", spec.local, e))?
+    } else {
+        SyncTree::new()
+    };
+    let remote_exists = sftp.try_exists(&spec.remote).await.unwrap_or(false);
+    let (remote, unreadable) = if remote_exists {
+        remote_sync_tree(sftp, &spec.remote).await?
+    } else {
+        (SyncTree::new(), Vec::new())
+    };
+    let under_unreadable = |rel: &str| {
+        unreadable.iter().any(|(dir, _)| {
+            rel.strip_prefix(dir.as_str()).is_some_and(|rest| rest.starts_with('/'))
+        })
+    };
+    let (source, dest, dest_exists, source_exists) = match spec.direction {
+        SyncDirection::Upload => (&local, &remote, remote_exists, local_exists),
+        SyncDirection::Download => (&remote, &local, local_exists, remote_exists),
+    };
+    if !source_exists {
+        return Err("source folder does not exist".into());
+    }
+
+    let item = |op, rel: &str, file: &SyncFile, reason| SyncItem {
+        op,
+        rel: rel.to_string(),
+        is_dir: file.is_dir,
+        size: file.size,
+        reason,
+        replaces: op == SyncOp::Copy && dest.contains_key(rel),
+        enabled: true,
+    };
+    let mut items = Vec::new();
+    if !dest_exists {
+        let root = SyncFile {
+            size: 0,
+            modified: 0,
+            is_dir: true,
+        };
+        items.push(item(SyncOp::Mkdir, "", &root, "new dir"));
+    }
+    let mut same_size = Vec::new();
+    for (rel, src) in source {
+        if under_unreadable(rel) {
+            continue;
+        }
+        let reason = match dest.get(rel) {
+            None if src.is_dir => Some("new dir"),
+            None => Some("new"),
+            Some(dst) if src.is_dir || dst.is_dir => None,
+            Some(dst) if src.size != dst.size => Some("size differs"),
+            Some(_) if spec.checksum => {
+                same_size.push(rel.clone());
+                None
+            }
+            Some(dst) if src.modified > dst.modified => Some("newer"),
+            Some(_) => None,
+        };
+        if let Some(reason) = reason {
+            let op = if src.is_dir { SyncOp::Mkdir } else { SyncOp::Copy };
+            items.push(item(op, rel, src, reason));
+        }
+    }
+    if !same_size.is_empty() {
+        for rel in differing_checksums(session, spec, same_size).await? {
+            items.push(item(SyncOp::Copy, &rel, &source[&rel], "checksum differs"));
+        }
+    }
+    if spec.delete {
+        // В обратном порядке — содержимое раньше своей папки
+        for (rel, dst) in dest.iter().rev() {
+            if !source.contains_key(rel) && !under_unreadable(rel) {
+                items.push(item(SyncOp::Delete, rel, dst, "not in source"));
+            }
+        }
+    }
+    let unreadable = unreadable
+        .into_iter()
+        .map(|(rel, error)| format!("{}/: {}", rel, error))
+        .collect();
+    Ok(SyncPlan { items, unreadable })
+}
+
+async fn local_sync_tree(root: &Path) -> std::io::Result<SyncTree> {
+    let tree = walk_local_dir_async(root).await?;
+    let mut map = SyncTree::new();
+    for dir in tree.dirs {
+        let file = SyncFile {
+            size: 0,
+            modified: 0,
+            is_dir: true,
+        };
+        map.insert(dir, file);
+    }
+    for file in tree.files {
+        let entry = SyncFile {
+            size: file.size,
+            modified: file.modified,
+            is_dir: false,
+        };
+        map.insert(file.rel, entry);
+    }
+    Ok(map)
+}
+
+/// Симлинки на папки не обходим — как и при локальном обходе.
+/// Дерево папки сервера. Корень обязан читаться; вложенная папка, которую
+/// прочитать не удалось, попадает в список (путь, ошибка), обход идёт дальше.
+async fn remote_sync_tree(
+    sftp: &russh_sftp::client::SftpSession,
+    root: &str,
+) -> Result<(SyncTree, Vec<(String, String)>), Box<dyn std::error::Error + Send + Sync>> {
+    let mut map = SyncTree::new();
+    let mut unreadable = Vec::new();
+    let mut stack = vec![String::new()];
+    while let Some(rel) = stack.pop() {
+        let entries = match list_dir_async(sftp, &remote_join(root, &rel)).await {
+            Ok(entries) => entries,
+            Err(e) if !rel.is_empty() => {
+                unreadable.push((rel, e.to_string()));
+                continue;
+            }
+            Err(e) => return Err(e),
+        };
+        for entry in entries {
+            let child = if rel.is_empty() {
+                entry.name.clone()
+            } else {
+                format!("{}/{}", rel, entry.name)
+            };
+            if entry.is_dir && entry.link_target.is_none() {
+                stack.push(child.clone());
+            }
+            let file = SyncFile {
+                size: entry.size,
+                modified: entry.modified.unwrap_or(0),
+                is_dir: entry.is_dir,
+            };
+            map.insert(child, file);
+        }
+    }
+    Ok((map, unreadable))
+}
+
+/// Из файлов одного размера — те, у которых sha256 различается. Локальные
+/// суммы считаем сами, удалённые — `sha256sum` на сервере; файл, для которого
+/// сумму получить не удалось, считается отличающимся.
+async fn differing_checksums(
+    session: &russh::client::Handle<SshHandler>,
+    spec: &SyncSpec,
+    rels: Vec<String>,
+) -> Result<Vec<String>, Box<dyn std::error::Error + Send + Sync>> {
+    let mut remote_sums = HashMap::new();
+    for chunk in rels.chunks(100) {
+        let mut command = format!("cd {} && sha256sum --", shell_quote(&spec.remote));
+        for rel in chunk {
+            command.push(' ');
+            command.push_str(&shell_quote(rel));
+        }
+        // "<hex>  <name>" (или "<hex> *<name>" в двоичном режиме)
+        for line in exec_output(session, command).await?.0.lines() {
+            if let Some((sum, name)) = line.split_once("  ").or_else(|| line.split_once(" *")) {
+                remote_sums.insert(name.to_string(), sum.to_string());
+            }
+        }
+    }
+    let root = PathBuf::from(&spec.local);
+    let local_sums = tokio::task::spawn_blocking(move || {
+        rels.into_iter()
+            .map(|rel| {
+                let sum = local_sha256(&root.join(&rel));
+                (rel, sum)
+            })
+            .collect::<Vec<_>>()
+    })
+    .await?;
+    Ok(local_sums
+        .into_iter()
+        .filter(|(rel, sum)| sum.is_none() || remote_sums.get(rel) != sum.as_ref())
+        .map(|(rel, _)| rel)
+        .collect())
+}
+
+fn local_sha256(path: &Path) -> Option<String> {
+    use sha2::Digest;
+    use std::io::Read;
+
+    let mut file = std::fs::File::open(path).ok()?;
+    let mut hasher = sha2::Sha256::new();
+    let mut buf = vec![0u8; CHUNK_SIZE];
+    loop {
+        let n = file.read(&mut buf).ok()?;
+        if n == 0 {
+            break;
+        }
+        hasher.update(&buf[..n]);
+    }
+    Some(hasher.finalize().iter().map(|b| format!("{:02x}", b)).collect())
+}
+
+/// Шаги плана по порядку. Прогресс — как у папки: байты копий и шаги.
+pub(super) async fn run_sync(
+    sftp: &russh_sftp::client::SftpSession,
+    reader: &Arc<russh_sftp::client::RawSftpSession>,
+    spec: &SyncSpec,
+    items: &[SyncItem],
+    progress: &TransferState,
+    perms: UploadPermissions,
+    limiter: &RateLimiter,
+) -> Result<(), Box<dyn std::error::Error + Send + Sync>> {
+    let total = items
+        .iter()
+        .filter(|i| i.op == SyncOp::Copy)
+        .map(|i| i.size)
+        .sum();
+    progress.total.store(total, Ordering::Relaxed);
+    progress.files_total.store(items.len() as u64, Ordering::Relaxed);
+
+    for item in items {
+        progress.check_interrupted()?;
+        *progress.current.lock() = item.rel.clone();
+        progress.file_total.store(0, Ordering::Relaxed);
+        progress.file_transferred.store(0, Ordering::Relaxed);
+        let local = Path::new(&spec.local).join(&item.rel);
+        let local_str = local.to_string_lossy();
+        let remote = remote_join(&spec.remote, &item.rel);
+        match (item.op, spec.direction) {
+            (SyncOp::Mkdir, SyncDirection::Upload) => {
+                let mode = perms.dir_mode(local_mode(&local));
+                ensure_remote_dir(sftp, &remote, mode, progress).await?
+            }
+            (SyncOp::Mkdir, SyncDirection::Download) => tokio::fs::create_dir_all(&local)
+                .await
+                .map_err(|e| format!("mkdir local '{}': {}", local_str, e))?,
+            (SyncOp::Copy, SyncDirection::Upload) => {
+                let mode = perms.file_mode(local_mode(&local));
+                upload_chunked(sftp, &local_str, &remote, progress, 0, mode, limiter).await?;
+                // Иначе следующее сравнение сочтёт копию новее источника
+                if let Ok(meta) = std::fs::metadata(&local) {
+                    let attrs = russh_sftp::protocol::FileAttributes {
+                        atime: meta.accessed().ok().and_then(unix_secs),
+                        mtime: meta.modified().ok().and_then(unix_secs),
+                        ..russh_sftp::protocol::FileAttributes::empty()
+                    };
+                    sftp.set_metadata(&remote, attrs).await.ok();
+                }
+            }
+            (SyncOp::Copy, SyncDirection::Download) => {
+                download_chunked(reader, &remote, &local_str, progress, 0, limiter).await?;
+                if let Ok(meta) = sftp.metadata(&remote).await {
+                    let at = |secs: u32| std::time::UNIX_EPOCH + Duration::from_secs(secs as u64);
+                    let mut times = std::fs::FileTimes::new();
+                    if let Some(atime) = meta.atime {
+                        times = times.set_accessed(at(atime));
+                    }
+                    if let Some(mtime) = meta.mtime {
+                        times = times.set_modified(at(mtime));
+                    }
+                    if let Ok(file) = std::fs::File::options().write(true).open(&local) {
+                        file.set_times(times).ok();
+                    }
+                }
+            }
+            (SyncOp::Delete, SyncDirection::Upload) => {
+                if sftp.remove_file(&remote).await.is_err() {
+                    sftp.remove_dir(&remote)
+                        .await
+                        .map_err(|e| format!("rm remote '{}': {}", remote, e))?;
+                }
+            }
+            (SyncOp::Delete, SyncDirection::Download) => {
+                let result = if item.is_dir {
+                    tokio::fs::remove_dir(&local).await
+                } else {
+                    tokio::fs::remove_file(&local).await
+                };
+                result.map_err(|e| format!("rm local '{}': {}", local_str, e))?;
+            }
+        }
+        progress.files_done.fetch_add(1, Ordering::Relaxed);
+    }
+    Ok(())
+}
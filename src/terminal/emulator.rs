@@ -1,5 +1,14 @@
+use std::collections::{HashMap, VecDeque};
+use std::sync::Arc;
+
+use unicode_width::UnicodeWidthChar;
 use vte::{Params, Parser, Perform};
 
+use crate::terminal::iterm2;
+use crate::terminal::keyboard;
+use crate::terminal::kitty;
+use crate::terminal::sixel::SixelDecoder;
+
 #[derive(Clone, Copy, PartialEq)]
 pub enum TermColor {
     Default,
@@ -7,7 +16,7 @@ pub enum TermColor {
     Rgb(u8, u8, u8),
 }
 
-#[derive(Clone, Copy)]
+#[derive(Clone, Copy, PartialEq)]
 pub struct CellAttr {
     pub fg: TermColor,
     pub bg: TermColor,
@@ -30,10 +39,31 @@ impl Default for CellAttr {
     }
 }
 
-#[derive(Clone, Copy)]
+/// Сколько combining-знаков хранится на ячейку (остальные отбрасываются).
+const MAX_COMBINING: usize = 2;
+
+/// Combining-знаки (диакритика, ZWJ, variation selectors) поверх базового символа.
+/// Фиксированный массив, чтобы Cell оставался Copy; '\0' — пустой слот.
+#[derive(Clone, Copy, PartialEq, Default)]
+pub struct Combining([char; MAX_COMBINING]);
+
+impl Combining {
+    fn push(&mut self, c: char) {
+        if let Some(slot) = self.0.iter_mut().find(|slot| **slot == '\0') {
+            *slot = c;
+        }
+    }
+
+    pub fn iter(&self) -> impl Iterator<Item = char> + '_ {
+        self.0.iter().copied().take_while(|&c| c != '\0')
+    }
+}
+
+#[derive(Clone, Copy, PartialEq)]
 pub struct Cell {
     pub c: char,
     pub attr: CellAttr,
+    pub combining: Combining,
 }
 
 impl Default for Cell {
@@ -41,12 +71,96 @@ impl Default for Cell {
         Cell {
             c: ' ',
             attr: CellAttr::default(),
+            combining: Combining::default(),
         }
     }
 }
 
+impl Cell {
+    /// Текст ячейки: базовый символ ('\0' → пробел) и его combining-знаки.
+    pub fn push_text(&self, out: &mut String) {
+        out.push(if self.c == '\0' { ' ' } else { self.c });
+        out.extend(self.combining.iter());
+    }
+}
+
+/// Картинка, привязанная к сетке (sixel и т.п.).
+/// Едет вместе с текстом при прокрутке.
+pub struct TermImage {
+    pub id: u64,
+    pub width: usize,
+    pub height: usize,
+    pub rgba: Arc<Vec<u8>>,
+    /// Размер на экране в физических пикселях
+    pub display_size: (f32, f32),
+    pub col: usize,
+    /// Сколько строк сетки занимает
+    pub rows: usize,
+    // Строка привязки в координатах scroll_counter
+    anchor: i64,
+    // Картинка альтернативного экрана
+    alt: bool,
+    // (image id, placement id) для kitty graphics
+    kitty: Option<(u32, u32)>,
+}
+
+/// Команда, размеченная shell integration (OSC 133 A/B/C/D).
+/// Строки — в координатах scroll_counter, как у картинок.
+#[derive(Clone)]
+pub struct CommandMark {
+    /// Начало приглашения (A)
+    pub prompt: i64,
+    /// Начало вывода команды (C)
+    pub output: Option<i64>,
+    /// Строка, на которой команда завершилась (D)
+    pub end: Option<i64>,
+    pub exit_code: Option<i32>,
+    /// Начало ввода команды (B): строка и колонка
+    pub input: Option<(i64, usize)>,
+    pub started: Option<std::time::Instant>,
+    pub duration: Option<std::time::Duration>,
+}
+
+/// Завершённая команда — для уведомлений
+pub struct FinishedCommand {
+    pub command: String,
+    pub exit_code: Option<i32>,
+    pub duration: std::time::Duration,
+}
+
+/// Сколько последних команд помним
+const MAX_COMMAND_MARKS: usize = 1000;
+
+/// Куда ставить курсор после картинки
+#[derive(Clone, Copy, PartialEq)]
+enum ImageCursor {
+    /// На последнюю строку картинки, колонка не меняется (xterm: sixel, iTerm2)
+    LastRow,
+    /// Правее картинки на её последней строке (kitty)
+    AfterImage,
+    /// Не двигать (kitty C=1)
+    Stay,
+}
+
+/// Выделение APC-строк (ESC _ ... ESC \) из потока — vte их игнорирует
+#[derive(Clone, Copy, PartialEq)]
+enum ApcState {
+    Ground,
+    Escape,
+    Body,
+    BodyEscape,
+}
+
+/// Предел APC-строки (kitty шлёт картинки чанками по 4096)
+const MAX_APC_LEN: usize = 1024 * 1024;
+
+/// Предел числа картинок — старые вытесняются
+const MAX_IMAGES: usize = 64;
+
 pub struct TerminalEmulator {
     grid: Vec<Vec<Cell>>,
+    // Строка продолжается на следующей (автоперенос) — нужно для reflow
+    grid_wrapped: Vec<bool>,
     cols: usize,
     rows: usize,
     cursor_row: usize,
@@ -58,15 +172,73 @@ pub struct TerminalEmulator {
     scroll_bottom: usize,
     parser: Parser,
     scrollback: Vec<Vec<Cell>>,
+    scrollback_wrapped: Vec<bool>,
     scroll_offset: usize,
     alt_grid: Option<Vec<Vec<Cell>>>,
+    alt_wrapped: Option<Vec<bool>>,
     alt_cursor: Option<(usize, usize)>,
     app_cursor_keys: bool,
+    // Расширенная клавиатура: xterm modifyOtherKeys (CSI > 4 ; n m) и стек флагов
+    // kitty keyboard protocol (у альтернативного экрана свой стек)
+    modify_other_keys: u16,
+    kitty_keyboard: Vec<u16>,
+    kitty_keyboard_saved: Vec<u16>,
     auto_wrap: bool,
+    // DECOM — координаты курсора относительно области прокрутки
+    origin_mode: bool,
+    // IRM — вставка символов со сдвигом строки вправо
+    insert_mode: bool,
+    // ?1007 — колёсико на альтернативном экране шлёт стрелки
+    alternate_scroll: bool,
     wrap_next: bool,
     tab_stops: Vec<bool>,
+    // Последний напечатанный символ — для REP (CSI b)
+    last_char: Option<char>,
     #[allow(dead_code)]
     pending_data: Vec<u8>,
+    // Захват завершённых строк (для триггеров подсветки)
+    line_capture: bool,
+    completed_lines: Vec<String>,
+    // Графика
+    images: Vec<TermImage>,
+    next_image_id: u64,
+    // Сколько раз экран прокрутился вверх целиком (для привязки картинок)
+    scroll_counter: i64,
+    // Растёт при любом изменении содержимого (вывод, ресайз) — для кэша рендера
+    generation: u64,
+    alt_scroll_counter: Option<i64>,
+    cell_pixel_size: (f32, f32),
+    sixel: Option<SixelDecoder>,
+    // Многочастная картинка iTerm2: аргументы + накопленный base64
+    iterm_multipart: Option<(iterm2::ImageArgs, Vec<u8>)>,
+    apc_state: ApcState,
+    apc_buf: Vec<u8>,
+    // Kitty graphics: переданные картинки по id и незаконченная чанковая передача
+    kitty_images: HashMap<u32, (usize, usize, Arc<Vec<u8>>)>,
+    // Порядок передачи картинок — для вытеснения старых сверх kitty::MAX_STORED_BYTES
+    kitty_order: VecDeque<u32>,
+    kitty_pending: Option<(kitty::Command, Vec<u8>)>,
+    // Ответы терминала хосту (kitty graphics и т.п.)
+    responses: Vec<u8>,
+    // Был BEL с прошлого take_bell
+    bell: bool,
+    // Shell integration: приглашения и команды основного экрана
+    command_marks: Vec<CommandMark>,
+    finished_commands: Vec<FinishedCommand>,
+}
+
+/// Предел буфера завершённых строк, если их никто не забирает
+const MAX_COMPLETED_LINES: usize = 1000;
+
+/// Глубина стека флагов kitty keyboard protocol
+const MAX_KITTY_KEYBOARD_DEPTH: usize = 16;
+
+/// Версия для DA2: major·10000 + minor·100 + patch (как у alacritty).
+fn version_number() -> u32 {
+    let part = |s: &str| s.parse::<u32>().unwrap_or(0);
+    part(env!("CARGO_PKG_VERSION_MAJOR")) * 10000
+        + part(env!("CARGO_PKG_VERSION_MINOR")) * 100
+        + part(env!("CARGO_PKG_VERSION_PATCH"))
 }
 
 impl TerminalEmulator {
@@ -79,6 +251,7 @@ impl TerminalEmulator {
 
         TerminalEmulator {
             grid,
+            grid_wrapped: vec![false; rows],
             cols,
             rows,
             cursor_row: 0,
@@ -90,14 +263,42 @@ impl TerminalEmulator {
             scroll_bottom: rows.saturating_sub(1),
             parser: Parser::new(),
             scrollback: Vec::new(),
+            scrollback_wrapped: Vec::new(),
             scroll_offset: 0,
             alt_grid: None,
+            alt_wrapped: None,
             alt_cursor: None,
             app_cursor_keys: false,
+            modify_other_keys: 0,
+            kitty_keyboard: Vec::new(),
+            kitty_keyboard_saved: Vec::new(),
             auto_wrap: true,
+            origin_mode: false,
+            insert_mode: false,
+            alternate_scroll: true,
             wrap_next: false,
+            last_char: None,
             tab_stops,
             pending_data: Vec::new(),
+            line_capture: false,
+            completed_lines: Vec::new(),
+            images: Vec::new(),
+            next_image_id: 1,
+            scroll_counter: 0,
+            generation: 0,
+            alt_scroll_counter: None,
+            cell_pixel_size: (8.0, 16.0),
+            sixel: None,
+            iterm_multipart: None,
+            apc_state: ApcState::Ground,
+            apc_buf: Vec::new(),
+            kitty_images: HashMap::new(),
+            kitty_order: VecDeque::new(),
+            kitty_pending: None,
+            responses: Vec::new(),
+            bell: false,
+            command_marks: Vec::new(),
+            finished_commands: Vec::new(),
         }
     }
 
@@ -119,14 +320,60 @@ impl TerminalEmulator {
 
     /// Обработать данные напрямую (без буферизации)
     pub fn process(&mut self, data: &[u8]) {
+        self.generation += 1;
         self.scroll_offset = 0;
         let mut parser = std::mem::replace(&mut self.parser, Parser::new());
         for &byte in data {
-            parser.advance(self, byte);
+            // APC вырезаем до vte; остальное идёт в парсер как есть
+            match (self.apc_state, byte) {
+                (ApcState::Ground, 0x1b) => self.apc_state = ApcState::Escape,
+                (ApcState::Ground, _) => parser.advance(self, byte),
+                (ApcState::Escape, b'_') => {
+                    self.apc_state = ApcState::Body;
+                    self.apc_buf.clear();
+                }
+                (ApcState::Escape, _) => {
+                    self.apc_state = ApcState::Ground;
+                    parser.advance(self, 0x1b);
+                    if byte == 0x1b {
+                        self.apc_state = ApcState::Escape;
+                    } else {
+                        parser.advance(self, byte);
+                    }
+                }
+                (ApcState::Body, 0x1b) => self.apc_state = ApcState::BodyEscape,
+                (ApcState::Body, _) => {
+                    if self.apc_buf.len() < MAX_APC_LEN {
+                        self.apc_buf.push(byte);
+                    }
+                }
+                (ApcState::BodyEscape, b'\\') => {
+                    self.apc_state = ApcState::Ground;
+                    let body = std::mem::take(&mut self.apc_buf);
+                    self.apc_dispatch(&body);
+                }
+                (ApcState::BodyEscape, _) => {
+                    // Оборванная строка — выбрасываем
+                    self.apc_state = ApcState::Ground;
+                    self.apc_buf.clear();
+                    parser.advance(self, 0x1b);
+                    parser.advance(self, byte);
+                }
+            }
         }
         self.parser = parser;
     }
 
+    /// Ответы терминала, которые нужно отправить на удалённую сторону.
+    pub fn take_responses(&mut self) -> Vec<u8> {
+        std::mem::take(&mut self.responses)
+    }
+
+    /// Звонил ли терминал (BEL) с прошлого вызова.
+    pub fn take_bell(&mut self) -> bool {
+        std::mem::take(&mut self.bell)
+    }
+
     pub fn grid(&self) -> &[Vec<Cell>] {
         &self.grid
     }
@@ -135,12 +382,10 @@ impl TerminalEmulator {
         (self.cursor_row, self.cursor_col, self.cursor_visible)
     }
 
-    #[allow(dead_code)]
     pub fn cols(&self) -> usize {
         self.cols
     }
 
-    #[allow(dead_code)]
     pub fn rows(&self) -> usize {
         self.rows
     }
@@ -149,10 +394,30 @@ impl TerminalEmulator {
         self.app_cursor_keys
     }
 
+    /// Колёсико превращается в стрелки: альтернативный экран и ?1007 не выключен.
+    pub fn alternate_scroll(&self) -> bool {
+        self.alternate_scroll && self.alt_grid.is_some()
+    }
+
+    /// Текущие флаги kitty keyboard protocol (0 — legacy).
+    pub fn kitty_keyboard_flags(&self) -> u16 {
+        self.kitty_keyboard.last().copied().unwrap_or(0)
+    }
+
+    /// Уровень xterm modifyOtherKeys (0 — выключен).
+    pub fn modify_other_keys(&self) -> u16 {
+        self.modify_other_keys
+    }
+
     pub fn scrollback_len(&self) -> usize {
         self.scrollback.len()
     }
 
+    /// Версия содержимого: не изменилась — строки экрана те же, что в прошлом кадре.
+    pub fn generation(&self) -> u64 {
+        self.generation
+    }
+
     pub fn scroll_offset(&self) -> usize {
         self.scroll_offset
     }
@@ -169,10 +434,130 @@ impl TerminalEmulator {
         self.scroll_offset = 0;
     }
 
+    /// Абсолютная позиция прокрутки (скроллбар); ограничивается размером scrollback.
+    pub fn set_scroll_offset(&mut self, offset: usize) {
+        self.scroll_offset = offset.min(self.scrollback.len());
+    }
+
     pub fn is_scrolled(&self) -> bool {
         self.scroll_offset > 0
     }
 
+    /// Размер ячейки в пикселях — нужен, чтобы понять, сколько строк займёт картинка.
+    pub fn set_cell_pixel_size(&mut self, width: f32, height: f32) {
+        self.cell_pixel_size = (width.max(1.0), height.max(1.0));
+    }
+
+    /// Картинки текущего экрана и строка их верхнего края в текущем виде
+    /// (с учётом прокрутки; может быть отрицательной, если картинка частично выше).
+    pub fn visible_images(&self) -> Vec<(i64, &TermImage)> {
+        let in_alt = self.alt_grid.is_some();
+        let offset = if in_alt { 0 } else { self.scroll_offset as i64 };
+        self.images
+            .iter()
+            .filter(|img| img.alt == in_alt)
+            .map(|img| (img.anchor - self.scroll_counter + offset, img))
+            .filter(|(row, img)| *row + img.rows as i64 > 0 && *row < self.rows as i64)
+            .collect()
+    }
+
+    // --- Shell integration (OSC 133) ---
+
+    pub fn command_marks(&self) -> &[CommandMark] {
+        &self.command_marks
+    }
+
+    pub fn is_alt_screen(&self) -> bool {
+        self.alt_grid.is_some()
+    }
+
+    /// Команды, завершившиеся с прошлого вызова.
+    pub fn take_finished_commands(&mut self) -> Vec<FinishedCommand> {
+        std::mem::take(&mut self.finished_commands)
+    }
+
+    /// Абсолютная строка курсора (координаты меток).
+    pub fn cursor_line(&self) -> i64 {
+        self.scroll_counter + self.cursor_row as i64
+    }
+
+    /// Абсолютная строка (координаты меток) верхней видимой строки.
+    pub fn view_top_line(&self) -> i64 {
+        self.scroll_counter - self.scroll_offset as i64
+    }
+
+    /// Прокрутить так, чтобы строка `line` оказалась наверху (насколько возможно).
+    pub fn scroll_to_line(&mut self, line: i64) {
+        let offset = (self.scroll_counter - line).max(0) as usize;
+        self.scroll_offset = offset.min(self.scrollback.len());
+    }
+
+    /// Абсолютная строка первой строки scrollback (начало all_rows).
+    pub fn first_line(&self) -> i64 {
+        self.scroll_counter - self.scrollback.len() as i64
+    }
+
+    /// Абсолютная строка последней строки экрана.
+    pub fn last_line(&self) -> i64 {
+        self.scroll_counter + self.rows as i64 - 1
+    }
+
+    /// Строка буфера по абсолютному номеру (scrollback или экран).
+    pub fn row_at(&self, line: i64) -> Option<&Vec<Cell>> {
+        let idx = usize::try_from(line - self.first_line()).ok()?;
+        match idx.checked_sub(self.scrollback.len()) {
+            None => self.scrollback.get(idx),
+            Some(row) => self.grid.get(row),
+        }
+    }
+
+    /// Строка `line` продолжается на следующей: перенос по ширине, а не перевод строки.
+    pub fn line_wrapped(&self, line: i64) -> bool {
+        let Ok(idx) = usize::try_from(line - self.first_line()) else {
+            return false;
+        };
+        let wrapped = match idx.checked_sub(self.scrollback.len()) {
+            None => self.scrollback_wrapped.get(idx),
+            Some(row) => self.grid_wrapped.get(row),
+        };
+        wrapped.copied().unwrap_or(false)
+    }
+
+    /// Текст строк [from, to) в абсолютных координатах (вывод команды).
+    pub fn text_between(&self, from: i64, to: i64) -> String {
+        let base = self.first_line();
+        let rows: Vec<Vec<Cell>> = self
+            .all_rows()
+            .enumerate()
+            .filter(|(i, _)| (from..to).contains(&(base + *i as i64)))
+            .map(|(_, row)| row.clone())
+            .collect();
+        crate::terminal::export::cells_to_text(&rows)
+    }
+
+    /// Текст одной строки (абсолютной) начиная с колонки `col`.
+    fn line_text_from(&self, line: i64, col: usize) -> String {
+        let text = self.text_between(line, line + 1);
+        text.chars().skip(col).collect::<String>().trim().to_string()
+    }
+
+    /// Весь буфер: scrollback + текущий экран (для экспорта).
+    pub fn all_rows(&self) -> impl Iterator<Item = &Vec<Cell>> {
+        self.scrollback.iter().chain(self.grid.iter())
+    }
+
+    /// Включает накопление строк, завершённых переводом строки.
+    pub fn set_line_capture(&mut self, enabled: bool) {
+        self.line_capture = enabled;
+        if !enabled {
+            self.completed_lines.clear();
+        }
+    }
+
+    pub fn take_completed_lines(&mut self) -> Vec<String> {
+        std::mem::take(&mut self.completed_lines)
+    }
+
     /// Возвращает строки для отображения с учётом scroll_offset.
     /// При scroll_offset == 0 возвращает текущую сетку.
     /// При scroll_offset > 0 показывает строки из scrollback + часть сетки.
@@ -203,51 +588,193 @@ impl TerminalEmulator {
         if new_cols == 0 || new_rows == 0 || (new_cols == self.cols && new_rows == self.rows) {
             return;
         }
+        self.generation += 1;
 
-        let mut new_grid = vec![vec![Cell::default(); new_cols]; new_rows];
-        let copy_rows = new_rows.min(self.rows);
-        let copy_cols = new_cols.min(self.cols);
-
-        // Если новый экран меньше и курсор ниже видимой области — прокручиваем
-        if self.cursor_row >= new_rows {
-            let shift = self.cursor_row - new_rows + 1;
-            for i in 0..shift {
-                if i < self.rows {
-                    self.scrollback.push(self.grid[i].clone());
-                }
-            }
-            for r in 0..copy_rows {
-                let src_row = r + shift;
-                if src_row < self.rows {
-                    for c in 0..copy_cols {
-                        new_grid[r][c] = self.grid[src_row][c];
-                    }
-                }
+        if self.alt_grid.is_some() {
+            // Альтернативный экран приложение перерисует само — просто обрезаем,
+            // а сохранённый основной экран переносим вместе со scrollback.
+            self.resize_grid_truncate(new_cols, new_rows);
+            let main_grid = self.alt_grid.take().unwrap_or_default();
+            let main_wrapped = self.alt_wrapped.take().unwrap_or_default();
+            let cursor = self.alt_cursor.unwrap_or((0, 0));
+            let (grid, wrapped, new_cursor) =
+                self.reflow_primary(main_grid, main_wrapped, cursor, new_cols, new_rows);
+            // Картинки основного экрана держим на том же расстоянии от курсора
+            if let Some(counter) = &mut self.alt_scroll_counter {
+                *counter -= new_cursor.0 as i64 - cursor.0 as i64;
             }
-            self.cursor_row = new_rows - 1;
+            let cursor = new_cursor;
+            self.alt_grid = Some(grid);
+            self.alt_wrapped = Some(wrapped);
+            self.alt_cursor = Some(cursor);
         } else {
-            for r in 0..copy_rows {
-                for c in 0..copy_cols {
-                    if r < self.grid.len() && c < self.grid[r].len() {
-                        new_grid[r][c] = self.grid[r][c];
-                    }
-                }
-            }
+            let grid = std::mem::take(&mut self.grid);
+            let wrapped = std::mem::take(&mut self.grid_wrapped);
+            let cursor = (self.cursor_row, self.cursor_col);
+            let (grid, wrapped, (row, col)) =
+                self.reflow_primary(grid, wrapped, cursor, new_cols, new_rows);
+            self.scroll_counter -= row as i64 - cursor.0 as i64;
+            self.grid = grid;
+            self.grid_wrapped = wrapped;
+            self.cursor_row = row;
+            self.cursor_col = col;
         }
 
-        self.grid = new_grid;
         self.cols = new_cols;
         self.rows = new_rows;
         self.scroll_top = 0;
         self.scroll_bottom = new_rows.saturating_sub(1);
-        self.cursor_col = self.cursor_col.min(new_cols.saturating_sub(1));
+        self.cursor_row = self.cursor_row.min(new_rows - 1);
+        self.cursor_col = self.cursor_col.min(new_cols - 1);
+        self.wrap_next = false;
+        if let Some((row, col, attr)) = self.saved_cursor {
+            self.saved_cursor = Some((row.min(new_rows - 1), col.min(new_cols - 1), attr));
+        }
 
-        self.tab_stops = vec![false; new_cols];
-        for i in (0..new_cols).step_by(8) {
+        // Заданные приложением позиции табуляции сохраняются, новые колонки — по умолчанию
+        let old_cols = self.tab_stops.len();
+        self.tab_stops.resize(new_cols, false);
+        for i in (0..new_cols).step_by(8).filter(|&i| i >= old_cols) {
             self.tab_stops[i] = true;
         }
+    }
+
+    /// Изменение размера без переноса (для альтернативного экрана).
+    fn resize_grid_truncate(&mut self, new_cols: usize, new_rows: usize) {
+        let mut new_grid = vec![vec![Cell::default(); new_cols]; new_rows];
+        // Курсор ниже новой области — отрезаем строки сверху
+        let shift = (self.cursor_row + 1).saturating_sub(new_rows);
+        self.scroll_counter += shift as i64;
+        for (new_row, old_row) in new_grid.iter_mut().zip(self.grid.iter().skip(shift)) {
+            let n = new_cols.min(old_row.len());
+            new_row[..n].copy_from_slice(&old_row[..n]);
+        }
+        self.grid = new_grid;
+        self.grid_wrapped = vec![false; new_rows];
+        self.cursor_row -= shift;
+    }
+
+    /// Reflow основного экрана: scrollback + экран склеиваются в логические строки
+    /// (по флагам автопереноса) и заново нарезаются по новой ширине.
+    /// Возвращает новый экран, его флаги и позицию курсора на нём.
+    fn reflow_primary(
+        &mut self,
+        grid: Vec<Vec<Cell>>,
+        grid_wrapped: Vec<bool>,
+        cursor: (usize, usize),
+        new_cols: usize,
+        new_rows: usize,
+    ) -> (Vec<Vec<Cell>>, Vec<bool>, (usize, usize)) {
+        let old_base = self.scrollback.len();
+        // Пустые строки ниже курсора не переносим — иначе они вытеснят вывод в scrollback
+        let last_used = grid
+            .iter()
+            .rposition(|row| row.iter().any(|c| *c != Cell::default()))
+            .unwrap_or(0)
+            .max(cursor.0)
+            .min(grid.len().saturating_sub(1));
+
+        let scrollback = std::mem::take(&mut self.scrollback);
+        let scrollback_wrapped = std::mem::take(&mut self.scrollback_wrapped);
+        let cursor_abs = scrollback.len() + cursor.0;
+
+        let mut rows_in: Vec<(Vec<Cell>, bool)> = scrollback
+            .into_iter()
+            .zip(scrollback_wrapped.into_iter().chain(std::iter::repeat(false)))
+            .collect();
+        rows_in.extend(
+            grid.into_iter()
+                .zip(grid_wrapped.into_iter().chain(std::iter::repeat(false)))
+                .take(last_used + 1),
+        );
+
+        let mut out_rows: Vec<Vec<Cell>> = Vec::with_capacity(rows_in.len());
+        let mut out_wrapped: Vec<bool> = Vec::with_capacity(rows_in.len());
+        let mut new_cursor_abs = (0, 0);
+
+        // Входная строка → первая выходная строка её логической строки (для меток)
+        let mut row_map: Vec<usize> = Vec::with_capacity(rows_in.len());
+        let mut logical: Vec<Cell> = Vec::new();
+        // Смещение курсора внутри текущей логической строки
+        let mut cursor_offset: Option<usize> = None;
+
+        let row_count = rows_in.len();
+        for (abs_row, (row, wrapped)) in rows_in.into_iter().enumerate() {
+            // Последняя строка всегда закрывает логическую строку
+            let wrapped = wrapped && abs_row + 1 < row_count;
+            if abs_row == cursor_abs {
+                cursor_offset = Some(logical.len() + cursor.1);
+            }
+            logical.extend(row);
+            if wrapped {
+                continue;
+            }
+
+            // Конец логической строки — нарезаем заново
+            row_map.resize(abs_row + 1, out_rows.len());
+            let mut len = logical
+                .iter()
+                .rposition(|c| *c != Cell::default())
+                .map(|i| i + 1)
+                .unwrap_or(0);
+            if let Some(offset) = cursor_offset {
+                len = len.max(offset);
+            }
+            let first_row = out_rows.len();
+            let chunks = len.div_ceil(new_cols).max(1);
+            for chunk in 0..chunks {
+                let from = (chunk * new_cols).min(len);
+                let to = ((chunk + 1) * new_cols).min(len);
+                let mut cells = logical[from..to].to_vec();
+                cells.resize(new_cols, Cell::default());
+                out_rows.push(cells);
+                out_wrapped.push(chunk + 1 < chunks);
+            }
+            if let Some(offset) = cursor_offset.take() {
+                let row = first_row + offset / new_cols;
+                if row >= out_rows.len() {
+                    out_rows.push(vec![Cell::default(); new_cols]);
+                    out_wrapped.push(false);
+                }
+                new_cursor_abs = (row, offset % new_cols);
+            }
+            logical.clear();
+        }
+
+        // Последние new_rows строк — экран, остальное — scrollback.
+        // Курсор обязан остаться на экране.
+        let total = out_rows.len();
+        let grid_start = total.saturating_sub(new_rows).min(new_cursor_abs.0);
+        let mut new_grid = out_rows.split_off(grid_start);
+        let mut new_wrapped = out_wrapped.split_off(grid_start);
+        new_grid.truncate(new_rows);
+        new_wrapped.truncate(new_rows);
+        new_grid.resize(new_rows, vec![Cell::default(); new_cols]);
+        new_wrapped.resize(new_rows, false);
+
+        self.scrollback = out_rows;
+        self.scrollback_wrapped = out_wrapped;
+        self.scroll_offset = self.scroll_offset.min(self.scrollback.len());
 
-    
+        // Метки команд едут вместе со своими строками. Пока активен альтернативный
+        // экран, scroll_counter основного экрана лежит в alt_scroll_counter.
+        let counter = self.alt_scroll_counter.unwrap_or(self.scroll_counter);
+        let old_start = counter - old_base as i64;
+        let new_start = counter + (cursor.0 as i64 - (new_cursor_abs.0 - grid_start) as i64)
+            - self.scrollback.len() as i64;
+        let last = row_map.last().copied().unwrap_or(0);
+        let remap = |line: i64| {
+            let idx = (line - old_start).max(0) as usize;
+            new_start + row_map.get(idx).copied().unwrap_or(last) as i64
+        };
+        for mark in &mut self.command_marks {
+            mark.prompt = remap(mark.prompt);
+            mark.output = mark.output.map(remap);
+            mark.end = mark.end.map(remap);
+        }
+
+        let cursor = (new_cursor_abs.0 - grid_start, new_cursor_abs.1);
+        (new_grid, new_wrapped, cursor)
     }
 
     // --- Внутренние методы ---
@@ -258,25 +785,54 @@ impl TerminalEmulator {
 
         if top == 0 && self.alt_grid.is_none() {
             self.scrollback.push(self.grid[0].clone());
+            self.scrollback_wrapped.push(self.grid_wrapped[0]);
+        }
+        if top == 0 {
+            self.scroll_counter += 1;
+            if !self.images.is_empty() {
+                self.prune_images();
+            }
         }
 
         for r in top..bottom {
             self.grid[r] = self.grid[r + 1].clone();
+            self.grid_wrapped[r] = self.grid_wrapped[r + 1];
         }
         self.grid[bottom] = vec![Cell::default(); self.cols];
+        self.grid_wrapped[bottom] = false;
     }
 
     fn scroll_down(&mut self) {
         let top = self.scroll_top;
         let bottom = self.scroll_bottom;
 
+        if top == 0 {
+            self.scroll_counter -= 1;
+        }
+
         for r in (top + 1..=bottom).rev() {
             self.grid[r] = self.grid[r - 1].clone();
+            self.grid_wrapped[r] = self.grid_wrapped[r - 1];
         }
         self.grid[top] = vec![Cell::default(); self.cols];
+        self.grid_wrapped[top] = false;
     }
 
     fn newline(&mut self) {
+        if self.line_capture && self.cursor_row < self.grid.len() {
+            let mut text = String::new();
+            for cell in &self.grid[self.cursor_row] {
+                cell.push_text(&mut text);
+            }
+            let text = text.trim_end();
+            if !text.is_empty() {
+                if self.completed_lines.len() >= MAX_COMPLETED_LINES {
+                    self.completed_lines.remove(0);
+                }
+                self.completed_lines.push(text.to_string());
+            }
+        }
+
         if self.cursor_row == self.scroll_bottom {
             self.scroll_up();
         } else if self.cursor_row < self.rows.saturating_sub(1) {
@@ -284,17 +840,100 @@ impl TerminalEmulator {
         }
     }
 
+    /// Строка экрана для номера строки из CUP/VPA (с учётом DECOM).
+    fn absolute_row(&self, row: usize) -> usize {
+        if self.origin_mode {
+            (self.scroll_top + row).min(self.scroll_bottom)
+        } else {
+            row.min(self.rows.saturating_sub(1))
+        }
+    }
+
+    /// Курсор в начало: верх области прокрутки в origin mode, иначе (0, 0).
+    fn home_cursor(&mut self) {
+        self.cursor_row = if self.origin_mode { self.scroll_top } else { 0 };
+        self.cursor_col = 0;
+        self.wrap_next = false;
+    }
+
+    /// DECSTR — мягкий сброс: режимы, атрибуты и область прокрутки,
+    /// содержимое экрана и позиция курсора не меняются.
+    fn soft_reset(&mut self) {
+        self.cursor_visible = true;
+        self.origin_mode = false;
+        self.insert_mode = false;
+        self.auto_wrap = true;
+        self.app_cursor_keys = false;
+        self.scroll_top = 0;
+        self.scroll_bottom = self.rows.saturating_sub(1);
+        self.current_attr = CellAttr::default();
+        self.saved_cursor = None;
+        self.wrap_next = false;
+    }
+
+    /// Вперёд на `n` позиций табуляции (без следующей — к последней колонке).
+    fn tab_forward(&mut self, n: usize) {
+        for _ in 0..n {
+            self.cursor_col = (self.cursor_col + 1..self.cols)
+                .find(|&c| self.tab_stops.get(c).copied().unwrap_or(false))
+                .unwrap_or(self.cols.saturating_sub(1));
+        }
+        self.wrap_next = false;
+    }
+
+    /// Назад на `n` позиций табуляции (без предыдущей — к колонке 0).
+    fn tab_backward(&mut self, n: usize) {
+        for _ in 0..n {
+            self.cursor_col = (0..self.cursor_col)
+                .rev()
+                .find(|&c| self.tab_stops.get(c).copied().unwrap_or(false))
+                .unwrap_or(0);
+        }
+        self.wrap_next = false;
+    }
+
     fn put_char(&mut self, c: char) {
+        // Combining-знак (нулевая ширина) дописывается к предыдущей ячейке.
+        // При wrap_next курсор ещё стоит на только что напечатанном символе.
+        if c.width() == Some(0) {
+            let col = if self.wrap_next {
+                Some(self.cursor_col)
+            } else {
+                self.cursor_col.checked_sub(1)
+            };
+            if let Some(cell) = col.and_then(|col| {
+                self.grid
+                    .get_mut(self.cursor_row)
+                    .and_then(|row| row.get_mut(col))
+            }) {
+                cell.combining.push(c);
+            }
+            return;
+        }
+        self.last_char = Some(c);
+
         if self.wrap_next {
+            if let Some(wrapped) = self.grid_wrapped.get_mut(self.cursor_row) {
+                *wrapped = true;
+            }
             self.cursor_col = 0;
             self.newline();
             self.wrap_next = false;
         }
 
+        if self.insert_mode && self.cursor_row < self.rows && self.cursor_col < self.cols {
+            let row = &mut self.grid[self.cursor_row];
+            if row.len() >= self.cols {
+                row.pop();
+            }
+            row.insert(self.cursor_col, Cell::default());
+        }
+
         if self.cursor_row < self.rows && self.cursor_col < self.cols {
             self.grid[self.cursor_row][self.cursor_col] = Cell {
                 c,
                 attr: self.current_attr,
+                combining: Combining::default(),
             };
         }
 
@@ -315,11 +954,15 @@ impl TerminalEmulator {
                 for r in (self.cursor_row + 1)..self.rows {
                     self.grid[r] = vec![Cell::default(); self.cols];
                 }
+                for wrapped in &mut self.grid_wrapped[self.cursor_row..] {
+                    *wrapped = false;
+                }
             }
             1 => {
                 // Erase from start to cursor
                 for r in 0..self.cursor_row {
                     self.grid[r] = vec![Cell::default(); self.cols];
+                    self.grid_wrapped[r] = false;
                 }
                 for c in 0..=self.cursor_col.min(self.cols.saturating_sub(1)) {
                     self.grid[self.cursor_row][c] = Cell::default();
@@ -330,14 +973,27 @@ impl TerminalEmulator {
                 for r in 0..self.rows {
                     self.grid[r] = vec![Cell::default(); self.cols];
                 }
+                self.grid_wrapped.fill(false);
+                self.clear_screen_images();
+                if self.alt_grid.is_none() {
+                    let top = self.scroll_counter;
+                    self.command_marks.retain(|m| m.prompt < top);
+                }
             }
             3 => {
                 // Erase display + scrollback
                 for r in 0..self.rows {
                     self.grid[r] = vec![Cell::default(); self.cols];
                 }
+                self.grid_wrapped.fill(false);
                 self.scrollback.clear();
+                self.scrollback_wrapped.clear();
                 self.scroll_offset = 0;
+                if self.alt_grid.is_none() {
+                    self.command_marks.clear();
+                }
+                let in_alt = self.alt_grid.is_some();
+                self.images.retain(|img| img.alt != in_alt);
             }
             _ => {}
         }
@@ -352,6 +1008,7 @@ impl TerminalEmulator {
                 for c in self.cursor_col..self.cols {
                     self.grid[self.cursor_row][c] = Cell::default();
                 }
+                self.grid_wrapped[self.cursor_row] = false;
             }
             1 => {
                 for c in 0..=self.cursor_col.min(self.cols.saturating_sub(1)) {
@@ -360,6 +1017,7 @@ impl TerminalEmulator {
             }
             2 => {
                 self.grid[self.cursor_row] = vec![Cell::default(); self.cols];
+                self.grid_wrapped[self.cursor_row] = false;
             }
             _ => {}
         }
@@ -450,13 +1108,88 @@ impl TerminalEmulator {
         }
     }
 
+    /// kitty keyboard protocol: CSI > flags u — push, CSI < n u — pop,
+    /// CSI = flags ; mode u — изменить текущие, CSI ? u — запрос.
+    /// Храним только поддерживаемые флаги, их же и сообщаем.
+    fn handle_kitty_keyboard(&mut self, marker: Option<u8>, query: bool, p1: u16, p2: u16) {
+        let flags = p1 & keyboard::KITTY_DISAMBIGUATE;
+        match marker {
+            Some(b'>') => {
+                if self.kitty_keyboard.len() >= MAX_KITTY_KEYBOARD_DEPTH {
+                    self.kitty_keyboard.remove(0);
+                }
+                self.kitty_keyboard.push(flags);
+            }
+            Some(b'<') => {
+                let n = (p1.max(1) as usize).min(self.kitty_keyboard.len());
+                self.kitty_keyboard.truncate(self.kitty_keyboard.len() - n);
+            }
+            Some(b'=') => {
+                let current = self.kitty_keyboard_flags();
+                let flags = match p2 {
+                    2 => current | flags,
+                    3 => current & !flags,
+                    _ => flags,
+                };
+                match self.kitty_keyboard.last_mut() {
+                    Some(top) => *top = flags,
+                    None => self.kitty_keyboard.push(flags),
+                }
+            }
+            _ if query => {
+                let response = format!("\x1b[?{}u", self.kitty_keyboard_flags());
+                self.responses.extend_from_slice(response.as_bytes());
+            }
+            _ => {}
+        }
+    }
+
+    /// DSR: CSI 5 n — статус, CSI 6 n — позиция курсора (CPR), CSI ? 6 n — DECXCPR.
+    fn device_status_report(&mut self, kind: u16, private: bool) {
+        // В origin mode строка считается от верха области прокрутки
+        let row = if self.origin_mode {
+            self.cursor_row.saturating_sub(self.scroll_top)
+        } else {
+            self.cursor_row
+        } + 1;
+        let col = self.cursor_col + 1;
+        let response = match (kind, private) {
+            (5, false) => "\x1b[0n".to_string(),
+            (6, false) => format!("\x1b[{};{}R", row, col),
+            (6, true) => format!("\x1b[?{};{};1R", row, col),
+            // Принтера нет
+            (15, true) => "\x1b[?13n".to_string(),
+            _ => return,
+        };
+        self.responses.extend_from_slice(response.as_bytes());
+    }
+
+    /// DA: CSI c — VT220 с sixel и ANSI-цветом; CSI > c — тип 0, версия, ROM 1;
+    /// CSI = c — DECRPTUI с нулевым id.
+    fn device_attributes(&mut self, marker: Option<u8>) {
+        let response = match marker {
+            None => "\x1b[?62;4;22c".to_string(),
+            Some(b'>') => format!("\x1b[>0;{};1c", version_number()),
+            Some(b'=') => "\x1bP!|00000000\x1b\\".to_string(),
+            _ => return,
+        };
+        self.responses.extend_from_slice(response.as_bytes());
+    }
+
     fn enter_alt_screen(&mut self) {
         if self.alt_grid.is_none() {
             self.alt_grid = Some(std::mem::replace(
                 &mut self.grid,
                 vec![vec![Cell::default(); self.cols]; self.rows],
             ));
+            self.alt_wrapped = Some(std::mem::replace(
+                &mut self.grid_wrapped,
+                vec![false; self.rows],
+            ));
             self.alt_cursor = Some((self.cursor_row, self.cursor_col));
+            self.alt_scroll_counter = Some(self.scroll_counter);
+            std::mem::swap(&mut self.kitty_keyboard, &mut self.kitty_keyboard_saved);
+            self.kitty_keyboard.clear();
             self.cursor_row = 0;
             self.cursor_col = 0;
         }
@@ -465,6 +1198,15 @@ impl TerminalEmulator {
     fn exit_alt_screen(&mut self) {
         if let Some(grid) = self.alt_grid.take() {
             self.grid = grid;
+            self.grid_wrapped = self
+                .alt_wrapped
+                .take()
+                .unwrap_or_else(|| vec![false; self.rows]);
+            self.images.retain(|img| !img.alt);
+            if let Some(counter) = self.alt_scroll_counter.take() {
+                self.scroll_counter = counter;
+            }
+            self.kitty_keyboard = std::mem::take(&mut self.kitty_keyboard_saved);
             if let Some((row, col)) = self.alt_cursor.take() {
                 self.cursor_row = row.min(self.rows.saturating_sub(1));
                 self.cursor_col = col.min(self.cols.saturating_sub(1));
@@ -473,6 +1215,381 @@ impl TerminalEmulator {
     }
 }
 
+// --- Графика ---
+
+impl TerminalEmulator {
+    /// Поставить картинку в позицию курсора.
+    fn place_image(
+        &mut self,
+        size: (usize, usize),
+        rgba: Arc<Vec<u8>>,
+        display_size: (f32, f32),
+        cursor: ImageCursor,
+        kitty: Option<(u32, u32)>,
+    ) {
+        let (cell_w, cell_h) = self.cell_pixel_size;
+        let rows = ((display_size.1 / cell_h).ceil() as usize).max(1);
+        let cols = ((display_size.0 / cell_w).ceil() as usize).max(1);
+        let col = self.cursor_col;
+
+        self.images.push(TermImage {
+            id: self.next_image_id,
+            width: size.0,
+            height: size.1,
+            rgba,
+            display_size,
+            col,
+            rows,
+            anchor: self.scroll_counter + self.cursor_row as i64,
+            alt: self.alt_grid.is_some(),
+            kitty,
+        });
+        self.next_image_id += 1;
+        if self.images.len() > MAX_IMAGES {
+            self.images.remove(0);
+        }
+
+        if cursor == ImageCursor::Stay {
+            return;
+        }
+        for _ in 1..rows.min(self.rows) {
+            self.newline();
+        }
+        self.cursor_col = match cursor {
+            ImageCursor::AfterImage => col + cols,
+            _ => col,
+        }
+        .min(self.cols.saturating_sub(1));
+        self.wrap_next = false;
+    }
+
+    // --- Kitty graphics ---
+
+    fn apc_dispatch(&mut self, body: &[u8]) {
+        let (control, payload) = match kitty::split_apc(body) {
+            Some(parts) => parts,
+            None => return,
+        };
+
+        // Продолжение чанковой передачи: ключи берём из первого чанка
+        let (cmd, payload) = match self.kitty_pending.take() {
+            Some((first, mut data)) => {
+                if data.len() + payload.len() > kitty::MAX_PENDING_BYTES {
+                    // Передача больше любой допустимой картинки — бросаем её целиком
+                    let result = Err("EFBIG:image too large".to_string());
+                    if let Some(response) = first.response(&result) {
+                        self.responses.extend_from_slice(&response);
+                    }
+                    return;
+                }
+                data.extend_from_slice(payload);
+                let more = kitty::Command::parse(control).more;
+                if more {
+                    self.kitty_pending = Some((first, data));
+                    return;
+                }
+                (first, data)
+            }
+            None => {
+                let cmd = kitty::Command::parse(control);
+                if cmd.more {
+                    self.kitty_pending = Some((cmd, payload.to_vec()));
+                    return;
+                }
+                (cmd, payload.to_vec())
+            }
+        };
+
+        let result = self.kitty_command(&cmd, &payload);
+        if let Some(response) = cmd.response(&result) {
+            self.responses.extend_from_slice(&response);
+        }
+    }
+
+    fn kitty_command(&mut self, cmd: &kitty::Command, payload: &[u8]) -> Result<(), String> {
+        match cmd.action {
+            'q' => kitty::decode_payload(cmd, payload).map(|_| ()),
+            't' | 'T' => {
+                let (w, h, rgba) = kitty::decode_payload(cmd, payload)?;
+                let id = self.kitty_image_id(cmd);
+                self.kitty_store(id, w, h, rgba);
+                if cmd.action == 'T' {
+                    self.kitty_place(cmd, id)?;
+                }
+                Ok(())
+            }
+            'p' => {
+                let id = self.kitty_image_id(cmd);
+                self.kitty_place(cmd, id)
+            }
+            'd' => {
+                self.kitty_delete(cmd);
+                Ok(())
+            }
+            _ => Err("EINVAL:unsupported action".to_string()),
+        }
+    }
+
+    /// Запоминает картинку; сверх kitty::MAX_STORED_BYTES вытесняет самые старые.
+    fn kitty_store(&mut self, id: u32, w: usize, h: usize, rgba: Vec<u8>) {
+        self.kitty_images.insert(id, (w, h, Arc::new(rgba)));
+        self.kitty_order.retain(|&i| i != id);
+        self.kitty_order.push_back(id);
+        let mut total: usize = self.kitty_images.values().map(|(_, _, data)| data.len()).sum();
+        while total > kitty::MAX_STORED_BYTES {
+            let Some(oldest) = self.kitty_order.pop_front() else {
+                break;
+            };
+            if oldest == id {
+                self.kitty_order.push_front(oldest);
+                break;
+            }
+            if let Some((_, _, data)) = self.kitty_images.remove(&oldest) {
+                total -= data.len();
+            }
+        }
+    }
+
+    /// id картинки; для I= (номер без id) выдаём свободный id сами.
+    fn kitty_image_id(&self, cmd: &kitty::Command) -> u32 {
+        if cmd.image_id != 0 {
+            return cmd.image_id;
+        }
+        if cmd.image_number != 0 {
+            return u32::MAX - cmd.image_number;
+        }
+        (1..).find(|id| !self.kitty_images.contains_key(id)).unwrap_or(1)
+    }
+
+    fn kitty_place(&mut self, cmd: &kitty::Command, id: u32) -> Result<(), String> {
+        let (w, h, rgba) = self
+            .kitty_images
+            .get(&id)
+            .cloned()
+            .ok_or("ENOENT:no such image")?;
+        let (w, h, rgba) = match kitty::crop(cmd, w, h, &rgba) {
+            Some((cw, ch, cropped)) => (cw, ch, Arc::new(cropped)),
+            None => (w, h, rgba),
+        };
+
+        let (cell_w, cell_h) = self.cell_pixel_size;
+        let (iw, ih) = (w as f32, h as f32);
+        let display_size = match (cmd.cols, cmd.rows) {
+            (0, 0) => (iw, ih),
+            (c, 0) => (c as f32 * cell_w, c as f32 * cell_w * ih / iw),
+            (0, r) => (r as f32 * cell_h * iw / ih, r as f32 * cell_h),
+            (c, r) => (c as f32 * cell_w, r as f32 * cell_h),
+        };
+
+        // Та же пара (картинка, placement) заменяет старое размещение
+        if cmd.placement_id != 0 {
+            self.images
+                .retain(|img| img.kitty != Some((id, cmd.placement_id)));
+        }
+        let cursor = if cmd.no_cursor_move {
+            ImageCursor::Stay
+        } else {
+            ImageCursor::AfterImage
+        };
+        self.place_image((w, h), rgba, display_size, cursor, Some((id, cmd.placement_id)));
+        Ok(())
+    }
+
+    fn kitty_delete(&mut self, cmd: &kitty::Command) {
+        let counter = self.scroll_counter;
+        let (cursor_row, cursor_col) = (self.cursor_row as i64, self.cursor_col);
+        let (cell_w, _) = self.cell_pixel_size;
+        let covers = |img: &TermImage, row: i64, col: usize| {
+            let top = img.anchor - counter;
+            let cols = (img.display_size.0 / cell_w).ceil() as usize;
+            row >= top && row < top + img.rows as i64 && col >= img.col && col < img.col + cols
+        };
+
+        let target_id = self.kitty_image_id(cmd);
+        let matches = |img: &TermImage| -> bool {
+            let (id, placement) = match img.kitty {
+                Some(k) => k,
+                None => return false,
+            };
+            match cmd.delete.to_ascii_lowercase() {
+                'i' | 'n' => {
+                    id == target_id && (cmd.placement_id == 0 || placement == cmd.placement_id)
+                }
+                'c' => covers(img, cursor_row, cursor_col),
+                'p' => covers(
+                    img,
+                    cmd.cell_y.saturating_sub(1) as i64,
+                    cmd.cell_x.saturating_sub(1),
+                ),
+                _ => true,
+            }
+        };
+
+        let removed: Vec<u32> = self
+            .images
+            .iter()
+            .filter(|img| matches(img))
+            .filter_map(|img| img.kitty.map(|(id, _)| id))
+            .collect();
+        self.images.retain(|img| !matches(img));
+
+        // Заглавная буква — освободить и данные картинок без размещений
+        if cmd.delete.is_ascii_uppercase() {
+            let ids: Vec<u32> = if matches!(cmd.delete, 'I' | 'N') {
+                vec![target_id]
+            } else {
+                removed
+            };
+            for id in ids {
+                if !self.images.iter().any(|img| img.kitty.map(|(i, _)| i) == Some(id)) {
+                    self.kitty_images.remove(&id);
+                    self.kitty_order.retain(|&i| i != id);
+                }
+            }
+        }
+    }
+
+    /// OSC 133: A — приглашение, B — начало ввода, C — начало вывода, D;код — конец.
+    fn handle_prompt_mark(&mut self, kind: &[u8], arg: Option<&[u8]>) {
+        // Полноэкранные приложения не размечаем
+        if self.alt_grid.is_some() {
+            return;
+        }
+        let line = self.scroll_counter + self.cursor_row as i64;
+        match kind.first() {
+            Some(b'A') => {
+                // Повторный A на той же строке (перерисовка приглашения) не плодит меток
+                if let Some(last) = self.command_marks.last_mut() {
+                    if last.prompt == line && last.output.is_none() {
+                        return;
+                    }
+                }
+                self.command_marks.push(CommandMark {
+                    prompt: line,
+                    output: None,
+                    end: None,
+                    exit_code: None,
+                    input: None,
+                    started: None,
+                    duration: None,
+                });
+                if self.command_marks.len() > MAX_COMMAND_MARKS {
+                    self.command_marks.remove(0);
+                }
+            }
+            Some(b'B') => {
+                let col = self.cursor_col;
+                if let Some(last) = self.command_marks.last_mut() {
+                    if last.output.is_none() {
+                        last.input = Some((line, col));
+                    }
+                }
+            }
+            Some(b'C') => {
+                if let Some(last) = self.command_marks.last_mut() {
+                    if last.end.is_none() {
+                        last.output = Some(line);
+                        last.started = Some(std::time::Instant::now());
+                    }
+                }
+            }
+            Some(b'D') => {
+                let mut finished = None;
+                if let Some(last) = self.command_marks.last_mut() {
+                    // D без C — пустая команда (просто Enter), код не интересен
+                    if last.output.is_some() && last.end.is_none() {
+                        last.end = Some(line);
+                        last.exit_code = arg
+                            .and_then(|a| std::str::from_utf8(a).ok())
+                            .and_then(|a| a.parse().ok());
+                        last.duration = last.started.map(|t| t.elapsed());
+                        finished = last.duration.map(|d| (last.input, last.exit_code, d));
+                    }
+                }
+                if let Some((input, exit_code, duration)) = finished {
+                    let command = input
+                        .map(|(row, col)| self.line_text_from(row, col))
+                        .unwrap_or_default();
+                    if self.finished_commands.len() < MAX_COMMAND_MARKS {
+                        self.finished_commands.push(FinishedCommand {
+                            command,
+                            exit_code,
+                            duration,
+                        });
+                    }
+                }
+            }
+            _ => {}
+        }
+    }
+
+    /// OSC 1337 (iTerm2 inline images)
+    fn handle_iterm2(&mut self, body: &[u8]) {
+        match iterm2::parse_command(body) {
+            Some(iterm2::Command::File(args, data)) => self.place_iterm2_image(&args, &data),
+            Some(iterm2::Command::MultipartStart(args)) => {
+                self.iterm_multipart = Some((args, Vec::new()));
+            }
+            Some(iterm2::Command::Part(chunk)) => {
+                if let Some((_, buf)) = &mut self.iterm_multipart {
+                    if buf.len() + chunk.len() > kitty::MAX_PENDING_BYTES {
+                        // Больше любой допустимой картинки — передачу бросаем
+                        self.iterm_multipart = None;
+                    } else {
+                        buf.extend_from_slice(&chunk);
+                    }
+                }
+            }
+            Some(iterm2::Command::End) => {
+                if let Some((args, encoded)) = self.iterm_multipart.take() {
+                    if let Some(data) = iterm2::decode_base64(&encoded) {
+                        self.place_iterm2_image(&args, &data);
+                    }
+                }
+            }
+            None => {}
+        }
+    }
+
+    fn place_iterm2_image(&mut self, args: &iterm2::ImageArgs, data: &[u8]) {
+        if !args.is_inline() || data.len() > kitty::MAX_IMAGE_BYTES {
+            return;
+        }
+        if let Some((width, height, rgba)) = iterm2::decode_image(data)
+            .filter(|(w, h, _)| kitty::rgba_len(*w, *h).is_some())
+        {
+            let (cell_w, cell_h) = self.cell_pixel_size;
+            let screen = (self.cols as f32 * cell_w, self.rows as f32 * cell_h);
+            let size = args.display_size((width, height), (cell_w, cell_h), screen);
+            self.place_image((width, height), Arc::new(rgba), size, ImageCursor::LastRow, None);
+        }
+    }
+
+    /// Удалить картинки, целиком ушедшие за пределы scrollback.
+    fn prune_images(&mut self) {
+        let counter = self.scroll_counter;
+        let history = self.scrollback.len() as i64;
+        self.images.retain(|img| {
+            let bottom = img.anchor - counter + img.rows as i64;
+            if img.alt {
+                bottom > 0
+            } else {
+                bottom > -history
+            }
+        });
+    }
+
+    fn clear_screen_images(&mut self) {
+        let in_alt = self.alt_grid.is_some();
+        let counter = self.scroll_counter;
+        let rows = self.rows as i64;
+        self.images.retain(|img| {
+            let top = img.anchor - counter;
+            img.alt != in_alt || top + img.rows as i64 <= 0 || top >= rows
+        });
+    }
+}
+
 impl Perform for TerminalEmulator {
     fn print(&mut self, c: char) {
         self.put_char(c);
@@ -480,7 +1597,7 @@ impl Perform for TerminalEmulator {
 
     fn execute(&mut self, byte: u8) {
         match byte {
-            0x07 => {} // BEL
+            0x07 => self.bell = true, // BEL
             0x08 => {
                 // BS — backspace
                 if self.cursor_col > 0 {
@@ -488,14 +1605,7 @@ impl Perform for TerminalEmulator {
                     self.wrap_next = false;
                 }
             }
-            0x09 => {
-                // HT — tab
-                let next_tab = (self.cursor_col + 1..self.cols)
-                    .find(|&c| self.tab_stops.get(c).copied().unwrap_or(false))
-                    .unwrap_or(self.cols.saturating_sub(1));
-                self.cursor_col = next_tab;
-                self.wrap_next = false;
-            }
+            0x09 => self.tab_forward(1), // HT — tab
             0x0A | 0x0B | 0x0C => {
                 // LF, VT, FF
                 self.newline();
@@ -510,10 +1620,46 @@ impl Perform for TerminalEmulator {
         }
     }
 
-    fn hook(&mut self, _params: &Params, _intermediates: &[u8], _ignore: bool, _action: char) {}
-    fn put(&mut self, _byte: u8) {}
-    fn unhook(&mut self) {}
-    fn osc_dispatch(&mut self, _params: &[&[u8]], _bell_terminated: bool) {}
+    fn hook(&mut self, params: &Params, _intermediates: &[u8], _ignore: bool, action: char) {
+        if action == 'q' {
+            // DECSIXEL
+            let flat: Vec<u16> = params
+                .iter()
+                .map(|p| p.first().copied().unwrap_or(0))
+                .collect();
+            self.sixel = Some(SixelDecoder::new(&flat));
+        }
+    }
+
+    fn put(&mut self, byte: u8) {
+        if let Some(decoder) = &mut self.sixel {
+            decoder.put(byte);
+        }
+    }
+
+    fn unhook(&mut self) {
+        if let Some(decoder) = self.sixel.take() {
+            if let Some((width, height, rgba)) = decoder.finish() {
+                self.place_image(
+                    (width, height),
+                    Arc::new(rgba),
+                    (width as f32, height as f32),
+                    ImageCursor::LastRow,
+                    None,
+                );
+            }
+        }
+    }
+    fn osc_dispatch(&mut self, params: &[&[u8]], _bell_terminated: bool) {
+        if params.first() == Some(&&b"1337"[..]) && params.len() > 1 {
+            // Аргументы сами разделены ';' — склеиваем обратно
+            let body = params[1..].join(&b';');
+            self.handle_iterm2(&body);
+        }
+        if params.first() == Some(&&b"133"[..]) && params.len() > 1 {
+            self.handle_prompt_mark(params[1], params.get(2).copied());
+        }
+    }
 
     fn csi_dispatch(
         &mut self,
@@ -531,6 +1677,11 @@ impl Perform for TerminalEmulator {
         let p2 = flat_params.get(1).copied().unwrap_or(0);
 
         let has_question = intermediates.contains(&b'?');
+        // Приватные маркеры '>', '<', '=' (vte кладёт их в intermediates)
+        let marker = intermediates
+            .first()
+            .copied()
+            .filter(|b| matches!(b, b'>' | b'<' | b'='));
 
         match action {
             'A' => {
@@ -579,7 +1730,7 @@ impl Perform for TerminalEmulator {
                 // CUP — cursor position
                 let row = if p1 == 0 { 1 } else { p1 as usize };
                 let col = if p2 == 0 { 1 } else { p2 as usize };
-                self.cursor_row = (row - 1).min(self.rows.saturating_sub(1));
+                self.cursor_row = self.absolute_row(row - 1);
                 self.cursor_col = (col - 1).min(self.cols.saturating_sub(1));
                 self.wrap_next = false;
             }
@@ -596,9 +1747,11 @@ impl Perform for TerminalEmulator {
                     if self.cursor_row <= self.scroll_bottom {
                         if self.scroll_bottom < self.grid.len() {
                             self.grid.remove(self.scroll_bottom);
+                            self.grid_wrapped.remove(self.scroll_bottom);
                         }
                         self.grid
                             .insert(self.cursor_row, vec![Cell::default(); self.cols]);
+                        self.grid_wrapped.insert(self.cursor_row, false);
                     }
                 }
             }
@@ -608,8 +1761,10 @@ impl Perform for TerminalEmulator {
                 for _ in 0..n {
                     if self.cursor_row <= self.scroll_bottom {
                         self.grid.remove(self.cursor_row);
+                        self.grid_wrapped.remove(self.cursor_row);
                         self.grid
                             .insert(self.scroll_bottom, vec![Cell::default(); self.cols]);
+                        self.grid_wrapped.insert(self.scroll_bottom, false);
                     }
                 }
             }
@@ -662,57 +1817,110 @@ impl Perform for TerminalEmulator {
                     }
                 }
             }
+            'I' => {
+                // CHT — cursor forward tabulation
+                self.tab_forward(p1.max(1) as usize);
+            }
+            'Z' => {
+                // CBT — cursor backward tabulation
+                self.tab_backward(p1.max(1) as usize);
+            }
+            'g' => {
+                // TBC — tab clear: 0 — в текущей колонке, 3 — все
+                match p1 {
+                    0 => {
+                        if let Some(stop) = self.tab_stops.get_mut(self.cursor_col) {
+                            *stop = false;
+                        }
+                    }
+                    3 => self.tab_stops.fill(false),
+                    _ => {}
+                }
+            }
+            'b' => {
+                // REP — повторить последний напечатанный символ
+                if let Some(c) = self.last_char {
+                    let n = (p1.max(1) as usize).min(self.cols * self.rows);
+                    for _ in 0..n {
+                        self.put_char(c);
+                    }
+                }
+            }
             'd' => {
                 // VPA — vertical position absolute
                 let row = if p1 == 0 { 1 } else { p1 as usize };
-                self.cursor_row = (row - 1).min(self.rows.saturating_sub(1));
+                self.cursor_row = self.absolute_row(row - 1);
                 self.wrap_next = false;
             }
-            'h' => {
+            'h' if has_question => {
                 // SM — set mode
-                if has_question {
-                    for &p in &flat_params {
-                        match p {
-                            1 => self.app_cursor_keys = true,
-                            7 => self.auto_wrap = true,
-                            25 => self.cursor_visible = true,
-                            47 | 1047 => self.enter_alt_screen(),
-                            1049 => {
-                                self.saved_cursor = Some((
-                                    self.cursor_row,
-                                    self.cursor_col,
-                                    self.current_attr,
-                                ));
-                                self.enter_alt_screen();
-                            }
-                            _ => {}
+                for &p in &flat_params {
+                    match p {
+                        1 => self.app_cursor_keys = true,
+                        6 => {
+                            self.origin_mode = true;
+                            self.home_cursor();
+                        }
+                        7 => self.auto_wrap = true,
+                        1007 => self.alternate_scroll = true,
+                        25 => self.cursor_visible = true,
+                        47 | 1047 => self.enter_alt_screen(),
+                        1049 => {
+                            self.saved_cursor = Some((
+                                self.cursor_row,
+                                self.cursor_col,
+                                self.current_attr,
+                            ));
+                            self.enter_alt_screen();
                         }
+                        _ => {}
                     }
                 }
             }
-            'l' => {
+            'l' if has_question => {
                 // RM — reset mode
-                if has_question {
-                    for &p in &flat_params {
-                        match p {
-                            1 => self.app_cursor_keys = false,
-                            7 => self.auto_wrap = false,
-                            25 => self.cursor_visible = false,
-                            47 | 1047 => self.exit_alt_screen(),
-                            1049 => {
-                                self.exit_alt_screen();
-                                if let Some((row, col, attr)) = self.saved_cursor.take() {
-                                    self.cursor_row = row.min(self.rows.saturating_sub(1));
-                                    self.cursor_col = col.min(self.cols.saturating_sub(1));
-                                    self.current_attr = attr;
-                                }
+                for &p in &flat_params {
+                    match p {
+                        1 => self.app_cursor_keys = false,
+                        6 => {
+                            self.origin_mode = false;
+                            self.home_cursor();
+                        }
+                        7 => self.auto_wrap = false,
+                        1007 => self.alternate_scroll = false,
+                        25 => self.cursor_visible = false,
+                        47 | 1047 => self.exit_alt_screen(),
+                        1049 => {
+                            self.exit_alt_screen();
+                            if let Some((row, col, attr)) = self.saved_cursor.take() {
+                                self.cursor_row = row.min(self.rows.saturating_sub(1));
+                                self.cursor_col = col.min(self.cols.saturating_sub(1));
+                                self.current_attr = attr;
                             }
-                            _ => {}
                         }
+                        _ => {}
                     }
                 }
             }
-            'm' => {
+            'h' if marker.is_none() && flat_params.contains(&4) => {
+                // SM 4 — IRM insert mode
+                self.insert_mode = true;
+            }
+            'l' if marker.is_none() && flat_params.contains(&4) => {
+                // RM 4 — replace mode
+                self.insert_mode = false;
+            }
+            'p' if intermediates == b"!" => self.soft_reset(),
+            'm' if marker == Some(b'>') && p1 == 4 => {
+                // xterm modifyOtherKeys: CSI > 4 ; n m (без n — выключить)
+                self.modify_other_keys = p2.min(2);
+            }
+            'm' if has_question && p1 == 4 => {
+                // Запрос modifyOtherKeys: CSI ? 4 m → CSI > 4 ; n m
+                let response = format!("\x1b[>4;{}m", self.modify_other_keys);
+                self.responses.extend_from_slice(response.as_bytes());
+            }
+            'm' if marker.is_none() && !has_question => {
                 // SGR — select graphic rendition
                 if flat_params.is_empty() {
                     self.current_attr = CellAttr::default();
@@ -720,9 +1928,8 @@ impl Perform for TerminalEmulator {
                     self.handle_sgr(&flat_params);
                 }
             }
-            'n' => {
-                // DSR — device status report (игнорируем)
-            }
+            'n' if marker.is_none() => self.device_status_report(p1, has_question),
+            'c' if p1 == 0 => self.device_attributes(marker),
             'r' => {
                 // DECSTBM — set scroll region
                 if !has_question {
@@ -734,8 +1941,7 @@ impl Perform for TerminalEmulator {
                         self.scroll_top = 0;
                         self.scroll_bottom = self.rows.saturating_sub(1);
                     }
-                    self.cursor_row = self.scroll_top;
-                    self.cursor_col = 0;
+                    self.home_cursor();
                 }
             }
             's' => {
@@ -745,6 +1951,9 @@ impl Perform for TerminalEmulator {
                         Some((self.cursor_row, self.cursor_col, self.current_attr));
                 }
             }
+            'u' if marker.is_some() || has_question => {
+                self.handle_kitty_keyboard(marker, has_question, p1, p2);
+            }
             'u' => {
                 // SCORC — restore cursor
                 if let Some((row, col, attr)) = self.saved_cursor {
@@ -781,6 +1990,12 @@ impl Perform for TerminalEmulator {
                 self.cursor_col = 0;
                 self.newline();
             }
+            b'H' => {
+                // HTS — set tab stop
+                if let Some(stop) = self.tab_stops.get_mut(self.cursor_col) {
+                    *stop = true;
+                }
+            }
             b'M' => {
                 // RI — reverse index
                 if self.cursor_row == self.scroll_top {
@@ -790,12 +2005,21 @@ impl Perform for TerminalEmulator {
                 }
             }
             b'c' => {
-                // RIS — full reset
+                // RIS — full reset. Настройки со стороны приложения (захват строк
+                // для триггеров, размер ячейки) хост не сбрасывает; счётчик
+                // изменений только растёт, чтобы кеши увидели сброс
                 let cols = self.cols;
                 let rows = self.rows;
+                let line_capture = self.line_capture;
+                let cell_pixel_size = self.cell_pixel_size;
+                let generation = self.generation;
                 *self = Self::new(cols, rows);
+                self.line_capture = line_capture;
+                self.cell_pixel_size = cell_pixel_size;
+                self.generation = generation + 1;
             }
             _ => {}
         }
     }
 }
+
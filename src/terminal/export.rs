@@ -0,0 +1,172 @@
+use crate::terminal::emulator::{Cell, CellAttr, TermColor};
+use crate::terminal::palette::ColorScheme;
+
+// Сериализация ячеек терминала: plain text, ANSI SGR, HTML.
+// Используется копированием выделения и экспортом буфера.
+
+/// Обрезает хвостовые пустые ячейки строки (пробел без фона).
+pub fn trim_line(line: &[Cell]) -> &[Cell] {
+    let end = line
+        .iter()
+        .rposition(|cell| {
+            !((cell.c == ' ' || cell.c == '\0')
+                && cell.attr.bg == TermColor::Default
+                && !cell.attr.inverse)
+        })
+        .map(|i| i + 1)
+        .unwrap_or(0);
+    &line[..end]
+}
+
+pub fn cells_to_text(lines: &[Vec<Cell>]) -> String {
+    lines
+        .iter()
+        .map(|line| {
+            let mut text = String::new();
+            for cell in line {
+                cell.push_text(&mut text);
+            }
+            text.trim_end().to_string()
+        })
+        .collect::<Vec<_>>()
+        .join("\n")
+}
+
+/// Текст с ANSI SGR-последовательностями — воспроизводит цвета при `cat` в терминале.
+pub fn cells_to_ansi(lines: &[Vec<Cell>]) -> String {
+    let mut out = String::new();
+
+    for (i, line) in lines.iter().enumerate() {
+        if i > 0 {
+            out.push('\n');
+        }
+        let mut current = CellAttr::default();
+        for cell in trim_line(line) {
+            if cell.attr != current {
+                out.push_str(&sgr_sequence(&cell.attr));
+                current = cell.attr;
+            }
+            cell.push_text(&mut out);
+        }
+        if current != CellAttr::default() {
+            out.push_str("\x1b[0m");
+        }
+    }
+
+    out
+}
+
+/// HTML-фрагмент `<pre>` со span'ами на каждый участок с одинаковыми атрибутами.
+/// Цвета берутся из той же палитры, что и рендер.
+pub fn cells_to_html(lines: &[Vec<Cell>], scheme: &ColorScheme) -> String {
+    let bg_default = scheme.background_color();
+    let fg_default = scheme.foreground_color();
+    let mut out = format!(
+        "<pre style=\"background:{};color:{};font-family:monospace;padding:8px\">",
+        css_color(bg_default),
+        css_color(fg_default),
+    );
+
+    for (i, line) in lines.iter().enumerate() {
+        if i > 0 {
+            out.push('\n');
+        }
+        let mut run = String::new();
+        let mut run_attr: Option<CellAttr> = None;
+        for cell in trim_line(line) {
+            if run_attr != Some(cell.attr) {
+                if let Some(attr) = run_attr {
+                    push_html_span(&mut out, &run, &attr, scheme);
+                }
+                run.clear();
+                run_attr = Some(cell.attr);
+            }
+            cell.push_text(&mut run);
+        }
+        if let Some(attr) = run_attr {
+            push_html_span(&mut out, &run, &attr, scheme);
+        }
+    }
+
+    out.push_str("</pre>");
+    out
+}
+
+fn push_html_span(out: &mut String, text: &str, attr: &CellAttr, scheme: &ColorScheme) {
+    let escaped = html_escape(text);
+    if *attr == CellAttr::default() {
+        out.push_str(&escaped);
+        return;
+    }
+
+    let (fg, bg) = scheme.resolve(&Cell {
+        attr: *attr,
+        ..Cell::default()
+    });
+    let mut style = format!("color:{}", css_color(fg));
+    if bg != scheme.background_color() {
+        style.push_str(&format!(";background:{}", css_color(bg)));
+    }
+    if attr.bold {
+        style.push_str(";font-weight:bold");
+    }
+    if attr.italic {
+        style.push_str(";font-style:italic");
+    }
+    if attr.underline {
+        style.push_str(";text-decoration:underline");
+    }
+    out.push_str(&format!("<span style=\"{}\">{}</span>", style, escaped));
+}
+
+fn sgr_sequence(attr: &CellAttr) -> String {
+    let mut params = vec!["0".to_string()];
+    if attr.bold {
+        params.push("1".to_string());
+    }
+    if attr.italic {
+        params.push("3".to_string());
+    }
+    if attr.underline {
+        params.push("4".to_string());
+    }
+    if attr.inverse {
+        params.push("7".to_string());
+    }
+    if let Some(p) = color_params(attr.fg, 30) {
+        params.push(p);
+    }
+    if let Some(p) = color_params(attr.bg, 40) {
+        params.push(p);
+    }
+    format!("\x1b[{}m", params.join(";"))
+}
+
+/// base = 30 для fg, 40 для bg
+fn color_params(color: TermColor, base: u16) -> Option<String> {
+    match color {
+        TermColor::Default => None,
+        TermColor::Indexed(idx) if idx < 8 => Some(format!("{}", base + idx as u16)),
+        TermColor::Indexed(idx) if idx < 16 => Some(format!("{}", base + 60 + (idx - 8) as u16)),
+        TermColor::Indexed(idx) => Some(format!("{};5;{}", base + 8, idx)),
+        TermColor::Rgb(r, g, b) => Some(format!("{};2;{};{};{}", base + 8, r, g, b)),
+    }
+}
+
+fn css_color(color: egui::Color32) -> String {
+    format!("#{:02x}{:02x}{:02x}", color.r(), color.g(), color.b())
+}
+
+fn html_escape(text: &str) -> String {
+    let mut out = String::with_capacity(text.len());
+    for c in text.chars() {
+        match c {
+            '&' => out.push_str("&amp;"),
+            '<' => out.push_str("&lt;"),
+            '>' => out.push_str("&gt;"),
+            '"' => out.push_str("&quot;"),
+            _ => out.push(c),
+        }
+    }
+    out
+}
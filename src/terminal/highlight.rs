@@ -0,0 +1,166 @@
+use serde::{Deserialize, Serialize};
+
+/// Правило подсветки/триггера: regex по выводу терминала.
+/// Хранится в SessionConfig (per-session).
+#[derive(Clone, Serialize, Deserialize)]
+pub struct HighlightRule {
+    pub pattern: String,
+    /// Цвет текста совпадения (RGB)
+    pub color: [u8; 3],
+    #[serde(default = "default_enabled")]
+    pub enabled: bool,
+    /// Уведомление при появлении совпадения в новом выводе
+    #[serde(default)]
+    pub notify: bool,
+    /// Локальная команда при срабатывании (пусто — нет).
+    /// Получает SSHERALD_SESSION, SSHERALD_PATTERN и SSHERALD_LINE в окружении.
+    #[serde(default)]
+    pub action: String,
+}
+
+fn default_enabled() -> bool {
+    true
+}
+
+impl Default for HighlightRule {
+    fn default() -> Self {
+        HighlightRule {
+            pattern: String::new(),
+            color: [0xff, 0x33, 0x33],
+            enabled: true,
+            notify: false,
+            action: String::new(),
+        }
+    }
+}
+
+/// Срабатывание триггера на завершённой строке вывода.
+pub struct RuleHit {
+    pub pattern: String,
+    pub line: String,
+    pub notify: bool,
+    pub action: String,
+}
+
+// Минимальный интервал между срабатываниями одного правила
+const TRIGGER_COOLDOWN: std::time::Duration = std::time::Duration::from_secs(2);
+
+struct CompiledRule {
+    rule: HighlightRule,
+    regex: regex::Regex,
+    last_fired: Option<std::time::Instant>,
+}
+
+/// Скомпилированный набор правил сессии.
+#[derive(Default)]
+pub struct Highlighter {
+    rules: Vec<CompiledRule>,
+}
+
+impl Highlighter {
+    /// Невалидные и выключенные правила пропускаются.
+    pub fn new(rules: &[HighlightRule]) -> Self {
+        let rules = rules
+            .iter()
+            .filter(|r| r.enabled && !r.pattern.is_empty())
+            .filter_map(|r| {
+                regex::Regex::new(&r.pattern).ok().map(|regex| CompiledRule {
+                    rule: r.clone(),
+                    regex,
+                    last_fired: None,
+                })
+            })
+            .collect();
+        Highlighter { rules }
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.rules.is_empty()
+    }
+
+    pub fn has_triggers(&self) -> bool {
+        self.rules
+            .iter()
+            .any(|r| r.rule.notify || !r.rule.action.trim().is_empty())
+    }
+
+    /// Участки строки для подсветки: (начальная колонка, конечная колонка exclusive, цвет).
+    /// Одна ячейка = один символ, поэтому индекс символа совпадает с колонкой.
+    pub fn spans(&self, text: &str) -> Vec<(usize, usize, egui::Color32)> {
+        let mut spans = Vec::new();
+        for compiled in &self.rules {
+            let [r, g, b] = compiled.rule.color;
+            let color = egui::Color32::from_rgb(r, g, b);
+            for m in compiled.regex.find_iter(text) {
+                if m.start() == m.end() {
+                    continue;
+                }
+                let start = text[..m.start()].chars().count();
+                let end = start + m.as_str().chars().count();
+                spans.push((start, end, color));
+            }
+        }
+        spans
+    }
+
+    /// Проверить завершённые строки вывода на триггеры.
+    pub fn check_lines(&mut self, lines: &[String]) -> Vec<RuleHit> {
+        let now = std::time::Instant::now();
+        let mut hits = Vec::new();
+        for compiled in &mut self.rules {
+            if !compiled.rule.notify && compiled.rule.action.trim().is_empty() {
+                continue;
+            }
+            for line in lines {
+                if !compiled.regex.is_match(line) {
+                    continue;
+                }
+                if compiled
+                    .last_fired
+                    .is_some_and(|t| now.duration_since(t) < TRIGGER_COOLDOWN)
+                {
+                    break;
+                }
+                compiled.last_fired = Some(now);
+                hits.push(RuleHit {
+                    pattern: compiled.rule.pattern.clone(),
+                    line: line.trim_end().to_string(),
+                    notify: compiled.rule.notify,
+                    action: compiled.rule.action.clone(),
+                });
+                break;
+            }
+        }
+        hits
+    }
+}
+
+/// Запуск локальной команды триггера (fire-and-forget).
+pub fn run_action(command: &str, session_name: &str, hit: &RuleHit) {
+    #[cfg(windows)]
+    let mut cmd = {
+        let mut c = std::process::Command::new("cmd");
+        c.args(["/C", command]);
+        c
+    };
+    #[cfg(not(windows))]
+    let mut cmd = {
+        let mut c = std::process::Command::new("sh");
+        c.args(["-c", command]);
+        c
+    };
+    let spawned = cmd
+        .env("SSHERALD_SESSION", session_name)
+        .env("SSHERALD_PATTERN", &hit.pattern)
+        .env("SSHERALD_LINE", &hit.line)
+        .stdin(std::process::Stdio::null())
+        .stdout(std::process::Stdio::null())
+        .stderr(std::process::Stdio::null())
+        .spawn();
+    // Ждём в стороне, иначе каждый сработавший триггер оставляет зомби
+    if let Ok(mut child) = spawned {
+        std::thread::spawn(move || {
+            let _ = child.wait();
+        });
+    }
+}
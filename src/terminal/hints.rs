@@ -0,0 +1,105 @@
+use std::sync::OnceLock;
+
+// Hint mode (как tmux-thumbs): находим в видимом буфере токены,
+// которые часто нужно скопировать, и помечаем их короткими метками.
+
+/// Алфавит меток — сначала домашний ряд.
+const LABEL_ALPHABET: &str = "asdfghjklqwertyuiopzxcvbnm";
+
+pub struct Hint {
+    pub row: usize,
+    pub start_col: usize,
+    /// Exclusive
+    pub end_col: usize,
+    pub text: String,
+    pub label: String,
+}
+
+fn patterns() -> &'static [regex::Regex] {
+    static PATTERNS: OnceLock<Vec<regex::Regex>> = OnceLock::new();
+    PATTERNS.get_or_init(|| {
+        [
+            // UUID
+            r"\b[0-9a-fA-F]{8}-[0-9a-fA-F]{4}-[0-9a-fA-F]{4}-[0-9a-fA-F]{4}-[0-9a-fA-F]{12}\b",
+            // IPv4 (+ порт, + маска)
+            r"\b(?:\d{1,3}\.){3}\d{1,3}(?::\d{1,5}|/\d{1,2})?\b",
+            // IPv6 (упрощённо: минимум три группы)
+            r"\b(?:[0-9a-fA-F]{1,4}:){2,7}(?::|[0-9a-fA-F]{1,4})\b",
+            // Пути: абсолютные, ~/, ./, ../
+            r"(?:~|\.{1,2})?/[\w.\-@+]+(?:/[\w.\-@+]*)*",
+            // git-хэши и ID контейнеров (7–64 hex, хотя бы одна цифра)
+            r"\b[0-9a-f]*[0-9][0-9a-f]*\b",
+        ]
+        .iter()
+        .map(|p| regex::Regex::new(p).expect("hint pattern"))
+        .collect()
+    })
+}
+
+fn is_hex_id(text: &str) -> bool {
+    (7..=64).contains(&text.len()) && text.chars().any(|c| c.is_ascii_alphabetic())
+}
+
+/// Найти токены в строках экрана. Строка = одна строка терминала,
+/// индекс символа = колонка.
+pub fn find_hints(lines: &[String]) -> Vec<Hint> {
+    let mut hints: Vec<Hint> = Vec::new();
+
+    for (row, line) in lines.iter().enumerate() {
+        // (start, end, text) в колонках; раньше в списке — выше приоритет
+        let mut found: Vec<(usize, usize, String)> = Vec::new();
+        for (idx, pattern) in patterns().iter().enumerate() {
+            for m in pattern.find_iter(line) {
+                let text = m.as_str();
+                // Шестнадцатеричный паттерн ловит и обычные числа — фильтруем
+                if idx == 4 && !is_hex_id(text) {
+                    continue;
+                }
+                // Одиночный "/" или "./" бесполезен
+                if idx == 3 && text.trim_start_matches(['~', '.']).len() < 2 {
+                    continue;
+                }
+                let start = line[..m.start()].chars().count();
+                let end = start + text.chars().count();
+                if found.iter().any(|(s, e, _)| start < *e && end > *s) {
+                    continue;
+                }
+                found.push((start, end, text.to_string()));
+            }
+        }
+        found.sort_by_key(|(start, _, _)| *start);
+        hints.extend(found.into_iter().map(|(start_col, end_col, text)| Hint {
+            row,
+            start_col,
+            end_col,
+            text,
+            label: String::new(),
+        }));
+    }
+
+    // Метки раздаём снизу вверх: свежий вывод получает самые удобные
+    let labels = make_labels(hints.len());
+    for (hint, label) in hints.iter_mut().rev().zip(labels) {
+        hint.label = label;
+    }
+    hints.retain(|h| !h.label.is_empty());
+    hints
+}
+
+/// Метки одинаковой длины, чтобы ни одна не была префиксом другой.
+fn make_labels(count: usize) -> Vec<String> {
+    let alphabet: Vec<char> = LABEL_ALPHABET.chars().collect();
+    if count <= alphabet.len() {
+        return alphabet.iter().take(count).map(|c| c.to_string()).collect();
+    }
+    let mut labels = Vec::with_capacity(count);
+    'outer: for a in &alphabet {
+        for b in &alphabet {
+            if labels.len() >= count {
+                break 'outer;
+            }
+            labels.push(format!("{}{}", a, b));
+        }
+    }
+    labels
+}
@@ -0,0 +1,161 @@
+use base64::Engine;
+
+// iTerm2 inline images: OSC 1337 ; File=[args]:<base64> ST
+// Также поддерживается многочастная форма:
+// MultipartFile=[args] / FilePart=<base64> / FileEnd.
+
+/// Размер по одной оси из аргументов width=/height=
+#[derive(Clone, Copy)]
+enum Dimension {
+    Auto,
+    Cells(f32),
+    Pixels(f32),
+    Percent(f32),
+}
+
+impl Dimension {
+    fn parse(value: &str) -> Dimension {
+        let value = value.trim();
+        if value.is_empty() || value == "auto" {
+            Dimension::Auto
+        } else if let Some(px) = value.strip_suffix("px") {
+            px.parse().map(Dimension::Pixels).unwrap_or(Dimension::Auto)
+        } else if let Some(pct) = value.strip_suffix('%') {
+            pct.parse().map(Dimension::Percent).unwrap_or(Dimension::Auto)
+        } else {
+            value.parse().map(Dimension::Cells).unwrap_or(Dimension::Auto)
+        }
+    }
+
+    /// В пиксели; `cell` — размер ячейки, `total` — размер терминала (px).
+    fn resolve(self, cell: f32, total: f32) -> Option<f32> {
+        match self {
+            Dimension::Auto => None,
+            Dimension::Cells(n) => Some(n * cell),
+            Dimension::Pixels(n) => Some(n),
+            Dimension::Percent(n) => Some(total * n / 100.0),
+        }
+    }
+}
+
+pub struct ImageArgs {
+    width: Dimension,
+    height: Dimension,
+    preserve_aspect: bool,
+    inline: bool,
+}
+
+impl ImageArgs {
+    /// `name=...;size=...;width=...;inline=1`
+    pub fn parse(args: &str) -> ImageArgs {
+        let mut parsed = ImageArgs {
+            width: Dimension::Auto,
+            height: Dimension::Auto,
+            preserve_aspect: true,
+            inline: false,
+        };
+        for pair in args.split(';') {
+            let (key, value) = match pair.split_once('=') {
+                Some(kv) => kv,
+                None => continue,
+            };
+            match key {
+                "width" => parsed.width = Dimension::parse(value),
+                "height" => parsed.height = Dimension::parse(value),
+                "preserveAspectRatio" => parsed.preserve_aspect = value != "0",
+                "inline" => parsed.inline = value == "1",
+                _ => {}
+            }
+        }
+        parsed
+    }
+
+    /// Без inline=1 iTerm2 предлагает скачать файл — картинку не показываем.
+    pub fn is_inline(&self) -> bool {
+        self.inline
+    }
+
+    /// Итоговый размер на экране в пикселях.
+    /// `cell` — размер ячейки, `screen` — размер терминала (px).
+    pub fn display_size(
+        &self,
+        image: (usize, usize),
+        cell: (f32, f32),
+        screen: (f32, f32),
+    ) -> (f32, f32) {
+        let (iw, ih) = (image.0.max(1) as f32, image.1.max(1) as f32);
+        let w = self.width.resolve(cell.0, screen.0);
+        let h = self.height.resolve(cell.1, screen.1);
+
+        let (mut w, mut h) = match (w, h) {
+            (None, None) => (iw, ih),
+            (Some(w), None) => (w, w * ih / iw),
+            (None, Some(h)) => (h * iw / ih, h),
+            (Some(w), Some(h)) if self.preserve_aspect => {
+                let scale = (w / iw).min(h / ih);
+                (iw * scale, ih * scale)
+            }
+            (Some(w), Some(h)) => (w, h),
+        };
+
+        // Не шире терминала
+        if w > screen.0 && screen.0 > 0.0 {
+            let scale = screen.0 / w;
+            w *= scale;
+            if self.preserve_aspect {
+                h *= scale;
+            }
+        }
+        (w.max(1.0), h.max(1.0))
+    }
+}
+
+/// Разбор тела OSC 1337 (всё после "1337;").
+pub enum Command {
+    /// Картинка целиком
+    File(ImageArgs, Vec<u8>),
+    MultipartStart(ImageArgs),
+    /// Кусок base64 — декодируется целиком на FileEnd
+    Part(Vec<u8>),
+    End,
+}
+
+pub fn parse_command(body: &[u8]) -> Option<Command> {
+    if let Some(rest) = body.strip_prefix(b"File=") {
+        let colon = rest.iter().position(|&b| b == b':')?;
+        let args = ImageArgs::parse(&String::from_utf8_lossy(&rest[..colon]));
+        let data = decode_base64(&rest[colon + 1..])?;
+        return Some(Command::File(args, data));
+    }
+    if let Some(rest) = body.strip_prefix(b"MultipartFile=") {
+        return Some(Command::MultipartStart(ImageArgs::parse(
+            &String::from_utf8_lossy(rest),
+        )));
+    }
+    if let Some(rest) = body.strip_prefix(b"FilePart=") {
+        return Some(Command::Part(rest.to_vec()));
+    }
+    if body == b"FileEnd" {
+        return Some(Command::End);
+    }
+    None
+}
+
+pub fn decode_base64(data: &[u8]) -> Option<Vec<u8>> {
+    let cleaned: Vec<u8> = data
+        .iter()
+        .copied()
+        .filter(|b| !b.is_ascii_whitespace())
+        .collect();
+    base64::engine::general_purpose::STANDARD
+        .decode(&cleaned)
+        .or_else(|_| base64::engine::general_purpose::STANDARD_NO_PAD.decode(&cleaned))
+        .ok()
+}
+
+/// PNG / JPEG / GIF → RGBA
+pub fn decode_image(data: &[u8]) -> Option<(usize, usize, Vec<u8>)> {
+    let image = image::load_from_memory(data).ok()?.to_rgba8();
+    let (w, h) = image.dimensions();
+    Some((w as usize, h as usize, image.into_raw()))
+}
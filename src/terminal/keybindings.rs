@@ -0,0 +1,340 @@
+use parking_lot::RwLock;
+use serde::{Deserialize, Serialize};
+use std::sync::atomic::{AtomicBool, AtomicU32, Ordering};
+
+/// Действие терминала, которое можно повесить на сочетание клавиш.
+#[derive(Clone, Copy, PartialEq, Eq, Debug, Serialize, Deserialize)]
+pub enum Action {
+    Copy,
+    Paste,
+    ZoomIn,
+    ZoomOut,
+    ZoomReset,
+    ScrollPageUp,
+    ScrollPageDown,
+    ScrollTop,
+    ScrollBottom,
+    PrevPrompt,
+    NextPrompt,
+    Search,
+    CopyMode,
+    Hints,
+}
+
+impl Action {
+    pub const ALL: [Action; 14] = [
+        Action::Copy,
+        Action::Paste,
+        Action::ZoomIn,
+        Action::ZoomOut,
+        Action::ZoomReset,
+        Action::ScrollPageUp,
+        Action::ScrollPageDown,
+        Action::ScrollTop,
+        Action::ScrollBottom,
+        Action::PrevPrompt,
+        Action::NextPrompt,
+        Action::Search,
+        Action::CopyMode,
+        Action::Hints,
+    ];
+
+    pub fn label(self) -> &'static str {
+        match self {
+            Action::Copy => "copy",
+            Action::Paste => "paste",
+            Action::ZoomIn => "zoom in",
+            Action::ZoomOut => "zoom out",
+            Action::ZoomReset => "zoom reset",
+            Action::ScrollPageUp => "scroll page up",
+            Action::ScrollPageDown => "scroll page down",
+            Action::ScrollTop => "scroll to top",
+            Action::ScrollBottom => "scroll to bottom",
+            Action::PrevPrompt => "previous prompt",
+            Action::NextPrompt => "next prompt",
+            Action::Search => "search",
+            Action::CopyMode => "copy mode",
+            Action::Hints => "hints",
+        }
+    }
+
+    /// Сочетания по умолчанию в формате keybindings.json: через запятую,
+    /// как их читает `KeyChord::parse_list`.
+    pub fn default_keys(self) -> &'static str {
+        match self {
+            Action::Copy => "Ctrl+Shift+C",
+            Action::Paste => "Ctrl+Shift+V",
+            Action::ZoomIn => "Ctrl+Equals, Ctrl+Shift+Plus",
+            Action::ZoomOut => "Ctrl+Minus",
+            Action::ZoomReset => "Ctrl+0",
+            Action::ScrollPageUp => "Shift+PageUp",
+            Action::ScrollPageDown => "Shift+PageDown",
+            Action::ScrollTop => "Shift+Home",
+            Action::ScrollBottom => "Shift+End",
+            Action::PrevPrompt => "Ctrl+Shift+Up",
+            Action::NextPrompt => "Ctrl+Shift+Down",
+            Action::Search => "Ctrl+Shift+F",
+            Action::CopyMode => "Ctrl+Shift+Space",
+            Action::Hints => "Ctrl+Shift+H",
+        }
+    }
+}
+
+/// Сочетание клавиш: "Ctrl+Shift+C", "Alt+F5", "PageUp".
+/// Модификаторы сравниваются точно.
+#[derive(Clone, Copy, PartialEq, Eq, Debug)]
+pub struct KeyChord {
+    pub key: egui::Key,
+    pub ctrl: bool,
+    pub shift: bool,
+    pub alt: bool,
+}
+
+impl KeyChord {
+    pub fn parse(s: &str) -> Option<Self> {
+        let s = s.trim();
+        // "Ctrl++" — последний '+' это сама клавиша
+        let (mods, key) = match s.strip_suffix("++") {
+            Some(rest) => (rest, "+"),
+            None => match s.rsplit_once('+') {
+                Some((mods, key)) => (mods, key),
+                None => ("", s),
+            },
+        };
+        let mut chord = KeyChord {
+            key: egui::Key::from_name(key.trim())?,
+            ctrl: false,
+            shift: false,
+            alt: false,
+        };
+        for m in mods.split('+').map(str::trim).filter(|m| !m.is_empty()) {
+            match m.to_ascii_lowercase().as_str() {
+                "ctrl" | "control" => chord.ctrl = true,
+                "shift" => chord.shift = true,
+                "alt" | "meta" => chord.alt = true,
+                _ => return None,
+            }
+        }
+        Some(chord)
+    }
+
+    /// Несколько сочетаний через запятую; пустая строка — без привязки.
+    pub fn parse_list(s: &str) -> Option<Vec<Self>> {
+        s.split(',')
+            .map(str::trim)
+            .filter(|part| !part.is_empty())
+            .map(Self::parse)
+            .collect()
+    }
+
+    pub fn matches(&self, key: egui::Key, modifiers: egui::Modifiers) -> bool {
+        self.key == key
+            && self.ctrl == modifiers.ctrl
+            && self.shift == modifiers.shift
+            && self.alt == modifiers.alt
+    }
+}
+
+/// Привязка действия (элемент keybindings.json).
+#[derive(Clone, Debug, Serialize, Deserialize)]
+pub struct ActionBinding {
+    pub action: Action,
+    pub keys: String,
+}
+
+/// Глобальная карта действий. Хранится в keybindings.json.
+#[derive(Clone, Debug, Serialize, Deserialize)]
+pub struct KeyMap {
+    pub bindings: Vec<ActionBinding>,
+    /// Alt как Meta: Alt+клавиша отправляет ESC перед символом (M-b, M-f в readline)
+    #[serde(default = "default_alt_meta")]
+    pub alt_meta: bool,
+    /// Колёсико на альтернативном экране (less, vim) — столько стрелок на щелчок, 0 — выкл.
+    #[serde(default = "default_alt_scroll_lines")]
+    pub alt_scroll_lines: u32,
+}
+
+fn default_alt_meta() -> bool {
+    true
+}
+
+fn default_alt_scroll_lines() -> u32 {
+    3
+}
+
+impl Default for KeyMap {
+    fn default() -> Self {
+        KeyMap {
+            bindings: Action::ALL
+                .iter()
+                .map(|&action| ActionBinding {
+                    action,
+                    keys: action.default_keys().to_string(),
+                })
+                .collect(),
+            alt_meta: true,
+            alt_scroll_lines: default_alt_scroll_lines(),
+        }
+    }
+}
+
+impl KeyMap {
+    /// Добавляет действия, которых нет в файле (появились в новой версии),
+    /// и убирает дубликаты. Порядок — как в Action::ALL. Сочетание по умолчанию,
+    /// уже занятое в файле, новому действию не достаётся.
+    pub fn normalized(mut self) -> Self {
+        let taken: Vec<KeyChord> = self
+            .bindings
+            .iter()
+            .flat_map(|b| KeyChord::parse_list(&b.keys).unwrap_or_default())
+            .collect();
+        let bindings = Action::ALL
+            .iter()
+            .map(|&action| {
+                self.bindings
+                    .iter()
+                    .find(|b| b.action == action)
+                    .cloned()
+                    .unwrap_or_else(|| ActionBinding {
+                        action,
+                        keys: action
+                            .default_keys()
+                            .split(',')
+                            .map(str::trim)
+                            .filter(|part| {
+                                KeyChord::parse(part).is_some_and(|c| !taken.contains(&c))
+                            })
+                            .collect::<Vec<_>>()
+                            .join(", "),
+                    })
+            })
+            .collect();
+        self.bindings = bindings;
+        self
+    }
+
+    pub fn keys_for(&self, action: Action) -> &str {
+        self.bindings
+            .iter()
+            .find(|b| b.action == action)
+            .map(|b| b.keys.as_str())
+            .unwrap_or("")
+    }
+}
+
+// Активная карта — её читают все TerminalWidget (включая панели и tmux).
+// Невалидные сочетания пропускаются.
+static ACTIVE: RwLock<Vec<(KeyChord, Action)>> = RwLock::new(Vec::new());
+static ACTIVE_MAP: RwLock<Option<KeyMap>> = RwLock::new(None);
+static ALT_META: AtomicBool = AtomicBool::new(true);
+static ALT_SCROLL_LINES: AtomicU32 = AtomicU32::new(3);
+
+pub fn install(map: &KeyMap) {
+    let chords = map
+        .bindings
+        .iter()
+        .flat_map(|b| {
+            KeyChord::parse_list(&b.keys)
+                .unwrap_or_default()
+                .into_iter()
+                .map(move |chord| (chord, b.action))
+        })
+        .collect();
+    *ACTIVE.write() = chords;
+    *ACTIVE_MAP.write() = Some(map.clone());
+    ALT_META.store(map.alt_meta, Ordering::Relaxed);
+    ALT_SCROLL_LINES.store(map.alt_scroll_lines, Ordering::Relaxed);
+}
+
+pub fn action_for(key: egui::Key, modifiers: egui::Modifiers) -> Option<Action> {
+    ACTIVE
+        .read()
+        .iter()
+        .find(|(chord, _)| chord.matches(key, modifiers))
+        .map(|(_, action)| *action)
+}
+
+pub fn alt_meta() -> bool {
+    ALT_META.load(Ordering::Relaxed)
+}
+
+pub fn alt_scroll_lines() -> u32 {
+    ALT_SCROLL_LINES.load(Ordering::Relaxed)
+}
+
+/// Копия активной карты (для редактирования).
+pub fn current() -> KeyMap {
+    ACTIVE_MAP.read().clone().unwrap_or_default()
+}
+
+/// Текст сочетаний действия — для подписей в меню.
+pub fn keys_for(action: Action) -> String {
+    ACTIVE_MAP
+        .read()
+        .as_ref()
+        .map(|map| map.keys_for(action).to_string())
+        .unwrap_or_else(|| action.default_keys().to_string())
+}
+
+/// Своя последовательность сессии: сочетание → байты в канал.
+/// Хранится в SessionConfig (per-session), проверяется раньше действий.
+#[derive(Clone, Default, Serialize, Deserialize)]
+pub struct KeySend {
+    pub keys: String,
+    /// Текст с escape-последовательностями: \e, \n, \r, \t, \xHH, \\
+    pub send: String,
+}
+
+impl KeySend {
+    pub fn compile(sends: &[KeySend]) -> Vec<(KeyChord, Vec<u8>)> {
+        sends
+            .iter()
+            .filter_map(|s| {
+                let bytes = unescape(&s.send);
+                if bytes.is_empty() {
+                    return None;
+                }
+                let chords = KeyChord::parse_list(&s.keys)?;
+                Some(chords.into_iter().map(move |c| (c, bytes.clone())))
+            })
+            .flatten()
+            .collect()
+    }
+}
+
+/// "\e[15~" → ESC [ 1 5 ~. Неизвестные escape'ы остаются как есть.
+pub fn unescape(s: &str) -> Vec<u8> {
+    let mut out = Vec::with_capacity(s.len());
+    let mut chars = s.chars().peekable();
+    while let Some(c) = chars.next() {
+        if c != '\\' {
+            let mut buf = [0u8; 4];
+            out.extend_from_slice(c.encode_utf8(&mut buf).as_bytes());
+            continue;
+        }
+        match chars.next() {
+            Some('e') => out.push(0x1b),
+            Some('n') => out.push(b'\n'),
+            Some('r') => out.push(b'\r'),
+            Some('t') => out.push(b'\t'),
+            Some('0') => out.push(0),
+            Some('\\') => out.push(b'\\'),
+            Some('x') => {
+                let hex: String = (0..2)
+                    .filter_map(|_| chars.next_if(|c| c.is_ascii_hexdigit()))
+                    .collect();
+                match u8::from_str_radix(&hex, 16) {
+                    Ok(b) => out.push(b),
+                    Err(_) => out.extend_from_slice(format!("\\x{}", hex).as_bytes()),
+                }
+            }
+            Some(other) => {
+                out.push(b'\\');
+                let mut buf = [0u8; 4];
+                out.extend_from_slice(other.encode_utf8(&mut buf).as_bytes());
+            }
+            None => out.push(b'\\'),
+        }
+    }
+    out
+}
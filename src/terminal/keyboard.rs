@@ -0,0 +1,136 @@
+// Расширенные кодировки клавиш: модифицированные функциональные клавиши (xterm),
+// xterm modifyOtherKeys и kitty keyboard protocol (CSI u). Режимы включает
+// удалённое приложение, эмулятор их запоминает, а виджет кодирует нажатия.
+
+/// Флаг kitty keyboard protocol, который мы поддерживаем: disambiguate escape codes.
+pub const KITTY_DISAMBIGUATE: u16 = 1;
+
+/// Параметр модификаторов xterm: 1 + Shift + 2·Alt + 4·Ctrl.
+fn modifier_param(m: egui::Modifiers) -> u8 {
+    1 + m.shift as u8 + 2 * m.alt as u8 + 4 * m.ctrl as u8
+}
+
+/// Символ клавиши (без Shift) для CSI u / modifyOtherKeys.
+fn key_char(key: egui::Key) -> Option<char> {
+    match key {
+        egui::Key::Space => Some(' '),
+        egui::Key::Minus => Some('-'),
+        _ => {
+            let mut chars = key.symbol_or_name().chars();
+            match (chars.next(), chars.next()) {
+                (Some(c), None) if c.is_ascii_graphic() => Some(c.to_ascii_lowercase()),
+                _ => None,
+            }
+        }
+    }
+}
+
+/// Даёт ли клавиша текст (egui пришлёт за ней Event::Text).
+pub fn is_text_key(key: egui::Key) -> bool {
+    key_char(key).is_some()
+}
+
+/// Стрелки, Home/End, F1–F12, PageUp/Down, Insert/Delete с модификаторами
+/// в стиле xterm: CSI 1;m A, CSI 5;m ~. Без модификаторов — None (legacy).
+pub fn modified_special(key: egui::Key, m: egui::Modifiers) -> Option<Vec<u8>> {
+    if !(m.shift || m.alt || m.ctrl) {
+        return None;
+    }
+    let p = modifier_param(m);
+    let letter = match key {
+        egui::Key::ArrowUp => Some('A'),
+        egui::Key::ArrowDown => Some('B'),
+        egui::Key::ArrowRight => Some('C'),
+        egui::Key::ArrowLeft => Some('D'),
+        egui::Key::Home => Some('H'),
+        egui::Key::End => Some('F'),
+        egui::Key::F1 => Some('P'),
+        egui::Key::F2 => Some('Q'),
+        egui::Key::F3 => Some('R'),
+        egui::Key::F4 => Some('S'),
+        _ => None,
+    };
+    if let Some(letter) = letter {
+        return Some(format!("\x1b[1;{}{}", p, letter).into_bytes());
+    }
+    let number = match key {
+        egui::Key::Insert => 2,
+        egui::Key::Delete => 3,
+        egui::Key::PageUp => 5,
+        egui::Key::PageDown => 6,
+        egui::Key::F5 => 15,
+        egui::Key::F6 => 17,
+        egui::Key::F7 => 18,
+        egui::Key::F8 => 19,
+        egui::Key::F9 => 20,
+        egui::Key::F10 => 21,
+        egui::Key::F11 => 23,
+        egui::Key::F12 => 24,
+        _ => return None,
+    };
+    Some(format!("\x1b[{};{}~", number, p).into_bytes())
+}
+
+/// Комбинации, неразличимые в legacy-кодировке (Ctrl+Shift+буква, Ctrl+Enter,
+/// Alt+символ, Esc...). kitty (disambiguate) → CSI code;m u,
+/// modifyOtherKeys → CSI 27;m;code ~. None — кодировать как обычно.
+pub fn encode_extended(
+    key: egui::Key,
+    m: egui::Modifiers,
+    kitty_flags: u16,
+    modify_other_keys: u16,
+) -> Option<Vec<u8>> {
+    let control_key = matches!(
+        key,
+        egui::Key::Enter | egui::Key::Tab | egui::Key::Backspace | egui::Key::Escape
+    );
+    let code = match key {
+        egui::Key::Enter => 13,
+        egui::Key::Tab => 9,
+        egui::Key::Backspace => 127,
+        egui::Key::Escape => 27,
+        _ => key_char(key)? as u32,
+    };
+    let p = modifier_param(m);
+
+    if kitty_flags & KITTY_DISAMBIGUATE != 0 {
+        // Shift+символ — это просто текст; Enter/Tab/Backspace без модификаторов — legacy,
+        // чтобы `reset` набирался даже после упавшего приложения.
+        let encode = match key {
+            egui::Key::Escape => true,
+            _ if control_key => p > 1,
+            _ => m.ctrl || m.alt,
+        };
+        if !encode {
+            return None;
+        }
+        let seq = if p == 1 {
+            format!("\x1b[{}u", code)
+        } else {
+            format!("\x1b[{};{}u", code, p)
+        };
+        return Some(seq.into_bytes());
+    }
+
+    let encode = match modify_other_keys {
+        // Уровень 1: только то, у чего нет общепринятой legacy-кодировки
+        1 => {
+            m.ctrl
+                && (m.shift
+                    || control_key
+                    || !key_char(key).is_some_and(|c| c.is_ascii_lowercase()))
+        }
+        // Уровень 2: все клавиши с Ctrl/Alt и модифицированные Enter/Tab/Backspace
+        2 => m.ctrl || m.alt || (control_key && m.shift),
+        _ => false,
+    };
+    if !encode {
+        return None;
+    }
+    // xterm сообщает символ с учётом Shift
+    let code = match char::from_u32(code) {
+        Some(c) if m.shift && c.is_ascii_lowercase() => c.to_ascii_uppercase() as u32,
+        _ => code,
+    };
+    Some(format!("\x1b[27;{};{}~", p, code).into_bytes())
+}
@@ -0,0 +1,205 @@
+use std::io::Read;
+
+use crate::terminal::iterm2::{decode_base64, decode_image};
+
+// Kitty graphics protocol: APC G <ключ=значение,...> ; <base64> ST
+// Поддерживается передача напрямую (t=d), форматы RGB/RGBA/PNG,
+// zlib-сжатие, чанки (m=1), размещение, удаление и query.
+
+/// Предел размера одной картинки (RGBA, байт)
+pub const MAX_IMAGE_BYTES: usize = 64 * 1024 * 1024;
+/// Предел незаконченной чанковой передачи: base64 от MAX_IMAGE_BYTES
+pub const MAX_PENDING_BYTES: usize = MAX_IMAGE_BYTES / 3 * 4 + 4;
+/// Все переданные картинки вместе; старые вытесняются
+pub const MAX_STORED_BYTES: usize = 256 * 1024 * 1024;
+
+/// Байт RGBA для w×h; None — переполнение или больше MAX_IMAGE_BYTES.
+pub fn rgba_len(w: usize, h: usize) -> Option<usize> {
+    w.checked_mul(h)?
+        .checked_mul(4)
+        .filter(|&len| len <= MAX_IMAGE_BYTES)
+}
+
+#[derive(Default, Clone)]
+pub struct Command {
+    pub action: char,
+    pub format: u32,
+    pub medium: char,
+    pub compressed: bool,
+    pub more: bool,
+    pub quiet: u32,
+    pub image_id: u32,
+    pub image_number: u32,
+    pub placement_id: u32,
+    pub data_width: usize,
+    pub data_height: usize,
+    // Вырезка из исходной картинки
+    pub src_x: usize,
+    pub src_y: usize,
+    pub src_w: usize,
+    pub src_h: usize,
+    // Размер на экране в ячейках (0 — по размеру картинки)
+    pub cols: usize,
+    pub rows: usize,
+    pub no_cursor_move: bool,
+    pub delete: char,
+    pub cell_x: usize,
+    pub cell_y: usize,
+}
+
+impl Command {
+    /// Разбор управляющей части: `a=T,f=100,i=1`
+    pub fn parse(control: &[u8]) -> Command {
+        let mut cmd = Command {
+            action: 't',
+            format: 32,
+            medium: 'd',
+            delete: 'a',
+            ..Default::default()
+        };
+        for pair in control.split(|&b| b == b',') {
+            let mut kv = pair.splitn(2, |&b| b == b'=');
+            let (key, value) = match (kv.next(), kv.next()) {
+                (Some(&[k]), Some(v)) => (k, v),
+                _ => continue,
+            };
+            let text = std::str::from_utf8(value).unwrap_or("");
+            let num = || text.parse::<u64>().unwrap_or(0);
+            let ch = || text.chars().next().unwrap_or('\0');
+            match key {
+                b'a' => cmd.action = ch(),
+                b'f' => cmd.format = num() as u32,
+                b't' => cmd.medium = ch(),
+                b'o' => cmd.compressed = ch() == 'z',
+                b'm' => cmd.more = num() == 1,
+                b'q' => cmd.quiet = num() as u32,
+                b'i' => cmd.image_id = num() as u32,
+                b'I' => cmd.image_number = num() as u32,
+                b'p' => cmd.placement_id = num() as u32,
+                b's' => cmd.data_width = num() as usize,
+                b'v' => cmd.data_height = num() as usize,
+                b'x' => cmd.src_x = num() as usize,
+                b'y' => cmd.src_y = num() as usize,
+                b'w' => cmd.src_w = num() as usize,
+                b'h' => cmd.src_h = num() as usize,
+                b'c' => cmd.cols = num() as usize,
+                b'r' => cmd.rows = num() as usize,
+                b'C' => cmd.no_cursor_move = num() == 1,
+                b'd' => cmd.delete = ch(),
+                b'X' => cmd.cell_x = num() as usize,
+                b'Y' => cmd.cell_y = num() as usize,
+                _ => {}
+            }
+        }
+        cmd
+    }
+
+    /// Ответ терминала. None — клиент просил молчать или без id.
+    pub fn response(&self, result: &Result<(), String>) -> Option<Vec<u8>> {
+        if self.image_id == 0 && self.image_number == 0 {
+            return None;
+        }
+        let message = match result {
+            Ok(()) if self.quiet >= 1 => return None,
+            Ok(()) => "OK".to_string(),
+            Err(_) if self.quiet >= 2 => return None,
+            Err(e) => e.clone(),
+        };
+        let mut keys = Vec::new();
+        if self.image_id != 0 {
+            keys.push(format!("i={}", self.image_id));
+        }
+        if self.image_number != 0 {
+            keys.push(format!("I={}", self.image_number));
+        }
+        if self.placement_id != 0 {
+            keys.push(format!("p={}", self.placement_id));
+        }
+        Some(format!("\x1b_G{};{}\x1b\\", keys.join(","), message).into_bytes())
+    }
+}
+
+/// Разделить тело APC на управляющую часть и payload.
+pub fn split_apc(body: &[u8]) -> Option<(&[u8], &[u8])> {
+    let body = body.strip_prefix(b"G")?;
+    match body.iter().position(|&b| b == b';') {
+        Some(pos) => Some((&body[..pos], &body[pos + 1..])),
+        None => Some((body, &[])),
+    }
+}
+
+/// Декодировать payload (base64 уже склеен из чанков) в RGBA.
+pub fn decode_payload(cmd: &Command, encoded: &[u8]) -> Result<(usize, usize, Vec<u8>), String> {
+    if cmd.medium != 'd' {
+        // Файлы/shm живут на удалённой машине — нам недоступны
+        return Err("EINVAL:only direct transmission is supported".to_string());
+    }
+    let mut data = decode_base64(encoded).ok_or("EINVAL:bad base64")?;
+    if cmd.compressed {
+        let mut inflated = Vec::new();
+        flate2::read::ZlibDecoder::new(&data[..])
+            .take(MAX_IMAGE_BYTES as u64)
+            .read_to_end(&mut inflated)
+            .map_err(|_| "EINVAL:bad zlib data")?;
+        data = inflated;
+    }
+
+    match cmd.format {
+        100 => {
+            let (w, h, rgba) =
+                decode_image(&data).ok_or_else(|| "EBADPNG:cannot decode image".to_string())?;
+            if rgba_len(w, h).is_none() {
+                return Err("EINVAL:bad image size".to_string());
+            }
+            Ok((w, h, rgba))
+        }
+        24 | 32 => {
+            // Размеры приходят от удалённой стороны — без переполнений
+            let (w, h) = (cmd.data_width, cmd.data_height);
+            let bpp = if cmd.format == 24 { 3 } else { 4 };
+            let len = match rgba_len(w, h) {
+                Some(len) if len > 0 => len,
+                _ => return Err("EINVAL:bad image size".to_string()),
+            };
+            if data.len() < len / 4 * bpp {
+                return Err("ENODATA:insufficient image data".to_string());
+            }
+            let rgba = if bpp == 4 {
+                data.truncate(len);
+                data
+            } else {
+                data.chunks_exact(3)
+                    .take(w * h)
+                    .flat_map(|px| [px[0], px[1], px[2], 0xff])
+                    .collect()
+            };
+            Ok((w, h, rgba))
+        }
+        _ => Err("EINVAL:unsupported format".to_string()),
+    }
+}
+
+/// Вырезка x,y,w,h из картинки (0 — до края).
+pub fn crop(
+    cmd: &Command,
+    width: usize,
+    height: usize,
+    rgba: &[u8],
+) -> Option<(usize, usize, Vec<u8>)> {
+    if cmd.src_x == 0 && cmd.src_y == 0 && cmd.src_w == 0 && cmd.src_h == 0 {
+        return None;
+    }
+    let x = cmd.src_x.min(width);
+    let y = cmd.src_y.min(height);
+    let w = if cmd.src_w == 0 { width - x } else { cmd.src_w.min(width - x) };
+    let h = if cmd.src_h == 0 { height - y } else { cmd.src_h.min(height - y) };
+    if w == 0 || h == 0 {
+        return None;
+    }
+    let mut out = Vec::with_capacity(rgba_len(w, h)?);
+    for row in y..y + h {
+        let start = (row * width + x) * 4;
+        out.extend_from_slice(&rgba[start..start + w * 4]);
+    }
+    Some((w, h, out))
+}
@@ -1,2 +1,16 @@
 pub mod emulator;
+pub mod export;
+pub mod highlight;
+pub mod hints;
+pub mod iterm2;
+pub mod keybindings;
+pub mod keyboard;
+pub mod kitty;
+pub mod palette;
+pub mod panes;
+pub mod primary;
+pub mod recorder;
+pub mod replay;
+pub mod sixel;
+pub mod tmux;
 pub mod widget;
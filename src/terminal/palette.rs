@@ -0,0 +1,363 @@
+use serde::{Deserialize, Serialize};
+use std::collections::HashMap;
+use std::path::Path;
+
+use crate::terminal::emulator::{Cell, TermColor};
+
+/// Цветовая схема терминала: основные цвета + 16 ANSI.
+/// Цвета 16..255 (куб 6×6×6 и оттенки серого) общие для всех схем.
+#[derive(Clone, PartialEq, Serialize, Deserialize)]
+pub struct ColorScheme {
+    pub name: String,
+    pub foreground: [u8; 3],
+    pub background: [u8; 3],
+    pub cursor: [u8; 3],
+    pub selection: [u8; 3],
+    pub ansi: [[u8; 3]; 16],
+}
+
+impl Default for ColorScheme {
+    fn default() -> Self {
+        Self::phosphor()
+    }
+}
+
+fn rgb(c: [u8; 3]) -> egui::Color32 {
+    egui::Color32::from_rgb(c[0], c[1], c[2])
+}
+
+impl ColorScheme {
+    /// CRT hacker palette — схема по умолчанию.
+    pub fn phosphor() -> Self {
+        ColorScheme {
+            name: "phosphor".to_string(),
+            foreground: [0x00, 0xff, 0x41],
+            background: [0x06, 0x06, 0x06],
+            cursor: [0x00, 0xff, 0x41],
+            selection: [0x00, 0x99, 0x28],
+            ansi: [
+                [0x08, 0x08, 0x08], // black
+                [0xcc, 0x33, 0x33], // red
+                [0x00, 0xcc, 0x33], // green
+                [0xcc, 0xaa, 0x00], // yellow/amber
+                [0x33, 0x88, 0xcc], // blue
+                [0x88, 0x44, 0xcc], // magenta
+                [0x00, 0xaa, 0x88], // cyan
+                [0xaa, 0xbb, 0xaa], // white (dim)
+                [0x44, 0x55, 0x44], // bright black (grey)
+                [0xff, 0x44, 0x44], // bright red
+                [0x00, 0xff, 0x41], // bright green (phosphor)
+                [0xff, 0xcc, 0x00], // bright yellow
+                [0x44, 0xaa, 0xff], // bright blue
+                [0xbb, 0x66, 0xff], // bright magenta
+                [0x00, 0xdd, 0xbb], // bright cyan
+                [0xcc, 0xee, 0xcc], // bright white
+            ],
+        }
+    }
+
+    /// Встроенные схемы (phosphor первой).
+    pub fn builtin() -> Vec<Self> {
+        let amber = ColorScheme {
+            name: "amber".to_string(),
+            foreground: [0xff, 0xb0, 0x00],
+            cursor: [0xff, 0xb0, 0x00],
+            selection: [0x99, 0x66, 0x00],
+            ..Self::phosphor()
+        };
+        let xterm = ColorScheme {
+            name: "xterm".to_string(),
+            foreground: [0xe5, 0xe5, 0xe5],
+            background: [0x00, 0x00, 0x00],
+            cursor: [0xe5, 0xe5, 0xe5],
+            selection: [0x44, 0x44, 0x66],
+            ansi: [
+                [0x00, 0x00, 0x00],
+                [0xcd, 0x00, 0x00],
+                [0x00, 0xcd, 0x00],
+                [0xcd, 0xcd, 0x00],
+                [0x00, 0x00, 0xee],
+                [0xcd, 0x00, 0xcd],
+                [0x00, 0xcd, 0xcd],
+                [0xe5, 0xe5, 0xe5],
+                [0x7f, 0x7f, 0x7f],
+                [0xff, 0x00, 0x00],
+                [0x00, 0xff, 0x00],
+                [0xff, 0xff, 0x00],
+                [0x5c, 0x5c, 0xff],
+                [0xff, 0x00, 0xff],
+                [0x00, 0xff, 0xff],
+                [0xff, 0xff, 0xff],
+            ],
+        };
+        let solarized = ColorScheme {
+            name: "solarized dark".to_string(),
+            foreground: [0x83, 0x94, 0x96],
+            background: [0x00, 0x2b, 0x36],
+            cursor: [0x93, 0xa1, 0xa1],
+            selection: [0x07, 0x36, 0x42],
+            ansi: [
+                [0x07, 0x36, 0x42],
+                [0xdc, 0x32, 0x2f],
+                [0x85, 0x99, 0x00],
+                [0xb5, 0x89, 0x00],
+                [0x26, 0x8b, 0xd2],
+                [0xd3, 0x36, 0x82],
+                [0x2a, 0xa1, 0x98],
+                [0xee, 0xe8, 0xd5],
+                [0x00, 0x2b, 0x36],
+                [0xcb, 0x4b, 0x16],
+                [0x58, 0x6e, 0x75],
+                [0x65, 0x7b, 0x83],
+                [0x83, 0x94, 0x96],
+                [0x6c, 0x71, 0xc4],
+                [0x93, 0xa1, 0xa1],
+                [0xfd, 0xf6, 0xe3],
+            ],
+        };
+        vec![Self::phosphor(), amber, xterm, solarized]
+    }
+
+    pub fn background_color(&self) -> egui::Color32 {
+        rgb(self.background)
+    }
+
+    pub fn foreground_color(&self) -> egui::Color32 {
+        rgb(self.foreground)
+    }
+
+    pub fn cursor_color(&self) -> egui::Color32 {
+        rgb(self.cursor)
+    }
+
+    pub fn selection_color(&self) -> egui::Color32 {
+        rgb(self.selection)
+    }
+
+    /// Индексированный цвет (16 из схемы + 256 extended)
+    pub fn indexed(&self, idx: u8) -> egui::Color32 {
+        match idx {
+            0..=15 => rgb(self.ansi[idx as usize]),
+            16..=231 => {
+                let n = idx - 16;
+                let r_comp = n / 36;
+                let g_comp = (n % 36) / 6;
+                let b_comp = n % 6;
+                let r = if r_comp > 0 { 55 + r_comp * 40 } else { 0 };
+                let g = if g_comp > 0 { 55 + g_comp * 40 } else { 0 };
+                let b = if b_comp > 0 { 55 + b_comp * 40 } else { 0 };
+                egui::Color32::from_rgb(r, g, b)
+            }
+            232..=255 => {
+                let v = 8 + (idx - 232) * 10;
+                egui::Color32::from_rgb(v, v, v)
+            }
+        }
+    }
+
+    fn term_color(&self, color: TermColor, is_fg: bool, is_bold: bool) -> egui::Color32 {
+        match color {
+            TermColor::Default => {
+                if is_fg {
+                    self.foreground_color()
+                } else {
+                    self.background_color()
+                }
+            }
+            TermColor::Indexed(idx) => {
+                let effective_idx = if is_bold && idx < 8 { idx + 8 } else { idx };
+                self.indexed(effective_idx)
+            }
+            TermColor::Rgb(r, g, b) => egui::Color32::from_rgb(r, g, b),
+        }
+    }
+
+    /// (fg, bg) ячейки с учётом bold и inverse.
+    pub fn resolve(&self, cell: &Cell) -> (egui::Color32, egui::Color32) {
+        let mut fg = self.term_color(cell.attr.fg, true, cell.attr.bold);
+        let mut bg = self.term_color(cell.attr.bg, false, false);
+
+        if cell.attr.inverse {
+            std::mem::swap(&mut fg, &mut bg);
+        }
+
+        (fg, bg)
+    }
+}
+
+// ── Импорт ──
+
+/// Схема из файла: iTerm2 .itermcolors, Xresources или base16 (yaml).
+pub fn import(path: &Path) -> Result<ColorScheme, String> {
+    let data = std::fs::read_to_string(path)
+        .map_err(|e| format!("cannot read '{}': {}", path.display(), e))?;
+    let name = path
+        .file_stem()
+        .unwrap_or_default()
+        .to_string_lossy()
+        .to_string();
+
+    let scheme = if data.contains("<plist") || data.contains("<dict>") {
+        parse_itermcolors(&data, name)
+    } else if base16_regex().find_iter(&data).count() >= 16 {
+        parse_base16(&data, name)
+    } else {
+        parse_xresources(&data, name)
+    };
+    scheme.ok_or_else(|| format!("'{}': no colors found", path.display()))
+}
+
+/// "Ansi 0 Color" → <dict> с Red/Green/Blue Component (0..1).
+fn parse_itermcolors(data: &str, name: String) -> Option<ColorScheme> {
+    let entry = regex::Regex::new(r"(?s)<key>([^<]+)</key>\s*<dict>(.*?)</dict>").ok()?;
+    let component =
+        regex::Regex::new(r"(?s)<key>(Red|Green|Blue) Component</key>\s*<real>([^<]+)</real>")
+            .ok()?;
+
+    let mut colors = HashMap::new();
+    for cap in entry.captures_iter(data) {
+        let mut color = [0u8; 3];
+        for c in component.captures_iter(&cap[2]) {
+            let value = c[2].trim().parse::<f64>().unwrap_or(0.0);
+            let idx = match &c[1] {
+                "Red" => 0,
+                "Green" => 1,
+                _ => 2,
+            };
+            color[idx] = (value.clamp(0.0, 1.0) * 255.0).round() as u8;
+        }
+        let key = match cap[1].trim() {
+            "Foreground Color" => "foreground".to_string(),
+            "Background Color" => "background".to_string(),
+            "Cursor Color" => "cursor".to_string(),
+            "Selection Color" => "selection".to_string(),
+            other => match other
+                .strip_prefix("Ansi ")
+                .and_then(|s| s.strip_suffix(" Color"))
+            {
+                Some(n) => format!("color{}", n),
+                None => continue,
+            },
+        };
+        colors.insert(key, color);
+    }
+    build_scheme(name, &colors)
+}
+
+/// `*.color0: #rrggbb`, `URxvt*foreground: rgb:rr/gg/bb`, `#define` подставляются.
+fn parse_xresources(data: &str, name: String) -> Option<ColorScheme> {
+    let define = regex::Regex::new(r"(?m)^\s*#define\s+(\S+)\s+(\S+)").ok()?;
+    let resource = regex::Regex::new(
+        r"(?m)^\s*[A-Za-z0-9_.*-]*?[.*]?(color\d{1,2}|foreground|background|cursorColor)\s*:\s*(\S+)",
+    )
+    .ok()?;
+
+    let defines: HashMap<&str, &str> = define
+        .captures_iter(data)
+        .filter_map(|c| Some((c.get(1)?.as_str(), c.get(2)?.as_str())))
+        .collect();
+
+    let mut colors = HashMap::new();
+    for cap in resource.captures_iter(data) {
+        let value = defines.get(&cap[2]).copied().unwrap_or(&cap[2]);
+        if let Some(color) = parse_color(value) {
+            let key = match &cap[1] {
+                "cursorColor" => "cursor".to_string(),
+                other => other.to_string(),
+            };
+            colors.insert(key, color);
+        }
+    }
+    build_scheme(name, &colors)
+}
+
+fn base16_regex() -> regex::Regex {
+    regex::Regex::new(r#"(?m)^\s*base0([0-9A-Fa-f])\s*:\s*"?#?([0-9A-Fa-f]{6})"?"#)
+        .expect("valid regex")
+}
+
+/// base16 yaml → ANSI по раскладке base16-shell.
+fn parse_base16(data: &str, name: String) -> Option<ColorScheme> {
+    let mut base = [[0u8; 3]; 16];
+    for cap in base16_regex().captures_iter(data) {
+        let idx = usize::from_str_radix(&cap[1], 16).ok()?;
+        base[idx] = parse_color(&cap[2])?;
+    }
+    let title = regex::Regex::new(r#"(?m)^\s*scheme\s*:\s*"?([^"\n]+)"?"#)
+        .ok()?
+        .captures(data)
+        .map(|c| c[1].trim().to_string())
+        .unwrap_or(name);
+
+    // color0..15 = base 00 08 0B 0A 0D 0E 0C 05 03 08 0B 0A 0D 0E 0C 07
+    const MAP: [usize; 16] = [
+        0x0, 0x8, 0xb, 0xa, 0xd, 0xe, 0xc, 0x5, 0x3, 0x8, 0xb, 0xa, 0xd, 0xe, 0xc, 0x7,
+    ];
+    Some(ColorScheme {
+        name: title,
+        foreground: base[0x5],
+        background: base[0x0],
+        cursor: base[0x5],
+        selection: base[0x2],
+        ansi: MAP.map(|i| base[i]),
+    })
+}
+
+/// Недостающие цвета берутся из phosphor; совсем пусто — None.
+fn build_scheme(name: String, colors: &HashMap<String, [u8; 3]>) -> Option<ColorScheme> {
+    if colors.is_empty() {
+        return None;
+    }
+    let mut scheme = ColorScheme {
+        name,
+        ..ColorScheme::phosphor()
+    };
+    for (i, slot) in scheme.ansi.iter_mut().enumerate() {
+        if let Some(c) = colors.get(&format!("color{}", i)) {
+            *slot = *c;
+        }
+    }
+    if let Some(c) = colors.get("foreground") {
+        scheme.foreground = *c;
+        scheme.cursor = *c;
+    }
+    if let Some(c) = colors.get("background") {
+        scheme.background = *c;
+    }
+    if let Some(c) = colors.get("cursor") {
+        scheme.cursor = *c;
+    }
+    if let Some(c) = colors.get("selection") {
+        scheme.selection = *c;
+    }
+    Some(scheme)
+}
+
+/// "#rrggbb", "rrggbb" или X11 "rgb:rr/gg/bb".
+fn parse_color(s: &str) -> Option<[u8; 3]> {
+    let s = s.trim().trim_matches('"');
+    if let Some(rest) = s.strip_prefix("rgb:") {
+        let parts: Vec<&str> = rest.split('/').collect();
+        if parts.len() != 3 {
+            return None;
+        }
+        let mut color = [0u8; 3];
+        for (slot, part) in color.iter_mut().zip(parts) {
+            // 1–4 hex-цифры на канал; берём старший байт
+            let value = u32::from_str_radix(part, 16).ok()?;
+            let bits = part.len() as u32 * 4;
+            *slot = if bits >= 8 {
+                (value >> (bits - 8)) as u8
+            } else {
+                (value * 255 / ((1 << bits) - 1)) as u8
+            };
+        }
+        return Some(color);
+    }
+    let hex = s.strip_prefix('#').unwrap_or(s);
+    if hex.len() != 6 {
+        return None;
+    }
+    let value = u32::from_str_radix(hex, 16).ok()?;
+    Some([(value >> 16) as u8, (value >> 8) as u8, value as u8])
+}
@@ -0,0 +1,379 @@
+use crate::ssh::session::SshConnection;
+use crate::terminal::widget::TerminalWidget;
+
+// Split panes внутри Shell-вкладки. Каждая панель — свой shell-канал
+// на том же SSH-соединении и свой TerminalWidget (свой scrollback).
+// Панель 0 — основная: её терминал и канал живут в Connection.
+
+const PRIMARY: usize = 0;
+const DIVIDER: f32 = 4.0;
+
+#[derive(Clone, Copy, PartialEq)]
+pub enum SplitDir {
+    /// Панели рядом (разделитель вертикальный)
+    Horizontal,
+    /// Панели друг над другом
+    Vertical,
+}
+
+#[derive(Clone, Copy, PartialEq)]
+enum Direction {
+    Left,
+    Right,
+    Up,
+    Down,
+}
+
+enum Node {
+    Leaf(usize),
+    Split {
+        dir: SplitDir,
+        ratio: f32,
+        first: Box<Node>,
+        second: Box<Node>,
+    },
+}
+
+impl Node {
+    /// Заменить лист `id` на разделение с новой панелью `new_id`.
+    fn split(&mut self, id: usize, new_id: usize, dir: SplitDir) -> bool {
+        match self {
+            Node::Leaf(leaf) if *leaf == id => {
+                *self = Node::Split {
+                    dir,
+                    ratio: 0.5,
+                    first: Box::new(Node::Leaf(id)),
+                    second: Box::new(Node::Leaf(new_id)),
+                };
+                true
+            }
+            Node::Leaf(_) => false,
+            Node::Split { first, second, .. } => {
+                first.split(id, new_id, dir) || second.split(id, new_id, dir)
+            }
+        }
+    }
+
+    /// Убрать лист `id`; его сосед занимает место разделения.
+    fn remove(&mut self, id: usize) -> bool {
+        let replacement = match self {
+            Node::Leaf(_) => return false,
+            Node::Split { first, second, .. } => {
+                if matches!(**first, Node::Leaf(leaf) if leaf == id) {
+                    Some(std::mem::replace(&mut **second, Node::Leaf(PRIMARY)))
+                } else if matches!(**second, Node::Leaf(leaf) if leaf == id) {
+                    Some(std::mem::replace(&mut **first, Node::Leaf(PRIMARY)))
+                } else {
+                    return first.remove(id) || second.remove(id);
+                }
+            }
+        };
+        if let Some(node) = replacement {
+            *self = node;
+        }
+        true
+    }
+
+    fn first_leaf(&self) -> usize {
+        match self {
+            Node::Leaf(id) => *id,
+            Node::Split { first, .. } => first.first_leaf(),
+        }
+    }
+}
+
+struct Pane {
+    id: usize,
+    terminal: TerminalWidget,
+    ssh: SshConnection,
+}
+
+pub struct SplitPanes {
+    root: Node,
+    // Дополнительные панели (id > 0)
+    panes: Vec<Pane>,
+    focused: usize,
+    next_id: usize,
+    // Прямоугольники панелей с прошлого кадра — для навигации с клавиатуры
+    rects: Vec<(usize, egui::Rect)>,
+}
+
+impl Default for SplitPanes {
+    fn default() -> Self {
+        SplitPanes {
+            root: Node::Leaf(PRIMARY),
+            panes: Vec::new(),
+            focused: PRIMARY,
+            next_id: 1,
+            rects: Vec::new(),
+        }
+    }
+}
+
+impl SplitPanes {
+    pub fn is_split(&self) -> bool {
+        !self.panes.is_empty()
+    }
+
+    pub fn is_primary_focused(&self) -> bool {
+        self.focused == PRIMARY
+    }
+
+    /// Разделить активную панель: новый shell-канал на соединении `primary_ssh`.
+    pub fn split(&mut self, dir: SplitDir, primary_ssh: &SshConnection, terminal: TerminalWidget) {
+        let id = self.next_id;
+        self.next_id += 1;
+        if self.root.split(self.focused, id, dir) {
+            self.panes.push(Pane {
+                id,
+                terminal,
+                ssh: primary_ssh.open_channel(),
+            });
+            self.focused = id;
+        }
+    }
+
+    /// Закрыть активную панель. Основную панель закрыть нельзя — это само соединение.
+    pub fn close_focused(&mut self) {
+        if self.focused != PRIMARY {
+            self.close(self.focused);
+        }
+    }
+
+    fn close(&mut self, id: usize) {
+        self.root.remove(id);
+        self.panes.retain(|p| p.id != id);
+        if self.focused == id {
+            self.focused = self.root.first_leaf();
+        }
+    }
+
+    /// Канал активной панели (основная — `primary`).
+    pub fn focused_ssh<'a>(&'a self, primary: &'a SshConnection) -> &'a SshConnection {
+        self.panes
+            .iter()
+            .find(|p| p.id == self.focused)
+            .map_or(primary, |p| &p.ssh)
+    }
+
+    /// Дополнительные панели (для фоновой обработки вывода и настроек).
+    pub fn extra_mut(&mut self) -> impl Iterator<Item = (&mut TerminalWidget, &SshConnection)> {
+        self.panes.iter_mut().map(|p| (&mut p.terminal, &p.ssh))
+    }
+
+    fn focus_direction(&mut self, direction: Direction) {
+        let current = match self.rects.iter().find(|(id, _)| *id == self.focused) {
+            Some((_, rect)) => *rect,
+            None => return,
+        };
+        let from = current.center();
+        let best = self
+            .rects
+            .iter()
+            .filter(|(id, _)| *id != self.focused)
+            .filter(|(_, rect)| match direction {
+                Direction::Left => rect.right() <= current.left() + 1.0,
+                Direction::Right => rect.left() >= current.right() - 1.0,
+                Direction::Up => rect.bottom() <= current.top() + 1.0,
+                Direction::Down => rect.top() >= current.bottom() - 1.0,
+            })
+            .min_by(|(_, a), (_, b)| {
+                let da = a.center().distance_sq(from);
+                let db = b.center().distance_sq(from);
+                da.total_cmp(&db)
+            });
+        if let Some((id, _)) = best {
+            self.focused = *id;
+        }
+    }
+
+    /// Горячие клавиши панелей. Съедаем их до того, как их увидит терминал.
+    /// Alt+стрелки — только при разделении и если сочетание не занято в терминале
+    /// (у всех панелей сессии одни и те же привязки, смотрим основную).
+    pub fn handle_shortcuts(
+        &mut self,
+        ui: &egui::Ui,
+        (primary, primary_ssh): (&TerminalWidget, &SshConnection),
+        new_terminal: impl Fn() -> TerminalWidget,
+    ) {
+        let cs = egui::Modifiers::CTRL | egui::Modifiers::SHIFT;
+        if ui.input_mut(|i| i.consume_key(cs, egui::Key::E)) {
+            self.split(SplitDir::Horizontal, primary_ssh, new_terminal());
+        }
+        if ui.input_mut(|i| i.consume_key(cs, egui::Key::O)) {
+            self.split(SplitDir::Vertical, primary_ssh, new_terminal());
+        }
+        if ui.input_mut(|i| i.consume_key(cs, egui::Key::W)) {
+            self.close_focused();
+        }
+        if !self.is_split() {
+            return;
+        }
+        for (key, direction) in [
+            (egui::Key::ArrowLeft, Direction::Left),
+            (egui::Key::ArrowRight, Direction::Right),
+            (egui::Key::ArrowUp, Direction::Up),
+            (egui::Key::ArrowDown, Direction::Down),
+        ] {
+            if primary.binds_key(key, egui::Modifiers::ALT) {
+                continue;
+            }
+            if ui.input_mut(|i| i.consume_key(egui::Modifiers::ALT, key)) {
+                self.focus_direction(direction);
+            }
+        }
+    }
+
+    /// Отрисовать все панели. `primary` — терминал и канал основной панели.
+    pub fn show(
+        &mut self,
+        ui: &mut egui::Ui,
+        primary: (&mut TerminalWidget, &SshConnection),
+        interactive: bool,
+    ) {
+        // Панели, у которых закрылся shell, убираем
+        let dead: Vec<usize> = self
+            .panes
+            .iter()
+            .filter(|p| !p.ssh.is_alive())
+            .map(|p| p.id)
+            .collect();
+        for id in dead {
+            self.close(id);
+        }
+
+        let rect = ui.available_rect_before_wrap();
+        ui.allocate_rect(rect, egui::Sense::hover());
+
+        let mut root = std::mem::replace(&mut self.root, Node::Leaf(PRIMARY));
+        let mut ctx = ShowContext {
+            primary,
+            panes: &mut self.panes,
+            focused: self.focused,
+            clicked: None,
+            rects: Vec::new(),
+            interactive,
+            split: matches!(root, Node::Split { .. }),
+        };
+        show_node(ui, &mut root, rect, &mut ctx);
+        let (clicked, rects) = (ctx.clicked, ctx.rects);
+        self.root = root;
+        self.rects = rects;
+        if let Some(id) = clicked {
+            self.focused = id;
+        }
+    }
+}
+
+struct ShowContext<'a> {
+    primary: (&'a mut TerminalWidget, &'a SshConnection),
+    panes: &'a mut Vec<Pane>,
+    focused: usize,
+    clicked: Option<usize>,
+    rects: Vec<(usize, egui::Rect)>,
+    interactive: bool,
+    split: bool,
+}
+
+fn show_node(ui: &mut egui::Ui, node: &mut Node, rect: egui::Rect, ctx: &mut ShowContext) {
+    match node {
+        Node::Leaf(id) => {
+            let id = *id;
+            ctx.rects.push((id, rect));
+            let is_focused = id == ctx.focused;
+
+            let mut child = ui.new_child(
+                egui::UiBuilder::new()
+                    .max_rect(rect)
+                    .id_salt(("pane", id))
+                    .layout(egui::Layout::top_down(egui::Align::Min)),
+            );
+            let (terminal, ssh): (&mut TerminalWidget, &SshConnection) = if id == PRIMARY {
+                (&mut *ctx.primary.0, ctx.primary.1)
+            } else {
+                match ctx.panes.iter_mut().find(|p| p.id == id) {
+                    Some(p) => (&mut p.terminal, &p.ssh),
+                    None => return,
+                }
+            };
+            terminal.set_focus(is_focused);
+            terminal.show(&mut child, ssh, ctx.interactive);
+            if !is_focused && terminal.has_focus() {
+                ctx.clicked = Some(id);
+            }
+
+            // Рамка активной панели — только когда панелей несколько
+            if ctx.split {
+                let color = if is_focused {
+                    crate::theme::GREEN_DIM
+                } else {
+                    crate::theme::GREEN_DARK
+                };
+                ui.painter()
+                    .rect_stroke(rect.shrink(0.5), 0.0, egui::Stroke::new(1.0, color));
+            }
+        }
+        Node::Split {
+            dir,
+            ratio,
+            first,
+            second,
+        } => {
+            let (first_rect, divider, second_rect) = match dir {
+                SplitDir::Horizontal => {
+                    let x = rect.left() + (rect.width() - DIVIDER) * *ratio;
+                    (
+                        egui::Rect::from_min_max(rect.min, egui::pos2(x, rect.bottom())),
+                        egui::Rect::from_min_max(
+                            egui::pos2(x, rect.top()),
+                            egui::pos2(x + DIVIDER, rect.bottom()),
+                        ),
+                        egui::Rect::from_min_max(egui::pos2(x + DIVIDER, rect.top()), rect.max),
+                    )
+                }
+                SplitDir::Vertical => {
+                    let y = rect.top() + (rect.height() - DIVIDER) * *ratio;
+                    (
+                        egui::Rect::from_min_max(rect.min, egui::pos2(rect.right(), y)),
+                        egui::Rect::from_min_max(
+                            egui::pos2(rect.left(), y),
+                            egui::pos2(rect.right(), y + DIVIDER),
+                        ),
+                        egui::Rect::from_min_max(egui::pos2(rect.left(), y + DIVIDER), rect.max),
+                    )
+                }
+            };
+
+            show_node(ui, first, first_rect, ctx);
+            show_node(ui, second, second_rect, ctx);
+
+            // Разделитель — тащится мышью
+            let response = ui.interact(
+                divider,
+                ui.id()
+                    .with(("pane_divider", divider.min.x as i32, divider.min.y as i32)),
+                egui::Sense::drag(),
+            );
+            let cursor = match dir {
+                SplitDir::Horizontal => egui::CursorIcon::ResizeHorizontal,
+                SplitDir::Vertical => egui::CursorIcon::ResizeVertical,
+            };
+            let response = response.on_hover_cursor(cursor);
+            if response.dragged() {
+                if let Some(pos) = response.interact_pointer_pos() {
+                    let new_ratio = match dir {
+                        SplitDir::Horizontal => (pos.x - rect.left()) / rect.width(),
+                        SplitDir::Vertical => (pos.y - rect.top()) / rect.height(),
+                    };
+                    *ratio = new_ratio.clamp(0.1, 0.9);
+                }
+            }
+            let color = if response.hovered() || response.dragged() {
+                crate::theme::GREEN_DIM
+            } else {
+                crate::theme::GREEN_DARK
+            };
+            ui.painter().rect_filled(divider, 0.0, color);
+        }
+    }
+}
@@ -0,0 +1,44 @@
+// PRIMARY selection (X11/Wayland): выделение мышью сразу попадает в PRIMARY,
+// средняя кнопка вставляет его. На других платформах PRIMARY нет — заглушки.
+
+#[cfg(target_os = "linux")]
+mod imp {
+    use arboard::{GetExtLinux, LinuxClipboardKind, SetExtLinux};
+    use parking_lot::Mutex;
+
+    // Clipboard держим живым: на X11 содержимое выделения отдаёт наш процесс,
+    // пока владеет им.
+    static CLIPBOARD: Mutex<Option<arboard::Clipboard>> = Mutex::new(None);
+
+    fn with_clipboard<T>(f: impl FnOnce(&mut arboard::Clipboard) -> Option<T>) -> Option<T> {
+        let mut guard = CLIPBOARD.lock();
+        if guard.is_none() {
+            *guard = arboard::Clipboard::new().ok();
+        }
+        guard.as_mut().and_then(f)
+    }
+
+    pub fn set(text: &str) {
+        with_clipboard(|c| {
+            c.set()
+                .clipboard(LinuxClipboardKind::Primary)
+                .text(text.to_string())
+                .ok()
+        });
+    }
+
+    pub fn get() -> Option<String> {
+        with_clipboard(|c| c.get().clipboard(LinuxClipboardKind::Primary).text().ok())
+    }
+}
+
+#[cfg(not(target_os = "linux"))]
+mod imp {
+    pub fn set(_text: &str) {}
+
+    pub fn get() -> Option<String> {
+        None
+    }
+}
+
+pub use imp::{get, set};
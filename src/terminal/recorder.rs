@@ -0,0 +1,100 @@
+use std::io::Write;
+use std::path::{Path, PathBuf};
+use std::time::{Instant, SystemTime, UNIX_EPOCH};
+
+/// Запись вывода терминала в формате asciicast v2
+/// (https://docs.asciinema.org/manual/asciicast/v2/).
+///
+/// Первая строка файла — JSON-заголовок, далее по одному событию на строку:
+/// `[время_в_секундах, "o", "данные"]` для вывода и `[t, "r", "COLSxROWS"]` для resize.
+pub struct SessionRecorder {
+    writer: std::io::BufWriter<std::fs::File>,
+    path: PathBuf,
+    started: Instant,
+    // Незавершённая UTF-8 последовательность с конца предыдущего чанка
+    utf8_tail: Vec<u8>,
+}
+
+impl SessionRecorder {
+    pub fn start(path: &Path, cols: usize, rows: usize, title: &str) -> std::io::Result<Self> {
+        let file = std::fs::File::create(path)?;
+        let mut writer = std::io::BufWriter::new(file);
+
+        let timestamp = SystemTime::now()
+            .duration_since(UNIX_EPOCH)
+            .map(|d| d.as_secs())
+            .unwrap_or(0);
+        let header = serde_json::json!({
+            "version": 2,
+            "width": cols,
+            "height": rows,
+            "timestamp": timestamp,
+            "title": title,
+            "env": { "TERM": "xterm-256color" },
+        });
+        writeln!(writer, "{}", header)?;
+
+        Ok(SessionRecorder {
+            writer,
+            path: path.to_path_buf(),
+            started: Instant::now(),
+            utf8_tail: Vec::new(),
+        })
+    }
+
+    pub fn path(&self) -> &Path {
+        &self.path
+    }
+
+    pub fn record_output(&mut self, data: &[u8]) -> std::io::Result<()> {
+        let mut bytes = std::mem::take(&mut self.utf8_tail);
+        bytes.extend_from_slice(data);
+
+        // Чанк SSH может разрезать многобайтовый символ — хвост откладываем до следующего
+        let valid_up_to = match std::str::from_utf8(&bytes) {
+            Ok(_) => bytes.len(),
+            Err(e) if e.error_len().is_none() => e.valid_up_to(),
+            Err(_) => bytes.len(),
+        };
+        self.utf8_tail = bytes.split_off(valid_up_to);
+        if bytes.is_empty() {
+            return Ok(());
+        }
+
+        let text = String::from_utf8_lossy(&bytes);
+        self.write_event("o", &text)
+    }
+
+    pub fn record_resize(&mut self, cols: usize, rows: usize) -> std::io::Result<()> {
+        self.write_event("r", &format!("{}x{}", cols, rows))
+    }
+
+    pub fn finish(mut self) -> std::io::Result<PathBuf> {
+        if !self.utf8_tail.is_empty() {
+            let tail = std::mem::take(&mut self.utf8_tail);
+            self.write_event("o", &String::from_utf8_lossy(&tail))?;
+        }
+        self.writer.flush()?;
+        Ok(self.path)
+    }
+
+    fn write_event(&mut self, kind: &str, data: &str) -> std::io::Result<()> {
+        let elapsed = self.started.elapsed().as_secs_f64();
+        let event = serde_json::json!([(elapsed * 1_000_000.0).round() / 1_000_000.0, kind, data]);
+        writeln!(self.writer, "{}", event)
+    }
+}
+
+/// Имя файла по умолчанию: `<сессия>-<unix time>.<ext>`
+/// (запись сессии, экспорт буфера).
+pub fn default_file_name(session_name: &str, extension: &str) -> String {
+    let timestamp = SystemTime::now()
+        .duration_since(UNIX_EPOCH)
+        .map(|d| d.as_secs())
+        .unwrap_or(0);
+    let safe: String = session_name
+        .chars()
+        .map(|c| if c.is_alphanumeric() || c == '-' || c == '_' { c } else { '_' })
+        .collect();
+    format!("{}-{}.{}", safe, timestamp, extension)
+}
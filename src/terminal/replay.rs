@@ -0,0 +1,326 @@
+use std::path::{Path, PathBuf};
+use std::time::Instant;
+
+use crate::terminal::emulator::TerminalEmulator;
+use crate::terminal::palette::ColorScheme;
+use crate::terminal::widget::{cell_size_for, row_layout_job};
+
+const SPEEDS: [f64; 5] = [0.5, 1.0, 2.0, 4.0, 8.0];
+
+enum ReplayEvent {
+    Output(Vec<u8>),
+    Resize(usize, usize),
+}
+
+/// Размер экрана (cols, rows) + события с абсолютным временем в секундах.
+type Recording = ((usize, usize), Vec<(f64, ReplayEvent)>);
+
+/// Просмотр записанной сессии: asciicast v1/v2 или сырой лог `script`
+/// с файлом таймингов. События прогоняются через TerminalEmulator.
+pub struct ReplayViewer {
+    pub title: String,
+    path: PathBuf,
+    events: Vec<(f64, ReplayEvent)>,
+    initial_size: (usize, usize),
+    emulator: TerminalEmulator,
+    // Позиция воспроизведения (секунды записи)
+    position: f64,
+    next_event: usize,
+    playing: bool,
+    speed_idx: usize,
+    last_tick: Option<Instant>,
+    font_size: f32,
+    scheme: ColorScheme,
+}
+
+impl ReplayViewer {
+    pub fn load(path: &Path) -> Result<Self, String> {
+        let data = std::fs::read(path)
+            .map_err(|e| format!("cannot read '{}': {}", path.display(), e))?;
+
+        let (initial_size, events) = match parse_asciicast(&data) {
+            Some(parsed) => parsed,
+            None => parse_script_log(path, data)?,
+        };
+
+        let title = path
+            .file_name()
+            .unwrap_or_default()
+            .to_string_lossy()
+            .to_string();
+
+        Ok(ReplayViewer {
+            title,
+            path: path.to_path_buf(),
+            events,
+            initial_size,
+            emulator: TerminalEmulator::new(initial_size.0, initial_size.1),
+            position: 0.0,
+            next_event: 0,
+            playing: true,
+            speed_idx: 1,
+            last_tick: None,
+            font_size: 13.0,
+            scheme: ColorScheme::default(),
+        })
+    }
+
+    pub fn duration(&self) -> f64 {
+        self.events.last().map(|(t, _)| *t).unwrap_or(0.0)
+    }
+
+    fn finished(&self) -> bool {
+        self.next_event >= self.events.len()
+    }
+
+    /// Применить все события с временем <= `position`.
+    fn advance_to(&mut self, position: f64) {
+        while let Some((t, event)) = self.events.get(self.next_event) {
+            if *t > position {
+                break;
+            }
+            match event {
+                ReplayEvent::Output(data) => self.emulator.process(data),
+                ReplayEvent::Resize(cols, rows) => self.emulator.resize(*cols, *rows),
+            }
+            self.next_event += 1;
+        }
+        self.position = position;
+    }
+
+    /// Перемотка. Назад — с начала записи (эмулятор не умеет «отматывать»).
+    pub fn seek(&mut self, position: f64) {
+        let position = position.clamp(0.0, self.duration());
+        if position < self.position {
+            self.emulator = TerminalEmulator::new(self.initial_size.0, self.initial_size.1);
+            self.next_event = 0;
+        }
+        self.advance_to(position);
+        self.last_tick = None;
+    }
+
+    fn tick(&mut self) {
+        let now = Instant::now();
+        if self.playing {
+            if let Some(last) = self.last_tick {
+                let dt = now.duration_since(last).as_secs_f64() * SPEEDS[self.speed_idx];
+                self.advance_to(self.position + dt);
+            }
+            if self.finished() {
+                self.playing = false;
+            }
+        }
+        self.last_tick = Some(now);
+    }
+
+    pub fn show(&mut self, ui: &mut egui::Ui) {
+        self.tick();
+
+        let duration = self.duration();
+
+        // Панель управления
+        ui.horizontal(|ui| {
+            let play_label = if self.playing { "[pause]" } else { "[play]" };
+            if ui.button(play_label).clicked() {
+                if !self.playing && self.finished() {
+                    self.seek(0.0);
+                }
+                self.playing = !self.playing;
+                self.last_tick = None;
+            }
+            if ui.button("[|<]").on_hover_text("restart").clicked() {
+                self.seek(0.0);
+            }
+            if ui.button("[-5s]").clicked() {
+                self.seek(self.position - 5.0);
+            }
+            if ui.button("[+5s]").clicked() {
+                self.seek(self.position + 5.0);
+            }
+            ui.separator();
+            for (idx, speed) in SPEEDS.iter().enumerate() {
+                ui.selectable_value(&mut self.speed_idx, idx, format!("{}x", speed));
+            }
+            ui.separator();
+            ui.colored_label(
+                crate::theme::GREEN_DIM,
+                format!("{} / {}", format_clock(self.position), format_clock(duration)),
+            );
+        });
+
+        let mut position = self.position;
+        let slider = ui.add(
+            egui::Slider::new(&mut position, 0.0..=duration.max(0.001))
+                .show_value(false)
+                .trailing_fill(true),
+        );
+        if slider.changed() {
+            self.seek(position);
+        }
+
+        ui.colored_label(crate::theme::GREY, self.path.to_string_lossy());
+        ui.separator();
+
+        // Экран — в натуральном размере записи, со скроллом если не влезает
+        let cell_size = cell_size_for(ui, self.font_size);
+        let bg_color = self.scheme.background_color();
+        let cols = self.emulator.cols();
+        let rows = self.emulator.rows();
+
+        egui::ScrollArea::both().show(ui, |ui| {
+            let desired_size = egui::vec2(cols as f32 * cell_size.x, rows as f32 * cell_size.y);
+            let (response, painter) = ui.allocate_painter(desired_size, egui::Sense::hover());
+            let origin = response.rect.min;
+            painter.rect_filled(response.rect, 0.0, bg_color);
+
+            for (row_idx, row) in self.emulator.grid().iter().enumerate() {
+                let job = row_layout_job(row, cols, self.font_size, &self.scheme, &[], |_| false);
+                let galley = ui.fonts(|f| f.layout_job(job));
+                painter.galley(
+                    egui::pos2(origin.x, origin.y + row_idx as f32 * cell_size.y),
+                    galley,
+                    egui::Color32::TRANSPARENT,
+                );
+            }
+        });
+
+        if self.playing {
+            ui.ctx().request_repaint();
+        }
+    }
+}
+
+// --- Парсеры форматов ---
+
+/// asciicast v2 (JSON lines) или v1 (единый JSON с массивом stdout).
+fn parse_asciicast(data: &[u8]) -> Option<Recording> {
+    let text = std::str::from_utf8(data).ok()?;
+    let mut lines = text.lines();
+    let header: Option<serde_json::Value> = lines
+        .next()
+        .and_then(|line| serde_json::from_str(line).ok());
+
+    if let Some(header) = header.filter(|h| h.get("version").and_then(|v| v.as_u64()) == Some(2)) {
+        let size = header_size(&header);
+        let idle_limit = header.get("idle_time_limit").and_then(|v| v.as_f64());
+        let mut events = Vec::new();
+        let mut prev_raw = 0.0;
+        let mut t = 0.0;
+        for line in lines {
+            let event: serde_json::Value = match serde_json::from_str(line) {
+                Ok(v) => v,
+                Err(_) => continue,
+            };
+            let (raw, kind, payload) = match (
+                event.get(0).and_then(|v| v.as_f64()),
+                event.get(1).and_then(|v| v.as_str()),
+                event.get(2).and_then(|v| v.as_str()),
+            ) {
+                (Some(raw), Some(kind), Some(payload)) => (raw, kind, payload),
+                _ => continue,
+            };
+            let mut delay = (raw - prev_raw).max(0.0);
+            if let Some(limit) = idle_limit {
+                delay = delay.min(limit);
+            }
+            prev_raw = raw;
+            t += delay;
+
+            match kind {
+                "o" => events.push((t, ReplayEvent::Output(payload.as_bytes().to_vec()))),
+                "r" => {
+                    if let Some((cols, rows)) = payload.split_once('x') {
+                        if let (Ok(cols), Ok(rows)) = (cols.parse(), rows.parse()) {
+                            events.push((t, ReplayEvent::Resize(cols, rows)));
+                        }
+                    }
+                }
+                _ => {}
+            }
+        }
+        return Some((size, events));
+    }
+
+    // v1: весь файл — один JSON-объект
+    let whole: serde_json::Value = serde_json::from_str(text).ok()?;
+    if whole.get("version").and_then(|v| v.as_u64()) != Some(1) {
+        return None;
+    }
+    let size = header_size(&whole);
+    let mut events = Vec::new();
+    let mut t = 0.0;
+    for frame in whole.get("stdout")?.as_array()? {
+        let delay = frame.get(0).and_then(|v| v.as_f64()).unwrap_or(0.0);
+        let payload = frame.get(1).and_then(|v| v.as_str()).unwrap_or("");
+        t += delay.max(0.0);
+        events.push((t, ReplayEvent::Output(payload.as_bytes().to_vec())));
+    }
+    Some((size, events))
+}
+
+fn header_size(header: &serde_json::Value) -> (usize, usize) {
+    let cols = header.get("width").and_then(|v| v.as_u64()).unwrap_or(80) as usize;
+    let rows = header.get("height").and_then(|v| v.as_u64()).unwrap_or(24) as usize;
+    (cols.max(1), rows.max(1))
+}
+
+/// Сырой лог `script`. Тайминги ищем рядом: `<file>.timing`, `<stem>.timing` или `timing`.
+/// Формат таймингов (scriptreplay): `<задержка> <кол-во байт>` на строку.
+/// Без файла таймингов весь лог выводится сразу.
+fn parse_script_log(
+    path: &Path,
+    mut data: Vec<u8>,
+) -> Result<Recording, String> {
+    // Заголовок `Script started on ...` не является частью вывода
+    if data.starts_with(b"Script started") {
+        if let Some(nl) = data.iter().position(|&b| b == b'\n') {
+            data.drain(..=nl);
+        }
+    }
+
+    let candidates = [
+        PathBuf::from(format!("{}.timing", path.display())),
+        path.with_extension("timing"),
+        path.with_file_name("timing"),
+    ];
+    let timing = candidates
+        .iter()
+        .filter(|p| p.as_path() != path)
+        .find_map(|p| std::fs::read_to_string(p).ok());
+
+    let mut events = Vec::new();
+    match timing {
+        Some(timing) => {
+            let mut t = 0.0;
+            let mut offset = 0usize;
+            for line in timing.lines() {
+                let mut parts = line.split_whitespace();
+                let (delay, len) = match (parts.next(), parts.next()) {
+                    (Some(d), Some(n)) => match (d.parse::<f64>(), n.parse::<usize>()) {
+                        (Ok(d), Ok(n)) => (d, n),
+                        _ => continue,
+                    },
+                    _ => continue,
+                };
+                t += delay.max(0.0);
+                let end = (offset + len).min(data.len());
+                events.push((t, ReplayEvent::Output(data[offset..end].to_vec())));
+                offset = end;
+                if offset >= data.len() {
+                    break;
+                }
+            }
+            if offset < data.len() {
+                events.push((t, ReplayEvent::Output(data[offset..].to_vec())));
+            }
+        }
+        None => events.push((0.0, ReplayEvent::Output(data))),
+    }
+
+    Ok(((80, 24), events))
+}
+
+fn format_clock(secs: f64) -> String {
+    let total = secs.max(0.0) as u64;
+    format!("{:02}:{:02}:{:02}", total / 3600, (total / 60) % 60, total % 60)
+}
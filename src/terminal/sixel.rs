@@ -0,0 +1,267 @@
+// Декодер DECSIXEL (DCS P1;P2;P3 q ... ST).
+// Байты приходят по одному из vte put(); результат — RGBA-картинка.
+
+/// Защита от мусора/атак: больше этого картинку не растим
+const MAX_DIMENSION: usize = 4096;
+
+/// Палитра VT340 по умолчанию (проценты RGB)
+const DEFAULT_PALETTE: [(u8, u8, u8); 16] = [
+    (0, 0, 0),
+    (20, 20, 80),
+    (80, 13, 13),
+    (20, 80, 20),
+    (80, 20, 80),
+    (20, 80, 80),
+    (80, 80, 20),
+    (53, 53, 53),
+    (26, 26, 26),
+    (33, 33, 60),
+    (60, 26, 26),
+    (33, 60, 33),
+    (60, 33, 60),
+    (33, 60, 60),
+    (60, 60, 33),
+    (80, 80, 80),
+];
+
+#[derive(PartialEq)]
+enum Command {
+    None,
+    Raster,
+    Color,
+    Repeat,
+}
+
+pub struct SixelDecoder {
+    palette: Vec<[u8; 4]>,
+    color: usize,
+    // Позиция: x в пикселях, y — верх текущей полосы из 6 пикселей
+    x: usize,
+    y: usize,
+    // Буфер RGBA, растёт по мере необходимости
+    pixels: Vec<u8>,
+    buf_width: usize,
+    buf_height: usize,
+    // Фактически закрашенная область
+    width: usize,
+    height: usize,
+    background: [u8; 4],
+    command: Command,
+    params: Vec<u32>,
+    param_started: bool,
+}
+
+impl SixelDecoder {
+    /// `params` — параметры DCS. P2 = 1 означает прозрачный фон.
+    pub fn new(params: &[u16]) -> Self {
+        let mut palette = vec![[0, 0, 0, 0xff]; 256];
+        for (slot, &(r, g, b)) in palette.iter_mut().zip(DEFAULT_PALETTE.iter()) {
+            *slot = [percent(r as u32), percent(g as u32), percent(b as u32), 0xff];
+        }
+        let transparent = params.get(1).copied() == Some(1);
+        let background = if transparent { [0, 0, 0, 0] } else { palette[0] };
+
+        SixelDecoder {
+            palette,
+            color: 0,
+            x: 0,
+            y: 0,
+            pixels: Vec::new(),
+            buf_width: 0,
+            buf_height: 0,
+            width: 0,
+            height: 0,
+            background,
+            command: Command::None,
+            params: Vec::new(),
+            param_started: false,
+        }
+    }
+
+    pub fn put(&mut self, byte: u8) {
+        if self.command != Command::None {
+            match byte {
+                b'0'..=b'9' => {
+                    if !self.param_started {
+                        self.params.push(0);
+                        self.param_started = true;
+                    }
+                    if let Some(last) = self.params.last_mut() {
+                        *last = last.saturating_mul(10).saturating_add((byte - b'0') as u32);
+                    }
+                    return;
+                }
+                b';' => {
+                    if !self.param_started {
+                        self.params.push(0);
+                    }
+                    self.param_started = false;
+                    return;
+                }
+                _ => {
+                    // Repeat применяется к следующему sixel-символу
+                    if self.command == Command::Repeat {
+                        let count = self.params.first().copied().unwrap_or(1).max(1) as usize;
+                        self.command = Command::None;
+                        self.params.clear();
+                        self.param_started = false;
+                        if (0x3f..=0x7e).contains(&byte) {
+                            self.draw_sixel(byte - 0x3f, count);
+                            return;
+                        }
+                    } else {
+                        self.finish_command();
+                    }
+                }
+            }
+        }
+
+        match byte {
+            0x3f..=0x7e => self.draw_sixel(byte - 0x3f, 1),
+            b'$' => self.x = 0,
+            b'-' => {
+                self.x = 0;
+                self.y += 6;
+            }
+            b'"' => self.start_command(Command::Raster),
+            b'#' => self.start_command(Command::Color),
+            b'!' => self.start_command(Command::Repeat),
+            _ => {}
+        }
+    }
+
+    /// Готовая картинка: (ширина, высота, RGBA)
+    pub fn finish(mut self) -> Option<(usize, usize, Vec<u8>)> {
+        self.finish_command();
+        if self.width == 0 || self.height == 0 {
+            return None;
+        }
+        let mut out = Vec::with_capacity(self.width * self.height * 4);
+        for row in 0..self.height {
+            let start = row * self.buf_width * 4;
+            out.extend_from_slice(&self.pixels[start..start + self.width * 4]);
+        }
+        Some((self.width, self.height, out))
+    }
+
+    fn start_command(&mut self, command: Command) {
+        self.command = command;
+        self.params.clear();
+        self.param_started = false;
+    }
+
+    fn finish_command(&mut self) {
+        let params = std::mem::take(&mut self.params);
+        match self.command {
+            Command::Raster => {
+                // "Pan;Pad;Ph;Pv — заранее выделяем область, заливая фоном
+                if let (Some(&w), Some(&h)) = (params.get(2), params.get(3)) {
+                    let w = (w as usize).min(MAX_DIMENSION);
+                    let h = (h as usize).min(MAX_DIMENSION);
+                    if w > 0 && h > 0 {
+                        self.ensure_size(w, h);
+                        self.width = self.width.max(w);
+                        self.height = self.height.max(h);
+                    }
+                }
+            }
+            Command::Color => {
+                let idx = params.first().copied().unwrap_or(0) as usize % 256;
+                if params.len() >= 5 {
+                    let (px, py, pz) = (params[2], params[3], params[4]);
+                    self.palette[idx] = match params[1] {
+                        1 => hls_to_rgb(px, py, pz),
+                        _ => [percent(px), percent(py), percent(pz), 0xff],
+                    };
+                }
+                self.color = idx;
+            }
+            Command::Repeat | Command::None => {}
+        }
+        self.command = Command::None;
+        self.param_started = false;
+    }
+
+    fn draw_sixel(&mut self, bits: u8, count: usize) {
+        let count = count.min(MAX_DIMENSION.saturating_sub(self.x));
+        if count == 0 || self.y + 6 > MAX_DIMENSION {
+            return;
+        }
+        if bits != 0 {
+            self.ensure_size(self.x + count, self.y + 6);
+            let color = self.palette[self.color];
+            for bit in 0..6 {
+                if bits & (1 << bit) == 0 {
+                    continue;
+                }
+                let row = self.y + bit;
+                for col in self.x..self.x + count {
+                    let offset = (row * self.buf_width + col) * 4;
+                    self.pixels[offset..offset + 4].copy_from_slice(&color);
+                }
+                self.height = self.height.max(row + 1);
+            }
+            self.width = self.width.max(self.x + count);
+        }
+        self.x += count;
+    }
+
+    fn ensure_size(&mut self, width: usize, height: usize) {
+        if width <= self.buf_width && height <= self.buf_height {
+            return;
+        }
+        // Растём с запасом, чтобы не копировать буфер на каждом символе
+        let new_width = width.max(self.buf_width).next_power_of_two().min(MAX_DIMENSION);
+        let new_height = height.max(self.buf_height).next_power_of_two().min(MAX_DIMENSION);
+        let mut pixels = Vec::with_capacity(new_width * new_height * 4);
+        for row in 0..new_height {
+            if row < self.buf_height {
+                let start = row * self.buf_width * 4;
+                pixels.extend_from_slice(&self.pixels[start..start + self.buf_width * 4]);
+                for _ in self.buf_width..new_width {
+                    pixels.extend_from_slice(&self.background);
+                }
+            } else {
+                for _ in 0..new_width {
+                    pixels.extend_from_slice(&self.background);
+                }
+            }
+        }
+        self.pixels = pixels;
+        self.buf_width = new_width;
+        self.buf_height = new_height;
+    }
+}
+
+fn percent(value: u32) -> u8 {
+    ((value.min(100) * 255 + 50) / 100) as u8
+}
+
+/// HLS в нотации sixel: hue 0° = синий (у стандартного HSL 0° = красный).
+fn hls_to_rgb(hue: u32, lightness: u32, saturation: u32) -> [u8; 4] {
+    let h = ((hue + 240) % 360) as f32 / 360.0;
+    let l = lightness.min(100) as f32 / 100.0;
+    let s = saturation.min(100) as f32 / 100.0;
+
+    if s == 0.0 {
+        let v = (l * 255.0).round() as u8;
+        return [v, v, v, 0xff];
+    }
+
+    let q = if l < 0.5 { l * (1.0 + s) } else { l + s - l * s };
+    let p = 2.0 * l - q;
+    let channel = |t: f32| {
+        let t = t.rem_euclid(1.0);
+        let v = if t < 1.0 / 6.0 {
+            p + (q - p) * 6.0 * t
+        } else if t < 0.5 {
+            q
+        } else if t < 2.0 / 3.0 {
+            p + (q - p) * (2.0 / 3.0 - t) * 6.0
+        } else {
+            p
+        };
+        (v * 255.0).round() as u8
+    };
+    [channel(h + 1.0 / 3.0), channel(h), channel(h - 1.0 / 3.0), 0xff]
+}
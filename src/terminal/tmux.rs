@@ -0,0 +1,762 @@
+use std::collections::{HashMap, VecDeque};
+use std::sync::mpsc;
+
+use crate::ssh::session::{SshCommand, SshConnection, DEFAULT_WORD_SEPARATORS};
+use crate::terminal::highlight::HighlightRule;
+use crate::terminal::keybindings::KeySend;
+use crate::terminal::palette::ColorScheme;
+use crate::terminal::widget::{cell_size_for, TerminalWidget};
+
+// tmux control mode (`tmux -CC`): вместо отрисовки escape-последовательностями
+// tmux шлёт построчный протокол (%output, %layout-change, ...), а мы
+// показываем окна tmux вкладками, а панели — нативными терминалами.
+
+/// DCS, которым tmux открывает control mode
+pub const CONTROL_MODE_START: &[u8] = b"\x1bP1000p";
+
+/// Сколько байт ввода кладём в одну команду send-keys
+const SEND_KEYS_CHUNK: usize = 128;
+
+// ── Раскладка окна ──
+
+/// Панель из строки раскладки: позиция и размер в ячейках.
+#[derive(Clone, Copy)]
+struct LayoutPane {
+    id: u32,
+    x: usize,
+    y: usize,
+    width: usize,
+    height: usize,
+}
+
+/// `b25d,80x24,0,0{40x24,0,0,1,39x24,41,0,2}` → список панелей.
+/// Ориентация разбиения не нужна: координаты у каждой панели абсолютные.
+fn parse_layout(layout: &str) -> Option<Vec<LayoutPane>> {
+    // Отрезаем контрольную сумму
+    let (_, body) = layout.split_once(',')?;
+    let mut parser = LayoutParser {
+        bytes: body.as_bytes(),
+        pos: 0,
+    };
+    let mut panes = Vec::new();
+    parser.node(&mut panes)?;
+    Some(panes)
+}
+
+struct LayoutParser<'a> {
+    bytes: &'a [u8],
+    pos: usize,
+}
+
+impl LayoutParser<'_> {
+    fn number(&mut self) -> Option<usize> {
+        let start = self.pos;
+        while self.bytes.get(self.pos).is_some_and(|b| b.is_ascii_digit()) {
+            self.pos += 1;
+        }
+        std::str::from_utf8(&self.bytes[start..self.pos])
+            .ok()?
+            .parse()
+            .ok()
+    }
+
+    fn expect(&mut self, byte: u8) -> Option<()> {
+        if self.bytes.get(self.pos) == Some(&byte) {
+            self.pos += 1;
+            Some(())
+        } else {
+            None
+        }
+    }
+
+    fn node(&mut self, panes: &mut Vec<LayoutPane>) -> Option<()> {
+        let width = self.number()?;
+        self.expect(b'x')?;
+        let height = self.number()?;
+        self.expect(b',')?;
+        let x = self.number()?;
+        self.expect(b',')?;
+        let y = self.number()?;
+
+        match self.bytes.get(self.pos) {
+            Some(&open @ (b'{' | b'[')) => {
+                let close = if open == b'{' { b'}' } else { b']' };
+                self.pos += 1;
+                loop {
+                    self.node(panes)?;
+                    match self.bytes.get(self.pos) {
+                        Some(b',') => self.pos += 1,
+                        Some(&b) if b == close => {
+                            self.pos += 1;
+                            return Some(());
+                        }
+                        _ => return None,
+                    }
+                }
+            }
+            Some(b',') => {
+                self.pos += 1;
+                let id = self.number()? as u32;
+                panes.push(LayoutPane {
+                    id,
+                    x,
+                    y,
+                    width,
+                    height,
+                });
+                Some(())
+            }
+            _ => None,
+        }
+    }
+}
+
+/// `\ooo` → байт (tmux экранирует управляющие символы и `\`).
+fn unescape_output(data: &[u8]) -> Vec<u8> {
+    let mut out = Vec::with_capacity(data.len());
+    let mut i = 0;
+    while i < data.len() {
+        if data[i] == b'\\'
+            && i + 4 <= data.len()
+            && data[i + 1..i + 4].iter().all(|b| (b'0'..=b'7').contains(b))
+        {
+            let value = data[i + 1..i + 4]
+                .iter()
+                .fold(0u32, |acc, b| acc * 8 + (b - b'0') as u32);
+            out.push(value as u8);
+            i += 4;
+        } else {
+            out.push(data[i]);
+            i += 1;
+        }
+    }
+    out
+}
+
+/// `@12` / `%3` / `$1` → 12 / 3 / 1
+fn parse_id(token: &str) -> Option<u32> {
+    token.get(1..)?.parse().ok()
+}
+
+// ── Сессия ──
+
+struct Window {
+    id: u32,
+    index: u32,
+    name: String,
+    panes: Vec<LayoutPane>,
+    active_pane: Option<u32>,
+}
+
+struct Pane {
+    terminal: TerminalWidget,
+    // Локальная труба: ввод терминала → send-keys, %output → терминал
+    ssh: SshConnection,
+    input_rx: mpsc::Receiver<SshCommand>,
+    output_tx: mpsc::Sender<Vec<u8>>,
+}
+
+/// Чего ждём в ответ на отправленную команду (ответы приходят по порядку).
+enum Pending {
+    Ignore,
+    ListWindows,
+    ListPanes,
+    Capture { pane: u32, cursor: (usize, usize) },
+}
+
+pub enum TmuxAction {
+    SplitHorizontal,
+    SplitVertical,
+    ClosePane,
+    NewWindow,
+    Detach,
+}
+
+pub struct TmuxSession {
+    buf: Vec<u8>,
+    // Первый блок %begin/%end — ответ на команду запуска tmux, не наш
+    ready: bool,
+    block: Option<Vec<Vec<u8>>>,
+    pending: VecDeque<Pending>,
+    windows: Vec<Window>,
+    active_window: Option<u32>,
+    panes: HashMap<u32, Pane>,
+    client_size: (usize, usize),
+    highlight_rules: Vec<HighlightRule>,
+    key_sends: Vec<KeySend>,
+    scheme: ColorScheme,
+    word_separators: String,
+    paste_confirm_chars: u32,
+    // Остаток потока после %exit — возвращается обычному терминалу
+    exited: Option<Vec<u8>>,
+}
+
+impl TmuxSession {
+    /// `initial` — всё, что пришло после DCS в том же куске вывода.
+    pub fn new(initial: Vec<u8>, highlight_rules: &[HighlightRule]) -> Self {
+        TmuxSession {
+            buf: initial,
+            ready: false,
+            block: None,
+            pending: VecDeque::new(),
+            windows: Vec::new(),
+            active_window: None,
+            panes: HashMap::new(),
+            client_size: (0, 0),
+            highlight_rules: highlight_rules.to_vec(),
+            key_sends: Vec::new(),
+            scheme: ColorScheme::default(),
+            word_separators: DEFAULT_WORD_SEPARATORS.to_string(),
+            paste_confirm_chars: crate::ssh::session::default_paste_confirm_chars(),
+            exited: None,
+        }
+    }
+
+    /// Control mode закончился: остаток вывода для обычного терминала.
+    pub fn take_exit(&mut self) -> Option<Vec<u8>> {
+        self.exited.take()
+    }
+
+    pub fn set_highlight_rules(&mut self, rules: &[HighlightRule]) {
+        self.highlight_rules = rules.to_vec();
+        for pane in self.panes.values_mut() {
+            pane.terminal.set_highlight_rules(rules);
+        }
+    }
+
+    pub fn set_key_sends(&mut self, sends: &[KeySend]) {
+        self.key_sends = sends.to_vec();
+        for pane in self.panes.values_mut() {
+            pane.terminal.set_key_sends(sends);
+        }
+    }
+
+    pub fn set_color_scheme(&mut self, scheme: &ColorScheme) {
+        self.scheme = scheme.clone();
+        for pane in self.panes.values_mut() {
+            pane.terminal.set_color_scheme(scheme);
+        }
+    }
+
+    pub fn set_word_separators(&mut self, separators: &str) {
+        self.word_separators = separators.to_string();
+        for pane in self.panes.values_mut() {
+            pane.terminal.set_word_separators(separators);
+        }
+    }
+
+    pub fn set_paste_confirm_chars(&mut self, chars: u32) {
+        self.paste_confirm_chars = chars;
+        for pane in self.panes.values_mut() {
+            pane.terminal.set_paste_confirm_chars(chars);
+        }
+    }
+
+    /// Терминалы панелей (для фоновой обработки триггеров).
+    pub fn terminals_mut(&mut self) -> impl Iterator<Item = &mut TerminalWidget> {
+        self.panes.values_mut().map(|p| &mut p.terminal)
+    }
+
+    // ── Протокол ──
+
+    /// Читаем control-поток из канала и раздаём ввод панелей в tmux.
+    pub fn process(&mut self, ssh: &SshConnection) {
+        while let Ok(data) = ssh.output_rx.try_recv() {
+            if let Some(rest) = &mut self.exited {
+                rest.extend_from_slice(&data);
+                continue;
+            }
+            self.buf.extend_from_slice(&data);
+        }
+        self.parse_lines(ssh);
+
+        let mut commands = Vec::new();
+        for (&id, pane) in &self.panes {
+            while let Ok(command) = pane.input_rx.try_recv() {
+                if let SshCommand::Data(bytes) = command {
+                    for chunk in bytes.chunks(SEND_KEYS_CHUNK) {
+                        let hex: Vec<String> = chunk.iter().map(|b| format!("{:02x}", b)).collect();
+                        commands.push(format!("send-keys -t %{} -H {}", id, hex.join(" ")));
+                    }
+                }
+                // Resize панели игнорируем: размер задаёт refresh-client
+            }
+        }
+        for command in commands {
+            self.send(ssh, &command, Pending::Ignore);
+        }
+
+        for pane in self.panes.values_mut() {
+            pane.terminal.process_ssh_output(&pane.ssh);
+        }
+    }
+
+    fn send(&mut self, ssh: &SshConnection, command: &str, pending: Pending) {
+        ssh.send(format!("{}\n", command).as_bytes());
+        self.pending.push_back(pending);
+    }
+
+    fn parse_lines(&mut self, ssh: &SshConnection) {
+        // Парсим из буфера построчно; хвост без \n ждёт следующего куска
+        let mut start = 0;
+        while self.exited.is_none() {
+            let end = match self.buf[start..].iter().position(|&b| b == b'\n') {
+                Some(pos) => start + pos,
+                None => break,
+            };
+            let mut line = self.buf[start..end].to_vec();
+            if line.last() == Some(&b'\r') {
+                line.pop();
+            }
+            start = end + 1;
+            self.handle_line(line, ssh);
+        }
+        self.buf.drain(..start);
+
+        if let Some(rest) = &mut self.exited {
+            // После %exit tmux закрывает DCS (ST) — дальше обычный вывод
+            let mut tail = std::mem::take(&mut self.buf);
+            if tail.starts_with(b"\x1b\\") {
+                tail.drain(..2);
+            }
+            tail.append(rest);
+            *rest = tail;
+        }
+    }
+
+    fn handle_line(&mut self, line: Vec<u8>, ssh: &SshConnection) {
+        if let Some(block) = &mut self.block {
+            let end = line.starts_with(b"%end");
+            let error = line.starts_with(b"%error");
+            if end || error {
+                let lines = self.block.take().unwrap_or_default();
+                self.finish_block(lines, error, ssh);
+            } else {
+                block.push(line);
+            }
+            return;
+        }
+
+        if let Some(rest) = line.strip_prefix(b"%output ") {
+            if let Some(space) = rest.iter().position(|&b| b == b' ') {
+                let pane = parse_id(&String::from_utf8_lossy(&rest[..space]));
+                self.pane_output(pane, &rest[space + 1..]);
+            }
+            return;
+        }
+        if let Some(rest) = line.strip_prefix(b"%extended-output ") {
+            // %extended-output %id age ... : data
+            let pane = rest
+                .split(|&b| b == b' ')
+                .next()
+                .and_then(|id| parse_id(&String::from_utf8_lossy(id)));
+            if let Some(pos) = rest.windows(3).position(|w| w == b" : ") {
+                self.pane_output(pane, &rest[pos + 3..]);
+            }
+            return;
+        }
+
+        let text = String::from_utf8_lossy(&line).into_owned();
+        let mut parts = text.split(' ');
+        let command = parts.next().unwrap_or("");
+        let args: Vec<&str> = parts.collect();
+        match command {
+            "%begin" => self.block = Some(Vec::new()),
+            "%window-add" | "%session-changed" | "%sessions-changed" if self.ready => {
+                self.request_windows(ssh);
+            }
+            "%window-close" | "%unlinked-window-close" => {
+                if let Some(id) = args.first().and_then(|t| parse_id(t)) {
+                    self.windows.retain(|w| w.id != id);
+                    if self.active_window == Some(id) {
+                        self.active_window = self.windows.first().map(|w| w.id);
+                    }
+                    self.prune_panes();
+                }
+            }
+            "%window-renamed" => {
+                if let Some(id) = args.first().and_then(|t| parse_id(t)) {
+                    let name = args[1..].join(" ");
+                    if let Some(window) = self.windows.iter_mut().find(|w| w.id == id) {
+                        window.name = name;
+                    }
+                }
+            }
+            "%layout-change" => {
+                // %layout-change @id layout visible-layout flags
+                if let Some(id) = args.first().and_then(|t| parse_id(t)) {
+                    let layout = args.get(2).or(args.get(1)).copied().unwrap_or("");
+                    if let Some(panes) = parse_layout(layout) {
+                        if let Some(window) = self.windows.iter_mut().find(|w| w.id == id) {
+                            window.panes = panes;
+                        }
+                        self.sync_panes();
+                    }
+                }
+            }
+            "%session-window-changed" => {
+                if let Some(id) = args.get(1).and_then(|t| parse_id(t)) {
+                    self.active_window = Some(id);
+                }
+            }
+            "%window-pane-changed" => {
+                let window = args.first().and_then(|t| parse_id(t));
+                let pane = args.get(1).and_then(|t| parse_id(t));
+                if let Some(window) = self.windows.iter_mut().find(|w| Some(w.id) == window) {
+                    window.active_pane = pane;
+                }
+            }
+            "%exit" => self.exited = Some(Vec::new()),
+            _ => {}
+        }
+    }
+
+    fn pane_output(&mut self, pane: Option<u32>, data: &[u8]) {
+        if let Some(pane) = pane.and_then(|id| self.panes.get(&id)) {
+            let _ = pane.output_tx.send(unescape_output(data));
+        }
+    }
+
+    fn finish_block(&mut self, lines: Vec<Vec<u8>>, error: bool, ssh: &SshConnection) {
+        if !self.ready {
+            self.ready = true;
+            self.start(ssh);
+            return;
+        }
+        let pending = match self.pending.pop_front() {
+            Some(p) => p,
+            None => return,
+        };
+        if error {
+            return;
+        }
+        match pending {
+            Pending::Ignore => {}
+            Pending::ListWindows => {
+                let mut windows = Vec::new();
+                for line in &lines {
+                    let line = String::from_utf8_lossy(line);
+                    // @id index active layout name...
+                    let mut parts = line.splitn(5, ' ');
+                    let (id, index, active, layout) =
+                        match (parts.next(), parts.next(), parts.next(), parts.next()) {
+                            (Some(id), Some(index), Some(active), Some(layout)) => (
+                                parse_id(id),
+                                index.parse().unwrap_or(0),
+                                active == "1",
+                                layout,
+                            ),
+                            _ => continue,
+                        };
+                    let id = match id {
+                        Some(id) => id,
+                        None => continue,
+                    };
+                    let previous = self.windows.iter().find(|w| w.id == id);
+                    windows.push(Window {
+                        id,
+                        index,
+                        name: parts.next().unwrap_or("").to_string(),
+                        panes: parse_layout(layout).unwrap_or_default(),
+                        active_pane: previous.and_then(|w| w.active_pane),
+                    });
+                    if active {
+                        self.active_window = Some(id);
+                    }
+                }
+                self.windows = windows;
+                self.sync_panes();
+            }
+            Pending::ListPanes => {
+                let mut captures = Vec::new();
+                for line in &lines {
+                    let line = String::from_utf8_lossy(line);
+                    // %id @window active cursor_x cursor_y
+                    let parts: Vec<&str> = line.split(' ').collect();
+                    if parts.len() < 5 {
+                        continue;
+                    }
+                    let (pane, window) = match (parse_id(parts[0]), parse_id(parts[1])) {
+                        (Some(p), Some(w)) => (p, w),
+                        _ => continue,
+                    };
+                    if parts[2] == "1" {
+                        if let Some(window) = self.windows.iter_mut().find(|w| w.id == window) {
+                            window.active_pane = Some(pane);
+                        }
+                    }
+                    let cursor = (parts[3].parse().unwrap_or(0), parts[4].parse().unwrap_or(0));
+                    captures.push((pane, cursor));
+                }
+                // Текущее содержимое панелей — tmux сам его не присылает
+                for (pane, cursor) in captures {
+                    self.send(
+                        ssh,
+                        &format!("capture-pane -p -e -J -t %{}", pane),
+                        Pending::Capture { pane, cursor },
+                    );
+                }
+            }
+            Pending::Capture { pane, cursor } => {
+                if let Some(pane) = self.panes.get(&pane) {
+                    let mut content = lines.join(&b"\r\n"[..]);
+                    content.extend_from_slice(
+                        format!("\x1b[{};{}H", cursor.1 + 1, cursor.0 + 1).as_bytes(),
+                    );
+                    let _ = pane.output_tx.send(content);
+                }
+            }
+        }
+    }
+
+    fn start(&mut self, ssh: &SshConnection) {
+        if self.client_size.0 > 0 {
+            let (cols, rows) = self.client_size;
+            self.send(
+                ssh,
+                &format!("refresh-client -C {},{}", cols, rows),
+                Pending::Ignore,
+            );
+        }
+        self.request_windows(ssh);
+        self.send(
+            ssh,
+            "list-panes -s -F \"#{pane_id} #{window_id} #{pane_active} #{cursor_x} #{cursor_y}\"",
+            Pending::ListPanes,
+        );
+    }
+
+    fn request_windows(&mut self, ssh: &SshConnection) {
+        self.send(
+            ssh,
+            "list-windows -F \"#{window_id} #{window_index} #{window_active} #{window_visible_layout} #{window_name}\"",
+            Pending::ListWindows,
+        );
+    }
+
+    /// Создать терминалы для новых панелей и убрать исчезнувшие.
+    fn sync_panes(&mut self) {
+        for window in &self.windows {
+            for layout in &window.panes {
+                if self.panes.contains_key(&layout.id) {
+                    continue;
+                }
+                let mut terminal = TerminalWidget::new(layout.width.max(1), layout.height.max(1));
+                terminal.set_highlight_rules(&self.highlight_rules);
+                terminal.set_key_sends(&self.key_sends);
+                terminal.set_color_scheme(&self.scheme);
+                terminal.set_word_separators(&self.word_separators);
+                terminal.set_paste_confirm_chars(self.paste_confirm_chars);
+                let (ssh, input_rx, output_tx) = SshConnection::local();
+                self.panes.insert(
+                    layout.id,
+                    Pane {
+                        terminal,
+                        ssh,
+                        input_rx,
+                        output_tx,
+                    },
+                );
+            }
+        }
+        self.prune_panes();
+    }
+
+    fn prune_panes(&mut self) {
+        let windows = &self.windows;
+        self.panes
+            .retain(|id, _| windows.iter().any(|w| w.panes.iter().any(|p| p.id == *id)));
+    }
+
+    fn focused_pane(&self) -> Option<u32> {
+        let window = self
+            .windows
+            .iter()
+            .find(|w| Some(w.id) == self.active_window)?;
+        window
+            .active_pane
+            .or_else(|| window.panes.first().map(|p| p.id))
+    }
+
+    /// Ввод в активную панель — как с клавиатуры (уйдёт через send-keys).
+    pub fn send_to_focused(&self, data: &[u8]) {
+        if let Some(pane) = self.focused_pane().and_then(|id| self.panes.get(&id)) {
+            pane.ssh.send(data);
+        }
+    }
+
+    // ── UI ──
+
+    pub fn action(&mut self, ssh: &SshConnection, action: TmuxAction) {
+        let target = self
+            .focused_pane()
+            .map(|id| format!(" -t %{}", id))
+            .unwrap_or_default();
+        let command = match action {
+            TmuxAction::SplitHorizontal => format!("split-window -h{}", target),
+            TmuxAction::SplitVertical => format!("split-window -v{}", target),
+            TmuxAction::ClosePane => format!("kill-pane{}", target),
+            TmuxAction::NewWindow => "new-window".to_string(),
+            TmuxAction::Detach => "detach-client".to_string(),
+        };
+        self.send(ssh, &command, Pending::Ignore);
+    }
+
+    /// Те же горячие клавиши, что у split panes, но команды уходят в tmux.
+    pub fn handle_shortcuts(&mut self, ui: &egui::Ui, ssh: &SshConnection) {
+        let cs = egui::Modifiers::CTRL | egui::Modifiers::SHIFT;
+        if ui.input_mut(|i| i.consume_key(cs, egui::Key::E)) {
+            self.action(ssh, TmuxAction::SplitHorizontal);
+        }
+        if ui.input_mut(|i| i.consume_key(cs, egui::Key::O)) {
+            self.action(ssh, TmuxAction::SplitVertical);
+        }
+        if ui.input_mut(|i| i.consume_key(cs, egui::Key::W)) {
+            self.action(ssh, TmuxAction::ClosePane);
+        }
+        // Alt+стрелки нужны и самим программам: берём, только если есть куда
+        // переключаться и терминал их не использует
+        let Some(pane) = self.focused_pane() else {
+            return;
+        };
+        let split = self
+            .windows
+            .iter()
+            .find(|w| Some(w.id) == self.active_window)
+            .is_some_and(|w| w.panes.len() > 1);
+        if !split {
+            return;
+        }
+        let terminal = self.panes.get(&pane).map(|p| &p.terminal);
+        let mut select = None;
+        for (key, flag) in [
+            (egui::Key::ArrowLeft, "-L"),
+            (egui::Key::ArrowRight, "-R"),
+            (egui::Key::ArrowUp, "-U"),
+            (egui::Key::ArrowDown, "-D"),
+        ] {
+            if terminal.is_some_and(|t| t.binds_key(key, egui::Modifiers::ALT)) {
+                continue;
+            }
+            if ui.input_mut(|i| i.consume_key(egui::Modifiers::ALT, key)) {
+                select = Some(flag);
+            }
+        }
+        if let Some(flag) = select {
+            let command = format!("select-pane -t %{} {}", pane, flag);
+            self.send(ssh, &command, Pending::Ignore);
+        }
+    }
+
+    pub fn show(&mut self, ui: &mut egui::Ui, ssh: &SshConnection, interactive: bool) {
+        self.process(ssh);
+
+        // Окна tmux — вкладками
+        let mut select_window: Option<u32> = None;
+        ui.horizontal(|ui| {
+            ui.colored_label(crate::theme::AMBER, "tmux:");
+            let mut windows: Vec<&Window> = self.windows.iter().collect();
+            windows.sort_by_key(|w| w.index);
+            for window in windows {
+                let active = self.active_window == Some(window.id);
+                let label = format!("[{}:{}]", window.index, window.name);
+                if ui.selectable_label(active, label).clicked() && !active {
+                    select_window = Some(window.id);
+                }
+            }
+        });
+        if let Some(id) = select_window {
+            self.active_window = Some(id);
+            self.send(ssh, &format!("select-window -t @{}", id), Pending::Ignore);
+        }
+
+        let area = ui.available_rect_before_wrap();
+        ui.allocate_rect(area, egui::Sense::hover());
+        let cell = self
+            .panes
+            .values()
+            .next()
+            .map(|p| p.terminal.calculate_cell_size(ui))
+            .unwrap_or_else(|| cell_size_for(ui, 14.0));
+
+        // Размер клиента tmux = вся область в ячейках
+        let size = (
+            ((area.width() / cell.x) as usize).max(1),
+            ((area.height() / cell.y) as usize).max(1),
+        );
+        if size != self.client_size {
+            self.client_size = size;
+            if self.ready {
+                self.send(
+                    ssh,
+                    &format!("refresh-client -C {},{}", size.0, size.1),
+                    Pending::Ignore,
+                );
+            }
+        }
+
+        let layout = match self
+            .windows
+            .iter()
+            .find(|w| Some(w.id) == self.active_window)
+        {
+            Some(window) => window.panes.clone(),
+            None => {
+                ui.painter().text(
+                    area.center(),
+                    egui::Align2::CENTER_CENTER,
+                    "// attaching to tmux...",
+                    egui::FontId::monospace(13.0),
+                    crate::theme::GREEN_DIM,
+                );
+                return;
+            }
+        };
+        let focused = self.focused_pane();
+
+        // Промежутки между панелями tmux оставляет под рамки
+        ui.painter()
+            .rect_filled(area, 0.0, crate::theme::GREEN_DARK);
+
+        let mut clicked: Option<u32> = None;
+        for layout_pane in &layout {
+            let pane = match self.panes.get_mut(&layout_pane.id) {
+                Some(p) => p,
+                None => continue,
+            };
+            let min =
+                area.min + egui::vec2(layout_pane.x as f32 * cell.x, layout_pane.y as f32 * cell.y);
+            let size = egui::vec2(
+                layout_pane.width as f32 * cell.x + 0.5,
+                layout_pane.height as f32 * cell.y + 0.5,
+            );
+            let rect = egui::Rect::from_min_size(min, size).intersect(area);
+            if rect.width() <= 0.0 || rect.height() <= 0.0 {
+                continue;
+            }
+            let mut child = ui.new_child(
+                egui::UiBuilder::new()
+                    .max_rect(rect)
+                    .id_salt(("tmux_pane", layout_pane.id))
+                    .layout(egui::Layout::top_down(egui::Align::Min)),
+            );
+            let is_focused = focused == Some(layout_pane.id);
+            pane.terminal.set_focus(is_focused);
+            pane.terminal.show(&mut child, &pane.ssh, interactive);
+            if !is_focused && pane.terminal.has_focus() {
+                clicked = Some(layout_pane.id);
+            }
+        }
+
+        if let Some(pane) = clicked {
+            if let Some(window) = self
+                .windows
+                .iter_mut()
+                .find(|w| Some(w.id) == self.active_window)
+            {
+                window.active_pane = Some(pane);
+            }
+            self.send(ssh, &format!("select-pane -t %{}", pane), Pending::Ignore);
+        }
+    }
+}
@@ -1,5 +1,37 @@
-use crate::ssh::session::SshConnection;
-use crate::terminal::emulator::{Cell, TermColor, TerminalEmulator};
+use std::sync::Arc;
+use std::time::{Duration, Instant};
+
+use crate::ssh::session::{SshConnection, DEFAULT_WORD_SEPARATORS};
+use crate::terminal::emulator::{Cell, FinishedCommand, TerminalEmulator};
+use crate::terminal::export;
+use crate::terminal::highlight::{HighlightRule, Highlighter, RuleHit};
+use crate::terminal::hints::{self, Hint};
+use crate::terminal::keybindings::{self, Action, KeyChord, KeySend};
+use crate::terminal::keyboard;
+use crate::terminal::palette::ColorScheme;
+use crate::terminal::primary;
+use crate::terminal::recorder::SessionRecorder;
+use crate::terminal::tmux;
+
+/// Вставка длиннее этого (или с переводом строки) требует подтверждения;
+/// по умолчанию, пока сессия не задала свой порог
+const PASTE_CONFIRM_CHARS: usize = 1024;
+
+/// Крупная вставка уходит кусками с паузами, чтобы медленный shell не терял символы
+const PASTE_CHUNK: usize = 4096;
+const PASTE_CHUNK_DELAY: Duration = Duration::from_millis(10);
+/// "Медленная" вставка — для serial-консолей и устройств без управления потоком
+const SLOW_PASTE_CHUNK: usize = 32;
+const SLOW_PASTE_CHUNK_DELAY: Duration = Duration::from_millis(20);
+
+/// Размер шрифта по умолчанию и пределы масштабирования
+const DEFAULT_FONT_SIZE: f32 = 14.0;
+const MIN_FONT_SIZE: f32 = 8.0;
+const MAX_FONT_SIZE: f32 = 32.0;
+
+/// Полоса меток команд (OSC 133) у левого края и зона клика по ней
+const MARK_WIDTH: f32 = 3.0;
+const MARK_CLICK_WIDTH: f32 = 8.0;
 
 // --- Выделение текста ---
 
@@ -25,23 +57,6 @@ impl Selection {
         self.start_row == self.end_row && self.start_col == self.end_col
     }
 
-    fn contains(&self, row: usize, col: usize) -> bool {
-        let ((sr, sc), (er, ec)) = self.normalized();
-        if row < sr || row > er {
-            return false;
-        }
-        if sr == er {
-            return col >= sc && col <= ec;
-        }
-        if row == sr {
-            return col >= sc;
-        }
-        if row == er {
-            return col <= ec;
-        }
-        true
-    }
-
     fn selection_ranges(&self, max_cols: usize) -> Vec<(usize, usize, usize)> {
         let ((sr, sc), (er, ec)) = self.normalized();
         let mut ranges = Vec::new();
@@ -61,6 +76,176 @@ impl Selection {
     }
 }
 
+// --- Кэш раскладки строк ---
+
+/// Galley строки экрана и всё, от чего он зависит. Строка перекладывается,
+/// только если изменились её ячейки, выделение или подсветка.
+struct RowCache {
+    cells: Vec<Cell>,
+    // Участки правил подсветки (из HighlightCache)
+    highlights: Vec<(usize, usize, egui::Color32)>,
+    // Совпадения поиска
+    marks: Vec<(usize, usize, egui::Color32)>,
+    selected: Option<(usize, usize)>,
+    galley: Arc<egui::Galley>,
+}
+
+/// Сколько строк экрана максимум склеиваем в одну логическую для подсветки
+const HIGHLIGHT_MAX_WRAP: i64 = 64;
+
+/// Участки правил подсветки по абсолютным строкам. Regex идёт по логической строке
+/// (с переносами по ширине), результат держится, пока не изменился вывод.
+#[derive(Default)]
+struct HighlightCache {
+    generation: u64,
+    lines: std::collections::HashMap<i64, Vec<(usize, usize, egui::Color32)>>,
+}
+
+impl HighlightCache {
+    fn clear(&mut self) {
+        self.lines.clear();
+    }
+
+    fn row(
+        &mut self,
+        emulator: &TerminalEmulator,
+        highlighter: &Highlighter,
+        line: i64,
+    ) -> Vec<(usize, usize, egui::Color32)> {
+        if highlighter.is_empty() {
+            return Vec::new();
+        }
+        if self.generation != emulator.generation() {
+            self.generation = emulator.generation();
+            self.lines.clear();
+        }
+        if let Some(spans) = self.lines.get(&line) {
+            return spans.clone();
+        }
+
+        // Границы логической строки: предыдущие строки с переносом и последующие
+        let mut first = line;
+        while line - first < HIGHLIGHT_MAX_WRAP && emulator.line_wrapped(first - 1) {
+            first -= 1;
+        }
+        let mut last = line;
+        while last - first < HIGHLIGHT_MAX_WRAP && emulator.line_wrapped(last) {
+            last += 1;
+        }
+        let mut text = String::new();
+        let mut starts = Vec::new();
+        let mut col = 0;
+        for l in first..=last {
+            starts.push(col);
+            if let Some(row) = emulator.row_at(l) {
+                text.extend(row.iter().map(|cell| if cell.c == '\0' { ' ' } else { cell.c }));
+                col += row.len();
+            }
+        }
+        starts.push(col);
+
+        let spans = highlighter.spans(&text);
+        for (i, l) in (first..=last).enumerate() {
+            let (row_start, row_end) = (starts[i], starts[i + 1]);
+            let row_spans = spans
+                .iter()
+                .filter(|&&(start, end, _)| start < row_end && end > row_start)
+                .map(|&(start, end, color)| {
+                    (start.max(row_start) - row_start, end.min(row_end) - row_start, color)
+                })
+                .collect();
+            self.lines.insert(l, row_spans);
+        }
+        self.lines.get(&line).cloned().unwrap_or_default()
+    }
+}
+
+// --- Подтверждение вставки ---
+
+struct PendingPaste {
+    text: String,
+    editing: bool,
+}
+
+/// Вставка, которая отправляется по кускам (см. pump_paste).
+struct PasteQueue {
+    data: Vec<u8>,
+    sent: usize,
+    slow: bool,
+    next_at: Instant,
+}
+
+// --- Hint mode (быстрое копирование токенов) ---
+
+struct HintMode {
+    hints: Vec<Hint>,
+    // Набранный префикс метки
+    typed: String,
+    // Текущий токен при переборе через Tab
+    current: Option<usize>,
+}
+
+impl HintMode {
+    fn matching(&self) -> impl Iterator<Item = (usize, &Hint)> {
+        self.hints
+            .iter()
+            .enumerate()
+            .filter(|(_, h)| h.label.starts_with(&self.typed))
+    }
+}
+
+/// Что произошло в терминале с прошлого take_activity (бейджи в списке сессий).
+#[derive(Default, Clone, Copy)]
+pub struct Activity {
+    pub output: bool,
+    pub bell: bool,
+}
+
+impl Activity {
+    pub fn merge(&mut self, other: Activity) {
+        self.output |= other.output;
+        self.bell |= other.bell;
+    }
+}
+
+// --- Поиск по буферу ---
+
+struct SearchState {
+    query: String,
+    /// Совпадения: (абсолютная строка, колонка начала, колонка конца exclusive)
+    matches: Vec<(i64, usize, usize)>,
+    current: Option<usize>,
+}
+
+// --- Copy mode (навигация по буферу с клавиатуры) ---
+
+struct CopyMode {
+    /// Курсор: абсолютная строка и колонка
+    line: i64,
+    col: usize,
+    /// Начало выделения (v / V)
+    anchor: Option<(i64, usize)>,
+    /// V — выделение целыми строками
+    linewise: bool,
+}
+
+impl CopyMode {
+    /// Начало и конец выделения (включительно) в порядке следования.
+    fn range(&self, cols: usize) -> Option<((i64, usize), (i64, usize))> {
+        let anchor = self.anchor?;
+        let (start, end) = if anchor <= (self.line, self.col) {
+            (anchor, (self.line, self.col))
+        } else {
+            ((self.line, self.col), anchor)
+        };
+        if self.linewise {
+            Some(((start.0, 0), (end.0, cols.saturating_sub(1))))
+        } else {
+            Some((start, end))
+        }
+    }
+}
+
 // --- Виджет терминала ---
 
 pub struct TerminalWidget {
@@ -73,6 +258,48 @@ pub struct TerminalWidget {
     selection: Option<Selection>,
     selection_anchor: Option<(usize, usize)>,
     selecting: bool,
+    // Запись сессии (asciicast)
+    recorder: Option<SessionRecorder>,
+    recording_error: Option<String>,
+    // Многострочная вставка, ожидающая подтверждения
+    pending_paste: Option<PendingPaste>,
+    paste_queue: Option<PasteQueue>,
+    slow_paste: bool,
+    // Правила подсветки/триггеров сессии
+    highlighter: Highlighter,
+    rule_hits: Vec<RuleHit>,
+    hint_mode: Option<HintMode>,
+    // Текстуры картинок (sixel и т.п.) по id
+    image_textures: std::collections::HashMap<u64, egui::TextureHandle>,
+    // Копия пользовательского ввода для broadcast mode (None — не собираем)
+    captured_input: Option<Vec<u8>>,
+    // Поток после входа в tmux control mode — забирает app
+    tmux_handoff: Option<Vec<u8>>,
+    // Хвост куска, похожий на начало DCS tmux: ждёт следующего куска
+    tmux_marker_tail: Vec<u8>,
+    // Завершённые команды (OSC 133) — для уведомлений о долгих командах
+    finished_commands: Vec<FinishedCommand>,
+    search: Option<SearchState>,
+    copy_mode: Option<CopyMode>,
+    // Свои последовательности сессии (сочетание → байты)
+    key_sends: Vec<(KeyChord, Vec<u8>)>,
+    scheme: ColorScheme,
+    activity: Activity,
+    // Смещение указателя от верха ползунка, пока его тянут
+    scrollbar_grab: Option<f32>,
+    // Символы, на которых заканчивается слово при двойном клике
+    word_separators: Vec<char>,
+    // Вставка длиннее стольких символов — через подтверждение (0 — только многострочная)
+    paste_confirm_chars: usize,
+    // Дробный остаток прокрутки колёсиком на альтернативном экране (в строках)
+    wheel_remainder: f32,
+    // Кэш galley по строкам экрана; (generation, view_top) кадра, для которого он собран
+    row_cache: Vec<Option<RowCache>>,
+    row_cache_view: Option<(u64, i64)>,
+    // pixels_per_point и заполненность атласа шрифтов: egui пересоздаёт атлас,
+    // и старые galley становятся невалидными
+    row_cache_fonts: (f32, f32),
+    highlight_cache: HighlightCache,
 }
 
 impl TerminalWidget {
@@ -80,36 +307,252 @@ impl TerminalWidget {
         TerminalWidget {
             emulator: TerminalEmulator::new(cols, rows),
             focus: true,
-            font_size: 14.0,
+            font_size: DEFAULT_FONT_SIZE,
             last_cols: cols,
             last_rows: rows,
             selection: None,
             selection_anchor: None,
             selecting: false,
+            recorder: None,
+            recording_error: None,
+            pending_paste: None,
+            paste_queue: None,
+            slow_paste: false,
+            highlighter: Highlighter::default(),
+            rule_hits: Vec::new(),
+            hint_mode: None,
+            image_textures: std::collections::HashMap::new(),
+            captured_input: None,
+            tmux_handoff: None,
+            tmux_marker_tail: Vec::new(),
+            finished_commands: Vec::new(),
+            search: None,
+            copy_mode: None,
+            key_sends: Vec::new(),
+            scheme: ColorScheme::default(),
+            activity: Activity::default(),
+            scrollbar_grab: None,
+            word_separators: DEFAULT_WORD_SEPARATORS.chars().collect(),
+            paste_confirm_chars: PASTE_CONFIRM_CHARS,
+            wheel_remainder: 0.0,
+            row_cache: Vec::new(),
+            row_cache_view: None,
+            row_cache_fonts: (0.0, 0.0),
+            highlight_cache: HighlightCache::default(),
+        }
+    }
+
+    /// Клавиатурный фокус (в split panes ввод получает только активная панель)
+    pub fn set_focus(&mut self, focus: bool) {
+        self.focus = focus;
+    }
+
+    pub fn has_focus(&self) -> bool {
+        self.focus
+    }
+
+    /// Собирать ли набранное пользователем (для рассылки в другие сессии).
+    pub fn set_input_capture(&mut self, enabled: bool) {
+        if enabled != self.captured_input.is_some() {
+            self.captured_input = if enabled { Some(Vec::new()) } else { None };
+        }
+    }
+
+    pub fn take_captured_input(&mut self) -> Vec<u8> {
+        self.captured_input.as_mut().map(std::mem::take).unwrap_or_default()
+    }
+
+    /// Отправка пользовательского ввода (в отличие от ответов эмулятора —
+    /// попадает в broadcast).
+    fn send_input(&mut self, ssh: &SshConnection, data: &[u8]) {
+        ssh.send(data);
+        if let Some(captured) = &mut self.captured_input {
+            captured.extend_from_slice(data);
         }
     }
 
+    pub fn set_highlight_rules(&mut self, rules: &[HighlightRule]) {
+        self.invalidate_row_cache();
+        self.highlighter = Highlighter::new(rules);
+        self.emulator.set_line_capture(self.highlighter.has_triggers());
+    }
+
+    pub fn set_key_sends(&mut self, sends: &[KeySend]) {
+        self.key_sends = KeySend::compile(sends);
+    }
+
+    /// Сочетание занято терминалом: своя последовательность сессии или действие из карты.
+    pub fn binds_key(&self, key: egui::Key, modifiers: egui::Modifiers) -> bool {
+        self.key_sends.iter().any(|(chord, _)| chord.matches(key, modifiers))
+            || keybindings::action_for(key, modifiers).is_some()
+    }
+
+    pub fn set_color_scheme(&mut self, scheme: &ColorScheme) {
+        self.invalidate_row_cache();
+        self.scheme = scheme.clone();
+    }
+
+    fn invalidate_row_cache(&mut self) {
+        self.row_cache.clear();
+        self.row_cache_view = None;
+        self.highlight_cache.clear();
+    }
+
+    pub fn set_word_separators(&mut self, separators: &str) {
+        self.word_separators = separators.chars().collect();
+    }
+
+    pub fn set_paste_confirm_chars(&mut self, chars: u32) {
+        self.paste_confirm_chars = chars as usize;
+    }
+
+    /// Сработавшие триггеры с прошлого вызова (уведомления/действия — на стороне app).
+    pub fn take_rule_hits(&mut self) -> Vec<RuleHit> {
+        std::mem::take(&mut self.rule_hits)
+    }
+
     /// Вычитываем все доступные данные из SSH и отдаём эмулятору
     pub fn process_ssh_output(&mut self, ssh: &SshConnection) {
+        // Пока app не забрал поток tmux, канал не читаем
+        if self.tmux_handoff.is_some() {
+            return;
+        }
         while let Ok(data) = ssh.output_rx.try_recv() {
-            self.emulator.process(&data);
+            self.activity.output = true;
+            if let Some(recorder) = &mut self.recorder {
+                if let Err(e) = recorder.record_output(&data) {
+                    self.recording_error = Some(format!("recording stopped: {}", e));
+                    self.recorder = None;
+                }
+            }
+            // DCS может разрезаться между кусками — склеиваем с отложенным хвостом
+            let data = if self.tmux_marker_tail.is_empty() {
+                data
+            } else {
+                let mut joined = std::mem::take(&mut self.tmux_marker_tail);
+                joined.extend_from_slice(&data);
+                joined
+            };
+            if let Some(pos) = find_bytes(&data, tmux::CONTROL_MODE_START) {
+                self.emulator.process(&data[..pos]);
+                self.tmux_handoff = Some(data[pos + tmux::CONTROL_MODE_START.len()..].to_vec());
+                break;
+            }
+            let keep = partial_suffix(&data, tmux::CONTROL_MODE_START);
+            let (ready, tail) = data.split_at(data.len() - keep);
+            self.emulator.process(ready);
+            self.tmux_marker_tail = tail.to_vec();
+        }
+
+        let responses = self.emulator.take_responses();
+        if !responses.is_empty() {
+            ssh.send(&responses);
+        }
+        if self.emulator.take_bell() {
+            self.activity.bell = true;
+        }
+
+        self.finished_commands.extend(self.emulator.take_finished_commands());
+
+        if self.highlighter.has_triggers() {
+            let lines = self.emulator.take_completed_lines();
+            if !lines.is_empty() {
+                let hits = self.highlighter.check_lines(&lines);
+                self.rule_hits.extend(hits);
+            }
+        }
+    }
+
+    pub fn take_activity(&mut self) -> Activity {
+        std::mem::take(&mut self.activity)
+    }
+
+    /// Команды, завершившиеся с прошлого вызова (длительность, код выхода).
+    pub fn take_finished_commands(&mut self) -> Vec<FinishedCommand> {
+        std::mem::take(&mut self.finished_commands)
+    }
+
+    // --- Запись сессии ---
+
+    pub fn start_recording(&mut self, path: &std::path::Path, title: &str) -> Result<(), String> {
+        let recorder = SessionRecorder::start(path, self.last_cols, self.last_rows, title)
+            .map_err(|e| format!("cannot record to '{}': {}", path.display(), e))?;
+        self.recorder = Some(recorder);
+        self.recording_error = None;
+        Ok(())
+    }
+
+    /// Останавливает запись и возвращает путь к сохранённому файлу.
+    pub fn stop_recording(&mut self) -> Result<Option<std::path::PathBuf>, String> {
+        match self.recorder.take() {
+            Some(recorder) => {
+                let path = recorder.path().to_path_buf();
+                recorder
+                    .finish()
+                    .map(Some)
+                    .map_err(|e| format!("cannot finish '{}': {}", path.display(), e))
+            }
+            None => Ok(None),
+        }
+    }
+
+    pub fn is_recording(&self) -> bool {
+        self.recorder.is_some()
+    }
+
+    /// Удалённая сторона запустила `tmux -CC`: остаток потока (уже без DCS).
+    pub fn take_tmux_handoff(&mut self) -> Option<Vec<u8>> {
+        self.tmux_handoff.take()
+    }
+
+    pub fn take_recording_error(&mut self) -> Option<String> {
+        self.recording_error.take()
+    }
+
+    // --- Экспорт буфера ---
+
+    /// Сохраняет scrollback + экран в файл: plain text или с ANSI-цветами.
+    pub fn save_buffer(&self, path: &std::path::Path, ansi: bool) -> Result<(), String> {
+        let mut lines: Vec<Vec<Cell>> = self.emulator.all_rows().cloned().collect();
+        // Пустой хвост экрана не нужен
+        while lines.last().is_some_and(|l| export::trim_line(l).is_empty()) {
+            lines.pop();
         }
+        let mut content = if ansi {
+            export::cells_to_ansi(&lines)
+        } else {
+            export::cells_to_text(&lines)
+        };
+        content.push('\n');
+        std::fs::write(path, content)
+            .map_err(|e| format!("cannot write '{}': {}", path.display(), e))
     }
 
     pub fn show(&mut self, ui: &mut egui::Ui, ssh: &SshConnection, interactive: bool) {
         self.process_ssh_output(ssh);
+        self.pump_paste(ui.ctx(), ssh);
 
         let cell_size = self.calculate_cell_size(ui);
         let available = ui.available_size();
+        let pixels_per_point = ui.ctx().pixels_per_point();
+        self.emulator
+            .set_cell_pixel_size(cell_size.x * pixels_per_point, cell_size.y * pixels_per_point);
 
         let new_cols = ((available.x / cell_size.x) as usize).max(1);
         let new_rows = ((available.y / cell_size.y) as usize).max(1);
 
         if new_cols != self.last_cols || new_rows != self.last_rows {
+            self.hint_mode = None;
             self.emulator.resize(new_cols, new_rows);
             ssh.resize(new_cols as u32, new_rows as u32);
             self.last_cols = new_cols;
             self.last_rows = new_rows;
+            if let Some(recorder) = &mut self.recorder {
+                if let Err(e) = recorder.record_resize(new_cols, new_rows) {
+                    self.recording_error = Some(format!("recording stopped: {}", e));
+                    self.recorder = None;
+                }
+            }
         }
 
         let desired_size = egui::vec2(
@@ -120,8 +563,11 @@ impl TerminalWidget {
             ui.allocate_painter(desired_size, egui::Sense::click_and_drag());
 
         let origin = response.rect.min;
-        let bg_color = egui::Color32::from_rgb(0x06, 0x06, 0x06);
-        let selection_bg = egui::Color32::from_rgb(0x00, 0x99, 0x28);
+        if self.copy_mode.is_some() {
+            self.sync_copy_selection();
+        }
+        let bg_color = self.scheme.background_color();
+        let selection_bg = self.scheme.selection_color();
 
         painter.rect_filled(response.rect, 0.0, bg_color);
 
@@ -148,62 +594,99 @@ impl TerminalWidget {
         }
 
         {
+            let fonts = ui.fonts(|f| (f.pixels_per_point(), f.font_atlas_fill_ratio()));
+            if fonts.0 != self.row_cache_fonts.0 || fonts.1 < self.row_cache_fonts.1 {
+                self.invalidate_row_cache();
+            }
+            self.row_cache_fonts = fonts;
+
             let visible = self.emulator.visible_rows();
+            let view_top = self.emulator.view_top_line();
+            // Ни вывода, ни прокрутки с прошлого кадра — ячейки можно не сравнивать
+            let view = (self.emulator.generation(), view_top);
+            let content_same = self.row_cache_view == Some(view);
+            self.row_cache_view = Some(view);
+            self.row_cache.resize_with(new_rows, || None);
+
+            let selection = self.selection.as_ref().filter(|s| !s.is_empty());
+            let selected_rows: Vec<(usize, usize, usize)> = selection
+                .map(|s| s.selection_ranges(new_cols))
+                .unwrap_or_default();
 
             for (row_idx, row) in visible.iter().enumerate() {
                 if row_idx >= new_rows {
                     break;
                 }
 
-                let mut job = egui::text::LayoutJob::default();
-
-                for (col_idx, cell) in row.iter().enumerate() {
-                    if col_idx >= new_cols {
-                        break;
-                    }
-
-                    let (fg, cell_bg) = resolve_colors(cell, bg_color);
-                    let text = if cell.c < ' ' || cell.c == '\0' {
-                        " ".to_string()
-                    } else {
-                        cell.c.to_string()
-                    };
-
-                    let is_selected = self
-                        .selection
-                        .as_ref()
-                        .map_or(false, |s| !s.is_empty() && s.contains(row_idx, col_idx));
-
-                    let mut format = egui::TextFormat {
-                        font_id: egui::FontId::monospace(self.font_size),
-                        color: if is_selected { bg_color } else { fg },
-                        ..Default::default()
-                    };
-
-                    if !is_selected && cell_bg != bg_color {
-                        format.background = cell_bg;
+                let selected = selected_rows
+                    .iter()
+                    .find(|(r, _, _)| *r == row_idx)
+                    .map(|&(_, start, end)| (start, end));
+                let marks: Vec<(usize, usize, egui::Color32)> = match &self.search {
+                    Some(search) => {
+                        let line = view_top + row_idx as i64;
+                        search
+                            .matches
+                            .iter()
+                            .filter(|m| m.0 == line)
+                            .map(|m| (m.1, m.2, crate::theme::AMBER))
+                            .collect()
                     }
+                    None => Vec::new(),
+                };
 
-                    if cell.attr.underline {
-                        format.underline = egui::Stroke::new(1.0, fg);
+                // Подсветка зависит и от соседних строк (перенос), поэтому сравниваем и её
+                let highlights = self.highlight_cache.row(
+                    &self.emulator,
+                    &self.highlighter,
+                    view_top + row_idx as i64,
+                );
+                let cached = self.row_cache[row_idx].take();
+                let cells_same = cached
+                    .as_ref()
+                    .is_some_and(|c| content_same || c.cells.as_slice() == row.as_slice());
+                let entry = match cached {
+                    Some(c)
+                        if cells_same
+                            && c.selected == selected
+                            && c.marks == marks
+                            && c.highlights == highlights =>
+                    {
+                        c
                     }
-                    if cell.attr.italic {
-                        format.italics = true;
+                    _ => {
+                        let mut spans = highlights.clone();
+                        spans.extend_from_slice(&marks);
+                        let job = row_layout_job(
+                            row,
+                            new_cols,
+                            self.font_size,
+                            &self.scheme,
+                            &spans,
+                            |col_idx| selected.is_some_and(|(s, e)| col_idx >= s && col_idx <= e),
+                        );
+                        RowCache {
+                            cells: row.to_vec(),
+                            highlights,
+                            marks,
+                            selected,
+                            galley: ui.fonts(|f| f.layout_job(job)),
+                        }
                     }
+                };
 
-                    job.append(&text, 0.0, format);
-                }
-
-                let galley = ui.fonts(|f| f.layout_job(job));
                 painter.galley(
                     egui::pos2(origin.x, origin.y + row_idx as f32 * cell_size.y),
-                    galley,
+                    entry.galley.clone(),
                     egui::Color32::TRANSPARENT,
                 );
+                self.row_cache[row_idx] = Some(entry);
             }
-
         }
 
+        self.paint_images(ui.ctx(), &painter.with_clip_rect(response.rect), origin, cell_size);
+        self.paint_command_marks(&painter, origin, cell_size, new_rows);
+
         // Курсор — вычисляем X-позицию через LayoutJob (тот же подход, что и рендер),
         // чтобы позиция курсора точно совпадала с позицией символов.
         {
@@ -216,13 +699,8 @@ impl TerminalWidget {
                 let cursor_x = if cursor_row < grid.len() && cursor_col > 0 {
                     let row = &grid[cursor_row];
                     let mut job = egui::text::LayoutJob::default();
-                    for col in 0..cursor_col.min(row.len()).min(new_cols) {
-                        let c = row[col].c;
-                        let text = if c < ' ' || c == '\0' {
-                            " ".to_string()
-                        } else {
-                            c.to_string()
-                        };
+                    for cell in row.iter().take(cursor_col.min(new_cols)) {
+                        let text = cell_display_text(cell);
                         job.append(
                             &text,
                             0.0,
@@ -253,7 +731,7 @@ impl TerminalWidget {
                     painter.rect_filled(
                         cursor_rect,
                         0.0,
-                        egui::Color32::from_rgba_premultiplied(0x00, 0xff, 0x41, 0xcc),
+                        self.scheme.cursor_color().gamma_multiply(0.8),
                     );
                 }
             }
@@ -263,13 +741,64 @@ impl TerminalWidget {
             self.handle_mouse(&response, origin, cell_size, new_rows, new_cols);
         }
 
-        if interactive && response.clicked() {
+        // Второй клик двойного — не сбрасываем только что выделенное слово
+        if interactive && response.clicked() && !response.double_clicked() {
             self.selection = None;
             self.focus = true;
+            // Клик по метке команды — выделить и скопировать её вывод
+            if let Some(pos) = response.interact_pointer_pos() {
+                if pos.x < origin.x + MARK_CLICK_WIDTH {
+                    let row = ((pos.y - origin.y) / cell_size.y).max(0.0) as usize;
+                    self.select_command_output(ui.ctx(), row, new_cols);
+                }
+            }
+        }
+
+        // Средняя кнопка — вставка PRIMARY selection
+        if interactive && response.clicked_by(egui::PointerButton::Middle) {
+            self.focus = true;
+            if let Some(text) = primary::get() {
+                self.paste(&text, ssh);
+            }
+        }
+
+        if self.hint_mode.is_some() {
+            self.paint_hints(&painter, origin, cell_size, response.rect);
+        }
+        if self.search.is_some() {
+            self.paint_search(&painter, origin, cell_size, response.rect);
+        }
+        if self.copy_mode.is_some() {
+            self.paint_copy_mode(&painter, origin, cell_size, response.rect);
+        }
+        if self.paste_queue.is_some() {
+            self.paint_paste_progress(&painter, response.rect);
+        }
+
+        // Подсказка над меткой команды: код выхода и длительность
+        if let Some(pos) = response.hover_pos() {
+            if pos.x < origin.x + MARK_CLICK_WIDTH {
+                let row = ((pos.y - origin.y) / cell_size.y).max(0.0) as usize;
+                if let Some(text) = self.command_mark_tooltip(row) {
+                    response.clone().on_hover_text_at_pointer(text);
+                }
+            }
+        }
+
+        if self.focus && interactive && self.pending_paste.is_none() {
+            if self.hint_mode.is_some() {
+                self.handle_hint_input(ui, ssh);
+            } else if self.search.is_some() {
+                self.handle_search_input(ui);
+            } else if self.copy_mode.is_some() {
+                self.handle_copy_mode_input(ui);
+            } else {
+                self.handle_input(ui, ssh);
+            }
         }
 
-        if self.focus && interactive {
-            self.handle_input(ui, ssh);
+        if self.pending_paste.is_some() {
+            self.render_paste_dialog(ui.ctx(), ssh);
         }
 
         // Контекстное меню (ПКМ)
@@ -280,7 +809,10 @@ impl TerminalWidget {
                 .map_or(false, |s| !s.is_empty());
 
             if ui
-                .add_enabled(has_sel, egui::Button::new("[copy]  C-S-c"))
+                .add_enabled(
+                    has_sel,
+                    egui::Button::new(format!("[copy] {}", keybindings::keys_for(Action::Copy))),
+                )
                 .clicked()
             {
                 let text = self.get_selected_text();
@@ -290,18 +822,104 @@ impl TerminalWidget {
                 self.selection = None;
                 ui.close_menu();
             }
-            if ui.button("[paste] C-S-v").clicked() {
+            if ui
+                .add_enabled(has_sel, egui::Button::new("[copy html]"))
+                .on_hover_text("copy with colors as rich text / HTML")
+                .clicked()
+            {
+                let cells = self.get_selected_cells();
+                let html = export::cells_to_html(&cells, &self.scheme);
+                let plain = export::cells_to_text(&cells);
+                match arboard::Clipboard::new() {
+                    Ok(mut clipboard) => {
+                        if clipboard.set_html(&html, Some(&plain)).is_err() {
+                            ui.ctx().copy_text(html);
+                        }
+                    }
+                    Err(_) => ui.ctx().copy_text(html),
+                }
+                self.selection = None;
+                ui.close_menu();
+            }
+            if ui
+                .add_enabled(has_sel, egui::Button::new("[copy ansi]"))
+                .on_hover_text("copy with ANSI color escapes")
+                .clicked()
+            {
+                let ansi = export::cells_to_ansi(&self.get_selected_cells());
+                if !ansi.is_empty() {
+                    ui.ctx().copy_text(ansi);
+                }
+                self.selection = None;
+                ui.close_menu();
+            }
+            if ui
+                .button(format!("[hints] {}", keybindings::keys_for(Action::Hints)))
+                .clicked()
+            {
+                self.enter_hint_mode();
+                ui.close_menu();
+            }
+            if ui
+                .button(format!("[copy mode] {}", keybindings::keys_for(Action::CopyMode)))
+                .clicked()
+            {
+                self.enter_copy_mode();
+                ui.close_menu();
+            }
+            if ui
+                .button(format!("[search] {}", keybindings::keys_for(Action::Search)))
+                .clicked()
+            {
+                self.open_search();
+                ui.close_menu();
+            }
+            ui.separator();
+            if ui
+                .button(format!("[paste] {}", keybindings::keys_for(Action::Paste)))
+                .clicked()
+            {
                 if let Ok(mut clipboard) = arboard::Clipboard::new() {
                     if let Ok(text) = clipboard.get_text() {
-                        ssh.send(text.as_bytes());
+                        self.paste(&text, ssh);
                     }
                 }
                 ui.close_menu();
             }
         });
 
-        // Скролл колёсиком (пропорционально)
-        if response.hovered() {
+        // На альтернативном экране колёсико — стрелки (less, vim, htop):
+        // локальный scrollback там бесполезен
+        let alt_scroll_lines = keybindings::alt_scroll_lines();
+        if response.hovered() && alt_scroll_lines > 0 && self.emulator.alternate_scroll() {
+            let lines: f32 = ui.input(|i| {
+                i.events
+                    .iter()
+                    .filter_map(|e| match e {
+                        egui::Event::MouseWheel { unit, delta, .. } => Some(match unit {
+                            egui::MouseWheelUnit::Line => delta.y * alt_scroll_lines as f32,
+                            egui::MouseWheelUnit::Point => delta.y / cell_size.y,
+                            egui::MouseWheelUnit::Page => delta.y * self.last_rows as f32,
+                        }),
+                        _ => None,
+                    })
+                    .sum()
+            });
+            self.wheel_remainder += lines;
+            let whole = self.wheel_remainder.trunc();
+            self.wheel_remainder -= whole;
+            if whole != 0.0 && interactive {
+                let key = match (whole > 0.0, self.emulator.app_cursor_keys()) {
+                    (true, true) => "\x1bOA",
+                    (true, false) => "\x1b[A",
+                    (false, true) => "\x1bOB",
+                    (false, false) => "\x1b[B",
+                };
+                let bytes = key.repeat(whole.abs() as usize);
+                self.send_input(ssh, bytes.as_bytes());
+            }
+        } else if response.hovered() {
+            // Скролл колёсиком (пропорционально)
             let scroll_delta = ui.input(|i| i.smooth_scroll_delta.y);
             if scroll_delta.abs() > 1.0 {
                 let lines = (scroll_delta.abs() / cell_size.y).ceil().max(1.0) as usize;
@@ -314,37 +932,77 @@ impl TerminalWidget {
             }
         }
 
-        // Скроллбар
+        // Скроллбар: ползунок тянется мышью, клик по дорожке — переход к этому месту.
+        // Свой interact поверх response, чтобы drag не начинал выделение.
         let scrollback_len = self.emulator.scrollback_len();
         if scrollback_len > 0 {
             let scrollbar_width = 6.0;
             let scrollbar_x = response.rect.right() - scrollbar_width - 2.0;
             let scrollbar_height = response.rect.height();
-
-            painter.rect_filled(
-                egui::Rect::from_min_size(
-                    egui::pos2(scrollbar_x, origin.y),
-                    egui::vec2(scrollbar_width, scrollbar_height),
-                ),
-                0.0,
-                egui::Color32::from_rgba_premultiplied(0, 30, 0, 60),
+            let track_rect = egui::Rect::from_min_size(
+                egui::pos2(scrollbar_x, origin.y),
+                egui::vec2(scrollbar_width, scrollbar_height),
+            );
+            let track_response = ui.interact(
+                track_rect.expand2(egui::vec2(2.0, 0.0)),
+                response.id.with("scrollbar"),
+                egui::Sense::click_and_drag(),
             );
 
             let total_lines = scrollback_len + new_rows;
             let visible_fraction = new_rows as f32 / total_lines as f32;
             let thumb_height = (scrollbar_height * visible_fraction).max(20.0);
-            let max_offset = scrollback_len.max(1) as f32;
-            let scroll_fraction = self.emulator.scroll_offset() as f32 / max_offset;
-            let thumb_y =
-                origin.y + (scrollbar_height - thumb_height) * (1.0 - scroll_fraction);
+            let travel = (scrollbar_height - thumb_height).max(1.0);
+            let max_offset = scrollback_len as f32;
+            // Верх ползунка → смещение прокрутки (внизу — 0, наверху — весь scrollback)
+            let offset_for = |thumb_y: f32| {
+                let fraction = ((thumb_y - origin.y) / travel).clamp(0.0, 1.0);
+                ((1.0 - fraction) * max_offset).round() as usize
+            };
+
+            let thumb_y_now =
+                |offset: usize| origin.y + travel * (1.0 - offset as f32 / max_offset);
+            let thumb_y = thumb_y_now(self.emulator.scroll_offset());
+
+            if let Some(pointer) = track_response.interact_pointer_pos() {
+                if track_response.drag_started_by(egui::PointerButton::Primary)
+                    || track_response.clicked_by(egui::PointerButton::Primary)
+                {
+                    let on_thumb = pointer.y >= thumb_y && pointer.y <= thumb_y + thumb_height;
+                    // Мимо ползунка — центрируем его под указателем
+                    self.scrollbar_grab = Some(if on_thumb {
+                        pointer.y - thumb_y
+                    } else {
+                        thumb_height / 2.0
+                    });
+                }
+                if let Some(grab) = self.scrollbar_grab {
+                    self.emulator.set_scroll_offset(offset_for(pointer.y - grab));
+                    self.selection = None;
+                }
+            }
+            if track_response.drag_stopped() || track_response.clicked() {
+                self.scrollbar_grab = None;
+            }
+
+            painter.rect_filled(
+                track_rect,
+                0.0,
+                egui::Color32::from_rgba_premultiplied(0, 30, 0, 60),
+            );
 
+            let thumb_color = if self.scrollbar_grab.is_some() || track_response.hovered() {
+                crate::theme::GREEN
+            } else {
+                egui::Color32::from_rgba_premultiplied(0x00, 0xaa, 0x33, 0xbb)
+            };
             painter.rect_filled(
                 egui::Rect::from_min_size(
-                    egui::pos2(scrollbar_x, thumb_y),
+                    egui::pos2(scrollbar_x, thumb_y_now(self.emulator.scroll_offset())),
                     egui::vec2(scrollbar_width, thumb_height),
                 ),
                 0.0,
-                egui::Color32::from_rgba_premultiplied(0x00, 0xaa, 0x33, 0xbb),
+                thumb_color,
             );
         }
 
@@ -378,22 +1036,52 @@ impl TerminalWidget {
         }
     }
 
+    // --- Графика ---
+
+    fn paint_images(
+        &mut self,
+        ctx: &egui::Context,
+        painter: &egui::Painter,
+        origin: egui::Pos2,
+        cell_size: egui::Vec2,
+    ) {
+        let images = self.emulator.visible_images();
+        // Текстуры ушедших картинок освобождаем
+        self.image_textures
+            .retain(|id, _| images.iter().any(|(_, img)| img.id == *id));
+
+        let pixels_per_point = ctx.pixels_per_point();
+        for (row, img) in images {
+            let texture = self.image_textures.entry(img.id).or_insert_with(|| {
+                let color_image =
+                    egui::ColorImage::from_rgba_unmultiplied([img.width, img.height], &img.rgba);
+                ctx.load_texture(
+                    format!("term-image-{}", img.id),
+                    color_image,
+                    egui::TextureOptions::LINEAR,
+                )
+            });
+            let rect = egui::Rect::from_min_size(
+                egui::pos2(
+                    origin.x + img.col as f32 * cell_size.x,
+                    origin.y + row as f32 * cell_size.y,
+                ),
+                egui::vec2(img.display_size.0, img.display_size.1) / pixels_per_point,
+            );
+            painter.image(
+                texture.id(),
+                rect,
+                egui::Rect::from_min_max(egui::pos2(0.0, 0.0), egui::pos2(1.0, 1.0)),
+                egui::Color32::WHITE,
+            );
+        }
+    }
+
     // --- Расчёт размера ячейки ---
     // Для определения кол-ва колонок/строк и мышиных координат.
     // Точная X-позиция курсора вычисляется отдельно через LayoutJob.
-    fn calculate_cell_size(&self, ui: &egui::Ui) -> egui::Vec2 {
-        let font_id = egui::FontId::monospace(self.font_size);
-        // Усредняем по 10 символам для стабильного результата
-        let g = ui.fonts(|f| {
-            f.layout_no_wrap(
-                "MMMMMMMMMM".to_string(),
-                font_id.clone(),
-                egui::Color32::WHITE,
-            )
-        });
-        let char_width = g.rect.width() / 10.0;
-        let line_height = g.rect.height();
-        egui::vec2(char_width.max(1.0), line_height.max(1.0))
+    pub(crate) fn calculate_cell_size(&self, ui: &egui::Ui) -> egui::Vec2 {
+        cell_size_for(ui, self.font_size)
     }
 
     fn handle_mouse(
@@ -432,14 +1120,65 @@ impl TerminalWidget {
 
         if self.selecting && response.drag_stopped() {
             self.selecting = false;
+            // Copy-on-select в PRIMARY (X11/Wayland)
+            let text = self.get_selected_text();
+            if !text.is_empty() {
+                primary::set(&text);
+            }
+        }
+
+        // Двойной клик — выделение слова
+        if response.double_clicked_by(egui::PointerButton::Primary) {
+            if let Some(pos) = response.interact_pointer_pos() {
+                let (row, col) = pos_to_cell(pos, origin, cell_size, max_rows, max_cols);
+                if let Some((start, end)) = self.word_at(row, col) {
+                    self.selection = Some(Selection {
+                        start_row: row,
+                        start_col: start,
+                        end_row: row,
+                        end_col: end,
+                    });
+                    let text = self.get_selected_text();
+                    if !text.is_empty() {
+                        primary::set(&text);
+                    }
+                }
+            }
+        }
+    }
+
+    fn is_word_char(&self, c: char) -> bool {
+        c != '\0' && !c.is_whitespace() && !self.word_separators.contains(&c)
+    }
+
+    /// Границы слова (включительно) вокруг ячейки видимой строки.
+    fn word_at(&self, row: usize, col: usize) -> Option<(usize, usize)> {
+        let visible = self.emulator.visible_rows();
+        let line = visible.get(row)?;
+        if !self.is_word_char(line.get(col)?.c) {
+            return None;
+        }
+        let mut start = col;
+        while start > 0 && self.is_word_char(line[start - 1].c) {
+            start -= 1;
         }
+        let mut end = col;
+        while end + 1 < line.len() && self.is_word_char(line[end + 1].c) {
+            end += 1;
+        }
+        Some((start, end))
     }
 
     // --- Получение выделенного текста ---
     fn get_selected_text(&self) -> String {
+        export::cells_to_text(&self.get_selected_cells())
+    }
+
+    /// Выделенные ячейки построчно (с атрибутами — для copy as HTML/ANSI).
+    fn get_selected_cells(&self) -> Vec<Vec<Cell>> {
         let sel = match &self.selection {
             Some(s) if !s.is_empty() => s,
-            _ => return String::new(),
+            _ => return Vec::new(),
         };
 
         let ((sr, sc), (er, ec)) = sel.normalized();
@@ -458,17 +1197,999 @@ impl TerminalWidget {
                 line.len().saturating_sub(1)
             };
 
-            let mut line_text = String::new();
-            for col in col_start..=col_end {
-                if col < line.len() {
-                    let c = line[col].c;
-                    line_text.push(if c == '\0' { ' ' } else { c });
-                }
+            if col_start < line.len() {
+                lines.push(line[col_start..=col_end].to_vec());
+            } else {
+                lines.push(Vec::new());
+            }
+        }
+
+        lines
+    }
+
+    // --- Hint mode ---
+
+    fn enter_hint_mode(&mut self) {
+        let lines: Vec<String> = self
+            .emulator
+            .visible_rows()
+            .iter()
+            .map(|row| row.iter().map(|c| if c.c == '\0' { ' ' } else { c.c }).collect())
+            .collect();
+        let hints = hints::find_hints(&lines);
+        self.selection = None;
+        self.copy_mode = None;
+        self.hint_mode = if hints.is_empty() {
+            None
+        } else {
+            Some(HintMode {
+                hints,
+                typed: String::new(),
+                current: None,
+            })
+        };
+    }
+
+    fn paint_hints(
+        &self,
+        painter: &egui::Painter,
+        origin: egui::Pos2,
+        cell_size: egui::Vec2,
+        area: egui::Rect,
+    ) {
+        let mode = match &self.hint_mode {
+            Some(m) => m,
+            None => return,
+        };
+
+        // Затемняем экран, чтобы токены выделялись
+        painter.rect_filled(area, 0.0, egui::Color32::from_black_alpha(120));
+
+        let label_font = egui::FontId::monospace(self.font_size);
+        for (idx, hint) in mode.matching() {
+            let rect = egui::Rect::from_min_max(
+                egui::pos2(
+                    origin.x + hint.start_col as f32 * cell_size.x,
+                    origin.y + hint.row as f32 * cell_size.y,
+                ),
+                egui::pos2(
+                    origin.x + hint.end_col as f32 * cell_size.x,
+                    origin.y + (hint.row + 1) as f32 * cell_size.y,
+                ),
+            );
+            let is_current = mode.current == Some(idx);
+            let fill = if is_current {
+                egui::Color32::from_rgb(0x00, 0x99, 0x28)
+            } else {
+                crate::theme::BG_SELECTION
+            };
+            painter.rect_filled(rect, 0.0, fill);
+            painter.text(
+                rect.left_center(),
+                egui::Align2::LEFT_CENTER,
+                &hint.text,
+                label_font.clone(),
+                crate::theme::GREEN_BRIGHT,
+            );
+
+            // Метка поверх начала токена; набранная часть приглушена
+            let remaining = &hint.label[mode.typed.len()..];
+            let label_rect = egui::Rect::from_min_size(
+                rect.min,
+                egui::vec2(hint.label.len() as f32 * cell_size.x, cell_size.y),
+            );
+            painter.rect_filled(label_rect, 0.0, crate::theme::AMBER);
+            painter.text(
+                label_rect.left_center(),
+                egui::Align2::LEFT_CENTER,
+                format!("{}{}", " ".repeat(mode.typed.len()), remaining),
+                label_font.clone(),
+                egui::Color32::BLACK,
+            );
+        }
+
+        let text = "-- HINT: type label to copy (Shift = paste), Tab cycle, Esc exit --";
+        let indicator_rect = egui::Rect::from_min_size(
+            egui::pos2(area.center().x - 260.0, area.bottom() - 26.0),
+            egui::vec2(520.0, 22.0),
+        );
+        painter.rect_filled(
+            indicator_rect,
+            0.0,
+            egui::Color32::from_rgba_premultiplied(0, 20, 0, 220),
+        );
+        painter.rect_stroke(
+            indicator_rect,
+            0.0,
+            egui::Stroke::new(1.0, crate::theme::AMBER),
+        );
+        painter.text(
+            indicator_rect.center(),
+            egui::Align2::CENTER_CENTER,
+            text,
+            egui::FontId::monospace(11.0),
+            crate::theme::AMBER,
+        );
+    }
+
+    fn handle_hint_input(&mut self, ui: &egui::Ui, ssh: &SshConnection) {
+        let events = ui.input(|i| i.events.clone());
+        // (текст, вставить в терминал)
+        let mut chosen: Option<(String, bool)> = None;
+        let mut exit = false;
+
+        if let Some(mode) = &mut self.hint_mode {
+            for event in &events {
+                match event {
+                    egui::Event::Key {
+                        key,
+                        pressed: true,
+                        modifiers,
+                        ..
+                    } => match key {
+                        egui::Key::Escape => exit = true,
+                        egui::Key::Backspace => {
+                            mode.typed.pop();
+                        }
+                        egui::Key::Tab => {
+                            let matching: Vec<usize> = mode.matching().map(|(i, _)| i).collect();
+                            if matching.is_empty() {
+                                continue;
+                            }
+                            let pos = mode
+                                .current
+                                .and_then(|c| matching.iter().position(|&i| i == c));
+                            let next = match (pos, modifiers.shift) {
+                                (None, false) => matching.len() - 1,
+                                (None, true) => 0,
+                                (Some(p), false) => (p + matching.len() - 1) % matching.len(),
+                                (Some(p), true) => (p + 1) % matching.len(),
+                            };
+                            mode.current = Some(matching[next]);
+                        }
+                        egui::Key::Enter => {
+                            if let Some(hint) = mode.current.and_then(|c| mode.hints.get(c)) {
+                                chosen = Some((hint.text.clone(), modifiers.shift));
+                            }
+                        }
+                        _ => {}
+                    },
+                    egui::Event::Text(text) => {
+                        for ch in text.chars().filter(|c| c.is_ascii_alphabetic()) {
+                            let paste = ch.is_ascii_uppercase();
+                            mode.typed.push(ch.to_ascii_lowercase());
+                            if mode.matching().next().is_none() {
+                                mode.typed.pop();
+                                continue;
+                            }
+                            if let Some(hint) = mode.hints.iter().find(|h| h.label == mode.typed) {
+                                chosen = Some((hint.text.clone(), paste));
+                                break;
+                            }
+                        }
+                    }
+                    _ => {}
+                }
+                if chosen.is_some() {
+                    break;
+                }
+            }
+        }
+
+        if let Some((text, paste)) = chosen {
+            ui.ctx().copy_text(text.clone());
+            if paste {
+                self.emulator.reset_scroll();
+                self.send_input(ssh, text.as_bytes());
+            }
+            exit = true;
+        }
+        if exit {
+            self.hint_mode = None;
+        }
+    }
+
+    // --- Copy mode ---
+
+    fn enter_copy_mode(&mut self) {
+        self.hint_mode = None;
+        self.search = None;
+        // С курсора терминала; если вид прокручен — с нижней строки вида
+        let (line, col) = if self.emulator.is_scrolled() {
+            (self.emulator.view_top_line() + self.last_rows as i64 - 1, 0)
+        } else {
+            let (_, cursor_col, _) = self.emulator.cursor();
+            (self.emulator.cursor_line(), cursor_col)
+        };
+        self.selection = None;
+        self.copy_mode = Some(CopyMode {
+            line,
+            col: col.min(self.last_cols.saturating_sub(1)),
+            anchor: None,
+            linewise: false,
+        });
+    }
+
+    fn exit_copy_mode(&mut self) {
+        if self.copy_mode.take().is_some() {
+            self.selection = None;
+        }
+    }
+
+    /// Выделение copy mode (абсолютные строки) → self.selection в координатах вида,
+    /// обрезанное по видимой области. Так его рисует обычный рендер.
+    fn sync_copy_selection(&mut self) {
+        let range = self.copy_mode.as_ref().and_then(|m| m.range(self.last_cols));
+        let ((start_line, start_col), (end_line, end_col)) = match range {
+            Some(r) => r,
+            None => {
+                self.selection = None;
+                return;
+            }
+        };
+        let top = self.emulator.view_top_line();
+        let bottom = top + self.last_rows as i64 - 1;
+        if end_line < top || start_line > bottom {
+            self.selection = None;
+            return;
+        }
+        let (start_row, start_col) = if start_line < top {
+            (0, 0)
+        } else {
+            ((start_line - top) as usize, start_col)
+        };
+        let (end_row, end_col) = if end_line > bottom {
+            (self.last_rows - 1, self.last_cols.saturating_sub(1))
+        } else {
+            ((end_line - top) as usize, end_col)
+        };
+        self.selection = Some(Selection {
+            start_row,
+            start_col,
+            end_row,
+            end_col,
+        });
+    }
+
+    /// Текст выделения copy mode (по всему буферу, не только видимой части).
+    fn copy_mode_text(&self) -> String {
+        let range = self.copy_mode.as_ref().and_then(|m| m.range(self.last_cols));
+        let ((start_line, start_col), (end_line, end_col)) = match range {
+            Some(r) => r,
+            None => return String::new(),
+        };
+        let lines: Vec<Vec<Cell>> = (start_line..=end_line)
+            .map(|line| {
+                let row = match self.emulator.row_at(line) {
+                    Some(r) => r,
+                    None => return Vec::new(),
+                };
+                let from = if line == start_line { start_col } else { 0 };
+                let to = if line == end_line { end_col + 1 } else { row.len() };
+                row.get(from..to.min(row.len())).unwrap_or_default().to_vec()
+            })
+            .collect();
+        export::cells_to_text(&lines)
+    }
+
+    fn copy_mode_is_word_char(&self, line: i64, col: usize) -> bool {
+        self.emulator
+            .row_at(line)
+            .and_then(|row| row.get(col))
+            .is_some_and(|cell| self.is_word_char(cell.c))
+    }
+
+    /// w / b: к началу следующего / предыдущего слова в пределах строки.
+    fn copy_mode_word_motion(&mut self, forward: bool) {
+        let (line, mut col) = match &self.copy_mode {
+            Some(m) => (m.line, m.col),
+            None => return,
+        };
+        let max_col = self.last_cols.saturating_sub(1);
+        if forward {
+            while col < max_col && self.copy_mode_is_word_char(line, col) {
+                col += 1;
+            }
+            while col < max_col && !self.copy_mode_is_word_char(line, col) {
+                col += 1;
+            }
+        } else {
+            col = col.saturating_sub(1);
+            while col > 0 && !self.copy_mode_is_word_char(line, col) {
+                col -= 1;
+            }
+            while col > 0 && self.copy_mode_is_word_char(line, col - 1) {
+                col -= 1;
+            }
+        }
+        if let Some(mode) = &mut self.copy_mode {
+            mode.col = col;
+        }
+    }
+
+    /// Последняя непустая колонка строки ($).
+    fn copy_mode_line_end(&self, line: i64) -> usize {
+        self.emulator
+            .row_at(line)
+            .and_then(|row| row.iter().rposition(|c| c.c != ' ' && c.c != '\0'))
+            .unwrap_or(0)
+    }
+
+    /// Курсор copy mode в пределах буфера; вид прокручивается за ним.
+    fn copy_mode_clamp_and_follow(&mut self) {
+        let first = self.emulator.first_line();
+        let last = self.emulator.last_line();
+        let max_col = self.last_cols.saturating_sub(1);
+        let line = match &mut self.copy_mode {
+            Some(mode) => {
+                mode.line = mode.line.clamp(first, last);
+                mode.col = mode.col.min(max_col);
+                mode.line
+            }
+            None => return,
+        };
+        let top = self.emulator.view_top_line();
+        let rows = self.last_rows as i64;
+        if line < top {
+            self.emulator.scroll_to_line(line);
+        } else if line >= top + rows {
+            self.emulator.scroll_to_line(line - rows + 1);
+        }
+    }
+
+    /// hjkl / стрелки — курсор, w b 0 $ g G — переходы, PageUp/PageDown — страница,
+    /// v / V — выделение, y / Enter — скопировать и выйти, Esc / q — выйти.
+    fn handle_copy_mode_input(&mut self, ui: &egui::Ui) {
+        let events = ui.input(|i| i.events.clone());
+        let page = self.last_rows.max(1) as i64;
+
+        for event in &events {
+            let mut yank = false;
+            let mut exit = false;
+            match event {
+                egui::Event::Key {
+                    key,
+                    pressed: true,
+                    modifiers,
+                    ..
+                } => {
+                    if keybindings::action_for(*key, *modifiers) == Some(Action::CopyMode) {
+                        exit = true;
+                    } else if let Some(mode) = &mut self.copy_mode {
+                        match key {
+                            egui::Key::Escape => exit = true,
+                            egui::Key::Enter => yank = true,
+                            egui::Key::ArrowUp => mode.line -= 1,
+                            egui::Key::ArrowDown => mode.line += 1,
+                            egui::Key::ArrowLeft => mode.col = mode.col.saturating_sub(1),
+                            egui::Key::ArrowRight => mode.col += 1,
+                            egui::Key::PageUp => mode.line -= page,
+                            egui::Key::PageDown => mode.line += page,
+                            egui::Key::Home => mode.col = 0,
+                            _ => {}
+                        }
+                        if *key == egui::Key::End {
+                            let line = mode.line;
+                            let end = self.copy_mode_line_end(line);
+                            if let Some(mode) = &mut self.copy_mode {
+                                mode.col = end;
+                            }
+                        }
+                    }
+                }
+                egui::Event::Text(text) => {
+                    for ch in text.chars() {
+                        match ch {
+                            'w' => self.copy_mode_word_motion(true),
+                            'b' => self.copy_mode_word_motion(false),
+                            '$' => {
+                                if let Some(line) = self.copy_mode.as_ref().map(|m| m.line) {
+                                    let end = self.copy_mode_line_end(line);
+                                    if let Some(mode) = &mut self.copy_mode {
+                                        mode.col = end;
+                                    }
+                                }
+                            }
+                            'g' => {
+                                let first = self.emulator.first_line();
+                                if let Some(mode) = &mut self.copy_mode {
+                                    mode.line = first;
+                                    mode.col = 0;
+                                }
+                            }
+                            'G' => {
+                                let last = self.emulator.last_line();
+                                if let Some(mode) = &mut self.copy_mode {
+                                    mode.line = last;
+                                    mode.col = 0;
+                                }
+                            }
+                            'y' => yank = true,
+                            'q' => exit = true,
+                            _ => {}
+                        }
+                        let Some(mode) = &mut self.copy_mode else {
+                            break;
+                        };
+                        match ch {
+                            'h' => mode.col = mode.col.saturating_sub(1),
+                            'j' => mode.line += 1,
+                            'k' => mode.line -= 1,
+                            'l' => mode.col += 1,
+                            '0' => mode.col = 0,
+                            'v' | 'V' => {
+                                let linewise = ch == 'V';
+                                // Повторное v/V того же вида снимает выделение
+                                if mode.anchor.is_some() && mode.linewise == linewise {
+                                    mode.anchor = None;
+                                } else {
+                                    mode.anchor.get_or_insert((mode.line, mode.col));
+                                    mode.linewise = linewise;
+                                }
+                            }
+                            _ => {}
+                        }
+                        if yank || exit {
+                            break;
+                        }
+                    }
+                }
+                _ => {}
+            }
+
+            if yank {
+                let text = self.copy_mode_text();
+                if !text.is_empty() {
+                    ui.ctx().copy_text(text.clone());
+                    primary::set(&text);
+                }
+                exit = true;
+            }
+            if exit {
+                self.exit_copy_mode();
+                return;
+            }
+            self.copy_mode_clamp_and_follow();
+        }
+    }
+
+    fn paint_copy_mode(
+        &self,
+        painter: &egui::Painter,
+        origin: egui::Pos2,
+        cell_size: egui::Vec2,
+        area: egui::Rect,
+    ) {
+        let mode = match &self.copy_mode {
+            Some(m) => m,
+            None => return,
+        };
+
+        let row = mode.line - self.emulator.view_top_line();
+        if (0..self.last_rows as i64).contains(&row) {
+            let rect = egui::Rect::from_min_size(
+                egui::pos2(
+                    origin.x + mode.col as f32 * cell_size.x,
+                    origin.y + row as f32 * cell_size.y,
+                ),
+                cell_size,
+            );
+            painter.rect_filled(rect, 0.0, crate::theme::AMBER.gamma_multiply(0.5));
+            painter.rect_stroke(rect, 0.0, egui::Stroke::new(1.0, crate::theme::AMBER));
+        }
+
+        let state = match (mode.anchor.is_some(), mode.linewise) {
+            (false, _) => "",
+            (true, false) => " [visual]",
+            (true, true) => " [visual line]",
+        };
+        let text = format!(
+            "-- COPY{}: hjkl move, v/V select, y copy, Esc exit --  {}",
+            state,
+            mode.line - self.emulator.first_line() + 1
+        );
+        let indicator_rect = egui::Rect::from_min_size(
+            egui::pos2(area.center().x - 260.0, area.bottom() - 26.0),
+            egui::vec2(520.0, 22.0),
+        );
+        painter.rect_filled(
+            indicator_rect,
+            0.0,
+            egui::Color32::from_rgba_premultiplied(0, 20, 0, 220),
+        );
+        painter.rect_stroke(
+            indicator_rect,
+            0.0,
+            egui::Stroke::new(1.0, crate::theme::AMBER),
+        );
+        painter.text(
+            indicator_rect.center(),
+            egui::Align2::CENTER_CENTER,
+            text,
+            egui::FontId::monospace(11.0),
+            crate::theme::AMBER,
+        );
+    }
+
+    // --- Масштаб ---
+
+    fn set_font_size(&mut self, size: f32) {
+        self.font_size = size.clamp(MIN_FONT_SIZE, MAX_FONT_SIZE);
+        self.invalidate_row_cache();
+        // Координаты выделения и подсказок после ресайза неактуальны
+        self.selection = None;
+        self.hint_mode = None;
+    }
+
+    // --- Поиск по буферу ---
+
+    fn open_search(&mut self) {
+        self.hint_mode = None;
+        self.exit_copy_mode();
+        if self.search.is_none() {
+            self.search = Some(SearchState {
+                query: String::new(),
+                matches: Vec::new(),
+                current: None,
+            });
+        }
+    }
+
+    /// Пересчитать совпадения (без учёта регистра) и встать на самое новое.
+    fn update_search(&mut self) {
+        let search = match &mut self.search {
+            Some(s) => s,
+            None => return,
+        };
+        let needle: Vec<char> = search.query.chars().map(fold_case).collect();
+        search.matches.clear();
+        if !needle.is_empty() {
+            let base = self.emulator.first_line();
+            for (idx, row) in self.emulator.all_rows().enumerate() {
+                let hay: Vec<char> = row.iter().map(|cell| fold_case(cell.c)).collect();
+                let mut col = 0;
+                while col + needle.len() <= hay.len() {
+                    if hay[col..col + needle.len()] == needle[..] {
+                        search.matches.push((base + idx as i64, col, col + needle.len()));
+                        col += needle.len();
+                    } else {
+                        col += 1;
+                    }
+                }
+            }
+        }
+        search.current = search.matches.len().checked_sub(1);
+        self.scroll_to_search_match();
+    }
+
+    /// К следующему совпадению: `older` — вверх по буферу.
+    fn step_search(&mut self, older: bool) {
+        if let Some(search) = &mut self.search {
+            let len = search.matches.len();
+            if len == 0 {
+                return;
+            }
+            search.current = Some(match (search.current, older) {
+                (None, _) => len - 1,
+                (Some(c), true) => (c + len - 1) % len,
+                (Some(c), false) => (c + 1) % len,
+            });
+        }
+        self.scroll_to_search_match();
+    }
+
+    fn scroll_to_search_match(&mut self) {
+        let line = match self
+            .search
+            .as_ref()
+            .and_then(|s| s.current.and_then(|c| s.matches.get(c)))
+        {
+            Some(m) => m.0,
+            None => return,
+        };
+        let top = self.emulator.view_top_line();
+        if line < top || line >= top + self.last_rows as i64 {
+            self.emulator.scroll_to_line(line - self.last_rows as i64 / 2);
+        }
+    }
+
+    fn paint_search(
+        &self,
+        painter: &egui::Painter,
+        origin: egui::Pos2,
+        cell_size: egui::Vec2,
+        area: egui::Rect,
+    ) {
+        let search = match &self.search {
+            Some(s) => s,
+            None => return,
+        };
+
+        // Рамка вокруг текущего совпадения
+        if let Some(&(line, start, end)) = search.current.and_then(|c| search.matches.get(c)) {
+            let row = line - self.emulator.view_top_line();
+            if (0..self.last_rows as i64).contains(&row) {
+                let rect = egui::Rect::from_min_max(
+                    egui::pos2(
+                        origin.x + start as f32 * cell_size.x,
+                        origin.y + row as f32 * cell_size.y,
+                    ),
+                    egui::pos2(
+                        origin.x + end as f32 * cell_size.x,
+                        origin.y + (row + 1) as f32 * cell_size.y,
+                    ),
+                );
+                painter.rect_stroke(rect, 0.0, egui::Stroke::new(1.0, crate::theme::AMBER));
+            }
+        }
+
+        let status = if search.query.is_empty() {
+            String::new()
+        } else if search.matches.is_empty() {
+            "no matches".to_string()
+        } else {
+            format!(
+                "{}/{}",
+                search.current.map(|c| c + 1).unwrap_or(0),
+                search.matches.len()
+            )
+        };
+        let text = format!("search: {}_  {}", search.query, status);
+        let bar = egui::Rect::from_min_size(
+            egui::pos2(area.right() - 320.0, area.top() + 4.0),
+            egui::vec2(300.0, 22.0),
+        );
+        painter.rect_filled(bar, 0.0, egui::Color32::from_rgba_premultiplied(0, 20, 0, 230));
+        painter.rect_stroke(bar, 0.0, egui::Stroke::new(1.0, crate::theme::GREEN_DARK));
+        painter.text(
+            egui::pos2(bar.left() + 6.0, bar.center().y),
+            egui::Align2::LEFT_CENTER,
+            text,
+            egui::FontId::monospace(11.0),
+            crate::theme::AMBER,
+        );
+    }
+
+    /// Ввод в строку поиска: Enter / Up — старее, Shift+Enter / Down — новее, Esc — закрыть.
+    fn handle_search_input(&mut self, ui: &egui::Ui) {
+        let events = ui.input(|i| i.events.clone());
+        let mut changed = false;
+
+        for event in &events {
+            let search = match &mut self.search {
+                Some(s) => s,
+                None => return,
+            };
+            match event {
+                egui::Event::Text(text) => {
+                    search.query.push_str(text);
+                    changed = true;
+                }
+                egui::Event::Paste(text) => {
+                    search.query.push_str(text.lines().next().unwrap_or(""));
+                    changed = true;
+                }
+                egui::Event::Key {
+                    key,
+                    pressed: true,
+                    modifiers,
+                    ..
+                } => match key {
+                    egui::Key::Escape => {
+                        self.search = None;
+                        return;
+                    }
+                    egui::Key::Backspace => {
+                        search.query.pop();
+                        changed = true;
+                    }
+                    egui::Key::Enter | egui::Key::ArrowUp | egui::Key::ArrowDown => {
+                        if changed {
+                            self.update_search();
+                            changed = false;
+                        }
+                        let older = match key {
+                            egui::Key::Enter => !modifiers.shift,
+                            k => *k == egui::Key::ArrowUp,
+                        };
+                        self.step_search(older);
+                    }
+                    _ => {}
+                },
+                _ => {}
+            }
+        }
+
+        if changed {
+            self.update_search();
+        }
+    }
+
+    // --- Shell integration (OSC 133) ---
+
+    /// Метки у левого края строк приглашений: зелёная — успех, красная — ошибка,
+    /// серая — команда ещё идёт или код неизвестен.
+    fn paint_command_marks(
+        &self,
+        painter: &egui::Painter,
+        origin: egui::Pos2,
+        cell_size: egui::Vec2,
+        rows: usize,
+    ) {
+        if self.emulator.is_alt_screen() {
+            return;
+        }
+        let top = self.emulator.view_top_line();
+        for mark in self.emulator.command_marks() {
+            let row = mark.prompt - top;
+            if row < 0 || row >= rows as i64 {
+                continue;
+            }
+            let color = match (mark.output, mark.exit_code) {
+                (None, _) => continue,
+                (Some(_), Some(0)) => crate::theme::GREEN,
+                (Some(_), Some(_)) => crate::theme::RED,
+                (Some(_), None) => crate::theme::GREY,
+            };
+            let rect = egui::Rect::from_min_size(
+                egui::pos2(origin.x, origin.y + row as f32 * cell_size.y),
+                egui::vec2(MARK_WIDTH, cell_size.y),
+            );
+            painter.rect_filled(rect, 0.0, color);
+        }
+    }
+
+    fn command_mark_tooltip(&self, row: usize) -> Option<String> {
+        if self.emulator.is_alt_screen() {
+            return None;
+        }
+        let line = self.emulator.view_top_line() + row as i64;
+        let mark = self.emulator.command_marks().iter().find(|m| m.prompt == line)?;
+        mark.output?;
+        let status = match (mark.exit_code, mark.duration) {
+            (Some(code), Some(duration)) => format!(
+                "exit {} -- {}",
+                code,
+                crate::notify::format_duration(duration)
+            ),
+            (None, Some(duration)) => crate::notify::format_duration(duration),
+            _ => match mark.started {
+                Some(started) => format!(
+                    "running for {}",
+                    crate::notify::format_duration(started.elapsed())
+                ),
+                None => "running".to_string(),
+            },
+        };
+        Some(format!("{}
+click: select + copy output", status))
+    }
+
+    fn jump_to_prompt(&mut self, previous: bool) {
+        if self.emulator.is_alt_screen() {
+            return;
+        }
+        let top = self.emulator.view_top_line();
+        let marks = self.emulator.command_marks();
+        let target = if previous {
+            marks.iter().rev().find(|m| m.prompt < top)
+        } else {
+            marks.iter().find(|m| m.prompt > top)
+        }
+        .map(|m| m.prompt);
+        match target {
+            Some(line) => self.emulator.scroll_to_line(line),
+            None if !previous => self.emulator.reset_scroll(),
+            None => {}
+        }
+    }
+
+    /// Выделить вывод команды, чья метка на строке `row` экрана, и скопировать его.
+    fn select_command_output(&mut self, ctx: &egui::Context, row: usize, cols: usize) {
+        if self.emulator.is_alt_screen() {
+            return;
+        }
+        let line = self.emulator.view_top_line() + row as i64;
+        let (start, end) = match self.emulator.command_marks().iter().find(|m| m.prompt == line) {
+            Some(mark) => match mark.output {
+                Some(start) => (start, mark.end.unwrap_or(self.emulator.cursor_line() + 1)),
+                None => return,
+            },
+            None => return,
+        };
+        if end <= start {
+            return;
+        }
+
+        // Начало вывода выше экрана — прокручиваем к нему
+        if start < self.emulator.view_top_line() {
+            self.emulator.scroll_to_line(start);
+        }
+        let top = self.emulator.view_top_line();
+        let first = (start - top).max(0) as usize;
+        let last = ((end - 1 - top).max(0) as usize).min(self.last_rows.saturating_sub(1));
+        self.selection = Some(Selection {
+            start_row: first,
+            start_col: 0,
+            end_row: last,
+            end_col: cols.saturating_sub(1),
+        });
+
+        // Копируем вывод целиком, даже если он длиннее экрана
+        let text = self.emulator.text_between(start, end);
+        ctx.copy_text(text.trim_end().to_string());
+    }
+
+    // --- Вставка ---
+
+    /// Вставка из буфера обмена. Многострочный или длинный текст
+    /// сначала показываем в диалоге — защита от случайного запуска скрипта.
+    fn paste(&mut self, text: &str, ssh: &SshConnection) {
+        if text.is_empty() {
+            return;
+        }
+        let long = self.paste_confirm_chars > 0 && text.chars().count() > self.paste_confirm_chars;
+        if text.contains(['\n', '\r']) || long {
+            self.pending_paste = Some(PendingPaste {
+                text: text.to_string(),
+                editing: false,
+            });
+            return;
+        }
+        self.start_paste(ssh, text.as_bytes().to_vec(), false);
+    }
+
+    /// Короткая вставка уходит сразу, длинная (или медленная) — через очередь.
+    fn start_paste(&mut self, ssh: &SshConnection, data: Vec<u8>, slow: bool) {
+        self.emulator.reset_scroll();
+        if !slow && data.len() <= PASTE_CHUNK && self.paste_queue.is_none() {
+            self.send_input(ssh, &data);
+            return;
+        }
+        match &mut self.paste_queue {
+            // Новая вставка во время текущей — в хвост очереди
+            Some(queue) => queue.data.extend_from_slice(&data),
+            None => {
+                self.paste_queue = Some(PasteQueue {
+                    data,
+                    sent: 0,
+                    slow,
+                    next_at: Instant::now(),
+                })
+            }
+        }
+    }
+
+    /// Очередной кусок вставки, если подошло время.
+    fn pump_paste(&mut self, ctx: &egui::Context, ssh: &SshConnection) {
+        let now = Instant::now();
+        let (chunk, delay) = match &self.paste_queue {
+            Some(q) if now < q.next_at => {
+                ctx.request_repaint_after(q.next_at - now);
+                return;
             }
-            lines.push(line_text.trim_end().to_string());
+            Some(q) if q.slow => (SLOW_PASTE_CHUNK, SLOW_PASTE_CHUNK_DELAY),
+            Some(_) => (PASTE_CHUNK, PASTE_CHUNK_DELAY),
+            None => return,
+        };
+        let Some(queue) = self.paste_queue.take() else {
+            return;
+        };
+        let end = (queue.sent + chunk).min(queue.data.len());
+        self.send_input(ssh, &queue.data[queue.sent..end]);
+        if end < queue.data.len() {
+            self.paste_queue = Some(PasteQueue {
+                sent: end,
+                next_at: now + delay,
+                ..queue
+            });
+            ctx.request_repaint_after(delay);
         }
+    }
+
+    fn paint_paste_progress(&self, painter: &egui::Painter, area: egui::Rect) {
+        let queue = match &self.paste_queue {
+            Some(q) => q,
+            None => return,
+        };
+        let text = format!(
+            "pasting {}/{} KiB{}  Esc cancel",
+            queue.sent / 1024,
+            queue.data.len().div_ceil(1024),
+            if queue.slow { " (slow)" } else { "" }
+        );
+        let bar = egui::Rect::from_min_size(
+            egui::pos2(area.right() - 320.0, area.bottom() - 26.0),
+            egui::vec2(300.0, 22.0),
+        );
+        let progress = queue.sent as f32 / queue.data.len().max(1) as f32;
+        painter.rect_filled(bar, 0.0, egui::Color32::from_rgba_premultiplied(0, 20, 0, 230));
+        painter.rect_filled(
+            egui::Rect::from_min_size(bar.min, egui::vec2(bar.width() * progress, bar.height())),
+            0.0,
+            crate::theme::BG_SELECTION,
+        );
+        painter.rect_stroke(bar, 0.0, egui::Stroke::new(1.0, crate::theme::GREEN_DARK));
+        painter.text(
+            egui::pos2(bar.left() + 6.0, bar.center().y),
+            egui::Align2::LEFT_CENTER,
+            text,
+            egui::FontId::monospace(11.0),
+            crate::theme::AMBER,
+        );
+    }
 
-        lines.join("\n")
+    fn render_paste_dialog(&mut self, ctx: &egui::Context, ssh: &SshConnection) {
+        let pending = match &mut self.pending_paste {
+            Some(p) => p,
+            None => return,
+        };
+
+        let line_count = pending.text.lines().count().max(1);
+        let char_count = pending.text.chars().count();
+
+        let mut send = false;
+        let mut cancel = false;
+        let mut open = true;
+
+        egui::Window::new("confirm paste")
+            .open(&mut open)
+            .collapsible(false)
+            .resizable(true)
+            .default_width(560.0)
+            .default_pos(egui::pos2(
+                ctx.screen_rect().center().x - 280.0,
+                ctx.screen_rect().center().y - 180.0,
+            ))
+            .show(ctx, |ui| {
+                ui.colored_label(
+                    crate::theme::AMBER,
+                    format!(
+                        "// pasting {} line(s), {} chars -- review before sending",
+                        line_count, char_count
+                    ),
+                );
+                ui.add_space(4.0);
+
+                egui::ScrollArea::vertical()
+                    .max_height(280.0)
+                    .show(ui, |ui| {
+                        ui.add(
+                            egui::TextEdit::multiline(&mut pending.text)
+                                .font(egui::TextStyle::Monospace)
+                                .desired_width(f32::INFINITY)
+                                .desired_rows(8)
+                                .interactive(pending.editing),
+                        );
+                    });
+
+                ui.add_space(4.0);
+                ui.separator();
+                ui.add_space(4.0);
+
+                ui.horizontal(|ui| {
+                    if ui.button("[send]").clicked() {
+                        send = true;
+                    }
+                    let edit_label = if pending.editing { "[done]" } else { "[edit]" };
+                    if ui.button(edit_label).clicked() {
+                        pending.editing = !pending.editing;
+                    }
+                    if ui.button("[cancel]").clicked() {
+                        cancel = true;
+                    }
+                    ui.checkbox(&mut self.slow_paste, "paste slowly")
+                        .on_hover_text("small chunks with pauses, for serial-like consoles");
+                });
+            });
+
+        if send {
+            if let Some(pending) = self.pending_paste.take() {
+                self.start_paste(ssh, pending.text.into_bytes(), self.slow_paste);
+            }
+        } else if cancel || !open {
+            self.pending_paste = None;
+        }
     }
 
     // --- Клавиатурный ввод ---
@@ -482,6 +2203,17 @@ impl TerminalWidget {
         let mut handled_copy = false;
         let mut handled_paste = false;
 
+        // Alt как Meta: ESC перед символом. Ctrl+Alt не трогаем — на Windows это AltGr.
+        let meta = keybindings::alt_meta() && ui.input(|i| i.modifiers.alt && !i.modifiers.ctrl);
+        // Клавиша уже закодирована расширенным протоколом — её Event::Text не отправляем
+        let mut skip_text = false;
+
+        // Esc во время кусковой вставки — отменить остаток
+        if self.paste_queue.is_some() && ui.input(|i| i.key_pressed(egui::Key::Escape)) {
+            self.paste_queue = None;
+            return;
+        }
+
         for event in &events {
             match event {
                 // --- Семантические события egui (Ctrl+X/C/V) ---
@@ -489,7 +2221,7 @@ impl TerminalWidget {
                 egui::Event::Cut => {
                     // Ctrl+X → отправляем байт 24 (используется в nano, etc.)
                     self.emulator.reset_scroll();
-                    ssh.send(&[24]);
+                    self.send_input(ssh, &[24]);
                     handled_cut = true;
                     self.selection = None;
                 }
@@ -500,21 +2232,29 @@ impl TerminalWidget {
                         ui.ctx().copy_text(text);
                         self.selection = None;
                     } else {
-                        ssh.send(&[3]);
+                        self.send_input(ssh, &[3]);
                     }
                     handled_copy = true;
                 }
                 egui::Event::Paste(text) => {
-                    self.emulator.reset_scroll();
-                    ssh.send(text.as_bytes());
+                    self.paste(text, ssh);
                     self.selection = None;
                     handled_paste = true;
                 }
 
                 // --- Обычный текстовый ввод ---
                 egui::Event::Text(text) => {
+                    if std::mem::take(&mut skip_text) {
+                        continue;
+                    }
                     self.emulator.reset_scroll();
-                    ssh.send(text.as_bytes());
+                    if meta {
+                        let mut bytes = vec![0x1b];
+                        bytes.extend_from_slice(text.as_bytes());
+                        self.send_input(ssh, &bytes);
+                    } else {
+                        self.send_input(ssh, text.as_bytes());
+                    }
                     self.selection = None;
                 }
 
@@ -525,37 +2265,60 @@ impl TerminalWidget {
                     modifiers,
                     ..
                 } => {
-                    // Shift+PageUp/Down — прокрутка терминала
-                    if modifiers.shift && *key == egui::Key::PageUp {
-                        self.emulator.scroll_up_view(self.last_rows / 2);
-                        continue;
-                    }
-                    if modifiers.shift && *key == egui::Key::PageDown {
-                        self.emulator.scroll_down_view(self.last_rows / 2);
+                    // Свои последовательности сессии — раньше всего остального
+                    if let Some((_, bytes)) = self
+                        .key_sends
+                        .iter()
+                        .find(|(chord, _)| chord.matches(*key, *modifiers))
+                    {
+                        let bytes = bytes.clone();
+                        self.emulator.reset_scroll();
+                        self.send_input(ssh, &bytes);
+                        self.selection = None;
                         continue;
                     }
 
-                    // Ctrl+Shift+C — копирование выделения
-                    if modifiers.ctrl && modifiers.shift && *key == egui::Key::C {
-                        if !handled_copy {
-                            let text = self.get_selected_text();
-                            if !text.is_empty() {
-                                ui.ctx().copy_text(text);
-                                self.selection = None;
+                    // Действия из карты сочетаний (keybindings.json)
+                    if let Some(action) = keybindings::action_for(*key, *modifiers) {
+                        match action {
+                            Action::Copy => {
+                                if !handled_copy {
+                                    let text = self.get_selected_text();
+                                    if !text.is_empty() {
+                                        ui.ctx().copy_text(text);
+                                        self.selection = None;
+                                    }
+                                }
                             }
-                        }
-                        continue;
-                    }
-                    // Ctrl+Shift+V — вставка через arboard
-                    if modifiers.ctrl && modifiers.shift && *key == egui::Key::V {
-                        if !handled_paste {
-                            if let Ok(mut clipboard) = arboard::Clipboard::new() {
-                                if let Ok(text) = clipboard.get_text() {
-                                    ssh.send(text.as_bytes());
+                            Action::Paste => {
+                                if !handled_paste {
+                                    if let Ok(mut clipboard) = arboard::Clipboard::new() {
+                                        if let Ok(text) = clipboard.get_text() {
+                                            self.paste(&text, ssh);
+                                        }
+                                    }
                                 }
+                                self.selection = None;
+                            }
+                            Action::ZoomIn => self.set_font_size(self.font_size + 1.0),
+                            Action::ZoomOut => self.set_font_size(self.font_size - 1.0),
+                            Action::ZoomReset => self.set_font_size(DEFAULT_FONT_SIZE),
+                            Action::ScrollPageUp => {
+                                self.emulator.scroll_up_view(self.last_rows / 2)
+                            }
+                            Action::ScrollPageDown => {
+                                self.emulator.scroll_down_view(self.last_rows / 2)
+                            }
+                            Action::ScrollTop => {
+                                self.emulator.scroll_up_view(self.emulator.scrollback_len())
                             }
+                            Action::ScrollBottom => self.emulator.reset_scroll(),
+                            Action::PrevPrompt => self.jump_to_prompt(true),
+                            Action::NextPrompt => self.jump_to_prompt(false),
+                            Action::Search => self.open_search(),
+                            Action::CopyMode => self.enter_copy_mode(),
+                            Action::Hints => self.enter_hint_mode(),
                         }
-                        self.selection = None;
                         continue;
                     }
 
@@ -571,7 +2334,7 @@ impl TerminalWidget {
                                         ui.ctx().copy_text(text);
                                         self.selection = None;
                                     } else {
-                                        ssh.send(&[3]);
+                                        self.send_input(ssh, &[3]);
                                     }
                                     handled_copy = true;
                                 }
@@ -579,7 +2342,7 @@ impl TerminalWidget {
                             }
                             egui::Key::X => {
                                 if !handled_cut {
-                                    ssh.send(&[24]);
+                                    self.send_input(ssh, &[24]);
                                     handled_cut = true;
                                 }
                                 continue;
@@ -592,9 +2355,27 @@ impl TerminalWidget {
                         }
                     }
 
-                    if let Some(bytes) = self.key_to_bytes(*key, *modifiers) {
+                    if let Some(bytes) = keyboard::encode_extended(
+                        *key,
+                        *modifiers,
+                        self.emulator.kitty_keyboard_flags(),
+                        self.emulator.modify_other_keys(),
+                    ) {
+                        // С Ctrl egui текст не присылает
+                        skip_text = keyboard::is_text_key(*key) && !modifiers.ctrl;
                         self.emulator.reset_scroll();
-                        ssh.send(&bytes);
+                        self.send_input(ssh, &bytes);
+                        self.selection = None;
+                        continue;
+                    }
+
+                    if let Some(mut bytes) = self.key_to_bytes(*key, *modifiers) {
+                        // Alt+Backspace, Alt+Enter, Ctrl+Alt+буква — тоже с ESC-префиксом
+                        if modifiers.alt && keybindings::alt_meta() && bytes.len() == 1 {
+                            bytes.insert(0, 0x1b);
+                        }
+                        self.emulator.reset_scroll();
+                        self.send_input(ssh, &bytes);
                         self.selection = None;
                     }
                 }
@@ -606,6 +2387,11 @@ impl TerminalWidget {
     fn key_to_bytes(&self, key: egui::Key, modifiers: egui::Modifiers) -> Option<Vec<u8>> {
         let app_mode = self.emulator.app_cursor_keys();
 
+        // Стрелки/функциональные клавиши с модификаторами — CSI 1;m X / CSI n;m ~
+        if let Some(bytes) = keyboard::modified_special(key, modifiers) {
+            return Some(bytes);
+        }
+
         // Ctrl+Key (без Shift) — отправляем control byte
         if modifiers.ctrl && !modifiers.shift {
             let ctrl_byte: Option<u8> = match key {
@@ -644,6 +2430,7 @@ impl TerminalWidget {
 
         match key {
             egui::Key::Enter => Some(b"\r".to_vec()),
+            egui::Key::Tab if modifiers.shift => Some(b"\x1b[Z".to_vec()),
             egui::Key::Tab => Some(b"\t".to_vec()),
             egui::Key::Backspace => Some(vec![127]),
             egui::Key::Escape => Some(vec![27]),
@@ -700,36 +2487,104 @@ impl TerminalWidget {
 
 // --- Вспомогательные функции (standalone, без &self, чтобы не конфликтовать с borrow) ---
 
-fn resolve_colors(cell: &Cell, bg_default: egui::Color32) -> (egui::Color32, egui::Color32) {
-    let mut fg = term_color_to_egui(cell.attr.fg, true, cell.attr.bold);
-    let mut bg = term_color_to_egui(cell.attr.bg, false, false);
+/// Строка терминала → LayoutJob. Общий код для живого терминала и replay.
+/// `highlights` — участки (col_start, col_end exclusive, цвет) от правил подсветки.
+pub(crate) fn row_layout_job(
+    row: &[Cell],
+    max_cols: usize,
+    font_size: f32,
+    scheme: &ColorScheme,
+    highlights: &[(usize, usize, egui::Color32)],
+    is_selected: impl Fn(usize) -> bool,
+) -> egui::text::LayoutJob {
+    let mut job = egui::text::LayoutJob::default();
+    let bg_color = scheme.background_color();
+
+    for (col_idx, cell) in row.iter().enumerate() {
+        if col_idx >= max_cols {
+            break;
+        }
 
-    if cell.attr.inverse {
-        std::mem::swap(&mut fg, &mut bg);
-    }
+        let (mut fg, cell_bg) = scheme.resolve(cell);
+        if let Some((_, _, color)) = highlights
+            .iter()
+            .find(|(start, end, _)| col_idx >= *start && col_idx < *end)
+        {
+            fg = *color;
+        }
+        let text = cell_display_text(cell);
 
-    if cell.attr.bg == TermColor::Default && !cell.attr.inverse {
-        bg = bg_default;
-    }
+        let is_selected = is_selected(col_idx);
 
-    (fg, bg)
-}
+        let mut format = egui::TextFormat {
+            font_id: egui::FontId::monospace(font_size),
+            color: if is_selected { bg_color } else { fg },
+            ..Default::default()
+        };
 
-fn term_color_to_egui(color: TermColor, is_fg: bool, is_bold: bool) -> egui::Color32 {
-    match color {
-        TermColor::Default => {
-            if is_fg {
-                egui::Color32::from_rgb(0x00, 0xff, 0x41) // phosphor green
-            } else {
-                egui::Color32::from_rgb(0x06, 0x06, 0x06) // near-black
-            }
+        if !is_selected && cell_bg != bg_color {
+            format.background = cell_bg;
         }
-        TermColor::Indexed(idx) => {
-            let effective_idx = if is_bold && idx < 8 { idx + 8 } else { idx };
-            indexed_color(effective_idx)
+
+        if cell.attr.underline {
+            format.underline = egui::Stroke::new(1.0, fg);
+        }
+        if cell.attr.italic {
+            format.italics = true;
         }
-        TermColor::Rgb(r, g, b) => egui::Color32::from_rgb(r, g, b),
+
+        job.append(&text, 0.0, format);
     }
+
+    job
+}
+
+/// Текст ячейки для рендера: управляющие символы — пробел, combining-знаки
+/// идут вместе с базовым символом (шрифт накладывает их с нулевым advance).
+fn cell_display_text(cell: &Cell) -> String {
+    if cell.c < ' ' {
+        return " ".to_string();
+    }
+    let mut text = String::new();
+    cell.push_text(&mut text);
+    text
+}
+
+/// Символ для поиска без учёта регистра (колонки не сдвигаются).
+fn fold_case(c: char) -> char {
+    if c == '\0' {
+        ' '
+    } else {
+        c.to_lowercase().next().unwrap_or(c)
+    }
+}
+
+fn find_bytes(haystack: &[u8], needle: &[u8]) -> Option<usize> {
+    haystack.windows(needle.len()).position(|w| w == needle)
+}
+
+/// Длина самого длинного конца `data`, с которого начинается `needle` (но не весь он).
+fn partial_suffix(data: &[u8], needle: &[u8]) -> usize {
+    (1..needle.len())
+        .rev()
+        .find(|&len| data.ends_with(&needle[..len]))
+        .unwrap_or(0)
+}
+
+/// Размер ячейки моноширинного шрифта.
+/// Усредняем по 10 символам для стабильного результата.
+pub(crate) fn cell_size_for(ui: &egui::Ui, font_size: f32) -> egui::Vec2 {
+    let font_id = egui::FontId::monospace(font_size);
+    let g = ui.fonts(|f| {
+        f.layout_no_wrap(
+            "MMMMMMMMMM".to_string(),
+            font_id.clone(),
+            egui::Color32::WHITE,
+        )
+    });
+    let char_width = g.rect.width() / 10.0;
+    let line_height = g.rect.height();
+    egui::vec2(char_width.max(1.0), line_height.max(1.0))
 }
 
 fn pos_to_cell(
@@ -746,39 +2601,3 @@ fn pos_to_cell(
         col.min(max_cols.saturating_sub(1)),
     )
 }
-
-/// CRT hacker palette (16 base + 256 extended)
-fn indexed_color(idx: u8) -> egui::Color32 {
-    match idx {
-        0  => egui::Color32::from_rgb(0x08, 0x08, 0x08), // black
-        1  => egui::Color32::from_rgb(0xcc, 0x33, 0x33), // red
-        2  => egui::Color32::from_rgb(0x00, 0xcc, 0x33), // green
-        3  => egui::Color32::from_rgb(0xcc, 0xaa, 0x00), // yellow/amber
-        4  => egui::Color32::from_rgb(0x33, 0x88, 0xcc), // blue
-        5  => egui::Color32::from_rgb(0x88, 0x44, 0xcc), // magenta
-        6  => egui::Color32::from_rgb(0x00, 0xaa, 0x88), // cyan
-        7  => egui::Color32::from_rgb(0xaa, 0xbb, 0xaa), // white (dim)
-        8  => egui::Color32::from_rgb(0x44, 0x55, 0x44), // bright black (grey)
-        9  => egui::Color32::from_rgb(0xff, 0x44, 0x44), // bright red
-        10 => egui::Color32::from_rgb(0x00, 0xff, 0x41), // bright green (phosphor)
-        11 => egui::Color32::from_rgb(0xff, 0xcc, 0x00), // bright yellow
-        12 => egui::Color32::from_rgb(0x44, 0xaa, 0xff), // bright blue
-        13 => egui::Color32::from_rgb(0xbb, 0x66, 0xff), // bright magenta
-        14 => egui::Color32::from_rgb(0x00, 0xdd, 0xbb), // bright cyan
-        15 => egui::Color32::from_rgb(0xcc, 0xee, 0xcc), // bright white
-        16..=231 => {
-            let n = idx - 16;
-            let r_comp = n / 36;
-            let g_comp = (n % 36) / 6;
-            let b_comp = n % 6;
-            let r = if r_comp > 0 { 55 + r_comp * 40 } else { 0 };
-            let g = if g_comp > 0 { 55 + g_comp * 40 } else { 0 };
-            let b = if b_comp > 0 { 55 + b_comp * 40 } else { 0 };
-            egui::Color32::from_rgb(r, g, b)
-        }
-        232..=255 => {
-            let v = 8 + (idx - 232) * 10;
-            egui::Color32::from_rgb(v, v, v)
-        }
-    }
-}
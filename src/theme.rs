@@ -18,6 +18,7 @@ pub const GREEN_BRIGHT: egui::Color32 = egui::Color32::from_rgb(0x39, 0xff, 0x14
 pub const AMBER:        egui::Color32 = egui::Color32::from_rgb(0xff, 0xb0, 0x00);
 pub const RED:          egui::Color32 = egui::Color32::from_rgb(0xff, 0x33, 0x33);
 pub const CYAN:         egui::Color32 = egui::Color32::from_rgb(0x00, 0xdd, 0xcc);
+pub const MAGENTA:      egui::Color32 = egui::Color32::from_rgb(0xdd, 0x66, 0xff);
 pub const GREY:         egui::Color32 = egui::Color32::from_rgb(0x44, 0x55, 0x44);
 
 pub fn apply(ctx: &egui::Context) {